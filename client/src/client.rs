@@ -0,0 +1,381 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::entity::{Entity, Values, WireCollection};
+use crate::types::{Comment, HealthReport, SearchResults, Stock};
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use invman_core::{ColumnType, SchemaCollection, SchemaDeclaration};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+const CONNECT_TIMEOUT_SECONDS: u64 = 10;
+
+/**
+ * Client of an invman started with serve, whose methods mirror the commands of the command
+ * line. It can be shared between threads, every method being a request of its own.
+ */
+pub struct InvManClient {
+    url: String,
+    agent: ureq::Agent,
+    /**
+     * Value of the Authorization header
+     */
+    authorization: Option<String>,
+    /**
+     * One-time password of a user with two-factor authentication
+     */
+    otp: Option<String>,
+    /**
+     * Schema of the inventory, fetched once it is needed to check values
+     */
+    schema: Mutex<Option<Vec<SchemaDeclaration>>>,
+}
+
+/**
+ * Part of the server's config the client needs
+ */
+#[derive(Deserialize)]
+struct RemoteConfig {
+    inventory_schema_declaration: SchemaCollection,
+}
+
+/**
+ * Options of inventory list, which lists all entities unless set.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery {
+    limit: Option<i32>,
+    after_id: Option<i64>,
+    include_archived: bool,
+    supplier: Option<u32>,
+    assigned_to: Option<u32>,
+    category: Option<u32>,
+    recursive: bool,
+    raw: Option<String>,
+    params: Vec<String>,
+    changed_since_tx: Option<u32>,
+}
+
+impl ListQuery {
+    pub fn new() -> ListQuery {
+        return ListQuery::default();
+    }
+
+    pub fn limit(mut self, limit: u32) -> ListQuery {
+        self.limit = Some(i32::try_from(limit).unwrap_or(i32::MAX));
+        return self;
+    }
+
+    /**
+     * Lists the entities after the given id, to page through the inventory
+     */
+    pub fn after_id(mut self, id: u32) -> ListQuery {
+        self.after_id = Some(id.into());
+        return self;
+    }
+
+    pub fn include_archived(mut self) -> ListQuery {
+        self.include_archived = true;
+        return self;
+    }
+
+    pub fn supplier(mut self, supplier_id: u32) -> ListQuery {
+        self.supplier = Some(supplier_id);
+        return self;
+    }
+
+    pub fn assigned_to(mut self, user_id: u32) -> ListQuery {
+        self.assigned_to = Some(user_id);
+        return self;
+    }
+
+    /**
+     * Lists the entities assigned to the category, see [`InvManClient::category_id`]
+     */
+    pub fn category(mut self, category_id: u32) -> ListQuery {
+        self.category = Some(category_id);
+        return self;
+    }
+
+    /**
+     * Also lists the entities of all descendants of the category
+     */
+    pub fn recursive(mut self) -> ListQuery {
+        self.recursive = true;
+        return self;
+    }
+
+    /**
+     * Clause appended to the query like --raw, e.g. WHERE sku=?1, whose ?1, ?2, ... are bound
+     * to the params
+     */
+    pub fn raw(mut self, clause: &str, params: &[&str]) -> ListQuery {
+        self.raw = Some(clause.into());
+        self.params = params.iter().map(|e| e.to_string()).collect();
+        return self;
+    }
+
+    /**
+     * Only lists entities changed after the transaction of the given id
+     */
+    pub fn changed_since_tx(mut self, tx: u32) -> ListQuery {
+        self.changed_since_tx = Some(tx);
+        return self;
+    }
+
+    fn to_wire(&self) -> Value {
+        return json!({
+            "limit": self.limit.unwrap_or(-1),
+            "after_id": self.after_id,
+            "fuzzy": null,
+            "fuzzy_threshold": 0.0,
+            "with_audit": false,
+            "with_attachments": false,
+            "include_archived": self.include_archived,
+            "supplier": self.supplier,
+            "assigned_to": self.assigned_to,
+            "category": self.category,
+            "recursive": self.recursive,
+            "raw": self.raw,
+            "params": self.params,
+            "changed_since": self.changed_since_tx.map(|tx| json!({ "Tx": tx })),
+        });
+    }
+}
+
+impl InvManClient {
+    /**
+     * Client of the server at the given url, e.g. http://127.0.0.1:8080, that is not logged in
+     */
+    pub fn new(url: &str) -> InvManClient {
+        return InvManClient {
+            url: url.trim_end_matches('/').into(),
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(CONNECT_TIMEOUT_SECONDS))
+                .build(),
+            authorization: None,
+            otp: None,
+            schema: Mutex::new(None),
+        };
+    }
+
+    pub fn with_credentials(mut self, username: &str, password: &str) -> InvManClient {
+        self.authorization = Some(format!(
+            "Basic {}",
+            STANDARD.encode(format!("{}:{}", username, password))
+        ));
+        return self;
+    }
+
+    /**
+     * Logs in with an API token, see user token issue
+     */
+    pub fn with_token(mut self, token: &str) -> InvManClient {
+        self.authorization = Some(format!("Bearer {}", token));
+        return self;
+    }
+
+    /**
+     * Passes the one-time password of a user with two-factor authentication along with the
+     * credentials. As it expires, long-running services rather log in with a token.
+     */
+    pub fn with_otp(mut self, otp: &str) -> InvManClient {
+        self.otp = Some(otp.into());
+        return self;
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let mut request = self.agent.post(&format!("{}/rpc/{}", self.url, method));
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        if let Some(otp) = &self.otp {
+            request = request.set("X-Invman-Otp", otp);
+        }
+        let mut response: Value = match request.send_json(params) {
+            Ok(response) => response.into_json()?,
+            Err(ureq::Error::Status(status, response)) => {
+                let body: Value = response.into_json().unwrap_or_default();
+                match body["error"].as_str() {
+                    Some(error) => bail!("{}", error),
+                    None => bail!("Server responded with status {}", status),
+                }
+            }
+            Err(e) => bail!("Server at {} is not reachable ({})", self.url, e),
+        };
+        return Ok(serde_json::from_value(response["result"].take())?);
+    }
+
+    /**
+     * Schema declaration of the inventory, fetched from the server each time.
+     */
+    pub fn schema(&self) -> Result<Vec<SchemaDeclaration>> {
+        let config: RemoteConfig = self.call("get_config", json!([]))?;
+        let declarations = config.inventory_schema_declaration.collection;
+        *self.schema.lock().unwrap() = Some(declarations.clone());
+        return Ok(declarations);
+    }
+
+    /**
+     * Checks the values against the schema fetched last, fetching it first if needed. Values
+     * for a column added since are checked against the schema fetched anew.
+     */
+    fn to_wire(&self, values: &Values) -> Result<WireCollection> {
+        let cached = self.schema.lock().unwrap().clone();
+        if let Some(declarations) = cached {
+            if let Ok(wire) = values.to_wire(&declarations) {
+                return Ok(wire);
+            }
+        }
+        return values.to_wire(&self.schema()?);
+    }
+
+    pub fn health(&self) -> Result<HealthReport> {
+        return self.call("health", json!([]));
+    }
+
+    pub fn register(&self, username: &str, password: &str) -> Result<String> {
+        return self.call("user_register", json!([username, password]));
+    }
+
+    pub fn inventory_add(&self, values: &Values) -> Result<String> {
+        return self.call("inventory_add", json!([self.to_wire(values)?]));
+    }
+
+    /**
+     * Adds the entity together with metadata, such as the columns unknown to the schema
+     */
+    pub fn inventory_add_with_meta(
+        &self,
+        values: &Values,
+        meta: &BTreeMap<String, String>,
+    ) -> Result<String> {
+        return self.call(
+            "inventory_add_with_meta",
+            json!([self.to_wire(values)?, meta]),
+        );
+    }
+
+    pub fn inventory_list(&self, query: &ListQuery) -> Result<Vec<Entity>> {
+        let entities: Vec<WireCollection> =
+            self.call("inventory_list", json!([query.to_wire()]))?;
+        return Ok(entities.into_iter().map(Entity::from_wire).collect());
+    }
+
+    pub fn inventory_get(&self, identifier: &str) -> Result<Option<Entity>> {
+        let entity: Option<WireCollection> = self.call("inventory_get", json!([identifier]))?;
+        return Ok(entity.map(Entity::from_wire));
+    }
+
+    pub fn inventory_exists(&self, identifier: &str) -> Result<bool> {
+        return self.call("inventory_exists", json!([identifier]));
+    }
+
+    /**
+     * Counts the entities matching all of the conditions, all entities without any
+     */
+    pub fn inventory_count(&self, conditions: &Values) -> Result<u32> {
+        return self.call("inventory_count", json!([self.to_wire(conditions)?]));
+    }
+
+    /**
+     * Edits an entity, failing if the entity is no longer at the expected updated_at or
+     * revision, if given
+     */
+    pub fn inventory_edit(
+        &self,
+        identifier: &str,
+        values: &Values,
+        expect_version: Option<&str>,
+    ) -> Result<String> {
+        if values.is_empty() {
+            bail!("No values given to edit");
+        }
+        return self.call(
+            "inventory_edit",
+            json!([identifier, self.to_wire(values)?, expect_version]),
+        );
+    }
+
+    /**
+     * Removes an entity, the reason has to be one of the reason codes configured on the server
+     * unless none are
+     */
+    pub fn inventory_remove(&self, identifier: &str, reason: Option<&str>) -> Result<String> {
+        return self.call("inventory_remove", json!([identifier, reason]));
+    }
+
+    /**
+     * Searches the text and varchar columns, comments, attachments, suppliers and metadata for
+     * the text, up to limit matches per group
+     */
+    pub fn search(&self, text: &str, limit: u32) -> Result<SearchResults> {
+        let columns: Vec<String> = self
+            .schema()?
+            .into_iter()
+            .filter(|e| matches!(e.column_type, ColumnType::TEXT | ColumnType::VARCHAR))
+            .map(|e| e.name)
+            .collect();
+        let props = json!({
+            "columns": columns,
+            "comments": true,
+            "attachments": true,
+            "suppliers": true,
+            "meta": true,
+            "limit": limit,
+        });
+        return self.call("search", json!([text, props]));
+    }
+
+    pub fn comment_add(&self, identifier: &str, body: &str) -> Result<String> {
+        return self.call("comment_add", json!([identifier, body]));
+    }
+
+    pub fn comment_list(&self, identifier: &str) -> Result<Vec<Comment>> {
+        return self.call("comment_list", json!([identifier]));
+    }
+
+    pub fn meta_set(&self, identifier: &str, key: &str, value: &str) -> Result<String> {
+        return self.call("meta_set", json!([identifier, key, value]));
+    }
+
+    pub fn meta_remove(&self, identifier: &str, key: &str) -> Result<String> {
+        return self.call("meta_remove", json!([identifier, key]));
+    }
+
+    /**
+     * Metadata of the entity by key
+     */
+    pub fn meta_get(&self, identifier: &str) -> Result<BTreeMap<String, String>> {
+        return self.call("meta_get", json!([identifier]));
+    }
+
+    pub fn stock_list(&self, identifier: &str) -> Result<Vec<Stock>> {
+        return self.call("stock_list", json!([identifier]));
+    }
+
+    /**
+     * Looks up the id of the category at the given path, e.g. electronics/laptops
+     */
+    pub fn category_id(&self, path: &str) -> Result<u32> {
+        return self.call("category_id", json!([path]));
+    }
+}
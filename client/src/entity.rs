@@ -0,0 +1,353 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use anyhow::{bail, Result};
+use invman_core::{ColumnType, Money, SchemaDeclaration, SchemaDeclarationVerify};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+
+/**
+ * Value of a column, typed by the declaration of the column. Values of money columns are text
+ * like 12.50 EUR.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Text(String),
+    Int(i64),
+    Real(f64),
+    Bool(bool),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        return matches!(self, Value::Null);
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        return match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        };
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        return match self {
+            Value::Int(int) => Some(*int),
+            _ => None,
+        };
+    }
+
+    /**
+     * The value of a REAL or INT column
+     */
+    pub fn as_f64(&self) -> Option<f64> {
+        return match self {
+            Value::Real(real) => Some(*real),
+            Value::Int(int) => Some(*int as f64),
+            _ => None,
+        };
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        return match self {
+            Value::Bool(bool) => Some(*bool),
+            _ => None,
+        };
+    }
+
+    /**
+     * Reads a value as the server sends it, which is the text of the value next to the type of
+     * its column. Values not matching their type, e.g. of columns whose type was altered, are
+     * kept as text.
+     */
+    fn from_wire(value: Option<String>, column_type: ColumnType) -> Value {
+        let value = match value {
+            Some(value) => value,
+            None => return Value::Null,
+        };
+        return match column_type {
+            ColumnType::INT => match value.parse::<i64>() {
+                Ok(int) => Value::Int(int),
+                Err(_) => Value::Text(value),
+            },
+            ColumnType::REAL => match value.parse::<f64>() {
+                Ok(real) => Value::Real(real),
+                Err(_) => Value::Text(value),
+            },
+            ColumnType::BOOL => Value::Bool(value == "true" || value == "1"),
+            ColumnType::TEXT | ColumnType::VARCHAR | ColumnType::MONEY => Value::Text(value),
+        };
+    }
+
+    fn from_json(value: serde_json::Value) -> Result<Value> {
+        return match value {
+            serde_json::Value::Null => Ok(Value::Null),
+            serde_json::Value::Bool(bool) => Ok(Value::Bool(bool)),
+            serde_json::Value::String(text) => Ok(Value::Text(text)),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(int) => Ok(Value::Int(int)),
+                None => match number.as_f64() {
+                    Some(real) => Ok(Value::Real(real)),
+                    None => bail!("Number {} is out of range", number),
+                },
+            },
+            _ => bail!("Only plain values can be stored in a column, not {}", value),
+        };
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        return match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Text(text) => text.clone().into(),
+            Value::Int(int) => (*int).into(),
+            Value::Real(real) => (*real).into(),
+            Value::Bool(bool) => (*bool).into(),
+        };
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Value::Null => write!(f, "null"),
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Int(int) => write!(f, "{}", int),
+            Value::Real(real) => write!(f, "{}", real),
+            Value::Bool(bool) => write!(f, "{}", bool),
+        };
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Value {
+        return Value::Text(value.into());
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        return Value::Text(value);
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Value {
+        return Value::Int(value);
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Value {
+        return Value::Int(value.into());
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Value {
+        return Value::Int(value.into());
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Value {
+        return Value::Real(value);
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        return Value::Bool(value);
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Value {
+        return match value {
+            Some(value) => value.into(),
+            None => Value::Null,
+        };
+    }
+}
+
+/**
+ * Columns of every entity that are set by the server
+ */
+const GENERATED_COLUMNS: [&str; 4] = ["id", "created_at", "updated_at", "deleted_at"];
+
+/**
+ * Value of a column as the server sends and receives it
+ */
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct WireEntry {
+    key: String,
+    value: Option<String>,
+    column_type: ColumnType,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct WireCollection {
+    collection: Vec<WireEntry>,
+}
+
+/**
+ * Entity of the inventory with its columns in the order of the schema, next to id,
+ * created_at, updated_at and deleted_at. Columns the user may not read are left out by the
+ * server or hold a masked value.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entity {
+    columns: Vec<(String, Value)>,
+}
+
+impl Entity {
+    pub fn id(&self) -> Option<u32> {
+        return self
+            .get("id")
+            .and_then(Value::as_i64)
+            .and_then(|id| u32::try_from(id).ok());
+    }
+
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        return self
+            .columns
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, value)| value);
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = (&str, &Value)> {
+        return self
+            .columns
+            .iter()
+            .map(|(name, value)| (name.as_str(), value));
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        return serde_json::Value::Object(
+            self.columns
+                .iter()
+                .map(|(name, value)| (name.clone(), value.to_json()))
+                .collect(),
+        );
+    }
+
+    /**
+     * Converts the entity into a type of the caller, whose fields are named like the columns,
+     * e.g. a struct deriving Deserialize.
+     */
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        return Ok(serde_json::from_value(self.to_json())?);
+    }
+
+    pub(crate) fn from_wire(wire: WireCollection) -> Entity {
+        return Entity {
+            columns: wire
+                .collection
+                .into_iter()
+                .map(|e| (e.key, Value::from_wire(e.value, e.column_type)))
+                .collect(),
+        };
+    }
+}
+
+/**
+ * Values of columns to set on an entity or to match entities by, which are checked against
+ * the schema before being sent to the server.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Values {
+    entries: Vec<(String, Value)>,
+}
+
+impl Values {
+    pub fn new() -> Values {
+        return Values::default();
+    }
+
+    /**
+     * Sets the value of a column, replacing the one set before.
+     */
+    pub fn set(mut self, column: &str, value: impl Into<Value>) -> Values {
+        let value = value.into();
+        match self.entries.iter_mut().find(|(name, _)| name == column) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((column.into(), value)),
+        }
+        return self;
+    }
+
+    /**
+     * Takes the values from a type of the caller, whose fields are named like the columns,
+     * e.g. a struct deriving Serialize. Fields that are None are set to null, those of the
+     * columns kept by the server like id are left out, so the same struct can be used to read
+     * entities.
+     */
+    pub fn from_struct<T: Serialize>(value: &T) -> Result<Values> {
+        let fields = match serde_json::to_value(value)? {
+            serde_json::Value::Object(fields) => fields,
+            _ => bail!("Values can only be taken from a struct or map"),
+        };
+        let mut values = Values::new();
+        for (name, value) in fields {
+            if GENERATED_COLUMNS.contains(&name.as_str()) {
+                continue;
+            }
+            values = values.set(&name, Value::from_json(value)?);
+        }
+        return Ok(values);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    /**
+     * Checks the values against their declarations like the command line does with the
+     * name=value notation, and converts them to the notation the server expects.
+     */
+    pub(crate) fn to_wire(&self, declarations: &[SchemaDeclaration]) -> Result<WireCollection> {
+        let mut collection = vec![];
+        for (name, value) in &self.entries {
+            let declaration = match declarations.iter().find(|e| e.name == *name) {
+                Some(declaration) => declaration,
+                None => bail!("Could not find '{}' in table schema", name),
+            };
+            let value = match value {
+                Value::Null if !declaration.nullable => {
+                    bail!("Column '{}' is not nullable", name)
+                }
+                Value::Null => None,
+                value => {
+                    format!("{}={}", name, value).check_against_declaration(declarations)?;
+                    match declaration.column_type {
+                        ColumnType::MONEY => Some(Money::parse(&value.to_string())?.to_string()),
+                        _ => Some(value.to_string()),
+                    }
+                }
+            };
+            collection.push(WireEntry {
+                key: name.clone(),
+                value,
+                column_type: declaration.column_type,
+            });
+        }
+        return Ok(WireCollection { collection });
+    }
+}
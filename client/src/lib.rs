@@ -0,0 +1,33 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Typed client for the API of an invman started with serve, so that services written in Rust
+// work with entities and their values instead of requests. The server checks the permissions
+// of the authenticated user like the command line does; values are checked against the schema
+// before they are sent.
+mod client;
+mod entity;
+mod types;
+
+pub use client::{InvManClient, ListQuery};
+pub use entity::{Entity, Value, Values};
+pub use invman_core::{ColumnType, SchemaDeclaration};
+pub use types::{
+    Attachment, Comment, HealthCheck, HealthReport, SearchMatch, SearchResults, Stock, Supplier,
+};
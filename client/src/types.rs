@@ -0,0 +1,94 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: u32,
+    pub inventory_id: u32,
+    pub author: String,
+    pub body: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stock {
+    pub location: String,
+    pub quantity: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: u32,
+    pub inventory_id: u32,
+    pub filename: String,
+    pub size: u64,
+    pub dispatcher: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Supplier {
+    pub id: u32,
+    pub name: String,
+    pub contact: Option<String>,
+    pub lead_time_days: Option<u32>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub inventory_id: u32,
+    pub column: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub inventory: Vec<SearchMatch>,
+    pub comments: Vec<Comment>,
+    /**
+     * Attachments whose filename matches
+     */
+    pub attachments: Vec<Attachment>,
+    /**
+     * Suppliers whose name or contact matches
+     */
+    pub suppliers: Vec<Supplier>,
+    /**
+     * Metadata whose key or value matches, with the key as column
+     */
+    #[serde(default)]
+    pub meta: Vec<SearchMatch>,
+}
@@ -0,0 +1,152 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use anyhow::{bail, Result};
+use core::fmt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/**
+ * Amount of money in a currency, written as amount and ISO 4217 code, e.g. 12.50 EUR
+ */
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Money {
+    pub amount: f64,
+    pub currency: String,
+}
+
+impl Money {
+    pub fn parse(value: &str) -> Result<Money> {
+        let (amount, currency) = match value.trim().split_once(' ') {
+            Some((amount, currency)) => (amount, currency.trim()),
+            None => bail!(
+                "Money '{}' must be an amount and a currency, e.g. 12.50 EUR",
+                value
+            ),
+        };
+        let amount = match amount.parse::<f64>() {
+            Ok(amount) if amount.is_finite() => amount,
+            _ => bail!("Amount '{}' is not a valid number", amount),
+        };
+        return Ok(Money {
+            amount,
+            currency: parse_currency(currency)?,
+        });
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{} {}", self.amount, self.currency);
+    }
+}
+
+/**
+ * Checks the currency to be a code of three letters like EUR, returned in upper case.
+ */
+pub fn parse_currency(code: &str) -> Result<String> {
+    if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        bail!(
+            "Currency '{}' must be a code of three letters, e.g. EUR",
+            code
+        );
+    }
+    return Ok(code.to_ascii_uppercase());
+}
+
+/**
+ * Exchange rates between currencies, which amounts are converted with into the base currency
+ * of reports
+ */
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ExchangeRates {
+    /**
+     * Currency of amounts given without one, and that reports are converted into
+     */
+    pub base: Option<String>,
+    /**
+     * Rates by their pair in FROM:TO notation, where one FROM is worth rate TO
+     */
+    pub rates: BTreeMap<String, f64>,
+}
+
+impl ExchangeRates {
+    /**
+     * Splits a pair in FROM:TO notation into its two currencies
+     */
+    pub fn parse_pair(pair: &str) -> Result<(String, String)> {
+        return match pair.split_once(':') {
+            Some((from, to)) => {
+                let (from, to) = (parse_currency(from)?, parse_currency(to)?);
+                if from == to {
+                    bail!("Currencies of the pair '{}' must differ", pair);
+                }
+                Ok((from, to))
+            }
+            None => bail!("Pair '{}' must be in FROM:TO notation, e.g. EUR:USD", pair),
+        };
+    }
+
+    /**
+     * Rate to convert from one currency into the other. Pairs can be used in both directions
+     * and be chained, e.g. EUR:USD and USD:GBP convert EUR into GBP, the shortest chain wins.
+     */
+    pub fn rate(&self, from: &str, to: &str) -> Result<f64> {
+        let mut rates = BTreeMap::from([(from.to_string(), 1.0)]);
+        let mut queue = VecDeque::from([from.to_string()]);
+        while let Some(currency) = queue.pop_front() {
+            let rate = rates[&currency];
+            if currency == to {
+                return Ok(rate);
+            }
+            for (pair, pair_rate) in &self.rates {
+                let next = match pair.split_once(':') {
+                    Some((a, b)) if a == currency => (b, *pair_rate),
+                    Some((a, b)) if b == currency => (a, 1.0 / pair_rate),
+                    _ => continue,
+                };
+                if !rates.contains_key(next.0) {
+                    rates.insert(next.0.to_string(), rate * next.1);
+                    queue.push_back(next.0.to_string());
+                }
+            }
+        }
+        bail!(
+            "No exchange rate from {} to {}, set one with config rates set {}:{} <RATE>",
+            from,
+            to,
+            from,
+            to
+        );
+    }
+
+    /**
+     * Converts the amount into the currency, amounts without a currency being in the base
+     * currency
+     */
+    pub fn convert(&self, amount: f64, from: Option<&str>, to: &str) -> Result<f64> {
+        let from = match (from, &self.base) {
+            (Some(from), _) => from,
+            (None, Some(base)) => base,
+            (None, None) => return Ok(amount),
+        };
+        return Ok(amount * self.rate(from, to)?);
+    }
+}
@@ -0,0 +1,117 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::money::Money;
+use crate::schema::{ColumnType, SchemaDeclaration};
+use anyhow::{anyhow, bail, Result};
+
+pub trait SchemaDeclarationVerify {
+    /**
+     * Check if a given String is in schema notation and is found within the vector
+     * of declarations. Then the string's value is checked against that schema. Limits of 0
+     * are not set.
+     *
+     * @returns A tuple in (name, value) syntax, the value being a JSON literal
+     */
+    fn check_against_declaration(
+        &self,
+        declarations: &[SchemaDeclaration],
+    ) -> Result<(String, String)>;
+}
+
+impl SchemaDeclarationVerify for String {
+    fn check_against_declaration(
+        &self,
+        declarations: &[SchemaDeclaration],
+    ) -> Result<(String, String)> {
+        let (name, value) = match self.split_once("=") {
+            Some(notation) => notation,
+            None => bail!("Given string {} is not in valid schema notation", self),
+        };
+        let name = String::from(name);
+        let schema = match declarations.iter().find(|e| e.name == name) {
+            Some(schema) => schema,
+            None => bail!("Field {} could not be found in schema declaration", name),
+        };
+
+        return match schema.column_type {
+            ColumnType::BOOL => {
+                if value.eq_ignore_ascii_case("true") {
+                    Ok((name, String::from("true")))
+                } else if value.eq_ignore_ascii_case("false") {
+                    Ok((name, String::from("false")))
+                } else {
+                    Err(anyhow!("Value not of boolean type"))
+                }
+            }
+            ColumnType::VARCHAR | ColumnType::TEXT => {
+                let value_len = value.chars().count();
+                if value_len < usize::try_from(schema.min_length)? {
+                    Err(anyhow!(
+                        "Field's {} length is less than schema's min length",
+                        name
+                    ))
+                } else if schema.max_length > 0 && value_len > usize::try_from(schema.max_length)? {
+                    Err(anyhow!(
+                        "Field's {} length is more than schema's max length",
+                        name
+                    ))
+                } else {
+                    Ok((name, serde_json::to_string(value)?))
+                }
+            }
+            ColumnType::INT => match value.parse::<i64>() {
+                Ok(s) => {
+                    if schema.min > 0 && s < schema.min.into() {
+                        bail!("Field {} is smaller than schema's min", name);
+                    } else if schema.max > 0 && s > schema.max.into() {
+                        bail!("Field {} is larger than schema's max", name);
+                    } else {
+                        Ok((name, value.into()))
+                    }
+                }
+                Err(_) => Err(anyhow!("Field {} is not a valid integer type", name)),
+            },
+            ColumnType::REAL => match value.parse::<f64>() {
+                Ok(s) if s.is_finite() => {
+                    if schema.min > 0 && s < schema.min.into() {
+                        bail!("Field {} is smaller than schema's min", name);
+                    } else if schema.max > 0 && s > schema.max.into() {
+                        bail!("Field {} is larger than schema's max", name);
+                    } else {
+                        Ok((name, value.into()))
+                    }
+                }
+                _ => Err(anyhow!("Field {} is not a valid real type", name)),
+            },
+            ColumnType::MONEY => {
+                let money = match Money::parse(value) {
+                    Ok(money) => money,
+                    Err(e) => bail!("Field {} is not a valid money type ({})", name, e),
+                };
+                if schema.min > 0 && money.amount < schema.min.into() {
+                    bail!("Field {} is smaller than schema's min", name);
+                } else if schema.max > 0 && money.amount > schema.max.into() {
+                    bail!("Field {} is larger than schema's max", name);
+                }
+                Ok((name, serde_json::to_string(&money.to_string())?))
+            }
+        };
+    }
+}
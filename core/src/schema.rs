@@ -0,0 +1,312 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::money::Money;
+use anyhow::{bail, Result};
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum ColumnType {
+    #[default]
+    TEXT,
+    VARCHAR,
+    INT,
+    REAL,
+    BOOL,
+    /**
+     * Amount and currency, stored as text in the notation of Money, e.g. 12.50 EUR
+     */
+    MONEY,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SchemaDeclaration {
+    pub name: String,
+    pub display_name: String,
+    pub unique: bool,
+    pub max_length: u32,
+    pub min_length: u32,
+    pub max: u32,
+    pub min: u32,
+    pub nullable: bool,
+    pub column_type: ColumnType,
+    pub default: String,
+    pub hint: String,
+    pub layout: String,
+    #[serde(default)]
+    pub serialized: bool,
+}
+
+/**
+ * Default of a column taken from where the entity is added instead of from the database, so
+ * that e.g. each entity records the operator or station that added it
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContextDefault {
+    /**
+     * CURRENT_USER, the name of the authenticated user
+     */
+    CurrentUser,
+    /**
+     * ENV(NAME), the environment variable of the process adding the entity
+     */
+    Env(String),
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnType::BOOL => write!(f, "bool"),
+            ColumnType::INT => write!(f, "int"),
+            ColumnType::REAL => write!(f, "real"),
+            ColumnType::TEXT => write!(f, "text"),
+            ColumnType::VARCHAR => write!(f, "varchar"),
+            ColumnType::MONEY => write!(f, "money"),
+        }
+    }
+}
+
+impl SchemaDeclaration {
+    /**
+     * Checks that the limits, the default and the flags of the column fit together.
+     */
+    pub fn validate(&self) -> Result<()> {
+        if self.min_length > self.max_length {
+            bail!("Schema min-length parameter cannot be larger than max-length!");
+        }
+
+        if self.min > self.max {
+            bail!("Schema min parameter cannot be larger than max!");
+        }
+
+        if self.column_type == ColumnType::VARCHAR && self.max_length == 0 {
+            bail!("Schema cannot have column type varchar with max-length being 0!");
+        }
+
+        if self.serialized && self.column_type != ColumnType::BOOL {
+            bail!("Schema can only flag columns of type bool as serialized!");
+        }
+
+        if self.default.starts_with("ENV(") && self.context_default().is_none() {
+            bail!("Schema default ENV(NAME) requires a name of letters, digits and _!");
+        }
+
+        let static_default = self.default != "NULL" && self.context_default().is_none();
+        if self.column_type == ColumnType::MONEY && static_default {
+            Money::parse(&self.default)?;
+        }
+
+        if static_default {
+            if self.max_length > 0 && self.default.len() > usize::try_from(self.max_length)? {
+                bail!("Schema default value cannot be longer than max-length!");
+            }
+            if self.min_length > 0 && self.default.len() < usize::try_from(self.min_length)? {
+                bail!("Schema default value cannot be shorter than min-length!");
+            }
+        }
+
+        return Ok(());
+    }
+
+    /**
+     * @returns The default if it is taken from the context of the addition, None for defaults
+     * the database sets itself
+     */
+    pub fn context_default(&self) -> Option<ContextDefault> {
+        if self.default == "CURRENT_USER" {
+            return Some(ContextDefault::CurrentUser);
+        }
+        let name = self.default.strip_prefix("ENV(")?.strip_suffix(')')?;
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return None;
+        }
+        return Some(ContextDefault::Env(name.into()));
+    }
+
+    /**
+     * Describes the column as property of a JSON Schema. Limits of 0 are not set, as is the
+     * default of the column.
+     */
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        let column_type = match self.column_type {
+            ColumnType::BOOL => "boolean",
+            ColumnType::INT => "integer",
+            ColumnType::REAL => "number",
+            ColumnType::TEXT | ColumnType::VARCHAR | ColumnType::MONEY => "string",
+        };
+        let mut property = serde_json::Map::new();
+        property.insert(
+            "type".into(),
+            match self.nullable {
+                true => serde_json::json!([column_type, "null"]),
+                false => serde_json::json!(column_type),
+            },
+        );
+        property.insert("title".into(), self.display_name.clone().into());
+        if !self.hint.is_empty() {
+            property.insert("description".into(), self.hint.clone().into());
+        }
+        match self.column_type {
+            ColumnType::TEXT | ColumnType::VARCHAR => {
+                if self.min_length > 0 {
+                    property.insert("minLength".into(), self.min_length.into());
+                }
+                if self.max_length > 0 {
+                    property.insert("maxLength".into(), self.max_length.into());
+                }
+            }
+            ColumnType::MONEY => {
+                property.insert(
+                    "pattern".into(),
+                    "^-?[0-9]+(\\.[0-9]+)? [A-Za-z]{3}$".into(),
+                );
+            }
+            ColumnType::INT | ColumnType::REAL => {
+                if self.min > 0 {
+                    property.insert("minimum".into(), self.min.into());
+                }
+                if self.max > 0 {
+                    property.insert("maximum".into(), self.max.into());
+                }
+            }
+            ColumnType::BOOL => {}
+        }
+        if let Some(default) = self.json_default() {
+            property.insert("default".into(), default);
+        }
+        if self.unique {
+            property.insert("x-unique".into(), true.into());
+        }
+        return serde_json::Value::Object(property);
+    }
+
+    /**
+     * @returns The default as JSON value, None if there is none or it is evaluated on addition
+     */
+    fn json_default(&self) -> Option<serde_json::Value> {
+        // Defaults evaluated by the database, like CURRENT_TIMESTAMP, have no JSON equivalent
+        return match self.column_type {
+            _ if self.default == "NULL" || self.context_default().is_some() => None,
+            ColumnType::BOOL => self.default.parse::<bool>().ok().map(|e| e.into()),
+            ColumnType::INT => self.default.parse::<i64>().ok().map(|e| e.into()),
+            ColumnType::REAL => self.default.parse::<f64>().ok().map(|e| e.into()),
+            ColumnType::MONEY => Some(self.default.clone().into()),
+            ColumnType::TEXT | ColumnType::VARCHAR if !self.default.starts_with("CURRENT_") => {
+                Some(self.default.clone().into())
+            }
+            ColumnType::TEXT | ColumnType::VARCHAR => None,
+        };
+    }
+
+    /**
+     * A value the column accepts, e.g. for templates of an import. It is the default of the
+     * column if that is a plain value, else one within the limits of the column. Defaults
+     * evaluated on addition are left to the caller.
+     */
+    pub fn example(&self) -> serde_json::Value {
+        if let Some(default) = self.json_default() {
+            return default;
+        }
+        let number = match self.max {
+            0 => self.min.max(1),
+            max => self.min.max(1).min(max),
+        };
+        return match self.column_type {
+            ColumnType::BOOL => false.into(),
+            ColumnType::INT => number.into(),
+            ColumnType::REAL => f64::from(number).into(),
+            ColumnType::MONEY => "9.99 EUR".into(),
+            ColumnType::TEXT | ColumnType::VARCHAR => {
+                let mut text = format!("example {}", self.name);
+                while text.len() < self.min_length as usize {
+                    text.push('x');
+                }
+                if self.max_length > 0 {
+                    text = text.chars().take(self.max_length as usize).collect();
+                }
+                text.into()
+            }
+        };
+    }
+
+    /**
+     * @returns The column's name and type in GraphQL
+     */
+    pub fn to_graphql(&self) -> (String, &'static str) {
+        let column_type = match self.column_type {
+            ColumnType::BOOL => "Boolean",
+            ColumnType::INT => "Int",
+            ColumnType::REAL => "Float",
+            ColumnType::TEXT | ColumnType::VARCHAR | ColumnType::MONEY => "String",
+        };
+        return (self.name.replace('-', "_"), column_type);
+    }
+
+    pub fn is_equal(&self, other: &SchemaDeclaration) -> bool {
+        return self.name == other.name;
+    }
+
+    pub fn to_json(&self) -> String {
+        return format!("{{\"name\":\"{}\",\"display_name\":\"{}\",\"unique\":{},\"max_length\":{},\"min_length\":{},\"max\":{},\"min\":{},\"nullable\":{},\"column_type\":\"{}\",\"default\":\"{}\",\"hint\":\"{}\",\"layout\":\"{}\",\"serialized\":{}}}",
+                       self.name, self.display_name, self.unique, self.max_length, self.min_length, self.max, self.min, self.nullable, self.column_type, self.default, self.hint, self.layout, self.serialized);
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct SchemaCollection {
+    pub collection: Vec<SchemaDeclaration>,
+}
+
+impl SchemaCollection {
+    pub fn new(collection: Vec<SchemaDeclaration>) -> SchemaCollection {
+        return SchemaCollection { collection };
+    }
+
+    pub fn sql_names(&self) -> String {
+        return if self.collection.is_empty() {
+            "id,created_at,updated_at,deleted_at".into()
+        } else {
+            format!(
+                "id,created_at,updated_at,deleted_at,{}",
+                self.collection
+                    .iter()
+                    .map(|e| e.name.clone())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            )
+        };
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = self
+            .collection
+            .iter()
+            .map(|e| e.to_json())
+            .collect::<Vec<String>>()
+            .join(",");
+        json.insert(0, '[');
+        json.push(']');
+        return json;
+    }
+
+    pub fn contains(&self, declaration: &SchemaDeclaration) -> Option<usize> {
+        return self.collection.iter().position(|d| d.is_equal(declaration));
+    }
+}
@@ -0,0 +1,165 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::{notation::SchemaDeclarationVerify, schema::SchemaCollection};
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+/**
+ * Values of an entity by column name, without its id
+ */
+pub type Entity = BTreeMap<String, serde_json::Value>;
+
+/**
+ * Keeps the entities of an Inventory, e.g. in memory or the storage of a browser. Entities are
+ * validated before they are handed to the storage.
+ */
+pub trait EntityStorage {
+    /**
+     * @returns The id of the new entity, greater than 0
+     */
+    fn insert(&mut self, entity: Entity) -> Result<u32>;
+    fn get(&self, id: u32) -> Option<Entity>;
+    /**
+     * @returns All entities ordered by their id
+     */
+    fn list(&self) -> Vec<(u32, Entity)>;
+    fn update(&mut self, id: u32, entity: Entity) -> Result<()>;
+    fn delete(&mut self, id: u32) -> Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    entities: BTreeMap<u32, Entity>,
+    last_id: u32,
+}
+
+impl EntityStorage for MemoryStorage {
+    fn insert(&mut self, entity: Entity) -> Result<u32> {
+        self.last_id += 1;
+        self.entities.insert(self.last_id, entity);
+        return Ok(self.last_id);
+    }
+
+    fn get(&self, id: u32) -> Option<Entity> {
+        return self.entities.get(&id).cloned();
+    }
+
+    fn list(&self) -> Vec<(u32, Entity)> {
+        return self
+            .entities
+            .iter()
+            .map(|(id, entity)| (*id, entity.clone()))
+            .collect();
+    }
+
+    fn update(&mut self, id: u32, entity: Entity) -> Result<()> {
+        match self.entities.get_mut(&id) {
+            Some(stored) => *stored = entity,
+            None => bail!("Entity {} does not exist", id),
+        }
+        return Ok(());
+    }
+
+    fn delete(&mut self, id: u32) -> Result<()> {
+        if self.entities.remove(&id).is_none() {
+            bail!("Entity {} does not exist", id);
+        }
+        return Ok(());
+    }
+}
+
+/**
+ * Entities following the schema, added and edited in name=value notation like on the command
+ * line of invman.
+ */
+pub struct Inventory<S: EntityStorage> {
+    pub schema: SchemaCollection,
+    pub storage: S,
+}
+
+impl<S: EntityStorage> Inventory<S> {
+    pub fn new(schema: SchemaCollection, storage: S) -> Inventory<S> {
+        return Inventory { schema, storage };
+    }
+
+    pub fn add(&mut self, params: &[String]) -> Result<u32> {
+        let mut entity = Entity::new();
+        self.apply(&mut entity, params, None)?;
+        return self.storage.insert(entity);
+    }
+
+    pub fn edit(&mut self, id: u32, params: &[String]) -> Result<()> {
+        let mut entity = match self.storage.get(id) {
+            Some(entity) => entity,
+            None => bail!("Entity {} does not exist", id),
+        };
+        self.apply(&mut entity, params, Some(id))?;
+        return self.storage.update(id, entity);
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<()> {
+        return self.storage.delete(id);
+    }
+
+    /**
+     * Sets the values of the params on the entity, after checking them against the schema.
+     * Columns without a value are set to their default, unless they are not nullable.
+     */
+    fn apply(&self, entity: &mut Entity, params: &[String], id: Option<u32>) -> Result<()> {
+        for param in params {
+            let (name, value) = param.check_against_declaration(&self.schema.collection)?;
+            entity.insert(name, serde_json::from_str(&value)?);
+        }
+        for column in self.schema.collection.iter() {
+            // Without a user or an environment, these are left to the invman adding the entity
+            if entity.get(&column.name).is_some_and(|e| !e.is_null())
+                || column.context_default().is_some()
+            {
+                continue;
+            }
+            if column.default != "NULL" {
+                let (name, value) = format!("{}={}", column.name, column.default)
+                    .check_against_declaration(&self.schema.collection)?;
+                entity.insert(name, serde_json::from_str(&value)?);
+            } else if !column.nullable {
+                bail!("Field {} is not nullable and must be given", column.name);
+            }
+        }
+        for column in self.schema.collection.iter().filter(|e| e.unique) {
+            let value = match entity.get(&column.name) {
+                Some(value) if !value.is_null() => value,
+                _ => continue,
+            };
+            let taken = self
+                .storage
+                .list()
+                .iter()
+                .any(|(other, e)| Some(*other) != id && e.get(&column.name) == Some(value));
+            if taken {
+                bail!(
+                    "Field {} must be unique, {} is already taken",
+                    column.name,
+                    value
+                );
+            }
+        }
+        return Ok(());
+    }
+}
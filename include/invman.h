@@ -0,0 +1,71 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+#ifndef INVMAN_H
+#define INVMAN_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/**
+ * Database opened by invman_open, together with the user all calls are made as
+ */
+typedef struct InvMan InvMan;
+
+/**
+ * Opens the database at the path, which is created if it does not exist, and authenticates
+ * the user by username:password or token.
+ *
+ * @returns The handle to pass to the other functions, NULL if opening or authenticating failed
+ */
+InvMan *invman_open(const char *path, const char *auth);
+
+/**
+ * Closes the database of the handle, which must not be used afterwards.
+ */
+void invman_close(InvMan *invman);
+
+/**
+ * Adds the entity given as JSON object, e.g. {"sku":"A1","weight":4}.
+ *
+ * @returns {"ok":true,"result":...} or {"ok":false,"error":"..."}, freed with invman_free
+ */
+char *invman_add_json(InvMan *invman, const char *entity);
+
+/**
+ * Lists the entities with an identifier greater than after_id, at most limit of them. A limit
+ * of 0 or less lists all of them.
+ *
+ * @returns {"ok":true,"result":[...]} or {"ok":false,"error":"..."}, freed with invman_free
+ */
+char *invman_list_json(InvMan *invman, int32_t limit, int64_t after_id);
+
+/**
+ * Frees a string returned by invman.
+ */
+void invman_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,349 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use anyhow::{bail, Result};
+
+use crate::{
+    common::args::{CommandContext, WhereExpr},
+    database::{AppConfig, DBUser, KeyValueCollection},
+};
+
+/**
+ * What a user must be allowed to do for an operation of the database. Commands and the requests
+ * of a server check it alike, the checks that depend on the arguments, e.g. of the owner of an
+ * entity or of the columns of a collection, are left to the operations.
+ */
+#[derive(Clone, Copy)]
+pub(crate) struct Access {
+    /**
+     * Whether the operation changes the database, which must not be opened read-only then
+     */
+    pub writes: bool,
+    pub read: &'static [&'static str],
+    pub write: &'static [&'static str],
+}
+
+impl Access {
+    /**
+     * Any user may run the operation
+     */
+    pub(crate) const USER: Access = Access {
+        writes: false,
+        read: &[],
+        write: &[],
+    };
+    /**
+     * Any user may run the operation, which changes the database
+     */
+    pub(crate) const CHANGE: Access = Access {
+        writes: true,
+        read: &[],
+        write: &[],
+    };
+
+    pub(crate) const fn read(tables: &'static [&'static str]) -> Access {
+        return Access {
+            writes: false,
+            read: tables,
+            write: &[],
+        };
+    }
+
+    pub(crate) const fn write(tables: &'static [&'static str]) -> Access {
+        return Access {
+            writes: true,
+            read: &[],
+            write: tables,
+        };
+    }
+
+    pub(crate) fn check(&self, ctx: &CommandContext, user: &DBUser) -> Result<()> {
+        if self.writes {
+            ctx.check_writable()?;
+        }
+        for table in self.read {
+            require_read(user, table)?;
+        }
+        for table in self.write {
+            require_write(user, table)?;
+        }
+        return Ok(());
+    }
+}
+
+pub(crate) fn require_read(user: &DBUser, table: &str) -> Result<()> {
+    if !user.can_read_table(table) {
+        bail!("Cannot read {}", describe(table));
+    }
+    return Ok(());
+}
+
+pub(crate) fn require_write(user: &DBUser, table: &str) -> Result<()> {
+    if !user.can_write_table(table) {
+        bail!("Cannot write to {}", describe(table));
+    }
+    return Ok(());
+}
+
+/**
+ * The keys of collections are put into the SQL as they are, so they must be columns of the
+ * schema.
+ */
+pub(crate) fn require_columns(config: &AppConfig, collection: &KeyValueCollection) -> Result<()> {
+    for entry in collection.collection.iter() {
+        require_column(config, &entry.key)?;
+    }
+    return Ok(());
+}
+
+pub(crate) fn require_column(config: &AppConfig, column: &str) -> Result<()> {
+    if !config
+        .inventory_schema_declaration
+        .collection
+        .iter()
+        .any(|e| e.name == column)
+    {
+        bail!("Column {} is not part of the schema", column);
+    }
+    return Ok(());
+}
+
+pub(crate) fn require_write_collection(
+    user: &DBUser,
+    config: &AppConfig,
+    collection: &KeyValueCollection,
+) -> Result<()> {
+    require_columns(config, collection)?;
+    if !user.can_write_collection("inventory", collection) {
+        bail!("Cannot write arguments to inventory");
+    }
+    return Ok(());
+}
+
+pub(crate) fn require_read_collection(
+    user: &DBUser,
+    config: &AppConfig,
+    collection: &KeyValueCollection,
+) -> Result<()> {
+    require_columns(config, collection)?;
+    if let Some(entry) = collection
+        .collection
+        .iter()
+        .find(|e| user.is_read_denied("inventory", &e.key))
+    {
+        bail!("Cannot read column {} of inventory", entry.key);
+    }
+    return Ok(());
+}
+
+/**
+ * Checks that the where expression only compares columns of the inventory the user may read
+ */
+pub(crate) fn require_read_filter(
+    user: &DBUser,
+    config: &AppConfig,
+    filter: &WhereExpr,
+) -> Result<()> {
+    filter.check_columns(config)?;
+    if let Some(column) = filter
+        .columns()
+        .into_iter()
+        .find(|e| user.is_read_denied("inventory", e))
+    {
+        bail!("Cannot read column {} of inventory", column);
+    }
+    return Ok(());
+}
+
+fn describe(table: &str) -> String {
+    return match table {
+        "db" => "the database".into(),
+        _ => format!("the {} table", table),
+    };
+}
+
+/**
+ * The access of the operations by the name the server offers them by. Requests of operations that
+ * are not listed are rejected.
+ */
+const OPERATIONS: &[(&str, Access)] = &[
+    ("token_add", Access::CHANGE),
+    ("token_list", Access::USER),
+    ("token_auth", Access::USER),
+    ("token_revoke", Access::CHANGE),
+    ("user_id", Access::USER),
+    ("user_totp_enable", Access::CHANGE),
+    ("user_totp_disable", Access::CHANGE),
+    ("user_edit", Access::CHANGE),
+    ("user_purge", Access::write(&["users"])),
+    ("org_create", Access::write(&["org"])),
+    ("org_invite", Access::write(&["org"])),
+    ("org_list", Access::read(&["org"])),
+    ("role_list", Access::read(&["roles"])),
+    ("role_set_ownership", Access::write(&["roles"])),
+    ("role_deny_column", Access::write(&["roles"])),
+    ("inventory_owner", Access::USER),
+    ("schema_alter", Access::write(&["config"])),
+    ("schema_remove", Access::write(&["config"])),
+    ("schema_extend", Access::write(&["config"])),
+    ("inventory_add", Access::CHANGE),
+    ("inventory_add_with_meta", Access::write(&["meta"])),
+    ("inventory_list", Access::USER),
+    ("inventory_list_explain", Access::USER),
+    ("inventory_archive", Access::write(&["archive"])),
+    ("inventory_count", Access::USER),
+    ("inventory_exists", Access::USER),
+    ("inventory_get", Access::USER),
+    ("inventory_distinct", Access::USER),
+    ("inventory_edit", Access::CHANGE),
+    ("inventory_remove", Access::CHANGE),
+    ("inventory_merge", Access::CHANGE),
+    ("inventory_checkout", Access::write(&["loans"])),
+    ("inventory_checkin", Access::write(&["loans"])),
+    ("loan_list", Access::read(&["loans"])),
+    ("inventory_assign", Access::write(&["assignments"])),
+    ("inventory_unassign", Access::write(&["assignments"])),
+    ("purchase_set", Access::write(&["purchases"])),
+    ("purchase_list", Access::read(&["purchases"])),
+    ("stock_adjust", Access::write(&["stock"])),
+    ("stock_transfer", Access::write(&["stock"])),
+    ("stock_list", Access::read(&["stock"])),
+    ("stock_movements", Access::read(&["stock"])),
+    ("stock_consumption", Access::read(&["stock"])),
+    ("reason_summary", Access::read(&["stock"])),
+    ("stock_ledger", Access::read(&["stock", "purchases"])),
+    ("serial_history", Access::read(&["serials"])),
+    ("bom_define", Access::write(&["bom"])),
+    ("bom_list", Access::read(&["bom"])),
+    (
+        "bom_build",
+        Access {
+            writes: true,
+            read: &["bom"],
+            write: &["stock"],
+        },
+    ),
+    ("maintenance_schedule", Access::write(&["maintenance"])),
+    ("maintenance_done", Access::write(&["maintenance"])),
+    ("maintenance_due", Access::read(&["maintenance"])),
+    ("attachment_add", Access::write(&["attachments"])),
+    ("attachment_list", Access::read(&["attachments"])),
+    ("attachment_content", Access::read(&["attachments"])),
+    ("attachment_remove", Access::write(&["attachments"])),
+    ("comment_add", Access::write(&["comments"])),
+    ("meta_set", Access::write(&["meta"])),
+    ("meta_remove", Access::write(&["meta"])),
+    ("meta_get", Access::read(&["meta"])),
+    ("comment_list", Access::read(&["comments"])),
+    ("image_set", Access::write(&["images"])),
+    ("image_get", Access::read(&["images"])),
+    ("image_thumbnail", Access::read(&["images"])),
+    ("image_thumbnail_store", Access::read(&["images"])),
+    ("image_remove", Access::write(&["images"])),
+    (
+        "read_only_set",
+        Access {
+            writes: false,
+            read: &[],
+            write: &["db"],
+        },
+    ),
+    ("timezone_set", Access::write(&["config"])),
+    ("display_format_set", Access::write(&["config"])),
+    ("label_printer_set", Access::write(&["config"])),
+    ("exchange_rates_set", Access::write(&["config"])),
+    ("reason_codes_set", Access::write(&["config"])),
+    ("registration_set", Access::write(&["config"])),
+    ("password_policy_set", Access::write(&["config"])),
+    ("ldap_set", Access::write(&["config"])),
+    ("retention_set", Access::write(&["db"])),
+    // Dry runs only read the database
+    (
+        "retention_apply",
+        Access {
+            writes: false,
+            read: &[],
+            write: &["db"],
+        },
+    ),
+    ("dump", Access::read(&["db"])),
+    ("fixture_users", Access::read(&["users"])),
+    ("smtp_set", Access::write(&["notify"])),
+    ("scheduler_set", Access::write(&["db"])),
+    ("hooks_set", Access::write(&["db"])),
+    ("rule_add", Access::write(&["rule"])),
+    ("rule_list", Access::read(&["rule"])),
+    ("rule_remove", Access::write(&["rule"])),
+    ("rule_matches", Access::USER),
+    ("event_list", Access::read(&["db"])),
+    ("notify_rule_add", Access::write(&["notify"])),
+    ("notify_rule_list", Access::read(&["notify"])),
+    ("notify_rule_remove", Access::write(&["notify"])),
+    (
+        "notify_pending",
+        Access {
+            writes: false,
+            read: &[],
+            write: &["notify"],
+        },
+    ),
+    ("notify_advance", Access::write(&["notify"])),
+    // Fixing also requires to write to the database
+    ("doctor", Access::read(&["db"])),
+    ("optimize", Access::write(&["db"])),
+    ("metrics", Access::read(&["db"])),
+    ("history_compact", Access::write(&["db"])),
+    ("supplier_add", Access::write(&["suppliers"])),
+    ("supplier_list", Access::read(&["suppliers"])),
+    ("search", Access::USER),
+    ("supplier_edit", Access::write(&["suppliers"])),
+    ("supplier_remove", Access::write(&["suppliers"])),
+    ("supplier_link", Access::write(&["suppliers"])),
+    ("supplier_unlink", Access::write(&["suppliers"])),
+    ("category_create", Access::write(&["categories"])),
+    ("category_list", Access::read(&["categories"])),
+    ("category_id", Access::read(&["categories"])),
+    ("category_remove", Access::write(&["categories"])),
+    ("category_assign", Access::write(&["categories"])),
+    ("category_unassign", Access::write(&["categories"])),
+    ("kit_define", Access::write(&["kits"])),
+    ("kit_list", Access::read(&["kits"])),
+    ("kit_get", Access::read(&["kits"])),
+    ("kit_remove", Access::write(&["kits"])),
+    (
+        "kit_pick",
+        Access {
+            writes: true,
+            read: &["kits"],
+            write: &["stock"],
+        },
+    ),
+    ("stocktake_start", Access::write(&["stocktakes"])),
+    ("stocktake_record", Access::write(&["stocktakes"])),
+    ("stocktake_status", Access::read(&["stocktakes"])),
+    ("stocktake_close", Access::write(&["stocktakes", "stock"])),
+    ("stocktake_cancel", Access::write(&["stocktakes"])),
+];
+
+pub(crate) fn operation_access(operation: &str) -> Option<Access> {
+    return OPERATIONS
+        .iter()
+        .find(|(name, _)| *name == operation)
+        .map(|(_, access)| *access);
+}
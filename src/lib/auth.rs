@@ -0,0 +1,543 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::common::args::LdapSettings;
+use anyhow::{anyhow, bail, Result};
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, ServerName},
+    ClientConfig, ClientConnection, RootCertStore, StreamOwned,
+};
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
+
+/**
+ * Connecting to the directory and each of its responses may take at most this long.
+ */
+const LDAP_TIMEOUT_SECONDS: u64 = 10;
+
+/**
+ * The responses read are small, anything larger is most likely not an LDAP server.
+ */
+const LDAP_MAX_MESSAGE_BYTES: usize = 1 << 20;
+
+const STARTTLS_OID: &str = "1.3.6.1.4.1.1466.20037";
+
+const RESULT_INVALID_CREDENTIALS: i64 = 49;
+
+/**
+ * A source other than the users table that verifies passwords, e.g. a company's directory.
+ */
+pub trait AuthProvider {
+    /**
+     * Verifies the password of the user and returns the name of the role it is given.
+     */
+    fn authenticate(&self, username: &str, password: &str) -> Result<String>;
+}
+
+/**
+ * Creates the provider passwords are verified by when the users table has none, if one is
+ * configured.
+ */
+pub fn auth_provider(ldap: Option<&LdapSettings>) -> Option<Box<dyn AuthProvider + '_>> {
+    return ldap.map(|settings| Box::new(LdapProvider::new(settings)) as Box<dyn AuthProvider>);
+}
+
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+/**
+ * Binds as the user to verify its password and reads the groups it is a member of, which are
+ * mapped to a role.
+ */
+pub struct LdapProvider<'a> {
+    settings: &'a LdapSettings,
+}
+
+impl<'a> LdapProvider<'a> {
+    pub fn new(settings: &'a LdapSettings) -> Self {
+        return LdapProvider { settings };
+    }
+
+    /**
+     * Checks the URL and certificates of the settings without connecting.
+     */
+    pub fn check(&self) -> Result<()> {
+        self.address()?;
+        self.tls_config()?;
+        return Ok(());
+    }
+
+    /**
+     * Returns whether TLS is used from the start, and the host and port of the server.
+     */
+    fn address(&self) -> Result<(bool, String, u16)> {
+        let (implicit_tls, rest) = match self.settings.url.split_once("://") {
+            Some(("ldaps", rest)) => (true, rest),
+            Some(("ldap", rest)) => (false, rest),
+            _ => bail!("The URL must start with ldaps:// or ldap://"),
+        };
+        let authority = rest.trim_end_matches('/');
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>()?),
+            None => (authority, if implicit_tls { 636 } else { 389 }),
+        };
+        if host.is_empty() || host.contains('/') {
+            bail!("The URL must be in ldaps://host[:port] notation");
+        }
+        return Ok((implicit_tls, host.into(), port));
+    }
+
+    fn tls_config(&self) -> Result<Arc<ClientConfig>> {
+        let mut roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        if let Some(pem) = &self.settings.ca_certificates {
+            let mut added = 0;
+            for certificate in CertificateDer::pem_slice_iter(pem.as_bytes()) {
+                roots.add(certificate?)?;
+                added += 1;
+            }
+            if added == 0 {
+                bail!("No PEM encoded certificate found in the CA file");
+            }
+        }
+        let config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+        return Ok(Arc::new(config));
+    }
+
+    /**
+     * Opens an encrypted connection, the password is never sent in plain text.
+     */
+    fn connect(&self) -> Result<Box<dyn Stream>> {
+        let (implicit_tls, host, port) = self.address()?;
+        let timeout = Duration::from_secs(LDAP_TIMEOUT_SECONDS);
+        let unreachable =
+            |error: String| anyhow!("Could not reach {} ({})", self.settings.url, error);
+        let mut last_error = "no address found".to_string();
+        let mut connected = None;
+        for address in (host.as_str(), port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => {
+                    connected = Some(stream);
+                    break;
+                }
+                Err(err) => last_error = err.to_string(),
+            }
+        }
+        let Some(mut tcp) = connected else {
+            return Err(unreachable(last_error));
+        };
+        tcp.set_read_timeout(Some(timeout))?;
+        tcp.set_write_timeout(Some(timeout))?;
+        if !implicit_tls {
+            let request = ber(0x77, &ber(0x80, STARTTLS_OID.as_bytes()));
+            send(&mut tcp, 1, &request)?;
+            let (tag, content) = receive(&mut tcp, 1)?;
+            if tag != 0x78 {
+                bail!("LDAP server sent an invalid response to StartTLS");
+            }
+            check_result(&content, "StartTLS")?;
+        }
+        let connection = ClientConnection::new(self.tls_config()?, ServerName::try_from(host)?)?;
+        return Ok(Box::new(StreamOwned::new(connection, tcp)));
+    }
+
+    /**
+     * Returns the distinguished names of the groups the user is directly a member of.
+     */
+    fn groups(&self, username: &str, password: &str) -> Result<Vec<String>> {
+        let mut stream = self.connect()?;
+        let bind_dn = self
+            .settings
+            .bind_dn
+            .replace("{username}", &escape_dn_value(username));
+        let bind = [
+            ber_integer(0x02, 3),
+            ber(0x04, bind_dn.as_bytes()),
+            ber(0x80, password.as_bytes()),
+        ]
+        .concat();
+        send(&mut stream, 2, &ber(0x60, &bind))?;
+        let (tag, content) = receive(&mut stream, 2)?;
+        if tag != 0x61 {
+            bail!("LDAP server sent an invalid response to the bind");
+        }
+        check_result(&content, "bind")?;
+
+        let filter = [
+            ber(0x04, self.settings.user_attribute.as_bytes()),
+            ber(0x04, username.as_bytes()),
+        ]
+        .concat();
+        let search = [
+            ber(0x04, self.settings.base_dn.as_bytes()),
+            // Whole subtree, never dereference aliases, at most 2 entries, 10 seconds
+            ber_integer(0x0a, 2),
+            ber_integer(0x0a, 0),
+            ber_integer(0x02, 2),
+            ber_integer(0x02, LDAP_TIMEOUT_SECONDS as u32),
+            ber(0x01, &[0]),
+            ber(0xa3, &filter),
+            ber(0x30, &ber(0x04, b"memberOf")),
+        ]
+        .concat();
+        send(&mut stream, 3, &ber(0x63, &search))?;
+        let mut entries = 0;
+        let mut groups = vec![];
+        loop {
+            let (tag, content) = receive(&mut stream, 3)?;
+            match tag {
+                // Entry
+                0x64 => {
+                    entries += 1;
+                    let entry = elements(&content)?;
+                    let Some((0x30, attributes)) = entry.get(1) else {
+                        bail!("LDAP server sent an invalid search entry");
+                    };
+                    for (_, attribute) in elements(attributes)? {
+                        let attribute = elements(&attribute)?;
+                        match attribute.as_slice() {
+                            [(0x04, name), (0x31, values)]
+                                if name.eq_ignore_ascii_case(b"memberOf") =>
+                            {
+                                for (_, value) in elements(values)? {
+                                    groups.push(String::from_utf8_lossy(&value).into_owned());
+                                }
+                            }
+                            _ => continue,
+                        }
+                    }
+                }
+                // Reference to another server, which is not followed
+                0x73 => continue,
+                // Done
+                0x65 => {
+                    check_result(&content, "search")?;
+                    break;
+                }
+                _ => bail!("LDAP server sent an invalid response to the search"),
+            }
+        }
+        if entries > 1 {
+            bail!(
+                "More than one entry has {} {} in the directory",
+                self.settings.user_attribute,
+                username
+            );
+        }
+        // Unbinding is only polite, the server closes the connection either way
+        let _ = send(&mut stream, 4, &ber(0x42, &[]));
+        return Ok(groups);
+    }
+}
+
+impl AuthProvider for LdapProvider<'_> {
+    fn authenticate(&self, username: &str, password: &str) -> Result<String> {
+        // A simple bind without a password is anonymous and would succeed for anyone
+        if password.is_empty() {
+            bail!("Either username or password is incorrect");
+        }
+        if username.is_empty() {
+            bail!("Either username or password is incorrect");
+        }
+        let groups = self.groups(username, password)?;
+        for mapping in &self.settings.group_roles {
+            if groups.iter().any(|e| group_matches(e, &mapping.group)) {
+                return Ok(mapping.role.clone());
+            }
+        }
+        return match &self.settings.default_role {
+            Some(role) => Ok(role.clone()),
+            None => bail!(
+                "User {} is in none of the groups mapped to a role",
+                username
+            ),
+        };
+    }
+}
+
+/**
+ * Groups are given either by their DN or only by their common name.
+ */
+fn group_matches(dn: &str, group: &str) -> bool {
+    if dn.eq_ignore_ascii_case(group) {
+        return true;
+    }
+    let first = dn.split(',').next().unwrap_or_default();
+    return first
+        .split_once('=')
+        .is_some_and(|(_, name)| name.trim().eq_ignore_ascii_case(group.trim()));
+}
+
+/**
+ * Escapes the value of an attribute of a DN (RFC 4514), so that a username put into the bind
+ * DN cannot point to another entry.
+ */
+fn escape_dn_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let last = value.chars().count().saturating_sub(1);
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            ',' | '+' | '"' | '\\' | '<' | '>' | ';' | '=' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '#' if i == 0 => escaped.push_str("\\#"),
+            ' ' if i == 0 || i == last => escaped.push_str("\\ "),
+            // Control characters are escaped by their hex value, which also covers NUL
+            c if c.is_ascii_control() => escaped.push_str(&format!("\\{:02x}", c as u8)),
+            c => escaped.push(c),
+        }
+    }
+    return escaped;
+}
+
+/**
+ * Encodes an element with the definite length form of BER.
+ */
+fn ber(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    if content.len() < 0x80 {
+        encoded.push(content.len() as u8);
+    } else {
+        let length = (content.len() as u32).to_be_bytes();
+        let skip = length.iter().take_while(|e| **e == 0).count();
+        encoded.push(0x80 | (length.len() - skip) as u8);
+        encoded.extend(&length[skip..]);
+    }
+    encoded.extend(content);
+    return encoded;
+}
+
+fn ber_integer(tag: u8, value: u32) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|e| **e == 0).count().min(3);
+    let mut content = bytes[skip..].to_vec();
+    // The leading bit is the sign
+    if content[0] & 0x80 != 0 {
+        content.insert(0, 0);
+    }
+    return ber(tag, &content);
+}
+
+fn integer(bytes: &[u8]) -> i64 {
+    let initial = if bytes.first().is_some_and(|e| e & 0x80 != 0) {
+        -1
+    } else {
+        0
+    };
+    return bytes.iter().fold(initial, |acc, e| (acc << 8) | *e as i64);
+}
+
+/**
+ * Reads one element and returns its tag and content.
+ */
+fn read_element(reader: &mut dyn Read) -> Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header)?;
+    let length = if header[1] & 0x80 == 0 {
+        header[1] as usize
+    } else {
+        let count = (header[1] & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            bail!("LDAP server sent an invalid length");
+        }
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes[4 - count..])?;
+        u32::from_be_bytes(bytes) as usize
+    };
+    if length > LDAP_MAX_MESSAGE_BYTES {
+        bail!(
+            "LDAP server sent a message larger than {} bytes",
+            LDAP_MAX_MESSAGE_BYTES
+        );
+    }
+    let mut content = vec![0; length];
+    reader.read_exact(&mut content)?;
+    return Ok((header[0], content));
+}
+
+/**
+ * Splits the content of a sequence or set into its elements.
+ */
+fn elements(mut content: &[u8]) -> Result<Vec<(u8, Vec<u8>)>> {
+    let mut elements = vec![];
+    while !content.is_empty() {
+        elements.push(read_element(&mut content)?);
+    }
+    return Ok(elements);
+}
+
+fn send(writer: &mut dyn Write, message_id: u32, operation: &[u8]) -> Result<()> {
+    let message = [ber_integer(0x02, message_id), operation.to_vec()].concat();
+    writer.write_all(&ber(0x30, &message))?;
+    writer.flush()?;
+    return Ok(());
+}
+
+/**
+ * Reads the response to the message and returns the tag and content of its operation.
+ */
+fn receive(reader: &mut dyn Read, message_id: u32) -> Result<(u8, Vec<u8>)> {
+    let (tag, content) = read_element(reader)?;
+    let mut message = elements(&content)?.into_iter();
+    match (tag, message.next(), message.next()) {
+        (0x30, Some((0x02, id)), Some(operation)) if integer(&id) == message_id as i64 => {
+            return Ok(operation);
+        }
+        // The server announces that it closes the connection with the ID 0
+        (0x30, Some((0x02, id)), Some(_)) if integer(&id) == 0 => {
+            bail!("LDAP server closed the connection")
+        }
+        _ => bail!("LDAP server sent an invalid response"),
+    }
+}
+
+/**
+ * Fails unless the LDAPResult reports success.
+ */
+fn check_result(content: &[u8], operation: &str) -> Result<()> {
+    let result = elements(content)?;
+    let code = match result.first() {
+        Some((0x0a, code)) => integer(code),
+        _ => bail!("LDAP server sent an invalid response to the {}", operation),
+    };
+    if code == 0 {
+        return Ok(());
+    }
+    if code == RESULT_INVALID_CREDENTIALS {
+        bail!("Either username or password is incorrect");
+    }
+    let message = result
+        .get(2)
+        .map(|e| String::from_utf8_lossy(&e.1).into_owned())
+        .unwrap_or_default();
+    bail!(
+        "LDAP {} failed with result code {} {}",
+        operation,
+        code,
+        message
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usernames_are_escaped_within_the_bind_dn() {
+        assert_eq!(escape_dn_value("jdoe"), "jdoe");
+        assert_eq!(escape_dn_value("admin,ou=admins"), "admin\\,ou\\=admins");
+        assert_eq!(
+            escape_dn_value(r#"a+b"c\d<e>f;g"#),
+            r#"a\+b\"c\\d\<e\>f\;g"#
+        );
+        assert_eq!(escape_dn_value("#x#"), "\\#x#");
+        assert_eq!(escape_dn_value(" x y "), "\\ x y\\ ");
+        assert_eq!(escape_dn_value("x\0\n"), "x\\00\\0a");
+        assert_eq!(escape_dn_value("Jürgen"), "Jürgen");
+    }
+
+    #[test]
+    fn ber_encodes_short_and_long_lengths() {
+        assert_eq!(ber(0x04, b"abc"), vec![0x04, 3, b'a', b'b', b'c']);
+        assert_eq!(ber(0x04, &[0; 200])[..3], [0x04, 0x81, 200]);
+        assert_eq!(ber(0x04, &[0; 300])[..4], [0x04, 0x82, 0x01, 0x2c]);
+        assert_eq!(ber(0x04, &[0; 300]).len(), 304);
+    }
+
+    #[test]
+    fn ber_integers_keep_their_sign_bit_clear() {
+        assert_eq!(ber_integer(0x02, 0), vec![0x02, 1, 0]);
+        assert_eq!(ber_integer(0x02, 127), vec![0x02, 1, 0x7f]);
+        assert_eq!(ber_integer(0x02, 128), vec![0x02, 2, 0, 0x80]);
+        assert_eq!(ber_integer(0x0a, 300), vec![0x0a, 2, 0x01, 0x2c]);
+        assert_eq!(integer(&[0, 0x80]), 128);
+        assert_eq!(integer(&[0xff]), -1);
+        assert_eq!(integer(&[0x01, 0x2c]), 300);
+    }
+
+    #[test]
+    fn elements_are_parsed_as_encoded() {
+        let encoded = [ber(0x04, b"a"), ber(0x30, &[7; 300]), ber_integer(0x02, 5)].concat();
+        let parsed = elements(&encoded).unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0], (0x04, b"a".to_vec()));
+        assert_eq!(parsed[1], (0x30, vec![7; 300]));
+        assert_eq!(integer(&parsed[2].1), 5);
+    }
+
+    #[test]
+    fn invalid_lengths_are_rejected() {
+        // Indefinite length, more than 4 length bytes and content shorter than its length
+        assert!(elements(&[0x04, 0x80, 0, 0]).is_err());
+        assert!(elements(&[0x04, 0x85, 1, 0, 0, 0, 0]).is_err());
+        assert!(elements(&[0x04, 5, 1, 2]).is_err());
+        let too_large = (LDAP_MAX_MESSAGE_BYTES as u32 + 1).to_be_bytes();
+        assert!(elements(&[&[0x04, 0x84][..], &too_large].concat()).is_err());
+    }
+
+    #[test]
+    fn responses_are_matched_by_their_message_id() {
+        let mut sent = vec![];
+        send(&mut sent, 2, &ber(0x42, &[])).unwrap();
+        assert_eq!(sent, vec![0x30, 5, 0x02, 1, 2, 0x42, 0]);
+        let response = [ber_integer(0x02, 2), ber(0x61, &[1])].concat();
+        assert_eq!(
+            receive(&mut &ber(0x30, &response)[..], 2).unwrap(),
+            (0x61, vec![1])
+        );
+        assert!(receive(&mut &ber(0x30, &response)[..], 3).is_err());
+        let notice = [ber_integer(0x02, 0), ber(0x78, &[])].concat();
+        let error = receive(&mut &ber(0x30, &notice)[..], 2).unwrap_err();
+        assert!(error.to_string().contains("closed"));
+    }
+
+    #[test]
+    fn results_report_their_code() {
+        let result = |code: u32, message: &str| {
+            return [
+                ber_integer(0x0a, code),
+                ber(0x04, b""),
+                ber(0x04, message.as_bytes()),
+            ]
+            .concat();
+        };
+        assert!(check_result(&result(0, ""), "bind").is_ok());
+        let error = check_result(&result(49, ""), "bind").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Either username or password is incorrect"
+        );
+        let error = check_result(&result(32, "no such object"), "search").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "LDAP search failed with result code 32 no such object"
+        );
+    }
+}
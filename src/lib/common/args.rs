@@ -1,13 +1,56 @@
 use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Datelike, Local, Months, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
 use core::fmt;
+use croner::Cron;
+use flate2::{write::GzEncoder, Compression};
+use ring::rand::SecureRandom;
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    io::{self, IsTerminal, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::Path,
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
 
+pub use crate::filter::{Grouping, WhereExpr};
+pub use crate::import::ImportFormat;
+pub use crate::progress::{init_progress, ProgressOutput};
+pub use crate::utils::split_command_line;
 use crate::{
+    access::{
+        operation_access, require_column, require_read, require_read_collection,
+        require_read_filter, require_write, require_write_collection, Access,
+    },
+    auth::LdapProvider,
     database::{
-        AppConfig, DBUser, InvManDBPool, KeyValueCollection, KeyValueTypeEntry, SchemaCollection,
+        event_action_no, AppConfig, DBDump, DBFixture, DBLedgerEntry, DBMetrics, DBNotification,
+        DBQueryPlan, DBUser, InvManConnection, InvManDBPool, KeyValueCollection, KeyValueTypeEntry,
+        PermissionMode, SchemaCollection, DUMP_FORMAT, DUMP_VERSION, EVENT_NAMES, PASSWORD_EXPIRED,
+    },
+    document::{Chart, ChartKind, ChartPoint, Dashboard, Label, ReportDocument},
+    graphql::Schema,
+    import,
+    notify::{notify_channel, send_email, NotifyCondition},
+    plugins,
+    progress::Progress,
+    server,
+    utils::{
+        base32_encode, parse_days, prepare_raw_sql, read_decompressed, resize_image,
+        string_similarity, verify_checksum, write_checksum, InvManSerialization,
     },
-    utils::InvManSerialization,
+    yaml,
 };
+use invman_core::parse_currency;
+pub use invman_core::{ColumnType, ContextDefault, ExchangeRates, Money, SchemaDeclaration};
 
 pub trait InvManNotationHelper {
     fn to_typed_key_value_entry(
@@ -32,7 +75,6 @@ impl InvManNotationHelperVec for Vec<String> {
             collection: self
                 .iter()
                 .map(|e| e.to_typed_key_value_entry(declarations))
-                .into_iter()
                 .collect::<Result<Vec<_>>>()?,
         });
     }
@@ -47,9 +89,13 @@ impl InvManNotationHelper for String {
             None => Err(anyhow!("Could not split parsed parameter")),
             Some(val) => {
                 if let Some(decl) = declarations.collection.iter().find(|e| e.name == val.0) {
+                    let value = match decl.column_type {
+                        ColumnType::MONEY => Money::parse(val.1)?.to_string(),
+                        _ => val.1.to_string(),
+                    };
                     Ok(KeyValueTypeEntry::new(
                         val.0.to_string(),
-                        Some(val.1.to_string()),
+                        Some(value),
                         decl.column_type,
                     ))
                 } else {
@@ -60,6 +106,201 @@ impl InvManNotationHelper for String {
     }
 }
 
+/**
+ * Columns holding a timestamp in UTC, which the plain output displays in the configured timezone
+ */
+const TIMESTAMP_COLUMNS: [&str; 4] = ["created_at", "updated_at", "deleted_at", "last_modified_at"];
+
+/**
+ * Parses the IANA name of a timezone, e.g. Europe/Berlin, an empty name being UTC.
+ */
+pub fn parse_timezone(name: &str) -> Result<Tz> {
+    if name.is_empty() {
+        return Ok(Tz::UTC);
+    }
+    return match name.parse::<Tz>() {
+        Ok(timezone) => Ok(timezone),
+        Err(_) => bail!(
+            "Timezone '{}' is unknown, use an IANA name like Europe/Berlin",
+            name
+        ),
+    };
+}
+
+/**
+ * How the plain output formats numbers and timestamps, e.g. to match the conventions of a locale
+ */
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DisplayFormat {
+    pub decimal_separator: String,
+    /**
+     * Separates groups of three digits, empty for no grouping
+     */
+    pub thousands_separator: String,
+    /**
+     * Format of timestamps in strftime notation
+     */
+    pub date_format: String,
+}
+
+impl Default for DisplayFormat {
+    fn default() -> Self {
+        return DisplayFormat {
+            decimal_separator: ".".into(),
+            thousands_separator: "".into(),
+            date_format: "%Y-%m-%d %H:%M:%S %Z".into(),
+        };
+    }
+}
+
+impl DisplayFormat {
+    /**
+     * Converts a timestamp stored in UTC into the given timezone, leaving values that are no
+     * timestamp as they are. Only displaying converts, the stored values stay in UTC.
+     */
+    fn timestamp(&self, value: &str, timezone: &Tz) -> String {
+        let time = match NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f") {
+            Ok(time) => time.and_utc().with_timezone(timezone),
+            Err(_) => return value.to_string(),
+        };
+        // Unlike to_string, writing reports invalid formats instead of panicking
+        use std::fmt::Write as _;
+        let mut formatted = String::new();
+        return match write!(formatted, "{}", time.format(&self.date_format)) {
+            Ok(_) => formatted,
+            Err(_) => value.to_string(),
+        };
+    }
+
+    /**
+     * Formats a number with the separators, leaving values that are no number as they are.
+     */
+    fn number(&self, value: &str) -> String {
+        if value.parse::<f64>().is_err() || value.contains(['e', 'E', 'i', 'n', 'N']) {
+            return value.to_string();
+        }
+        let (sign, value) = match value.strip_prefix('-') {
+            Some(value) => ("-", value),
+            None => ("", value),
+        };
+        let (integer, fraction) = match value.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (value, None),
+        };
+        let mut grouped = String::new();
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                grouped.push_str(&self.thousands_separator);
+            }
+            grouped.push(digit);
+        }
+        return match fraction {
+            Some(fraction) => format!("{}{}{}{}", sign, grouped, self.decimal_separator, fraction),
+            None => format!("{}{}", sign, grouped),
+        };
+    }
+}
+
+pub trait InvManPlainTable {
+    /**
+     * Formats the values of the entries for display, timestamps being displayed in the given
+     * timezone.
+     *
+     * @returns The column names and the rows of formatted values
+     */
+    fn to_display_rows(
+        &self,
+        format: &DisplayFormat,
+        timezone: &Tz,
+    ) -> (Vec<String>, Vec<Vec<String>>);
+
+    /**
+     * Formats the entries as a table with a header line and aligned columns, timestamps being
+     * displayed in the given timezone.
+     */
+    fn to_plain_table(&self, format: &DisplayFormat, timezone: &Tz) -> String;
+}
+
+impl InvManPlainTable for Vec<KeyValueCollection> {
+    fn to_display_rows(
+        &self,
+        format: &DisplayFormat,
+        timezone: &Tz,
+    ) -> (Vec<String>, Vec<Vec<String>>) {
+        let header = match self.first() {
+            Some(first) => first
+                .collection
+                .iter()
+                .map(|e| e.key.clone())
+                .collect::<Vec<String>>(),
+            None => return (vec![], vec![]),
+        };
+        let rows = self
+            .iter()
+            .map(|entry| {
+                entry
+                    .collection
+                    .iter()
+                    .map(|e| match e.to_plain_value() {
+                        Some(value) if TIMESTAMP_COLUMNS.contains(&e.key.as_str()) => {
+                            format.timestamp(&value, timezone)
+                        }
+                        Some(value) if e.column_type == ColumnType::MONEY => {
+                            match value.split_once(' ') {
+                                Some((amount, currency)) => {
+                                    format!("{} {}", format.number(amount), currency)
+                                }
+                                None => value,
+                            }
+                        }
+                        // Identifiers are no quantities and thus not grouped
+                        Some(value)
+                            if matches!(e.column_type, ColumnType::INT | ColumnType::REAL)
+                                && !["id", "revision", "attachments"].contains(&e.key.as_str()) =>
+                        {
+                            format.number(&value)
+                        }
+                        Some(value) => value,
+                        None => "".into(),
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .collect::<Vec<Vec<String>>>();
+        return (header, rows);
+    }
+
+    fn to_plain_table(&self, format: &DisplayFormat, timezone: &Tz) -> String {
+        let (header, rows) = self.to_display_rows(format, timezone);
+        if header.is_empty() {
+            return "".into();
+        }
+        let widths = header
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                rows.iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|e| e.chars().count())
+                    .fold(name.chars().count(), usize::max)
+            })
+            .collect::<Vec<usize>>();
+        return std::iter::once(&header)
+            .chain(rows.iter())
+            .map(|row| {
+                row.iter()
+                    .zip(widths.iter())
+                    .map(|(value, width)| format!("{:width$}", value, width = width))
+                    .collect::<Vec<String>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+    }
+}
+
 impl InvManSerialization for Vec<KeyValueCollection> {
     fn to_json(&self) -> String {
         let mut jsons = self
@@ -77,11 +318,162 @@ pub struct CommandContext<'a> {
     pub db: &'a mut dyn InvManDBPool,
     pub config: &'a mut AppConfig,
     pub auth: Option<String>,
+    /**
+     * One-time password of a user with two-factor authentication, or one of the user's
+     * recovery codes
+     */
+    pub otp: Option<String>,
     pub output: OutputType,
+    /**
+     * Rejects every command that would change the database
+     */
+    pub read_only: bool,
+    /**
+     * User of a session, e.g. of the shell, whose credentials are not checked again
+     */
+    pub session: Option<DBUser>,
 }
 
 impl<'a> CommandContext<'a> {
-    fn authenticate(&self) -> Result<DBUser> {
+    /**
+     * Context for the commands on the database, which is read-only if its config says so. Only
+     * main creates its own, where the command lifting the read-only option ignores it.
+     */
+    pub fn new(
+        db: &'a mut dyn InvManDBPool,
+        config: &'a mut AppConfig,
+        auth: Option<String>,
+        output: OutputType,
+    ) -> CommandContext<'a> {
+        let read_only = config.read_only;
+        return CommandContext {
+            db,
+            config,
+            auth,
+            otp: None,
+            output,
+            read_only,
+            session: None,
+        };
+    }
+
+    /**
+     * Authenticates the user once for all following commands.
+     */
+    pub fn start_session(&mut self) -> Result<()> {
+        self.session = Some(self.authenticate_expired()?);
+        return Ok(());
+    }
+
+    pub fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            bail!("Database is opened in read-only mode");
+        }
+        return Ok(());
+    }
+
+    /**
+     * Checks that the user may change the entity according to the ownership policy of the
+     * user's role.
+     */
+    pub fn check_owner(&self, user: &DBUser, identifier: &str) -> Result<()> {
+        if user.ownership == OwnershipPolicy::Own
+            && self.db.inventory_owner(identifier)? != Some(user.id)
+        {
+            bail!(
+                "Entity {} was added by another user, your role may only change its own entities",
+                identifier
+            );
+        }
+        return Ok(());
+    }
+
+    /**
+     * Checks that the token the user logged in with has a scope allowing the area at the
+     * given level. Logging in with username and password is not restricted by scopes.
+     */
+    pub fn check_scope(&self, area: &str, level: ScopeLevel) -> Result<()> {
+        if self.auth.as_deref().is_none_or(|e| e.contains(':')) {
+            return Ok(());
+        }
+        let user = self.authenticate()?;
+        if user.scopes.is_empty() {
+            return Ok(());
+        }
+        for scope in &user.scopes {
+            let (scope_area, scope_level) = parse_scope(scope)?;
+            if (scope_area == "*" || scope_area == area) && scope_level >= level {
+                return Ok(());
+            }
+        }
+        bail!(
+            "Token lacks the scope {}:{} required by this command",
+            area,
+            level
+        );
+    }
+
+    /**
+     * Runs the command only to find out the queries it reads with, and returns their plans
+     * instead of its output. The user is authenticated beforehand, so that the queries of the
+     * authentication are left out.
+     */
+    pub fn explain(
+        &mut self,
+        command: impl FnOnce(&CommandContext) -> Result<String>,
+    ) -> Result<String> {
+        let session = self.session.is_some();
+        if !session {
+            self.session = Some(self.authenticate()?);
+        }
+        self.db.explain_start();
+        let result = command(self);
+        let plans = self.db.explain_finish();
+        if !session {
+            self.session = None;
+        }
+        result?;
+        return format_query_plans(&plans?, self.output);
+    }
+
+    pub(crate) fn authenticate(&self) -> Result<DBUser> {
+        let user = self.authenticate_expired()?;
+        if user.password_expired {
+            bail!("User authentication failure ({})", PASSWORD_EXPIRED);
+        }
+        return Ok(user);
+    }
+
+    /**
+     * Authenticates the user and checks that it may run the operation as a request to a server
+     * would, see access.rs.
+     */
+    pub(crate) fn authorize(&self, operation: &str) -> Result<DBUser> {
+        let access = match operation_access(operation) {
+            Some(access) => access,
+            None => bail!("Operation {} has no access declared", operation),
+        };
+        return self.authorize_access(access);
+    }
+
+    /**
+     * Authenticates the user and checks the access of a command that is made of several
+     * operations or not offered by a server.
+     */
+    pub(crate) fn authorize_access(&self, access: Access) -> Result<DBUser> {
+        let user = self.authenticate()?;
+        access.check(self, &user)?;
+        return Ok(user);
+    }
+
+    /**
+     * Authenticates the user even if its password expired, which only changing the password
+     * accepts.
+     */
+    pub(crate) fn authenticate_expired(&self) -> Result<DBUser> {
+        if let Some(user) = &self.session {
+            return Ok(user.clone());
+        }
         let auth = self.auth.clone().unwrap_or("".into());
         if auth.is_empty() {
             bail!("User authentication failure (No auth token was provided)");
@@ -89,57 +481,234 @@ impl<'a> CommandContext<'a> {
         let mut user = DBUser::default();
 
         return match auth.split_once(":") {
-            Some(s) => match self.db.user_auth(s.0, s.1, &mut user) {
-                Ok(_) => Ok(user),
-                Err(e) => bail!("User authentication failure ({})", e.to_string()),
+            Some(s) => match self.db.user_auth(s.0, s.1, self.otp.as_deref(), &mut user) {
+                Ok(_) => {
+                    tracing::info!(user = s.0, id = user.id, "User authenticated");
+                    user.read_only = self.read_only;
+                    self.db.scope_to_org(user.org_id);
+                    Ok(user)
+                }
+                Err(e) => {
+                    tracing::warn!(user = s.0, error = %e, "User authentication failed");
+                    bail!("User authentication failure ({})", e)
+                }
+            },
+            // Without a password, the token is one issued by user token issue
+            None => match self.db.token_auth(&auth, &mut user) {
+                Ok(_) => {
+                    tracing::info!(
+                        user = user.username,
+                        id = user.id,
+                        "User authenticated by token"
+                    );
+                    user.read_only = self.read_only;
+                    self.db.scope_to_org(user.org_id);
+                    Ok(user)
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "User authentication by token failed");
+                    bail!("User authentication failure ({})", e)
+                }
             },
-            None => bail!("User authentication failure (Failed to split the token)"),
         };
     }
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
-pub enum ColumnType {
-    #[default]
-    TEXT,
-    VARCHAR,
-    INT,
-    REAL,
-    BOOL,
-}
-
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum OutputType {
     Plain,
     Json,
+    Ndjson,
+    /**
+     * Only supported by reports, which write it into a file
+     */
+    Pdf,
 }
 
 pub struct InventoryRemoveArgs {
     pub identifier: String,
+    /**
+     * One of the configured reason codes
+     */
+    pub reason: Option<String>,
 }
 
 impl InventoryRemoveArgs {
     pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
-        let user = ctx.authenticate()?;
+        let user = ctx.authorize("inventory_remove")?;
+        ctx.check_owner(&user, &self.identifier)?;
         ctx.db
-            .inventory_remove(&self.identifier, &ctx.config, &user)
+            .inventory_remove(&self.identifier, self.reason.as_deref(), ctx.config, &user)
+    }
+}
+
+pub struct InventoryMergeArgs {
+    pub keep: String,
+    pub duplicate: String,
+}
+
+impl InventoryMergeArgs {
+    pub fn merge(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("inventory_merge")?;
+        ctx.check_owner(&user, &self.keep)?;
+        ctx.check_owner(&user, &self.duplicate)?;
+        return ctx
+            .db
+            .inventory_merge(&self.keep, &self.duplicate, ctx.config, &user);
+    }
+}
+
+#[derive(Serialize)]
+struct DuplicateGroup {
+    ids: Vec<u32>,
+    values: Vec<String>,
+}
+
+pub struct InventoryDedupeArgs {
+    /**
+     * Column whose values are compared
+     */
+    pub by: String,
+    /**
+     * Also considers values as duplicates that are similar, not only equal
+     */
+    pub fuzzy: bool,
+    pub threshold: f64,
+}
+
+impl InventoryDedupeArgs {
+    pub fn dedupe(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authenticate()?;
+        if !ctx
+            .config
+            .inventory_schema_declaration
+            .collection
+            .iter()
+            .any(|e| e.name == self.by)
+        {
+            bail!("Could not find '{}' in table schema", self.by);
+        }
+        if user.is_read_denied("inventory", &self.by) {
+            bail!("Cannot read column {} of inventory", self.by);
+        }
+        if !(0.0..=1.0).contains(&self.threshold) {
+            bail!("Fuzzy threshold must be between 0 and 1");
+        }
+        let props = InventoryListProps {
+            limit: -1,
+            after_id: None,
+            fuzzy: None,
+            fuzzy_threshold: 0.0,
+            with_audit: false,
+            with_attachments: false,
+            include_archived: false,
+            supplier: None,
+            assigned_to: None,
+            category: None,
+            recursive: false,
+            raw: &None,
+            params: &vec![],
+            changed_since: None,
+            filter: None,
+            group: None,
+            expand: vec![],
+        };
+        // Values are compared regardless of case and surrounding or repeated whitespace
+        let mut entities = vec![];
+        for entry in ctx.db.inventory_list(&props, ctx.config)? {
+            let removed = entry
+                .collection
+                .iter()
+                .any(|e| e.key == "deleted_at" && e.to_plain_value().is_some());
+            if removed {
+                continue;
+            }
+            let value = entry
+                .collection
+                .iter()
+                .find(|e| e.key == self.by)
+                .and_then(|e| e.to_plain_value())
+                .unwrap_or_default();
+            let normalized = value
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+                .to_lowercase();
+            if !normalized.is_empty() {
+                entities.push((entry.get_id()?.parse::<u32>()?, value, normalized));
+            }
+        }
+
+        // Groups are formed transitively, each entity points towards the first one of its group
+        let mut group = (0..entities.len()).collect::<Vec<usize>>();
+        let root = |group: &Vec<usize>, mut i: usize| {
+            while group[i] != i {
+                i = group[i];
+            }
+            i
+        };
+        for i in 0..entities.len() {
+            for j in (i + 1)..entities.len() {
+                let duplicate = match self.fuzzy {
+                    true => string_similarity(&entities[i].2, &entities[j].2) >= self.threshold,
+                    false => entities[i].2 == entities[j].2,
+                };
+                if duplicate {
+                    let (a, b) = (root(&group, i), root(&group, j));
+                    group[a.max(b)] = a.min(b);
+                }
+            }
+        }
+        let mut groups: BTreeMap<usize, DuplicateGroup> = BTreeMap::new();
+        for (i, (id, value, _)) in entities.iter().enumerate() {
+            let duplicates = groups.entry(root(&group, i)).or_insert(DuplicateGroup {
+                ids: vec![],
+                values: vec![],
+            });
+            duplicates.ids.push(*id);
+            duplicates.values.push(value.clone());
+        }
+        let groups = groups
+            .into_values()
+            .filter(|e| e.ids.len() > 1)
+            .collect::<Vec<DuplicateGroup>>();
+        return Ok(serde_json::to_string(&groups)?);
+    }
+}
+
+pub struct InventoryArchiveArgs {
+    pub condition: String,
+}
+
+impl InventoryArchiveArgs {
+    pub fn archive(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("inventory_archive")?;
+        let filter = WhereExpr::parse(&self.condition)?;
+        require_read_filter(&user, ctx.config, &filter)?;
+        return ctx.db.inventory_archive(&filter, ctx.config, &user);
     }
 }
 
 pub struct InventoryEditArgs {
     pub identifier: String,
     pub set: Vec<String>,
+    pub expect_version: Option<String>,
 }
 
 impl InventoryEditArgs {
     pub fn edit(&self, ctx: &mut CommandContext) -> Result<String> {
-        let user = ctx.authenticate()?;
+        let user = ctx.authorize("inventory_edit")?;
+        ctx.check_owner(&user, &self.identifier)?;
+        plugins::validate_entity(&self.set)?;
+        let params = self
+            .set
+            .to_key_value_collection(&ctx.config.inventory_schema_declaration)?;
+        require_write_collection(&user, ctx.config, &params)?;
         ctx.db.inventory_edit(
             &self.identifier,
-            &self
-                .set
-                .to_key_value_collection(&ctx.config.inventory_schema_declaration)?,
-            &ctx.config,
+            &params,
+            self.expect_version.as_deref(),
+            ctx.config,
             &user,
         )
     }
@@ -147,196 +716,1020 @@ impl InventoryEditArgs {
 
 pub struct InventoryListArgs {
     pub limit: Option<i32>,
+    pub after_id: Option<i64>,
+    pub fuzzy: Option<String>,
+    pub fuzzy_threshold: f64,
+    pub with_audit: bool,
+    pub with_attachments: bool,
+    pub include_archived: bool,
+    pub supplier: Option<u32>,
+    pub assigned_to: Option<String>,
+    /**
+     * Path of the category the entities are assigned to
+     */
+    pub category: Option<String>,
+    /**
+     * Also lists the entities of all descendants of the category
+     */
+    pub recursive: bool,
     pub sort: Vec<String>,
     pub raw: Option<String>,
     pub params: Vec<String>,
+    /**
+     * Expression like quantity >= 10 and (location ~ 'A%' or price is null)
+     */
+    pub filter: Option<String>,
+    /**
+     * Columns the entities are grouped by, returning a row per group instead
+     */
+    pub group_by: Vec<String>,
+    /**
+     * Aggregates of every group like sum(quantity),avg(price), count(*) if not given
+     */
+    pub aggregate: Option<String>,
+    pub expand: Vec<Expansion>,
     pub condition: Vec<String>,
+    /**
+     * Returns the plan of the query instead of executing it
+     */
+    pub explain: bool,
 }
 
+#[derive(Serialize)]
 pub struct InventoryListProps<'a> {
     pub limit: i32,
+    pub after_id: Option<i64>,
+    pub fuzzy: Option<KeyValueTypeEntry>,
+    pub fuzzy_threshold: f64,
+    pub with_audit: bool,
+    pub with_attachments: bool,
+    pub include_archived: bool,
+    pub supplier: Option<u32>,
+    pub assigned_to: Option<u32>,
+    pub category: Option<u32>,
+    pub recursive: bool,
     pub raw: &'a Option<String>,
     pub params: &'a Vec<String>,
+    /**
+     * Only lists entities changed since the marker, together with the kind of their change
+     */
+    pub changed_since: Option<ChangeMarker>,
+    /**
+     * Parsed --where expression the entities have to match
+     */
+    pub filter: Option<WhereExpr>,
+    /**
+     * Returns one row per group with its aggregates instead of the entities
+     */
+    pub group: Option<Grouping>,
+    /**
+     * Related records embedded into every entity as JSON
+     */
+    pub expand: Vec<Expansion>,
 }
 
-impl InventoryListArgs {
-    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
-        let _ = ctx.authenticate()?;
-        let props = InventoryListProps {
-            limit: self.limit.unwrap_or(-1),
-            raw: &self.raw,
-            params: &self.params,
-        };
-        let data = ctx.db.inventory_list(&props, &ctx.config)?;
-        return Ok(data.to_json());
-    }
+/**
+ * Point in the history of the inventory that changes are looked up from
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ChangeMarker {
+    /**
+     * Changes recorded after the transaction of the given id
+     */
+    Tx(u32),
+    /**
+     * Changes recorded at or after the given time, in %Y-%m-%d %H:%M:%S notation
+     */
+    Time(String),
 }
 
-pub struct InventorySchemaListArgs;
+/**
+ * Related records that can be embedded into the listed entities instead of being looked up
+ * entity by entity
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Expansion {
+    /**
+     * Linked suppliers as array of objects with id, name, contact and lead_time_days
+     */
+    Suppliers,
+    /**
+     * Stock on hand as array of objects with location and quantity
+     */
+    Locations,
+    /**
+     * Assigned category as object with id, name and path, or null
+     */
+    Category,
+    /**
+     * Current assignment as object with user_id, username and assigned_at, or null
+     */
+    Assignment,
+    /**
+     * Metadata as object of the keys and their values, embedded into the JSON output by default
+     */
+    Meta,
+}
 
-impl InventorySchemaListArgs {
-    pub fn schema_list(&self, ctx: &CommandContext) -> Result<String> {
-        let user = ctx.authenticate()?;
-        if !user.can_read_table("config") {
-            bail!("Cannot read the config table");
-        }
-        return Ok(ctx.config.inventory_schema_declaration.to_json());
+impl Expansion {
+    /**
+     * Key of the embedded records in the entity
+     */
+    pub fn key(&self) -> &'static str {
+        return match self {
+            Expansion::Suppliers => "suppliers",
+            Expansion::Locations => "locations",
+            Expansion::Category => "category",
+            Expansion::Assignment => "assignment",
+            Expansion::Meta => "meta",
+        };
     }
-}
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
-pub struct SchemaDeclaration {
-    pub name: String,
-    pub display_name: String,
-    pub unique: bool,
-    pub max_length: u32,
-    pub min_length: u32,
-    pub max: u32,
-    pub min: u32,
-    pub nullable: bool,
-    pub column_type: ColumnType,
-    pub default: String,
-    pub hint: String,
-    pub layout: String,
+    /**
+     * Table the user must be allowed to read
+     */
+    pub fn table(&self) -> &'static str {
+        return match self {
+            Expansion::Suppliers => "suppliers",
+            Expansion::Locations => "stock",
+            Expansion::Category => "categories",
+            Expansion::Assignment => "assignments",
+            Expansion::Meta => "meta",
+        };
+    }
 }
 
-impl fmt::Display for ColumnType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            ColumnType::BOOL => write!(f, "bool"),
-            ColumnType::INT => write!(f, "int"),
-            ColumnType::REAL => write!(f, "real"),
-            ColumnType::TEXT => write!(f, "text"),
-            ColumnType::VARCHAR => write!(f, "varchar"),
-        }
+impl InventoryListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        return self.list_into(ctx, &mut std::io::stdout().lock());
     }
-}
 
-impl SchemaDeclaration {
-    fn new(args: &InventorySchemaAlterArgs) -> Result<SchemaDeclaration> {
-        let name = args.name.clone();
-        let default = args.default.clone();
-        let hint = args.hint.clone();
-        let layout = args.layout.clone();
-        let display_name = match args.display_name.clone() {
-            Some(name) => name,
-            None => {
-                let name = name.replace("-", " ").replace("_", " ");
-                let mut chars = name.chars();
-                match chars.next() {
-                    None => String::new(),
-                    Some(first) => first
-                        .to_uppercase()
-                        .chain(chars.map(|c| c.to_ascii_lowercase()))
-                        .collect(),
-                }
+    /**
+     * Lists like list, but streams the rows of the ndjson output into out
+     */
+    pub fn list_into(&self, ctx: &CommandContext, out: &mut dyn Write) -> Result<String> {
+        let user = ctx.authorize("inventory_list")?;
+        let assigned_to = match self.assigned_to.as_deref() {
+            Some("me") => Some(user.id),
+            Some(username) => {
+                require_read(&user, "assignments")?;
+                Some(ctx.db.user_id(username)?)
             }
+            None => None,
         };
-
-        let decl = SchemaDeclaration {
-            name,
-            display_name,
-            unique: args.unique,
-            max_length: args.max_length.unwrap_or(0),
-            min_length: args.min_length.unwrap_or(0),
-            max: args.max.unwrap_or(0),
-            min: args.min.unwrap_or(0),
-            nullable: args.nullable.unwrap_or(false),
-            column_type: args.column_type,
-            default: default.unwrap_or("NULL".into()),
-            hint: hint.unwrap_or("".into()),
-            layout: layout.unwrap_or("".into()),
+        let category = match self.category.as_deref() {
+            Some(path) => {
+                require_read(&user, "categories")?;
+                Some(ctx.db.category_id(path)?)
+            }
+            None => None,
         };
-
-        if decl.min_length > decl.max_length {
-            bail!("Schema min-length parameter cannot be larger than max-length!");
+        if self.include_archived && !user.can_read_table("archive") {
+            bail!("Cannot read the archive");
         }
-
-        if decl.min > decl.max {
-            bail!("Schema min parameter cannot be larger than max!");
+        if self.with_attachments && !user.can_read_table("attachments") {
+            bail!("Cannot read the attachments table");
         }
-
-        if decl.column_type == ColumnType::VARCHAR && decl.max_length == 0 {
-            bail!("Schema cannot have column type varchar with max-length being 0!");
+        // Past values and raw conditions could reveal the columns a role is denied to read
+        let denies_reading = ctx
+            .config
+            .inventory_schema_declaration
+            .collection
+            .iter()
+            .any(|e| user.is_read_denied("inventory", &e.name));
+        if denies_reading && (self.with_audit || self.raw.is_some()) {
+            bail!("Cannot use --with-audit or --raw while denied to read columns of inventory");
         }
-
-        if decl.default != "NULL" {
-            if decl.max_length > 0 && decl.default.len() > usize::try_from(decl.max_length)? {
-                bail!("Schema default value cannot be longer than max-length!");
+        // The organization only scopes what the query selects from, not what raw SQL selects
+        if user.org_id.is_some() && (self.raw.is_some() || self.explain) {
+            bail!("Cannot use --raw or --explain as member of an organization");
+        }
+        let fuzzy = match &self.fuzzy {
+            Some(fuzzy) => {
+                let entry =
+                    fuzzy.to_typed_key_value_entry(&ctx.config.inventory_schema_declaration)?;
+                if user.is_read_denied("inventory", &entry.key) {
+                    bail!("Cannot read column {} of inventory", entry.key);
+                }
+                if !matches!(entry.column_type, ColumnType::TEXT | ColumnType::VARCHAR) {
+                    bail!("Fuzzy matching is only supported on text and varchar columns");
+                }
+                Some(entry)
+            }
+            None => None,
+        };
+        if !(0.0..=1.0).contains(&self.fuzzy_threshold) {
+            bail!("Fuzzy threshold must be between 0 and 1");
+        }
+        if self.after_id.is_some() && (self.raw.is_some() || self.fuzzy.is_some()) {
+            bail!("Cannot combine --after-id with --raw or --fuzzy");
+        }
+        let filter = match &self.filter {
+            Some(filter) => {
+                if self.raw.is_some() {
+                    bail!("Cannot combine --where with --raw");
+                }
+                let filter = WhereExpr::parse(filter)?;
+                require_read_filter(&user, ctx.config, &filter)?;
+                Some(filter)
             }
-            if decl.min_length > 0 && decl.default.len() < usize::try_from(decl.min_length)? {
-                bail!("Schema default value cannot be shorter than min-length!");
+            None => None,
+        };
+        let group = match self.group_by.is_empty() && self.aggregate.is_none() {
+            true => None,
+            false => {
+                if self.after_id.is_some() || self.fuzzy.is_some() {
+                    bail!("Cannot combine --group-by or --aggregate with --after-id or --fuzzy");
+                }
+                let group = Grouping::parse(&self.group_by, self.aggregate.as_deref())?;
+                group.output_columns(ctx.config)?;
+                for column in group.columns() {
+                    if user.is_read_denied("inventory", column) {
+                        bail!("Cannot read column {} of inventory", column);
+                    }
+                }
+                Some(group)
+            }
+        };
+        for expansion in self.expand.iter() {
+            if group.is_some() {
+                bail!("Cannot combine --expand with --group-by or --aggregate");
+            }
+            require_read(&user, expansion.table())?;
+            check_expansion(ctx.config, expansion)?;
+        }
+        let mut expand = self.expand.clone();
+        // Metadata is part of the entities in JSON, unless a schema column takes its key
+        if matches!(ctx.output, OutputType::Json | OutputType::Ndjson)
+            && group.is_none()
+            && user.can_read_table(Expansion::Meta.table())
+            && check_expansion(ctx.config, &Expansion::Meta).is_ok()
+            && !expand.contains(&Expansion::Meta)
+        {
+            expand.push(Expansion::Meta);
+        }
+        let (raw, params) = match &self.raw {
+            Some(raw) => {
+                let (raw, params) = prepare_raw_sql(raw, &self.params)?;
+                (Some(raw), params)
             }
+            None => (None, self.params.clone()),
+        };
+        let props = InventoryListProps {
+            limit: self.limit.unwrap_or(-1),
+            after_id: self.after_id,
+            fuzzy,
+            fuzzy_threshold: self.fuzzy_threshold,
+            with_audit: self.with_audit,
+            with_attachments: self.with_attachments,
+            include_archived: self.include_archived,
+            supplier: self.supplier,
+            assigned_to,
+            category,
+            recursive: self.recursive,
+            raw: &raw,
+            params: &params,
+            changed_since: None,
+            filter,
+            group,
+            expand,
+        };
+        if self.explain {
+            return format_query_plans(
+                &ctx.db.inventory_list_explain(&props, ctx.config)?,
+                ctx.output,
+            );
+        }
+        if ctx.output == OutputType::Ndjson {
+            // Rows are written out as they are read, nothing is returned for printing
+            ctx.db
+                .inventory_list_each(&props, ctx.config, &mut |mut entry| {
+                    user.mask_collection("inventory", &mut entry);
+                    writeln!(out, "{}", entry.to_json())?;
+                    return Ok(());
+                })?;
+            return Ok("".into());
         }
 
-        return Ok(decl);
-    }
+        let mut data = ctx.db.inventory_list(&props, ctx.config)?;
+        for entry in data.iter_mut() {
+            user.mask_collection("inventory", entry);
+        }
+        if ctx.output == OutputType::Plain {
+            return Ok(data.to_plain_table(
+                &ctx.config.display_format,
+                &parse_timezone(&ctx.config.timezone)?,
+            ));
+        }
+        if self.after_id.is_none() {
+            return Ok(data.to_json());
+        }
 
-    pub fn is_equal(&self, other: &SchemaDeclaration) -> bool {
-        return self.name == other.name;
+        // Pages are wrapped into an envelope, so that the caller knows where to continue
+        let next_cursor = match data.last() {
+            Some(last) if props.limit > 0 && data.len() == usize::try_from(props.limit)? => {
+                last.get_id()?
+            }
+            _ => "null".into(),
+        };
+        return Ok(format!(
+            "{{\"data\":{},\"next_cursor\":{}}}",
+            data.to_json(),
+            next_cursor
+        ));
     }
+}
 
-    pub fn to_json(&self) -> String {
-        return format!("{{\"name\":\"{}\",\"display_name\":\"{}\",\"unique\":{},\"max_length\":{},\"min_length\":{},\"max\":{},\"min\":{},\"nullable\":{},\"column_type\":\"{}\",\"default\":\"{}\",\"hint\":\"{}\",\"layout\":\"{}\"}}",
-                       self.name, self.display_name, self.unique, self.max_length, self.min_length, self.max, self.min, self.nullable, self.column_type, self.default, self.hint, self.layout);
+/**
+ * Fails if the schema has a column named like the embedded records, which they would replace.
+ */
+pub(crate) fn check_expansion(config: &AppConfig, expansion: &Expansion) -> Result<()> {
+    if config
+        .inventory_schema_declaration
+        .collection
+        .iter()
+        .any(|e| e.name == expansion.key())
+    {
+        bail!(
+            "Cannot expand {} as the schema has a column of the same name",
+            expansion.key()
+        );
     }
+    return Ok(());
 }
 
-pub struct UserArgs {
-    pub name: String,
-    pub password: String,
+/**
+ * Format the entities are exported in
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Ndjson,
+    Csv,
 }
 
-impl UserArgs {
-    pub fn register(&self, param: &mut CommandContext) -> Result<String> {
-        if !param.config.allow_registration {
-            bail!("User registration failed (Registration is disabled by inventory administrator)");
+pub struct InventoryExportArgs {
+    pub format: ExportFormat,
+    /**
+     * Columns left out of the export, or hashed if hash is set
+     */
+    pub redact: Vec<String>,
+    pub hash: bool,
+    /**
+     * Only exports the entities created, changed or removed after the transaction of this id
+     */
+    pub since_tx: Option<u32>,
+    /**
+     * Only exports the entities created, changed or removed since this date, in %Y-%m-%d or
+     * %Y-%m-%d %H:%M:%S notation
+     */
+    pub since: Option<String>,
+    /**
+     * Writes the export into this file instead of out
+     */
+    pub file: Option<String>,
+    pub gzip: bool,
+    /**
+     * Writes the SHA-256 checksum of the file into a sidecar file next to it
+     */
+    pub sha256: bool,
+}
+
+impl InventoryExportArgs {
+    pub fn export(&self, ctx: &CommandContext, out: &mut dyn Write) -> Result<String> {
+        if self.sha256 && self.file.is_none() {
+            bail!("A checksum can only be written for an export into a file");
+        }
+        let mut file = match &self.file {
+            Some(file) => match fs::File::create(file) {
+                Ok(file) => Some(io::BufWriter::new(file)),
+                Err(e) => bail!("Could not create file '{}' ({})", file, e),
+            },
+            None => None,
+        };
+        let out: &mut dyn Write = match file.as_mut() {
+            Some(file) => file,
+            None => out,
+        };
+        if self.gzip {
+            let mut gzip = GzEncoder::new(out, Compression::default());
+            self.write_entities(ctx, &mut gzip)?;
+            gzip.finish()?.flush()?;
+        } else {
+            self.write_entities(ctx, out)?;
+            out.flush()?;
         }
+        drop(file);
+        return match &self.file {
+            Some(file) if self.sha256 => Ok(format!(
+                "Inventory was exported to '{}' with checksum '{}'",
+                file,
+                write_checksum(file)?
+            )),
+            Some(file) => Ok(format!("Inventory was exported to '{}'", file)),
+            None => Ok("".into()),
+        };
+    }
 
-        return match param
-            .db
-            .user_register(self.name.as_str(), self.password.as_str())
-        {
-            Ok(s) => Ok(s),
-            Err(e) => bail!("User registration failed ({})", e.to_string()),
+    fn write_entities(&self, ctx: &CommandContext, out: &mut dyn Write) -> Result<()> {
+        let user = ctx.authenticate()?;
+        let columns = ctx
+            .config
+            .inventory_schema_declaration
+            .sql_names()
+            .split(',')
+            .map(String::from)
+            .collect::<Vec<String>>();
+        if let Some(unknown) = self.redact.iter().find(|e| !columns.contains(e)) {
+            bail!("Field {} could not be found in schema declaration", unknown);
+        }
+        let changed_since = match (self.since_tx, self.since.as_deref()) {
+            (Some(tx), _) => Some(ChangeMarker::Tx(tx)),
+            (None, Some(since)) => {
+                let time = match NaiveDateTime::parse_from_str(since, "%Y-%m-%d %H:%M:%S") {
+                    Ok(time) => time,
+                    Err(_) => match NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+                        Ok(date) => date.and_time(NaiveTime::MIN),
+                        Err(_) => bail!(
+                            "Since '{}' must be in YYYY-MM-DD or YYYY-MM-DD HH:MM:SS notation",
+                            since
+                        ),
+                    },
+                };
+                Some(ChangeMarker::Time(
+                    time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                ))
+            }
+            (None, None) => None,
+        };
+        let mut header = columns
+            .into_iter()
+            .filter(|e| !user.is_read_denied("inventory", e))
+            .filter(|e| self.hash || !self.redact.contains(e))
+            .collect::<Vec<String>>();
+        if changed_since.is_some() {
+            header.push("change".into());
+            header.push("revision".into());
+        }
+        let props = InventoryListProps {
+            limit: -1,
+            after_id: None,
+            fuzzy: None,
+            fuzzy_threshold: 0.0,
+            with_audit: false,
+            with_attachments: false,
+            include_archived: false,
+            supplier: None,
+            assigned_to: None,
+            category: None,
+            recursive: false,
+            raw: &None,
+            params: &vec![],
+            changed_since,
+            filter: None,
+            group: None,
+            expand: vec![],
+        };
+        match self.format {
+            ExportFormat::Csv => out.write_all(&csv_record(&header)?)?,
+            ExportFormat::Json => write!(out, "[")?,
+            ExportFormat::Ndjson => {}
+        }
+        // Only a full export knows how many entities it is going to write
+        let total = match props.changed_since {
+            Some(_) => None,
+            None => Some(ctx.db.inventory_count(&vec![].into())? as u64),
         };
+        let mut progress = Progress::start("export", "Exporting entities", total);
+        let mut count = 0;
+        ctx.db
+            .inventory_list_each(&props, ctx.config, &mut |mut entry| {
+                entry.collection.retain(|e| header.contains(&e.key));
+                for e in entry.collection.iter_mut() {
+                    if self.redact.contains(&e.key) {
+                        e.hash_value();
+                    }
+                }
+                match self.format {
+                    ExportFormat::Csv => {
+                        let record = header
+                            .iter()
+                            .map(|name| {
+                                entry
+                                    .collection
+                                    .iter()
+                                    .find(|e| &e.key == name)
+                                    .and_then(|e| e.to_plain_value())
+                                    .unwrap_or_default()
+                            })
+                            .collect::<Vec<String>>();
+                        out.write_all(&csv_record(&record)?)?;
+                    }
+                    ExportFormat::Json => {
+                        let separator = if count > 0 { "," } else { "" };
+                        write!(out, "{}{}", separator, entry.to_json())?;
+                    }
+                    ExportFormat::Ndjson => writeln!(out, "{}", entry.to_json())?,
+                }
+                count += 1;
+                progress.advance(1);
+                return Ok(());
+            })?;
+        if self.format == ExportFormat::Json {
+            writeln!(out, "]")?;
+        }
+        tracing::info!(entities = count, "Exported inventory");
+        return Ok(());
     }
 }
 
-pub struct UserEditArgs {
-    pub options: Vec<String>,
+/**
+ * Formats the fields as one line of comma separated values, quoted where needed.
+ */
+fn csv_record(fields: &[String]) -> Result<Vec<u8>> {
+    let mut csv = csv::Writer::from_writer(vec![]);
+    csv.write_record(fields)?;
+    return Ok(csv.into_inner()?);
 }
 
-impl UserEditArgs {
-    pub fn edit(&self, ctx: &CommandContext) -> Result<String> {
-        let _user = ctx.authenticate()?;
-        return Ok("".into());
+pub struct InventoryCountArgs {
+    pub conditions: Vec<String>,
+}
+
+impl InventoryCountArgs {
+    pub fn count(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authorize("inventory_count")?;
+        let conditions = self
+            .conditions
+            .to_key_value_collection(&ctx.config.inventory_schema_declaration)?;
+        require_read_collection(&user, ctx.config, &conditions)?;
+        return Ok(ctx.db.inventory_count(&conditions)?.to_string());
     }
 }
 
-pub struct InventorySchemaAlterArgs {
-    pub name: String,
-    pub display_name: Option<String>,
-    pub unique: bool,
-    pub max_length: Option<u32>,
-    pub min_length: Option<u32>,
-    pub max: Option<u32>,
-    pub min: Option<u32>,
-    pub nullable: Option<bool>,
-    pub column_type: ColumnType,
-    pub default: Option<String>,
-    pub hint: Option<String>,
+pub struct InventoryExistsArgs {
+    pub identifier: String,
+}
+
+impl InventoryExistsArgs {
+    pub fn exists(&self, ctx: &CommandContext) -> Result<String> {
+        let _ = ctx.authorize("inventory_exists")?;
+        return Ok(ctx.db.inventory_exists(&self.identifier)?.to_string());
+    }
+}
+
+pub struct InventoryLabelArgs {
+    pub identifier: String,
+    /**
+     * Columns printed on the label, all columns of the schema if empty
+     */
+    pub fields: Vec<String>,
+    /**
+     * Column encoded by the barcode, the id if not given
+     */
+    pub barcode: Option<String>,
+    pub copies: u32,
+    /**
+     * Sends the label to the configured printer instead of returning it
+     */
+    pub send: bool,
+}
+
+impl InventoryLabelArgs {
+    pub fn label(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authorize("inventory_get")?;
+        if self.copies == 0 {
+            bail!("At least one copy must be printed");
+        }
+        let mut entity = match ctx.db.inventory_get(&self.identifier, ctx.config)? {
+            Some(entity) => entity,
+            None => bail!("Entity {} does not exist", self.identifier),
+        };
+        user.mask_collection("inventory", &mut entity);
+        let value = |column: &str| -> Result<String> {
+            if user.is_read_denied("inventory", column) {
+                bail!("Cannot read column {} of inventory", column);
+            }
+            return match entity.collection.iter().find(|e| e.key == column) {
+                Some(entry) => Ok(entry.to_plain_value().unwrap_or_default()),
+                None => bail!("Could not find '{}' in table schema", column),
+            };
+        };
+        let columns = match self.fields.is_empty() {
+            true => ctx
+                .config
+                .inventory_schema_declaration
+                .collection
+                .iter()
+                .map(|e| e.name.clone())
+                .filter(|e| !user.is_read_denied("inventory", e))
+                .collect(),
+            false => self.fields.clone(),
+        };
+        let mut fields = vec![];
+        for column in &columns {
+            fields.push((column.clone(), value(column)?));
+        }
+        let barcode = value(self.barcode.as_deref().unwrap_or("id"))?;
+        if barcode.is_empty() {
+            bail!(
+                "Entity {} has no value to encode in the barcode",
+                self.identifier
+            );
+        }
+        let zpl = Label {
+            fields,
+            barcode,
+            copies: self.copies,
+        }
+        .to_zpl();
+        if !self.send {
+            return Ok(zpl);
+        }
+
+        let printer = match &ctx.config.label_printer {
+            Some(printer) => printer,
+            None => bail!("No label printer is configured, set one with config label-printer"),
+        };
+        let address = match printer.to_socket_addrs()?.next() {
+            Some(address) => address,
+            None => bail!("Could not resolve the label printer {}", printer),
+        };
+        let sent = TcpStream::connect_timeout(&address, Duration::from_secs(5))
+            .and_then(|mut stream| stream.write_all(zpl.as_bytes()));
+        if let Err(e) = sent {
+            bail!(
+                "Could not send the label to the printer {} ({})",
+                printer,
+                e
+            );
+        }
+        return Ok(format!("Label was sent to the printer {}", printer));
+    }
+}
+
+pub struct InventoryDistinctArgs {
+    pub column: String,
+    pub conditions: Vec<String>,
+    pub count: bool,
+}
+
+impl InventoryDistinctArgs {
+    pub fn distinct(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authorize("inventory_distinct")?;
+        let conditions = self
+            .conditions
+            .to_key_value_collection(&ctx.config.inventory_schema_declaration)?;
+        require_read_collection(&user, ctx.config, &conditions)?;
+        require_column(ctx.config, &self.column)?;
+        if user.is_read_denied("inventory", &self.column) {
+            bail!("Cannot read column {} of inventory", self.column);
+        }
+        let data = ctx
+            .db
+            .inventory_distinct(&self.column, &conditions, ctx.config)?;
+        if self.count {
+            return Ok(data.to_json());
+        }
+        let values = data
+            .iter()
+            .filter_map(|e| e.collection.first())
+            .map(|e| e.to_json_value())
+            .collect::<Vec<String>>()
+            .join(",");
+        return Ok(format!("[{}]", values));
+    }
+}
+
+pub struct InventorySchemaListArgs;
+
+impl InventorySchemaListArgs {
+    pub fn schema_list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["config"]))?;
+        return Ok(ctx.config.inventory_schema_declaration.to_json());
+    }
+}
+
+pub struct InventorySchemaJsonSchemaArgs;
+
+impl InventorySchemaJsonSchemaArgs {
+    /**
+     * Describes the entities of the inventory as JSON Schema, e.g. for external forms and
+     * validators. Columns managed by invman itself, like id and created_at, are left out.
+     */
+    pub fn json_schema(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["config"]))?;
+        let columns = &ctx.config.inventory_schema_declaration.collection;
+        let properties = columns
+            .iter()
+            .map(|e| (e.name.clone(), e.to_json_schema()))
+            .collect::<serde_json::Map<String, serde_json::Value>>();
+        let required = columns
+            .iter()
+            .filter(|e| !e.nullable && e.default == "NULL")
+            .map(|e| e.name.clone())
+            .collect::<Vec<String>>();
+        let schema = serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "Inventory entity",
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        });
+        return Ok(serde_json::to_string(&schema)?);
+    }
+}
+
+pub struct InventorySchemaExampleArgs {
+    pub format: ExportFormat,
+}
+
+impl InventorySchemaExampleArgs {
+    /**
+     * Prints a template of one example entity in the shape inventory import and the API expect,
+     * with a value for every column. Defaults of the schema are taken as they are, those
+     * evaluated on addition as they would be evaluated now.
+     */
+    pub fn example(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authorize_access(Access::read(&["config"]))?;
+        let columns = &ctx.config.inventory_schema_declaration.collection;
+        let now = chrono::Utc::now();
+        let values = columns
+            .iter()
+            .map(|e| {
+                return match (e.context_default(), e.default.as_str()) {
+                    (Some(ContextDefault::CurrentUser), _) => user.username.clone().into(),
+                    (Some(ContextDefault::Env(name)), _) => match std::env::var(name) {
+                        Ok(value) => value.into(),
+                        Err(_) => e.example(),
+                    },
+                    (_, "CURRENT_TIMESTAMP") => now.format("%Y-%m-%d %H:%M:%S").to_string().into(),
+                    (_, "CURRENT_DATE") => now.format("%Y-%m-%d").to_string().into(),
+                    (_, "CURRENT_TIME") => now.format("%H:%M:%S").to_string().into(),
+                    _ => e.example(),
+                };
+            })
+            .collect::<Vec<serde_json::Value>>();
+        // Built by hand to keep the columns in the order of the schema
+        let object = format!(
+            "{{{}}}",
+            columns
+                .iter()
+                .zip(values.iter())
+                .map(|(column, value)| format!("{}:{}", serde_json::json!(column.name), value))
+                .collect::<Vec<String>>()
+                .join(",")
+        );
+        return Ok(match self.format {
+            ExportFormat::Json => format!("[{}]", object),
+            ExportFormat::Ndjson => object,
+            ExportFormat::Csv => {
+                let names = columns.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+                let cells = values
+                    .iter()
+                    .map(|e| match e {
+                        serde_json::Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                let mut csv = csv_record(&names)?;
+                csv.extend(csv_record(&cells)?);
+                String::from_utf8(csv)?.trim_end().to_string()
+            }
+        });
+    }
+}
+
+pub struct InventorySchemaGraphqlArgs;
+
+impl InventorySchemaGraphqlArgs {
+    /**
+     * Describes the entities of the inventory together with the queries, mutations and
+     * subscriptions on them in the GraphQL schema definition language, as served by serve.
+     */
+    pub fn graphql(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["config"]))?;
+        return Ok(Schema::inventory(&ctx.config.inventory_schema_declaration).sdl());
+    }
+}
+
+/**
+ * Rules passwords have to follow when they are set, all of which are off by default
+ */
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub min_length: u32,
+    /**
+     * How many of lowercase letters, uppercase letters, digits and other characters a password
+     * has to contain
+     */
+    pub min_classes: u32,
+    /**
+     * Passwords that are refused regardless of case, e.g. the company's name
+     */
+    pub banned: Vec<String>,
+    /**
+     * Days after which a password has to be changed, 0 for never
+     */
+    pub max_age_days: u32,
+}
+
+impl PasswordPolicy {
+    pub fn check(&self, password: &str) -> Result<()> {
+        if (password.chars().count() as u32) < self.min_length {
+            bail!(
+                "Password must be at least {} characters long",
+                self.min_length
+            );
+        }
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if (classes.iter().filter(|e| **e).count() as u32) < self.min_classes {
+            bail!(
+                "Password must contain {} of lowercase letters, uppercase letters, digits and other characters",
+                self.min_classes
+            );
+        }
+        let lowercase = password.to_lowercase();
+        if self.banned.iter().any(|e| e.to_lowercase() == lowercase) {
+            bail!("Password is banned, choose another one");
+        }
+        return Ok(());
+    }
+}
+
+pub struct UserArgs {
+    pub name: String,
+    pub password: String,
+}
+
+impl UserArgs {
+    pub fn register(&self, param: &mut CommandContext) -> Result<String> {
+        param.check_writable()?;
+        // Admins still add users while others cannot register themselves
+        let is_admin = || {
+            param.auth.is_some()
+                && param
+                    .authenticate()
+                    .is_ok_and(|e| e.can_write_table("users"))
+        };
+        if !param.config.allow_registration && !is_admin() {
+            bail!("User registration failed (Registration is disabled by inventory administrator)");
+        }
+        if let Err(e) = param.config.password_policy.check(&self.password) {
+            bail!("User registration failed ({})", e);
+        }
+
+        return match param
+            .db
+            .user_register(self.name.as_str(), self.password.as_str())
+        {
+            Ok(s) => Ok(s),
+            Err(e) => bail!("User registration failed ({})", e),
+        };
+    }
+}
+
+pub struct UserEditArgs {
+    pub options: Vec<String>,
+    /**
+     * User to edit instead of the authenticated one, which requires writing the users table
+     */
+    pub username: Option<String>,
+    pub must_change_password: bool,
+}
+
+impl UserEditArgs {
+    pub fn edit(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        // Changing the password is what a user whose password expired has to do
+        let user = ctx.authenticate_expired()?;
+        let mut password = None;
+        for option in &self.options {
+            match option.split_once('=') {
+                Some(("password", value)) => password = Some(value),
+                _ => bail!("Unknown option {}, supported is password=<NEW>", option),
+            }
+        }
+        if user.password_expired && (password.is_none() || self.username.is_some()) {
+            bail!(PASSWORD_EXPIRED);
+        }
+        if self.username.is_some() || self.must_change_password {
+            require_write(&user, "users")?;
+        }
+        if password.is_none() && !self.must_change_password {
+            bail!("Nothing to change, give password=<NEW> or --must-change-password");
+        }
+        let result = ctx.db.user_edit(
+            self.username.as_deref(),
+            password,
+            self.must_change_password,
+            ctx.config,
+            &user,
+        )?;
+        // The rest of the invocation, e.g. a shell session, goes on with the new password
+        if let (None, Some(password)) = (&self.username, password) {
+            let name = ctx
+                .auth
+                .as_deref()
+                .and_then(|e| e.split_once(':'))
+                .map(|e| e.0);
+            if let Some(name) = name.map(String::from) {
+                ctx.auth = Some(format!("{}:{}", name, password));
+            }
+            if let Some(session) = ctx.session.as_mut() {
+                session.password_expired = self.must_change_password;
+            }
+        }
+        return Ok(result);
+    }
+}
+
+pub struct InventorySchemaAlterArgs {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub unique: bool,
+    pub max_length: Option<u32>,
+    pub min_length: Option<u32>,
+    pub max: Option<u32>,
+    pub min: Option<u32>,
+    pub nullable: Option<bool>,
+    pub column_type: ColumnType,
+    pub default: Option<String>,
+    pub hint: Option<String>,
     pub layout: Option<String>,
+    pub serialized: bool,
 }
 
 impl InventorySchemaAlterArgs {
+    /**
+     * Builds the column declaration from the arguments, unset limits being 0
+     */
+    pub fn to_declaration(&self) -> Result<SchemaDeclaration> {
+        let name = self.name.clone();
+        let default = self.default.clone();
+        let hint = self.hint.clone();
+        let layout = self.layout.clone();
+        let display_name = match self.display_name.clone() {
+            Some(name) => name,
+            None => {
+                let name = name.replace("-", " ").replace("_", " ");
+                let mut chars = name.chars();
+                match chars.next() {
+                    None => String::new(),
+                    Some(first) => first
+                        .to_uppercase()
+                        .chain(chars.map(|c| c.to_ascii_lowercase()))
+                        .collect(),
+                }
+            }
+        };
+
+        let decl = SchemaDeclaration {
+            name,
+            display_name,
+            unique: self.unique,
+            max_length: self.max_length.unwrap_or(0),
+            min_length: self.min_length.unwrap_or(0),
+            max: self.max.unwrap_or(0),
+            min: self.min.unwrap_or(0),
+            nullable: self.nullable.unwrap_or(false),
+            column_type: self.column_type,
+            default: match default {
+                Some(default) if self.column_type == ColumnType::MONEY => {
+                    Money::parse(&default)?.to_string()
+                }
+                Some(default) => default,
+                None => "NULL".into(),
+            },
+            hint: hint.unwrap_or("".into()),
+            layout: layout.unwrap_or("".into()),
+            serialized: self.serialized,
+        };
+        decl.validate()?;
+        return Ok(decl);
+    }
+
     pub fn alter(&self, ctx: &mut CommandContext) -> Result<String> {
-        let mut user = ctx.authenticate()?;
-        if !user.can_write_table("config") {
-            bail!("Cannot write to config table");
+        let user = ctx.authorize("schema_alter")?;
+        let decl = self.to_declaration()?;
+        if decl.serialized
+            && ctx
+                .config
+                .inventory_schema_declaration
+                .collection
+                .iter()
+                .any(|e| e.serialized && !e.is_equal(&decl))
+        {
+            bail!("Only one schema column can be flagged as serialized");
         }
-        let decl = SchemaDeclaration::new(self)?;
-        return ctx.db.schema_alter(ctx.config, decl, &mut user);
+        return ctx.db.schema_alter(ctx.config, decl, &user);
     }
 }
 
@@ -346,30 +1739,5544 @@ pub struct InventorySchemaRemoveArgs {
 
 impl InventorySchemaRemoveArgs {
     pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
-        let user = ctx.authenticate()?;
-        return ctx
+        let user = ctx.authorize("schema_remove")?;
+        return ctx.db.schema_remove(ctx.config, self.name.as_str(), &user);
+    }
+}
+
+pub struct InventorySchemaInferArgs {
+    pub file: String,
+    /**
+     * Guessed from the extension of the file if None
+     */
+    pub format: Option<ImportFormat>,
+    pub sheet: Option<String>,
+    /**
+     * Adds the proposed columns to the schema instead of only printing them
+     */
+    pub apply: bool,
+}
+
+impl InventorySchemaInferArgs {
+    /**
+     * Proposes a schema column for each header of the file that is not part of the schema yet.
+     * The type is the narrowest one all values of the column fit in (bool, int, real, else
+     * varchar or text), the max length leaves room for longer values than the ones seen, and
+     * the column is nullable if any of its cells is empty or the inventory has entities already.
+     */
+    pub fn infer(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize_access(Access::read(&["config"]))?;
+        if self.apply {
+            ctx.check_writable()?;
+            require_write(&user, "config")?;
+        }
+        let format = self
+            .format
+            .unwrap_or_else(|| ImportFormat::of_file(&self.file));
+        let mut rows = import::read_rows(&self.file, format, self.sheet.as_deref())?.into_iter();
+        let header = match rows.next() {
+            Some(header) => header,
+            None => bail!("File '{}' has no header", self.file),
+        };
+        let rows = rows.collect::<Vec<Vec<String>>>();
+
+        // Entities already in the inventory have no value for the new columns
+        let populated = ctx
             .db
-            .schema_remove(&mut ctx.config, self.name.as_str(), &user);
+            .inventory_count(&KeyValueCollection { collection: vec![] })?
+            > 0;
+        let managed = ["id", "created_at", "updated_at", "deleted_at"];
+        let mut decls = vec![];
+        for (i, header) in header.iter().enumerate() {
+            let name = header
+                .trim()
+                .to_lowercase()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>();
+            if name.is_empty()
+                || managed.contains(&name.as_str())
+                || decls.iter().any(|e: &SchemaDeclaration| e.name == name)
+                || ctx
+                    .config
+                    .inventory_schema_declaration
+                    .collection
+                    .iter()
+                    .any(|e| e.name == name)
+            {
+                continue;
+            }
+            let values = rows
+                .iter()
+                .map(|row| row.get(i).map(|e| e.trim()).unwrap_or(""))
+                .collect::<Vec<&str>>();
+            let mut column = infer_column(name, &values);
+            if populated {
+                column.nullable = Some(true);
+            }
+            decls.push(column.to_declaration()?);
+        }
+
+        if !self.apply {
+            return Ok(decls.to_json());
+        }
+        if decls.is_empty() {
+            bail!("File '{}' has no columns missing in the schema", self.file);
+        }
+        return ctx.db.schema_extend(ctx.config, decls, &user);
+    }
+}
+
+/**
+ * Rounds the length up to the next power of two, but at least 16, so that slightly longer
+ * values than the sampled ones still fit.
+ */
+fn length_with_headroom(length: u64) -> u64 {
+    return length.max(16).next_power_of_two();
+}
+
+fn infer_column(name: String, values: &[&str]) -> InventorySchemaAlterArgs {
+    let filled = values
+        .iter()
+        .filter(|e| !e.is_empty())
+        .copied()
+        .collect::<Vec<&str>>();
+    let mut args = InventorySchemaAlterArgs {
+        name,
+        display_name: None,
+        unique: false,
+        max_length: None,
+        min_length: None,
+        max: None,
+        min: None,
+        nullable: Some(filled.len() < values.len()),
+        column_type: ColumnType::VARCHAR,
+        default: None,
+        hint: None,
+        layout: None,
+        serialized: false,
+    };
+    let reals = filled
+        .iter()
+        .filter_map(|e| e.parse::<f64>().ok())
+        .filter(|e| e.is_finite())
+        .collect::<Vec<f64>>();
+    if !filled.is_empty()
+        && filled
+            .iter()
+            .all(|e| e.eq_ignore_ascii_case("true") || e.eq_ignore_ascii_case("false"))
+    {
+        args.column_type = ColumnType::BOOL;
+    } else if !filled.is_empty() && filled.iter().all(|e| e.parse::<i64>().is_ok()) {
+        args.column_type = ColumnType::INT;
+    } else if !filled.is_empty()
+        && reals.len() == filled.len()
+        && reals.iter().all(|e| *e >= 0.0 && *e <= f64::from(u32::MAX))
+    {
+        // Real columns are checked against their max, even if it is 0
+        let max = reals.iter().fold(0.0, |a: f64, b| a.max(*b)).ceil() as u64;
+        args.column_type = ColumnType::REAL;
+        args.max = Some(u32::try_from(length_with_headroom(max)).unwrap_or(u32::MAX));
+    } else if !filled.is_empty() && filled.iter().all(|e| Money::parse(e).is_ok()) {
+        args.column_type = ColumnType::MONEY;
+    } else {
+        let length = filled.iter().map(|e| e.len()).max().unwrap_or(0) as u64;
+        let (column_type, max_length) = if length > 255 {
+            (ColumnType::TEXT, length_with_headroom(length))
+        } else {
+            (ColumnType::VARCHAR, length_with_headroom(length).min(255))
+        };
+        args.column_type = column_type;
+        args.max_length = Some(u32::try_from(max_length).unwrap_or(u32::MAX));
     }
+    return args;
 }
 
 pub struct InventoryAddArgs {
     pub params: Vec<String>,
+    /**
+     * Takes leading values without a name in the declared order of the schema columns, e.g.
+     * Widget 5 9.99 for name, quantity and price
+     */
+    pub positional: bool,
+    /**
+     * Keeps name=value pairs of columns unknown to the schema as metadata of the entity
+     */
+    pub allow_extra: bool,
+    /**
+     * Runs every check of the addition, including the constraints of the database, without
+     * keeping the entity
+     */
+    pub validate_only: bool,
 }
 
 impl InventoryAddArgs {
     pub fn add(&self, ctx: &mut CommandContext) -> Result<String> {
-        let user = ctx.authenticate()?;
-        let entries: KeyValueCollection = self
-            .params
+        let user = ctx.authorize("inventory_add")?;
+        let params = self.named_params(&ctx.config.inventory_schema_declaration)?;
+        let params = plugins::transform_on_add(&params)?;
+        plugins::validate_entity(&params)?;
+        let (params, meta) = match self.allow_extra {
+            true => split_extra_params(&params, &ctx.config.inventory_schema_declaration)?,
+            false => (params, BTreeMap::new()),
+        };
+        let entries: KeyValueCollection = params
             .iter()
             .map(|e| e.to_typed_key_value_entry(&ctx.config.inventory_schema_declaration))
-            .into_iter()
             .collect::<Result<Vec<_>>>()?
             .into();
-        if !user.can_write_collection("inventory", &entries) {
-            bail!("Cannot write arguments to inventory");
+        require_write_collection(&user, ctx.config, &entries)?;
+        let entries =
+            with_context_defaults(entries, &ctx.config.inventory_schema_declaration, &user)?;
+        if !meta.is_empty() && !user.can_write_table("meta") {
+            bail!("Cannot write to meta table, which keeps the columns unknown to the schema");
+        }
+        // The entity is added within a transaction that is rolled back, so that the database
+        // checks it the same way
+        if self.validate_only {
+            ctx.db.batch_begin()?;
+        }
+        let added = match meta.is_empty() {
+            true => ctx.db.inventory_add(&entries, ctx.config, &user),
+            false => ctx
+                .db
+                .inventory_add_with_meta(&entries, &meta, ctx.config, &user),
+        };
+        if !self.validate_only {
+            return added;
+        }
+        ctx.db.batch_rollback()?;
+        added?;
+        return Ok("Entity is valid, it was not added".into());
+    }
+
+    /**
+     * The parameters in name=value notation. Positional values are those before the first
+     * parameter naming a schema column. Values containing = are rejected as they could be a
+     * mistyped column, these have to be given as name=value pair. With extra columns allowed,
+     * a pair naming a valid metadata key ends the positional values too.
+     */
+    fn named_params(&self, declarations: &SchemaCollection) -> Result<Vec<String>> {
+        let is_named = |param: &String| match param.split_once('=') {
+            Some((name, _)) => {
+                declarations.collection.iter().any(|e| e.name == name)
+                    || (self.allow_extra && check_meta_key(name).is_ok())
+            }
+            None => false,
+        };
+        if !self.positional {
+            if let Some(param) = self.params.iter().find(|e| !e.contains('=')) {
+                bail!(
+                    "Parameter '{}' is not in name=value notation, pass --positional to give the values in the order of the schema columns",
+                    param
+                );
+            }
+            return Ok(self.params.clone());
+        }
+        let count = self.params.iter().take_while(|e| !is_named(e)).count();
+        let (values, named) = self.params.split_at(count);
+        if let Some(value) = values.iter().find(|e| e.contains('=')) {
+            bail!(
+                "Value '{}' names no schema column, give values containing = as name=value pair",
+                value
+            );
+        }
+        if let Some(value) = named.iter().find(|e| !is_named(e)) {
+            bail!(
+                "Value '{}' must come before the name=value parameters or name a schema column",
+                value
+            );
+        }
+        let columns = &declarations.collection;
+        if values.len() > columns.len() {
+            bail!(
+                "{} values were given, but the schema only has {} columns ({})",
+                values.len(),
+                columns.len(),
+                columns
+                    .iter()
+                    .map(|e| e.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            );
         }
-        return ctx.db.inventory_add(&entries, &ctx.config, &user);
+        for param in named {
+            let name = param.split_once('=').map(|e| e.0).unwrap_or_default();
+            if let Some(position) = columns[..values.len()].iter().position(|e| e.name == name) {
+                bail!(
+                    "Column {} is given both as value {} ('{}') and as '{}'",
+                    name,
+                    position + 1,
+                    values[position],
+                    param
+                );
+            }
+        }
+        return Ok(columns
+            .iter()
+            .zip(values)
+            .map(|(column, value)| format!("{}={}", column.name, value))
+            .chain(named.iter().cloned())
+            .collect());
+    }
+}
+
+/**
+ * Adds the values of the columns defaulting to the context of the addition, which were not
+ * given. Like other defaults they are not checked against the permissions of the user.
+ */
+pub(crate) fn with_context_defaults(
+    mut entries: KeyValueCollection,
+    declarations: &SchemaCollection,
+    user: &DBUser,
+) -> Result<KeyValueCollection> {
+    for decl in declarations.collection.iter() {
+        if entries.collection.iter().any(|e| e.key == decl.name) {
+            continue;
+        }
+        let value = match decl.context_default() {
+            None => continue,
+            Some(ContextDefault::CurrentUser) => user.username.clone(),
+            Some(ContextDefault::Env(name)) => match std::env::var(&name) {
+                Ok(value) => value,
+                Err(_) if decl.nullable => continue,
+                Err(_) => bail!(
+                    "Field {} defaults to the environment variable {}, which is not set",
+                    decl.name,
+                    name
+                ),
+            },
+        };
+        entries
+            .collection
+            .push(format!("{}={}", decl.name, value).to_typed_key_value_entry(declarations)?);
+    }
+    return Ok(entries);
+}
+
+pub struct InventoryImportArgs {
+    pub file: String,
+    /**
+     * Guessed from the extension of the file if None
+     */
+    pub format: Option<ImportFormat>,
+    pub sheet: Option<String>,
+    /**
+     * Headers of the file that are named differently than their schema column, in
+     * header=column notation
+     */
+    pub map: Vec<String>,
+    /**
+     * Threads parsing and validating the rows, while the entities are written by a single one
+     */
+    pub workers: usize,
+    /**
+     * Rows committed per transaction, all rows are imported in a single transaction if 0
+     */
+    pub batch_size: usize,
+    /**
+     * Keeps the columns of the file unknown to the schema as metadata of the entities
+     */
+    pub allow_extra: bool,
+    /**
+     * Runs every check of the import and lists the errors of all rows without keeping any
+     * entity, the batch size is ignored
+     */
+    pub validate_only: bool,
+}
+
+/**
+ * Columns invman manages itself, which are never taken from the input
+ */
+const MANAGED_COLUMNS: [&str; 4] = ["id", "created_at", "updated_at", "deleted_at"];
+
+/**
+ * Splits name=value parameters into those of schema columns and the metadata of unknown
+ * columns. Managed columns and parameters without a name are left to fail with the schema.
+ */
+fn split_extra_params(
+    params: &[String],
+    declarations: &SchemaCollection,
+) -> Result<(Vec<String>, BTreeMap<String, String>)> {
+    let mut known = vec![];
+    let mut meta = BTreeMap::new();
+    for param in params {
+        match param.split_once('=') {
+            Some((name, value))
+                if !MANAGED_COLUMNS.contains(&name)
+                    && !declarations.collection.iter().any(|e| e.name == name) =>
+            {
+                check_meta_key(name)?;
+                if meta.insert(name.to_string(), value.to_string()).is_some() {
+                    bail!("Column {} is given more than once", name);
+                }
+            }
+            _ => known.push(param.clone()),
+        }
+    }
+    return Ok((known, meta));
+}
+
+/**
+ * Rows a worker of the import parses and validates at once
+ */
+const IMPORT_CHUNK_ROWS: usize = 1000;
+
+/**
+ * Validation errors listed per chunk, the others are only counted unless the file is only
+ * validated
+ */
+const IMPORT_CHUNK_ERRORS: usize = 10;
+
+impl InventoryImportArgs {
+    /**
+     * Adds an entity for each row of the file within a single transaction, so either every row
+     * is imported or none, or within a transaction per batch. Columns are matched by the header,
+     * the columns invman manages itself (e.g. id) are skipped, and empty cells are left to the
+     * schema's defaults. Files with a checksum file are verified first.
+     *
+     * The rows are parsed and validated in chunks by the workers, while the entities of the
+     * chunks are written in order of the file. When only validating, the entities are written
+     * within a transaction that is rolled back, so that the constraints of the database are
+     * checked too, e.g. duplicates within the file.
+     */
+    pub fn import(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        verify_checksum(&self.file)?;
+        let format = self
+            .format
+            .unwrap_or_else(|| ImportFormat::of_file(&self.file));
+        if self.workers == 0 {
+            bail!("At least one worker is required");
+        }
+        let mut rows = import::read_rows(&self.file, format, self.sheet.as_deref())?;
+        if rows.is_empty() {
+            bail!("File '{}' has no header", self.file);
+        }
+        let header = rows.remove(0);
+        let mut mapping = BTreeMap::new();
+        for map in &self.map {
+            match map.split_once('=') {
+                Some((header, column)) => mapping.insert(header.trim(), column.trim()),
+                None => bail!("Mapping '{}' is not in header=column notation", map),
+            };
+        }
+        let mut columns = vec![];
+        let mut extra = false;
+        for name in &header {
+            let name = mapping.get(name.trim()).copied().unwrap_or(name.trim());
+            if MANAGED_COLUMNS.contains(&name) {
+                columns.push(None);
+            } else if ctx
+                .config
+                .inventory_schema_declaration
+                .collection
+                .iter()
+                .any(|e| e.name == name)
+            {
+                columns.push(Some(name.to_string()));
+            } else if self.allow_extra {
+                check_meta_key(name)?;
+                columns.push(Some(name.to_string()));
+                extra = true;
+            } else {
+                bail!(
+                    "Column '{}' of the file could not be found in schema declaration, map it with --map or keep it as metadata with --allow-extra",
+                    name
+                );
+            }
+        }
+
+        let user = ctx.authenticate()?;
+        if extra && !user.can_write_table("meta") {
+            bail!("Cannot write to meta table, which keeps the columns unknown to the schema");
+        }
+        let schema = ctx.config.inventory_schema_declaration.clone();
+        let chunks = rows.chunks(IMPORT_CHUNK_ROWS).collect::<Vec<_>>();
+        let max_errors = match self.validate_only {
+            true => usize::MAX,
+            false => IMPORT_CHUNK_ERRORS,
+        };
+        let next_chunk = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::sync_channel(self.workers * 2);
+        return std::thread::scope(|scope| {
+            for _ in 0..self.workers {
+                let sender = sender.clone();
+                let (chunks, next_chunk) = (&chunks, &next_chunk);
+                let (columns, schema, user) = (&columns, &schema, &user);
+                scope.spawn(move || loop {
+                    let i = next_chunk.fetch_add(1, Ordering::Relaxed);
+                    if i >= chunks.len() {
+                        break;
+                    }
+                    let parsed =
+                        parse_import_chunk(i, chunks[i], columns, schema, user, extra, max_errors);
+                    // The writer stopped at an error, the remaining chunks are not needed
+                    if sender.send((i, parsed)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+            return self.write_chunks(ctx, &user, chunks.len(), rows.len(), receiver);
+        });
+    }
+
+    /**
+     * Adds the entities of the parsed chunks in order of the file, committing every batch.
+     */
+    fn write_chunks(
+        &self,
+        ctx: &mut CommandContext,
+        user: &DBUser,
+        chunks: usize,
+        total: usize,
+        receiver: mpsc::Receiver<(usize, ImportChunk)>,
+    ) -> Result<String> {
+        let mut pending = BTreeMap::new();
+        let mut imported = 0;
+        // Number of entities and the row up to which they are committed
+        let mut committed = (0, 1);
+        let mut in_batch = 0;
+        let mut errors = vec![];
+        let label = match self.validate_only {
+            true => "Validating rows",
+            false => "Importing rows",
+        };
+        let mut progress = Progress::start("import", label, Some(total as u64));
+        ctx.db.batch_begin()?;
+        for i in 0..chunks {
+            let parsed = loop {
+                if let Some(parsed) = pending.remove(&i) {
+                    break parsed;
+                }
+                match receiver.recv() {
+                    Ok((j, parsed)) => pending.insert(j, parsed),
+                    Err(_) => {
+                        ctx.db.batch_rollback()?;
+                        bail!("A worker of the import stopped unexpectedly");
+                    }
+                };
+            };
+            let entities = match parsed {
+                Ok(_) if !errors.is_empty() && !self.validate_only => continue,
+                Ok(entities) => entities,
+                // Without batches nothing is imported anyway, so every chunk's errors are listed
+                Err(chunk_errors) => {
+                    errors.extend(chunk_errors);
+                    if self.validate_only || self.batch_size == 0 {
+                        continue;
+                    }
+                    break;
+                }
+            };
+            for (row, entity, meta) in entities {
+                let added = match meta.is_empty() {
+                    true => ctx.db.inventory_add(&entity, ctx.config, user),
+                    false => ctx
+                        .db
+                        .inventory_add_with_meta(&entity, &meta, ctx.config, user),
+                };
+                if let Err(e) = added {
+                    if self.validate_only {
+                        errors.push(format!("Row {}: {}", row, e));
+                        continue;
+                    }
+                    ctx.db.batch_rollback()?;
+                    bail!("Row {}: {}\n{}", row, e, import_outcome(committed));
+                }
+                imported += 1;
+                in_batch += 1;
+                if in_batch == self.batch_size && !self.validate_only {
+                    ctx.db.batch_commit()?;
+                    ctx.db.batch_begin()?;
+                    committed = (imported, row);
+                    in_batch = 0;
+                }
+            }
+            progress.set(((i + 1) * IMPORT_CHUNK_ROWS).min(total) as u64);
+        }
+        if self.validate_only {
+            ctx.db.batch_rollback()?;
+            if !errors.is_empty() {
+                bail!(
+                    "{}\n{} rows are invalid, nothing was imported",
+                    errors.join("\n"),
+                    errors.len()
+                );
+            }
+            return Ok(format!("{} rows are valid, nothing was imported", imported));
+        }
+        if !errors.is_empty() {
+            ctx.db.batch_rollback()?;
+            bail!("{}\n{}", errors.join("\n"), import_outcome(committed));
+        }
+        ctx.db.batch_commit()?;
+        return Ok(format!("{} entities were imported", imported));
+    }
+}
+
+/**
+ * Entities of the rows of a chunk with their row number and metadata, or the errors of its rows
+ */
+type ImportChunk =
+    std::result::Result<Vec<(usize, KeyValueCollection, BTreeMap<String, String>)>, Vec<String>>;
+
+/**
+ * Parses and validates the rows of a chunk like inventory add does, skipping empty rows.
+ */
+fn parse_import_chunk(
+    chunk: usize,
+    rows: &[Vec<String>],
+    columns: &[Option<String>],
+    schema: &SchemaCollection,
+    user: &DBUser,
+    allow_extra: bool,
+    max_errors: usize,
+) -> ImportChunk {
+    let mut entities = vec![];
+    let mut errors = vec![];
+    let mut invalid = 0;
+    for (i, row) in rows.iter().enumerate() {
+        // Counted from 1, after the header
+        let number = chunk * IMPORT_CHUNK_ROWS + i + 2;
+        let params = columns
+            .iter()
+            .zip(row.iter())
+            .filter_map(|(column, value)| match column {
+                Some(column) if !value.is_empty() => Some(format!("{}={}", column, value)),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+        if params.is_empty() {
+            continue;
+        }
+        let entity = plugins::transform_on_add(&params).and_then(|params| {
+            plugins::validate_entity(&params)?;
+            let (params, meta) = match allow_extra {
+                true => split_extra_params(&params, schema)?,
+                false => (params, BTreeMap::new()),
+            };
+            let entries: KeyValueCollection = params
+                .iter()
+                .map(|e| e.to_typed_key_value_entry(schema))
+                .collect::<Result<Vec<_>>>()?
+                .into();
+            if !user.can_write_collection("inventory", &entries) {
+                bail!("Cannot write arguments to inventory");
+            }
+            return Ok((with_context_defaults(entries, schema, user)?, meta));
+        });
+        match entity {
+            Ok((entity, meta)) => entities.push((number, entity, meta)),
+            Err(e) => {
+                invalid += 1;
+                if errors.len() < max_errors {
+                    errors.push(format!("Row {}: {}", number, e));
+                }
+            }
+        }
+    }
+    if invalid == 0 {
+        return Ok(entities);
+    }
+    if invalid > errors.len() {
+        errors.push(format!(
+            "{} more rows between row {} and {} are invalid",
+            invalid - errors.len(),
+            chunk * IMPORT_CHUNK_ROWS + 2,
+            chunk * IMPORT_CHUNK_ROWS + rows.len() + 1
+        ));
+    }
+    return Err(errors);
+}
+
+/**
+ * Tells which rows were imported before the import stopped at an error
+ */
+fn import_outcome((imported, row): (usize, usize)) -> String {
+    return match imported {
+        0 => "No entity of the file was imported".into(),
+        _ => format!(
+            "{} entities up to row {} were imported, the rows after it were not",
+            imported, row
+        ),
+    };
+}
+
+#[derive(Serialize)]
+pub struct SupplierProps<'a> {
+    pub name: &'a Option<String>,
+    pub contact: &'a Option<String>,
+    pub lead_time_days: Option<u32>,
+}
+
+pub struct SupplierAddArgs {
+    pub name: String,
+    pub contact: Option<String>,
+    pub lead_time_days: Option<u32>,
+}
+
+impl SupplierAddArgs {
+    pub fn add(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("supplier_add")?;
+        let props = SupplierProps {
+            name: &Some(self.name.clone()),
+            contact: &self.contact,
+            lead_time_days: self.lead_time_days,
+        };
+        return ctx.db.supplier_add(&props, &user);
+    }
+}
+
+pub struct SupplierListArgs;
+
+impl SupplierListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("supplier_list")?;
+        return Ok(serde_json::to_string(&ctx.db.supplier_list()?)?);
+    }
+}
+
+pub struct SupplierEditArgs {
+    pub identifier: String,
+    pub name: Option<String>,
+    pub contact: Option<String>,
+    pub lead_time_days: Option<u32>,
+}
+
+impl SupplierEditArgs {
+    pub fn edit(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("supplier_edit")?;
+        let props = SupplierProps {
+            name: &self.name,
+            contact: &self.contact,
+            lead_time_days: self.lead_time_days,
+        };
+        return ctx.db.supplier_edit(&self.identifier, &props, &user);
+    }
+}
+
+pub struct SupplierRemoveArgs {
+    pub identifier: String,
+}
+
+impl SupplierRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("supplier_remove")?;
+        return ctx.db.supplier_remove(&self.identifier, &user);
+    }
+}
+
+pub struct SupplierLinkArgs {
+    pub identifier: String,
+    pub entity: String,
+}
+
+impl SupplierLinkArgs {
+    pub fn link(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("supplier_link")?;
+        return ctx.db.supplier_link(&self.identifier, &self.entity, &user);
+    }
+
+    pub fn unlink(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("supplier_unlink")?;
+        return ctx
+            .db
+            .supplier_unlink(&self.identifier, &self.entity, &user);
+    }
+}
+
+pub struct CategoryCreateArgs {
+    pub path: String,
+}
+
+impl CategoryCreateArgs {
+    pub fn create(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("category_create")?;
+        return ctx.db.category_create(&self.path, &user);
+    }
+}
+
+pub struct CategoryListArgs;
+
+impl CategoryListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("category_list")?;
+        return Ok(serde_json::to_string(&ctx.db.category_list()?)?);
+    }
+}
+
+pub struct CategoryRemoveArgs {
+    pub path: String,
+}
+
+impl CategoryRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("category_remove")?;
+        return ctx.db.category_remove(&self.path, &user);
+    }
+}
+
+pub struct CategoryAssignArgs {
+    pub identifier: String,
+    pub path: String,
+}
+
+impl CategoryAssignArgs {
+    pub fn assign(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("category_assign")?;
+        return ctx.db.category_assign(&self.identifier, &self.path, &user);
+    }
+}
+
+pub struct CategoryUnassignArgs {
+    pub identifier: String,
+}
+
+impl CategoryUnassignArgs {
+    pub fn unassign(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("category_unassign")?;
+        return ctx.db.category_unassign(&self.identifier, &user);
+    }
+}
+
+#[derive(Serialize)]
+pub struct LoanProps<'a> {
+    pub borrower: &'a str,
+    pub due_at: Option<String>,
+}
+
+pub struct InventoryCheckoutArgs {
+    pub identifier: String,
+    pub to: String,
+    pub due: Option<String>,
+}
+
+impl InventoryCheckoutArgs {
+    pub fn checkout(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("inventory_checkout")?;
+        if self.to.trim().is_empty() {
+            bail!("The borrower of a checkout cannot be empty");
+        }
+        let due_at = match &self.due {
+            Some(due) => match NaiveDate::parse_from_str(due, "%Y-%m-%d") {
+                Ok(date) => Some(date.format("%Y-%m-%d").to_string()),
+                Err(_) => bail!("Due date '{}' is not in YYYY-MM-DD format", due),
+            },
+            None => None,
+        };
+        let props = LoanProps {
+            borrower: &self.to,
+            due_at,
+        };
+        return ctx.db.inventory_checkout(&self.identifier, &props, &user);
+    }
+}
+
+pub struct InventoryCheckinArgs {
+    pub identifier: String,
+}
+
+impl InventoryCheckinArgs {
+    pub fn checkin(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("inventory_checkin")?;
+        return ctx.db.inventory_checkin(&self.identifier, &user);
+    }
+}
+
+pub struct LoanListArgs {
+    pub overdue_only: bool,
+}
+
+impl LoanListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("loan_list")?;
+        return Ok(serde_json::to_string(
+            &ctx.db.loan_list(self.overdue_only)?,
+        )?);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SerialStatus {
+    InStock,
+    Sold,
+    Rma,
+}
+
+impl fmt::Display for SerialStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerialStatus::InStock => write!(f, "in-stock"),
+            SerialStatus::Sold => write!(f, "sold"),
+            SerialStatus::Rma => write!(f, "rma"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AdjustProps<'a> {
+    pub quantity: i64,
+    pub location: &'a str,
+    pub serials: &'a Vec<String>,
+    pub status: SerialStatus,
+    /**
+     * Cost of a single unit booked into the stock, which the valuation is based on
+     */
+    pub unit_cost: Option<f64>,
+    /**
+     * Currency of the unit cost
+     */
+    pub currency: Option<String>,
+    /**
+     * One of the configured reason codes
+     */
+    pub reason: Option<String>,
+}
+
+pub struct InventoryAssignArgs {
+    pub identifier: String,
+    pub to: String,
+}
+
+impl InventoryAssignArgs {
+    pub fn assign(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("inventory_assign")?;
+        let assignee = ctx.db.user_id(&self.to)?;
+        return ctx.db.inventory_assign(&self.identifier, assignee, &user);
+    }
+}
+
+pub struct InventoryUnassignArgs {
+    pub identifier: String,
+}
+
+impl InventoryUnassignArgs {
+    pub fn unassign(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("inventory_unassign")?;
+        return ctx.db.inventory_unassign(&self.identifier, &user);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PurchaseProps {
+    pub purchased_at: String,
+    pub price: f64,
+    pub warranty_months: Option<u32>,
+    pub useful_life_months: Option<u32>,
+    pub currency: Option<String>,
+}
+
+/**
+ * Currency an amount is recorded in, which is the base currency if none is given
+ */
+fn amount_currency(currency: &Option<String>, config: &AppConfig) -> Result<Option<String>> {
+    return match currency {
+        Some(currency) => Ok(Some(parse_currency(currency)?)),
+        None => Ok(config.exchange_rates.base.clone()),
+    };
+}
+
+pub struct InventoryPurchaseArgs {
+    pub identifier: String,
+    pub date: String,
+    pub price: f64,
+    pub warranty_months: Option<u32>,
+    pub useful_life_months: Option<u32>,
+    /**
+     * Currency of the price, the base currency if None
+     */
+    pub currency: Option<String>,
+}
+
+impl InventoryPurchaseArgs {
+    pub fn purchase(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("purchase_set")?;
+        ctx.check_owner(&user, &self.identifier)?;
+        let purchased_at = match NaiveDate::parse_from_str(&self.date, "%Y-%m-%d") {
+            Ok(date) => date.format("%Y-%m-%d").to_string(),
+            Err(_) => bail!("Purchase date '{}' is not in YYYY-MM-DD format", self.date),
+        };
+        if !self.price.is_finite() || self.price < 0.0 {
+            bail!("Purchase price must be a positive number");
+        }
+        if self.useful_life_months == Some(0) {
+            bail!("Useful life must be at least one month");
+        }
+        let props = PurchaseProps {
+            purchased_at,
+            price: self.price,
+            warranty_months: self.warranty_months,
+            useful_life_months: self.useful_life_months,
+            currency: amount_currency(&self.currency, ctx.config)?,
+        };
+        return ctx.db.purchase_set(&self.identifier, &props, &user);
+    }
+}
+
+#[derive(Serialize)]
+struct WarrantyReportEntry {
+    inventory_id: u32,
+    purchased_at: String,
+    warranty_months: u32,
+    warranty_expires_at: String,
+}
+
+pub struct WarrantyReportArgs {
+    pub within: String,
+    /**
+     * File the report is written to with the PDF output
+     */
+    pub out: Option<String>,
+}
+
+impl WarrantyReportArgs {
+    pub fn report(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("purchase_list")?;
+        let today = Local::now().date_naive();
+        let until = today + chrono::Duration::days(parse_days(&self.within)?.into());
+        let mut entries = vec![];
+        for purchase in ctx.db.purchase_list()? {
+            let warranty_months = match purchase.warranty_months {
+                Some(months) => months,
+                None => continue,
+            };
+            let expires_at = NaiveDate::parse_from_str(&purchase.purchased_at, "%Y-%m-%d")?
+                .checked_add_months(Months::new(warranty_months));
+            match expires_at {
+                Some(expires_at) if expires_at >= today && expires_at <= until => {
+                    entries.push(WarrantyReportEntry {
+                        inventory_id: purchase.inventory_id,
+                        purchased_at: purchase.purchased_at,
+                        warranty_months,
+                        warranty_expires_at: expires_at.format("%Y-%m-%d").to_string(),
+                    })
+                }
+                _ => continue,
+            }
+        }
+        entries.sort_by(|a, b| a.warranty_expires_at.cmp(&b.warranty_expires_at));
+        if ctx.output == OutputType::Pdf {
+            let document = ReportDocument {
+                title: "Expiring warranties".into(),
+                filters: vec![("expiring within".into(), self.within.clone())],
+                generated_at: report_generated_at(ctx)?,
+                columns: ["Entity", "Purchased at", "Warranty months", "Expires at"]
+                    .map(String::from)
+                    .to_vec(),
+                rows: entries
+                    .iter()
+                    .map(|e| {
+                        vec![
+                            e.inventory_id.to_string(),
+                            e.purchased_at.clone(),
+                            e.warranty_months.to_string(),
+                            e.warranty_expires_at.clone(),
+                        ]
+                    })
+                    .collect(),
+                summary: vec![("Entities".into(), entries.len().to_string())],
+            };
+            return write_report_pdf(&document, self.out.as_deref());
+        }
+        return Ok(serde_json::to_string(&entries)?);
+    }
+}
+
+#[derive(Serialize)]
+struct ValuationEntry {
+    inventory_id: u32,
+    purchased_at: String,
+    purchase_price: f64,
+    useful_life_months: Option<u32>,
+    months_elapsed: u32,
+    depreciation: f64,
+    book_value: f64,
+}
+
+#[derive(Serialize)]
+struct ValuationReport {
+    /**
+     * Currency all amounts were converted into
+     */
+    currency: Option<String>,
+    entities: Vec<ValuationEntry>,
+    total_purchase_price: f64,
+    total_book_value: f64,
+}
+
+/**
+ * How the valuation report values the entities
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValuationMethod {
+    /**
+     * Purchase price of the entity, less its straight-line depreciation
+     */
+    Depreciation,
+    /**
+     * Stock valued at the unit costs of its latest receipts, as the oldest ones are used first
+     */
+    Fifo,
+    /**
+     * Stock valued at the weighted average unit cost of all its receipts
+     */
+    Average,
+}
+
+/**
+ * Receipts of an entity's stock, of which the oldest are issued first
+ */
+#[derive(Default)]
+struct StockReceipts {
+    /**
+     * Receipts still in stock as quantity and unit cost, oldest first
+     */
+    in_stock: VecDeque<(i64, Option<f64>)>,
+    /**
+     * Quantity of all receipts with a unit cost
+     */
+    costed_quantity: i64,
+    /**
+     * Cost of all receipts with a unit cost
+     */
+    cost: f64,
+}
+
+#[derive(Serialize)]
+struct StockValuationEntry {
+    inventory_id: u32,
+    quantity: i64,
+    unit_cost: Option<f64>,
+    value: f64,
+    /**
+     * Quantity in stock that was received without a unit cost and thus has no value
+     */
+    uncosted_quantity: i64,
+}
+
+#[derive(Serialize)]
+struct StockValuationReport {
+    currency: Option<String>,
+    entities: Vec<StockValuationEntry>,
+    total_value: f64,
+}
+
+pub struct ValuationReportArgs {
+    pub method: ValuationMethod,
+    /**
+     * File the report is written to with the PDF output
+     */
+    pub out: Option<String>,
+    /**
+     * Currency the amounts are converted into, the base currency if None
+     */
+    pub currency: Option<String>,
+}
+
+impl ValuationReportArgs {
+    /**
+     * Currency the amounts are converted into. Without one, the amounts are taken as they are,
+     * which requires them to be in a single currency.
+     */
+    fn report_currency<'a>(
+        &self,
+        rates: &ExchangeRates,
+        mut currencies: impl Iterator<Item = &'a str>,
+    ) -> Result<Option<String>> {
+        if let Some(currency) = &self.currency {
+            return Ok(Some(parse_currency(currency)?));
+        }
+        if let Some(base) = &rates.base {
+            return Ok(Some(base.clone()));
+        }
+        let first = currencies.next();
+        if let Some(other) = currencies.find(|e| Some(*e) != first) {
+            bail!(
+                "Amounts are in {} and {}, set a base currency with config rates base or pass --currency",
+                first.unwrap_or_default(),
+                other
+            );
+        }
+        return Ok(first.map(String::from));
+    }
+
+    /**
+     * Converts the amount into the currency of the report, amounts recorded without a
+     * currency being in the base currency
+     */
+    fn convert(
+        rates: &ExchangeRates,
+        amount: f64,
+        from: Option<&str>,
+        to: &Option<String>,
+    ) -> Result<f64> {
+        return match to {
+            Some(to) => rates.convert(amount, from, to),
+            None => Ok(amount),
+        };
+    }
+
+    /**
+     * Straight-line depreciation, i.e. the purchase price loses an equal share for every full
+     * month of its useful life that has passed. Entities without a useful life keep their value.
+     */
+    fn depreciate(price: f64, useful_life_months: Option<u32>, months_elapsed: u32) -> f64 {
+        return match useful_life_months {
+            Some(life) => price * f64::from(months_elapsed.min(life)) / f64::from(life),
+            None => 0.0,
+        };
+    }
+
+    /**
+     * Number of full months from the purchase until the given date
+     */
+    fn months_elapsed(purchased_at: NaiveDate, until: NaiveDate) -> u32 {
+        let mut months_elapsed = 0;
+        while purchased_at
+            .checked_add_months(Months::new(months_elapsed + 1))
+            .is_some_and(|date| date <= until)
+        {
+            months_elapsed += 1;
+        }
+        return months_elapsed;
+    }
+
+    fn round_cents(value: f64) -> f64 {
+        return (value * 100.0).round() / 100.0;
+    }
+
+    /**
+     * Values the stock of every entity by the unit costs of its receipts, without taking
+     * transfers between locations into account.
+     */
+    fn stock_valuation(
+        &self,
+        ledger: Vec<DBLedgerEntry>,
+        currency: Option<String>,
+    ) -> StockValuationReport {
+        let mut stock: BTreeMap<u32, StockReceipts> = BTreeMap::new();
+        for entry in ledger {
+            let receipts = stock.entry(entry.inventory_id).or_default();
+            if entry.quantity > 0 {
+                receipts
+                    .in_stock
+                    .push_back((entry.quantity, entry.unit_cost));
+                if let Some(unit_cost) = entry.unit_cost {
+                    receipts.costed_quantity += entry.quantity;
+                    receipts.cost += unit_cost * entry.quantity as f64;
+                }
+                continue;
+            }
+            let mut issued = -entry.quantity;
+            while let Some(oldest) = receipts.in_stock.front_mut() {
+                let taken = issued.min(oldest.0);
+                oldest.0 -= taken;
+                issued -= taken;
+                if oldest.0 == 0 {
+                    receipts.in_stock.pop_front();
+                }
+                if issued == 0 {
+                    break;
+                }
+            }
+        }
+
+        let mut entities = vec![];
+        for (inventory_id, receipts) in stock {
+            let quantity = receipts
+                .in_stock
+                .iter()
+                .map(|(quantity, _)| quantity)
+                .sum::<i64>();
+            if quantity == 0 {
+                continue;
+            }
+            let (unit_cost, uncosted_quantity) = match self.method {
+                ValuationMethod::Average => match receipts.costed_quantity > 0 {
+                    true => (Some(receipts.cost / receipts.costed_quantity as f64), 0),
+                    false => (None, quantity),
+                },
+                _ => {
+                    let uncosted_quantity = receipts
+                        .in_stock
+                        .iter()
+                        .filter(|(_, unit_cost)| unit_cost.is_none())
+                        .map(|(quantity, _)| quantity)
+                        .sum::<i64>();
+                    let value = receipts
+                        .in_stock
+                        .iter()
+                        .filter_map(|(quantity, unit_cost)| unit_cost.map(|e| e * *quantity as f64))
+                        .sum::<f64>();
+                    match quantity > uncosted_quantity {
+                        true => (
+                            Some(value / (quantity - uncosted_quantity) as f64),
+                            uncosted_quantity,
+                        ),
+                        false => (None, uncosted_quantity),
+                    }
+                }
+            };
+            let value = unit_cost.unwrap_or(0.0) * (quantity - uncosted_quantity) as f64;
+            entities.push(StockValuationEntry {
+                inventory_id,
+                quantity,
+                unit_cost: unit_cost.map(Self::round_cents),
+                value: Self::round_cents(value),
+                uncosted_quantity,
+            });
+        }
+        return StockValuationReport {
+            currency,
+            total_value: Self::round_cents(entities.iter().fold(0.0, |sum, e| sum + e.value)),
+            entities,
+        };
+    }
+
+    pub fn report(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authenticate()?;
+        if self.method != ValuationMethod::Depreciation {
+            require_read(&user, "stock")?;
+            let rates = &ctx.config.exchange_rates;
+            let mut ledger = ctx.db.stock_ledger()?;
+            let currency = self.report_currency(
+                rates,
+                ledger
+                    .iter()
+                    .filter(|e| e.unit_cost.is_some())
+                    .filter_map(|e| e.currency.as_deref()),
+            )?;
+            for entry in ledger.iter_mut() {
+                if let Some(unit_cost) = entry.unit_cost {
+                    entry.unit_cost = Some(Self::convert(
+                        rates,
+                        unit_cost,
+                        entry.currency.as_deref(),
+                        &currency,
+                    )?);
+                }
+            }
+            let report = self.stock_valuation(ledger, currency);
+            if ctx.output == OutputType::Pdf {
+                let format = &ctx.config.display_format;
+                let money = |value: f64| format.number(&format!("{:.2}", value));
+                let method = match self.method {
+                    ValuationMethod::Fifo => "FIFO",
+                    _ => "average cost",
+                };
+                let mut filters = vec![("method".into(), method.into())];
+                if let Some(currency) = &report.currency {
+                    filters.push(("currency".into(), currency.clone()));
+                }
+                let document = ReportDocument {
+                    title: "Stock valuation".into(),
+                    filters,
+                    generated_at: report_generated_at(ctx)?,
+                    columns: [
+                        "Entity",
+                        "Quantity",
+                        "Unit cost",
+                        "Value",
+                        "Quantity without cost",
+                    ]
+                    .map(String::from)
+                    .to_vec(),
+                    rows: report
+                        .entities
+                        .iter()
+                        .map(|e| {
+                            vec![
+                                e.inventory_id.to_string(),
+                                format.number(&e.quantity.to_string()),
+                                e.unit_cost.map(money).unwrap_or_default(),
+                                money(e.value),
+                                format.number(&e.uncosted_quantity.to_string()),
+                            ]
+                        })
+                        .collect(),
+                    summary: vec![("Total value".into(), money(report.total_value))],
+                };
+                return write_report_pdf(&document, self.out.as_deref());
+            }
+            return Ok(serde_json::to_string(&report)?);
+        }
+        require_read(&user, "purchases")?;
+        let today = Local::now().date_naive();
+        let rates = &ctx.config.exchange_rates;
+        let purchases = ctx.db.purchase_list()?;
+        let currency = self.report_currency(
+            rates,
+            purchases.iter().filter_map(|e| e.currency.as_deref()),
+        )?;
+        let mut entities = vec![];
+        for purchase in purchases {
+            let purchased_at = NaiveDate::parse_from_str(&purchase.purchased_at, "%Y-%m-%d")?;
+            let months_elapsed = Self::months_elapsed(purchased_at, today);
+            let price = Self::round_cents(Self::convert(
+                rates,
+                purchase.price,
+                purchase.currency.as_deref(),
+                &currency,
+            )?);
+            // Rounded to cents, so that book values add up in exports
+            let depreciation = Self::round_cents(Self::depreciate(
+                price,
+                purchase.useful_life_months,
+                months_elapsed,
+            ));
+            entities.push(ValuationEntry {
+                inventory_id: purchase.inventory_id,
+                purchased_at: purchase.purchased_at,
+                purchase_price: price,
+                useful_life_months: purchase.useful_life_months,
+                months_elapsed,
+                depreciation,
+                book_value: Self::round_cents(price - depreciation),
+            });
+        }
+        let report = ValuationReport {
+            currency,
+            total_purchase_price: Self::round_cents(
+                entities.iter().map(|e| e.purchase_price).sum(),
+            ),
+            total_book_value: Self::round_cents(entities.iter().map(|e| e.book_value).sum()),
+            entities,
+        };
+        if ctx.output == OutputType::Pdf {
+            let format = &ctx.config.display_format;
+            let money = |value: f64| format.number(&format!("{:.2}", value));
+            let document = ReportDocument {
+                title: "Valuation".into(),
+                filters: report
+                    .currency
+                    .iter()
+                    .map(|e| ("currency".into(), e.clone()))
+                    .collect(),
+                generated_at: report_generated_at(ctx)?,
+                columns: [
+                    "Entity",
+                    "Purchased at",
+                    "Purchase price",
+                    "Useful life months",
+                    "Months elapsed",
+                    "Depreciation",
+                    "Book value",
+                ]
+                .map(String::from)
+                .to_vec(),
+                rows: report
+                    .entities
+                    .iter()
+                    .map(|e| {
+                        vec![
+                            e.inventory_id.to_string(),
+                            e.purchased_at.clone(),
+                            money(e.purchase_price),
+                            e.useful_life_months
+                                .map(|e| e.to_string())
+                                .unwrap_or_default(),
+                            e.months_elapsed.to_string(),
+                            money(e.depreciation),
+                            money(e.book_value),
+                        ]
+                    })
+                    .collect(),
+                summary: vec![
+                    (
+                        "Total purchase price".into(),
+                        money(report.total_purchase_price),
+                    ),
+                    ("Total book value".into(), money(report.total_book_value)),
+                ],
+            };
+            return write_report_pdf(&document, self.out.as_deref());
+        }
+        return Ok(serde_json::to_string(&report)?);
+    }
+}
+
+pub struct HtmlReportArgs {
+    pub out: String,
+    /**
+     * Column the entities are grouped by in the stock chart, which is left out if not given
+     */
+    pub category: Option<String>,
+    /**
+     * Number of past months the value trend covers
+     */
+    pub months: u32,
+}
+
+impl HtmlReportArgs {
+    pub fn report(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authenticate()?;
+        if self.months == 0 {
+            bail!("The value trend must cover at least one month");
+        }
+        let format = &ctx.config.display_format;
+        let mut charts = vec![];
+        let mut tables = vec![];
+
+        if let Some(category) = &self.category {
+            if user.is_read_denied("inventory", category) {
+                bail!("Cannot read column {} of inventory", category);
+            }
+            let values = ctx.db.inventory_distinct(
+                category,
+                &KeyValueCollection::new(vec![]),
+                ctx.config,
+            )?;
+            let mut points = vec![];
+            for value in values {
+                let (label, count) = match value.collection.as_slice() {
+                    [label, count] => (label, count),
+                    _ => continue,
+                };
+                let count = count.to_plain_value().unwrap_or_default();
+                points.push(ChartPoint {
+                    label: label.to_plain_value().unwrap_or_else(|| "(none)".into()),
+                    value: count.parse().unwrap_or(0.0),
+                    display: format.number(&count),
+                });
+            }
+            charts.push(Chart {
+                title: format!("Stock by {}", category),
+                kind: ChartKind::Bar,
+                points,
+            });
+        }
+
+        // Purchases are optional, the page only shows what the user may read
+        if user.can_read_table("purchases") {
+            let purchases = ctx.db.purchase_list()?;
+            let today = Local::now().date_naive();
+            let mut points = vec![];
+            for months_ago in (0..self.months).rev() {
+                // Every month is valued at its end, the current one as of today
+                let until = match months_ago {
+                    0 => today,
+                    _ => match today
+                        .with_day(1)
+                        .and_then(|e| e.checked_sub_months(Months::new(months_ago - 1)))
+                        .and_then(|e| e.pred_opt())
+                    {
+                        Some(until) => until,
+                        None => continue,
+                    },
+                };
+                let mut value = 0.0;
+                for purchase in &purchases {
+                    let purchased_at =
+                        NaiveDate::parse_from_str(&purchase.purchased_at, "%Y-%m-%d")?;
+                    if purchased_at > until {
+                        continue;
+                    }
+                    value += purchase.price
+                        - ValuationReportArgs::round_cents(ValuationReportArgs::depreciate(
+                            purchase.price,
+                            purchase.useful_life_months,
+                            ValuationReportArgs::months_elapsed(purchased_at, until),
+                        ));
+                }
+                let value = ValuationReportArgs::round_cents(value);
+                points.push(ChartPoint {
+                    label: until.format("%Y-%m").to_string(),
+                    value,
+                    display: format.number(&format!("{:.2}", value)),
+                });
+            }
+            charts.push(Chart {
+                title: "Book value".into(),
+                kind: ChartKind::Line,
+                points,
+            });
+        }
+
+        let mut entities = ctx.db.inventory_list(
+            &InventoryListProps {
+                limit: -1,
+                after_id: None,
+                fuzzy: None,
+                fuzzy_threshold: 0.0,
+                with_audit: false,
+                with_attachments: false,
+                include_archived: false,
+                supplier: None,
+                assigned_to: None,
+                category: None,
+                recursive: false,
+                raw: &None,
+                params: &vec![],
+                changed_since: None,
+                filter: None,
+                group: None,
+                expand: vec![],
+            },
+            ctx.config,
+        )?;
+        for entry in entities.iter_mut() {
+            user.mask_collection("inventory", entry);
+        }
+        let (columns, rows) =
+            entities.to_display_rows(format, &parse_timezone(&ctx.config.timezone)?);
+        tables.push(ReportDocument {
+            title: "Entities".into(),
+            filters: vec![],
+            generated_at: "".into(),
+            columns,
+            summary: vec![("Entities".into(), rows.len().to_string())],
+            rows,
+        });
+
+        let dashboard = Dashboard {
+            title: "Inventory".into(),
+            generated_at: report_generated_at(ctx)?,
+            charts,
+            tables,
+            decimal_separator: format.decimal_separator.clone(),
+            thousands_separator: format.thousands_separator.clone(),
+        };
+        if let Err(e) = fs::write(&self.out, dashboard.to_html()) {
+            bail!("Could not write file '{}' ({})", self.out, e);
+        }
+        return Ok(format!("Report was written to '{}'", self.out));
+    }
+}
+
+/**
+ * The current time in the configured timezone and display format, stamped on printed reports
+ */
+fn report_generated_at(ctx: &CommandContext) -> Result<String> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    return Ok(ctx
+        .config
+        .display_format
+        .timestamp(&now, &parse_timezone(&ctx.config.timezone)?));
+}
+
+fn write_report_pdf(document: &ReportDocument, out: Option<&str>) -> Result<String> {
+    let out = match out {
+        Some(out) => out,
+        None => bail!("The PDF output needs the file to write the report to, given by --out"),
+    };
+    if let Err(e) = fs::write(out, document.to_pdf()) {
+        bail!("Could not write file '{}' ({})", out, e);
+    }
+    return Ok(format!("Report was written to '{}'", out));
+}
+
+#[derive(Serialize)]
+pub struct TransferProps<'a> {
+    pub quantity: i64,
+    pub from: &'a str,
+    pub to: &'a str,
+    pub serials: &'a Vec<String>,
+}
+
+pub struct InventoryAdjustArgs {
+    pub identifier: String,
+    pub quantity: i64,
+    pub location: String,
+    pub serials: Vec<String>,
+    pub status: SerialStatus,
+    pub unit_cost: Option<f64>,
+    /**
+     * Currency of the unit cost, the base currency if None
+     */
+    pub currency: Option<String>,
+    /**
+     * One of the configured reason codes
+     */
+    pub reason: Option<String>,
+}
+
+impl InventoryAdjustArgs {
+    pub fn adjust(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("stock_adjust")?;
+        if self.quantity > 0 && self.status != SerialStatus::InStock {
+            bail!("Serials booked into stock always have the status in-stock");
+        }
+        if self.quantity < 0 && self.status == SerialStatus::InStock {
+            bail!("Serials booked out of stock cannot keep the status in-stock");
+        }
+        if let Some(unit_cost) = self.unit_cost {
+            if self.quantity < 0 {
+                bail!("A unit cost can only be given when adding to the stock");
+            }
+            if !unit_cost.is_finite() || unit_cost < 0.0 {
+                bail!("Unit cost must be a positive number");
+            }
+        } else if self.currency.is_some() {
+            bail!("A currency can only be given together with a unit cost");
+        }
+        let props = AdjustProps {
+            quantity: self.quantity,
+            location: &self.location,
+            serials: &self.serials,
+            status: self.status,
+            unit_cost: self.unit_cost,
+            currency: match self.unit_cost {
+                Some(_) => amount_currency(&self.currency, ctx.config)?,
+                None => None,
+            },
+            reason: self.reason.clone(),
+        };
+        return ctx
+            .db
+            .stock_adjust(&self.identifier, &props, ctx.config, &user);
+    }
+}
+
+pub struct InventoryTransferArgs {
+    pub identifier: String,
+    pub quantity: i64,
+    pub from: String,
+    pub to: String,
+    pub serials: Vec<String>,
+}
+
+impl InventoryTransferArgs {
+    pub fn transfer(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("stock_transfer")?;
+        let props = TransferProps {
+            quantity: self.quantity,
+            from: &self.from,
+            to: &self.to,
+            serials: &self.serials,
+        };
+        return ctx
+            .db
+            .stock_transfer(&self.identifier, &props, ctx.config, &user);
+    }
+}
+
+pub struct InventoryStockArgs {
+    pub identifier: String,
+}
+
+impl InventoryStockArgs {
+    pub fn stock(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("stock_list")?;
+        return Ok(serde_json::to_string(
+            &ctx.db.stock_list(&self.identifier)?,
+        )?);
+    }
+}
+
+/**
+ * Length of the periods a time series is divided into
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrendInterval {
+    Day,
+    /**
+     * Weeks starting on Monday
+     */
+    Week,
+    Month,
+}
+
+impl TrendInterval {
+    fn period_start(&self, date: NaiveDate) -> NaiveDate {
+        return match self {
+            TrendInterval::Day => date,
+            TrendInterval::Week => {
+                date - chrono::Duration::days(date.weekday().num_days_from_monday().into())
+            }
+            TrendInterval::Month => date.with_day(1).unwrap_or(date),
+        };
+    }
+
+    fn next_period(&self, start: NaiveDate) -> Option<NaiveDate> {
+        return match self {
+            TrendInterval::Day => start.succ_opt(),
+            TrendInterval::Week => start.checked_add_days(chrono::Days::new(7)),
+            TrendInterval::Month => start.checked_add_months(Months::new(1)),
+        };
+    }
+}
+
+#[derive(Serialize)]
+struct StockTrendPoint {
+    /**
+     * First day of the period
+     */
+    period: String,
+    added: i64,
+    removed: i64,
+    /**
+     * Quantity in stock at the end of the period
+     */
+    quantity: i64,
+}
+
+pub struct StockTrendArgs {
+    pub identifier: String,
+    pub interval: TrendInterval,
+    /**
+     * Only follows the stock at the location, the total stock if not given
+     */
+    pub location: Option<String>,
+    pub format: ExportFormat,
+}
+
+impl StockTrendArgs {
+    pub fn report(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("stock_movements")?;
+        if !ctx.db.inventory_exists(&self.identifier)? {
+            bail!("Entity {} does not exist", self.identifier);
+        }
+        let timezone = parse_timezone(&ctx.config.timezone)?;
+        let mut movements = vec![];
+        for movement in ctx
+            .db
+            .stock_movements(&self.identifier, self.location.as_deref())?
+        {
+            let date = NaiveDateTime::parse_from_str(&movement.created_at, "%Y-%m-%d %H:%M:%S%.f")?
+                .and_utc()
+                .with_timezone(&timezone)
+                .date_naive();
+            movements.push((date, movement));
+        }
+
+        let mut points = vec![];
+        if let Some((first, _)) = movements.first() {
+            let today = chrono::Utc::now().with_timezone(&timezone).date_naive();
+            let last = self.interval.period_start(today);
+            let mut start = self.interval.period_start(*first);
+            let mut movements = movements.iter().peekable();
+            let mut quantity = 0;
+            while start <= last {
+                let end = self.interval.next_period(start);
+                let mut point = StockTrendPoint {
+                    period: start.format("%Y-%m-%d").to_string(),
+                    added: 0,
+                    removed: 0,
+                    quantity,
+                };
+                while let Some((_, movement)) =
+                    movements.next_if(|(date, _)| end.is_none_or(|end| *date < end))
+                {
+                    point.quantity += movement.quantity;
+                    // Both halves of a transfer cancel out in the total stock
+                    if movement.transfer && self.location.is_none() {
+                        continue;
+                    }
+                    match movement.quantity > 0 {
+                        true => point.added += movement.quantity,
+                        false => point.removed -= movement.quantity,
+                    }
+                }
+                quantity = point.quantity;
+                points.push(point);
+                start = match end {
+                    Some(end) => end,
+                    None => break,
+                };
+            }
+        }
+
+        return format_rows(&points, self.format);
+    }
+}
+
+#[derive(Serialize)]
+struct ReorderSuggestion {
+    inventory_id: u32,
+    quantity: i64,
+    average_daily_consumption: f64,
+    lead_time_days: u32,
+    /**
+     * Quantity at which to reorder, covering the consumption during the lead time and the
+     * safety days
+     */
+    reorder_point: i64,
+    /**
+     * Quantity to reorder, covering the consumption of the cover days
+     */
+    reorder_quantity: i64,
+    reorder_now: bool,
+}
+
+pub struct ReorderSuggestArgs {
+    /**
+     * Duration the average consumption is calculated over, e.g. 90d
+     */
+    pub window: String,
+    /**
+     * Lead time of entities without a linked supplier that has one
+     */
+    pub lead_time_days: u32,
+    /**
+     * Days of consumption kept in stock as buffer against late deliveries and peaks
+     */
+    pub safety_days: u32,
+    /**
+     * Duration a reorder should cover, e.g. 30d
+     */
+    pub cover: String,
+    pub format: ExportFormat,
+}
+
+impl ReorderSuggestArgs {
+    pub fn report(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("stock_consumption")?;
+        let window = parse_days(&self.window)?;
+        let cover = parse_days(&self.cover)?;
+        let since = (chrono::Utc::now() - chrono::Duration::days(window.into()))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        let mut suggestions = vec![];
+        for entity in ctx.db.stock_consumption(&since)? {
+            // Without consumption there is nothing to base a suggestion on
+            if entity.consumed <= 0 {
+                continue;
+            }
+            // Simple moving average of the daily consumption within the window
+            let average = entity.consumed as f64 / f64::from(window);
+            let lead_time_days = entity.lead_time_days.unwrap_or(self.lead_time_days);
+            let reorder_point =
+                (average * f64::from(lead_time_days + self.safety_days)).ceil() as i64;
+            suggestions.push(ReorderSuggestion {
+                inventory_id: entity.inventory_id,
+                quantity: entity.quantity,
+                average_daily_consumption: (average * 100.0).round() / 100.0,
+                lead_time_days,
+                reorder_point,
+                reorder_quantity: (average * f64::from(cover)).ceil() as i64,
+                reorder_now: entity.quantity <= reorder_point,
+            });
+        }
+        suggestions.sort_by_key(|e| (!e.reorder_now, e.inventory_id));
+        return format_rows(&suggestions, self.format);
+    }
+}
+
+pub struct ReasonReportArgs {
+    /**
+     * Duration the movements and removals are summed up over, e.g. 90d, all of them if None
+     */
+    pub window: Option<String>,
+    pub format: ExportFormat,
+}
+
+impl ReasonReportArgs {
+    pub fn report(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("reason_summary")?;
+        let since = match &self.window {
+            Some(window) => Some(
+                (chrono::Utc::now() - chrono::Duration::days(parse_days(window)?.into()))
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            ),
+            None => None,
+        };
+        return format_rows(&ctx.db.reason_summary(since.as_deref())?, self.format);
+    }
+}
+
+/**
+ * Writes the rows as one JSON array, one JSON object per line or comma separated values with a
+ * header line.
+ */
+/**
+ * Formats the plans as JSON, or for plain output as each query followed by its indented plan.
+ */
+fn format_query_plans(plans: &[DBQueryPlan], output: OutputType) -> Result<String> {
+    if output != OutputType::Plain {
+        return Ok(serde_json::to_string(plans)?);
+    }
+    return Ok(plans
+        .iter()
+        .map(|e| format!("{}\n  {}", e.sql, e.plan.join("\n  ")))
+        .collect::<Vec<String>>()
+        .join("\n\n"));
+}
+
+fn format_rows<T: Serialize>(rows: &[T], format: ExportFormat) -> Result<String> {
+    return match format {
+        ExportFormat::Json => Ok(serde_json::to_string(rows)?),
+        ExportFormat::Ndjson => Ok(rows
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<serde_json::Result<Vec<String>>>()?
+            .join("\n")),
+        ExportFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(vec![]);
+            for row in rows {
+                csv.serialize(row)?;
+            }
+            Ok(String::from_utf8(csv.into_inner()?)?.trim_end().to_string())
+        }
+    };
+}
+
+pub struct SerialHistoryArgs {
+    pub serial: String,
+}
+
+impl SerialHistoryArgs {
+    pub fn history(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("serial_history")?;
+        let history = ctx.db.serial_history(&self.serial)?;
+        if history.is_empty() {
+            bail!("No serial {} found", self.serial);
+        }
+        return Ok(serde_json::to_string(&history)?);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BomComponentProps {
+    pub identifier: u32,
+    pub quantity: i64,
+}
+
+#[derive(Serialize)]
+pub struct BuildProps<'a> {
+    pub quantity: i64,
+    pub location: &'a str,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KitItemProps {
+    pub identifier: u32,
+    pub quantity: i64,
+}
+
+#[derive(Serialize)]
+pub struct PickProps<'a> {
+    pub quantity: i64,
+    pub location: &'a str,
+}
+
+pub struct BomDefineArgs {
+    pub assembly: String,
+    pub components: Vec<String>,
+}
+
+impl BomDefineArgs {
+    /**
+     * Parses a component given in `id=<ID> qty=<QUANTITY>` notation.
+     */
+    fn parse_component(component: &[String]) -> Result<BomComponentProps> {
+        let mut identifier = None;
+        let mut quantity = None;
+        for part in component {
+            match part.split_once("=") {
+                Some(("id", value)) => identifier = value.parse::<u32>().ok(),
+                Some(("qty", value)) => quantity = value.parse::<i64>().ok(),
+                _ => bail!(
+                    "Component part '{}' is neither id=<ID> nor qty=<QUANTITY>",
+                    part
+                ),
+            }
+        }
+        return match (identifier, quantity) {
+            (Some(identifier), Some(quantity)) if quantity > 0 => Ok(BomComponentProps {
+                identifier,
+                quantity,
+            }),
+            _ => bail!(
+                "Component '{}' requires a valid id and a quantity greater than 0",
+                component.join(" ")
+            ),
+        };
+    }
+
+    pub fn define(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("bom_define")?;
+        let components = self
+            .components
+            .chunks(2)
+            .map(Self::parse_component)
+            .collect::<Result<Vec<_>>>()?;
+        return ctx.db.bom_define(&self.assembly, &components, &user);
+    }
+}
+
+pub struct BomShowArgs {
+    pub assembly: String,
+}
+
+impl BomShowArgs {
+    pub fn show(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("bom_list")?;
+        return Ok(serde_json::to_string(&ctx.db.bom_list(&self.assembly)?)?);
+    }
+}
+
+pub struct BomBuildArgs {
+    pub assembly: String,
+    pub quantity: i64,
+    pub location: String,
+}
+
+impl BomBuildArgs {
+    pub fn build(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("bom_build")?;
+        let props = BuildProps {
+            quantity: self.quantity,
+            location: &self.location,
+        };
+        return ctx.db.bom_build(&self.assembly, &props, ctx.config, &user);
+    }
+}
+
+pub struct KitDefineArgs {
+    pub name: String,
+    pub items: Vec<String>,
+}
+
+impl KitDefineArgs {
+    /**
+     * Parses an item given as its identifier followed by its quantity, e.g. `12 x2`.
+     */
+    fn parse_item(item: &[String]) -> Result<KitItemProps> {
+        let (identifier, quantity) = match item {
+            [identifier, quantity] => (identifier, quantity),
+            _ => bail!(
+                "Item '{}' requires an identifier and a quantity",
+                item.join(" ")
+            ),
+        };
+        let quantity = quantity.strip_prefix('x').unwrap_or(quantity);
+        return match (identifier.parse::<u32>(), quantity.parse::<i64>()) {
+            (Ok(identifier), Ok(quantity)) if quantity > 0 => Ok(KitItemProps {
+                identifier,
+                quantity,
+            }),
+            _ => bail!(
+                "Item '{}' requires a valid identifier and a quantity greater than 0, e.g. 12 x2",
+                item.join(" ")
+            ),
+        };
+    }
+
+    pub fn define(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("kit_define")?;
+        let items = self
+            .items
+            .chunks(2)
+            .map(Self::parse_item)
+            .collect::<Result<Vec<_>>>()?;
+        return ctx.db.kit_define(&self.name, &items, &user);
+    }
+}
+
+pub struct KitListArgs;
+
+impl KitListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("kit_list")?;
+        return Ok(serde_json::to_string(&ctx.db.kit_list()?)?);
+    }
+}
+
+pub struct KitShowArgs {
+    pub name: String,
+}
+
+impl KitShowArgs {
+    pub fn show(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("kit_get")?;
+        return Ok(serde_json::to_string(&ctx.db.kit_get(&self.name)?)?);
+    }
+}
+
+pub struct KitRemoveArgs {
+    pub name: String,
+}
+
+impl KitRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("kit_remove")?;
+        return ctx.db.kit_remove(&self.name, &user);
+    }
+}
+
+pub struct KitPickArgs {
+    pub name: String,
+    pub quantity: i64,
+    pub location: String,
+}
+
+impl KitPickArgs {
+    pub fn pick(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("kit_pick")?;
+        let props = PickProps {
+            quantity: self.quantity,
+            location: &self.location,
+        };
+        return ctx.db.kit_pick(&self.name, &props, ctx.config, &user);
+    }
+}
+
+pub struct StocktakeStartArgs {
+    pub location: String,
+}
+
+impl StocktakeStartArgs {
+    pub fn start(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("stocktake_start")?;
+        return ctx.db.stocktake_start(&self.location, &user);
+    }
+}
+
+pub struct StocktakeRecordArgs {
+    pub identifier: String,
+    pub counted: i64,
+}
+
+impl StocktakeRecordArgs {
+    pub fn record(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("stocktake_record")?;
+        return ctx
+            .db
+            .stocktake_record(&self.identifier, self.counted, ctx.config, &user);
+    }
+}
+
+pub struct StocktakeStatusArgs;
+
+impl StocktakeStatusArgs {
+    pub fn status(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("stocktake_status")?;
+        return Ok(serde_json::to_string(&ctx.db.stocktake_status()?)?);
+    }
+}
+
+pub struct StocktakeCloseArgs;
+
+impl StocktakeCloseArgs {
+    pub fn close(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("stocktake_close")?;
+        return Ok(serde_json::to_string(&ctx.db.stocktake_close(&user)?)?);
+    }
+}
+
+pub struct StocktakeCancelArgs;
+
+impl StocktakeCancelArgs {
+    pub fn cancel(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("stocktake_cancel")?;
+        return ctx.db.stocktake_cancel(&user);
+    }
+}
+
+pub struct MaintenanceScheduleArgs {
+    pub identifier: String,
+    pub every: String,
+    pub task: String,
+}
+
+impl MaintenanceScheduleArgs {
+    pub fn schedule(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("maintenance_schedule")?;
+        if self.task.trim().is_empty() {
+            bail!("The task of a maintenance schedule cannot be empty");
+        }
+        let interval_days = parse_days(&self.every)?;
+        return ctx
+            .db
+            .maintenance_schedule(&self.identifier, &self.task, interval_days, &user);
+    }
+}
+
+pub struct MaintenanceDoneArgs {
+    pub identifier: String,
+    pub task: String,
+}
+
+impl MaintenanceDoneArgs {
+    pub fn done(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("maintenance_done")?;
+        return ctx.db.maintenance_done(&self.identifier, &self.task, &user);
+    }
+}
+
+pub struct MaintenanceDueArgs {
+    pub within: String,
+}
+
+impl MaintenanceDueArgs {
+    pub fn due(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("maintenance_due")?;
+        let within_days = parse_days(&self.within)?;
+        return Ok(serde_json::to_string(
+            &ctx.db.maintenance_due(within_days)?,
+        )?);
+    }
+}
+
+pub struct InventoryAttachArgs {
+    pub identifier: String,
+    pub file: String,
+}
+
+impl InventoryAttachArgs {
+    pub fn attach(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("attachment_add")?;
+        ctx.check_owner(&user, &self.identifier)?;
+        let filename = match Path::new(&self.file).file_name() {
+            Some(filename) => filename.to_string_lossy().to_string(),
+            None => bail!("'{}' is not a file", self.file),
+        };
+        let content = match fs::read(&self.file) {
+            Ok(content) => content,
+            Err(e) => bail!("Could not read file '{}' ({})", self.file, e),
+        };
+        return ctx
+            .db
+            .attachment_add(&self.identifier, &filename, &content, &user);
+    }
+}
+
+pub struct AttachmentListArgs {
+    pub identifier: String,
+}
+
+impl AttachmentListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("attachment_list")?;
+        return Ok(serde_json::to_string(
+            &ctx.db.attachment_list(&self.identifier)?,
+        )?);
+    }
+}
+
+pub struct AttachmentGetArgs {
+    pub identifier: String,
+    pub out: Option<String>,
+}
+
+impl AttachmentGetArgs {
+    pub fn get(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("attachment_content")?;
+        let (filename, content) = ctx.db.attachment_content(&self.identifier)?;
+        let out = self.out.clone().unwrap_or(filename);
+        // Never overwrite existing files, e.g. when several attachments share a name
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&out)
+        {
+            Ok(file) => file,
+            Err(e) => bail!("Could not create file '{}' ({})", out, e),
+        };
+        file.write_all(&content)?;
+        return Ok(format!("Attachment was written to '{}'", out));
+    }
+}
+
+pub struct AttachmentRemoveArgs {
+    pub identifier: String,
+}
+
+impl AttachmentRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("attachment_remove")?;
+        return ctx.db.attachment_remove(&self.identifier, &user);
+    }
+}
+
+pub struct InventoryCommentArgs {
+    pub identifier: String,
+    pub body: String,
+}
+
+impl InventoryCommentArgs {
+    pub fn comment(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("comment_add")?;
+        if self.body.trim().is_empty() {
+            bail!("A comment cannot be empty");
+        }
+        return ctx.db.comment_add(&self.identifier, &self.body, &user);
+    }
+}
+
+/**
+ * Fails unless the key consists of letters, digits, _, - and ., so that it can be used in
+ * scripts without quoting.
+ */
+pub(crate) fn check_meta_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.len() > 64 {
+        bail!("Metadata keys must have 1 to 64 characters");
+    }
+    if !key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        bail!(
+            "Metadata key '{}' may only contain letters, digits, _, - and .",
+            key
+        );
+    }
+    return Ok(());
+}
+
+pub struct InventoryMetaSetArgs {
+    pub identifier: String,
+    pub key: String,
+    pub value: String,
+}
+
+impl InventoryMetaSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("meta_set")?;
+        check_meta_key(&self.key)?;
+        ctx.check_owner(&user, &self.identifier)?;
+        return ctx
+            .db
+            .meta_set(&self.identifier, &self.key, &self.value, &user);
+    }
+}
+
+pub struct InventoryMetaGetArgs {
+    pub identifier: String,
+    /**
+     * Prints only the value of the key instead of all metadata as JSON object
+     */
+    pub key: Option<String>,
+}
+
+impl InventoryMetaGetArgs {
+    pub fn get(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("meta_get")?;
+        let meta = ctx.db.meta_get(&self.identifier)?;
+        return match &self.key {
+            Some(key) => match meta.get(key) {
+                Some(value) => Ok(value.clone()),
+                None => bail!("Entity {} has no metadata {}", self.identifier, key),
+            },
+            None => Ok(serde_json::to_string(&meta)?),
+        };
+    }
+}
+
+pub struct InventoryMetaRemoveArgs {
+    pub identifier: String,
+    pub key: String,
+}
+
+impl InventoryMetaRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("meta_remove")?;
+        ctx.check_owner(&user, &self.identifier)?;
+        return ctx.db.meta_remove(&self.identifier, &self.key, &user);
+    }
+}
+
+pub struct CommentListArgs {
+    pub identifier: String,
+}
+
+impl CommentListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("comment_list")?;
+        return Ok(serde_json::to_string(
+            &ctx.db.comment_list(&self.identifier)?,
+        )?);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSize {
+    Thumb,
+    Medium,
+    Original,
+}
+
+impl ImageSize {
+    /**
+     * The edge length of the square the image is scaled into, None for the original image.
+     */
+    fn dimension(&self) -> Option<u32> {
+        return match self {
+            ImageSize::Thumb => Some(128),
+            ImageSize::Medium => Some(512),
+            ImageSize::Original => None,
+        };
+    }
+}
+
+impl fmt::Display for ImageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageSize::Thumb => write!(f, "thumb"),
+            ImageSize::Medium => write!(f, "medium"),
+            ImageSize::Original => write!(f, "original"),
+        }
+    }
+}
+
+pub struct InventoryImageSetArgs {
+    pub identifier: String,
+    pub file: String,
+}
+
+impl InventoryImageSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("image_set")?;
+        ctx.check_owner(&user, &self.identifier)?;
+        let filename = match Path::new(&self.file).file_name() {
+            Some(filename) => filename.to_string_lossy().to_string(),
+            None => bail!("'{}' is not a file", self.file),
+        };
+        let content = match fs::read(&self.file) {
+            Ok(content) => content,
+            Err(e) => bail!("Could not read file '{}' ({})", self.file, e),
+        };
+        if image::load_from_memory(&content).is_err() {
+            bail!("'{}' is not a supported image (PNG or JPEG)", self.file);
+        }
+        return ctx
+            .db
+            .image_set(&self.identifier, &filename, &content, &user);
+    }
+}
+
+pub struct InventoryImageGetArgs {
+    pub identifier: String,
+    pub size: ImageSize,
+    pub out: Option<String>,
+}
+
+impl InventoryImageGetArgs {
+    pub fn get(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.authorize("image_get")?;
+        let (filename, original) = ctx.db.image_get(&self.identifier)?;
+        let size = self.size.to_string();
+        let (default_out, content) = match self.size.dimension() {
+            None => (filename, original),
+            Some(dimension) => {
+                let content = match ctx.db.image_thumbnail(&self.identifier, &size)? {
+                    Some(content) => content,
+                    None => {
+                        let content = resize_image(&original, dimension)?;
+                        // Thumbnails are resized again each time, if they cannot be cached
+                        if !ctx.read_only {
+                            ctx.db
+                                .image_thumbnail_store(&self.identifier, &size, &content)?;
+                        }
+                        content
+                    }
+                };
+                let stem = Path::new(&filename)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or(self.identifier.clone());
+                (format!("{}-{}.png", stem, size), content)
+            }
+        };
+        let out = self.out.clone().unwrap_or(default_out);
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&out)
+        {
+            Ok(file) => file,
+            Err(e) => bail!("Could not create file '{}' ({})", out, e),
+        };
+        file.write_all(&content)?;
+        return Ok(format!("Image was written to '{}'", out));
+    }
+}
+
+pub struct InventoryImageRemoveArgs {
+    pub identifier: String,
+}
+
+impl InventoryImageRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("image_remove")?;
+        ctx.check_owner(&user, &self.identifier)?;
+        return ctx.db.image_remove(&self.identifier, &user);
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RetentionRules {
+    #[serde(default)]
+    pub purge_deleted_after_days: Option<u32>,
+    #[serde(default)]
+    pub prune_events_after_days: Option<u32>,
+}
+
+pub struct RetentionSetArgs {
+    pub purge_deleted_after: Option<String>,
+    pub prune_events_after: Option<String>,
+}
+
+impl RetentionSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("retention_set")?;
+        let rules = RetentionRules {
+            purge_deleted_after_days: self
+                .purge_deleted_after
+                .as_deref()
+                .map(parse_days)
+                .transpose()?,
+            prune_events_after_days: self
+                .prune_events_after
+                .as_deref()
+                .map(parse_days)
+                .transpose()?,
+        };
+        return ctx.db.retention_set(ctx.config, rules, &user);
+    }
+}
+
+pub struct RetentionShowArgs;
+
+impl RetentionShowArgs {
+    pub fn show(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["db"]))?;
+        return Ok(serde_json::to_string(&ctx.config.retention_rules)?);
+    }
+}
+
+pub struct RetentionApplyArgs {
+    pub dry_run: bool,
+}
+
+impl RetentionApplyArgs {
+    pub fn apply(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("retention_apply")?;
+        if !self.dry_run {
+            ctx.check_writable()?;
+        }
+        let rules = ctx.config.retention_rules.clone();
+        return Ok(serde_json::to_string(&ctx.db.retention_apply(
+            &rules,
+            self.dry_run,
+            &user,
+        )?)?);
+    }
+}
+
+pub struct HistoryCompactArgs {
+    pub keep_last: u32,
+    pub older_than: String,
+}
+
+impl HistoryCompactArgs {
+    pub fn compact(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("history_compact")?;
+        let older_than_days = parse_days(&self.older_than)?;
+        return Ok(serde_json::to_string(&ctx.db.history_compact(
+            self.keep_last,
+            older_than_days,
+            &user,
+        )?)?);
+    }
+}
+
+pub struct DoctorArgs {
+    pub fix: bool,
+}
+
+impl DoctorArgs {
+    pub fn diagnose(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("doctor")?;
+        if self.fix {
+            ctx.check_writable()?;
+            require_write(&user, "db")?;
+        }
+        return Ok(serde_json::to_string(
+            &ctx.db.doctor(ctx.config, self.fix)?,
+        )?);
+    }
+}
+
+pub struct OptimizeArgs;
+
+impl OptimizeArgs {
+    pub fn optimize(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("optimize")?;
+        return Ok(serde_json::to_string(&ctx.db.optimize(&user)?)?);
+    }
+}
+
+pub struct InitArgs {
+    pub admin_user: String,
+    /**
+     * Read from stdin if None, so that it does not show up in the shell's history
+     */
+    pub admin_password: Option<String>,
+    pub disable_registration: bool,
+}
+
+impl InitArgs {
+    /**
+     * Sets up a new database with its admin in one step, instead of relying on the first
+     * registered user becoming the admin.
+     */
+    pub fn init(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let password = match &self.admin_password {
+            Some(password) => password.clone(),
+            None => {
+                if std::io::stdin().is_terminal() {
+                    eprint!("Password of {}: ", self.admin_user);
+                }
+                let mut password = String::new();
+                std::io::stdin().read_line(&mut password)?;
+                password.trim_end_matches(['\r', '\n']).to_string()
+            }
+        };
+        if self.admin_user.trim().is_empty() || password.is_empty() {
+            bail!("The admin needs a username and a password");
+        }
+        ctx.config.password_policy.check(&password)?;
+        return ctx.db.init(
+            &self.admin_user,
+            &password,
+            !self.disable_registration,
+            ctx.config,
+        );
+    }
+}
+
+pub struct RegistrationSetArgs {
+    pub allow: bool,
+}
+
+impl RegistrationSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("registration_set")?;
+        return ctx.db.registration_set(ctx.config, self.allow, &user);
+    }
+}
+
+pub struct HealthArgs;
+
+impl HealthArgs {
+    /**
+     * Checks the health of the database without requiring authentication, so that it can be
+     * probed by monitoring. Fails with the report if any of the checks did not pass.
+     */
+    pub fn check(&self, ctx: &CommandContext) -> Result<String> {
+        let report = ctx.db.health();
+        let json = serde_json::to_string(&report)?;
+        if !report.healthy {
+            bail!(json);
+        }
+        return Ok(json);
+    }
+}
+
+pub struct MetricsArgs;
+
+impl MetricsArgs {
+    /**
+     * Renders the metrics of the database in the Prometheus text format, e.g. to be picked up
+     * by the textfile collector of the node exporter.
+     */
+    pub fn metrics(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("metrics")?;
+        return Ok(prometheus_lines(&ctx.db.metrics()?).join("\n"));
+    }
+}
+
+/**
+ * Metrics of the database in the Prometheus text format, one line each
+ */
+pub(crate) fn prometheus_lines(metrics: &DBMetrics) -> Vec<String> {
+    let mut lines = vec![
+        "# HELP invman_database_size_bytes Size of the database file".to_string(),
+        "# TYPE invman_database_size_bytes gauge".into(),
+        format!("invman_database_size_bytes {}", metrics.database_size),
+        "# HELP invman_inventory_entities Entities in the inventory".into(),
+        "# TYPE invman_inventory_entities gauge".into(),
+        format!(
+            "invman_inventory_entities{{state=\"active\"}} {}",
+            metrics.entities
+        ),
+        format!(
+            "invman_inventory_entities{{state=\"deleted\"}} {}",
+            metrics.deleted_entities
+        ),
+        "# HELP invman_users Registered users".into(),
+        "# TYPE invman_users gauge".into(),
+        format!("invman_users {}", metrics.users),
+        "# HELP invman_operations_total Operations recorded in the event log".into(),
+        "# TYPE invman_operations_total counter".into(),
+    ];
+    for (action_no, count) in &metrics.events {
+        lines.push(format!(
+            "invman_operations_total{{action_no=\"{}\"}} {}",
+            action_no, count
+        ));
+    }
+    return lines;
+}
+
+pub struct DumpArgs {
+    pub file: String,
+    pub gzip: bool,
+    /**
+     * Writes the SHA-256 checksum of the dump into a sidecar file next to it
+     */
+    pub sha256: bool,
+}
+
+impl DumpArgs {
+    pub fn dump(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("dump")?;
+        let dump = ctx.db.dump()?;
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&self.file)
+        {
+            Ok(file) => file,
+            Err(e) => bail!("Could not create file '{}' ({})", self.file, e),
+        };
+        let content = serde_json::to_string(&dump)?;
+        if self.gzip {
+            let mut gzip = GzEncoder::new(file, Compression::default());
+            gzip.write_all(content.as_bytes())?;
+            gzip.finish()?;
+        } else {
+            file.write_all(content.as_bytes())?;
+        }
+        if self.sha256 {
+            return Ok(format!(
+                "Database was dumped to '{}' with checksum '{}'",
+                self.file,
+                write_checksum(&self.file)?
+            ));
+        }
+        return Ok(format!("Database was dumped to '{}'", self.file));
+    }
+}
+
+pub struct LoadArgs {
+    pub file: String,
+}
+
+impl LoadArgs {
+    /**
+     * Loading does not authenticate, as the database has to be empty and thus has no users yet.
+     * Compressed dumps are decompressed, and dumps with a checksum file are verified first.
+     */
+    pub fn load(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        verify_checksum(&self.file)?;
+        let content = read_decompressed(&self.file)?;
+        let dump: DBDump = match serde_json::from_slice(&content) {
+            Ok(dump) => dump,
+            Err(e) => bail!("File '{}' is not an invman dump ({})", self.file, e),
+        };
+        if dump.format != DUMP_FORMAT {
+            bail!("File '{}' is not an invman dump", self.file);
+        }
+        if dump.version > DUMP_VERSION {
+            bail!(
+                "Dump is of version {}, which is newer than supported by this invman",
+                dump.version
+            );
+        }
+        return ctx.db.load(&dump);
+    }
+}
+
+pub struct FixtureSaveArgs {
+    pub file: String,
+    /**
+     * Condition selecting the saved entities, all if None
+     */
+    pub raw: Option<String>,
+    pub params: Vec<String>,
+    pub limit: Option<i32>,
+}
+
+impl FixtureSaveArgs {
+    /**
+     * Saves the schema, the users and the selected entities into a YAML file, from which
+     * fixture load recreates them. Users are saved with their password hash, empty columns and
+     * the columns invman manages itself are left out.
+     */
+    pub fn save(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authorize("fixture_users")?;
+        let schema = &ctx.config.inventory_schema_declaration.collection;
+        if schema
+            .iter()
+            .any(|e| user.is_read_denied("inventory", &e.name))
+        {
+            bail!("Cannot save a fixture while denied to read columns of inventory");
+        }
+        if user.org_id.is_some() && self.raw.is_some() {
+            bail!("Cannot use --raw as member of an organization");
+        }
+        let (raw, params) = match &self.raw {
+            Some(raw) => {
+                let (raw, params) = prepare_raw_sql(raw, &self.params)?;
+                (Some(raw), params)
+            }
+            None => (None, self.params.clone()),
+        };
+        let props = InventoryListProps {
+            limit: self.limit.unwrap_or(-1),
+            after_id: None,
+            fuzzy: None,
+            fuzzy_threshold: 0.0,
+            with_audit: false,
+            with_attachments: false,
+            include_archived: false,
+            supplier: None,
+            assigned_to: None,
+            category: None,
+            recursive: false,
+            raw: &raw,
+            params: &params,
+            changed_since: None,
+            filter: None,
+            group: None,
+            expand: vec![],
+        };
+        let entities = ctx
+            .db
+            .inventory_list(&props, ctx.config)?
+            .iter()
+            .map(|entity| {
+                entity
+                    .collection
+                    .iter()
+                    .filter(|e| schema.iter().any(|decl| decl.name == e.key))
+                    .filter_map(|e| Some((e.key.clone(), fixture_value(e)?)))
+                    .collect()
+            })
+            .collect::<Vec<serde_json::Map<String, serde_json::Value>>>();
+        let fixture = DBFixture {
+            schema: schema.clone(),
+            users: ctx.db.fixture_users()?,
+            entities,
+        };
+        // Sections in the order they are loaded, which a map would sort by their name
+        let content = [
+            ("schema", serde_json::to_value(&fixture.schema)?),
+            ("users", serde_json::to_value(&fixture.users)?),
+            ("entities", serde_json::to_value(&fixture.entities)?),
+        ]
+        .into_iter()
+        .map(|(name, value)| yaml::to_yaml(&serde_json::json!({ name: value })))
+        .collect::<String>();
+        if let Err(e) = fs::write(&self.file, content) {
+            bail!("Could not write file '{}' ({})", self.file, e);
+        }
+        return Ok(format!(
+            "Saved {} schema columns, {} users and {} entities to '{}'",
+            fixture.schema.len(),
+            fixture.users.len(),
+            fixture.entities.len(),
+            self.file
+        ));
+    }
+}
+
+/**
+ * The value of the column as it is written into a fixture, None if it is empty
+ */
+fn fixture_value(entry: &KeyValueTypeEntry) -> Option<serde_json::Value> {
+    let value = entry.to_plain_value()?;
+    return Some(match entry.column_type {
+        ColumnType::INT => value
+            .parse::<i64>()
+            .map_or_else(|_| value.clone().into(), |e| e.into()),
+        ColumnType::REAL => value
+            .parse::<f64>()
+            .map_or_else(|_| value.clone().into(), |e| e.into()),
+        ColumnType::BOOL => (value == "true").into(),
+        ColumnType::TEXT | ColumnType::VARCHAR | ColumnType::MONEY => value.into(),
+    });
+}
+
+pub struct FixtureLoadArgs {
+    pub file: String,
+}
+
+impl FixtureLoadArgs {
+    /**
+     * Loading does not authenticate, as the database has to be empty and thus has no users yet.
+     */
+    pub fn load(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let content = match fs::read_to_string(&self.file) {
+            Ok(content) => content,
+            Err(e) => bail!("Could not read file '{}' ({})", self.file, e),
+        };
+        let fixture =
+            yaml::from_yaml(&content).and_then(|e| Ok(serde_json::from_value::<DBFixture>(e)?));
+        let fixture = match fixture {
+            Ok(fixture) => fixture,
+            Err(e) => bail!("File '{}' is not an invman fixture ({})", self.file, e),
+        };
+
+        // The values are typed by the columns the fixture adds to the schema
+        let mut declarations = ctx.config.inventory_schema_declaration.clone();
+        declarations
+            .collection
+            .extend(fixture.schema.iter().cloned());
+        let mut entities = vec![];
+        for (i, entity) in fixture.entities.iter().enumerate() {
+            let mut entries = vec![];
+            for (name, value) in entity {
+                let value = match value {
+                    serde_json::Value::Null => continue,
+                    serde_json::Value::String(value) => value.clone(),
+                    serde_json::Value::Bool(_) | serde_json::Value::Number(_) => value.to_string(),
+                    _ => bail!(
+                        "Entity {}: Value of {} must not be a collection",
+                        i + 1,
+                        name
+                    ),
+                };
+                match format!("{}={}", name, value).to_typed_key_value_entry(&declarations) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => bail!("Entity {}: {}", i + 1, e),
+                }
+            }
+            if entries.is_empty() {
+                bail!("Entity {} has no values", i + 1);
+            }
+            entities.push(KeyValueCollection::new(entries));
+        }
+        return ctx
+            .db
+            .fixture_load(&fixture.schema, &fixture.users, &entities, ctx.config);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    Skip,
+    Overwrite,
+    Fail,
+}
+
+pub struct MergeArgs {
+    pub file: String,
+    pub strategy: MergeStrategy,
+}
+
+impl MergeArgs {
+    pub fn merge(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize_access(Access::write(&["db"]))?;
+        if !Path::new(&self.file).is_file() {
+            bail!("Database '{}' does not exist", self.file);
+        }
+        return Ok(serde_json::to_string(&ctx.db.merge(
+            &self.file,
+            self.strategy,
+            ctx.config,
+            &user,
+        )?)?);
+    }
+}
+
+pub struct OutboxRecordArgs {
+    pub command: Vec<String>,
+}
+
+impl OutboxRecordArgs {
+    /**
+     * Recording does not authenticate, as the command itself has already been authorized.
+     */
+    pub fn record(&self, ctx: &mut CommandContext) -> Result<()> {
+        ctx.check_writable()?;
+        return ctx.db.outbox_record(&self.command);
+    }
+}
+
+pub struct OutboxListArgs;
+
+impl OutboxListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["db"]))?;
+        return Ok(serde_json::to_string(&ctx.db.outbox_list()?)?);
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SyncPushResult {
+    id: u32,
+    command: Vec<String>,
+    applied: bool,
+    message: String,
+}
+
+pub struct SyncPushArgs {
+    pub central: String,
+}
+
+impl SyncPushArgs {
+    /**
+     * Replays the queued commands in order against the central database, using the same
+     * credentials. Applied commands leave the outbox, conflicting ones stay queued.
+     */
+    pub fn push(
+        &self,
+        ctx: &mut CommandContext,
+        replay: &dyn Fn(&[String], &mut CommandContext) -> Result<String>,
+    ) -> Result<String> {
+        ctx.authorize_access(Access::write(&["db"]))?;
+        let mut central = InvManConnection::sqlite_at(&self.central)?;
+        let mut central_config = central.get_config()?;
+        let mut central_ctx = CommandContext::new(
+            &mut central,
+            &mut central_config,
+            ctx.auth.clone(),
+            ctx.output,
+        );
+        central_ctx.otp = ctx.otp.clone();
+        let mut results = vec![];
+        for entry in ctx.db.outbox_list()? {
+            let result = replay(&entry.command, &mut central_ctx);
+            if result.is_ok() {
+                ctx.db.outbox_remove(entry.id)?;
+            }
+            results.push(SyncPushResult {
+                id: entry.id,
+                command: entry.command,
+                applied: result.is_ok(),
+                message: match result {
+                    Ok(message) => message,
+                    Err(e) => e.to_string(),
+                },
+            });
+        }
+        return Ok(serde_json::to_string(&results)?);
+    }
+}
+
+pub struct TimezoneSetArgs {
+    pub timezone: String,
+}
+
+impl TimezoneSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("timezone_set")?;
+        let timezone = parse_timezone(&self.timezone)?;
+        return ctx.db.timezone_set(ctx.config, timezone.name(), &user);
+    }
+}
+
+pub struct LabelPrinterSetArgs {
+    /**
+     * Address of the printer in host or host:port notation, None removes the printer
+     */
+    pub printer: Option<String>,
+}
+
+impl LabelPrinterSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("label_printer_set")?;
+        let printer = match &self.printer {
+            Some(printer) => Some(label_printer_address(printer)?),
+            None => None,
+        };
+        return ctx.db.label_printer_set(ctx.config, printer, &user);
+    }
+}
+
+/**
+ * Completes the address of a label printer with the raw printing port 9100 if it has no port
+ */
+fn label_printer_address(printer: &str) -> Result<String> {
+    let (host, port) = match printer.rsplit_once(':') {
+        Some((host, port)) if !host.ends_with(':') => (host, port),
+        _ => (printer, "9100"),
+    };
+    if host.is_empty() || port.parse::<u16>().is_err() {
+        bail!(
+            "Printer address '{}' must be in host or host:port notation",
+            printer
+        );
+    }
+    return Ok(format!("{}:{}", host, port));
+}
+
+pub struct DisplayFormatSetArgs {
+    pub format: DisplayFormat,
+}
+
+impl DisplayFormatSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("display_format_set")?;
+        use std::fmt::Write as _;
+        let mut probe = String::new();
+        if write!(probe, "{}", Local::now().format(&self.format.date_format)).is_err() {
+            bail!(
+                "Date format '{}' is not valid strftime notation",
+                self.format.date_format
+            );
+        }
+        if self.format.decimal_separator == self.format.thousands_separator {
+            bail!("Decimal and thousands separator cannot be the same");
+        }
+        return ctx
+            .db
+            .display_format_set(ctx.config, self.format.clone(), &user);
+    }
+}
+
+pub struct ExchangeRateSetArgs {
+    /**
+     * Currencies in FROM:TO notation
+     */
+    pub pair: String,
+    pub rate: f64,
+}
+
+impl ExchangeRateSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("exchange_rates_set")?;
+        let (from, to) = ExchangeRates::parse_pair(&self.pair)?;
+        if !self.rate.is_finite() || self.rate <= 0.0 {
+            bail!("Exchange rate must be a positive number");
+        }
+        let mut rates = ctx.config.exchange_rates.clone();
+        // The opposite pair would otherwise take precedence over its inverse
+        rates.rates.remove(&format!("{}:{}", to, from));
+        rates.rates.insert(format!("{}:{}", from, to), self.rate);
+        return ctx.db.exchange_rates_set(ctx.config, rates, &user);
+    }
+}
+
+pub struct ExchangeRateRemoveArgs {
+    pub pair: String,
+}
+
+impl ExchangeRateRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("exchange_rates_set")?;
+        let (from, to) = ExchangeRates::parse_pair(&self.pair)?;
+        let mut rates = ctx.config.exchange_rates.clone();
+        if rates.rates.remove(&format!("{}:{}", from, to)).is_none()
+            && rates.rates.remove(&format!("{}:{}", to, from)).is_none()
+        {
+            bail!("No exchange rate between {} and {} is set", from, to);
+        }
+        return ctx.db.exchange_rates_set(ctx.config, rates, &user);
+    }
+}
+
+pub struct ExchangeRateListArgs;
+
+impl ExchangeRateListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["config"]))?;
+        return Ok(serde_json::to_string(&ctx.config.exchange_rates)?);
+    }
+}
+
+pub struct BaseCurrencySetArgs {
+    pub currency: String,
+}
+
+impl BaseCurrencySetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("exchange_rates_set")?;
+        let mut rates = ctx.config.exchange_rates.clone();
+        rates.base = Some(parse_currency(&self.currency)?);
+        return ctx.db.exchange_rates_set(ctx.config, rates, &user);
+    }
+}
+
+pub struct ReasonCodeAddArgs {
+    pub code: String,
+}
+
+impl ReasonCodeAddArgs {
+    pub fn add(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("reason_codes_set")?;
+        let code = self.code.trim().to_lowercase();
+        if code.is_empty() || code.contains(char::is_whitespace) {
+            bail!("A reason code must be a single word, e.g. damage");
+        }
+        let mut codes = ctx.config.reason_codes.clone();
+        if codes.contains(&code) {
+            bail!("Reason code {} already exists", code);
+        }
+        codes.push(code);
+        return ctx.db.reason_codes_set(ctx.config, codes, &user);
+    }
+}
+
+pub struct ReasonCodeRemoveArgs {
+    pub code: String,
+}
+
+impl ReasonCodeRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("reason_codes_set")?;
+        let code = self.code.trim().to_lowercase();
+        let mut codes = ctx.config.reason_codes.clone();
+        if !codes.contains(&code) {
+            bail!("No reason code {} found", code);
+        }
+        codes.retain(|e| *e != code);
+        return ctx.db.reason_codes_set(ctx.config, codes, &user);
+    }
+}
+
+pub struct ReasonCodeListArgs;
+
+impl ReasonCodeListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["config"]))?;
+        return Ok(serde_json::to_string(&ctx.config.reason_codes)?);
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LdapGroupRole {
+    /**
+     * Distinguished name of the group, or only its common name, e.g. Warehouse Staff
+     */
+    pub group: String,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LdapSettings {
+    /**
+     * ldaps:// connects with TLS, ldap:// upgrades the connection with StartTLS
+     */
+    pub url: String,
+    /**
+     * Template of the DN users bind as, {username} is replaced by the username, e.g.
+     * {username}@corp.example.com for Active Directory
+     */
+    pub bind_dn: String,
+    /**
+     * Where the user is searched for to read its groups
+     */
+    pub base_dn: String,
+    /**
+     * Attribute holding the username, sAMAccountName for Active Directory or uid for OpenLDAP
+     */
+    pub user_attribute: String,
+    /**
+     * Checked in order, the first group the user is a member of decides its role
+     */
+    pub group_roles: Vec<LdapGroupRole>,
+    /**
+     * Role of users that are in none of the groups, without it they cannot log in
+     */
+    #[serde(default)]
+    pub default_role: Option<String>,
+    /**
+     * PEM encoded certificates trusted in addition to the public ones, e.g. of an internal CA
+     */
+    #[serde(default)]
+    pub ca_certificates: Option<String>,
+}
+
+pub struct LdapSetArgs {
+    pub url: String,
+    pub bind_dn: String,
+    pub base_dn: String,
+    pub user_attribute: String,
+    /**
+     * Mappings in GROUP=ROLE notation
+     */
+    pub groups: Vec<String>,
+    pub default_role: Option<String>,
+    /**
+     * Path of a PEM file with additional trusted certificates, its content is stored
+     */
+    pub ca_file: Option<String>,
+}
+
+impl LdapSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("ldap_set")?;
+        if !self.url.starts_with("ldaps://") && !self.url.starts_with("ldap://") {
+            bail!("The URL must start with ldaps:// or ldap://");
+        }
+        if !self.bind_dn.contains("{username}") {
+            bail!("The bind DN must contain {{username}}, e.g. {{username}}@corp.example.com");
+        }
+        let roles = ctx.db.role_list()?;
+        let role_exists = |role: &str| roles.iter().any(|e| e.name == role);
+        let mut group_roles = vec![];
+        for mapping in &self.groups {
+            let Some((group, role)) = mapping.rsplit_once('=') else {
+                bail!("Group mappings must be in GROUP=ROLE notation, e.g. Admins=skipper");
+            };
+            if !role_exists(role) {
+                bail!("Role {} does not exist", role);
+            }
+            group_roles.push(LdapGroupRole {
+                group: group.into(),
+                role: role.into(),
+            });
+        }
+        if let Some(role) = &self.default_role {
+            if !role_exists(role) {
+                bail!("Role {} does not exist", role);
+            }
+        }
+        if group_roles.is_empty() && self.default_role.is_none() {
+            bail!("Map at least one group to a role, or give a default role");
+        }
+        let ca_certificates = match &self.ca_file {
+            Some(path) => Some(std::fs::read_to_string(path)?),
+            None => None,
+        };
+        let settings = LdapSettings {
+            url: self.url.clone(),
+            bind_dn: self.bind_dn.clone(),
+            base_dn: self.base_dn.clone(),
+            user_attribute: self.user_attribute.clone(),
+            group_roles,
+            default_role: self.default_role.clone(),
+            ca_certificates,
+        };
+        // Fail now rather than on the first login
+        LdapProvider::new(&settings).check()?;
+        return ctx.db.ldap_set(ctx.config, Some(settings), &user);
+    }
+}
+
+pub struct LdapClearArgs;
+
+impl LdapClearArgs {
+    pub fn clear(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("ldap_set")?;
+        return ctx.db.ldap_set(ctx.config, None, &user);
+    }
+}
+
+pub struct PasswordPolicySetArgs {
+    pub min_length: u32,
+    pub min_classes: u32,
+    pub banned: Vec<String>,
+    /**
+     * File with further banned passwords, one per line, e.g. a list of common ones
+     */
+    pub banned_file: Option<String>,
+    /**
+     * Duration like 90d, None for passwords that never expire
+     */
+    pub max_age: Option<String>,
+}
+
+impl PasswordPolicySetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("password_policy_set")?;
+        if self.min_classes > 4 {
+            bail!("There are only 4 classes of characters");
+        }
+        let mut banned = self.banned.clone();
+        if let Some(path) = &self.banned_file {
+            let content = std::fs::read_to_string(path)?;
+            banned.extend(
+                content
+                    .lines()
+                    .map(|e| e.trim())
+                    .filter(|e| !e.is_empty())
+                    .map(String::from),
+            );
+        }
+        banned.sort_by_key(|e| e.to_lowercase());
+        banned.dedup_by_key(|e| e.to_lowercase());
+        let policy = PasswordPolicy {
+            min_length: self.min_length,
+            min_classes: self.min_classes,
+            banned,
+            max_age_days: match &self.max_age {
+                Some(max_age) => parse_days(max_age)?,
+                None => 0,
+            },
+        };
+        return ctx.db.password_policy_set(ctx.config, policy, &user);
+    }
+}
+
+pub struct PasswordPolicyShowArgs;
+
+impl PasswordPolicyShowArgs {
+    pub fn show(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["config"]))?;
+        return Ok(serde_json::to_string(&ctx.config.password_policy)?);
+    }
+}
+
+pub struct LdapShowArgs;
+
+impl LdapShowArgs {
+    pub fn show(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["config"]))?;
+        return Ok(serde_json::to_string(&ctx.config.ldap)?);
+    }
+}
+
+pub struct ReadOnlySetArgs {
+    pub read_only: bool,
+}
+
+impl ReadOnlySetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        let user = ctx.authorize("read_only_set")?;
+        return ctx.db.read_only_set(ctx.config, self.read_only, &user);
+    }
+}
+
+pub struct BatchArgs {
+    /**
+     * Script to read the commands from, stdin if None
+     */
+    pub file: Option<String>,
+}
+
+impl BatchArgs {
+    /**
+     * Runs each line of the script as a command within a single transaction, which is only
+     * committed if every command succeeds. Empty lines and lines starting with # are skipped.
+     */
+    pub fn run(
+        &self,
+        ctx: &mut CommandContext,
+        replay: &dyn Fn(&[String], &mut CommandContext) -> Result<String>,
+    ) -> Result<String> {
+        ctx.check_writable()?;
+        let script = match &self.file {
+            Some(file) => match fs::read_to_string(file) {
+                Ok(script) => script,
+                Err(e) => bail!("Could not read file '{}' ({})", file, e),
+            },
+            None => std::io::read_to_string(std::io::stdin())?,
+        };
+        let mut commands = vec![];
+        for (i, line) in script.lines().enumerate() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            match split_command_line(line) {
+                Ok(args) if args.is_empty() => {}
+                Ok(args) => commands.push((i + 1, args)),
+                Err(e) => bail!("Line {}: {}", i + 1, e),
+            }
+        }
+
+        let mut responses = vec![];
+        ctx.db.batch_begin()?;
+        for (line, args) in commands {
+            match replay(&args, ctx) {
+                Ok(response) if response.is_empty() => {}
+                Ok(response) => responses.push(response),
+                Err(e) => {
+                    ctx.db.batch_rollback()?;
+                    bail!("Line {}: {}\nNo command of the batch was applied", line, e);
+                }
+            }
+        }
+        ctx.db.batch_commit()?;
+        return Ok(responses.join("\n"));
+    }
+}
+
+pub struct StdinArgs;
+
+impl StdinArgs {
+    /**
+     * Runs the commands read from stdin one after another and writes the result of each as a
+     * line of JSON. Commands are either given like on the command line or as a JSON array of
+     * their arguments. Failing commands do not stop the following ones.
+     */
+    pub fn run(
+        &self,
+        ctx: &mut CommandContext,
+        replay: &dyn Fn(&[String], &mut CommandContext) -> Result<String>,
+        out: &mut dyn Write,
+    ) -> Result<String> {
+        if ctx.auth.is_some() {
+            ctx.start_session()?;
+        }
+        for (i, line) in std::io::stdin().lines().enumerate() {
+            let line = line?;
+            let args = match line.trim_start().starts_with('[') {
+                true => serde_json::from_str::<Vec<String>>(&line).map_err(|e| e.into()),
+                false => split_command_line(&line),
+            };
+            let result = match args {
+                Ok(args) if args.is_empty() => continue,
+                Ok(args) => replay(&args, ctx),
+                Err(e) => Err(e),
+            };
+            let result = match result {
+                // Responses in JSON are embedded as they are, anything else as string
+                Ok(response) => serde_json::json!({
+                    "line": i + 1,
+                    "ok": true,
+                    "result": serde_json::from_str::<serde_json::Value>(&response)
+                        .unwrap_or(serde_json::Value::String(response)),
+                }),
+                Err(e) => serde_json::json!({
+                    "line": i + 1,
+                    "ok": false,
+                    "error": e.to_string(),
+                }),
+            };
+            writeln!(out, "{}", result)?;
+            out.flush()?;
+        }
+        return Ok(String::new());
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SmtpSecurity {
+    /**
+     * TLS from the start, usually on port 465
+     */
+    Tls,
+    /**
+     * Upgrade to TLS after connecting, usually on port 587
+     */
+    #[default]
+    Starttls,
+    /**
+     * Unencrypted, e.g. for a relay on the same host
+     */
+    None,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+}
+
+pub struct SmtpSetArgs {
+    /**
+     * None removes the settings and thus disables sending emails
+     */
+    pub settings: Option<SmtpSettings>,
+}
+
+impl SmtpSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("smtp_set")?;
+        if let Some(settings) = &self.settings {
+            if settings.username.is_some() != settings.password.is_some() {
+                bail!("Username and password must be given together");
+            }
+        }
+        return ctx.db.smtp_set(ctx.config, self.settings.clone(), &user);
+    }
+}
+
+pub struct SmtpShowArgs;
+
+impl SmtpShowArgs {
+    pub fn show(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["notify"]))?;
+        let mut settings = ctx.config.smtp.clone();
+        if let Some(settings) = settings.as_mut() {
+            settings.password = settings.password.as_ref().map(|_| "***".into());
+        }
+        return Ok(serde_json::to_string(&settings)?);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyChannel {
+    Email,
+    /**
+     * Incoming webhook of Slack, or any chat accepting its payload
+     */
+    Slack,
+    /**
+     * Any HTTP endpoint, receiving the event as JSON
+     */
+    Webhook,
+}
+
+impl NotifyChannel {
+    /**
+     * Checks that the recipient fits the channel, i.e. an email address or the URL of a webhook
+     */
+    pub fn check_recipient(&self, recipient: &str) -> Result<()> {
+        match self {
+            NotifyChannel::Email if recipient.parse::<lettre::Address>().is_err() => {
+                bail!("'{}' is not a valid email address", recipient)
+            }
+            NotifyChannel::Slack | NotifyChannel::Webhook
+                if !recipient.starts_with("https://") && !recipient.starts_with("http://") =>
+            {
+                bail!("'{}' is not a valid URL of a webhook", recipient)
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for NotifyChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyChannel::Email => write!(f, "email"),
+            NotifyChannel::Slack => write!(f, "slack"),
+            NotifyChannel::Webhook => write!(f, "webhook"),
+        }
+    }
+}
+
+impl FromStr for NotifyChannel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        return match s {
+            "email" => Ok(NotifyChannel::Email),
+            "slack" => Ok(NotifyChannel::Slack),
+            "webhook" => Ok(NotifyChannel::Webhook),
+            _ => bail!("Unknown notification channel '{}'", s),
+        };
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NotifyRuleProps {
+    pub action_no: u32,
+    pub channel: NotifyChannel,
+    pub recipient: String,
+    pub condition: Option<String>,
+}
+
+pub struct NotifyRuleAddArgs {
+    pub event: String,
+    pub channel: NotifyChannel,
+    pub to: String,
+    pub condition: Option<String>,
+}
+
+impl NotifyRuleAddArgs {
+    pub fn add(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("notify_rule_add")?;
+        let action_no = match event_action_no(&self.event) {
+            Some(action_no) => action_no,
+            None => bail!(
+                "Unknown event '{}', expected one of: {}",
+                self.event,
+                EVENT_NAMES
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ),
+        };
+        self.channel.check_recipient(&self.to)?;
+        if let Some(condition) = &self.condition {
+            if !matches!(
+                self.event.as_str(),
+                "inventory-add" | "inventory-edit" | "inventory-remove"
+            ) {
+                bail!("Conditions are only supported for inventory-add, inventory-edit and inventory-remove");
+            }
+            let condition = NotifyCondition::parse(condition)?;
+            if !ctx
+                .config
+                .inventory_schema_declaration
+                .collection
+                .iter()
+                .any(|e| e.name == condition.column)
+            {
+                bail!("Could not find '{}' in table schema", condition.column);
+            }
+        }
+        let rule = NotifyRuleProps {
+            action_no,
+            channel: self.channel,
+            recipient: self.to.clone(),
+            condition: self.condition.clone(),
+        };
+        return ctx.db.notify_rule_add(&rule, &user);
+    }
+}
+
+pub struct NotifyRuleListArgs;
+
+impl NotifyRuleListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("notify_rule_list")?;
+        return Ok(serde_json::to_string(&ctx.db.notify_rule_list()?)?);
+    }
+}
+
+pub struct NotifyRuleRemoveArgs {
+    pub id: u32,
+}
+
+impl NotifyRuleRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("notify_rule_remove")?;
+        return ctx.db.notify_rule_remove(self.id, &user);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NotifySent {
+    pub event_id: u32,
+    pub recipient: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NotifyReport {
+    pub dry_run: bool,
+    pub sent: Vec<NotifySent>,
+    /**
+     * Why sending stopped, the failed and all later events are tried again on the next run
+     */
+    pub error: Option<String>,
+}
+
+pub struct NotifyRunArgs {
+    pub dry_run: bool,
+}
+
+impl NotifyRunArgs {
+    /**
+     * Notifies of each event recorded since the last run that matches a rule through the
+     * channel of the rule. Events are processed in order and a failure stops the run, so that no
+     * event is left out.
+     */
+    pub fn run(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.authorize("notify_pending")?;
+        let pending = ctx.db.notify_pending()?;
+        let notifications = pending
+            .notifications
+            .into_iter()
+            .map(|e| match &e.condition {
+                Some(condition) => Ok((
+                    NotifyCondition::parse(condition)?
+                        .started_matching(e.event.entity.as_ref(), e.previous_entity.as_ref()),
+                    e,
+                )),
+                None => Ok((true, e)),
+            })
+            .collect::<Result<Vec<(bool, DBNotification)>>>()?
+            .into_iter()
+            .filter(|(matched, _)| *matched)
+            .map(|(_, e)| e)
+            .collect::<Vec<DBNotification>>();
+        let mut report = NotifyReport {
+            dry_run: self.dry_run,
+            sent: vec![],
+            error: None,
+        };
+        if self.dry_run {
+            report.sent = notifications
+                .into_iter()
+                .map(|e| NotifySent {
+                    event_id: e.event.id,
+                    recipient: e.recipient,
+                })
+                .collect();
+            return Ok(serde_json::to_string(&report)?);
+        }
+        ctx.check_writable()?;
+        let mut notified = pending.latest_event;
+        for notification in notifications {
+            let sent = notify_channel(notification.channel, ctx.config)
+                .and_then(|channel| channel.send(&notification.recipient, &notification.event));
+            if let Err(e) = sent {
+                notified = notification.event.id - 1;
+                report.error = Some(format!(
+                    "Failed to notify {} of event {} ({})",
+                    notification.recipient, notification.event.id, e
+                ));
+                break;
+            }
+            report.sent.push(NotifySent {
+                event_id: notification.event.id,
+                recipient: notification.recipient,
+            });
+        }
+        ctx.db.notify_advance(notified)?;
+        if report.error.is_some() {
+            bail!(serde_json::to_string(&report)?);
+        }
+        return Ok(serde_json::to_string(&report)?);
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ScheduledJob {
+    pub name: String,
+    /**
+     * Cron expression of five fields, i.e. minute, hour, day of month, month and day of week
+     */
+    pub schedule: String,
+    /**
+     * Arguments of the command, {date} is replaced by the date the job runs at
+     */
+    pub command: Vec<String>,
+    /**
+     * Recipient of an email with the output of each run
+     */
+    #[serde(default)]
+    pub mail_to: Option<String>,
+}
+
+impl ScheduledJob {
+    fn cron(&self) -> Result<Cron> {
+        return match Cron::new(&self.schedule).parse() {
+            Ok(cron) => Ok(cron),
+            Err(e) => bail!("Invalid schedule '{}' ({})", self.schedule, e),
+        };
+    }
+
+    /**
+     * When the job runs next, strictly after the given time.
+     */
+    fn next_run(&self, after: &DateTime<Local>) -> Option<DateTime<Local>> {
+        return self.cron().ok()?.find_next_occurrence(after, false).ok();
+    }
+}
+
+pub struct SchedulerAddArgs {
+    pub name: String,
+    pub schedule: String,
+    pub command: String,
+    pub mail_to: Option<String>,
+}
+
+impl SchedulerAddArgs {
+    pub fn add(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("scheduler_set")?;
+        if ctx
+            .config
+            .scheduler_jobs
+            .iter()
+            .any(|e| e.name == self.name)
+        {
+            bail!("Job '{}' already exists", self.name);
+        }
+        let job = ScheduledJob {
+            name: self.name.clone(),
+            schedule: self.schedule.clone(),
+            command: split_command_line(&self.command)?,
+            mail_to: self.mail_to.clone(),
+        };
+        job.cron()?;
+        match job.command.first().map(|e| e.as_str()) {
+            None => bail!("Command of the job is empty"),
+            Some("scheduler") | Some("shell") => {
+                bail!("Scheduler and shell cannot be run as a job")
+            }
+            _ => {}
+        }
+        if let Some(mail_to) = &self.mail_to {
+            if mail_to.parse::<lettre::Address>().is_err() {
+                bail!("'{}' is not a valid email address", mail_to);
+            }
+        }
+        let mut jobs = ctx.config.scheduler_jobs.clone();
+        jobs.push(job);
+        return ctx.db.scheduler_set(ctx.config, jobs, &user);
+    }
+}
+
+pub struct SchedulerListArgs;
+
+impl SchedulerListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["db"]))?;
+        return Ok(serde_json::to_string(&ctx.config.scheduler_jobs)?);
+    }
+}
+
+pub struct SchedulerRemoveArgs {
+    pub name: String,
+}
+
+impl SchedulerRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("scheduler_set")?;
+        let mut jobs = ctx.config.scheduler_jobs.clone();
+        let count = jobs.len();
+        jobs.retain(|e| e.name != self.name);
+        if jobs.len() == count {
+            bail!("Job '{}' does not exist", self.name);
+        }
+        return ctx.db.scheduler_set(ctx.config, jobs, &user);
+    }
+}
+
+/**
+ * Longest time the scheduler sleeps, so that changes of the jobs are picked up in time.
+ */
+const SCHEDULER_MAX_SLEEP_SECONDS: u64 = 60;
+
+pub struct SchedulerRunArgs;
+
+impl SchedulerRunArgs {
+    /**
+     * Runs the scheduled jobs when they are due until the process is stopped. The result of
+     * each run is written as a line of JSON. Jobs run with the credentials given to the
+     * scheduler and are reloaded from the config before each run.
+     */
+    pub fn run(
+        &self,
+        ctx: &mut CommandContext,
+        replay: &dyn Fn(&[String], &mut CommandContext) -> Result<String>,
+        out: &mut dyn Write,
+    ) -> Result<String> {
+        if ctx.auth.is_some() {
+            ctx.start_session()?;
+        }
+        let mut last_check = Local::now();
+        loop {
+            let now = Local::now();
+            *ctx.config = ctx.db.get_config()?;
+            let jobs = ctx.config.scheduler_jobs.clone();
+            for job in jobs
+                .iter()
+                .filter(|e| e.next_run(&last_check).is_some_and(|next| next <= now))
+            {
+                let result = self.run_job(job, ctx, replay);
+                writeln!(out, "{}", result)?;
+                out.flush()?;
+            }
+            last_check = now;
+
+            let max_sleep = std::time::Duration::from_secs(SCHEDULER_MAX_SLEEP_SECONDS);
+            let sleep = jobs
+                .iter()
+                .filter_map(|e| e.next_run(&now))
+                .min()
+                .and_then(|next| (next - Local::now()).to_std().ok())
+                .unwrap_or(max_sleep)
+                .min(max_sleep);
+            std::thread::sleep(sleep);
+        }
+    }
+
+    fn run_job(
+        &self,
+        job: &ScheduledJob,
+        ctx: &mut CommandContext,
+        replay: &dyn Fn(&[String], &mut CommandContext) -> Result<String>,
+    ) -> serde_json::Value {
+        let started_at = Local::now();
+        let date = started_at.format("%Y-%m-%d").to_string();
+        let args = job
+            .command
+            .iter()
+            .map(|e| e.replace("{date}", &date))
+            .collect::<Vec<String>>();
+        let result = replay(&args, ctx);
+        let mailed = match (&job.mail_to, &ctx.config.smtp) {
+            (None, _) => Ok(()),
+            (Some(_), None) => Err(anyhow!("SMTP is not configured, see notify smtp set")),
+            (Some(mail_to), Some(settings)) => send_email(
+                settings,
+                mail_to,
+                &format!("[invman] {} ({})", job.name, date),
+                match &result {
+                    Ok(response) => response.clone(),
+                    Err(e) => format!("The job failed: {}", e),
+                },
+            ),
+        };
+        let mut line = match result {
+            Ok(response) => serde_json::json!({
+                "job": job.name,
+                "started_at": started_at.to_rfc3339(),
+                "ok": true,
+                "result": serde_json::from_str::<serde_json::Value>(&response)
+                    .unwrap_or(serde_json::Value::String(response)),
+            }),
+            Err(e) => serde_json::json!({
+                "job": job.name,
+                "started_at": started_at.to_rfc3339(),
+                "ok": false,
+                "error": e.to_string(),
+            }),
+        };
+        if let Err(e) = mailed {
+            line["mail_error"] = serde_json::Value::String(e.to_string());
+        }
+        return line;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RuleProps {
+    pub name: String,
+    pub action_no: u32,
+    pub condition: Option<String>,
+    pub set: Vec<String>,
+    pub notify_channel: Option<NotifyChannel>,
+    pub notify_recipient: Option<String>,
+}
+
+pub struct RuleAddArgs {
+    pub name: String,
+    pub event: String,
+    pub condition: Option<String>,
+    pub set: Vec<String>,
+    pub notify_channel: NotifyChannel,
+    pub notify: Option<String>,
+}
+
+impl RuleAddArgs {
+    pub fn add(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("rule_add")?;
+        if !matches!(
+            self.event.as_str(),
+            "inventory-add" | "inventory-edit" | "inventory-remove"
+        ) {
+            bail!(
+                "Rules are only supported for inventory-add, inventory-edit and inventory-remove"
+            );
+        }
+        if self.set.is_empty() && self.notify.is_none() {
+            bail!("A rule must set values, notify or both");
+        }
+        if self.event == "inventory-remove" && !self.set.is_empty() {
+            bail!("Values cannot be set on removed entities");
+        }
+        if let Some(condition) = &self.condition {
+            let condition = NotifyCondition::parse(condition)?;
+            if !ctx
+                .config
+                .inventory_schema_declaration
+                .collection
+                .iter()
+                .any(|e| e.name == condition.column)
+            {
+                bail!("Could not find '{}' in table schema", condition.column);
+            }
+        }
+        self.set
+            .to_key_value_collection(&ctx.config.inventory_schema_declaration)?;
+        if let Some(recipient) = &self.notify {
+            self.notify_channel.check_recipient(recipient)?;
+        }
+        let rule = RuleProps {
+            name: self.name.clone(),
+            action_no: event_action_no(&self.event).unwrap_or_default(),
+            condition: self.condition.clone(),
+            set: self.set.clone(),
+            notify_channel: self.notify.as_ref().map(|_| self.notify_channel),
+            notify_recipient: self.notify.clone(),
+        };
+        return ctx.db.rule_add(&rule, &user);
+    }
+}
+
+pub struct RuleListArgs;
+
+impl RuleListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("rule_list")?;
+        return Ok(serde_json::to_string(&ctx.db.rule_list()?)?);
+    }
+}
+
+pub struct RuleRemoveArgs {
+    pub name: String,
+}
+
+impl RuleRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("rule_remove")?;
+        return ctx.db.rule_remove(&self.name, &user);
+    }
+}
+
+pub struct RulesApplyArgs {
+    /**
+     * Latest event before the command ran, so that only the command's events are matched
+     */
+    pub after_event: u32,
+}
+
+impl RulesApplyArgs {
+    /**
+     * Applies the rules to the events recorded by a command. Changes made by the rules are not
+     * matched again, so that rules cannot trigger each other endlessly.
+     */
+    pub fn apply(&self, ctx: &mut CommandContext) -> Result<()> {
+        let matches = ctx.db.rule_matches(self.after_event)?;
+        if matches.is_empty() {
+            return Ok(());
+        }
+        let user = ctx.authenticate()?;
+        for rule_match in matches {
+            let rule = &rule_match.rule;
+            if let Some(condition) = &rule.condition {
+                let condition = NotifyCondition::parse(condition)?;
+                if !condition.started_matching(
+                    rule_match.event.entity.as_ref(),
+                    rule_match.previous_entity.as_ref(),
+                ) {
+                    continue;
+                }
+            }
+            tracing::info!(
+                rule = rule.name,
+                event = rule_match.event.id,
+                "Rule matched"
+            );
+            let mut applied = || -> Result<()> {
+                if !rule.set.is_empty() {
+                    let id = match rule_match.event.entity.as_ref().and_then(|e| e.get("id")) {
+                        Some(id) => id.to_string(),
+                        None => bail!("Changed entity is unknown"),
+                    };
+                    let set = rule
+                        .set
+                        .to_key_value_collection(&ctx.config.inventory_schema_declaration)?;
+                    ctx.db.inventory_edit(&id, &set, None, ctx.config, &user)?;
+                }
+                if let (Some(channel), Some(recipient)) =
+                    (rule.notify_channel, &rule.notify_recipient)
+                {
+                    notify_channel(channel, ctx.config)?.send(recipient, &rule_match.event)?;
+                }
+                return Ok(());
+            };
+            if let Err(e) = applied() {
+                bail!(
+                    "The command succeeded, but rule '{}' failed ({})",
+                    rule.name,
+                    e
+                );
+            }
+        }
+        return Ok(());
+    }
+}
+
+/**
+ * What happens to the command when one of its hooks fails
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookFailurePolicy {
+    /**
+     * The command fails. Pre hooks keep it from running, while the changes of a command are
+     * only undone by failing post hooks within a batch.
+     */
+    #[default]
+    Fail,
+    /**
+     * The failure is logged as a warning
+     */
+    Warn,
+    Ignore,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Hook {
+    /**
+     * When the hook runs, e.g. pre-inventory-add before the command or post-inventory-add after
+     * each event of that name
+     */
+    pub name: String,
+    /**
+     * Command line run by the shell, receiving the payload as JSON on stdin
+     */
+    pub command: String,
+    #[serde(default)]
+    pub on_failure: HookFailurePolicy,
+}
+
+impl Hook {
+    /**
+     * Runs the hook with the payload on stdin and handles its failure according to the policy.
+     */
+    fn run(&self, payload: &str) -> Result<()> {
+        let result = (|| -> Result<()> {
+            let mut child = Command::new("sh")
+                .arg("-c")
+                .arg(&self.command)
+                .env("INVMAN_HOOK", &self.name)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                // Hooks are free to ignore the payload
+                if let Err(e) = stdin.write_all(payload.as_bytes()) {
+                    if e.kind() != std::io::ErrorKind::BrokenPipe {
+                        return Err(e.into());
+                    }
+                }
+            }
+            let output = child.wait_with_output()?;
+            tracing::debug!(
+                hook = self.name,
+                stdout = %String::from_utf8_lossy(&output.stdout).trim(),
+                "Hook ran"
+            );
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                match stderr.trim() {
+                    "" => bail!("{}", output.status),
+                    stderr => bail!("{} ({})", output.status, stderr),
+                }
+            }
+            return Ok(());
+        })();
+        if let Err(e) = result {
+            match self.on_failure {
+                HookFailurePolicy::Fail => bail!("Hook '{}' failed: {}", self.name, e),
+                HookFailurePolicy::Warn => {
+                    tracing::warn!(hook = self.name, error = %e, "Hook failed")
+                }
+                HookFailurePolicy::Ignore => {
+                    tracing::debug!(hook = self.name, error = %e, "Hook failed")
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+pub struct HookSetArgs {
+    pub name: String,
+    pub command: String,
+    pub on_failure: HookFailurePolicy,
+}
+
+impl HookSetArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("hooks_set")?;
+        match self.name.split_once('-') {
+            Some(("pre", command)) if !command.is_empty() => {}
+            Some(("post", event)) if event_action_no(event).is_some() => {}
+            Some(("post", event)) => bail!(
+                "Unknown event '{}', expected one of: {}",
+                event,
+                EVENT_NAMES
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ),
+            _ => bail!(
+                "Hook '{}' must be named pre-<command> or post-<event>, e.g. post-inventory-add",
+                self.name
+            ),
+        }
+        if self.command.trim().is_empty() {
+            bail!("Command of the hook is empty");
+        }
+        let mut hooks = ctx.config.hooks.clone();
+        hooks.retain(|e| e.name != self.name);
+        hooks.push(Hook {
+            name: self.name.clone(),
+            command: self.command.clone(),
+            on_failure: self.on_failure,
+        });
+        return ctx.db.hooks_set(ctx.config, hooks, &user);
+    }
+}
+
+pub struct HookListArgs;
+
+impl HookListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize_access(Access::read(&["db"]))?;
+        return Ok(serde_json::to_string(&ctx.config.hooks)?);
+    }
+}
+
+pub struct HookRemoveArgs {
+    pub name: String,
+}
+
+impl HookRemoveArgs {
+    pub fn remove(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("hooks_set")?;
+        let mut hooks = ctx.config.hooks.clone();
+        let count = hooks.len();
+        hooks.retain(|e| e.name != self.name);
+        if hooks.len() == count {
+            bail!("Hook '{}' does not exist", self.name);
+        }
+        return ctx.db.hooks_set(ctx.config, hooks, &user);
+    }
+}
+
+pub struct PreHookArgs {
+    /**
+     * Name of the command about to run, e.g. inventory-add
+     */
+    pub command: String,
+    /**
+     * Arguments of the command without the global options
+     */
+    pub args: Vec<String>,
+}
+
+impl PreHookArgs {
+    pub fn run(&self, ctx: &CommandContext) -> Result<()> {
+        let name = format!("pre-{}", self.command);
+        let hook = match ctx.config.hooks.iter().find(|e| e.name == name) {
+            Some(hook) => hook,
+            None => return Ok(()),
+        };
+        let payload = serde_json::json!({
+            "hook": name,
+            "command": self.args,
+        });
+        return hook.run(&payload.to_string());
+    }
+}
+
+pub struct PostHookArgs {
+    /**
+     * Latest event before the command ran, so that only the command's events are passed on
+     */
+    pub after_event: u32,
+}
+
+impl PostHookArgs {
+    pub fn run(&self, ctx: &CommandContext) -> Result<()> {
+        if !ctx.config.hooks.iter().any(|e| e.name.starts_with("post-")) {
+            return Ok(());
+        }
+        for event in ctx.db.event_list(self.after_event)? {
+            let name = format!("post-{}", event.event);
+            if let Some(hook) = ctx.config.hooks.iter().find(|e| e.name == name) {
+                hook.run(&serde_json::to_string(&event)?)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+pub struct PluginListArgs;
+
+impl PluginListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        let _ = ctx.authenticate()?;
+        return Ok(serde_json::to_string(&plugins::list()?)?);
+    }
+}
+
+pub struct PluginReportArgs {
+    pub name: String,
+}
+
+impl PluginReportArgs {
+    pub fn report(&self, ctx: &CommandContext) -> Result<String> {
+        let _ = ctx.authenticate()?;
+        let props = InventoryListProps {
+            limit: -1,
+            after_id: None,
+            fuzzy: None,
+            fuzzy_threshold: 0.0,
+            with_audit: false,
+            with_attachments: false,
+            include_archived: false,
+            supplier: None,
+            assigned_to: None,
+            category: None,
+            recursive: false,
+            raw: &None,
+            params: &vec![],
+            changed_since: None,
+            filter: None,
+            group: None,
+            expand: vec![],
+        };
+        let entities = ctx.db.inventory_list(&props, ctx.config)?.to_json();
+        return plugins::report(&self.name, &entities);
+    }
+}
+
+/**
+ * How often the event log is checked for new events while watching it
+ */
+pub(crate) const EVENTS_POLL_MILLISECONDS: u64 = 500;
+
+pub struct EventsWatchArgs {
+    /**
+     * Names of the events to write, all events if empty
+     */
+    pub events: Vec<String>,
+    /**
+     * Event to start after, only new events are written if not given
+     */
+    pub after_event: Option<u32>,
+}
+
+impl EventsWatchArgs {
+    /**
+     * Writes the events as lines of JSON as soon as they are recorded, until the process is
+     * stopped, e.g. to update dashboards without polling the database themselves.
+     */
+    pub fn watch(&self, ctx: &CommandContext, out: &mut dyn Write) -> Result<String> {
+        ctx.authorize("event_list")?;
+        check_event_names(&self.events)?;
+        let mut after_event = match self.after_event {
+            Some(after_event) => after_event,
+            None => ctx.db.latest_event()?,
+        };
+        loop {
+            for event in ctx.db.event_list(after_event)? {
+                after_event = event.id;
+                if self.events.is_empty() || self.events.contains(&event.event) {
+                    writeln!(out, "{}", serde_json::to_string(&event)?)?;
+                }
+            }
+            out.flush()?;
+            std::thread::sleep(std::time::Duration::from_millis(EVENTS_POLL_MILLISECONDS));
+        }
+    }
+}
+
+pub(crate) fn check_event_names(events: &[String]) -> Result<()> {
+    if let Some(event) = events.iter().find(|e| event_action_no(e).is_none()) {
+        bail!(
+            "Unknown event '{}', expected one of: {}",
+            event,
+            EVENT_NAMES
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<&str>>()
+                .join(", ")
+        );
+    }
+    return Ok(());
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TokenClaims {
+    /**
+     * Name of the user the token was issued to
+     */
+    pub sub: String,
+    /**
+     * Id of the token, to check whether it was revoked
+     */
+    pub jti: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+impl TokenClaims {
+    pub(crate) fn decode(token: &str, secret: &str) -> Result<TokenClaims> {
+        return match jsonwebtoken::decode::<TokenClaims>(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(secret.as_bytes()),
+            &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256),
+        ) {
+            Ok(data) => Ok(data.claims),
+            Err(e) => bail!("Invalid token ({})", e),
+        };
+    }
+
+    fn encode(&self, secret: &str) -> Result<String> {
+        return Ok(jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256),
+            self,
+            &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()),
+        )?);
+    }
+}
+
+/**
+ * Areas of commands that scopes of tokens refer to, named like the commands. `schema` covers
+ * `inventory schema`, which is kept apart from the entities.
+ */
+pub const SCOPE_AREAS: [&str; 24] = [
+    "user",
+    "config",
+    "inventory",
+    "schema",
+    "supplier",
+    "category",
+    "loans",
+    "serial",
+    "bom",
+    "kit",
+    "stocktake",
+    "maintenance",
+    "attachments",
+    "comments",
+    "db",
+    "sync",
+    "notify",
+    "scheduler",
+    "rule",
+    "hook",
+    "plugin",
+    "events",
+    "org",
+    "role",
+];
+
+/**
+ * What a scope allows within its area, each level includes the ones below it.
+ */
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ScopeLevel {
+    Read,
+    Write,
+    /**
+     * Changes of the structure or the users, e.g. removing a schema column
+     */
+    Admin,
+}
+
+impl fmt::Display for ScopeLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScopeLevel::Read => write!(f, "read"),
+            ScopeLevel::Write => write!(f, "write"),
+            ScopeLevel::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for ScopeLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        return match s {
+            "read" => Ok(ScopeLevel::Read),
+            "write" => Ok(ScopeLevel::Write),
+            "admin" => Ok(ScopeLevel::Admin),
+            _ => bail!("Unknown scope level '{}', use read, write or admin", s),
+        };
+    }
+}
+
+/**
+ * Parses a scope in area:level notation, e.g. inventory:write. The area * stands for all areas.
+ */
+fn parse_scope(scope: &str) -> Result<(&str, ScopeLevel)> {
+    let (area, level) = match scope.split_once(':') {
+        Some(e) => e,
+        None => bail!("Scope '{}' is not in area:level notation", scope),
+    };
+    if area != "*" && !SCOPE_AREAS.contains(&area) {
+        bail!(
+            "Unknown scope area '{}', use * or one of: {}",
+            area,
+            SCOPE_AREAS.join(", ")
+        );
+    }
+    return Ok((area, level.parse()?));
+}
+
+/**
+ * Issues a signed token (JWT) for the user, valid for the given seconds.
+ */
+fn issue_token(
+    ctx: &mut CommandContext,
+    user: &DBUser,
+    username: &str,
+    ttl: i64,
+    scopes: &[String],
+) -> Result<String> {
+    let now = Local::now().timestamp();
+    let jti = ctx.db.token_add(now + ttl, scopes, user)?;
+    let claims = TokenClaims {
+        sub: username.to_string(),
+        jti,
+        iat: now,
+        exp: now + ttl,
+    };
+    return claims.encode(&ctx.db.token_secret()?);
+}
+
+pub struct UserPurgeArgs {
+    pub username: String,
+}
+
+impl UserPurgeArgs {
+    pub fn purge(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("user_purge")?;
+        let report = ctx.db.user_purge(&self.username, ctx.config, &user)?;
+        // Signed like tokens, so the summary can be proven to come from this database
+        let signature = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256),
+            &report,
+            &jsonwebtoken::EncodingKey::from_secret(ctx.db.token_secret()?.as_bytes()),
+        )?;
+        return Ok(serde_json::json!({
+            "summary": report,
+            "signature": signature,
+        })
+        .to_string());
+    }
+}
+
+/**
+ * Number of recovery codes generated when two-factor authentication is enabled
+ */
+const RECOVERY_CODES: usize = 10;
+
+pub struct UserTotpEnableArgs;
+
+#[derive(Serialize)]
+struct TotpEnrollment {
+    secret: String,
+    /**
+     * URI to import the secret into an authenticator app, which is what its QR codes contain
+     */
+    uri: String,
+    recovery_codes: Vec<String>,
+}
+
+impl UserTotpEnableArgs {
+    /**
+     * Generates a TOTP secret and recovery codes for the user logged in with username and
+     * password. They are only shown once, the recovery codes are stored hashed.
+     */
+    pub fn enable(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("user_totp_enable")?;
+        let username = match ctx.auth.as_deref().and_then(|e| e.split_once(':')) {
+            Some((username, _)) => username.to_string(),
+            None => bail!("Two-factor authentication can only be enabled when logging in with username and password"),
+        };
+        let random = ring::rand::SystemRandom::new();
+        let mut secret = [0u8; 20];
+        if random.fill(&mut secret).is_err() {
+            bail!("Could not generate a random secret");
+        }
+        let secret = base32_encode(&secret);
+        let mut recovery_codes = vec![];
+        for _ in 0..RECOVERY_CODES {
+            let mut code = [0u8; 5];
+            if random.fill(&mut code).is_err() {
+                bail!("Could not generate a random recovery code");
+            }
+            let code = base32_encode(&code).to_lowercase();
+            recovery_codes.push(format!("{}-{}", &code[..4], &code[4..]));
+        }
+        ctx.db.user_totp_enable(&secret, &recovery_codes, &user)?;
+        // Everything but unreserved characters of the username is percent-encoded
+        let label = username
+            .bytes()
+            .map(|e| match e {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (e as char).to_string()
+                }
+                _ => format!("%{:02X}", e),
+            })
+            .collect::<String>();
+        let enrollment = TotpEnrollment {
+            uri: format!(
+                "otpauth://totp/invman:{}?secret={}&issuer=invman",
+                label, secret
+            ),
+            secret,
+            recovery_codes,
+        };
+        return Ok(serde_json::to_string(&enrollment)?);
+    }
+}
+
+pub struct UserTotpDisableArgs {
+    /**
+     * User whose two-factor authentication is disabled, e.g. after losing the authenticator
+     * app and the recovery codes. The logged-in user if None.
+     */
+    pub username: Option<String>,
+}
+
+impl UserTotpDisableArgs {
+    pub fn disable(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("user_totp_disable")?;
+        if self.username.is_some() {
+            require_write(&user, "users")?;
+        }
+        return ctx.db.user_totp_disable(self.username.as_deref(), &user);
+    }
+}
+
+pub struct UserTokenIssueArgs {
+    /**
+     * How long the token is valid, e.g. 30d
+     */
+    pub ttl: String,
+    /**
+     * Scopes in area:level notation limiting the commands of the token, none for all commands
+     */
+    pub scopes: Vec<String>,
+}
+
+impl UserTokenIssueArgs {
+    pub fn issue(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("token_add")?;
+        return self.issue_for(ctx, &user);
+    }
+
+    /**
+     * Issues the token for the user the context was authenticated as before, e.g. by a server.
+     */
+    pub(crate) fn issue_for(&self, ctx: &mut CommandContext, user: &DBUser) -> Result<String> {
+        ctx.check_writable()?;
+        let username = match ctx.auth.as_deref().and_then(|e| e.split_once(':')) {
+            Some((username, _)) => username.to_string(),
+            None => bail!("Tokens can only be issued when logging in with username and password"),
+        };
+        let ttl = i64::from(parse_days(&self.ttl)?) * 24 * 60 * 60;
+        for scope in &self.scopes {
+            parse_scope(scope)?;
+        }
+        return issue_token(ctx, user, &username, ttl, &self.scopes);
+    }
+}
+
+pub struct UserTokenRefreshArgs;
+
+impl UserTokenRefreshArgs {
+    /**
+     * Replaces the token used to log in by a new one of the same validity period, so that it
+     * can be used continuously without ever knowing the password.
+     */
+    pub fn refresh(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authenticate()?;
+        return self.refresh_for(ctx, &user);
+    }
+
+    pub(crate) fn refresh_for(&self, ctx: &mut CommandContext, user: &DBUser) -> Result<String> {
+        ctx.check_writable()?;
+        let claims = match ctx.auth.as_deref() {
+            Some(token) if !token.contains(':') => {
+                TokenClaims::decode(token, &ctx.db.token_secret()?)?
+            }
+            _ => bail!("Only tokens can be refreshed, log in with the token to refresh"),
+        };
+        // The new token keeps the scopes, so refreshing never widens what it may run
+        let token = issue_token(
+            ctx,
+            user,
+            &claims.sub,
+            claims.exp - claims.iat,
+            &user.scopes,
+        )?;
+        ctx.db.token_revoke(&claims.jti, user)?;
+        return Ok(token);
+    }
+}
+
+pub struct UserTokenListArgs;
+
+impl UserTokenListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authorize("token_list")?;
+        return Ok(serde_json::to_string(&ctx.db.token_list(&user)?)?);
+    }
+}
+
+pub struct UserTokenRevokeArgs {
+    pub id: String,
+}
+
+impl UserTokenRevokeArgs {
+    pub fn revoke(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("token_revoke")?;
+        return ctx.db.token_revoke(&self.id, &user);
+    }
+}
+
+pub struct OrgCreateArgs {
+    pub name: String,
+}
+
+impl OrgCreateArgs {
+    pub fn create(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("org_create")?;
+        if user.org_id.is_some() {
+            bail!("Members of an organization cannot create further organizations");
+        }
+        if self.name.trim().is_empty() {
+            bail!("Organization name must not be empty");
+        }
+        return ctx.db.org_create(&self.name, &user);
+    }
+}
+
+pub struct OrgInviteArgs {
+    pub org: String,
+    pub username: String,
+}
+
+impl OrgInviteArgs {
+    pub fn invite(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("org_invite")?;
+        return ctx.db.org_invite(&self.org, &self.username, &user);
+    }
+}
+
+pub struct OrgListArgs;
+
+impl OrgListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("org_list")?;
+        return Ok(serde_json::to_string(&ctx.db.org_list()?)?);
+    }
+}
+
+/**
+ * Which entities the users of a role may change
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OwnershipPolicy {
+    /**
+     * Any entity, regardless of who added it
+     */
+    #[default]
+    All,
+    /**
+     * Only entities added by the user
+     */
+    Own,
+}
+
+impl fmt::Display for OwnershipPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OwnershipPolicy::All => write!(f, "all"),
+            OwnershipPolicy::Own => write!(f, "own"),
+        }
+    }
+}
+
+impl FromStr for OwnershipPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        return match s {
+            "all" => Ok(OwnershipPolicy::All),
+            "own" => Ok(OwnershipPolicy::Own),
+            _ => bail!("Unknown ownership policy '{}'", s),
+        };
+    }
+}
+
+pub struct RoleListArgs;
+
+impl RoleListArgs {
+    pub fn list(&self, ctx: &CommandContext) -> Result<String> {
+        ctx.authorize("role_list")?;
+        return Ok(serde_json::to_string(&ctx.db.role_list()?)?);
+    }
+}
+
+pub struct RoleOwnershipArgs {
+    pub role: String,
+    pub ownership: OwnershipPolicy,
+}
+
+impl RoleOwnershipArgs {
+    pub fn set(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("role_set_ownership")?;
+        return ctx.db.role_set_ownership(&self.role, self.ownership, &user);
+    }
+}
+
+pub struct RoleDenyArgs {
+    pub role: String,
+    pub column: String,
+    pub mode: PermissionMode,
+    /**
+     * Whether the column is denied, or allowed again
+     */
+    pub deny: bool,
+}
+
+impl RoleDenyArgs {
+    pub fn deny(&self, ctx: &mut CommandContext) -> Result<String> {
+        let user = ctx.authorize("role_deny_column")?;
+        if self.deny
+            && !ctx
+                .config
+                .inventory_schema_declaration
+                .collection
+                .iter()
+                .any(|e| e.name == self.column)
+        {
+            bail!(
+                "Field {} could not be found in schema declaration",
+                self.column
+            );
+        }
+        return ctx
+            .db
+            .role_deny_column(&self.role, &self.column, self.mode, self.deny, &user);
+    }
+}
+
+/**
+ * Where a search looks for its text, limited to what the user may read
+ */
+#[derive(Serialize, Deserialize)]
+pub struct SearchProps {
+    /**
+     * Text and varchar columns of the inventory
+     */
+    pub columns: Vec<String>,
+    pub comments: bool,
+    pub attachments: bool,
+    pub suppliers: bool,
+    /**
+     * Keys and values of the metadata, not searched for clients that do not know it
+     */
+    #[serde(default)]
+    pub meta: bool,
+    /**
+     * Maximum number of matches per group
+     */
+    pub limit: u32,
+}
+
+pub struct SearchArgs {
+    pub text: String,
+    pub limit: u32,
+}
+
+impl SearchArgs {
+    pub fn search(&self, ctx: &CommandContext) -> Result<String> {
+        let user = ctx.authorize("search")?;
+        if self.text.trim().is_empty() {
+            bail!("Search text cannot be empty");
+        }
+        let props = SearchProps {
+            columns: ctx
+                .config
+                .inventory_schema_declaration
+                .collection
+                .iter()
+                .filter(|e| matches!(e.column_type, ColumnType::TEXT | ColumnType::VARCHAR))
+                .filter(|e| !user.is_read_denied("inventory", &e.name))
+                .map(|e| e.name.clone())
+                .collect(),
+            comments: user.can_read_table("comments"),
+            attachments: user.can_read_table("attachments"),
+            suppliers: user.can_read_table("suppliers"),
+            meta: user.can_read_table("meta"),
+            limit: self.limit,
+        };
+        return Ok(serde_json::to_string(
+            &ctx.db.search(self.text.trim(), &props)?,
+        )?);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchPhase {
+    pub seconds: f64,
+    pub per_second: f64,
+}
+
+impl BenchPhase {
+    fn measure(count: u32, run: impl FnOnce() -> Result<()>) -> Result<BenchPhase> {
+        let started = Instant::now();
+        run()?;
+        let seconds = started.elapsed().as_secs_f64();
+        return Ok(BenchPhase {
+            seconds,
+            per_second: f64::from(count) / seconds.max(f64::EPSILON),
+        });
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    pub entities: u32,
+    pub schema_cols: u32,
+    /**
+     * Adding the entities one by one
+     */
+    pub add: BenchPhase,
+    /**
+     * Listing all entities at once, counted in entities
+     */
+    pub list: BenchPhase,
+    /**
+     * Editing a column of every entity one by one
+     */
+    pub edit: BenchPhase,
+    /**
+     * Size of the database file in bytes after all phases
+     */
+    pub file_size: u64,
+}
+
+pub struct BenchArgs {
+    pub entities: u32,
+    pub schema_cols: u32,
+}
+
+impl BenchArgs {
+    /**
+     * Measures the throughput of the inventory commands on synthetic data. The data is written
+     * into a temporary database, which is removed afterwards, so the storage is left untouched.
+     */
+    pub fn bench(&self) -> Result<String> {
+        if self.entities == 0 || self.schema_cols == 0 {
+            bail!("Entities and schema columns must be greater than 0");
+        }
+        let path = std::env::temp_dir().join(format!("invman-bench-{}", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let report = self.run(&path);
+        for suffix in ["", "-journal", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", path, suffix));
+        }
+        return Ok(serde_json::to_string(&report?)?);
+    }
+
+    fn run(&self, path: &str) -> Result<BenchReport> {
+        let mut db = InvManConnection::sqlite_create(path)?;
+        db.user_register("bench", "bench")?;
+        let mut config = db.get_config()?;
+        let mut ctx = CommandContext {
+            db: &mut db,
+            config: &mut config,
+            auth: Some("bench:bench".into()),
+            otp: None,
+            output: OutputType::Json,
+            read_only: false,
+            session: None,
+        };
+        ctx.start_session()?;
+        for column in 0..self.schema_cols {
+            // Every fourth column is of the same type, the first one is unique like an SKU
+            let (column_type, max_length, max) = match column % 4 {
+                0 => (ColumnType::VARCHAR, Some(64), None),
+                1 => (ColumnType::TEXT, Some(255), None),
+                2 => (ColumnType::INT, None, Some(1_000_000)),
+                _ => (ColumnType::REAL, None, Some(1_000_000)),
+            };
+            InventorySchemaAlterArgs {
+                name: format!("col_{}", column + 1),
+                display_name: None,
+                unique: column == 0,
+                max_length,
+                min_length: None,
+                max,
+                min: None,
+                nullable: Some(true),
+                column_type,
+                default: None,
+                hint: None,
+                layout: None,
+                serialized: false,
+            }
+            .alter(&mut ctx)?;
+        }
+
+        let add = BenchPhase::measure(self.entities, || {
+            for entity in 0..self.entities {
+                InventoryAddArgs {
+                    params: (0..self.schema_cols)
+                        .map(|column| bench_value(entity, column))
+                        .collect(),
+                    positional: false,
+                    allow_extra: false,
+                    validate_only: false,
+                }
+                .add(&mut ctx)?;
+            }
+            return Ok(());
+        })?;
+        let list = BenchPhase::measure(self.entities, || {
+            InventoryListArgs {
+                limit: None,
+                after_id: None,
+                fuzzy: None,
+                fuzzy_threshold: 0.6,
+                with_audit: false,
+                with_attachments: false,
+                include_archived: false,
+                supplier: None,
+                assigned_to: None,
+                category: None,
+                recursive: false,
+                sort: vec![],
+                raw: None,
+                params: vec![],
+                filter: None,
+                group_by: vec![],
+                aggregate: None,
+                expand: vec![],
+                condition: vec![],
+                explain: false,
+            }
+            .list(&ctx)?;
+            return Ok(());
+        })?;
+        let edit = BenchPhase::measure(self.entities, || {
+            for entity in 0..self.entities {
+                InventoryEditArgs {
+                    identifier: (entity + 1).to_string(),
+                    set: vec![bench_value(entity + self.entities, self.schema_cols - 1)],
+                    expect_version: None,
+                }
+                .edit(&mut ctx)?;
+            }
+            return Ok(());
+        })?;
+
+        return Ok(BenchReport {
+            entities: self.entities,
+            schema_cols: self.schema_cols,
+            add,
+            list,
+            edit,
+            file_size: fs::metadata(path)?.len(),
+        });
+    }
+}
+
+/**
+ * Synthetic value of the column for the entity in name=value notation, unique per entity.
+ */
+fn bench_value(entity: u32, column: u32) -> String {
+    let value = match column % 4 {
+        0 => format!("item-{}-{}", entity, column),
+        1 => format!(
+            "Synthetic note {} of entity {} used for benchmarking",
+            column, entity
+        ),
+        2 => ((u64::from(entity) * 31 + u64::from(column)) % 1_000_000).to_string(),
+        _ => format!("{:.2}", f64::from(entity % 100_000) / 10.0),
+    };
+    return format!("col_{}={}", column + 1, value);
+}
+
+pub struct DemoSeedArgs {
+    pub entities: u32,
+    /**
+     * Seed of the generated data, the same seed generating the same entities. Random if None.
+     */
+    pub seed: Option<u64>,
+}
+
+impl DemoSeedArgs {
+    /**
+     * Installs the sample schema (name, sku, quantity, price, location) and adds the entities
+     * with fake data within a single transaction. Columns the schema already has are kept as
+     * long as they are of the same type, so seeding can be repeated.
+     */
+    pub fn seed(&self, ctx: &mut CommandContext) -> Result<String> {
+        ctx.check_writable()?;
+        if self.entities == 0 {
+            bail!("Entities must be greater than 0");
+        }
+        let columns = [
+            ("name", ColumnType::VARCHAR, false, Some(64)),
+            ("sku", ColumnType::VARCHAR, true, Some(32)),
+            ("quantity", ColumnType::INT, false, None),
+            ("price", ColumnType::MONEY, false, None),
+            ("location", ColumnType::VARCHAR, false, Some(64)),
+        ];
+        for (name, column_type, unique, max_length) in columns {
+            let existing = ctx
+                .config
+                .inventory_schema_declaration
+                .collection
+                .iter()
+                .find(|e| e.name == name);
+            match existing {
+                Some(e) if e.column_type == column_type => continue,
+                Some(e) => bail!(
+                    "Schema column '{}' is of type {} but the demo needs {}",
+                    name,
+                    e.column_type,
+                    column_type
+                ),
+                None => InventorySchemaAlterArgs {
+                    name: name.into(),
+                    display_name: None,
+                    unique,
+                    max_length,
+                    min_length: None,
+                    max: None,
+                    min: None,
+                    nullable: Some(true),
+                    column_type,
+                    default: None,
+                    hint: None,
+                    layout: None,
+                    serialized: false,
+                }
+                .alter(ctx)?,
+            };
+        }
+
+        let seed = match self.seed {
+            Some(seed) => seed,
+            None => {
+                let mut bytes = [0u8; 8];
+                if ring::rand::SystemRandom::new().fill(&mut bytes).is_err() {
+                    bail!("Could not generate a random seed");
+                }
+                u64::from_le_bytes(bytes)
+            }
+        };
+        let currency = ctx
+            .config
+            .exchange_rates
+            .base
+            .clone()
+            .unwrap_or("EUR".into());
+        // SKUs continue after the entities already there, so they stay unique when seeding again
+        let offset = ctx.db.inventory_count(&vec![].into())?;
+        let mut random = DemoRandom::new(seed);
+
+        // Authenticated once instead of for every entity
+        let session = ctx.session.clone();
+        ctx.session = Some(ctx.authenticate()?);
+        ctx.db.batch_begin()?;
+        for entity in 0..self.entities {
+            let params = random.entity(offset + entity + 1, &currency);
+            if let Err(e) = (InventoryAddArgs {
+                params,
+                positional: false,
+                allow_extra: false,
+                validate_only: false,
+            })
+            .add(ctx)
+            {
+                ctx.db.batch_rollback()?;
+                ctx.session = session;
+                bail!("Entity {}: {}\nNo demo entity was added", entity + 1, e);
+            }
+        }
+        ctx.db.batch_commit()?;
+        ctx.session = session;
+        return Ok(format!(
+            "{} demo entities were added with seed {}",
+            self.entities, seed
+        ));
+    }
+}
+
+/**
+ * Pseudo-random generator of the demo data (xorshift64*), good enough for fake entities and
+ * reproducible by its seed.
+ */
+struct DemoRandom(u64);
+
+impl DemoRandom {
+    /**
+     * Scrambles the seed (splitmix64), as small seeds would otherwise start out alike
+     */
+    fn new(seed: u64) -> DemoRandom {
+        let mut x = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        // Xorshift gets stuck at 0, any other state works
+        return DemoRandom((x ^ (x >> 31)).max(1));
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        return x.wrapping_mul(0x2545_f491_4f6c_dd1d);
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        return self.next() % bound;
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        return items[self.below(items.len() as u64) as usize];
+    }
+
+    /**
+     * A fake entity in name=value notation, the number making its SKU unique
+     */
+    fn entity(&mut self, number: u32, currency: &str) -> Vec<String> {
+        const ADJECTIVES: [&str; 12] = [
+            "Compact",
+            "Heavy-Duty",
+            "Stainless",
+            "Wireless",
+            "Ergonomic",
+            "Industrial",
+            "Portable",
+            "Galvanized",
+            "Insulated",
+            "Adjustable",
+            "Precision",
+            "Reinforced",
+        ];
+        const PRODUCTS: [(&str, &str); 16] = [
+            ("Cordless Drill", "DRL"),
+            ("Hex Bolt M8", "BLT"),
+            ("Cable Tie Pack", "CTP"),
+            ("Safety Goggles", "SGG"),
+            ("Work Gloves", "GLV"),
+            ("Extension Cord", "EXT"),
+            ("Storage Bin", "BIN"),
+            ("Tape Measure", "TPM"),
+            ("Screwdriver Set", "SCR"),
+            ("LED Work Light", "LED"),
+            ("Pipe Wrench", "WRN"),
+            ("Wood Screws 4x40", "SCW"),
+            ("Soldering Iron", "SOL"),
+            ("Multimeter", "MTR"),
+            ("Label Printer Tape", "LBL"),
+            ("Shelf Bracket", "BRK"),
+        ];
+        const WAREHOUSES: [&str; 4] = ["Main Warehouse", "North Depot", "Workshop", "Store Front"];
+
+        let (product, code) = PRODUCTS[self.below(PRODUCTS.len() as u64) as usize];
+        let adjective = self.pick(&ADJECTIVES);
+        let location = format!(
+            "{} / Aisle {} / Shelf {}",
+            self.pick(&WAREHOUSES),
+            (b'A' + self.below(8) as u8) as char,
+            self.below(12) + 1
+        );
+        // Mostly small stock with the odd bulk item, prices from cents to a few hundred
+        let quantity = match self.below(10) {
+            0 => 0,
+            9 => 500 + self.below(4500),
+            _ => 1 + self.below(120),
+        };
+        let price = (self.below(40_000) + 99) as f64 / 100.0;
+        return vec![
+            format!("name={} {}", adjective, product),
+            format!("sku={}-{:06}", code, number),
+            format!("quantity={}", quantity),
+            format!("price={:.2} {}", price, currency),
+            format!("location={}", location),
+        ];
+    }
+}
+
+pub struct ServeArgs {
+    pub database: String,
+    /**
+     * Address the server listens on, in host:port notation
+     */
+    pub listen: String,
+    /**
+     * Number of requests handled at the same time, each with a connection of its own
+     */
+    pub workers: usize,
+    pub read_only: bool,
+    /**
+     * Encrypts the connections, otherwise the server speaks plain HTTP
+     */
+    pub tls: Option<ServeTls>,
+    /**
+     * Serves the InventoryService of proto/invman/v1/inventory.proto instead of JSON over HTTP
+     */
+    pub grpc: bool,
+    /**
+     * Credentials the retention rules are applied with once a day, without them they are only
+     * applied by db apply-retention
+     */
+    pub auth: Option<String>,
+}
+
+/**
+ * Certificate the server identifies itself with, as paths to PEM files
+ */
+pub struct ServeTls {
+    pub certificate: String,
+    pub key: String,
+    /**
+     * CA certificates that issued the certificates clients have to present, without it every
+     * client may connect
+     */
+    pub client_ca: Option<String>,
+}
+
+impl ServeArgs {
+    /**
+     * Serves the database to clients started with --remote until the process is stopped. Each
+     * request is authenticated on its own, so that no session has to be started.
+     */
+    pub fn serve(&self) -> Result<String> {
+        if self.workers == 0 {
+            bail!("At least one worker is needed to serve the database");
+        }
+        if let (Some(auth), false) = (&self.auth, self.read_only) {
+            // The first run verifies the credentials before the server starts
+            if let Some(report) = apply_retention(&self.database, auth)? {
+                tracing::info!(report, "Applied the retention rules");
+            }
+            let (database, auth) = (self.database.clone(), auth.clone());
+            std::thread::spawn(move || loop {
+                std::thread::sleep(std::time::Duration::from_secs(RETENTION_INTERVAL_SECONDS));
+                match apply_retention(&database, &auth) {
+                    Ok(Some(report)) => tracing::info!(report, "Applied the retention rules"),
+                    Ok(None) => {}
+                    Err(e) => tracing::error!(error = %e, "Could not apply the retention rules"),
+                }
+            });
+        }
+        server::serve(
+            &self.database,
+            &self.listen,
+            self.workers,
+            self.read_only,
+            self.tls.as_ref(),
+            self.grpc,
+        )?;
+        return Ok("Server stopped".into());
+    }
+}
+
+const RETENTION_INTERVAL_SECONDS: u64 = 24 * 60 * 60;
+
+/**
+ * Applies the retention rules of the database like db apply-retention, with the rules and the
+ * read-only option of its current config.
+ *
+ * @returns The report, or None if no rule is set or the database is read-only
+ */
+fn apply_retention(database: &str, auth: &str) -> Result<Option<String>> {
+    let mut db = InvManConnection::sqlite_at(database)?;
+    let mut config = db.get_config()?;
+    let rules = &config.retention_rules;
+    if config.read_only
+        || (rules.purge_deleted_after_days.is_none() && rules.prune_events_after_days.is_none())
+    {
+        return Ok(None);
     }
+    let mut ctx = CommandContext::new(&mut db, &mut config, Some(auth.into()), OutputType::Json);
+    let apply = RetentionApplyArgs { dry_run: false };
+    return match apply.apply(&mut ctx) {
+        Ok(report) => Ok(Some(report)),
+        Err(e) => bail!("Could not apply the retention rules ({})", e),
+    };
 }
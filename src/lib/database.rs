@@ -17,14 +17,38 @@
  * You should have received a copy of the GNU General Public License
  * along with invman. If not, see <https://www.gnu.org/licenses/>.
  */
+#[cfg(feature = "async")]
+mod asynchronous;
+mod remote;
 mod sqlite;
 
+#[cfg(feature = "async")]
+pub use self::asynchronous::{InvManDBPoolAsync, InvManSqliteAsync};
+pub use self::remote::InvManHttpClient;
 pub(crate) use self::sqlite::InvManSqlite;
+pub use self::sqlite::{InvManSqlitePool, PooledSqlite};
 use crate::{
-    common::args::{ColumnType, InventoryListProps, SchemaDeclaration},
+    common::args::{
+        AdjustProps, BomComponentProps, BuildProps, ColumnType, DisplayFormat, ExchangeRates, Hook,
+        InventoryListProps, KitItemProps, LdapSettings, LoanProps, MergeStrategy, NotifyChannel,
+        NotifyRuleProps, OwnershipPolicy, PasswordPolicy, PickProps, PurchaseProps, RetentionRules,
+        RuleProps, ScheduledJob, SchemaDeclaration, SearchProps, SmtpSettings, SupplierProps,
+        TransferProps, WhereExpr,
+    },
     utils::InvManSerialization,
 };
 use anyhow::{bail, Result};
+pub use invman_core::SchemaCollection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/**
+ * Identifies a dump of an invman database. The version is raised whenever the layout of the
+ * dump itself changes.
+ */
+pub const DUMP_FORMAT: &str = "invman-dump";
+pub const DUMP_VERSION: u32 = 1;
 
 #[derive(Debug, Copy, Clone)]
 enum SchemaActionNo {
@@ -37,21 +61,332 @@ enum DBOpNo {
     Add = 1,
     Edit = 2,
     Delete = 3,
+    Snapshot = 4,
+    Merge = 5,
 }
 
 #[derive(Debug, Copy, Clone)]
 enum EventActionNo {
+    #[allow(dead_code)]
     UserRegister = 100,
+    UserPurge = 101,
+    UserTotpEnable = 102,
+    UserTotpDisable = 103,
+    UserPasswordChange = 104,
+    UserPasswordExpire = 105,
 
     InventoryAdd = 200,
     InventoryEdit = 201,
     InventoryRemove = 202,
+    InventoryCheckout = 203,
+    InventoryCheckin = 204,
+    InventoryAdjust = 205,
+    InventoryTransfer = 206,
+    InventoryAssign = 207,
+    InventoryUnassign = 208,
+    InventoryPurchase = 209,
+    InventoryArchive = 210,
+    InventoryMerge = 211,
+
+    SupplierAdd = 300,
+    SupplierEdit = 301,
+    SupplierRemove = 302,
+    SupplierLink = 303,
+    SupplierUnlink = 304,
+
+    BomDefine = 400,
+    BomBuild = 401,
+
+    MaintenanceSchedule = 500,
+    MaintenanceDone = 501,
+
+    AttachmentAdd = 600,
+    AttachmentRemove = 601,
+
+    CommentAdd = 700,
+
+    ImageSet = 800,
+    ImageRemove = 801,
+
+    RetentionSet = 900,
+    RetentionApply = 901,
+    HistoryCompact = 902,
+    Optimize = 903,
+    ReadOnlySet = 904,
+
+    NotifySmtpSet = 1000,
+    NotifyRuleAdd = 1001,
+    NotifyRuleRemove = 1002,
+
+    SchedulerSet = 1100,
+
+    RuleAdd = 1200,
+    RuleRemove = 1201,
+
+    HooksSet = 1300,
+
+    TokenIssue = 1400,
+    TokenRevoke = 1401,
+
+    OrgCreate = 1500,
+    OrgInvite = 1501,
+
+    RoleEdit = 1600,
+
+    TimezoneSet = 1700,
+    DisplayFormatSet = 1701,
+    LabelPrinterSet = 1702,
+    ExchangeRatesSet = 1703,
+    ReasonCodesSet = 1704,
+    LdapSet = 1705,
+    PasswordPolicySet = 1706,
+    RegistrationSet = 1707,
+
+    CategoryCreate = 1800,
+    CategoryRemove = 1801,
+    CategoryAssign = 1802,
+    CategoryUnassign = 1803,
+
+    KitDefine = 1900,
+    KitRemove = 1901,
+    KitPick = 1902,
+
+    StocktakeStart = 2000,
+    StocktakeRecord = 2001,
+    StocktakeClose = 2002,
+    StocktakeCancel = 2003,
+
+    MetaSet = 2100,
+    MetaRemove = 2101,
+}
+
+/**
+ * Names of the events that notifications can be configured for, by their action number.
+ */
+pub const EVENT_NAMES: &[(&str, u32)] = &[
+    ("user-register", EventActionNo::UserRegister as u32),
+    ("user-purge", EventActionNo::UserPurge as u32),
+    ("user-totp-enable", EventActionNo::UserTotpEnable as u32),
+    ("user-totp-disable", EventActionNo::UserTotpDisable as u32),
+    (
+        "user-password-change",
+        EventActionNo::UserPasswordChange as u32,
+    ),
+    (
+        "user-password-expire",
+        EventActionNo::UserPasswordExpire as u32,
+    ),
+    ("inventory-add", EventActionNo::InventoryAdd as u32),
+    ("inventory-edit", EventActionNo::InventoryEdit as u32),
+    ("inventory-remove", EventActionNo::InventoryRemove as u32),
+    (
+        "inventory-checkout",
+        EventActionNo::InventoryCheckout as u32,
+    ),
+    ("inventory-checkin", EventActionNo::InventoryCheckin as u32),
+    ("inventory-adjust", EventActionNo::InventoryAdjust as u32),
+    (
+        "inventory-transfer",
+        EventActionNo::InventoryTransfer as u32,
+    ),
+    ("inventory-assign", EventActionNo::InventoryAssign as u32),
+    (
+        "inventory-unassign",
+        EventActionNo::InventoryUnassign as u32,
+    ),
+    (
+        "inventory-purchase",
+        EventActionNo::InventoryPurchase as u32,
+    ),
+    ("inventory-archive", EventActionNo::InventoryArchive as u32),
+    ("inventory-merge", EventActionNo::InventoryMerge as u32),
+    ("supplier-add", EventActionNo::SupplierAdd as u32),
+    ("supplier-edit", EventActionNo::SupplierEdit as u32),
+    ("supplier-remove", EventActionNo::SupplierRemove as u32),
+    ("supplier-link", EventActionNo::SupplierLink as u32),
+    ("supplier-unlink", EventActionNo::SupplierUnlink as u32),
+    ("bom-define", EventActionNo::BomDefine as u32),
+    ("bom-build", EventActionNo::BomBuild as u32),
+    (
+        "maintenance-schedule",
+        EventActionNo::MaintenanceSchedule as u32,
+    ),
+    ("maintenance-done", EventActionNo::MaintenanceDone as u32),
+    ("attachment-add", EventActionNo::AttachmentAdd as u32),
+    ("attachment-remove", EventActionNo::AttachmentRemove as u32),
+    ("comment-add", EventActionNo::CommentAdd as u32),
+    ("image-set", EventActionNo::ImageSet as u32),
+    ("image-remove", EventActionNo::ImageRemove as u32),
+    ("retention-set", EventActionNo::RetentionSet as u32),
+    ("retention-apply", EventActionNo::RetentionApply as u32),
+    ("history-compact", EventActionNo::HistoryCompact as u32),
+    ("optimize", EventActionNo::Optimize as u32),
+    ("read-only-set", EventActionNo::ReadOnlySet as u32),
+    ("scheduler-set", EventActionNo::SchedulerSet as u32),
+    ("hooks-set", EventActionNo::HooksSet as u32),
+    ("token-issue", EventActionNo::TokenIssue as u32),
+    ("token-revoke", EventActionNo::TokenRevoke as u32),
+    ("org-create", EventActionNo::OrgCreate as u32),
+    ("org-invite", EventActionNo::OrgInvite as u32),
+    ("role-edit", EventActionNo::RoleEdit as u32),
+    ("timezone-set", EventActionNo::TimezoneSet as u32),
+    ("display-format-set", EventActionNo::DisplayFormatSet as u32),
+    ("label-printer-set", EventActionNo::LabelPrinterSet as u32),
+    ("exchange-rates-set", EventActionNo::ExchangeRatesSet as u32),
+    ("reason-codes-set", EventActionNo::ReasonCodesSet as u32),
+    ("ldap-set", EventActionNo::LdapSet as u32),
+    (
+        "password-policy-set",
+        EventActionNo::PasswordPolicySet as u32,
+    ),
+    ("category-create", EventActionNo::CategoryCreate as u32),
+    ("category-remove", EventActionNo::CategoryRemove as u32),
+    ("category-assign", EventActionNo::CategoryAssign as u32),
+    ("category-unassign", EventActionNo::CategoryUnassign as u32),
+    ("kit-define", EventActionNo::KitDefine as u32),
+    ("kit-remove", EventActionNo::KitRemove as u32),
+    ("kit-pick", EventActionNo::KitPick as u32),
+    ("stocktake-start", EventActionNo::StocktakeStart as u32),
+    ("stocktake-record", EventActionNo::StocktakeRecord as u32),
+    ("stocktake-close", EventActionNo::StocktakeClose as u32),
+    ("stocktake-cancel", EventActionNo::StocktakeCancel as u32),
+    ("meta-set", EventActionNo::MetaSet as u32),
+    ("meta-remove", EventActionNo::MetaRemove as u32),
+];
+
+pub fn event_action_no(name: &str) -> Option<u32> {
+    return EVENT_NAMES
+        .iter()
+        .find(|(e, _)| *e == name)
+        .map(|(_, action_no)| *action_no);
+}
+
+pub fn event_name(action_no: u32) -> Option<&'static str> {
+    return EVENT_NAMES
+        .iter()
+        .find(|(_, e)| *e == action_no)
+        .map(|(name, _)| *name);
 }
 
 pub trait InvManDBPool {
-    fn get_config(&self) -> AppConfig;
+    fn get_config(&self) -> Result<AppConfig>;
     fn user_register(&mut self, username: &str, password: &str) -> Result<String>;
-    fn user_auth(&self, username: &str, password: &str, user: &mut DBUser) -> Result<()>;
+    /**
+     * Creates the admin of a database without users and sets whether others may register,
+     * both or neither.
+     */
+    fn init(
+        &mut self,
+        username: &str,
+        password: &str,
+        allow_registration: bool,
+        config: &mut AppConfig,
+    ) -> Result<String>;
+    /**
+     * Authenticates the user by username and password. Users without a password of their own
+     * are authenticated by the configured directory, which creates them on their first login
+     * and keeps their role in line with their groups. Users with two-factor authentication
+     * also need the one-time password of their authenticator app or one of their recovery
+     * codes, which is used up.
+     */
+    fn user_auth(
+        &self,
+        username: &str,
+        password: &str,
+        otp: Option<&str>,
+        user: &mut DBUser,
+    ) -> Result<()>;
+    /**
+     * Enables two-factor authentication of the user with the base32 encoded TOTP secret. The
+     * recovery codes replace any previous ones.
+     */
+    fn user_totp_enable(
+        &mut self,
+        secret: &str,
+        recovery_codes: &[String],
+        user: &DBUser,
+    ) -> Result<String>;
+    /**
+     * Disables two-factor authentication of the user of the given name, or of the
+     * authenticated user if None.
+     */
+    fn user_totp_disable(&mut self, username: Option<&str>, user: &DBUser) -> Result<String>;
+    /**
+     * Changes the password of the user of the given name, or of the authenticated user if
+     * None, according to the password policy. must_change_password forces the user to change
+     * it when authenticating the next time.
+     */
+    fn user_edit(
+        &mut self,
+        username: Option<&str>,
+        password: Option<&str>,
+        must_change_password: bool,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String>;
+    /**
+     * Authenticates the user of a token, which must be signed with the token secret and
+     * neither be expired nor revoked.
+     */
+    fn token_auth(&self, token: &str, user: &mut DBUser) -> Result<()>;
+    /**
+     * Secret the tokens are signed with, generated once per database.
+     */
+    fn token_secret(&self) -> Result<String>;
+    /**
+     * Records a token of the user expiring at the given unix time, which may only run the
+     * commands its scopes allow. A token without scopes runs any command.
+     *
+     * @returns The generated id of the token
+     */
+    fn token_add(&mut self, expires_at: i64, scopes: &[String], user: &DBUser) -> Result<String>;
+    fn token_list(&self, user: &DBUser) -> Result<Vec<DBToken>>;
+    fn token_revoke(&mut self, token_id: &str, user: &DBUser) -> Result<String>;
+    fn user_id(&self, username: &str) -> Result<u32>;
+    /**
+     * Deletes the user including its credentials, while the records the user dispatched are
+     * kept, referring to an anonymous tombstone user instead.
+     */
+    fn user_purge(
+        &mut self,
+        username: &str,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<DBPurgeReport>;
+    /**
+     * Restricts all following inventory queries to the entities of the given organization,
+     * None lifts the restriction.
+     */
+    fn scope_to_org(&self, org_id: Option<u32>);
+    fn org_create(&mut self, name: &str, user: &DBUser) -> Result<String>;
+    /**
+     * Moves the user into the organization, so the user only sees the organization's entities.
+     */
+    fn org_invite(&mut self, org: &str, username: &str, user: &DBUser) -> Result<String>;
+    fn org_list(&self) -> Result<Vec<DBOrg>>;
+    fn role_list(&self) -> Result<Vec<DBRole>>;
+    fn role_set_ownership(
+        &mut self,
+        role: &str,
+        ownership: OwnershipPolicy,
+        user: &DBUser,
+    ) -> Result<String>;
+    /**
+     * Denies (or with deny false allows again) the users of the role to read or write the
+     * inventory column.
+     */
+    fn role_deny_column(
+        &mut self,
+        role: &str,
+        column: &str,
+        mode: PermissionMode,
+        deny: bool,
+        user: &DBUser,
+    ) -> Result<String>;
+    /**
+     * User who added the entity, None for entities added before owners were recorded.
+     */
+    fn inventory_owner(&self, identifier: &str) -> Result<Option<u32>>;
 
     fn schema_alter(
         &mut self,
@@ -65,6 +400,16 @@ pub trait InvManDBPool {
         name: &str,
         user: &DBUser,
     ) -> Result<String>;
+    /**
+     * Adds the new columns to the schema by rebuilding the inventory table once, so that they
+     * are recorded as a single schema change.
+     */
+    fn schema_extend(
+        &mut self,
+        config: &mut AppConfig,
+        decls: Vec<SchemaDeclaration>,
+        user: &DBUser,
+    ) -> Result<String>;
 
     fn inventory_add(
         &mut self,
@@ -73,26 +418,694 @@ pub trait InvManDBPool {
         user: &DBUser,
     ) -> Result<String>;
 
+    /**
+     * Adds the entity together with its metadata, so that neither is stored without the other.
+     */
+    fn inventory_add_with_meta(
+        &mut self,
+        params: &KeyValueCollection,
+        meta: &BTreeMap<String, String>,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String>;
+
     fn inventory_list(
         &self,
         props: &InventoryListProps,
         config: &AppConfig,
     ) -> Result<Vec<KeyValueCollection>>;
 
+    /**
+     * Same as inventory_list, but hands each row to the callback as soon as it is read
+     * instead of collecting all of them, so memory stays flat for huge inventories.
+     */
+    fn inventory_list_each(
+        &self,
+        props: &InventoryListProps,
+        config: &AppConfig,
+        callback: &mut dyn FnMut(KeyValueCollection) -> Result<()>,
+    ) -> Result<()>;
+
+    /**
+     * Plans the query of inventory_list with SQLite's EXPLAIN QUERY PLAN without executing it.
+     */
+    fn inventory_list_explain(
+        &self,
+        props: &InventoryListProps,
+        config: &AppConfig,
+    ) -> Result<Vec<DBQueryPlan>>;
+
+    /**
+     * Starts recording the queries that read from the database, until explain_finish.
+     */
+    fn explain_start(&mut self);
+
+    /**
+     * Stops recording and plans each distinct query recorded since explain_start.
+     */
+    fn explain_finish(&mut self) -> Result<Vec<DBQueryPlan>>;
+
+    /**
+     * Moves the entities matching the where expression, together with their history, into the
+     * archive. Entities that are still referenced, e.g. by stock movements, cannot be archived.
+     */
+    fn inventory_archive(
+        &mut self,
+        filter: &WhereExpr,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn inventory_count(&self, conditions: &KeyValueCollection) -> Result<u32>;
+
+    fn inventory_exists(&self, identifier: &str) -> Result<bool>;
+
+    /**
+     * Returns the entity with all of its columns, or None if it does not exist
+     */
+    fn inventory_get(
+        &self,
+        identifier: &str,
+        config: &AppConfig,
+    ) -> Result<Option<KeyValueCollection>>;
+
+    /**
+     * Returns each unique value of the given column as collection of the value itself
+     * and a `count` entry holding the number of entities sharing that value.
+     */
+    fn inventory_distinct(
+        &self,
+        column: &str,
+        conditions: &KeyValueCollection,
+        config: &AppConfig,
+    ) -> Result<Vec<KeyValueCollection>>;
+
+    /**
+     * Changes the entity, unless an expected version is given that no longer matches. The
+     * version is either its updated_at timestamp or its revision, i.e. its latest history entry.
+     */
     fn inventory_edit(
         &mut self,
-        identifier: &String,
+        identifier: &str,
         params: &KeyValueCollection,
+        expect_version: Option<&str>,
         config: &AppConfig,
         user: &DBUser,
     ) -> Result<String>;
 
     fn inventory_remove(
         &mut self,
-        identifier: &String,
+        identifier: &str,
+        reason: Option<&str>,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Moves the history, stock, serials, loans, attachments and other records of the duplicate
+     * onto the entity to keep and removes the duplicate, recording the merge in its history.
+     * Records the keeper already has, e.g. its image or purchase, take precedence.
+     */
+    fn inventory_merge(
+        &mut self,
+        keep: &str,
+        duplicate: &str,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn inventory_checkout(
+        &mut self,
+        identifier: &str,
+        props: &LoanProps,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn inventory_checkin(&mut self, identifier: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Lists the loans that have not been checked in yet, optionally only those whose
+     * due date has passed.
+     */
+    fn loan_list(&self, overdue_only: bool) -> Result<Vec<DBLoan>>;
+
+    /**
+     * Hands the entity over to the user. An entity that is already held by someone else is
+     * reassigned, the previous assignment is kept as history.
+     */
+    fn inventory_assign(
+        &mut self,
+        identifier: &str,
+        assignee: u32,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn inventory_unassign(&mut self, identifier: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Sets the purchase details of the entity, replacing those recorded before.
+     */
+    fn purchase_set(
+        &mut self,
+        identifier: &str,
+        props: &PurchaseProps,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Lists the purchase details of all entities that have not been removed.
+     */
+    fn purchase_list(&self) -> Result<Vec<DBPurchase>>;
+
+    /**
+     * Books the given quantity into (positive) or out of (negative) the entity's stock at the
+     * location. The stock at a location can never become negative. Entities flagged by the
+     * serialized schema column require one serial per unit, whose status is tracked as well.
+     */
+    fn stock_adjust(
+        &mut self,
+        identifier: &str,
+        props: &AdjustProps,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Moves stock between two locations as one transaction of two paired movements, so that
+     * the total quantity of the entity stays the same.
+     */
+    fn stock_transfer(
+        &mut self,
+        identifier: &str,
+        props: &TransferProps,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn stock_list(&self, identifier: &str) -> Result<Vec<DBStock>>;
+
+    /**
+     * Lists the stock movements of the entity, optionally only those at the given location,
+     * oldest first.
+     */
+    fn stock_movements(
+        &self,
+        identifier: &str,
+        location: Option<&str>,
+    ) -> Result<Vec<DBStockMovement>>;
+
+    /**
+     * Sums up the stock of every entity with stock movements together with the quantity taken
+     * from it since the given UTC time, not counting transfers between locations.
+     */
+    fn stock_consumption(&self, since: &str) -> Result<Vec<DBStockConsumption>>;
+
+    /**
+     * Sums up the stock movements and removals of entities per reason since the given UTC time,
+     * or ever if None, not counting transfers between locations.
+     */
+    fn reason_summary(&self, since: Option<&str>) -> Result<Vec<DBReasonSummary>>;
+
+    /**
+     * Lists the stock movements of all entities that are not deleted, oldest first. Transfers
+     * are left out, as they do not change the total stock of an entity.
+     */
+    fn stock_ledger(&self) -> Result<Vec<DBLedgerEntry>>;
+
+    /**
+     * Lists every status and location change of the unit with the given serial,
+     * oldest first.
+     */
+    fn serial_history(&self, serial: &str) -> Result<Vec<DBSerialEvent>>;
+
+    /**
+     * Replaces the components the assembly is built from.
+     */
+    fn bom_define(
+        &mut self,
+        assembly: &str,
+        components: &[BomComponentProps],
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn bom_list(&self, assembly: &str) -> Result<Vec<DBBomComponent>>;
+
+    /**
+     * Consumes the components' stock at the location and books the built quantity of the
+     * assembly into it, all within one transaction. Fails with a shortage report if any
+     * component is insufficient.
+     */
+    fn bom_build(
+        &mut self,
+        assembly: &str,
+        props: &BuildProps,
         config: &AppConfig,
         user: &DBUser,
     ) -> Result<String>;
+
+    /**
+     * Schedules a recurring maintenance task for the entity, which is first due after one
+     * interval. Scheduling an existing task again changes its interval.
+     */
+    fn maintenance_schedule(
+        &mut self,
+        identifier: &str,
+        task: &str,
+        interval_days: u32,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Records the completion of the task and moves its due date one interval past today.
+     */
+    fn maintenance_done(&mut self, identifier: &str, task: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Lists the tasks that are overdue or due within the given amount of days, soonest first.
+     */
+    fn maintenance_due(&self, within_days: u32) -> Result<Vec<DBMaintenance>>;
+
+    fn attachment_add(
+        &mut self,
+        identifier: &str,
+        filename: &str,
+        content: &[u8],
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn attachment_list(&self, identifier: &str) -> Result<Vec<DBAttachment>>;
+
+    /**
+     * Fetches the stored file of the attachment with the given identifier.
+     *
+     * @returns A tuple in (filename, content) syntax
+     */
+    fn attachment_content(&self, identifier: &str) -> Result<(String, Vec<u8>)>;
+
+    fn attachment_remove(&mut self, identifier: &str, user: &DBUser) -> Result<String>;
+
+    fn comment_add(&mut self, identifier: &str, body: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Lists the comments on the entity, oldest first.
+     */
+    fn comment_list(&self, identifier: &str) -> Result<Vec<DBComment>>;
+
+    /**
+     * Sets the metadata of the entity under the key, replacing the previous value of the key.
+     */
+    fn meta_set(
+        &mut self,
+        identifier: &str,
+        key: &str,
+        value: &str,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn meta_remove(&mut self, identifier: &str, key: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Metadata of the entity by key
+     */
+    fn meta_get(&self, identifier: &str) -> Result<BTreeMap<String, String>>;
+
+    /**
+     * Sets the image of the entity, replacing the previous one and its cached thumbnails.
+     */
+    fn image_set(
+        &mut self,
+        identifier: &str,
+        filename: &str,
+        content: &[u8],
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Fetches the original image of the entity.
+     *
+     * @returns A tuple in (filename, content) syntax
+     */
+    fn image_get(&self, identifier: &str) -> Result<(String, Vec<u8>)>;
+
+    fn image_thumbnail(&self, identifier: &str, size: &str) -> Result<Option<Vec<u8>>>;
+
+    /**
+     * Caches the generated thumbnail, so that it does not need to be resized again.
+     */
+    fn image_thumbnail_store(&mut self, identifier: &str, size: &str, content: &[u8])
+        -> Result<()>;
+
+    fn image_remove(&mut self, identifier: &str, user: &DBUser) -> Result<String>;
+
+    fn read_only_set(
+        &mut self,
+        config: &mut AppConfig,
+        read_only: bool,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn timezone_set(
+        &mut self,
+        config: &mut AppConfig,
+        timezone: &str,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn display_format_set(
+        &mut self,
+        config: &mut AppConfig,
+        format: DisplayFormat,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Sets the address of the network printer labels are sent to, None removes it
+     */
+    fn label_printer_set(
+        &mut self,
+        config: &mut AppConfig,
+        printer: Option<String>,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn exchange_rates_set(
+        &mut self,
+        config: &mut AppConfig,
+        rates: ExchangeRates,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Sets the codes stock adjustments and removals are given a reason with, an empty list
+     * makes the reason optional
+     */
+    fn reason_codes_set(
+        &mut self,
+        config: &mut AppConfig,
+        codes: Vec<String>,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Sets the directory users without a password of their own are authenticated by, None
+     * turns it off
+     */
+    fn ldap_set(
+        &mut self,
+        config: &mut AppConfig,
+        settings: Option<LdapSettings>,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn password_policy_set(
+        &mut self,
+        config: &mut AppConfig,
+        policy: PasswordPolicy,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn registration_set(
+        &mut self,
+        config: &mut AppConfig,
+        allow: bool,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn retention_set(
+        &mut self,
+        config: &mut AppConfig,
+        rules: RetentionRules,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Removes what the retention rules no longer keep. Soft-deleted entities that are still
+     * referenced, e.g. by stock movements, are skipped. A dry run reports without removing.
+     */
+    fn retention_apply(
+        &mut self,
+        rules: &RetentionRules,
+        dry_run: bool,
+        user: &DBUser,
+    ) -> Result<DBRetentionReport>;
+
+    /**
+     * Exports every table, i.e. the schema declarations, entities, users and history, in a
+     * format that does not depend on the backend.
+     */
+    fn dump(&self) -> Result<DBDump>;
+
+    /**
+     * Recreates the database from a dump. Only empty databases, i.e. without any users or
+     * entities, can be loaded into.
+     */
+    fn load(&mut self, dump: &DBDump) -> Result<String>;
+
+    /**
+     * Users with their role and password hash, for saving them into a fixture. Purged users are
+     * left out.
+     */
+    fn fixture_users(&self) -> Result<Vec<DBFixtureUser>>;
+
+    /**
+     * Creates the users, schema columns and entities of a fixture, either all or none. Like
+     * dumps, fixtures can only be loaded into an empty database. The first user is the
+     * dispatcher of the changes.
+     */
+    fn fixture_load(
+        &mut self,
+        schema: &[SchemaDeclaration],
+        users: &[DBFixtureUser],
+        entities: &[KeyValueCollection],
+        config: &mut AppConfig,
+    ) -> Result<String>;
+
+    /**
+     * Imports the entities of another invman database. Columns that only the other schema
+     * declares are added, the entities receive new identifiers. Entities that share the value of
+     * a unique column with an existing entity are handled according to the strategy.
+     */
+    fn merge(
+        &mut self,
+        path: &str,
+        strategy: MergeStrategy,
+        config: &mut AppConfig,
+        user: &DBUser,
+    ) -> Result<DBMergeReport>;
+
+    /**
+     * Starts a transaction spanning several commands, which only take effect once the batch is
+     * committed. Batches nest, the changes of an inner batch taking effect with the outer one.
+     */
+    fn batch_begin(&mut self) -> Result<()>;
+
+    fn batch_commit(&mut self) -> Result<()>;
+
+    fn batch_rollback(&mut self) -> Result<()>;
+
+    /**
+     * Queues a command that was executed offline, so that it can be replayed later on.
+     */
+    fn outbox_record(&mut self, command: &[String]) -> Result<()>;
+
+    fn outbox_list(&self) -> Result<Vec<DBOutboxEntry>>;
+
+    fn outbox_remove(&mut self, id: u32) -> Result<()>;
+
+    fn smtp_set(
+        &mut self,
+        config: &mut AppConfig,
+        settings: Option<SmtpSettings>,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn scheduler_set(
+        &mut self,
+        config: &mut AppConfig,
+        jobs: Vec<ScheduledJob>,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn hooks_set(
+        &mut self,
+        config: &mut AppConfig,
+        hooks: Vec<Hook>,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn rule_add(&mut self, rule: &RuleProps, user: &DBUser) -> Result<String>;
+
+    fn rule_list(&self) -> Result<Vec<DBRule>>;
+
+    fn rule_remove(&mut self, name: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Matches the events recorded after the given one against the rules. Each event appears
+     * once per matching rule, ordered by the event.
+     */
+    fn rule_matches(&self, after_event: u32) -> Result<Vec<DBRuleMatch>>;
+
+    /**
+     * Lists the events recorded after the given one, ordered by their id.
+     */
+    fn event_list(&self, after_event: u32) -> Result<Vec<DBEvent>>;
+
+    fn latest_event(&self) -> Result<u32>;
+
+    fn notify_rule_add(&mut self, rule: &NotifyRuleProps, user: &DBUser) -> Result<String>;
+
+    fn notify_rule_list(&self) -> Result<Vec<DBNotifyRule>>;
+
+    fn notify_rule_remove(&mut self, id: u32, user: &DBUser) -> Result<String>;
+
+    /**
+     * Matches the events recorded since the last run against the notification rules. Each
+     * event appears once per matching rule, ordered by the event.
+     */
+    fn notify_pending(&self) -> Result<DBPendingNotifications>;
+
+    /**
+     * Marks every event up to the given one as notified.
+     */
+    fn notify_advance(&mut self, event_id: u32) -> Result<()>;
+
+    /**
+     * Checks the database for corruption, a schema declaration that differs from the inventory
+     * table and orphaned history. With `fix` the repairs that do not lose data are applied.
+     */
+    fn doctor(&mut self, config: &AppConfig, fix: bool) -> Result<DBDoctorReport>;
+
+    /**
+     * Rebuilds the database file to reclaim the space of removed rows and refreshes the
+     * statistics of the query planner.
+     */
+    fn optimize(&mut self, user: &DBUser) -> Result<DBOptimizeReport>;
+
+    /**
+     * Collects figures about the database to monitor it, i.e. its size, the amount of entities
+     * and users and how often each action was recorded in the event log.
+     */
+    fn metrics(&self) -> Result<DBMetrics>;
+
+    /**
+     * Checks that the database works, i.e. it can be queried, it is at the version this invman
+     * expects and data can be written and read back again within a temporary table.
+     */
+    fn health(&self) -> DBHealthReport;
+
+    /**
+     * Collapses the revisions of each entity that are older than the given amount of days into
+     * a single snapshot revision. The first and the last `keep_last` revisions are always kept.
+     */
+    fn history_compact(
+        &mut self,
+        keep_last: u32,
+        older_than_days: u32,
+        user: &DBUser,
+    ) -> Result<DBCompactionReport>;
+
+    fn supplier_add(&mut self, props: &SupplierProps, user: &DBUser) -> Result<String>;
+
+    fn supplier_list(&self) -> Result<Vec<DBSupplier>>;
+
+    /**
+     * Searches the sources of the props for the text, case insensitive, grouping the matches
+     * by their source
+     */
+    fn search(&self, text: &str, props: &SearchProps) -> Result<DBSearchResults>;
+
+    fn supplier_edit(
+        &mut self,
+        identifier: &str,
+        props: &SupplierProps,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    fn supplier_remove(&mut self, identifier: &str, user: &DBUser) -> Result<String>;
+
+    fn supplier_link(&mut self, identifier: &str, entity: &str, user: &DBUser) -> Result<String>;
+
+    fn supplier_unlink(&mut self, identifier: &str, entity: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Creates the category at the given path, e.g. `electronics/laptops`, together with any of
+     * its ancestors that do not exist yet
+     */
+    fn category_create(&mut self, path: &str, user: &DBUser) -> Result<String>;
+
+    fn category_list(&self) -> Result<Vec<DBCategory>>;
+
+    fn category_id(&self, path: &str) -> Result<u32>;
+
+    /**
+     * Removes the category and the assignments to it. Categories that still have children
+     * cannot be removed.
+     */
+    fn category_remove(&mut self, path: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Assigns the entity to the category, replacing its previous category
+     */
+    fn category_assign(&mut self, identifier: &str, path: &str, user: &DBUser) -> Result<String>;
+
+    fn category_unassign(&mut self, identifier: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Defines the items the kit of the given name is composed of, replacing the previous ones if
+     * the kit already exists
+     */
+    fn kit_define(&mut self, name: &str, items: &[KitItemProps], user: &DBUser) -> Result<String>;
+
+    fn kit_list(&self) -> Result<Vec<DBKit>>;
+
+    fn kit_get(&self, name: &str) -> Result<DBKit>;
+
+    fn kit_remove(&mut self, name: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Takes the items of the given quantity of kits out of the stock at the location, all within
+     * one transaction. Fails with a shortage report if any item is insufficient.
+     */
+    fn kit_pick(
+        &mut self,
+        name: &str,
+        props: &PickProps,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Opens a stocktake of the location. Only one stocktake can be open at a time.
+     */
+    fn stocktake_start(&mut self, location: &str, user: &DBUser) -> Result<String>;
+
+    /**
+     * Records the counted quantity of the entity within the open stocktake, together with the
+     * quantity the system expects at this moment. Counting an entity again replaces its count.
+     */
+    fn stocktake_record(
+        &mut self,
+        identifier: &str,
+        counted: i64,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String>;
+
+    /**
+     * Variances of the counts recorded so far within the open stocktake
+     */
+    fn stocktake_status(&self) -> Result<DBStocktakeReport>;
+
+    /**
+     * Closes the open stocktake, posting an adjustment for every counted entity whose count
+     * differs from the expected quantity.
+     */
+    fn stocktake_close(&mut self, user: &DBUser) -> Result<DBStocktakeReport>;
+
+    /**
+     * Discards the open stocktake together with its counts, without adjusting any stock
+     */
+    fn stocktake_cancel(&mut self, user: &DBUser) -> Result<String>;
 }
 
 pub struct InvManConnection;
@@ -101,12 +1114,106 @@ impl InvManConnection {
     pub fn sqlite() -> Result<InvManSqlite> {
         return InvManSqlite::new();
     }
+
+    /**
+     * Opens the storage file at the given path, which is created if it does not exist. The
+     * path `:memory:` opens an in-memory database instead.
+     */
+    pub fn sqlite_database(path: &str) -> Result<InvManSqlite> {
+        return InvManSqlite::new_at(path);
+    }
+
+    /**
+     * Runs the whole setup in memory, so nothing is written to disk and every connection gets
+     * its own database, e.g. for tests running in parallel.
+     */
+    pub fn sqlite_in_memory() -> Result<InvManSqlite> {
+        return InvManSqlite::in_memory();
+    }
+
+    /**
+     * Opens a pool of at most max_size connections to the storage file at the given path, which
+     * can be shared across threads, e.g. by a server.
+     */
+    pub fn sqlite_pool(path: &str, max_size: usize) -> Result<InvManSqlitePool> {
+        return InvManSqlitePool::new(path, max_size);
+    }
+
+    /**
+     * Uses the database of a remote invman started with serve, e.g. https://inventory.internal,
+     * authenticating with the credentials in the notation of --auth.
+     */
+    pub fn http(url: &str, auth: Option<&str>) -> InvManHttpClient {
+        return InvManHttpClient::new(url, auth);
+    }
+
+    /**
+     * Same as sqlite_pool, but for async services.
+     */
+    #[cfg(feature = "async")]
+    pub fn sqlite_async(path: &str, max_size: usize) -> Result<InvManSqliteAsync> {
+        return Ok(InvManSqliteAsync::new(InvManSqlitePool::new(
+            path, max_size,
+        )?));
+    }
+
+    /**
+     * Opens the existing storage file at the given path, e.g. of a central database.
+     */
+    pub fn sqlite_at(path: &str) -> Result<InvManSqlite> {
+        return InvManSqlite::open(path);
+    }
+
+    /**
+     * Creates a new storage file at the given path, e.g. for a scratch database.
+     */
+    pub fn sqlite_create(path: &str) -> Result<InvManSqlite> {
+        return InvManSqlite::create(path);
+    }
+
+    /**
+     * Opens the storage file without being able to change it.
+     */
+    pub fn sqlite_read_only() -> Result<InvManSqlite> {
+        return InvManSqlite::open_read_only();
+    }
+
+    /**
+     * Opens the storage file at the given path without being able to change it.
+     */
+    pub fn sqlite_read_only_at(path: &str) -> Result<InvManSqlite> {
+        return InvManSqlite::open_read_only_at(path);
+    }
 }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub allow_registration: bool,
     pub inventory_schema_declaration: SchemaCollection,
+    pub retention_rules: RetentionRules,
+    pub read_only: bool,
+    pub smtp: Option<SmtpSettings>,
+    pub scheduler_jobs: Vec<ScheduledJob>,
+    pub hooks: Vec<Hook>,
+    /**
+     * Timezone the timestamps, which are stored in UTC, are displayed in by the plain output
+     */
+    pub timezone: String,
+    pub display_format: DisplayFormat,
+    /**
+     * Address of the network printer labels are sent to, in host:port notation
+     */
+    pub label_printer: Option<String>,
+    pub exchange_rates: ExchangeRates,
+    /**
+     * Codes one of which has to be given as reason of stock adjustments and removals
+     */
+    pub reason_codes: Vec<String>,
+    /**
+     * Directory that authenticates users without a password of their own
+     */
+    pub ldap: Option<LdapSettings>,
+    pub password_policy: PasswordPolicy,
 }
 
 #[derive(Debug)]
@@ -125,64 +1232,17 @@ struct IdEntry {
     id: u32,
 }
 
-#[derive(Debug)]
-struct TextEntry {
-    text: String,
-}
-
 #[derive(Debug)]
 struct Config {
     name: String,
     value: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct KeyValueCollection {
     pub collection: Vec<KeyValueTypeEntry>,
 }
 
-#[derive(Default, Debug, Clone)]
-pub struct SchemaCollection {
-    pub collection: Vec<SchemaDeclaration>,
-}
-
-impl SchemaCollection {
-    pub fn new(collection: Vec<SchemaDeclaration>) -> SchemaCollection {
-        return SchemaCollection { collection };
-    }
-
-    pub fn sql_names(&self) -> String {
-        return if self.collection.iter().count() == 0 {
-            "id,created_at,updated_at,deleted_at".into()
-        } else {
-            format!(
-                "id,created_at,updated_at,deleted_at,{}",
-                self.collection
-                    .iter()
-                    .map(|e| e.name.clone())
-                    .collect::<Vec<String>>()
-                    .join(",")
-            )
-        };
-    }
-
-    pub fn to_json(&self) -> String {
-        let mut json = self
-            .collection
-            .iter()
-            .map(|e| e.to_json())
-            .collect::<Vec<String>>()
-            .join(",");
-        json.insert(0, '[');
-        json.push(']');
-        return json;
-    }
-
-    pub fn contains(&self, declaration: &SchemaDeclaration) -> Option<usize> {
-        return self.collection.iter().position(|d| d.is_equal(declaration));
-    }
-}
-
 pub trait InvManToSql {
     // Returns the SQL names as plain string that are fetched
     fn sql_names(&self) -> String;
@@ -192,7 +1252,7 @@ pub trait InvManToSql {
 }
 
 impl KeyValueCollection {
-    fn new(collection: Vec<KeyValueTypeEntry>) -> KeyValueCollection {
+    pub fn new(collection: Vec<KeyValueTypeEntry>) -> KeyValueCollection {
         return KeyValueCollection { collection };
     }
 
@@ -206,6 +1266,19 @@ impl KeyValueCollection {
             .join(",");
     }
 
+    pub fn sql_prepare_where_fields(&self, idx_offset: usize) -> String {
+        if self.collection.is_empty() {
+            return "1=1".into();
+        }
+        return self
+            .collection
+            .iter()
+            .enumerate()
+            .map(|(i, e)| format!("{}=?{}", e.key, (i + idx_offset + 1)))
+            .collect::<Vec<String>>()
+            .join(" AND ");
+    }
+
     pub fn get_id(&self) -> Result<String> {
         if let Some(val) = self.collection.iter().find(|e| e.key == "id") {
             if let Some(val) = val.value.clone() {
@@ -233,17 +1306,17 @@ impl InvManToSql for KeyValueCollection {
     }
 }
 
-impl Into<KeyValueCollection> for Vec<KeyValueTypeEntry> {
-    fn into(self) -> KeyValueCollection {
-        return KeyValueCollection::new(self);
+impl From<Vec<KeyValueTypeEntry>> for KeyValueCollection {
+    fn from(collection: Vec<KeyValueTypeEntry>) -> KeyValueCollection {
+        return KeyValueCollection::new(collection);
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyValueTypeEntry {
     pub key: String,
     value: Option<String>,
-    column_type: ColumnType,
+    pub column_type: ColumnType,
 }
 
 impl KeyValueTypeEntry {
@@ -255,24 +1328,51 @@ impl KeyValueTypeEntry {
         };
     }
 
+    pub fn to_json_value(&self) -> String {
+        return match self.value.clone() {
+            None => "null".into(),
+            Some(val) => match self.column_type {
+                ColumnType::TEXT | ColumnType::VARCHAR | ColumnType::MONEY => {
+                    format!("\"{}\"", val)
+                }
+                ColumnType::BOOL => {
+                    if val == "true" || val == "1" {
+                        "true".into()
+                    } else {
+                        "false".into()
+                    }
+                }
+                _ => val,
+            },
+        };
+    }
+
+    /**
+     * The value as written in plain text formats like CSV, booleans as true or false
+     */
+    pub fn to_plain_value(&self) -> Option<String> {
+        return match self.column_type {
+            ColumnType::BOOL => self
+                .value
+                .as_ref()
+                .map(|val| (val == "true" || val == "1").to_string()),
+            _ => self.value.clone(),
+        };
+    }
+
+    /**
+     * Replaces the value by its SHA-256 hash, so equal values can still be matched without
+     * revealing them.
+     */
+    pub fn hash_value(&mut self) {
+        if let Some(val) = self.to_plain_value() {
+            self.value = Some(format!("{:x}", Sha256::digest(val.as_bytes())));
+            self.column_type = ColumnType::TEXT;
+        }
+    }
+
     fn to_json_notation(&self) -> String {
-        return format!(
-            "\"{}\":{}",
-            self.key,
-            match self.value.clone() {
-                None => "null".into(),
-                Some(val) => match self.column_type {
-                    ColumnType::TEXT | ColumnType::VARCHAR => format!("\"{}\"", val),
-                    ColumnType::BOOL =>
-                        if val == "true" || val == "1" {
-                            "true".into()
-                        } else {
-                            "false".into()
-                        },
-                    _ => val,
-                },
-            }
-        );
+        return format!("\"{}\":{}", self.key, self.to_json_value());
     }
 }
 
@@ -292,7 +1392,7 @@ impl InvManSerialization for KeyValueCollection {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DBPermissionCollection {
     pub collection: Vec<String>,
 }
@@ -303,23 +1403,538 @@ impl DBPermissionCollection {
     }
 }
 
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum PermissionMode {
     Read,
     Write,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBSupplier {
+    pub id: u32,
+    pub name: String,
+    pub contact: Option<String>,
+    pub lead_time_days: Option<u32>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBCategory {
+    pub id: u32,
+    pub name: String,
+    /**
+     * Full path of the category, its ancestors' names joined by `/`
+     */
+    pub path: String,
+    pub parent_id: Option<u32>,
+    /**
+     * Amount of entities assigned directly to the category
+     */
+    pub entities: u32,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBSearchMatch {
+    pub inventory_id: u32,
+    pub column: String,
+    pub value: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DBSearchResults {
+    pub inventory: Vec<DBSearchMatch>,
+    pub comments: Vec<DBComment>,
+    /**
+     * Attachments whose filename matches
+     */
+    pub attachments: Vec<DBAttachment>,
+    /**
+     * Suppliers whose name or contact matches
+     */
+    pub suppliers: Vec<DBSupplier>,
+    /**
+     * Metadata whose key or value matches, with the key as column
+     */
+    #[serde(default)]
+    pub meta: Vec<DBSearchMatch>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBComment {
+    pub id: u32,
+    pub inventory_id: u32,
+    pub author: String,
+    pub body: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBLoan {
+    pub id: u32,
+    pub inventory_id: u32,
+    pub borrower: String,
+    pub dispatcher: String,
+    pub due_at: Option<String>,
+    pub checked_out_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBMaintenance {
+    pub inventory_id: u32,
+    pub task: String,
+    pub interval_days: u32,
+    pub last_done_at: Option<String>,
+    pub next_due_at: String,
+    pub overdue: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBPurchase {
+    pub inventory_id: u32,
+    pub purchased_at: String,
+    pub price: f64,
+    pub warranty_months: Option<u32>,
+    pub useful_life_months: Option<u32>,
+    /**
+     * Currency of the price, None if it was recorded before a base currency was set
+     */
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBStock {
+    pub location: String,
+    pub quantity: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBLedgerEntry {
+    pub inventory_id: u32,
+    pub quantity: i64,
+    pub unit_cost: Option<f64>,
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBReasonSummary {
+    pub reason: String,
+    /**
+     * Amount of stock movements booked with the reason
+     */
+    pub movements: u32,
+    pub quantity_removed: i64,
+    pub quantity_added: i64,
+    /**
+     * Amount of entities that were removed with the reason
+     */
+    pub removed_entities: u32,
+    /**
+     * Amount of distinct entities affected by the movements or removals
+     */
+    pub entities: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBStockConsumption {
+    pub inventory_id: u32,
+    pub quantity: i64,
+    pub consumed: i64,
+    /**
+     * Shortest lead time of the suppliers linked to the entity
+     */
+    pub lead_time_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBStockMovement {
+    pub quantity: i64,
+    /**
+     * Whether the movement is one half of a transfer between two locations
+     */
+    pub transfer: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBAttachment {
+    pub id: u32,
+    pub inventory_id: u32,
+    pub filename: String,
+    pub size: u64,
+    pub dispatcher: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBBomComponent {
+    pub component_id: u32,
+    pub quantity: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBStocktakeReport {
+    pub id: u32,
+    pub location: String,
+    pub started_at: String,
+    pub closed_at: Option<String>,
+    pub lines: Vec<DBStocktakeLine>,
+    /**
+     * Entities in stock at the location that have not been counted, their stock is left as is
+     */
+    pub uncounted: Vec<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBStocktakeLine {
+    pub inventory_id: u32,
+    /**
+     * Quantity in stock at the location when the entity was counted
+     */
+    pub expected: i64,
+    pub counted: i64,
+    pub variance: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBKit {
+    pub id: u32,
+    pub name: String,
+    pub items: Vec<DBKitItem>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBKitItem {
+    pub inventory_id: u32,
+    /**
+     * Quantity of the item within one kit
+     */
+    pub quantity: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBQueryPlan {
+    pub sql: String,
+    /**
+     * Steps of the plan, indented by two spaces per level below the top
+     */
+    pub plan: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBCompactionReport {
+    pub compacted_entities: usize,
+    pub removed_revisions: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBDump {
+    pub format: String,
+    pub version: u32,
+    /**
+     * Version of the database layout the rows belong to
+     */
+    pub schema_version: usize,
+    pub created_at: String,
+    pub tables: BTreeMap<String, Vec<serde_json::Map<String, serde_json::Value>>>,
+}
+
+/**
+ * Declarative content of a database for reproducible test environments, saved to and loaded
+ * from YAML
+ */
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DBFixture {
+    #[serde(default)]
+    pub schema: Vec<SchemaDeclaration>,
+    #[serde(default)]
+    pub users: Vec<DBFixtureUser>,
+    /**
+     * Column values of the entities, without the columns invman manages itself like id
+     */
+    #[serde(default)]
+    pub entities: Vec<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DBFixtureUser {
+    pub username: String,
+    /**
+     * Name of the role, e.g. skipper
+     */
+    pub role: String,
+    /**
+     * Plain password, for fixtures written by hand
+     */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /**
+     * Hash of the password as saved from a database. Users with neither password nor hash are
+     * authenticated by the directory.
+     */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DBMergeReport {
+    pub added_columns: Vec<String>,
+    pub added: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+    /**
+     * Identifiers of the other database mapped to the ones of this database
+     */
+    pub id_map: BTreeMap<u32, u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBToken {
+    pub id: String,
+    pub expires_at: String,
+    pub scopes: Vec<String>,
+    pub revoked_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBOrg {
+    pub id: u32,
+    pub name: String,
+    pub members: u32,
+    pub entities: u32,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBRole {
+    pub id: u32,
+    pub name: String,
+    pub display_name: Option<String>,
+    pub ownership: OwnershipPolicy,
+    /**
+     * Columns denied to the role, e.g. price.r for reading and price.w for writing
+     */
+    pub denied_columns: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DBOutboxEntry {
+    pub id: u32,
+    pub command: Vec<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBNotifyRule {
+    pub id: u32,
+    pub event: String,
+    pub channel: NotifyChannel,
+    pub recipient: String,
+    pub condition: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DBRule {
+    pub id: u32,
+    pub name: String,
+    pub event: String,
+    pub condition: Option<String>,
+    pub set: Vec<String>,
+    pub notify_channel: Option<NotifyChannel>,
+    pub notify_recipient: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBRuleMatch {
+    pub rule: DBRule,
+    pub event: DBEvent,
+    pub previous_entity: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBEvent {
+    pub id: u32,
+    pub event: String,
+    pub dispatcher: Option<String>,
+    pub target: Option<i64>,
+    pub reason: Option<String>,
+    pub created_at: String,
+    /**
+     * State of the inventory entity after the change, for events that changed one
+     */
+    pub entity: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBNotification {
+    pub channel: NotifyChannel,
+    pub recipient: String,
+    pub condition: Option<String>,
+    pub event: DBEvent,
+    /**
+     * State of the inventory entity before the change, to tell whether it started to match
+     * the condition
+     */
+    pub previous_entity: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBPendingNotifications {
+    /**
+     * Latest recorded event, including the ones no rule matched
+     */
+    pub latest_event: u32,
+    pub notifications: Vec<DBNotification>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBDoctorReport {
+    pub integrity: Vec<String>,
+    pub foreign_key_violations: Vec<String>,
+    pub missing_columns: Vec<String>,
+    pub undeclared_columns: Vec<String>,
+    pub orphaned_history: Vec<u32>,
+    pub repairs: Vec<String>,
+    pub fixed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBOptimizeReport {
+    pub size_before: u64,
+    pub size_after: u64,
+    pub reclaimed: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBHealthCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBHealthReport {
+    pub healthy: bool,
+    pub checks: Vec<DBHealthCheck>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBMetrics {
+    pub database_size: u64,
+    pub entities: u64,
+    pub deleted_entities: u64,
+    pub users: u64,
+    /**
+     * Amount of recorded events by their action number
+     */
+    pub events: BTreeMap<u32, u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBPurgeReport {
+    pub user_id: u32,
+    /**
+     * User the records of the purged user refer to from now on
+     */
+    pub tombstone_id: u32,
+    /**
+     * Amount of records anonymized per table
+     */
+    pub anonymized: BTreeMap<String, usize>,
+    pub deleted_tokens: usize,
+    pub purged_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBRetentionReport {
+    pub dry_run: bool,
+    pub purged_entities: Vec<u32>,
+    pub purged_entity_history: usize,
+    pub skipped_referenced_entities: Vec<u32>,
+    pub pruned_events: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DBSerialEvent {
+    pub serial: String,
+    pub inventory_id: u32,
+    pub dispatcher: String,
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub from_location: Option<String>,
+    pub to_location: String,
+    pub created_at: String,
+}
+
+/**
+ * Error of authenticating a user with two-factor authentication without a one-time password,
+ * upon which the command line asks for it.
+ */
+pub const OTP_REQUIRED: &str = "A one-time password is required, pass it with --otp";
+
+/**
+ * Error of authenticating a user whose password has to be changed before running any other
+ * command.
+ */
+pub const PASSWORD_EXPIRED: &str = "Password has expired, change it with user edit password=<NEW>";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DBUser {
     pub id: u32,
+    /**
+     * Name the user is registered with, regardless of whether a token authenticated the user
+     */
+    #[serde(default)]
+    pub username: String,
     pub permissions: DBPermissionCollection,
+    /**
+     * Denies writing regardless of the permissions, e.g. when the database is opened read-only
+     */
+    pub read_only: bool,
+    /**
+     * Organization the user belongs to, whose entities are the only ones visible to the user
+     */
+    pub org_id: Option<u32>,
+    /**
+     * Which entities the user may change, configured per role
+     */
+    pub ownership: OwnershipPolicy,
+    /**
+     * Columns the user's role is denied regardless of the permissions, in the permissions'
+     * notation, e.g. inventory.price.r
+     */
+    pub denied_columns: Vec<String>,
+    /**
+     * Scopes of the token the user authenticated with, e.g. inventory:write. Empty if the user
+     * is not restricted by a token.
+     */
+    pub scopes: Vec<String>,
+    /**
+     * Whether the password is older than the policy allows or an admin demanded its change,
+     * which then is the only command the user may run
+     */
+    pub password_expired: bool,
 }
 
 impl DBUser {
-    fn new(id: u32, permissions: DBPermissionCollection) -> DBUser {
-        return DBUser { id, permissions };
+    fn is_column_denied(&self, table: &str, column: &str, mode: PermissionMode) -> bool {
+        let rule = match mode {
+            PermissionMode::Read => format!("{}.{}.r", table, column),
+            PermissionMode::Write => format!("{}.{}.w", table, column),
+        };
+        return self.denied_columns.iter().any(|e| e.eq(rule.as_str()));
     }
 
     fn can_interact_table(&self, table: &str, mode: PermissionMode) -> bool {
+        if self.read_only && matches!(mode, PermissionMode::Write) {
+            return false;
+        }
         if self.permissions.collection.iter().any(|e| e == "*") {
             return true;
         }
@@ -335,6 +1950,12 @@ impl DBUser {
     }
 
     fn can_interact_table_column(&self, table: &str, column: &str, mode: PermissionMode) -> bool {
+        if self.read_only && matches!(mode, PermissionMode::Write) {
+            return false;
+        }
+        if self.is_column_denied(table, column, mode) {
+            return false;
+        }
         if self.permissions.collection.iter().any(|e| e == "*") {
             return true;
         }
@@ -365,6 +1986,23 @@ impl DBUser {
         return self.can_interact_table_column(table, column, PermissionMode::Write);
     }
 
+    pub fn is_read_denied(&self, table: &str, column: &str) -> bool {
+        return self.is_column_denied(table, column, PermissionMode::Read);
+    }
+
+    pub fn is_write_denied(&self, table: &str, column: &str) -> bool {
+        return self.is_column_denied(table, column, PermissionMode::Write);
+    }
+
+    /**
+     * Removes the columns the user is denied to read from the collection.
+     */
+    pub fn mask_collection(&self, table: &str, collection: &mut KeyValueCollection) {
+        collection
+            .collection
+            .retain(|e| !self.is_read_denied(table, e.key.as_str()));
+    }
+
     pub fn can_read_collection(&self, table: &str, collection: &KeyValueCollection) -> bool {
         return collection
             .collection
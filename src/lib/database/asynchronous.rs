@@ -0,0 +1,179 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{AppConfig, DBUser, InvManDBPool, InvManSqlitePool, KeyValueCollection};
+use anyhow::Result;
+use std::future::Future;
+use std::sync::Arc;
+
+/**
+ * Database layer for async services. The operations run on the blocking threads of the Tokio
+ * runtime, so they must be awaited within one, and never block the thread that awaits them.
+ * As for the users of InvManDBPool, the caller checks the permissions of the user.
+ */
+pub trait InvManDBPoolAsync {
+    /**
+     * Runs the operation with a connection of its own, e.g. for the operations that have no
+     * async variant of their own.
+     */
+    fn with<T, F>(&self, operation: F) -> impl Future<Output = Result<T>> + Send
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut dyn InvManDBPool) -> Result<T> + Send + 'static;
+
+    fn get_config(&self) -> impl Future<Output = Result<AppConfig>> + Send;
+
+    /**
+     * Authenticates the user by username and password, and the one-time password if the user
+     * has two-factor authentication.
+     */
+    fn user_auth(
+        &self,
+        username: String,
+        password: String,
+        otp: Option<String>,
+    ) -> impl Future<Output = Result<DBUser>> + Send;
+
+    fn inventory_get(
+        &self,
+        identifier: String,
+        config: AppConfig,
+        user: DBUser,
+    ) -> impl Future<Output = Result<Option<KeyValueCollection>>> + Send;
+
+    fn inventory_add(
+        &self,
+        params: KeyValueCollection,
+        config: AppConfig,
+        user: DBUser,
+    ) -> impl Future<Output = Result<String>> + Send;
+
+    fn inventory_edit(
+        &self,
+        identifier: String,
+        params: KeyValueCollection,
+        config: AppConfig,
+        user: DBUser,
+    ) -> impl Future<Output = Result<String>> + Send;
+
+    fn inventory_remove(
+        &self,
+        identifier: String,
+        reason: Option<String>,
+        config: AppConfig,
+        user: DBUser,
+    ) -> impl Future<Output = Result<String>> + Send;
+}
+
+/**
+ * InvManDBPoolAsync backed by a pool of SQLite connections, which is cheap to clone and can be
+ * shared across tasks.
+ */
+#[derive(Clone)]
+pub struct InvManSqliteAsync {
+    pool: Arc<InvManSqlitePool>,
+}
+
+impl InvManSqliteAsync {
+    pub fn new(pool: InvManSqlitePool) -> InvManSqliteAsync {
+        return InvManSqliteAsync {
+            pool: Arc::new(pool),
+        };
+    }
+}
+
+impl InvManDBPoolAsync for InvManSqliteAsync {
+    fn with<T, F>(&self, operation: F) -> impl Future<Output = Result<T>> + Send
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut dyn InvManDBPool) -> Result<T> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        return async move { tokio::task::spawn_blocking(move || pool.with(operation)).await? };
+    }
+
+    fn get_config(&self) -> impl Future<Output = Result<AppConfig>> + Send {
+        return self.with(|db| db.get_config());
+    }
+
+    fn user_auth(
+        &self,
+        username: String,
+        password: String,
+        otp: Option<String>,
+    ) -> impl Future<Output = Result<DBUser>> + Send {
+        return self.with(move |db| {
+            let mut user = DBUser::default();
+            db.user_auth(&username, &password, otp.as_deref(), &mut user)?;
+            return Ok(user);
+        });
+    }
+
+    // Connections are scoped per operation, as each may be of a user of another organization
+
+    fn inventory_get(
+        &self,
+        identifier: String,
+        config: AppConfig,
+        user: DBUser,
+    ) -> impl Future<Output = Result<Option<KeyValueCollection>>> + Send {
+        return self.with(move |db| {
+            db.scope_to_org(user.org_id);
+            return db.inventory_get(&identifier, &config);
+        });
+    }
+
+    fn inventory_add(
+        &self,
+        params: KeyValueCollection,
+        config: AppConfig,
+        user: DBUser,
+    ) -> impl Future<Output = Result<String>> + Send {
+        return self.with(move |db| {
+            db.scope_to_org(user.org_id);
+            return db.inventory_add(&params, &config, &user);
+        });
+    }
+
+    fn inventory_edit(
+        &self,
+        identifier: String,
+        params: KeyValueCollection,
+        config: AppConfig,
+        user: DBUser,
+    ) -> impl Future<Output = Result<String>> + Send {
+        return self.with(move |db| {
+            db.scope_to_org(user.org_id);
+            return db.inventory_edit(&identifier, &params, None, &config, &user);
+        });
+    }
+
+    fn inventory_remove(
+        &self,
+        identifier: String,
+        reason: Option<String>,
+        config: AppConfig,
+        user: DBUser,
+    ) -> impl Future<Output = Result<String>> + Send {
+        return self.with(move |db| {
+            db.scope_to_org(user.org_id);
+            return db.inventory_remove(&identifier, reason.as_deref(), &config, &user);
+        });
+    }
+}
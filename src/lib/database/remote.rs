@@ -0,0 +1,1096 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{
+    AppConfig, DBAttachment, DBBomComponent, DBCategory, DBComment, DBCompactionReport,
+    DBDoctorReport, DBDump, DBEvent, DBFixtureUser, DBHealthCheck, DBHealthReport, DBKit,
+    DBLedgerEntry, DBLoan, DBMaintenance, DBMergeReport, DBMetrics, DBNotifyRule, DBOptimizeReport,
+    DBOrg, DBOutboxEntry, DBPendingNotifications, DBPurchase, DBPurgeReport, DBQueryPlan,
+    DBReasonSummary, DBRetentionReport, DBRole, DBRule, DBRuleMatch, DBSearchResults,
+    DBSerialEvent, DBStock, DBStockConsumption, DBStockMovement, DBStocktakeReport, DBSupplier,
+    DBToken, DBUser, InvManDBPool, KeyValueCollection, PermissionMode,
+};
+use crate::common::args::{
+    AdjustProps, BomComponentProps, BuildProps, DisplayFormat, ExchangeRates, Hook,
+    InventoryListProps, KitItemProps, LdapSettings, LoanProps, MergeStrategy, NotifyRuleProps,
+    OwnershipPolicy, PasswordPolicy, PickProps, PurchaseProps, RetentionRules, RuleProps,
+    ScheduledJob, SchemaDeclaration, SearchProps, SmtpSettings, SupplierProps, TransferProps,
+    WhereExpr,
+};
+use crate::server::{read_certificates, read_private_key, OTP_HEADER};
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rustls::{ClientConfig, RootCertStore};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{cell::RefCell, collections::BTreeMap, sync::Arc, time::Duration};
+
+const CONNECT_TIMEOUT_SECONDS: u64 = 10;
+
+/**
+ * Database on a remote invman started with serve. Every operation is a request to the server,
+ * which authenticates the user and checks the permissions itself, so that the credentials are
+ * sent along with each of them. The config of the server is used in place of the one passed
+ * to the operations, as is the authenticated user in place of the passed user.
+ */
+pub struct InvManHttpClient {
+    url: String,
+    agent: ureq::Agent,
+    /**
+     * Credentials in the notation of --auth, i.e. username:password or a token
+     */
+    auth: RefCell<Option<String>>,
+    /**
+     * One-time password of a user with two-factor authentication, sent along with the
+     * credentials
+     */
+    otp: RefCell<Option<String>>,
+}
+
+impl InvManHttpClient {
+    pub fn new(url: &str, auth: Option<&str>) -> InvManHttpClient {
+        return InvManHttpClient {
+            url: url.trim_end_matches('/').into(),
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(CONNECT_TIMEOUT_SECONDS))
+                .build(),
+            auth: RefCell::new(auth.map(String::from)),
+            otp: RefCell::new(None),
+        };
+    }
+
+    /**
+     * Trusts the CA certificates of the PEM file ca besides the public ones, e.g. for a server
+     * started with --tls-cert, and presents the certificate and key of the PEM files of identity
+     * to servers started with --tls-client-ca.
+     */
+    pub fn with_tls(
+        mut self,
+        ca: Option<&str>,
+        identity: Option<(&str, &str)>,
+    ) -> Result<InvManHttpClient> {
+        let mut roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        if let Some(ca) = ca {
+            for certificate in read_certificates(ca)? {
+                roots.add(certificate)?;
+            }
+        }
+        let builder =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_root_certificates(roots);
+        let config = match identity {
+            Some((certificate, key)) => builder
+                .with_client_auth_cert(read_certificates(certificate)?, read_private_key(key)?)?,
+            None => builder.with_no_client_auth(),
+        };
+        self.agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(CONNECT_TIMEOUT_SECONDS))
+            .tls_config(Arc::new(config))
+            .build();
+        return Ok(self);
+    }
+
+    fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let mut request = self.agent.post(&format!("{}/rpc/{}", self.url, method));
+        if let Some(auth) = self.auth.borrow().as_deref() {
+            request = request.set("Authorization", &authorization_header(auth));
+        }
+        if let Some(otp) = self.otp.borrow().as_deref() {
+            request = request.set(OTP_HEADER, otp);
+        }
+        return match request.send_json(params) {
+            Ok(response) => Ok(response.into_json()?),
+            Err(ureq::Error::Status(status, response)) => {
+                let body: Value = response.into_json().unwrap_or_default();
+                match body["error"].as_str() {
+                    Some(error) => bail!("{}", error),
+                    None => bail!("Remote database responded with status {}", status),
+                }
+            }
+            Err(e) => bail!("Remote database at {} is not reachable ({})", self.url, e),
+        };
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let mut response = self.request(method, params)?;
+        return Ok(serde_json::from_value(response["result"].take())?);
+    }
+
+    /**
+     * Calls an operation that changes the config, which is then replaced by the server's.
+     */
+    fn call_config<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+        config: &mut AppConfig,
+    ) -> Result<T> {
+        let mut response = self.request(method, params)?;
+        *config = remote_config(serde_json::from_value(response["config"].take())?);
+        return Ok(serde_json::from_value(response["result"].take())?);
+    }
+}
+
+/**
+ * Username and password are sent by basic authentication, tokens as bearer.
+ */
+fn authorization_header(auth: &str) -> String {
+    return match auth.contains(':') {
+        true => format!("Basic {}", STANDARD.encode(auth)),
+        false => format!("Bearer {}", auth),
+    };
+}
+
+/**
+ * Hooks run programs on the machine of whoever invokes invman, so those of the server are
+ * never run by its clients.
+ */
+fn remote_config(mut config: AppConfig) -> AppConfig {
+    config.hooks = vec![];
+    return config;
+}
+
+impl InvManDBPool for InvManHttpClient {
+    /**
+     * Falls back to the default config if the server cannot be reached, the operations that
+     * follow fail with the reason then.
+     */
+    fn get_config(&self) -> Result<AppConfig> {
+        return Ok(match self.call("get_config", json!([])) {
+            Ok(config) => remote_config(config),
+            Err(e) => {
+                tracing::warn!(url = self.url, error = %e, "Could not get the remote config");
+                AppConfig::default()
+            }
+        });
+    }
+
+    fn user_register(&mut self, username: &str, password: &str) -> Result<String> {
+        return self.call("user_register", json!([username, password]));
+    }
+
+    fn init(
+        &mut self,
+        _username: &str,
+        _password: &str,
+        _allow_registration: bool,
+        _config: &mut AppConfig,
+    ) -> Result<String> {
+        bail!("A remote database is initialized on the server itself");
+    }
+
+    /**
+     * Keeps the credentials for the following operations, unless others were given already.
+     */
+    fn user_auth(
+        &self,
+        username: &str,
+        password: &str,
+        otp: Option<&str>,
+        user: &mut DBUser,
+    ) -> Result<()> {
+        *user = self.call("user_auth", json!([username, password, otp]))?;
+        self.auth
+            .borrow_mut()
+            .get_or_insert_with(|| format!("{}:{}", username, password));
+        if let Some(otp) = otp {
+            self.otp.borrow_mut().get_or_insert_with(|| otp.to_string());
+        }
+        return Ok(());
+    }
+
+    fn user_totp_enable(
+        &mut self,
+        secret: &str,
+        recovery_codes: &[String],
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("user_totp_enable", json!([secret, recovery_codes]));
+    }
+
+    fn user_totp_disable(&mut self, username: Option<&str>, _user: &DBUser) -> Result<String> {
+        return self.call("user_totp_disable", json!([username]));
+    }
+
+    fn user_edit(
+        &mut self,
+        username: Option<&str>,
+        password: Option<&str>,
+        must_change_password: bool,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        let result = self.call(
+            "user_edit",
+            json!([username, password, must_change_password]),
+        )?;
+        // The following operations are authenticated by the new password
+        if let (None, Some(password)) = (username, password) {
+            let mut auth = self.auth.borrow_mut();
+            let name = auth.as_deref().and_then(|e| e.split_once(':')).map(|e| e.0);
+            if let Some(name) = name.map(String::from) {
+                *auth = Some(format!("{}:{}", name, password));
+            }
+        }
+        return Ok(result);
+    }
+
+    /**
+     * The token is sent along with every operation, so the server verifies it like it does
+     * for those.
+     */
+    fn token_auth(&self, _token: &str, user: &mut DBUser) -> Result<()> {
+        *user = self.call("token_auth", json!([]))?;
+        return Ok(());
+    }
+
+    /**
+     * The secret never leaves the server, so tokens can only be issued by it.
+     */
+    fn token_secret(&self) -> Result<String> {
+        bail!("Tokens cannot be used with a remote database, authenticate with username:password");
+    }
+
+    fn token_add(
+        &mut self,
+        _expires_at: i64,
+        _scopes: &[String],
+        _user: &DBUser,
+    ) -> Result<String> {
+        bail!("Tokens cannot be used with a remote database, authenticate with username:password");
+    }
+
+    fn token_list(&self, _user: &DBUser) -> Result<Vec<DBToken>> {
+        return self.call("token_list", json!([]));
+    }
+
+    fn token_revoke(&mut self, token_id: &str, _user: &DBUser) -> Result<String> {
+        return self.call("token_revoke", json!([token_id]));
+    }
+
+    fn user_id(&self, username: &str) -> Result<u32> {
+        return self.call("user_id", json!([username]));
+    }
+
+    fn user_purge(
+        &mut self,
+        username: &str,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<DBPurgeReport> {
+        return self.call("user_purge", json!([username]));
+    }
+
+    /**
+     * The server scopes each operation to the organization of the authenticated user.
+     */
+    fn scope_to_org(&self, _org_id: Option<u32>) {}
+
+    fn org_create(&mut self, name: &str, _user: &DBUser) -> Result<String> {
+        return self.call("org_create", json!([name]));
+    }
+
+    fn org_invite(&mut self, org: &str, username: &str, _user: &DBUser) -> Result<String> {
+        return self.call("org_invite", json!([org, username]));
+    }
+
+    fn org_list(&self) -> Result<Vec<DBOrg>> {
+        return self.call("org_list", json!([]));
+    }
+
+    fn role_list(&self) -> Result<Vec<DBRole>> {
+        return self.call("role_list", json!([]));
+    }
+
+    fn role_set_ownership(
+        &mut self,
+        role: &str,
+        ownership: OwnershipPolicy,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("role_set_ownership", json!([role, ownership]));
+    }
+
+    fn role_deny_column(
+        &mut self,
+        role: &str,
+        column: &str,
+        mode: PermissionMode,
+        deny: bool,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("role_deny_column", json!([role, column, mode, deny]));
+    }
+
+    fn inventory_owner(&self, identifier: &str) -> Result<Option<u32>> {
+        return self.call("inventory_owner", json!([identifier]));
+    }
+
+    fn schema_alter(
+        &mut self,
+        config: &mut AppConfig,
+        decl: SchemaDeclaration,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("schema_alter", json!([decl]), config);
+    }
+
+    fn schema_remove(
+        &mut self,
+        config: &mut AppConfig,
+        name: &str,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("schema_remove", json!([name]), config);
+    }
+
+    fn schema_extend(
+        &mut self,
+        config: &mut AppConfig,
+        decls: Vec<SchemaDeclaration>,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("schema_extend", json!([decls]), config);
+    }
+
+    fn inventory_add(
+        &mut self,
+        params: &KeyValueCollection,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("inventory_add", json!([params]));
+    }
+
+    fn inventory_add_with_meta(
+        &mut self,
+        params: &KeyValueCollection,
+        meta: &BTreeMap<String, String>,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("inventory_add_with_meta", json!([params, meta]));
+    }
+
+    fn inventory_list(
+        &self,
+        props: &InventoryListProps,
+        _config: &AppConfig,
+    ) -> Result<Vec<KeyValueCollection>> {
+        return self.call("inventory_list", json!([props]));
+    }
+
+    fn inventory_list_each(
+        &self,
+        props: &InventoryListProps,
+        config: &AppConfig,
+        callback: &mut dyn FnMut(KeyValueCollection) -> Result<()>,
+    ) -> Result<()> {
+        for entry in self.inventory_list(props, config)? {
+            callback(entry)?;
+        }
+        return Ok(());
+    }
+
+    fn inventory_list_explain(
+        &self,
+        props: &InventoryListProps,
+        _config: &AppConfig,
+    ) -> Result<Vec<DBQueryPlan>> {
+        return self.call("inventory_list_explain", json!([props]));
+    }
+
+    fn explain_start(&mut self) {}
+
+    /**
+     * Statements run on the server, so only the plans of inventory_list_explain are available.
+     */
+    fn explain_finish(&mut self) -> Result<Vec<DBQueryPlan>> {
+        bail!("Query plans of a remote database are only available for inventory list");
+    }
+
+    fn inventory_archive(
+        &mut self,
+        filter: &WhereExpr,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("inventory_archive", json!([filter]));
+    }
+
+    fn inventory_count(&self, conditions: &KeyValueCollection) -> Result<u32> {
+        return self.call("inventory_count", json!([conditions]));
+    }
+
+    fn inventory_exists(&self, identifier: &str) -> Result<bool> {
+        return self.call("inventory_exists", json!([identifier]));
+    }
+
+    fn inventory_get(
+        &self,
+        identifier: &str,
+        _config: &AppConfig,
+    ) -> Result<Option<KeyValueCollection>> {
+        return self.call("inventory_get", json!([identifier]));
+    }
+
+    fn inventory_distinct(
+        &self,
+        column: &str,
+        conditions: &KeyValueCollection,
+        _config: &AppConfig,
+    ) -> Result<Vec<KeyValueCollection>> {
+        return self.call("inventory_distinct", json!([column, conditions]));
+    }
+
+    fn inventory_edit(
+        &mut self,
+        identifier: &str,
+        params: &KeyValueCollection,
+        expect_version: Option<&str>,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call(
+            "inventory_edit",
+            json!([identifier, params, expect_version]),
+        );
+    }
+
+    fn inventory_remove(
+        &mut self,
+        identifier: &str,
+        reason: Option<&str>,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("inventory_remove", json!([identifier, reason]));
+    }
+
+    fn inventory_merge(
+        &mut self,
+        keep: &str,
+        duplicate: &str,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("inventory_merge", json!([keep, duplicate]));
+    }
+
+    fn inventory_checkout(
+        &mut self,
+        identifier: &str,
+        props: &LoanProps,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("inventory_checkout", json!([identifier, props]));
+    }
+
+    fn inventory_checkin(&mut self, identifier: &str, _user: &DBUser) -> Result<String> {
+        return self.call("inventory_checkin", json!([identifier]));
+    }
+
+    fn loan_list(&self, overdue_only: bool) -> Result<Vec<DBLoan>> {
+        return self.call("loan_list", json!([overdue_only]));
+    }
+
+    fn inventory_assign(
+        &mut self,
+        identifier: &str,
+        assignee: u32,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("inventory_assign", json!([identifier, assignee]));
+    }
+
+    fn inventory_unassign(&mut self, identifier: &str, _user: &DBUser) -> Result<String> {
+        return self.call("inventory_unassign", json!([identifier]));
+    }
+
+    fn purchase_set(
+        &mut self,
+        identifier: &str,
+        props: &PurchaseProps,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("purchase_set", json!([identifier, props]));
+    }
+
+    fn purchase_list(&self) -> Result<Vec<DBPurchase>> {
+        return self.call("purchase_list", json!([]));
+    }
+
+    fn stock_adjust(
+        &mut self,
+        identifier: &str,
+        props: &AdjustProps,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("stock_adjust", json!([identifier, props]));
+    }
+
+    fn stock_transfer(
+        &mut self,
+        identifier: &str,
+        props: &TransferProps,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("stock_transfer", json!([identifier, props]));
+    }
+
+    fn stock_list(&self, identifier: &str) -> Result<Vec<DBStock>> {
+        return self.call("stock_list", json!([identifier]));
+    }
+
+    fn stock_movements(
+        &self,
+        identifier: &str,
+        location: Option<&str>,
+    ) -> Result<Vec<DBStockMovement>> {
+        return self.call("stock_movements", json!([identifier, location]));
+    }
+
+    fn stock_consumption(&self, since: &str) -> Result<Vec<DBStockConsumption>> {
+        return self.call("stock_consumption", json!([since]));
+    }
+
+    fn reason_summary(&self, since: Option<&str>) -> Result<Vec<DBReasonSummary>> {
+        return self.call("reason_summary", json!([since]));
+    }
+
+    fn stock_ledger(&self) -> Result<Vec<DBLedgerEntry>> {
+        return self.call("stock_ledger", json!([]));
+    }
+
+    fn serial_history(&self, serial: &str) -> Result<Vec<DBSerialEvent>> {
+        return self.call("serial_history", json!([serial]));
+    }
+
+    fn bom_define(
+        &mut self,
+        assembly: &str,
+        components: &[BomComponentProps],
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("bom_define", json!([assembly, components]));
+    }
+
+    fn bom_list(&self, assembly: &str) -> Result<Vec<DBBomComponent>> {
+        return self.call("bom_list", json!([assembly]));
+    }
+
+    fn bom_build(
+        &mut self,
+        assembly: &str,
+        props: &BuildProps,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("bom_build", json!([assembly, props]));
+    }
+
+    fn maintenance_schedule(
+        &mut self,
+        identifier: &str,
+        task: &str,
+        interval_days: u32,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call(
+            "maintenance_schedule",
+            json!([identifier, task, interval_days]),
+        );
+    }
+
+    fn maintenance_done(&mut self, identifier: &str, task: &str, _user: &DBUser) -> Result<String> {
+        return self.call("maintenance_done", json!([identifier, task]));
+    }
+
+    fn maintenance_due(&self, within_days: u32) -> Result<Vec<DBMaintenance>> {
+        return self.call("maintenance_due", json!([within_days]));
+    }
+
+    fn attachment_add(
+        &mut self,
+        identifier: &str,
+        filename: &str,
+        content: &[u8],
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("attachment_add", json!([identifier, filename, content]));
+    }
+
+    fn attachment_list(&self, identifier: &str) -> Result<Vec<DBAttachment>> {
+        return self.call("attachment_list", json!([identifier]));
+    }
+
+    fn attachment_content(&self, identifier: &str) -> Result<(String, Vec<u8>)> {
+        return self.call("attachment_content", json!([identifier]));
+    }
+
+    fn attachment_remove(&mut self, identifier: &str, _user: &DBUser) -> Result<String> {
+        return self.call("attachment_remove", json!([identifier]));
+    }
+
+    fn comment_add(&mut self, identifier: &str, body: &str, _user: &DBUser) -> Result<String> {
+        return self.call("comment_add", json!([identifier, body]));
+    }
+
+    fn comment_list(&self, identifier: &str) -> Result<Vec<DBComment>> {
+        return self.call("comment_list", json!([identifier]));
+    }
+
+    fn meta_set(
+        &mut self,
+        identifier: &str,
+        key: &str,
+        value: &str,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("meta_set", json!([identifier, key, value]));
+    }
+
+    fn meta_remove(&mut self, identifier: &str, key: &str, _user: &DBUser) -> Result<String> {
+        return self.call("meta_remove", json!([identifier, key]));
+    }
+
+    fn meta_get(&self, identifier: &str) -> Result<BTreeMap<String, String>> {
+        return self.call("meta_get", json!([identifier]));
+    }
+
+    fn image_set(
+        &mut self,
+        identifier: &str,
+        filename: &str,
+        content: &[u8],
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("image_set", json!([identifier, filename, content]));
+    }
+
+    fn image_get(&self, identifier: &str) -> Result<(String, Vec<u8>)> {
+        return self.call("image_get", json!([identifier]));
+    }
+
+    fn image_thumbnail(&self, identifier: &str, size: &str) -> Result<Option<Vec<u8>>> {
+        return self.call("image_thumbnail", json!([identifier, size]));
+    }
+
+    fn image_thumbnail_store(
+        &mut self,
+        identifier: &str,
+        size: &str,
+        content: &[u8],
+    ) -> Result<()> {
+        return self.call("image_thumbnail_store", json!([identifier, size, content]));
+    }
+
+    fn image_remove(&mut self, identifier: &str, _user: &DBUser) -> Result<String> {
+        return self.call("image_remove", json!([identifier]));
+    }
+
+    fn read_only_set(
+        &mut self,
+        config: &mut AppConfig,
+        read_only: bool,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("read_only_set", json!([read_only]), config);
+    }
+
+    fn timezone_set(
+        &mut self,
+        config: &mut AppConfig,
+        timezone: &str,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("timezone_set", json!([timezone]), config);
+    }
+
+    fn display_format_set(
+        &mut self,
+        config: &mut AppConfig,
+        format: DisplayFormat,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("display_format_set", json!([format]), config);
+    }
+
+    fn label_printer_set(
+        &mut self,
+        config: &mut AppConfig,
+        printer: Option<String>,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("label_printer_set", json!([printer]), config);
+    }
+
+    fn exchange_rates_set(
+        &mut self,
+        config: &mut AppConfig,
+        rates: ExchangeRates,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("exchange_rates_set", json!([rates]), config);
+    }
+
+    fn reason_codes_set(
+        &mut self,
+        config: &mut AppConfig,
+        codes: Vec<String>,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("reason_codes_set", json!([codes]), config);
+    }
+
+    fn ldap_set(
+        &mut self,
+        config: &mut AppConfig,
+        settings: Option<LdapSettings>,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("ldap_set", json!([settings]), config);
+    }
+
+    fn password_policy_set(
+        &mut self,
+        config: &mut AppConfig,
+        policy: PasswordPolicy,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("password_policy_set", json!([policy]), config);
+    }
+
+    fn registration_set(
+        &mut self,
+        config: &mut AppConfig,
+        allow: bool,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("registration_set", json!([allow]), config);
+    }
+
+    fn retention_set(
+        &mut self,
+        config: &mut AppConfig,
+        rules: RetentionRules,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("retention_set", json!([rules]), config);
+    }
+
+    fn retention_apply(
+        &mut self,
+        rules: &RetentionRules,
+        dry_run: bool,
+        _user: &DBUser,
+    ) -> Result<DBRetentionReport> {
+        return self.call("retention_apply", json!([rules, dry_run]));
+    }
+
+    fn dump(&self) -> Result<DBDump> {
+        return self.call("dump", json!([]));
+    }
+
+    fn load(&mut self, dump: &DBDump) -> Result<String> {
+        return self.call("load", json!([dump]));
+    }
+
+    fn fixture_users(&self) -> Result<Vec<DBFixtureUser>> {
+        return self.call("fixture_users", json!([]));
+    }
+
+    fn fixture_load(
+        &mut self,
+        schema: &[SchemaDeclaration],
+        users: &[DBFixtureUser],
+        entities: &[KeyValueCollection],
+        config: &mut AppConfig,
+    ) -> Result<String> {
+        return self.call_config("fixture_load", json!([schema, users, entities]), config);
+    }
+
+    /**
+     * The other database is a file on this machine, which the server cannot open.
+     */
+    fn merge(
+        &mut self,
+        _path: &str,
+        _strategy: MergeStrategy,
+        _config: &mut AppConfig,
+        _user: &DBUser,
+    ) -> Result<DBMergeReport> {
+        bail!("Databases cannot be merged into a remote database");
+    }
+
+    fn batch_begin(&mut self) -> Result<()> {
+        bail!("Batches cannot span the operations on a remote database");
+    }
+
+    fn batch_commit(&mut self) -> Result<()> {
+        bail!("Batches cannot span the operations on a remote database");
+    }
+
+    fn batch_rollback(&mut self) -> Result<()> {
+        bail!("Batches cannot span the operations on a remote database");
+    }
+
+    fn outbox_record(&mut self, _command: &[String]) -> Result<()> {
+        bail!("Commands cannot be queued offline for a remote database");
+    }
+
+    fn outbox_list(&self) -> Result<Vec<DBOutboxEntry>> {
+        bail!("Commands cannot be queued offline for a remote database");
+    }
+
+    fn outbox_remove(&mut self, _id: u32) -> Result<()> {
+        bail!("Commands cannot be queued offline for a remote database");
+    }
+
+    fn smtp_set(
+        &mut self,
+        config: &mut AppConfig,
+        settings: Option<SmtpSettings>,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("smtp_set", json!([settings]), config);
+    }
+
+    fn scheduler_set(
+        &mut self,
+        config: &mut AppConfig,
+        jobs: Vec<ScheduledJob>,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("scheduler_set", json!([jobs]), config);
+    }
+
+    fn hooks_set(
+        &mut self,
+        config: &mut AppConfig,
+        hooks: Vec<Hook>,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call_config("hooks_set", json!([hooks]), config);
+    }
+
+    fn rule_add(&mut self, rule: &RuleProps, _user: &DBUser) -> Result<String> {
+        return self.call("rule_add", json!([rule]));
+    }
+
+    fn rule_list(&self) -> Result<Vec<DBRule>> {
+        return self.call("rule_list", json!([]));
+    }
+
+    fn rule_remove(&mut self, name: &str, _user: &DBUser) -> Result<String> {
+        return self.call("rule_remove", json!([name]));
+    }
+
+    /**
+     * Without credentials, only commands no rule matches can have run, e.g. user register.
+     */
+    fn rule_matches(&self, after_event: u32) -> Result<Vec<DBRuleMatch>> {
+        if self.auth.borrow().is_none() {
+            return Ok(vec![]);
+        }
+        return self.call("rule_matches", json!([after_event]));
+    }
+
+    fn event_list(&self, after_event: u32) -> Result<Vec<DBEvent>> {
+        return self.call("event_list", json!([after_event]));
+    }
+
+    fn latest_event(&self) -> Result<u32> {
+        return self.call("latest_event", json!([]));
+    }
+
+    fn notify_rule_add(&mut self, rule: &NotifyRuleProps, _user: &DBUser) -> Result<String> {
+        return self.call("notify_rule_add", json!([rule]));
+    }
+
+    fn notify_rule_list(&self) -> Result<Vec<DBNotifyRule>> {
+        return self.call("notify_rule_list", json!([]));
+    }
+
+    fn notify_rule_remove(&mut self, id: u32, _user: &DBUser) -> Result<String> {
+        return self.call("notify_rule_remove", json!([id]));
+    }
+
+    fn notify_pending(&self) -> Result<DBPendingNotifications> {
+        return self.call("notify_pending", json!([]));
+    }
+
+    fn notify_advance(&mut self, event_id: u32) -> Result<()> {
+        return self.call("notify_advance", json!([event_id]));
+    }
+
+    fn doctor(&mut self, _config: &AppConfig, fix: bool) -> Result<DBDoctorReport> {
+        return self.call("doctor", json!([fix]));
+    }
+
+    fn optimize(&mut self, _user: &DBUser) -> Result<DBOptimizeReport> {
+        return self.call("optimize", json!([]));
+    }
+
+    fn metrics(&self) -> Result<DBMetrics> {
+        return self.call("metrics", json!([]));
+    }
+
+    fn health(&self) -> DBHealthReport {
+        return match self.call("health", json!([])) {
+            Ok(report) => report,
+            Err(e) => DBHealthReport {
+                healthy: false,
+                checks: vec![DBHealthCheck {
+                    name: "remote".into(),
+                    ok: false,
+                    detail: Some(e.to_string()),
+                }],
+            },
+        };
+    }
+
+    fn history_compact(
+        &mut self,
+        keep_last: u32,
+        older_than_days: u32,
+        _user: &DBUser,
+    ) -> Result<DBCompactionReport> {
+        return self.call("history_compact", json!([keep_last, older_than_days]));
+    }
+
+    fn supplier_add(&mut self, props: &SupplierProps, _user: &DBUser) -> Result<String> {
+        return self.call("supplier_add", json!([props]));
+    }
+
+    fn supplier_list(&self) -> Result<Vec<DBSupplier>> {
+        return self.call("supplier_list", json!([]));
+    }
+
+    fn search(&self, text: &str, props: &SearchProps) -> Result<DBSearchResults> {
+        return self.call("search", json!([text, props]));
+    }
+
+    fn supplier_edit(
+        &mut self,
+        identifier: &str,
+        props: &SupplierProps,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("supplier_edit", json!([identifier, props]));
+    }
+
+    fn supplier_remove(&mut self, identifier: &str, _user: &DBUser) -> Result<String> {
+        return self.call("supplier_remove", json!([identifier]));
+    }
+
+    fn supplier_link(&mut self, identifier: &str, entity: &str, _user: &DBUser) -> Result<String> {
+        return self.call("supplier_link", json!([identifier, entity]));
+    }
+
+    fn supplier_unlink(
+        &mut self,
+        identifier: &str,
+        entity: &str,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("supplier_unlink", json!([identifier, entity]));
+    }
+
+    fn category_create(&mut self, path: &str, _user: &DBUser) -> Result<String> {
+        return self.call("category_create", json!([path]));
+    }
+
+    fn category_list(&self) -> Result<Vec<DBCategory>> {
+        return self.call("category_list", json!([]));
+    }
+
+    fn category_id(&self, path: &str) -> Result<u32> {
+        return self.call("category_id", json!([path]));
+    }
+
+    fn category_remove(&mut self, path: &str, _user: &DBUser) -> Result<String> {
+        return self.call("category_remove", json!([path]));
+    }
+
+    fn category_assign(&mut self, identifier: &str, path: &str, _user: &DBUser) -> Result<String> {
+        return self.call("category_assign", json!([identifier, path]));
+    }
+
+    fn category_unassign(&mut self, identifier: &str, _user: &DBUser) -> Result<String> {
+        return self.call("category_unassign", json!([identifier]));
+    }
+
+    fn kit_define(&mut self, name: &str, items: &[KitItemProps], _user: &DBUser) -> Result<String> {
+        return self.call("kit_define", json!([name, items]));
+    }
+
+    fn kit_list(&self) -> Result<Vec<DBKit>> {
+        return self.call("kit_list", json!([]));
+    }
+
+    fn kit_get(&self, name: &str) -> Result<DBKit> {
+        return self.call("kit_get", json!([name]));
+    }
+
+    fn kit_remove(&mut self, name: &str, _user: &DBUser) -> Result<String> {
+        return self.call("kit_remove", json!([name]));
+    }
+
+    fn kit_pick(
+        &mut self,
+        name: &str,
+        props: &PickProps,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("kit_pick", json!([name, props]));
+    }
+
+    fn stocktake_start(&mut self, location: &str, _user: &DBUser) -> Result<String> {
+        return self.call("stocktake_start", json!([location]));
+    }
+
+    fn stocktake_record(
+        &mut self,
+        identifier: &str,
+        counted: i64,
+        _config: &AppConfig,
+        _user: &DBUser,
+    ) -> Result<String> {
+        return self.call("stocktake_record", json!([identifier, counted]));
+    }
+
+    fn stocktake_status(&self) -> Result<DBStocktakeReport> {
+        return self.call("stocktake_status", json!([]));
+    }
+
+    fn stocktake_close(&mut self, _user: &DBUser) -> Result<DBStocktakeReport> {
+        return self.call("stocktake_close", json!([]));
+    }
+
+    fn stocktake_cancel(&mut self, _user: &DBUser) -> Result<String> {
+        return self.call("stocktake_cancel", json!([]));
+    }
+}
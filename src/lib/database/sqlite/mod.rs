@@ -17,26 +17,253 @@
  * You should have received a copy of the GNU General Public License
  * along with invman. If not, see <https://www.gnu.org/licenses/>.
  */
+mod pool;
+
+pub use self::pool::{InvManSqlitePool, PooledSqlite};
 use super::{
-    AppConfig, Config, Count, DBOpNo, DBPermissionCollection, DBUser, EventActionNo, IdEntry,
-    IdPassword, InvManDBPool, InvManSerialization, InvManToSql, KeyValueCollection,
-    KeyValueTypeEntry, SchemaActionNo, SchemaCollection,
+    event_name, AppConfig, Config, Count, DBAttachment, DBBomComponent, DBCategory, DBComment,
+    DBCompactionReport, DBDoctorReport, DBDump, DBEvent, DBFixtureUser, DBHealthCheck,
+    DBHealthReport, DBKit, DBKitItem, DBLedgerEntry, DBLoan, DBMaintenance, DBMergeReport,
+    DBMetrics, DBNotification, DBNotifyRule, DBOpNo, DBOptimizeReport, DBOrg, DBOutboxEntry,
+    DBPendingNotifications, DBPermissionCollection, DBPurchase, DBPurgeReport, DBQueryPlan,
+    DBReasonSummary, DBRetentionReport, DBRole, DBRule, DBRuleMatch, DBSearchMatch,
+    DBSearchResults, DBSerialEvent, DBStock, DBStockConsumption, DBStockMovement, DBStocktakeLine,
+    DBStocktakeReport, DBSupplier, DBToken, DBUser, EventActionNo, IdEntry, IdPassword,
+    InvManDBPool, InvManSerialization, InvManToSql, KeyValueCollection, KeyValueTypeEntry,
+    PermissionMode, SchemaActionNo, SchemaCollection, DUMP_FORMAT, DUMP_VERSION, OTP_REQUIRED,
+};
+use crate::{
+    auth::auth_provider,
+    common::args::{
+        AdjustProps, BomComponentProps, BuildProps, ChangeMarker, ColumnType, DisplayFormat,
+        ExchangeRates, Expansion, Hook, InventoryListProps, KitItemProps, LdapSettings, LoanProps,
+        MergeStrategy, NotifyRuleProps, OwnershipPolicy, PasswordPolicy, PickProps, PurchaseProps,
+        RetentionRules, RuleProps, ScheduledJob, SchemaDeclaration, SearchProps, SerialStatus,
+        SmtpSettings, SupplierProps, TokenClaims, TransferProps, WhereExpr,
+    },
+    progress::Progress,
+    utils::{base32_decode, check_raw_clause, fuzzy_similarity, totp},
 };
-use crate::common::args::{ColumnType, InventoryListProps, SchemaDeclaration};
 use anyhow::{bail, Context, Result};
 use argon2::{
     password_hash::{rand_core::OsRng, SaltString},
     Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
 };
+use rusqlite::functions::FunctionFlags;
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
 use rusqlite::params;
-use rusqlite::types::Type;
-use rusqlite::{params_from_iter, Connection, Row};
+use rusqlite::types::{Type, Value, ValueRef};
+use rusqlite::{
+    params_from_iter, Connection, DatabaseName, OpenFlags, OptionalExtension, Row, Statement,
+};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct InvManSqlite {
     db: Connection,
+    /**
+     * Organization of the authenticated user, returned by invman_org() in queries, 0 for none
+     */
+    org: Arc<AtomicU32>,
+}
+
+/**
+ * Statements applied on top of the v0001 initial setup, where the n-th entry upgrades the
+ * database to version n + 2. The version a database is at is stored as its user_version.
+ */
+const MIGRATIONS: &[&[&str]] = &[
+    &[include_str!("./sql/v0002/create_inventory_tx_index.sql")],
+    &[
+        include_str!("./sql/v0003/create_suppliers_table.sql"),
+        include_str!("./sql/v0003/create_suppliers_trigger.sql"),
+        include_str!("./sql/v0003/create_inventory_suppliers_table.sql"),
+        include_str!("./sql/v0003/insert_supplier_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0004/create_loans_table.sql"),
+        include_str!("./sql/v0004/insert_loan_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0005/create_stock_movements_table.sql"),
+        include_str!("./sql/v0005/create_stock_movements_index.sql"),
+        include_str!("./sql/v0005/insert_stock_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0006/create_serials_table.sql"),
+        include_str!("./sql/v0006/create_serials_trigger.sql"),
+        include_str!("./sql/v0006/create_serials_tx_table.sql"),
+        include_str!("./sql/v0006/insert_serial_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0007/create_bom_table.sql"),
+        include_str!("./sql/v0007/insert_bom_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0008/create_assignments_table.sql"),
+        include_str!("./sql/v0008/create_assignments_index.sql"),
+        include_str!("./sql/v0008/insert_assignment_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0009/create_maintenance_table.sql"),
+        include_str!("./sql/v0009/create_maintenance_trigger.sql"),
+        include_str!("./sql/v0009/create_maintenance_tx_table.sql"),
+        include_str!("./sql/v0009/insert_maintenance_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0010/create_purchases_table.sql"),
+        include_str!("./sql/v0010/create_purchases_trigger.sql"),
+        include_str!("./sql/v0010/insert_purchase_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0011/create_attachments_table.sql"),
+        include_str!("./sql/v0011/create_attachments_index.sql"),
+        include_str!("./sql/v0011/insert_attachment_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0012/create_comments_table.sql"),
+        include_str!("./sql/v0012/create_comments_index.sql"),
+        include_str!("./sql/v0012/insert_comment_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0013/create_images_table.sql"),
+        include_str!("./sql/v0013/create_image_thumbnails_table.sql"),
+        include_str!("./sql/v0013/insert_image_permissions.sql"),
+    ],
+    &[include_str!("./sql/v0014/insert_archive_permissions.sql")],
+    &[
+        include_str!("./sql/v0015/insert_retention_config.sql"),
+        include_str!("./sql/v0015/insert_db_permissions.sql"),
+    ],
+    &[include_str!("./sql/v0016/create_outbox_table.sql")],
+    &[include_str!("./sql/v0017/insert_read_only_config.sql")],
+    &[include_str!("./sql/v0018/create_locks_table.sql")],
+    &[
+        include_str!("./sql/v0019/create_notify_rules_table.sql"),
+        include_str!("./sql/v0019/insert_notify_config.sql"),
+        include_str!("./sql/v0019/insert_notify_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0020/add_notify_rules_channel.sql"),
+        include_str!("./sql/v0020/add_notify_rules_condition.sql"),
+    ],
+    &[include_str!("./sql/v0021/insert_scheduler_config.sql")],
+    &[
+        include_str!("./sql/v0022/create_rules_table.sql"),
+        include_str!("./sql/v0022/insert_rule_permissions.sql"),
+    ],
+    &[include_str!("./sql/v0023/insert_hooks_config.sql")],
+    &[
+        include_str!("./sql/v0024/create_tokens_table.sql"),
+        include_str!("./sql/v0024/insert_token_secret.sql"),
+    ],
+    &[
+        include_str!("./sql/v0025/create_orgs_table.sql"),
+        include_str!("./sql/v0025/create_org_items_table.sql"),
+        include_str!("./sql/v0025/add_users_org.sql"),
+        include_str!("./sql/v0025/insert_org_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0026/create_inventory_owners_table.sql"),
+        include_str!("./sql/v0026/insert_inventory_owners.sql"),
+        include_str!("./sql/v0026/create_inventory_owners_trigger.sql"),
+        include_str!("./sql/v0026/add_roles_ownership.sql"),
+    ],
+    &[include_str!(
+        "./sql/v0027/create_roles_denied_columns_table.sql"
+    )],
+    &[include_str!("./sql/v0028/insert_timezone_config.sql")],
+    &[include_str!("./sql/v0029/insert_display_format_config.sql")],
+    &[include_str!("./sql/v0030/insert_label_printer_config.sql")],
+    &[include_str!(
+        "./sql/v0031/add_stock_movements_unit_cost.sql"
+    )],
+    &[
+        include_str!("./sql/v0032/insert_exchange_rates_config.sql"),
+        include_str!("./sql/v0032/add_purchases_currency.sql"),
+        include_str!("./sql/v0032/add_stock_movements_currency.sql"),
+    ],
+    &[
+        include_str!("./sql/v0033/create_categories_table.sql"),
+        include_str!("./sql/v0033/create_inventory_categories_table.sql"),
+        include_str!("./sql/v0033/create_inventory_categories_index.sql"),
+        include_str!("./sql/v0033/insert_category_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0034/create_kits_table.sql"),
+        include_str!("./sql/v0034/create_kit_items_table.sql"),
+        include_str!("./sql/v0034/insert_kit_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0035/create_stocktakes_table.sql"),
+        include_str!("./sql/v0035/create_stocktake_counts_table.sql"),
+        include_str!("./sql/v0035/add_stock_movements_reason.sql"),
+        include_str!("./sql/v0035/insert_stocktake_permissions.sql"),
+    ],
+    &[
+        include_str!("./sql/v0036/insert_reason_codes_config.sql"),
+        include_str!("./sql/v0036/create_stock_movements_reason_index.sql"),
+    ],
+    &[include_str!("./sql/v0037/add_tokens_scopes.sql")],
+    &[
+        include_str!("./sql/v0038/add_users_totp_secret.sql"),
+        include_str!("./sql/v0038/add_users_totp_last_step.sql"),
+        include_str!("./sql/v0038/add_users_otp_failures.sql"),
+        include_str!("./sql/v0038/add_users_otp_failed_at.sql"),
+        include_str!("./sql/v0038/create_recovery_codes_table.sql"),
+        include_str!("./sql/v0038/create_recovery_codes_index.sql"),
+    ],
+    &[include_str!("./sql/v0039/insert_ldap_config.sql")],
+    &[
+        include_str!("./sql/v0040/add_users_password_changed_at.sql"),
+        include_str!("./sql/v0040/update_users_password_changed_at.sql"),
+        include_str!("./sql/v0040/add_users_must_change_password.sql"),
+        include_str!("./sql/v0040/insert_password_policy_config.sql"),
+    ],
+    &[
+        include_str!("./sql/v0041/create_inventory_meta_table.sql"),
+        include_str!("./sql/v0041/insert_meta_permissions.sql"),
+    ],
+];
+
+/**
+ * Recovery codes are random, so a fast hash suffices. Dashes and case are ignored, so the
+ * code can be typed either way.
+ */
+fn recovery_code_hash(code: &str) -> String {
+    let code = code.replace('-', "").to_lowercase();
+    return format!("{:x}", Sha256::digest(code.as_bytes()));
 }
 
+/**
+ * Username of the tombstone user, that the records of purged users refer to
+ */
+const PURGED_USERNAME: &str = "[purged]";
+
+/**
+ * Locks held longer than this are considered stale, e.g. those of an invman that was stopped
+ * while holding one. Rebuilding a table holds the write lock of the database, so that a lock
+ * cannot be taken over while its table is still being rebuilt.
+ */
+const STALE_LOCK_MINUTES: u32 = 60;
+
+/**
+ * Rows copied at once when the inventory table is rebuilt, tables with more show a progress
+ */
+const REBUILD_CHUNK_ROWS: u64 = 10000;
+
+/**
+ * Incorrect one-time passwords in a row after which a user cannot log in for
+ * OTP_LOCKOUT_SECONDS, so that the 6 digits cannot be guessed by trying them all
+ */
+const OTP_MAX_FAILURES: u32 = 5;
+const OTP_LOCKOUT_SECONDS: u32 = 300;
+
 trait InvManTypedKeyValue {
     fn to_typed_key_value(&self, declarations: &SchemaCollection) -> Result<KeyValueCollection>;
 }
@@ -60,15 +287,29 @@ impl InvManTypedKeyValue for Row<'_> {
                             value: Some(value),
                         })
                     }
-                    "created_at" | "updated_at" | "deleted_at" => {
+                    "revision" => Ok(KeyValueTypeEntry {
+                        column_type: ColumnType::INT,
+                        key: key.to_string(),
+                        value: val_ref.as_i64_or_null()?.map(|val| val.to_string()),
+                    }),
+                    // Already serialized to JSON, which is printed as is just like numbers
+                    "attachments" | "suppliers" | "locations" | "category" | "assignment"
+                    | "meta"
+                        if !declarations.collection.iter().any(|e| e.name == key) =>
+                    {
+                        Ok(KeyValueTypeEntry {
+                            column_type: ColumnType::INT,
+                            key: key.to_string(),
+                            value: val_ref.as_str_or_null()?.map(|val| val.to_string()),
+                        })
+                    }
+                    "created_at" | "updated_at" | "deleted_at" | "last_modified_by"
+                    | "last_modified_at" | "change" => {
                         let value = val_ref.as_str_or_null()?;
                         Ok(KeyValueTypeEntry {
                             column_type: ColumnType::TEXT,
                             key: key.to_string(),
-                            value: match value {
-                                None => None,
-                                Some(val) => Some(val.to_string()),
-                            },
+                            value: value.map(|val| val.to_string()),
                         })
                     }
                     _ => {
@@ -78,61 +319,754 @@ impl InvManTypedKeyValue for Row<'_> {
                         }
                         let decl = decl.unwrap();
 
-                        fn string_or_none<T>(e: Option<T>) -> Option<String>
-                        where
-                            T: ToString,
-                        {
-                            if let Some(val) = e {
-                                Some(val.to_string())
-                            } else {
-                                None
-                            }
-                        }
-
-                        let value = match val_ref.data_type() {
-                            Type::Blob => {
-                                if let Some(val) = val_ref.as_blob_or_null()? {
-                                    Some(std::str::from_utf8(val).unwrap().to_string())
-                                } else {
-                                    None
-                                }
-                            }
-                            Type::Integer => string_or_none(val_ref.as_i64_or_null()?),
-                            Type::Real => string_or_none(val_ref.as_f64_or_null()?),
-                            Type::Text => string_or_none(val_ref.as_str_or_null()?),
-                            Type::Null => None,
-                        };
                         Ok(KeyValueTypeEntry {
                             column_type: decl.column_type,
                             key: key.to_string(),
-                            value,
+                            value: value_ref_to_string(val_ref)?,
                         })
                     }
                 }
             })
-            .into_iter()
             .collect::<Result<Vec<KeyValueTypeEntry>>>();
         Ok(KeyValueCollection { collection: items? })
     }
 }
 
+fn value_ref_to_string(val_ref: ValueRef) -> Result<Option<String>> {
+    fn string_or_none<T>(e: Option<T>) -> Option<String>
+    where
+        T: ToString,
+    {
+        e.map(|val| val.to_string())
+    }
+
+    return Ok(match val_ref.data_type() {
+        Type::Blob => val_ref
+            .as_blob_or_null()?
+            .map(|val| std::str::from_utf8(val).unwrap().to_string()),
+        Type::Integer => string_or_none(val_ref.as_i64_or_null()?),
+        Type::Real => string_or_none(val_ref.as_f64_or_null()?),
+        Type::Text => string_or_none(val_ref.as_str_or_null()?),
+        Type::Null => None,
+    });
+}
+
+/**
+ * Converts a column value for a dump. Blobs are stored as hex within an object, so that they
+ * can be told apart from text.
+ */
+fn value_ref_to_json(val_ref: ValueRef) -> serde_json::Value {
+    return match val_ref {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(val) => val.into(),
+        ValueRef::Real(val) => val.into(),
+        ValueRef::Text(val) => String::from_utf8_lossy(val).into(),
+        ValueRef::Blob(val) => serde_json::json!({
+            "blob": val.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+        }),
+    };
+}
+
+fn json_to_value(json: &serde_json::Value) -> Result<Value> {
+    return Ok(match json {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(val) => Value::Integer(i64::from(*val)),
+        serde_json::Value::Number(val) => match val.as_i64() {
+            Some(val) => Value::Integer(val),
+            None => Value::Real(val.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(val) => Value::Text(val.clone()),
+        serde_json::Value::Object(val) => match val.get("blob").and_then(|e| e.as_str()) {
+            Some(hex) if hex.len() % 2 == 0 => Value::Blob(
+                (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .with_context(|| "Dump contains an invalid blob")?,
+            ),
+            _ => bail!("Dump contains an invalid value {}", json),
+        },
+        serde_json::Value::Array(_) => bail!("Dump contains an invalid value {}", json),
+    });
+}
+
+fn table_names(db: &Connection) -> Result<Vec<String>> {
+    let mut stmt = db.prepare(
+        "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )?;
+    let names = stmt
+        .query_map((), |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    return Ok(names);
+}
+
+/**
+ * Condition on the id of entities, that they belong to the organization of the authenticated
+ * user. Users of no organization see all entities.
+ */
+fn org_scope(column: &str) -> String {
+    return format!(
+        "(invman_org() IS NULL OR {} IN (SELECT inventory_id FROM invman_org_items WHERE org_id=invman_org()))",
+        column
+    );
+}
+
+fn is_entity_available(db: &Connection, identifier: &str) -> Result<bool> {
+    let available = db.query_row(
+        format!(
+            "SELECT EXISTS(SELECT 1 FROM invman_inventory WHERE id=?1 AND deleted_at IS NULL AND {})",
+            org_scope("id")
+        )
+        .as_str(),
+        params![identifier],
+        |row| row.get(0),
+    )?;
+    return Ok(available);
+}
+
+fn stock_quantity(db: &Connection, identifier: &str, location: &str) -> Result<i64> {
+    let quantity = db.query_row(
+        "SELECT COALESCE(SUM(quantity), 0) FROM invman_stock_movements WHERE inventory_id=?1 AND location=?2",
+        params![identifier, location],
+        |row| row.get(0),
+    )?;
+    return Ok(quantity);
+}
+
+/**
+ * Checks that the reason is one of the configured codes. Without any codes configured, the
+ * reason is optional and can be any text.
+ */
+fn check_reason(reason: Option<&str>, config: &AppConfig) -> Result<()> {
+    if config.reason_codes.is_empty() {
+        return Ok(());
+    }
+    return match reason {
+        Some(reason) if config.reason_codes.iter().any(|e| e == reason) => Ok(()),
+        Some(reason) => bail!(
+            "Unknown reason '{}', use one of: {}",
+            reason,
+            config.reason_codes.join(", ")
+        ),
+        None => bail!(
+            "A reason is required, use one of: {}",
+            config.reason_codes.join(", ")
+        ),
+    };
+}
+
+/**
+ * The stocktake that is currently open, as its identifier and location
+ */
+fn open_stocktake(db: &Connection) -> Result<(u32, String)> {
+    let stocktake = db
+        .query_row(
+            "SELECT id, location FROM invman_stocktakes WHERE closed_at IS NULL",
+            (),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    return match stocktake {
+        Some(stocktake) => Ok(stocktake),
+        None => bail!("No stocktake is open, start one with stocktake start"),
+    };
+}
+
+fn stocktake_report(db: &Connection, id: u32) -> Result<DBStocktakeReport> {
+    let (location, started_at, closed_at): (String, String, Option<String>) = db.query_row(
+        "SELECT location, started_at, closed_at FROM invman_stocktakes WHERE id=?1",
+        params![id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let mut stmt = db.prepare(
+        "SELECT inventory_id, expected, counted FROM invman_stocktake_counts WHERE stocktake_id=?1 ORDER BY inventory_id",
+    )?;
+    let lines = stmt
+        .query_map(params![id], |row| {
+            let expected: i64 = row.get(1)?;
+            let counted: i64 = row.get(2)?;
+            Ok(DBStocktakeLine {
+                inventory_id: row.get(0)?,
+                expected,
+                counted,
+                variance: counted - expected,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<DBStocktakeLine>>>()?;
+    let mut stmt = db.prepare(
+        format!(
+            "SELECT m.inventory_id FROM invman_stock_movements m INNER JOIN invman_inventory i ON i.id=m.inventory_id WHERE m.location=?1 AND i.deleted_at IS NULL AND {} AND m.inventory_id NOT IN (SELECT inventory_id FROM invman_stocktake_counts WHERE stocktake_id=?2) GROUP BY m.inventory_id HAVING SUM(m.quantity) != 0 ORDER BY m.inventory_id",
+            org_scope("i.id")
+        )
+        .as_str(),
+    )?;
+    let uncounted = stmt
+        .query_map(params![location, id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<u32>>>()?;
+    return Ok(DBStocktakeReport {
+        id,
+        location,
+        started_at,
+        closed_at,
+        lines,
+        uncounted,
+    });
+}
+
+/**
+ * An entity is serialized when the schema column flagged as serialized is set for it.
+ */
+fn is_entity_serialized(db: &Connection, identifier: &str, config: &AppConfig) -> Result<bool> {
+    let column = match config
+        .inventory_schema_declaration
+        .collection
+        .iter()
+        .find(|e| e.serialized)
+    {
+        Some(decl) => decl.name.clone(),
+        None => return Ok(false),
+    };
+    let value = db.query_row(
+        format!("SELECT \"{}\" FROM invman_inventory WHERE id=?1", column).as_str(),
+        params![identifier],
+        |row| Ok(value_ref_to_string(row.get_ref(0)?)),
+    )??;
+    return Ok(matches!(value.as_deref(), Some("true") | Some("1")));
+}
+
+fn check_serials(serials: &[String], quantity: i64, serialized: bool) -> Result<()> {
+    if !serialized {
+        if !serials.is_empty() {
+            bail!("Serials can only be given for serialized entities");
+        }
+        return Ok(());
+    }
+    if serials.len() as u64 != quantity.unsigned_abs() {
+        bail!(
+            "A serialized entity requires one serial per unit ({} required, {} given)",
+            quantity.unsigned_abs(),
+            serials.len()
+        );
+    }
+    if let Some((_, serial)) = serials
+        .iter()
+        .enumerate()
+        .find(|(i, serial)| serials[..*i].contains(serial))
+    {
+        bail!("Serial {} is given more than once", serial);
+    }
+    return Ok(());
+}
+
+struct SerialState {
+    id: i64,
+    status: String,
+    location: String,
+}
+
+/**
+ * Looks up the unit with the given serial, which must belong to the given entity.
+ */
+fn serial_state(db: &Connection, identifier: &str, serial: &str) -> Result<Option<SerialState>> {
+    let state = db
+        .query_row(
+            "SELECT id, inventory_id=?2, status, location FROM invman_serials WHERE serial=?1",
+            params![serial, identifier],
+            |row| {
+                Ok((
+                    row.get::<_, bool>(1)?,
+                    SerialState {
+                        id: row.get(0)?,
+                        status: row.get(2)?,
+                        location: row.get(3)?,
+                    },
+                ))
+            },
+        )
+        .optional()?;
+    return match state {
+        Some((false, _)) => bail!("Serial {} belongs to another entity", serial),
+        Some((true, state)) => Ok(Some(state)),
+        None => Ok(None),
+    };
+}
+
+fn serial_in_stock(
+    db: &Connection,
+    identifier: &str,
+    serial: &str,
+    location: &str,
+) -> Result<SerialState> {
+    return match serial_state(db, identifier, serial)? {
+        Some(state)
+            if state.status == SerialStatus::InStock.to_string() && state.location == location =>
+        {
+            Ok(state)
+        }
+        _ => bail!(
+            "Serial {} is not in stock at location '{}'",
+            serial,
+            location
+        ),
+    };
+}
+
+fn record_serial_change(
+    db: &Connection,
+    state: &SerialState,
+    movement_id: i64,
+    to_status: SerialStatus,
+    to_location: &str,
+    user: &DBUser,
+) -> Result<()> {
+    db.execute(
+        "UPDATE invman_serials SET status=?1, location=?2 WHERE id=?3",
+        params![to_status.to_string(), to_location, state.id],
+    )?;
+    db.execute(
+        "INSERT INTO invman_serials_tx (serial_id, dispatcher, movement_id, from_status, to_status, from_location, to_location) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            state.id,
+            user.id,
+            movement_id,
+            state.status,
+            to_status.to_string(),
+            state.location,
+            to_location
+        ],
+    )?;
+    return Ok(());
+}
+
+/**
+ * @returns The tables and columns referencing the inventory, looked up from the schema
+ */
+fn inventory_references(db: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = db.prepare(
+        "SELECT m.name, f.\"from\" FROM sqlite_master AS m JOIN pragma_foreign_key_list(m.name) AS f WHERE m.type='table' AND f.\"table\"='invman_inventory'",
+    )?;
+    let references = stmt
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+    return Ok(references);
+}
+
+/**
+ * Builds a condition that only holds for entities no other table references, which are the
+ * only ones that can be deleted for good. The referencing tables are looked up from the schema.
+ */
+fn unreferenced_entity_condition(db: &Connection) -> Result<String> {
+    let conditions = inventory_references(db)?
+        .into_iter()
+        .map(|(table, column)| {
+            format!(
+                "NOT EXISTS(SELECT 1 FROM {table} WHERE {table}.{column} = invman_inventory.id)",
+            )
+        })
+        .collect::<Vec<String>>();
+    if conditions.is_empty() {
+        return Ok("1=1".into());
+    }
+    return Ok(conditions.join(" AND "));
+}
+
+/**
+ * Reads a rule from the columns of invman_rules in their order, starting at the given index.
+ * Errors of parsing the stored values are returned within, since rusqlite cannot hold them.
+ */
+fn row_to_rule(row: &rusqlite::Row, start: usize) -> rusqlite::Result<Result<DBRule>> {
+    let set_values: String = row.get(start + 4)?;
+    let notify_channel: Option<String> = row.get(start + 5)?;
+    let (set, notify_channel) = match (
+        serde_json::from_str(&set_values),
+        notify_channel.map(|e| e.parse()).transpose(),
+    ) {
+        (Ok(set), Ok(notify_channel)) => (set, notify_channel),
+        (Err(e), _) => return Ok(Err(e.into())),
+        (_, Err(e)) => return Ok(Err(e)),
+    };
+    return Ok(Ok(DBRule {
+        id: row.get(start)?,
+        name: row.get(start + 1)?,
+        event: event_name(row.get(start + 2)?).unwrap_or("unknown").into(),
+        condition: row.get(start + 3)?,
+        set,
+        notify_channel,
+        notify_recipient: row.get(start + 6)?,
+        created_at: row.get(start + 7)?,
+    }));
+}
+
+/**
+ * Logs every executed statement with the time it took, e.g. to find slow queries.
+ */
+fn trace_statement(sql: &str, elapsed: Duration) {
+    tracing::debug!(
+        target: "invman::sql",
+        elapsed_ms = elapsed.as_secs_f64() * 1000.0,
+        "{}",
+        sql
+    );
+}
+
+thread_local! {
+    /**
+     * Queries recorded between explain_start and explain_finish, None while not recording
+     */
+    static EXPLAINED_STATEMENTS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/**
+ * Records the statement for explaining it if it reads, with its parameters filled in.
+ */
+fn record_statement(sql: &str) {
+    let sql = sql.trim();
+    let prefix = sql.get(..6).unwrap_or(sql).to_uppercase();
+    if !prefix.starts_with("SELECT") && !prefix.starts_with("WITH") {
+        return;
+    }
+    EXPLAINED_STATEMENTS.with(|statements| {
+        if let Some(statements) = statements.borrow_mut().as_mut() {
+            if !statements.iter().any(|e| e == sql) {
+                statements.push(sql.into());
+            }
+        }
+    });
+}
+
 impl InvManSqlite {
+    fn query_plan(&self, sql: &str, params: &[String]) -> Result<DBQueryPlan> {
+        let mut stmt = self.db.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+        let rows = stmt.query_map(params_from_iter(params), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        // Steps come before the steps below them, so the depth of the parent is always known
+        let mut depths: BTreeMap<i64, usize> = BTreeMap::new();
+        let mut plan = vec![];
+        for row in rows {
+            let (id, parent, detail) = row?;
+            let depth = depths.get(&parent).map_or(0, |e| e + 1);
+            depths.insert(id, depth);
+            plan.push(format!("{}{}", "  ".repeat(depth), detail));
+        }
+        return Ok(DBQueryPlan {
+            sql: sql.into(),
+            plan,
+        });
+    }
+
+    /**
+     * Accepts the one-time password of the current 30 seconds, or of those before and after to
+     * allow for clocks that are slightly off, or an unused recovery code, which is used up. A
+     * one-time password is only accepted once, i.e. its step has to come after the last accepted
+     * one. After OTP_MAX_FAILURES incorrect ones in a row, none are accepted for
+     * OTP_LOCKOUT_SECONDS. A database opened read-only records neither.
+     */
+    fn verify_otp(&self, user_id: u32, secret: &str, otp: Option<&str>) -> Result<()> {
+        let otp = match otp {
+            Some(otp) => otp.trim(),
+            None => bail!(OTP_REQUIRED),
+        };
+        let (last_step, failures, locked): (Option<u64>, u32, bool) = self.db.query_row(
+            "SELECT totp_last_step, otp_failures, COALESCE(otp_failed_at > STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW', '-' || ?2 || ' seconds'), 0) FROM invman_users WHERE id=?1",
+            params![user_id, OTP_LOCKOUT_SECONDS],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        if failures >= OTP_MAX_FAILURES && locked {
+            bail!(
+                "Too many incorrect one-time passwords, try again in {} minutes",
+                OTP_LOCKOUT_SECONDS / 60
+            );
+        }
+        let writable = !self.db.is_readonly(DatabaseName::Main)?;
+        let accepted = match otp.len() == 6 && otp.chars().all(|c| c.is_ascii_digit()) {
+            true => {
+                let secret = base32_decode(secret)?;
+                let step = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() / 30;
+                #[allow(deprecated)]
+                let matches = |step: &u64| {
+                    ring::constant_time::verify_slices_are_equal(
+                        totp(&secret, *step).as_bytes(),
+                        otp.as_bytes(),
+                    )
+                    .is_ok()
+                };
+                match (step - 1..=step + 1)
+                    .filter(|e| last_step.is_none_or(|last| *e > last))
+                    .find(matches)
+                {
+                    // Of concurrent logins with the same password, only the first one records it
+                    Some(step) if writable => {
+                        self.db.execute(
+                            "UPDATE invman_users SET totp_last_step=?2 WHERE id=?1 AND (totp_last_step IS NULL OR totp_last_step < ?2)",
+                            params![user_id, step],
+                        )? == 1
+                    }
+                    Some(_) => true,
+                    None => false,
+                }
+            }
+            false => {
+                self.db.execute(
+                    "UPDATE invman_recovery_codes SET used_at=STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW') WHERE user_id=?1 AND code_hash=?2 AND used_at IS NULL",
+                    params![user_id, recovery_code_hash(otp)],
+                )? == 1
+            }
+        };
+        if !writable {
+            match accepted {
+                true => return Ok(()),
+                false => bail!("One-time password is incorrect"),
+            }
+        }
+        if !accepted {
+            // The failures start over once a lockout has passed
+            self.db.execute(
+                "UPDATE invman_users SET otp_failures=CASE WHEN otp_failures >= ?2 THEN 1 ELSE otp_failures + 1 END, otp_failed_at=STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW') WHERE id=?1",
+                params![user_id, OTP_MAX_FAILURES],
+            )?;
+            bail!("One-time password is incorrect");
+        }
+        if failures > 0 {
+            self.db.execute(
+                "UPDATE invman_users SET otp_failures=0, otp_failed_at=NULL WHERE id=?1",
+                params![user_id],
+            )?;
+        }
+        return Ok(());
+    }
+
+    /**
+     * Reads a value of the config, for operations that are not given the loaded one.
+     */
+    fn config_value<T: DeserializeOwned>(&self, name: &str) -> Result<T> {
+        let value: String = self.db.query_row(
+            "SELECT value FROM invman_config WHERE name=?1",
+            params![name],
+            |row| row.get(0),
+        )?;
+        return Ok(serde_json::from_str(&value)?);
+    }
+
+    /**
+     * Authenticates the user by the configured provider and returns its ID. The user is
+     * created on its first login and its role follows the one the provider determines.
+     */
+    fn provider_auth(&self, username: &str, password: &str) -> Result<u32> {
+        let ldap: Option<LdapSettings> = self.config_value("ldap")?;
+        let Some(provider) = auth_provider(ldap.as_ref()) else {
+            bail!("Either username or password is incorrect");
+        };
+        let role = provider.authenticate(username, password)?;
+        let role_id: u32 = match self
+            .db
+            .query_row(
+                "SELECT id FROM invman_roles WHERE name=?1 AND deleted_at IS NULL",
+                params![role],
+                |row| row.get(0),
+            )
+            .optional()?
+        {
+            Some(id) => id,
+            None => bail!(
+                "Role {} that the directory maps the user to does not exist",
+                role
+            ),
+        };
+        let existing: Option<(u32, Option<String>)> = self
+            .db
+            .query_row(
+                "SELECT id, deleted_at FROM invman_users WHERE username=?1",
+                params![username],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        return match existing {
+            Some((_, Some(_))) => bail!("User {} cannot log in", username),
+            Some((id, None)) => {
+                self.db.execute(
+                    "UPDATE invman_users SET role_id=?1, updated_at=STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW') WHERE id=?2 AND role_id<>?1",
+                    params![role_id, id],
+                )?;
+                Ok(id)
+            }
+            None => {
+                // Without a password of its own the user is always authenticated by the provider
+                self.db.execute(
+                    "INSERT INTO invman_users (username, role_id, password) VALUES (?1, ?2, '')",
+                    params![username, role_id],
+                )?;
+                Ok(self.db.last_insert_rowid() as u32)
+            }
+        };
+    }
+
+    fn load_permissions(&self, user: &mut DBUser) -> Result<()> {
+        let mut stmt = self.db.prepare("SELECT p.name FROM invman_users AS u JOIN invman_roles_permissions AS up ON up.role_id = u.role_id JOIN invman_permissions AS p ON p.id = up.permission_id WHERE u.id=?1")?;
+        let rows = stmt.query_map(params![user.id], |row| {
+            Ok(row.get::<usize, String>(0)?.to_owned())
+        })?;
+        user.permissions = DBPermissionCollection::new(rows.map(|e| e.unwrap()).collect());
+        let (username, org_id, ownership) = self.db.query_row(
+            "SELECT u.username, u.org_id, r.ownership FROM invman_users AS u JOIN invman_roles AS r ON r.id = u.role_id WHERE u.id=?1",
+            params![user.id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?)),
+        )?;
+        user.username = username;
+        user.org_id = org_id;
+        user.ownership = ownership.parse()?;
+        let mut stmt = self.db.prepare("SELECT 'inventory.' || d.column_name || '.' || d.mode FROM invman_users AS u JOIN invman_roles_denied_columns AS d ON d.role_id = u.role_id WHERE u.id=?1")?;
+        user.denied_columns = stmt
+            .query_map(params![user.id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        return Ok(());
+    }
+
     pub fn new() -> Result<InvManSqlite> {
-        let file = Path::new("./storage");
+        return InvManSqlite::new_at("./storage");
+    }
+
+    pub fn new_at(path: &str) -> Result<InvManSqlite> {
+        if path == ":memory:" {
+            return InvManSqlite::in_memory();
+        }
+        let file = Path::new(path);
         let file_exists = file.exists();
         let mut conn = InvManSqlite {
             db: Connection::open(file.to_str().unwrap_or(""))?,
+            org: Arc::default(),
         };
 
         if !file_exists {
             conn.create_inital_setup()?;
         }
+        conn.migrate()?;
+        conn.configure_connection()?;
+
+        return Ok(conn);
+    }
+
+    pub fn open(path: &str) -> Result<InvManSqlite> {
+        if !Path::new(path).is_file() {
+            bail!("Database '{}' does not exist", path);
+        }
+        let mut conn = InvManSqlite {
+            db: Connection::open(path)?,
+            org: Arc::default(),
+        };
+        conn.migrate()?;
+        conn.configure_connection()?;
+
+        return Ok(conn);
+    }
+
+    pub fn create(path: &str) -> Result<InvManSqlite> {
+        if Path::new(path).exists() {
+            bail!("Database '{}' already exists", path);
+        }
+        let mut conn = InvManSqlite {
+            db: Connection::open(path)?,
+            org: Arc::default(),
+        };
+        conn.create_inital_setup()?;
+        conn.migrate()?;
+        conn.configure_connection()?;
+
+        return Ok(conn);
+    }
+
+    pub fn in_memory() -> Result<InvManSqlite> {
+        let mut conn = InvManSqlite {
+            db: Connection::open_in_memory()?,
+            org: Arc::default(),
+        };
+        conn.create_inital_setup()?;
+        conn.migrate()?;
+        conn.configure_connection()?;
+
+        return Ok(conn);
+    }
+
+    pub fn open_read_only() -> Result<InvManSqlite> {
+        return InvManSqlite::open_read_only_at("./storage");
+    }
+
+    pub fn open_read_only_at(path: &str) -> Result<InvManSqlite> {
+        let file = Path::new(path);
+        if !file.is_file() {
+            bail!("Database does not exist, it cannot be created in read-only mode");
+        }
+        let mut conn = InvManSqlite {
+            db: Connection::open_with_flags(file, OpenFlags::SQLITE_OPEN_READ_ONLY)?,
+            org: Arc::default(),
+        };
+        let version: usize = conn
+            .db
+            .query_row("PRAGMA user_version", (), |row| row.get(0))?;
+        if version != MIGRATIONS.len() + 1 {
+            bail!(
+                "Database at version {} must be migrated, which is not possible in read-only mode",
+                version
+            );
+        }
+        conn.configure_connection()?;
 
         return Ok(conn);
     }
 
+    fn migrate(&mut self) -> Result<()> {
+        // Databases created before versioning was introduced report 0, but contain v0001
+        let version: usize = self
+            .db
+            .query_row("PRAGMA user_version", (), |row| row.get(0))?;
+        let version = version.max(1);
+        if version > MIGRATIONS.len() + 1 {
+            bail!(
+                "Database is at version {} which is newer than supported by this invman",
+                version
+            );
+        }
+
+        let tx = self.db.savepoint()?;
+        for (i, statements) in MIGRATIONS.iter().enumerate().skip(version - 1) {
+            for statement in statements.iter() {
+                tx.execute(statement, ())?;
+            }
+            tx.pragma_update(None, "user_version", i + 2)?;
+        }
+        tx.commit()?;
+        return Ok(());
+    }
+
+    fn configure_connection(&mut self) -> Result<()> {
+        self.db.profile(Some(trace_statement));
+        let org = self.org.clone();
+        self.db
+            .create_scalar_function("invman_org", 0, FunctionFlags::SQLITE_UTF8, move |_| {
+                Ok(match org.load(Ordering::Relaxed) {
+                    0 => None,
+                    org => Some(org),
+                })
+            })?;
+        self.db.create_scalar_function(
+            "invman_similarity",
+            2,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let haystack = ctx.get::<Option<String>>(0)?;
+                let needle = ctx.get::<String>(1)?;
+                Ok(haystack.map_or(0.0, |haystack| fuzzy_similarity(&haystack, &needle)))
+            },
+        )?;
+        return Ok(());
+    }
+
     fn create_inital_setup(&mut self) -> Result<()> {
-        let tx = self.db.transaction().unwrap();
+        let tx = self.db.savepoint().unwrap();
         let exec = |content: &str| tx.execute(content, ());
         // Create all the tables
         exec(include_str!("./sql/v0001/create_users_table.sql"))?;
@@ -176,7 +1110,7 @@ impl InvManSqlite {
             .prepare("SELECT COUNT(*) AS count FROM invman_users WHERE deleted_at IS NULL")?;
         let count_iter = stmt.query_map([], |row| Ok(Count { count: row.get(0)? }))?;
 
-        for count in count_iter {
+        if let Some(count) = count_iter.into_iter().next() {
             return Ok(count?.count);
         }
 
@@ -196,13 +1130,13 @@ impl InvManSqlite {
     }
 
     fn make_row_statement(&self, decl: &SchemaDeclaration) -> String {
-        let mut query = format!("{}", decl.name);
+        let mut query = decl.name.to_string();
 
         match decl.column_type {
             ColumnType::BOOL => query.push_str(" VARCHAR(5)"),
             ColumnType::INT => query.push_str(" INTEGER"),
             ColumnType::REAL => query.push_str(" REAL"),
-            ColumnType::TEXT => query.push_str(" TEXT"),
+            ColumnType::TEXT | ColumnType::MONEY => query.push_str(" TEXT"),
             ColumnType::VARCHAR => {
                 query.push_str(" VARCHAR(");
                 query.push_str(decl.max_length.to_string().as_str());
@@ -214,12 +1148,13 @@ impl InvManSqlite {
             query.push_str(" NOT NULL");
         }
 
-        if decl.default != "NULL" {
+        // Defaults of the context are set by the addition, as the database knows neither
+        if decl.default != "NULL" && decl.context_default().is_none() {
             let string;
             let default = match decl.default.as_str() {
                 "CURRENT_TIMESTAMP" => "(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))",
                 s => match decl.column_type {
-                    ColumnType::TEXT | ColumnType::VARCHAR => {
+                    ColumnType::TEXT | ColumnType::VARCHAR | ColumnType::MONEY => {
                         string = format!("'{}'", s);
                         &string
                     }
@@ -260,7 +1195,7 @@ CREATE TABLE invman_temp_inventory(
             )
         };
 
-        let count = declarations.collection.iter().count();
+        let count = declarations.collection.len();
         let mut i = 0;
         declarations
             .collection
@@ -281,73 +1216,682 @@ CREATE TABLE invman_temp_inventory(
         return query;
     }
 
-    fn alter_inventory_table(
-        &mut self,
-        new_schema: &SchemaCollection,
-        old_schema: &SchemaCollection,
-        action_no: &SchemaActionNo,
-        user: &DBUser,
-    ) -> Result<String> {
-        let old_schema_str = serde_json::to_string(&old_schema.collection)?;
-        let new_schema_str = serde_json::to_string(&new_schema.collection)?;
-        let create_inventory_table = self.make_temp_inventory_table(&new_schema);
-        let copy_table = format!(
-            "INSERT INTO invman_temp_inventory({cols}) SELECT {cols} FROM invman_inventory",
-            cols = match action_no {
-                SchemaActionNo::Alter => old_schema.sql_names(),
-                SchemaActionNo::Remove => new_schema.sql_names(),
+    /**
+     * @returns The query, its parameters and, if it has a raw clause, the query without it
+     */
+    fn make_inventory_list_query(
+        &self,
+        props: &InventoryListProps,
+        config: &AppConfig,
+        archived: bool,
+    ) -> (String, Vec<String>, Option<String>) {
+        let mut columns = vec![config.inventory_schema_declaration.sql_names()];
+        // Subqueries instead of joins, so that raw conditions keep unambiguous column names
+        if props.with_audit {
+            columns.push("(SELECT u.username FROM invman_inventory_tx AS t JOIN invman_users AS u ON u.id = t.dispatcher WHERE t.inventory_id = invman_inventory.id ORDER BY t.id DESC LIMIT 1) AS last_modified_by".into());
+            columns.push("(SELECT t.created_at FROM invman_inventory_tx AS t WHERE t.inventory_id = invman_inventory.id ORDER BY t.id DESC LIMIT 1) AS last_modified_at".into());
+            columns.push("(SELECT MAX(t.id) FROM invman_inventory_tx AS t WHERE t.inventory_id = invman_inventory.id) AS revision".into());
+        }
+        let mut params = props.params.clone();
+        let mut changed = None;
+        if let Some(marker) = &props.changed_since {
+            let window = match marker {
+                ChangeMarker::Tx(tx) => {
+                    params.push(tx.to_string());
+                    format!("t.id > ?{}", params.len())
+                }
+                ChangeMarker::Time(time) => {
+                    params.push(time.clone());
+                    format!("t.created_at >= ?{}", params.len())
+                }
+            };
+            columns.push(format!(
+                "CASE WHEN deleted_at IS NOT NULL THEN 'deleted' WHEN EXISTS(SELECT 1 FROM invman_inventory_tx AS t WHERE t.inventory_id = invman_inventory.id AND t.action_no = {} AND {}) THEN 'created' ELSE 'changed' END AS change",
+                DBOpNo::Add as u32,
+                window
+            ));
+            columns.push("(SELECT MAX(t.id) FROM invman_inventory_tx AS t WHERE t.inventory_id = invman_inventory.id) AS revision".into());
+            changed = Some(format!(
+                "id IN (SELECT t.inventory_id FROM invman_inventory_tx AS t WHERE {})",
+                window
+            ));
+        }
+        if props.with_attachments {
+            columns.push("(SELECT JSON_GROUP_ARRAY(JSON_OBJECT('id', a.id, 'filename', a.filename, 'size', a.size)) FROM invman_attachments AS a WHERE a.inventory_id = invman_inventory.id) AS attachments".into());
+        }
+        for expansion in props.expand.iter() {
+            columns.push(match expansion {
+                Expansion::Suppliers => "(SELECT JSON_GROUP_ARRAY(JSON_OBJECT('id', s.id, 'name', s.name, 'contact', s.contact, 'lead_time_days', s.lead_time_days)) FROM invman_inventory_suppliers AS l JOIN invman_suppliers AS s ON s.id = l.supplier_id WHERE l.inventory_id = invman_inventory.id AND s.deleted_at IS NULL) AS suppliers",
+                Expansion::Locations => "(SELECT JSON_GROUP_ARRAY(JSON_OBJECT('location', m.location, 'quantity', m.quantity)) FROM (SELECT location, SUM(quantity) AS quantity FROM invman_stock_movements WHERE inventory_id = invman_inventory.id GROUP BY location HAVING SUM(quantity) != 0 ORDER BY location) AS m) AS locations",
+                Expansion::Category => "(SELECT JSON_OBJECT('id', c.id, 'name', c.name, 'path', c.path) FROM invman_inventory_categories AS ic JOIN invman_categories AS c ON c.id = ic.category_id WHERE ic.inventory_id = invman_inventory.id) AS category",
+                Expansion::Meta => "(SELECT JSON_GROUP_OBJECT(m.key, m.value) FROM invman_inventory_meta AS m WHERE m.inventory_id = invman_inventory.id) AS meta",
+                Expansion::Assignment => "(SELECT JSON_OBJECT('user_id', u.id, 'username', u.username, 'assigned_at', a.assigned_at) FROM invman_assignments AS a JOIN invman_users AS u ON u.id = a.user_id WHERE a.inventory_id = invman_inventory.id AND a.unassigned_at IS NULL ORDER BY a.id DESC LIMIT 1) AS assignment",
+            }.into());
+        }
+        let source = if archived {
+            format!(
+                "(SELECT {cols} FROM main.invman_inventory WHERE {scope} UNION ALL SELECT {cols} FROM archive.invman_inventory WHERE {scope}) AS invman_inventory",
+                cols = config.inventory_schema_declaration.sql_names(),
+                scope = org_scope("id")
+            )
+        } else {
+            format!(
+                "(SELECT * FROM invman_inventory WHERE {}) AS invman_inventory",
+                org_scope("id")
+            )
+        };
+        let mut sql = format!("SELECT {} FROM {}", columns.join(", "), source);
+        let mut base = None;
+        match props.raw {
+            Some(raw) => {
+                base = Some(sql.clone());
+                sql.push(' ');
+                sql.push_str(raw);
             }
-        );
-
-        let tx = self.db.transaction()?;
-        let exec = |sql: &str| tx.execute(sql, ());
-        exec(&create_inventory_table)?;
-        exec(&copy_table)?;
-        exec("DROP TABLE invman_inventory")?;
-        exec("ALTER TABLE invman_temp_inventory RENAME TO invman_inventory")?;
-        exec(include_str!("./sql/v0001/create_inventory_trigger.sql"))?;
-        tx.execute(
-            "INSERT INTO invman_inventory_schema_tx (dispatcher, action_no, from_val, to_val) VALUES (?1, ?2, ?3, ?4)",
-            params![user.id, *action_no as u32, old_schema_str, new_schema_str],
-        )?;
-        tx.execute(
-            "UPDATE invman_config SET value=?1 WHERE name='inventory_schema_declaration'",
-            [new_schema_str],
+            None => {
+                let mut conditions = vec![];
+                let mut order = None;
+                if let Some(changed) = changed {
+                    conditions.push(changed);
+                    order = Some("revision".into());
+                }
+                if let Some(fuzzy) = &props.fuzzy {
+                    params.push(fuzzy.value.clone().unwrap_or_default());
+                    let similarity = format!("invman_similarity({}, ?{})", fuzzy.key, params.len());
+                    // Bound parameters are text, which would never compare equal to a real score
+                    conditions.push(format!("{} >= {:?}", similarity, props.fuzzy_threshold));
+                    order = Some(format!("{} DESC", similarity));
+                }
+                if let Some(supplier) = props.supplier {
+                    params.push(supplier.to_string());
+                    conditions.push(format!(
+                        "id IN (SELECT inventory_id FROM invman_inventory_suppliers WHERE supplier_id=?{})",
+                        params.len()
+                    ));
+                }
+                if let Some(category) = props.category {
+                    params.push(category.to_string());
+                    conditions.push(if props.recursive {
+                        format!(
+                            "id IN (SELECT inventory_id FROM invman_inventory_categories WHERE category_id IN (WITH RECURSIVE tree(id) AS (SELECT ?{} UNION SELECT c.id FROM invman_categories c INNER JOIN tree ON c.parent_id=tree.id) SELECT id FROM tree))",
+                            params.len()
+                        )
+                    } else {
+                        format!(
+                            "id IN (SELECT inventory_id FROM invman_inventory_categories WHERE category_id=?{})",
+                            params.len()
+                        )
+                    });
+                }
+                if let Some(filter) = &props.filter {
+                    conditions.push(filter.to_sql(&mut params));
+                }
+                if let Some(assigned_to) = props.assigned_to {
+                    params.push(assigned_to.to_string());
+                    conditions.push(format!(
+                        "id IN (SELECT inventory_id FROM invman_assignments WHERE user_id=?{} AND unassigned_at IS NULL)",
+                        params.len()
+                    ));
+                }
+                // Keyset pagination walks the rowid index instead of skipping rows with OFFSET
+                if let Some(after_id) = props.after_id {
+                    params.push(after_id.to_string());
+                    conditions.push(format!("id > ?{}", params.len()));
+                    order = Some("id".into());
+                }
+                if !conditions.is_empty() {
+                    sql.push_str(" WHERE ");
+                    sql.push_str(conditions.join(" AND ").as_str());
+                }
+                if let Some(order) = order {
+                    sql.push_str(" ORDER BY ");
+                    sql.push_str(order.as_str());
+                }
+                if props.limit > 0 && props.group.is_none() {
+                    sql.push_str(" LIMIT ");
+                    sql.push_str(props.limit.to_string().as_str());
+                }
+            }
+        }
+        if let Some(group) = &props.group {
+            sql = group.to_sql(&sql, props.limit);
+            base = base.map(|e| group.to_sql(&e, props.limit));
+        }
+        return (sql, params, base);
+    }
+
+    /**
+     * Prepares a query with a raw clause, which may only read the tables and columns the query
+     * reads without it. SQLite asks the authorizer for every access while preparing, so the
+     * accesses of the query without the clause are recorded first, and any other one of the
+     * clause is denied, e.g. to the password hashes of invman_users.
+     */
+    fn prepare_raw_query(&self, sql: &str, base: &str, raw: &str) -> Result<Statement<'_>> {
+        check_raw_clause(raw)?;
+        let reads = Arc::new(Mutex::new(BTreeSet::new()));
+        let recorded = reads.clone();
+        self.db.authorizer(Some(move |ctx: AuthContext<'_>| {
+            if let AuthAction::Read {
+                table_name,
+                column_name,
+            } = ctx.action
+            {
+                recorded.lock().unwrap_or_else(|e| e.into_inner()).insert((
+                    ctx.database_name.unwrap_or_default().to_string(),
+                    table_name.to_string(),
+                    column_name.to_string(),
+                ));
+            }
+            return Authorization::Allow;
+        }));
+        let prepared = self.db.prepare(base).map(|_| ());
+        self.db.authorizer(Some(move |ctx: AuthContext<'_>| {
+            return match ctx.action {
+                AuthAction::Read {
+                    table_name,
+                    column_name,
+                } => {
+                    let read = (
+                        ctx.database_name.unwrap_or_default().to_string(),
+                        table_name.to_string(),
+                        column_name.to_string(),
+                    );
+                    match reads
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .contains(&read)
+                    {
+                        true => Authorization::Allow,
+                        false => Authorization::Deny,
+                    }
+                }
+                AuthAction::Select | AuthAction::Function { .. } | AuthAction::Recursive => {
+                    Authorization::Allow
+                }
+                _ => Authorization::Deny,
+            };
+        }));
+        let stmt = self.db.prepare(sql);
+        self.db
+            .authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+        prepared?;
+        return match stmt {
+            Ok(stmt) => Ok(stmt),
+            Err(e)
+                if e.sqlite_error_code()
+                    == Some(rusqlite::ErrorCode::AuthorizationForStatementDenied) =>
+            {
+                bail!("Raw SQL may only read the columns of the inventory")
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    /**
+     * The archive file next to the database file, named like it with .archive appended. An
+     * in-memory database has an in-memory archive.
+     */
+    fn archive_path(&self) -> String {
+        return match self.db.path() {
+            Some(path) if !path.is_empty() => format!("{}.archive", path),
+            _ => ":memory:".into(),
+        };
+    }
+
+    /**
+     * Attaches the archive file as schema `archive` and brings its tables up to date with the
+     * inventory schema, so that archived entities can be selected like the inventory's.
+     *
+     * @returns False if there is no archive yet and none should be created
+     */
+    fn prepare_archive(&self, config: &AppConfig, create: bool) -> Result<bool> {
+        let attached: bool = self.db.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pragma_database_list WHERE name='archive')",
+            (),
+            |row| row.get(0),
+        )?;
+        if !attached {
+            let path = self.archive_path();
+            if !create && !Path::new(&path).exists() {
+                return Ok(false);
+            }
+            self.check_outside_batch("Attaching the archive")?;
+            self.db
+                .execute("ATTACH DATABASE ?1 AS archive", params![path])?;
+        }
+        // Archived rows never change again, so the tables go without constraints
+        self.db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS archive.invman_inventory(
+                id INTEGER PRIMARY KEY,
+                created_at TEXT,
+                updated_at TEXT,
+                deleted_at TEXT,
+                archived_at TEXT DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+            );
+            CREATE TABLE IF NOT EXISTS archive.invman_inventory_tx AS SELECT * FROM main.invman_inventory_tx WHERE 0;",
+        )?;
+        let mut stmt = self
+            .db
+            .prepare("SELECT name FROM pragma_table_info('invman_inventory', 'archive')")?;
+        let columns = stmt
+            .query_map((), |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        for decl in &config.inventory_schema_declaration.collection {
+            if columns.contains(&decl.name) {
+                continue;
+            }
+            let column_type = match decl.column_type {
+                ColumnType::BOOL => "VARCHAR(5)",
+                ColumnType::INT => "INTEGER",
+                ColumnType::REAL => "REAL",
+                ColumnType::TEXT | ColumnType::VARCHAR | ColumnType::MONEY => "TEXT",
+            };
+            self.db.execute(
+                format!(
+                    "ALTER TABLE archive.invman_inventory ADD COLUMN {} {}",
+                    decl.name, column_type
+                )
+                .as_str(),
+                (),
+            )?;
+        }
+        return Ok(true);
+    }
+
+    /**
+     * Splits a category path into its trimmed names, e.g. `electronics / laptops` into
+     * `electronics` and `laptops`
+     */
+    fn category_path_names(path: &str) -> Result<Vec<&str>> {
+        let names = path.split('/').map(|e| e.trim()).collect::<Vec<&str>>();
+        if names.iter().any(|e| e.is_empty()) {
+            bail!("Invalid category path '{}', names must not be empty", path);
+        }
+        return Ok(names);
+    }
+
+    fn kit_id(&self, name: &str) -> Result<u32> {
+        let id = self
+            .db
+            .query_row(
+                "SELECT id FROM invman_kits WHERE name=?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        return match id {
+            Some(id) => Ok(id),
+            None => bail!("No kit {} found", name),
+        };
+    }
+
+    fn kit_items(&self, kit_id: u32) -> Result<Vec<DBKitItem>> {
+        let mut stmt = self.db.prepare(
+            "SELECT inventory_id, quantity FROM invman_kit_items WHERE kit_id=?1 ORDER BY inventory_id",
+        )?;
+        let rows = stmt.query_map(params![kit_id], |row| {
+            Ok(DBKitItem {
+                inventory_id: row.get(0)?,
+                quantity: row.get(1)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBKitItem>>>()?);
+    }
+
+    fn supplier_exists(&self, identifier: &str) -> Result<bool> {
+        let exists = self.db.query_row(
+            "SELECT EXISTS(SELECT 1 FROM invman_suppliers WHERE id=?1 AND deleted_at IS NULL)",
+            params![identifier],
+            |row| row.get(0),
+        )?;
+        return Ok(exists);
+    }
+
+    fn alter_inventory_table(
+        &mut self,
+        new_schema: &SchemaCollection,
+        old_schema: &SchemaCollection,
+        action_no: &SchemaActionNo,
+        user: &DBUser,
+    ) -> Result<String> {
+        let old_schema_str = serde_json::to_string(&old_schema.collection)?;
+        let new_schema_str = serde_json::to_string(&new_schema.collection)?;
+        let create_inventory_table = self.make_temp_inventory_table(new_schema);
+        self.check_outside_batch("Rebuilding the inventory table")?;
+        let copy_table = format!(
+            "INSERT INTO invman_temp_inventory({cols}) SELECT {cols} FROM invman_inventory",
+            cols = match action_no {
+                SchemaActionNo::Alter => old_schema.sql_names(),
+                SchemaActionNo::Remove => new_schema.sql_names(),
+            }
+        );
+
+        let owner = self.acquire_lock("inventory_table")?;
+        // Another invman may have altered the schema since the config was read
+        let current_schema: String = self.db.query_row(
+            "SELECT value FROM invman_config WHERE name='inventory_schema_declaration'",
+            (),
+            |row| row.get(0),
+        )?;
+        if serde_json::from_str::<serde_json::Value>(&current_schema)?
+            != serde_json::from_str::<serde_json::Value>(&old_schema_str)?
+        {
+            self.release_lock("inventory_table", &owner)?;
+            bail!(
+                "The schema was changed by another invman in the meantime, run the command again"
+            );
+        }
+
+        // Other tables reference the inventory, so the checks are deferred until the table is
+        // rebuilt
+        let result = self.without_foreign_keys(|db| {
+            db.rebuild_inventory_table(
+                &create_inventory_table,
+                &copy_table,
+                action_no,
+                &old_schema_str,
+                &new_schema_str,
+                user,
+            )
+        });
+        self.release_lock("inventory_table", &owner)?;
+        return result;
+    }
+
+    /**
+     * Runs the operation with foreign keys turned off and restores the previous setting
+     * afterwards, e.g. to rebuild a table other tables reference. The pragma has no effect
+     * within a transaction and is thus set around the operation.
+     */
+    fn without_foreign_keys<T>(
+        &mut self,
+        operation: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let enabled: bool = self
+            .db
+            .query_row("PRAGMA foreign_keys", (), |row| row.get(0))?;
+        self.db.execute_batch("PRAGMA foreign_keys=OFF")?;
+        let result = operation(self);
+        self.db.pragma_update(None, "foreign_keys", enabled)?;
+        return result;
+    }
+
+    /**
+     * Fails within a batch for operations that need a connection without a transaction, e.g.
+     * to turn off foreign keys or to attach a database.
+     */
+    fn check_outside_batch(&self, operation: &str) -> Result<()> {
+        if !self.db.is_autocommit() {
+            bail!("{} cannot be part of a batch", operation);
+        }
+        return Ok(());
+    }
+
+    /**
+     * Takes the advisory lock of the given name, so that no other invman runs the same
+     * operation, e.g. rebuilding a table, at the same time. Each acquisition is owned by a
+     * random token instead of the process, which neither tells apart the connections of one
+     * process, e.g. the workers of a server, nor is unique across PID namespaces.
+     *
+     * @returns The token of the owner, which releases the lock
+     */
+    fn acquire_lock(&mut self, name: &str) -> Result<String> {
+        // A single statement takes the lock atomically, so that it also works within the
+        // transaction of a batch
+        let owner = self
+            .db
+            .query_row(
+                "INSERT INTO invman_locks (name, owner) VALUES (?1, LOWER(HEX(RANDOMBLOB(16)))) ON CONFLICT(name) DO UPDATE SET owner=excluded.owner, acquired_at=excluded.acquired_at WHERE acquired_at < STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW', '-' || ?2 || ' minutes') RETURNING owner",
+                params![name, STALE_LOCK_MINUTES],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        return match owner {
+            Some(owner) => Ok(owner),
+            None => {
+                let acquired_at: String = self.db.query_row(
+                    "SELECT acquired_at FROM invman_locks WHERE name=?1",
+                    params![name],
+                    |row| row.get(0),
+                )?;
+                bail!(
+                    "Another invman is running since {}, try again once it has finished",
+                    acquired_at
+                )
+            }
+        };
+    }
+
+    fn release_lock(&mut self, name: &str, owner: &str) -> Result<()> {
+        self.db.execute(
+            "DELETE FROM invman_locks WHERE name=?1 AND owner=?2",
+            params![name, owner],
+        )?;
+        return Ok(());
+    }
+
+    fn rebuild_inventory_table(
+        &mut self,
+        create_inventory_table: &str,
+        copy_table: &str,
+        action_no: &SchemaActionNo,
+        old_schema_str: &str,
+        new_schema_str: &str,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let exec = |sql: &str| tx.execute(sql, ());
+        exec(create_inventory_table)?;
+        // Large tables are copied in id ranges, so that the progress can be shown
+        let (rows, max_id): (u64, u64) = tx.query_row(
+            "SELECT COUNT(*), IFNULL(MAX(id), 0) FROM invman_inventory",
+            (),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let mut progress = (rows > REBUILD_CHUNK_ROWS)
+            .then(|| Progress::start("rebuild", "Rebuilding inventory table", Some(rows)));
+        let mut from = 0;
+        while from < max_id {
+            let to = from + REBUILD_CHUNK_ROWS;
+            let copied = tx.execute(
+                format!("{} WHERE id > ?1 AND id <= ?2", copy_table).as_str(),
+                params![from, to],
+            )?;
+            if let Some(progress) = progress.as_mut() {
+                progress.advance(copied as u64);
+            }
+            from = to;
+        }
+        drop(progress);
+        exec("DROP TABLE invman_inventory")?;
+        exec("ALTER TABLE invman_temp_inventory RENAME TO invman_inventory")?;
+        exec(include_str!("./sql/v0001/create_inventory_trigger.sql"))?;
+        // Only the references to the inventory are checked, those of other tables did not change
+        for (table, _) in inventory_references(&tx)? {
+            if tx
+                .prepare(
+                    "SELECT 1 FROM pragma_foreign_key_check(?1) WHERE parent='invman_inventory'",
+                )?
+                .exists([table])?
+            {
+                bail!("Altering the inventory table would break references to its entities");
+            }
+        }
+        tx.execute(
+            "INSERT INTO invman_inventory_schema_tx (dispatcher, action_no, from_val, to_val) VALUES (?1, ?2, ?3, ?4)",
+            params![user.id, *action_no as u32, old_schema_str, new_schema_str],
+        )?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='inventory_schema_declaration'",
+            [new_schema_str],
         )?;
         tx.commit()?;
         return Ok("Altered invman_inventory table".into());
     }
+
+    fn load_tables(&mut self, dump: &DBDump, create_inventory_table: &str) -> Result<()> {
+        let tx = self.db.savepoint()?;
+        let tables = table_names(&tx)?;
+        if let Some(table) = dump.tables.keys().find(|e| !tables.contains(e)) {
+            bail!("Dump contains the unknown table {}", table);
+        }
+        for table in &tables {
+            tx.execute(format!("DELETE FROM {}", table).as_str(), ())?;
+        }
+        tx.execute(create_inventory_table, ())?;
+        tx.execute("DROP TABLE invman_inventory", ())?;
+        tx.execute(
+            "ALTER TABLE invman_temp_inventory RENAME TO invman_inventory",
+            (),
+        )?;
+        tx.execute(include_str!("./sql/v0001/create_inventory_trigger.sql"), ())?;
+
+        // Registering users records events, which are replaced by the dumped ones afterwards
+        let events = "invman_event_tx".to_string();
+        for (table, rows) in dump
+            .tables
+            .iter()
+            .filter(|(e, _)| **e != events)
+            .chain(dump.tables.get_key_value(&events))
+        {
+            if *table == events {
+                tx.execute("DELETE FROM invman_event_tx", ())?;
+            }
+            let columns = {
+                let mut stmt = tx.prepare("SELECT name FROM pragma_table_info(?1)")?;
+                let columns = stmt
+                    .query_map([table], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()?;
+                columns
+            };
+            for row in rows {
+                if let Some(column) = row.keys().find(|e| !columns.contains(e)) {
+                    bail!("Dump contains the unknown column {} in {}", column, table);
+                }
+                let sql = format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    table,
+                    row.keys().cloned().collect::<Vec<String>>().join(", "),
+                    vec!["?"; row.len()].join(", ")
+                );
+                let values = row
+                    .values()
+                    .map(json_to_value)
+                    .collect::<Result<Vec<Value>>>()?;
+                tx.execute(sql.as_str(), params_from_iter(values.iter()))?;
+            }
+        }
+        if tx.prepare("PRAGMA foreign_key_check")?.exists([])? {
+            bail!("Dump contains rows with missing references");
+        }
+        tx.commit()?;
+        return Ok(());
+    }
+
+    /**
+     * Registers the users (username, role id, password hash) of a fixture and lets the first
+     * one extend the schema and add the entities.
+     */
+    fn load_fixture(
+        &mut self,
+        accounts: &[(&str, u32, String)],
+        old_schema: &SchemaCollection,
+        new_schema: &SchemaCollection,
+        entities: &[KeyValueCollection],
+        config: &mut AppConfig,
+    ) -> Result<()> {
+        let mut dispatcher = DBUser::default();
+        for (username, role_id, password_hash) in accounts {
+            self.db.execute(
+                "INSERT INTO invman_users (username, role_id, password, password_changed_at) VALUES (?1, ?2, ?3, STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))",
+                params![username, role_id, password_hash],
+            )?;
+            if dispatcher.id == 0 {
+                dispatcher.id = self.db.last_insert_rowid() as u32;
+            }
+        }
+        self.load_permissions(&mut dispatcher)?;
+        if new_schema.collection.len() > old_schema.collection.len() {
+            self.rebuild_inventory_table(
+                &self.make_temp_inventory_table(new_schema),
+                &format!(
+                    "INSERT INTO invman_temp_inventory({cols}) SELECT {cols} FROM invman_inventory",
+                    cols = old_schema.sql_names()
+                ),
+                &SchemaActionNo::Alter,
+                &serde_json::to_string(&old_schema.collection)?,
+                &serde_json::to_string(&new_schema.collection)?,
+                &dispatcher,
+            )?;
+            config.inventory_schema_declaration = new_schema.clone();
+        }
+        for entity in entities {
+            self.inventory_add(entity, config, &dispatcher)?;
+        }
+        return Ok(());
+    }
 }
 
 impl InvManDBPool for InvManSqlite {
-    fn get_config(&self) -> AppConfig {
-        let mut stmt = self
-            .db
-            .prepare("SELECT name, value FROM invman_config")
-            .unwrap();
-        let config_iter = stmt
-            .query_map([], |row| {
-                Ok(Config {
-                    name: row.get(0).unwrap(),
-                    value: row.get(1).unwrap(),
-                })
+    fn get_config(&self) -> Result<AppConfig> {
+        let mut stmt = self.db.prepare("SELECT name, value FROM invman_config")?;
+        let config_iter = stmt.query_map([], |row| {
+            Ok(Config {
+                name: row.get(0)?,
+                value: row.get(1)?,
             })
-            .unwrap();
+        })?;
         let mut app_config = AppConfig::default();
         for config in config_iter {
-            let config = config.unwrap();
+            let config = config?;
             match config.name.as_str() {
                 "allow_registration" => {
                     app_config.allow_registration = config.value == "true";
                 }
+                "read_only" => {
+                    app_config.read_only = config.value == "true";
+                }
                 "inventory_schema_declaration" => {
                     app_config.inventory_schema_declaration =
-                        SchemaCollection::new(serde_json::from_str(config.value.as_str()).unwrap());
+                        SchemaCollection::new(serde_json::from_str(config.value.as_str())?);
+                }
+                "retention_rules" => {
+                    app_config.retention_rules = serde_json::from_str(config.value.as_str())?;
+                }
+                "smtp" => {
+                    app_config.smtp = serde_json::from_str(config.value.as_str())?;
+                }
+                "scheduler_jobs" => {
+                    app_config.scheduler_jobs = serde_json::from_str(config.value.as_str())?;
+                }
+                "hooks" => {
+                    app_config.hooks = serde_json::from_str(config.value.as_str())?;
+                }
+                "timezone" => {
+                    app_config.timezone = config.value;
+                }
+                "display_format" => {
+                    app_config.display_format = serde_json::from_str(config.value.as_str())?;
+                }
+                "label_printer" => {
+                    app_config.label_printer = serde_json::from_str(config.value.as_str())?;
+                }
+                "exchange_rates" => {
+                    app_config.exchange_rates = serde_json::from_str(config.value.as_str())?;
+                }
+                "reason_codes" => {
+                    app_config.reason_codes = serde_json::from_str(config.value.as_str())?;
+                }
+                "ldap" => {
+                    app_config.ldap = serde_json::from_str(config.value.as_str())?;
+                }
+                "password_policy" => {
+                    app_config.password_policy = serde_json::from_str(config.value.as_str())?;
                 }
                 _ => continue,
             }
         }
-        return app_config;
+        return Ok(app_config);
     }
 
     fn user_register(&mut self, username: &str, password: &str) -> Result<String> {
@@ -361,9 +1905,9 @@ impl InvManDBPool for InvManSqlite {
             .hash_password(password.as_bytes(), &salt)?
             .to_string();
 
-        let tx = self.db.transaction()?;
+        let tx = self.db.savepoint()?;
         tx.execute(
-            "INSERT INTO invman_users (username, role_id, password) VALUES (?1, ?2, ?3)",
+            "INSERT INTO invman_users (username, role_id, password, password_changed_at) VALUES (?1, ?2, ?3, STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))",
             (username, role_id, password_hash),
         )?;
         tx.commit()?;
@@ -371,7 +1915,56 @@ impl InvManDBPool for InvManSqlite {
         Ok("Successfully registered new user".into())
     }
 
-    fn user_auth(&self, username: &str, password: &str, user: &mut DBUser) -> Result<()> {
+    fn init(
+        &mut self,
+        username: &str,
+        password: &str,
+        allow_registration: bool,
+        config: &mut AppConfig,
+    ) -> Result<String> {
+        if self.user_count()? > 0 {
+            bail!("The database is already initialized, it has users");
+        }
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)?
+            .to_string();
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "INSERT INTO invman_users (username, role_id, password, password_changed_at) VALUES (?1, 1, ?2, STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))",
+            params![username, password_hash],
+        )?;
+        let admin_id = tx.last_insert_rowid();
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='allow_registration'",
+            params![allow_registration.to_string()],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::RegistrationSet as u32,
+                admin_id,
+                allow_registration.to_string()
+            ],
+        )?;
+        tx.commit()?;
+        config.allow_registration = allow_registration;
+        return Ok(match allow_registration {
+            true => format!("Database was initialized with admin {}", username),
+            false => format!(
+                "Database was initialized with admin {}, registration is disabled",
+                username
+            ),
+        });
+    }
+
+    fn user_auth(
+        &self,
+        username: &str,
+        password: &str,
+        otp: Option<&str>,
+        user: &mut DBUser,
+    ) -> Result<()> {
         let mut stmt = self.db.prepare(
             "SELECT id, password FROM invman_users WHERE username=?1 AND deleted_at IS NULL",
         )?;
@@ -387,107 +1980,722 @@ impl InvManDBPool for InvManSqlite {
             };
         }
         if fetched_user.id == 0 || fetched_user.password.is_empty() {
-            bail!("Either username or password is incorrect");
+            // A local password takes precedence, so that an admin can log in while the
+            // directory is unreachable
+            fetched_user.id = self.provider_auth(username, password)?;
+        } else {
+            let parsed_hash = PasswordHash::new(&fetched_user.password)?;
+            if Argon2::default()
+                .verify_password(password.as_bytes(), &parsed_hash)
+                .is_err()
+            {
+                bail!("Either username or password is incorrect");
+            }
+            let policy: PasswordPolicy = self.config_value("password_policy")?;
+            user.password_expired = self.db.query_row(
+                "SELECT must_change_password OR (?2 > 0 AND password_changed_at < STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW', '-' || ?2 || ' days')) FROM invman_users WHERE id=?1",
+                params![fetched_user.id, policy.max_age_days],
+                |row| row.get(0),
+            )?;
         }
-        let parsed_hash = PasswordHash::new(&fetched_user.password)?;
-        if !Argon2::default()
-            .verify_password(password.as_bytes(), &parsed_hash)
-            .is_ok()
-        {
-            bail!("Either username or password is incorrect");
+        let secret: Option<String> = self.db.query_row(
+            "SELECT totp_secret FROM invman_users WHERE id=?1",
+            params![fetched_user.id],
+            |row| row.get(0),
+        )?;
+        if let Some(secret) = secret {
+            self.verify_otp(fetched_user.id, &secret, otp)?;
         }
 
         // Store the ID of the fetched user for usage in other areas of the program
         user.id = fetched_user.id;
-        let mut stmt = self.db.prepare("SELECT p.name FROM invman_users AS u JOIN invman_roles_permissions AS up ON up.role_id = u.role_id JOIN invman_permissions AS p ON p.id = up.permission_id WHERE u.id=?1")?;
-        let rows = stmt.query_map(params![user.id], |row| {
-            Ok(row.get::<usize, String>(0)?.to_owned())
-        })?;
-        user.permissions = DBPermissionCollection::new(rows.map(|e| e.unwrap()).collect());
-        return Ok(());
+        return self.load_permissions(user);
     }
 
-    fn schema_alter(
+    fn user_totp_enable(
         &mut self,
-        config: &mut AppConfig,
-        decl: SchemaDeclaration,
+        secret: &str,
+        recovery_codes: &[String],
         user: &DBUser,
     ) -> Result<String> {
-        let old_schema = config.inventory_schema_declaration.clone();
-        if let Some(idx) = config.inventory_schema_declaration.contains(&decl) {
-            let mut schema_declaration = config.inventory_schema_declaration.collection.clone();
-            schema_declaration.remove(idx);
-            config.inventory_schema_declaration.collection = schema_declaration;
+        let tx = self.db.savepoint()?;
+        let enabled = tx.execute(
+            "UPDATE invman_users SET totp_secret=?1 WHERE id=?2 AND totp_secret IS NULL",
+            params![secret, user.id],
+        )?;
+        if enabled == 0 {
+            bail!("Two-factor authentication is already enabled, disable it first");
         }
-        config.inventory_schema_declaration.collection.push(decl);
-        self.alter_inventory_table(
-            &config.inventory_schema_declaration,
-            &old_schema,
-            &SchemaActionNo::Alter,
-            user,
+        tx.execute(
+            "DELETE FROM invman_recovery_codes WHERE user_id=?1",
+            params![user.id],
         )?;
-        Ok("Altered schema".into())
+        for code in recovery_codes {
+            tx.execute(
+                "INSERT INTO invman_recovery_codes (user_id, code_hash) VALUES (?1, ?2)",
+                params![user.id, recovery_code_hash(code)],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher) VALUES (?1, ?2)",
+            params![EventActionNo::UserTotpEnable as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok("Two-factor authentication was successfully enabled".into());
     }
 
-    fn schema_remove(
-        &mut self,
-        config: &mut AppConfig,
-        name: &str,
-        user: &DBUser,
-    ) -> Result<String> {
-        let old_schema = config.inventory_schema_declaration.clone();
-        let id = config
-            .inventory_schema_declaration
-            .collection
-            .iter()
-            .position(|e| e.name == name);
-        if !id.is_some() {
-            bail!("The name attribute provided did not match any schema column definition");
+    fn user_totp_disable(&mut self, username: Option<&str>, user: &DBUser) -> Result<String> {
+        let user_id = match username {
+            Some(username) => self.user_id(username)?,
+            None => user.id,
+        };
+        let tx = self.db.savepoint()?;
+        let disabled = tx.execute(
+            "UPDATE invman_users SET totp_secret=NULL WHERE id=?1 AND totp_secret IS NOT NULL",
+            params![user_id],
+        )?;
+        if disabled == 0 {
+            bail!("Two-factor authentication is not enabled");
         }
-        let id = id.unwrap();
-        config.inventory_schema_declaration.collection.remove(id);
-        self.alter_inventory_table(
-            &config.inventory_schema_declaration,
-            &old_schema,
-            &SchemaActionNo::Remove,
-            user,
+        tx.execute(
+            "DELETE FROM invman_recovery_codes WHERE user_id=?1",
+            params![user_id],
         )?;
-        Ok("Removed schema column".into())
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::UserTotpDisable as u32, user.id, user_id],
+        )?;
+        tx.commit()?;
+        return Ok("Two-factor authentication was successfully disabled".into());
     }
 
-    fn inventory_add(
+    fn user_edit(
         &mut self,
-        params: &KeyValueCollection,
+        username: Option<&str>,
+        password: Option<&str>,
+        must_change_password: bool,
         config: &AppConfig,
         user: &DBUser,
     ) -> Result<String> {
-        let values = params.sql_values();
-        let sql = format!(
-            "INSERT INTO invman_inventory ({}) VALUES ({})",
+        let user_id = match username {
+            Some(username) => self.user_id(username)?,
+            None => user.id,
+        };
+        let hash: String = self.db.query_row(
+            "SELECT password FROM invman_users WHERE id=?1",
+            params![user_id],
+            |row| row.get(0),
+        )?;
+        if hash.is_empty() {
+            bail!("The user is authenticated by the directory, its password is changed there");
+        }
+        let tx = self.db.savepoint()?;
+        let Some(password) = password else {
+            tx.execute(
+                "UPDATE invman_users SET must_change_password=1 WHERE id=?1",
+                params![user_id],
+            )?;
+            tx.execute(
+                "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+                params![EventActionNo::UserPasswordExpire as u32, user.id, user_id],
+            )?;
+            tx.commit()?;
+            return Ok("The user has to change the password when logging in next".into());
+        };
+        config.password_policy.check(password)?;
+        let argon2 = Argon2::default();
+        if argon2
+            .verify_password(password.as_bytes(), &PasswordHash::new(&hash)?)
+            .is_ok()
+        {
+            bail!("The new password must differ from the current one");
+        }
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = argon2
+            .hash_password(password.as_bytes(), &salt)?
+            .to_string();
+        // A password an admin sets can be one to change right away, e.g. a temporary one
+        tx.execute(
+            "UPDATE invman_users SET password=?1, password_changed_at=STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'), must_change_password=?2, updated_at=STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW') WHERE id=?3",
+            params![password_hash, must_change_password, user_id],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::UserPasswordChange as u32, user.id, user_id],
+        )?;
+        tx.commit()?;
+        return Ok("Password was successfully changed".into());
+    }
+
+    fn token_auth(&self, token: &str, user: &mut DBUser) -> Result<()> {
+        let claims = TokenClaims::decode(token, &self.token_secret()?)?;
+        let token = self
+            .db
+            .query_row(
+                "SELECT t.user_id, t.scopes FROM invman_tokens AS t JOIN invman_users AS u ON u.id=t.user_id WHERE t.id=?1 AND t.revoked_at IS NULL AND t.expires_at > UNIXEPOCH() AND u.deleted_at IS NULL",
+                params![claims.jti],
+                |row| Ok((row.get::<_, u32>(0)?, row.get::<_, Option<String>>(1)?)),
+            )
+            .optional()?;
+        let scopes = match token {
+            Some((user_id, scopes)) => {
+                user.id = user_id;
+                scopes
+            }
+            None => bail!("Token is expired or was revoked"),
+        };
+        user.scopes = match scopes {
+            Some(scopes) => serde_json::from_str(&scopes)?,
+            None => vec![],
+        };
+        return self.load_permissions(user);
+    }
+
+    fn token_secret(&self) -> Result<String> {
+        return Ok(self.db.query_row(
+            "SELECT value FROM invman_config WHERE name='token_secret'",
+            (),
+            |row| row.get(0),
+        )?);
+    }
+
+    fn token_add(&mut self, expires_at: i64, scopes: &[String], user: &DBUser) -> Result<String> {
+        let scopes = match scopes.is_empty() {
+            true => None,
+            false => Some(serde_json::to_string(scopes)?),
+        };
+        let tx = self.db.savepoint()?;
+        let id: String = tx.query_row(
+            "INSERT INTO invman_tokens (id, user_id, expires_at, scopes) VALUES (LOWER(HEX(RANDOMBLOB(16))), ?1, ?2, ?3) RETURNING id",
+            params![user.id, expires_at, scopes],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::TokenIssue as u32, user.id, id],
+        )?;
+        tx.commit()?;
+        return Ok(id);
+    }
+
+    fn token_list(&self, user: &DBUser) -> Result<Vec<DBToken>> {
+        let mut stmt = self.db.prepare(
+            "SELECT id, DATETIME(expires_at, 'unixepoch'), scopes, revoked_at, created_at FROM invman_tokens WHERE user_id=?1 ORDER BY created_at",
+        )?;
+        let tokens = stmt
+            .query_map(params![user.id], |row| {
+                Ok((
+                    DBToken {
+                        id: row.get(0)?,
+                        expires_at: row.get(1)?,
+                        scopes: vec![],
+                        revoked_at: row.get(3)?,
+                        created_at: row.get(4)?,
+                    },
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<(DBToken, Option<String>)>>>()?;
+        return tokens
+            .into_iter()
+            .map(|(mut token, scopes)| {
+                if let Some(scopes) = scopes {
+                    token.scopes = serde_json::from_str(&scopes)?;
+                }
+                Ok(token)
+            })
+            .collect();
+    }
+
+    fn token_revoke(&mut self, token_id: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        // Users revoke their own tokens, those allowed to manage users revoke any token
+        let revoked = tx.execute(
+            "UPDATE invman_tokens SET revoked_at=STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW') WHERE id=?1 AND revoked_at IS NULL AND (user_id=?2 OR ?3)",
+            params![token_id, user.id, user.can_write_table("users")],
+        )?;
+        if revoked == 0 {
+            bail!("Token '{}' does not exist or was already revoked", token_id);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::TokenRevoke as u32, user.id, token_id],
+        )?;
+        tx.commit()?;
+        return Ok("Token was successfully revoked".into());
+    }
+
+    fn user_id(&self, username: &str) -> Result<u32> {
+        let id = self
+            .db
+            .query_row(
+                "SELECT id FROM invman_users WHERE username=?1 AND deleted_at IS NULL",
+                params![username],
+                |row| row.get(0),
+            )
+            .optional()?;
+        return match id {
+            Some(id) => Ok(id),
+            None => bail!("No user named {} found", username),
+        };
+    }
+
+    fn user_purge(
+        &mut self,
+        username: &str,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<DBPurgeReport> {
+        if username == PURGED_USERNAME {
+            bail!("The tombstone of purged users cannot be purged itself");
+        }
+        let purged_id = self
+            .db
+            .query_row(
+                "SELECT id FROM invman_users WHERE username=?1",
+                params![username],
+                |row| row.get::<_, u32>(0),
+            )
+            .optional()?;
+        let purged_id = match purged_id {
+            Some(purged_id) => purged_id,
+            None => bail!("No user named {} found", username),
+        };
+        if purged_id == user.id {
+            bail!("Cannot purge the user you are authenticated as");
+        }
+        // The archive has to be attached outside of the transaction
+        let archived = self.prepare_archive(config, false)?;
+        let tx = self.db.savepoint()?;
+        let tombstone_id = tx
+            .query_row(
+                "SELECT id FROM invman_users WHERE username=?1",
+                params![PURGED_USERNAME],
+                |row| row.get::<_, u32>(0),
+            )
+            .optional()?;
+        let tombstone_id = match tombstone_id {
+            Some(tombstone_id) => tombstone_id,
+            None => {
+                // Without a password hash and deleted, nobody can log in as the tombstone
+                tx.execute(
+                    "INSERT INTO invman_users (username, role_id, password, deleted_at) VALUES (?1, 2, '', STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))",
+                    params![PURGED_USERNAME],
+                )?;
+                tx.last_insert_rowid() as u32
+            }
+        };
+        let mut references = vec![
+            ("invman_event_tx", "dispatcher"),
+            ("invman_inventory_tx", "dispatcher"),
+            ("invman_inventory_schema_tx", "dispatcher"),
+            ("invman_loans", "dispatcher"),
+            ("invman_attachments", "dispatcher"),
+            ("invman_images", "dispatcher"),
+            ("invman_comments", "author"),
+            ("invman_stock_movements", "dispatcher"),
+            ("invman_serials_tx", "dispatcher"),
+            ("invman_maintenance_tx", "dispatcher"),
+            ("invman_assignments", "user_id"),
+            ("invman_assignments", "dispatcher"),
+            ("invman_inventory_owners", "user_id"),
+            ("invman_stocktakes", "started_by"),
+            ("invman_stocktakes", "closed_by"),
+            ("invman_stocktake_counts", "dispatcher"),
+        ];
+        if archived {
+            references.push(("archive.invman_inventory_tx", "dispatcher"));
+        }
+        let mut anonymized = BTreeMap::new();
+        for (table, column) in references {
+            let changed = tx.execute(
+                format!("UPDATE {} SET {col}=?1 WHERE {col}=?2", table, col = column).as_str(),
+                params![tombstone_id, purged_id],
+            )?;
+            if changed > 0 {
+                *anonymized.entry(table.to_string()).or_default() += changed;
+            }
+        }
+        // Invitations into organizations name the invited user
+        let invitation = format!("{} into ", username);
+        let changed = tx.execute(
+            "UPDATE invman_event_tx SET reason=?1 || SUBSTR(reason, LENGTH(?2) + 1) WHERE action_no=?3 AND SUBSTR(reason, 1, LENGTH(?2))=?2",
+            params![
+                format!("{} into ", PURGED_USERNAME),
+                invitation,
+                EventActionNo::OrgInvite as u32
+            ],
+        )?;
+        if changed > 0 {
+            *anonymized.entry("invman_event_tx".to_string()).or_default() += changed;
+        }
+        let deleted_tokens = tx.execute(
+            "DELETE FROM invman_tokens WHERE user_id=?1",
+            params![purged_id],
+        )?;
+        tx.execute(
+            "DELETE FROM invman_recovery_codes WHERE user_id=?1",
+            params![purged_id],
+        )?;
+        tx.execute("DELETE FROM invman_users WHERE id=?1", params![purged_id])?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::UserPurge as u32, user.id, purged_id],
+        )?;
+        let purged_at = tx.query_row("SELECT STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')", (), |row| {
+            row.get(0)
+        })?;
+        tx.commit()?;
+        return Ok(DBPurgeReport {
+            user_id: purged_id,
+            tombstone_id,
+            anonymized,
+            deleted_tokens,
+            purged_at,
+        });
+    }
+
+    fn scope_to_org(&self, org_id: Option<u32>) {
+        self.org.store(org_id.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    fn org_create(&mut self, name: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let exists = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM invman_orgs WHERE name=?1)",
+            params![name],
+            |row| row.get::<_, bool>(0),
+        )?;
+        if exists {
+            bail!("Organization '{}' already exists", name);
+        }
+        let id: u32 = tx.query_row(
+            "INSERT INTO invman_orgs (name) VALUES (?1) RETURNING id",
+            params![name],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::OrgCreate as u32, user.id, name],
+        )?;
+        tx.commit()?;
+        return Ok(format!(
+            "Organization '{}' was created with id {}",
+            name, id
+        ));
+    }
+
+    fn org_invite(&mut self, org: &str, username: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let org_id = tx
+            .query_row(
+                "SELECT id FROM invman_orgs WHERE name=?1",
+                params![org],
+                |row| row.get::<_, u32>(0),
+            )
+            .optional()?;
+        let org_id = match org_id {
+            Some(org_id) => org_id,
+            None => bail!("No organization named '{}' found", org),
+        };
+        if user.org_id.is_some_and(|e| e != org_id) {
+            bail!("Users can only be invited into your own organization");
+        }
+        let invited = tx.execute(
+            "UPDATE invman_users SET org_id=?1 WHERE username=?2 AND deleted_at IS NULL",
+            params![org_id, username],
+        )?;
+        if invited == 0 {
+            bail!("No user named {} found", username);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::OrgInvite as u32,
+                user.id,
+                format!("{} into {}", username, org)
+            ],
+        )?;
+        tx.commit()?;
+        return Ok(format!(
+            "User {} is now a member of organization '{}'",
+            username, org
+        ));
+    }
+
+    fn org_list(&self) -> Result<Vec<DBOrg>> {
+        let mut stmt = self.db.prepare(
+            "SELECT o.id, o.name, (SELECT COUNT(*) FROM invman_users AS u WHERE u.org_id = o.id AND u.deleted_at IS NULL), (SELECT COUNT(*) FROM invman_org_items AS i WHERE i.org_id = o.id), o.created_at FROM invman_orgs AS o WHERE invman_org() IS NULL OR o.id = invman_org() ORDER BY o.name",
+        )?;
+        let orgs = stmt
+            .query_map((), |row| {
+                Ok(DBOrg {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    members: row.get(2)?,
+                    entities: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<DBOrg>, rusqlite::Error>>()?;
+        return Ok(orgs);
+    }
+
+    fn role_list(&self) -> Result<Vec<DBRole>> {
+        let mut stmt = self.db.prepare(
+            "SELECT r.id, r.name, r.display_name, r.ownership, (SELECT GROUP_CONCAT(d.column_name || '.' || d.mode) FROM invman_roles_denied_columns AS d WHERE d.role_id = r.id) FROM invman_roles AS r WHERE r.deleted_at IS NULL ORDER BY r.id",
+        )?;
+        let mut rows = stmt.query(())?;
+        let mut roles = vec![];
+        while let Some(row) = rows.next()? {
+            roles.push(DBRole {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                display_name: row.get(2)?,
+                ownership: row.get::<_, String>(3)?.parse()?,
+                denied_columns: row
+                    .get::<_, Option<String>>(4)?
+                    .map(|e| e.split(',').map(String::from).collect())
+                    .unwrap_or_default(),
+            });
+        }
+        return Ok(roles);
+    }
+
+    fn role_set_ownership(
+        &mut self,
+        role: &str,
+        ownership: OwnershipPolicy,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let changed = tx.execute(
+            "UPDATE invman_roles SET ownership=?1 WHERE name=?2 AND deleted_at IS NULL",
+            params![ownership.to_string(), role],
+        )?;
+        if changed == 0 {
+            bail!("No role named {} found", role);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::RoleEdit as u32,
+                user.id,
+                format!("{} ownership {}", role, ownership)
+            ],
+        )?;
+        tx.commit()?;
+        return Ok(match ownership {
+            OwnershipPolicy::All => format!("Users of role {} may now change all entities", role),
+            OwnershipPolicy::Own => format!(
+                "Users of role {} may now only change the entities they added",
+                role
+            ),
+        });
+    }
+
+    fn role_deny_column(
+        &mut self,
+        role: &str,
+        column: &str,
+        mode: PermissionMode,
+        deny: bool,
+        user: &DBUser,
+    ) -> Result<String> {
+        let mode = match mode {
+            PermissionMode::Read => "r",
+            PermissionMode::Write => "w",
+        };
+        let tx = self.db.savepoint()?;
+        let role_id = tx
+            .query_row(
+                "SELECT id FROM invman_roles WHERE name=?1 AND deleted_at IS NULL",
+                params![role],
+                |row| row.get::<_, u32>(0),
+            )
+            .optional()?;
+        let role_id = match role_id {
+            Some(role_id) => role_id,
+            None => bail!("No role named {} found", role),
+        };
+        let changed = if deny {
+            tx.execute(
+                "INSERT OR IGNORE INTO invman_roles_denied_columns (role_id, column_name, mode) VALUES (?1, ?2, ?3)",
+                params![role_id, column, mode],
+            )?
+        } else {
+            tx.execute(
+                "DELETE FROM invman_roles_denied_columns WHERE role_id=?1 AND column_name=?2 AND mode=?3",
+                params![role_id, column, mode],
+            )?
+        };
+        if changed == 0 {
+            bail!(
+                "Column {} is already {} to role {}",
+                column,
+                if deny { "denied" } else { "not denied" },
+                role
+            );
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::RoleEdit as u32,
+                user.id,
+                format!(
+                    "{} {} {}.{}",
+                    role,
+                    if deny { "deny" } else { "allow" },
+                    column,
+                    mode
+                )
+            ],
+        )?;
+        tx.commit()?;
+        return Ok(format!(
+            "Column {} is {} to role {}",
+            column,
+            if deny {
+                "now denied"
+            } else {
+                "no longer denied"
+            },
+            role
+        ));
+    }
+
+    fn inventory_owner(&self, identifier: &str) -> Result<Option<u32>> {
+        return Ok(self
+            .db
+            .query_row(
+                "SELECT user_id FROM invman_inventory_owners WHERE inventory_id=?1",
+                params![identifier],
+                |row| row.get(0),
+            )
+            .optional()?);
+    }
+
+    fn schema_alter(
+        &mut self,
+        config: &mut AppConfig,
+        decl: SchemaDeclaration,
+        user: &DBUser,
+    ) -> Result<String> {
+        let old_schema = config.inventory_schema_declaration.clone();
+        if let Some(idx) = config.inventory_schema_declaration.contains(&decl) {
+            let mut schema_declaration = config.inventory_schema_declaration.collection.clone();
+            schema_declaration.remove(idx);
+            config.inventory_schema_declaration.collection = schema_declaration;
+        }
+        config.inventory_schema_declaration.collection.push(decl);
+        self.alter_inventory_table(
+            &config.inventory_schema_declaration,
+            &old_schema,
+            &SchemaActionNo::Alter,
+            user,
+        )?;
+        Ok("Altered schema".into())
+    }
+
+    fn schema_remove(
+        &mut self,
+        config: &mut AppConfig,
+        name: &str,
+        user: &DBUser,
+    ) -> Result<String> {
+        let old_schema = config.inventory_schema_declaration.clone();
+        let id = config
+            .inventory_schema_declaration
+            .collection
+            .iter()
+            .position(|e| e.name == name);
+        if id.is_none() {
+            bail!("The name attribute provided did not match any schema column definition");
+        }
+        let id = id.unwrap();
+        config.inventory_schema_declaration.collection.remove(id);
+        self.alter_inventory_table(
+            &config.inventory_schema_declaration,
+            &old_schema,
+            &SchemaActionNo::Remove,
+            user,
+        )?;
+        Ok("Removed schema column".into())
+    }
+
+    fn schema_extend(
+        &mut self,
+        config: &mut AppConfig,
+        decls: Vec<SchemaDeclaration>,
+        user: &DBUser,
+    ) -> Result<String> {
+        let old_schema = config.inventory_schema_declaration.clone();
+        for decl in &decls {
+            if config.inventory_schema_declaration.contains(decl).is_some() {
+                bail!("Schema column {} exists already", decl.name);
+            }
+        }
+        let count = decls.len();
+        config.inventory_schema_declaration.collection.extend(decls);
+        self.alter_inventory_table(
+            &config.inventory_schema_declaration,
+            &old_schema,
+            &SchemaActionNo::Alter,
+            user,
+        )?;
+        Ok(format!("Added {} columns to the schema", count))
+    }
+
+    fn inventory_add(
+        &mut self,
+        params: &KeyValueCollection,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        return self.inventory_add_with_meta(params, &BTreeMap::new(), config, user);
+    }
+
+    fn inventory_add_with_meta(
+        &mut self,
+        params: &KeyValueCollection,
+        meta: &BTreeMap<String, String>,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        let values = params.sql_values();
+        let sql = format!(
+            "INSERT INTO invman_inventory ({}) VALUES ({})",
             params.sql_names(),
-            vec!["?"; values.iter().count()].join(",")
+            vec!["?"; values.len()].join(",")
         );
         let select_item_sql = format!(
-            "SELECT id,created_at,updated_at,deleted_at,{} FROM invman_inventory WHERE id=?1",
+            "SELECT {} FROM invman_inventory WHERE id=?1",
             config.inventory_schema_declaration.sql_names(),
         );
-        let tx = self.db.transaction()?;
+        let tx = self.db.savepoint()?;
         let latest_schema = tx.query_row(
             "SELECT MAX(id) FROM invman_inventory_schema_tx",
             (),
             |row| Ok(IdEntry { id: row.get(0)? }),
         )?;
-        tx.execute(&sql, rusqlite::params_from_iter(values))?;
+        let changed = tx.execute(&sql, rusqlite::params_from_iter(values))?;
         let latest_item = tx.query_row("SELECT (LAST_INSERT_ROWID())", (), |row| {
             Ok(IdEntry { id: row.get(0)? })
         })?;
+        tracing::debug!(
+            rows = changed,
+            id = latest_item.id,
+            "Added inventory entity"
+        );
+        tx.execute(
+            "INSERT INTO invman_org_items (inventory_id, org_id) SELECT ?1, invman_org() WHERE invman_org() IS NOT NULL",
+            params![latest_item.id],
+        )?;
         let json = tx
             .query_row(&select_item_sql, params![latest_item.id], |row| {
                 Ok(row
                     .to_typed_key_value(&config.inventory_schema_declaration)
-                    .with_context(|| {
-                        format!("Failed to convert row into typed key value representation")
-                    }))
+                    .with_context(|| "Failed to convert row into typed key value representation"))
             })??
             .to_json();
         tx.execute(
@@ -495,6 +2703,16 @@ impl InvManDBPool for InvManSqlite {
             params![user.id, latest_schema.id, latest_item.id, DBOpNo::Add as u32, json]
         )?;
         tx.execute("INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))", params![EventActionNo::InventoryAdd as u32, user.id])?;
+        for (key, value) in meta {
+            tx.execute(
+                "INSERT INTO invman_inventory_meta (inventory_id, key, value) VALUES (?1, ?2, ?3)",
+                params![latest_item.id, key, value],
+            )?;
+            tx.execute(
+                "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+                params![EventActionNo::MetaSet as u32, user.id, latest_item.id, key],
+            )?;
+        }
         tx.commit()?;
         return Ok("Entity was successfully added to inventory".into());
     }
@@ -504,59 +2722,305 @@ impl InvManDBPool for InvManSqlite {
         props: &InventoryListProps,
         config: &AppConfig,
     ) -> Result<Vec<KeyValueCollection>> {
-        let mut sql = format!(
-            "SELECT id,created_at,updated_at,deleted_at,{} FROM invman_inventory",
-            config.inventory_schema_declaration.sql_names()
-        );
-        match props.raw {
-            Some(raw) => {
-                sql.push(' ');
-                sql.push_str(raw);
-            }
-            None => {
-                if props.limit > 0 {
-                    sql.push_str(" LIMIT ");
-                    sql.push_str(props.limit.to_string().as_str());
-                }
-            }
-        }
-        let mut stmt = self.db.prepare(&sql)?;
-        let entries = stmt.query_map(params_from_iter(props.params), |row| {
-            Ok(row
-                .to_typed_key_value(&config.inventory_schema_declaration)
-                .with_context(|| {
-                    format!("Failed to convert SQLite result into JSON representation")
-                })
-                .unwrap())
+        let mut entries = vec![];
+        self.inventory_list_each(props, config, &mut |entry| {
+            entries.push(entry);
+            Ok(())
         })?;
-        return Ok(entries.map(|e| e.unwrap()).collect());
+        return Ok(entries);
     }
 
-    fn inventory_edit(
-        &mut self,
-        identifier: &String,
-        params: &KeyValueCollection,
+    fn inventory_list_each(
+        &self,
+        props: &InventoryListProps,
         config: &AppConfig,
-        user: &DBUser,
-    ) -> Result<String> {
-        let sql = format!(
-            "SELECT {} FROM invman_inventory WHERE id=?1",
-            config.inventory_schema_declaration.sql_names(),
-        );
-        let update_sql = format!(
-            "UPDATE invman_inventory SET {} WHERE id=?1",
+        callback: &mut dyn FnMut(KeyValueCollection) -> Result<()>,
+    ) -> Result<()> {
+        let archived = props.include_archived && self.prepare_archive(config, false)?;
+        let (sql, params, base) = self.make_inventory_list_query(props, config, archived);
+        let mut stmt = match (&base, props.raw) {
+            (Some(base), Some(raw)) => self.prepare_raw_query(&sql, base, raw)?,
+            _ => self.db.prepare(&sql)?,
+        };
+        // Functions called by raw SQL could still write, which SQLite detects on the statement
+        if props.raw.is_some() && !stmt.readonly() {
+            bail!("Raw SQL must only read from the database");
+        }
+        let grouped = match &props.group {
+            Some(group) => Some(group.output_columns(config)?),
+            None => None,
+        };
+        let mut rows = stmt.query(params_from_iter(params))?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            let entry = match &grouped {
+                // Aggregates are not declared by the schema, their types come with the grouping
+                Some(columns) => KeyValueCollection::new(
+                    columns
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (key, column_type))| {
+                            Ok(KeyValueTypeEntry::new(
+                                key.clone(),
+                                value_ref_to_string(row.get_ref(i)?)?,
+                                *column_type,
+                            ))
+                        })
+                        .collect::<Result<Vec<KeyValueTypeEntry>>>()?,
+                ),
+                None => row
+                    .to_typed_key_value(&config.inventory_schema_declaration)
+                    .with_context(|| "Failed to convert SQLite result into JSON representation")?,
+            };
+            callback(entry)?;
+            count += 1;
+        }
+        tracing::debug!(rows = count, "Listed inventory entities");
+        return Ok(());
+    }
+
+    fn inventory_list_explain(
+        &self,
+        props: &InventoryListProps,
+        config: &AppConfig,
+    ) -> Result<Vec<DBQueryPlan>> {
+        let archived = props.include_archived && self.prepare_archive(config, false)?;
+        let (sql, params, base) = self.make_inventory_list_query(props, config, archived);
+        if let (Some(base), Some(raw)) = (&base, props.raw) {
+            self.prepare_raw_query(&sql, base, raw)?;
+        }
+        return Ok(vec![self.query_plan(&sql, &params)?]);
+    }
+
+    fn explain_start(&mut self) {
+        EXPLAINED_STATEMENTS.with(|e| *e.borrow_mut() = Some(vec![]));
+        self.db.trace(Some(record_statement));
+    }
+
+    fn explain_finish(&mut self) -> Result<Vec<DBQueryPlan>> {
+        self.db.trace(None);
+        let statements = EXPLAINED_STATEMENTS.with(|e| e.borrow_mut().take());
+        return statements
+            .unwrap_or_default()
+            .iter()
+            .map(|sql| self.query_plan(sql, &[]))
+            .collect();
+    }
+
+    fn inventory_archive(
+        &mut self,
+        filter: &WhereExpr,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        // Attaching is not possible within a transaction
+        self.prepare_archive(config, true)?;
+        let tx = self.db.savepoint()?;
+        // Violations are collected by the check below instead of failing the first delete
+        tx.pragma_update(None, "defer_foreign_keys", true)?;
+        let ids = {
+            let mut params = vec![];
+            let mut stmt = tx.prepare(
+                format!(
+                    "SELECT id FROM main.invman_inventory WHERE {} AND {}",
+                    filter.to_sql(&mut params),
+                    org_scope("id")
+                )
+                .as_str(),
+            )?;
+            let ids = stmt
+                .query_map(params_from_iter(params), |row| row.get::<_, i64>(0))?
+                .collect::<rusqlite::Result<Vec<i64>>>()?;
+            ids
+        };
+        if ids.is_empty() {
+            bail!("No entities matched the condition");
+        }
+        let ids_json = serde_json::to_string(&ids)?;
+        let cols = config.inventory_schema_declaration.sql_names();
+        tx.execute(
+            format!(
+                "INSERT INTO archive.invman_inventory ({cols}) SELECT {cols} FROM main.invman_inventory WHERE id IN (SELECT value FROM json_each(?1))",
+                cols = cols
+            )
+            .as_str(),
+            params![ids_json],
+        )?;
+        tx.execute(
+            "INSERT INTO archive.invman_inventory_tx SELECT * FROM main.invman_inventory_tx WHERE inventory_id IN (SELECT value FROM json_each(?1))",
+            params![ids_json],
+        )?;
+        tx.execute(
+            "DELETE FROM main.invman_inventory_tx WHERE inventory_id IN (SELECT value FROM json_each(?1))",
+            params![ids_json],
+        )?;
+        tx.execute(
+            "DELETE FROM main.invman_inventory WHERE id IN (SELECT value FROM json_each(?1))",
+            params![ids_json],
+        )?;
+        let referencing = {
+            let mut stmt = tx.prepare("SELECT DISTINCT \"table\" FROM pragma_foreign_key_check")?;
+            let tables = stmt
+                .query_map((), |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            tables
+        };
+        if !referencing.is_empty() {
+            bail!(
+                "Matched entities are still referenced by {} and cannot be archived",
+                referencing.join(", ")
+            );
+        }
+        for id in &ids {
+            tx.execute(
+                "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+                params![EventActionNo::InventoryArchive as u32, user.id, id],
+            )?;
+        }
+        tx.commit()?;
+        return Ok(format!("Archived {} entities", ids.len()));
+    }
+
+    fn inventory_count(&self, conditions: &KeyValueCollection) -> Result<u32> {
+        let sql = format!(
+            "SELECT COUNT(*) AS count FROM invman_inventory WHERE {} AND {}",
+            conditions.sql_prepare_where_fields(0),
+            org_scope("id")
+        );
+        let count = self
+            .db
+            .query_row(&sql, params_from_iter(conditions.sql_values()), |row| {
+                Ok(Count { count: row.get(0)? })
+            })?;
+        return Ok(count.count);
+    }
+
+    fn inventory_exists(&self, identifier: &str) -> Result<bool> {
+        let exists = self.db.query_row(
+            format!(
+                "SELECT EXISTS(SELECT 1 FROM invman_inventory WHERE id=?1 AND {})",
+                org_scope("id")
+            )
+            .as_str(),
+            params![identifier],
+            |row| row.get(0),
+        )?;
+        return Ok(exists);
+    }
+
+    fn inventory_get(
+        &self,
+        identifier: &str,
+        config: &AppConfig,
+    ) -> Result<Option<KeyValueCollection>> {
+        let sql = format!(
+            "SELECT {} FROM invman_inventory WHERE id=?1 AND deleted_at IS NULL AND {}",
+            config.inventory_schema_declaration.sql_names(),
+            org_scope("id")
+        );
+        let entity = self
+            .db
+            .query_row(sql.as_str(), params![identifier], |row| {
+                Ok(row
+                    .to_typed_key_value(&config.inventory_schema_declaration)
+                    .unwrap())
+            })
+            .optional()?;
+        return Ok(entity);
+    }
+
+    fn inventory_distinct(
+        &self,
+        column: &str,
+        conditions: &KeyValueCollection,
+        config: &AppConfig,
+    ) -> Result<Vec<KeyValueCollection>> {
+        let decl = match config
+            .inventory_schema_declaration
+            .collection
+            .iter()
+            .find(|e| e.name == column)
+        {
+            Some(decl) => decl,
+            None => bail!("Could not find '{}' in table schema", column),
+        };
+        let sql = format!(
+            "SELECT {col}, COUNT(*) AS count FROM invman_inventory WHERE {} AND {} GROUP BY {col} ORDER BY {col}",
+            conditions.sql_prepare_where_fields(0),
+            org_scope("id"),
+            col = decl.name,
+        );
+        let mut stmt = self.db.prepare(&sql)?;
+        let mut rows = stmt.query(params_from_iter(conditions.sql_values()))?;
+        let mut entries = vec![];
+        while let Some(row) = rows.next()? {
+            let count: u32 = row.get(1)?;
+            entries.push(KeyValueCollection::new(vec![
+                KeyValueTypeEntry::new(
+                    decl.name.clone(),
+                    value_ref_to_string(row.get_ref(0)?)?,
+                    decl.column_type,
+                ),
+                KeyValueTypeEntry::new("count".into(), Some(count.to_string()), ColumnType::INT),
+            ]));
+        }
+        return Ok(entries);
+    }
+
+    fn inventory_edit(
+        &mut self,
+        identifier: &str,
+        params: &KeyValueCollection,
+        expect_version: Option<&str>,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        let sql = format!(
+            "SELECT {} FROM invman_inventory WHERE id=?1 AND {}",
+            config.inventory_schema_declaration.sql_names(),
+            org_scope("id")
+        );
+        let update_sql = format!(
+            "UPDATE invman_inventory SET {} WHERE id=?1",
             params.sql_prepare_update_fields(1)
         );
         let mut sql_params = params.sql_values();
-        let mut values = vec![Some(identifier.clone())];
+        let mut values = vec![Some(identifier.to_string())];
         values.append(&mut sql_params);
-        let tx = self.db.transaction()?;
+        let tx = self.db.savepoint()?;
+        if let Some(expected) = expect_version {
+            // Digits name a revision, i.e. the latest history entry, anything else an updated_at
+            let current: Option<String> = match expected.parse::<u32>() {
+                Ok(_) => tx.query_row(
+                    "SELECT CAST(MAX(id) AS TEXT) FROM invman_inventory_tx WHERE inventory_id=?1",
+                    params![identifier],
+                    |row| row.get(0),
+                )?,
+                Err(_) => tx
+                    .query_row(
+                        "SELECT updated_at FROM invman_inventory WHERE id=?1",
+                        params![identifier],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+                    .flatten(),
+            };
+            if current.as_deref() != Some(expected) {
+                bail!(
+                    "Conflict: entity {} changed since version {} (now {})",
+                    identifier,
+                    expected,
+                    current.unwrap_or("unknown".into())
+                );
+            }
+        }
         let before_item = tx.query_row(sql.as_str(), params![identifier], |row| {
             Ok(row
                 .to_typed_key_value(&config.inventory_schema_declaration)
                 .unwrap())
         })?;
-        tx.execute(&update_sql, params_from_iter(values.iter()))?;
+        let changed = tx.execute(&update_sql, params_from_iter(values.iter()))?;
+        tracing::debug!(rows = changed, id = identifier, "Edited inventory entity");
         let after_item = tx.query_row(sql.as_str(), params![identifier], |row| {
             Ok(row
                 .to_typed_key_value(&config.inventory_schema_declaration)
@@ -578,24 +3042,28 @@ impl InvManDBPool for InvManSqlite {
 
     fn inventory_remove(
         &mut self,
-        identifier: &String,
+        identifier: &str,
+        reason: Option<&str>,
         config: &AppConfig,
         user: &DBUser,
     ) -> Result<String> {
+        check_reason(reason, config)?;
         let sql = format!(
-            "SELECT {} FROM invman_inventory WHERE id=?1",
+            "SELECT {} FROM invman_inventory WHERE id=?1 AND {}",
             config.inventory_schema_declaration.sql_names(),
+            org_scope("id")
         );
-        let tx = self.db.transaction()?;
+        let tx = self.db.savepoint()?;
         let before_item = tx.query_row(sql.as_str(), params![identifier], |row| {
             Ok(row
                 .to_typed_key_value(&config.inventory_schema_declaration)
                 .unwrap())
         })?;
-        tx.execute(
+        let changed = tx.execute(
             "UPDATE invman_inventory SET deleted_at=(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')) WHERE id=?1 AND deleted_at IS NULL",
             params![identifier],
         )?;
+        tracing::debug!(rows = changed, id = identifier, "Removed inventory entity");
         let after_item = tx.query_row(sql.as_str(), params![identifier], |row| {
             Ok(row
                 .to_typed_key_value(&config.inventory_schema_declaration)
@@ -610,8 +3078,3163 @@ impl InvManDBPool for InvManSqlite {
             "INSERT INTO invman_inventory_tx (dispatcher, schema_id, inventory_id, action_no, from_val, to_val) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![user.id, latest_schema.id, before_item.get_id()?, DBOpNo::Delete as u32, before_item.to_json(), after_item.to_json()]
         )?;
-        tx.execute("INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))", params![EventActionNo::InventoryRemove as u32, user.id])?;
+        tx.execute("INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, (LAST_INSERT_ROWID()), ?3)", params![EventActionNo::InventoryRemove as u32, user.id, reason])?;
         tx.commit()?;
         Ok("Entity was successfully removed".into())
     }
+
+    fn inventory_merge(
+        &mut self,
+        keep: &str,
+        duplicate: &str,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        if keep == duplicate {
+            bail!("An entity cannot be merged into itself");
+        }
+        let sql = format!(
+            "SELECT {} FROM invman_inventory WHERE id=?1 AND {}",
+            config.inventory_schema_declaration.sql_names(),
+            org_scope("id")
+        );
+        let tx = self.db.savepoint()?;
+        for identifier in [keep, duplicate] {
+            if !is_entity_available(&tx, identifier)? {
+                bail!("No entity with identifier {} found", identifier);
+            }
+        }
+        let same_org: bool = tx.query_row(
+            "SELECT (SELECT org_id FROM invman_org_items WHERE inventory_id=?1) IS (SELECT org_id FROM invman_org_items WHERE inventory_id=?2)",
+            params![keep, duplicate],
+            |row| row.get(0),
+        )?;
+        if !same_org {
+            bail!(
+                "Entities {} and {} belong to different organizations and cannot be merged",
+                duplicate,
+                keep
+            );
+        }
+        let before_item = tx.query_row(sql.as_str(), params![duplicate], |row| {
+            Ok(row
+                .to_typed_key_value(&config.inventory_schema_declaration)
+                .unwrap())
+        })?;
+        // Thumbnails are a cache of the image, which is rendered again when requested
+        tx.execute(
+            "DELETE FROM invman_image_thumbnails WHERE inventory_id=?1",
+            params![duplicate],
+        )?;
+        // Records that are unique per entity stay with the duplicate if the keeper has them
+        for (table, column) in [
+            ("invman_inventory_tx", "inventory_id"),
+            ("invman_stock_movements", "inventory_id"),
+            ("invman_serials", "inventory_id"),
+            ("invman_loans", "inventory_id"),
+            ("invman_assignments", "inventory_id"),
+            ("invman_attachments", "inventory_id"),
+            ("invman_comments", "inventory_id"),
+            ("invman_inventory_meta", "inventory_id"),
+            ("invman_images", "inventory_id"),
+            ("invman_purchases", "inventory_id"),
+            ("invman_maintenance", "inventory_id"),
+            ("invman_inventory_suppliers", "inventory_id"),
+            ("invman_inventory_categories", "inventory_id"),
+            ("invman_kit_items", "inventory_id"),
+            ("invman_stocktake_counts", "inventory_id"),
+            ("invman_bom", "assembly_id"),
+            ("invman_bom", "component_id"),
+        ] {
+            tx.execute(
+                format!("UPDATE OR IGNORE {table} SET {column}=?1 WHERE {column}=?2").as_str(),
+                params![keep, duplicate],
+            )?;
+        }
+        // Left over links would still take effect, e.g. components used by builds
+        tx.execute(
+            "DELETE FROM invman_bom WHERE assembly_id=?1 OR component_id=?1 OR assembly_id=component_id",
+            params![duplicate],
+        )?;
+        tx.execute(
+            "DELETE FROM invman_maintenance WHERE inventory_id=?1",
+            params![duplicate],
+        )?;
+        tx.execute(
+            "DELETE FROM invman_inventory_suppliers WHERE inventory_id=?1",
+            params![duplicate],
+        )?;
+        tx.execute(
+            "DELETE FROM invman_inventory_categories WHERE inventory_id=?1",
+            params![duplicate],
+        )?;
+        tx.execute(
+            "DELETE FROM invman_kit_items WHERE inventory_id=?1",
+            params![duplicate],
+        )?;
+        tx.execute(
+            "DELETE FROM invman_stocktake_counts WHERE inventory_id=?1",
+            params![duplicate],
+        )?;
+        tx.execute(
+            "DELETE FROM invman_inventory_meta WHERE inventory_id=?1",
+            params![duplicate],
+        )?;
+        tx.execute(
+            "UPDATE invman_inventory SET deleted_at=(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')) WHERE id=?1",
+            params![duplicate],
+        )?;
+        let after_item = tx.query_row(sql.as_str(), params![duplicate], |row| {
+            Ok(row
+                .to_typed_key_value(&config.inventory_schema_declaration)
+                .unwrap())
+        })?;
+        // The duplicate is only kept for its history, neither its owner nor organization act on it
+        for table in ["invman_inventory_owners", "invman_org_items"] {
+            tx.execute(
+                format!("DELETE FROM {} WHERE inventory_id=?1", table).as_str(),
+                params![duplicate],
+            )?;
+        }
+        let latest_schema = tx.query_row(
+            "SELECT MAX(id) FROM invman_inventory_schema_tx",
+            (),
+            |row| Ok(IdEntry { id: row.get(0)? }),
+        )?;
+        tx.execute(
+            "INSERT INTO invman_inventory_tx (dispatcher, schema_id, inventory_id, action_no, from_val, to_val) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![user.id, latest_schema.id, before_item.get_id()?, DBOpNo::Merge as u32, before_item.to_json(), after_item.to_json()]
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, (LAST_INSERT_ROWID()), ?3)",
+            params![EventActionNo::InventoryMerge as u32, user.id, format!("merged into {}", keep)],
+        )?;
+        tx.commit()?;
+        tracing::debug!(keep, duplicate, "Merged inventory entities");
+        return Ok(format!("Entity {} was merged into {}", duplicate, keep));
+    }
+
+    fn inventory_checkout(
+        &mut self,
+        identifier: &str,
+        props: &LoanProps,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        let lent: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM invman_loans WHERE inventory_id=?1 AND checked_in_at IS NULL)",
+            params![identifier],
+            |row| row.get(0),
+        )?;
+        if lent {
+            bail!("Entity {} is already checked out", identifier);
+        }
+        tx.execute(
+            "INSERT INTO invman_loans (inventory_id, borrower, dispatcher, due_at) VALUES (?1, ?2, ?3, ?4)",
+            params![identifier, props.borrower, user.id, props.due_at],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+            params![EventActionNo::InventoryCheckout as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok("Entity was successfully checked out".into());
+    }
+
+    fn inventory_checkin(&mut self, identifier: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let loan = tx.query_row(
+            "SELECT id FROM invman_loans WHERE inventory_id=?1 AND checked_in_at IS NULL",
+            params![identifier],
+            |row| Ok(IdEntry { id: row.get(0)? }),
+        );
+        let loan = match loan {
+            Ok(loan) => loan,
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                bail!("Entity {} is not checked out", identifier)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        tx.execute(
+            "UPDATE invman_loans SET checked_in_at=(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')) WHERE id=?1",
+            params![loan.id],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::InventoryCheckin as u32, user.id, loan.id],
+        )?;
+        tx.commit()?;
+        return Ok("Entity was successfully checked in".into());
+    }
+
+    fn inventory_assign(
+        &mut self,
+        identifier: &str,
+        assignee: u32,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        let holder: Option<u32> = tx
+            .query_row(
+                "SELECT user_id FROM invman_assignments WHERE inventory_id=?1 AND unassigned_at IS NULL",
+                params![identifier],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if holder == Some(assignee) {
+            bail!("Entity {} is already assigned to this user", identifier);
+        }
+        tx.execute(
+            "UPDATE invman_assignments SET unassigned_at=(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')) WHERE inventory_id=?1 AND unassigned_at IS NULL",
+            params![identifier],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_assignments (inventory_id, user_id, dispatcher) VALUES (?1, ?2, ?3)",
+            params![identifier, assignee, user.id],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+            params![EventActionNo::InventoryAssign as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok("Entity was successfully assigned".into());
+    }
+
+    fn inventory_unassign(&mut self, identifier: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let assignment: Option<u32> = tx
+            .query_row(
+                "SELECT id FROM invman_assignments WHERE inventory_id=?1 AND unassigned_at IS NULL",
+                params![identifier],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let assignment = match assignment {
+            Some(assignment) => assignment,
+            None => bail!("Entity {} is not assigned to anyone", identifier),
+        };
+        tx.execute(
+            "UPDATE invman_assignments SET unassigned_at=(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')) WHERE id=?1",
+            params![assignment],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::InventoryUnassign as u32, user.id, assignment],
+        )?;
+        tx.commit()?;
+        return Ok("Entity was successfully unassigned".into());
+    }
+
+    fn purchase_set(
+        &mut self,
+        identifier: &str,
+        props: &PurchaseProps,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        tx.execute(
+            "INSERT INTO invman_purchases (inventory_id, purchased_at, price, warranty_months, useful_life_months, currency) VALUES (?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT(inventory_id) DO UPDATE SET purchased_at=excluded.purchased_at, price=excluded.price, warranty_months=excluded.warranty_months, useful_life_months=excluded.useful_life_months, currency=excluded.currency",
+            params![
+                identifier,
+                props.purchased_at,
+                props.price,
+                props.warranty_months,
+                props.useful_life_months,
+                props.currency
+            ],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::InventoryPurchase as u32, user.id, identifier],
+        )?;
+        tx.commit()?;
+        return Ok("Purchase details were successfully set".into());
+    }
+
+    fn purchase_list(&self) -> Result<Vec<DBPurchase>> {
+        let mut stmt = self.db.prepare(
+            format!(
+                "SELECT p.inventory_id, p.purchased_at, p.price, p.warranty_months, p.useful_life_months, p.currency FROM invman_purchases AS p JOIN invman_inventory AS i ON i.id = p.inventory_id WHERE i.deleted_at IS NULL AND {} ORDER BY p.inventory_id",
+                org_scope("i.id")
+            )
+            .as_str(),
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(DBPurchase {
+                inventory_id: row.get(0)?,
+                purchased_at: row.get(1)?,
+                price: row.get(2)?,
+                warranty_months: row.get(3)?,
+                useful_life_months: row.get(4)?,
+                currency: row.get(5)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBPurchase>>>()?);
+    }
+
+    fn loan_list(&self, overdue_only: bool) -> Result<Vec<DBLoan>> {
+        let mut sql = String::from(
+            "SELECT l.id, l.inventory_id, l.borrower, u.username, l.due_at, l.checked_out_at FROM invman_loans AS l JOIN invman_users AS u ON u.id = l.dispatcher WHERE l.checked_in_at IS NULL",
+        );
+        if overdue_only {
+            sql.push_str(" AND l.due_at < STRFTIME('%Y-%m-%d', 'NOW')");
+        }
+        sql.push_str(" ORDER BY l.due_at IS NULL, l.due_at, l.id");
+        let mut stmt = self.db.prepare(&sql)?;
+        let rows = stmt.query_map([], |row| {
+            Ok(DBLoan {
+                id: row.get(0)?,
+                inventory_id: row.get(1)?,
+                borrower: row.get(2)?,
+                dispatcher: row.get(3)?,
+                due_at: row.get(4)?,
+                checked_out_at: row.get(5)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBLoan>>>()?);
+    }
+
+    fn stock_adjust(
+        &mut self,
+        identifier: &str,
+        props: &AdjustProps,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        if props.quantity == 0 {
+            bail!("Quantity of a stock adjustment cannot be 0");
+        }
+        check_reason(props.reason.as_deref(), config)?;
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        check_serials(
+            props.serials,
+            props.quantity,
+            is_entity_serialized(&tx, identifier, config)?,
+        )?;
+        let available = stock_quantity(&tx, identifier, props.location)?;
+        if available + props.quantity < 0 {
+            bail!(
+                "Insufficient stock at location '{}' ({} available, {} requested)",
+                props.location,
+                available,
+                -props.quantity
+            );
+        }
+        tx.execute(
+            "INSERT INTO invman_stock_movements (inventory_id, location, quantity, unit_cost, currency, reason, dispatcher) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                identifier,
+                props.location,
+                props.quantity,
+                props.unit_cost,
+                props.currency,
+                props.reason,
+                user.id
+            ],
+        )?;
+        let movement_id = tx.last_insert_rowid();
+        for serial in props.serials {
+            if props.quantity < 0 {
+                let state = serial_in_stock(&tx, identifier, serial, props.location)?;
+                record_serial_change(&tx, &state, movement_id, props.status, props.location, user)?;
+                continue;
+            }
+            match serial_state(&tx, identifier, serial)? {
+                Some(state) if state.status == SerialStatus::InStock.to_string() => {
+                    bail!("Serial {} is already in stock", serial);
+                }
+                Some(state) => {
+                    record_serial_change(
+                        &tx,
+                        &state,
+                        movement_id,
+                        props.status,
+                        props.location,
+                        user,
+                    )?;
+                }
+                None => {
+                    tx.execute(
+                        "INSERT INTO invman_serials (inventory_id, serial, status, location) VALUES (?1, ?2, ?3, ?4)",
+                        params![identifier, serial, props.status.to_string(), props.location],
+                    )?;
+                    tx.execute(
+                        "INSERT INTO invman_serials_tx (serial_id, dispatcher, movement_id, to_status, to_location) VALUES ((LAST_INSERT_ROWID()), ?1, ?2, ?3, ?4)",
+                        params![user.id, movement_id, props.status.to_string(), props.location],
+                    )?;
+                }
+            }
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::InventoryAdjust as u32, user.id, movement_id],
+        )?;
+        tx.commit()?;
+        return Ok("Stock was successfully adjusted".into());
+    }
+
+    fn stock_transfer(
+        &mut self,
+        identifier: &str,
+        props: &TransferProps,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        if props.quantity <= 0 {
+            bail!("Quantity of a transfer must be greater than 0");
+        }
+        if props.from == props.to {
+            bail!("Cannot transfer stock to the location it is taken from");
+        }
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        check_serials(
+            props.serials,
+            props.quantity,
+            is_entity_serialized(&tx, identifier, config)?,
+        )?;
+        let available = stock_quantity(&tx, identifier, props.from)?;
+        if available < props.quantity {
+            bail!(
+                "Insufficient stock at location '{}' ({} available, {} requested)",
+                props.from,
+                available,
+                props.quantity
+            );
+        }
+        let insert_movement = "INSERT INTO invman_stock_movements (inventory_id, location, quantity, transfer_id, dispatcher) VALUES (?1, ?2, ?3, ?4, ?5)";
+        tx.execute(
+            insert_movement,
+            params![
+                identifier,
+                props.from,
+                -props.quantity,
+                None::<u32>,
+                user.id
+            ],
+        )?;
+        // Both movements reference the first one, which pairs them up as a single transfer
+        let transfer_id = tx.last_insert_rowid();
+        tx.execute(
+            "UPDATE invman_stock_movements SET transfer_id=?1 WHERE id=?1",
+            params![transfer_id],
+        )?;
+        tx.execute(
+            insert_movement,
+            params![identifier, props.to, props.quantity, transfer_id, user.id],
+        )?;
+        for serial in props.serials {
+            let state = serial_in_stock(&tx, identifier, serial, props.from)?;
+            record_serial_change(
+                &tx,
+                &state,
+                transfer_id,
+                SerialStatus::InStock,
+                props.to,
+                user,
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::InventoryTransfer as u32,
+                user.id,
+                transfer_id
+            ],
+        )?;
+        tx.commit()?;
+        return Ok("Stock was successfully transferred".into());
+    }
+
+    fn stock_list(&self, identifier: &str) -> Result<Vec<DBStock>> {
+        let mut stmt = self.db.prepare(
+            "SELECT location, SUM(quantity) FROM invman_stock_movements WHERE inventory_id=?1 GROUP BY location HAVING SUM(quantity) != 0 ORDER BY location",
+        )?;
+        let rows = stmt.query_map(params![identifier], |row| {
+            Ok(DBStock {
+                location: row.get(0)?,
+                quantity: row.get(1)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBStock>>>()?);
+    }
+
+    fn stock_movements(
+        &self,
+        identifier: &str,
+        location: Option<&str>,
+    ) -> Result<Vec<DBStockMovement>> {
+        let mut stmt = self.db.prepare(
+            "SELECT quantity, transfer_id IS NOT NULL, created_at FROM invman_stock_movements WHERE inventory_id=?1 AND (?2 IS NULL OR location=?2) ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![identifier, location], |row| {
+            Ok(DBStockMovement {
+                quantity: row.get(0)?,
+                transfer: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBStockMovement>>>()?);
+    }
+
+    fn stock_consumption(&self, since: &str) -> Result<Vec<DBStockConsumption>> {
+        let sql = format!(
+            "SELECT m.inventory_id, SUM(m.quantity), SUM(CASE WHEN m.quantity < 0 AND m.transfer_id IS NULL AND m.created_at >= ?1 THEN -m.quantity ELSE 0 END), (SELECT MIN(s.lead_time_days) FROM invman_inventory_suppliers l INNER JOIN invman_suppliers s ON s.id=l.supplier_id WHERE l.inventory_id=m.inventory_id) FROM invman_stock_movements m INNER JOIN invman_inventory i ON i.id=m.inventory_id WHERE i.deleted_at IS NULL AND {} GROUP BY m.inventory_id ORDER BY m.inventory_id",
+            org_scope("m.inventory_id")
+        );
+        let mut stmt = self.db.prepare(&sql)?;
+        let rows = stmt.query_map(params![since], |row| {
+            Ok(DBStockConsumption {
+                inventory_id: row.get(0)?,
+                quantity: row.get(1)?,
+                consumed: row.get(2)?,
+                lead_time_days: row.get(3)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBStockConsumption>>>()?);
+    }
+
+    fn reason_summary(&self, since: Option<&str>) -> Result<Vec<DBReasonSummary>> {
+        // Removals are recorded by their event, which targets the history entry of the entity
+        let sql = format!(
+            "WITH reasons(reason, inventory_id, quantity, removed) AS (SELECT m.reason, m.inventory_id, m.quantity, 0 FROM invman_stock_movements m WHERE m.reason IS NOT NULL AND m.transfer_id IS NULL AND m.created_at >= COALESCE(?1, '') AND {} UNION ALL SELECT e.reason, t.inventory_id, 0, 1 FROM invman_event_tx e INNER JOIN invman_inventory_tx t ON t.id=e.target WHERE e.action_no=?2 AND e.reason IS NOT NULL AND e.created_at >= COALESCE(?1, '') AND {}) SELECT reason, SUM(1 - removed), SUM(CASE WHEN quantity < 0 THEN -quantity ELSE 0 END), SUM(CASE WHEN quantity > 0 THEN quantity ELSE 0 END), SUM(removed), COUNT(DISTINCT inventory_id) FROM reasons GROUP BY reason ORDER BY 3 DESC, reason",
+            org_scope("m.inventory_id"),
+            org_scope("t.inventory_id")
+        );
+        let mut stmt = self.db.prepare(&sql)?;
+        let rows = stmt.query_map(
+            params![since, EventActionNo::InventoryRemove as u32],
+            |row| {
+                Ok(DBReasonSummary {
+                    reason: row.get(0)?,
+                    movements: row.get(1)?,
+                    quantity_removed: row.get(2)?,
+                    quantity_added: row.get(3)?,
+                    removed_entities: row.get(4)?,
+                    entities: row.get(5)?,
+                })
+            },
+        )?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBReasonSummary>>>()?);
+    }
+
+    fn stock_ledger(&self) -> Result<Vec<DBLedgerEntry>> {
+        let sql = format!(
+            "SELECT m.inventory_id, m.quantity, m.unit_cost, m.currency FROM invman_stock_movements m INNER JOIN invman_inventory i ON i.id=m.inventory_id WHERE m.transfer_id IS NULL AND i.deleted_at IS NULL AND {} ORDER BY m.id",
+            org_scope("m.inventory_id")
+        );
+        let mut stmt = self.db.prepare(&sql)?;
+        let rows = stmt.query_map([], |row| {
+            Ok(DBLedgerEntry {
+                inventory_id: row.get(0)?,
+                quantity: row.get(1)?,
+                unit_cost: row.get(2)?,
+                currency: row.get(3)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBLedgerEntry>>>()?);
+    }
+
+    fn serial_history(&self, serial: &str) -> Result<Vec<DBSerialEvent>> {
+        let mut stmt = self.db.prepare(
+            "SELECT s.serial, s.inventory_id, u.username, tx.from_status, tx.to_status, tx.from_location, tx.to_location, tx.created_at FROM invman_serials_tx tx INNER JOIN invman_serials s ON s.id=tx.serial_id INNER JOIN invman_users u ON u.id=tx.dispatcher WHERE s.serial=?1 ORDER BY tx.id",
+        )?;
+        let rows = stmt.query_map(params![serial], |row| {
+            Ok(DBSerialEvent {
+                serial: row.get(0)?,
+                inventory_id: row.get(1)?,
+                dispatcher: row.get(2)?,
+                from_status: row.get(3)?,
+                to_status: row.get(4)?,
+                from_location: row.get(5)?,
+                to_location: row.get(6)?,
+                created_at: row.get(7)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBSerialEvent>>>()?);
+    }
+
+    fn bom_define(
+        &mut self,
+        assembly: &str,
+        components: &[BomComponentProps],
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, assembly)? {
+            bail!("No entity with identifier {} found", assembly);
+        }
+        for (i, component) in components.iter().enumerate() {
+            let identifier = component.identifier.to_string();
+            if identifier == assembly {
+                bail!("Assembly {} cannot be a component of itself", assembly);
+            }
+            if components[..i]
+                .iter()
+                .any(|e| e.identifier == component.identifier)
+            {
+                bail!("Component {} is given more than once", identifier);
+            }
+            if !is_entity_available(&tx, &identifier)? {
+                bail!("No entity with identifier {} found", identifier);
+            }
+        }
+        tx.execute(
+            "DELETE FROM invman_bom WHERE assembly_id=?1",
+            params![assembly],
+        )?;
+        for component in components {
+            tx.execute(
+                "INSERT INTO invman_bom (assembly_id, component_id, quantity) VALUES (?1, ?2, ?3)",
+                params![assembly, component.identifier, component.quantity],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::BomDefine as u32, user.id, assembly],
+        )?;
+        tx.commit()?;
+        return Ok("Bill of materials was successfully defined".into());
+    }
+
+    fn bom_list(&self, assembly: &str) -> Result<Vec<DBBomComponent>> {
+        let mut stmt = self.db.prepare(
+            "SELECT component_id, quantity FROM invman_bom WHERE assembly_id=?1 ORDER BY component_id",
+        )?;
+        let rows = stmt.query_map(params![assembly], |row| {
+            Ok(DBBomComponent {
+                component_id: row.get(0)?,
+                quantity: row.get(1)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBBomComponent>>>()?);
+    }
+
+    fn bom_build(
+        &mut self,
+        assembly: &str,
+        props: &BuildProps,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        if props.quantity <= 0 {
+            bail!("Quantity of a build must be greater than 0");
+        }
+        let components = self.bom_list(assembly)?;
+        if components.is_empty() {
+            bail!("No bill of materials defined for assembly {}", assembly);
+        }
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, assembly)? {
+            bail!("No entity with identifier {} found", assembly);
+        }
+        if is_entity_serialized(&tx, assembly, config)? {
+            bail!(
+                "Assembly {} is serialized and must be booked by serial",
+                assembly
+            );
+        }
+        let mut shortages = vec![];
+        for component in &components {
+            let identifier = component.component_id.to_string();
+            if !is_entity_available(&tx, &identifier)? {
+                bail!("Component {} is no longer available", identifier);
+            }
+            if is_entity_serialized(&tx, &identifier, config)? {
+                bail!(
+                    "Component {} is serialized and must be booked by serial",
+                    identifier
+                );
+            }
+            let required = component.quantity * props.quantity;
+            let available = stock_quantity(&tx, &identifier, props.location)?;
+            if available < required {
+                shortages.push(format!(
+                    "  component {}: {} required, {} available, {} missing",
+                    identifier,
+                    required,
+                    available,
+                    required - available
+                ));
+            }
+        }
+        if !shortages.is_empty() {
+            bail!(
+                "Insufficient components to build {} of assembly {} at location '{}'\n{}",
+                props.quantity,
+                assembly,
+                props.location,
+                shortages.join("\n")
+            );
+        }
+        let insert_movement = "INSERT INTO invman_stock_movements (inventory_id, location, quantity, dispatcher) VALUES (?1, ?2, ?3, ?4)";
+        for component in &components {
+            tx.execute(
+                insert_movement,
+                params![
+                    component.component_id,
+                    props.location,
+                    -(component.quantity * props.quantity),
+                    user.id
+                ],
+            )?;
+        }
+        tx.execute(
+            insert_movement,
+            params![assembly, props.location, props.quantity, user.id],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+            params![EventActionNo::BomBuild as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok(format!(
+            "Successfully built {} of assembly {}",
+            props.quantity, assembly
+        ));
+    }
+
+    fn maintenance_schedule(
+        &mut self,
+        identifier: &str,
+        task: &str,
+        interval_days: u32,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        // A rescheduled task keeps its due date, only the following ones use the new interval
+        tx.execute(
+            "INSERT INTO invman_maintenance (inventory_id, task, interval_days, next_due_at) VALUES (?1, ?2, ?3, DATE('now', '+' || ?3 || ' days')) ON CONFLICT(inventory_id, task) DO UPDATE SET interval_days=excluded.interval_days",
+            params![identifier, task, interval_days],
+        )?;
+        let maintenance: u32 = tx.query_row(
+            "SELECT id FROM invman_maintenance WHERE inventory_id=?1 AND task=?2",
+            params![identifier, task],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::MaintenanceSchedule as u32,
+                user.id,
+                maintenance
+            ],
+        )?;
+        tx.commit()?;
+        return Ok("Maintenance was successfully scheduled".into());
+    }
+
+    fn maintenance_done(&mut self, identifier: &str, task: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let maintenance: Option<u32> = tx
+            .query_row(
+                "SELECT id FROM invman_maintenance WHERE inventory_id=?1 AND task=?2",
+                params![identifier, task],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let maintenance = match maintenance {
+            Some(maintenance) => maintenance,
+            None => bail!(
+                "No maintenance task '{}' scheduled for entity {}",
+                task,
+                identifier
+            ),
+        };
+        tx.execute(
+            "INSERT INTO invman_maintenance_tx (maintenance_id, dispatcher, due_at) SELECT id, ?2, next_due_at FROM invman_maintenance WHERE id=?1",
+            params![maintenance, user.id],
+        )?;
+        tx.execute(
+            "UPDATE invman_maintenance SET next_due_at=DATE('now', '+' || interval_days || ' days') WHERE id=?1",
+            params![maintenance],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+            params![EventActionNo::MaintenanceDone as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok("Maintenance was successfully recorded".into());
+    }
+
+    fn maintenance_due(&self, within_days: u32) -> Result<Vec<DBMaintenance>> {
+        let mut stmt = self.db.prepare(
+            format!(
+                "SELECT m.inventory_id, m.task, m.interval_days, (SELECT MAX(t.done_at) FROM invman_maintenance_tx AS t WHERE t.maintenance_id = m.id), m.next_due_at, m.next_due_at < DATE('now') FROM invman_maintenance AS m JOIN invman_inventory AS i ON i.id = m.inventory_id WHERE i.deleted_at IS NULL AND m.next_due_at <= DATE('now', '+' || ?1 || ' days') AND {} ORDER BY m.next_due_at, m.id",
+                org_scope("i.id")
+            )
+            .as_str(),
+        )?;
+        let rows = stmt.query_map(params![within_days], |row| {
+            Ok(DBMaintenance {
+                inventory_id: row.get(0)?,
+                task: row.get(1)?,
+                interval_days: row.get(2)?,
+                last_done_at: row.get(3)?,
+                next_due_at: row.get(4)?,
+                overdue: row.get(5)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBMaintenance>>>()?);
+    }
+
+    fn attachment_add(
+        &mut self,
+        identifier: &str,
+        filename: &str,
+        content: &[u8],
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        tx.execute(
+            "INSERT INTO invman_attachments (inventory_id, filename, size, content, dispatcher) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![identifier, filename, content.len(), content, user.id],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+            params![EventActionNo::AttachmentAdd as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok("File was successfully attached".into());
+    }
+
+    fn attachment_list(&self, identifier: &str) -> Result<Vec<DBAttachment>> {
+        let mut stmt = self.db.prepare(
+            "SELECT a.id, a.inventory_id, a.filename, a.size, u.username, a.created_at FROM invman_attachments AS a JOIN invman_users AS u ON u.id = a.dispatcher WHERE a.inventory_id=?1 ORDER BY a.id",
+        )?;
+        let rows = stmt.query_map(params![identifier], |row| {
+            Ok(DBAttachment {
+                id: row.get(0)?,
+                inventory_id: row.get(1)?,
+                filename: row.get(2)?,
+                size: row.get(3)?,
+                dispatcher: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBAttachment>>>()?);
+    }
+
+    fn attachment_content(&self, identifier: &str) -> Result<(String, Vec<u8>)> {
+        let attachment = self
+            .db
+            .query_row(
+                "SELECT filename, content FROM invman_attachments WHERE id=?1",
+                params![identifier],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        return match attachment {
+            Some(attachment) => Ok(attachment),
+            None => bail!("No attachment with identifier {} found", identifier),
+        };
+    }
+
+    fn attachment_remove(&mut self, identifier: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let deleted = tx.execute(
+            "DELETE FROM invman_attachments WHERE id=?1",
+            params![identifier],
+        )?;
+        if deleted == 0 {
+            bail!("No attachment with identifier {} found", identifier);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::AttachmentRemove as u32, user.id, identifier],
+        )?;
+        tx.commit()?;
+        return Ok("Attachment was successfully removed".into());
+    }
+
+    fn comment_add(&mut self, identifier: &str, body: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        tx.execute(
+            "INSERT INTO invman_comments (inventory_id, author, body) VALUES (?1, ?2, ?3)",
+            params![identifier, user.id, body],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+            params![EventActionNo::CommentAdd as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok("Comment was successfully added".into());
+    }
+
+    fn comment_list(&self, identifier: &str) -> Result<Vec<DBComment>> {
+        let mut stmt = self.db.prepare(
+            "SELECT c.id, c.inventory_id, u.username, c.body, c.created_at FROM invman_comments AS c JOIN invman_users AS u ON u.id = c.author WHERE c.inventory_id=?1 ORDER BY c.id",
+        )?;
+        let rows = stmt.query_map(params![identifier], |row| {
+            Ok(DBComment {
+                id: row.get(0)?,
+                inventory_id: row.get(1)?,
+                author: row.get(2)?,
+                body: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBComment>>>()?);
+    }
+
+    fn meta_set(
+        &mut self,
+        identifier: &str,
+        key: &str,
+        value: &str,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        tx.execute(
+            "INSERT INTO invman_inventory_meta (inventory_id, key, value) VALUES (?1, ?2, ?3) ON CONFLICT(inventory_id, key) DO UPDATE SET value=excluded.value, updated_at=STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')",
+            params![identifier, key, value],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![EventActionNo::MetaSet as u32, user.id, identifier, key],
+        )?;
+        tx.commit()?;
+        return Ok("Metadata was successfully set".into());
+    }
+
+    fn meta_remove(&mut self, identifier: &str, key: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        let removed = tx.execute(
+            "DELETE FROM invman_inventory_meta WHERE inventory_id=?1 AND key=?2",
+            params![identifier, key],
+        )?;
+        if removed == 0 {
+            bail!("Entity {} has no metadata {}", identifier, key);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![EventActionNo::MetaRemove as u32, user.id, identifier, key],
+        )?;
+        tx.commit()?;
+        return Ok("Metadata was successfully removed".into());
+    }
+
+    fn meta_get(&self, identifier: &str) -> Result<BTreeMap<String, String>> {
+        if !is_entity_available(&self.db, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        let mut stmt = self
+            .db
+            .prepare("SELECT key, value FROM invman_inventory_meta WHERE inventory_id=?1")?;
+        let rows = stmt.query_map(params![identifier], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        return Ok(rows.collect::<rusqlite::Result<BTreeMap<String, String>>>()?);
+    }
+
+    fn image_set(
+        &mut self,
+        identifier: &str,
+        filename: &str,
+        content: &[u8],
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        // Cached thumbnails of the previous image are removed along with it
+        tx.execute(
+            "DELETE FROM invman_images WHERE inventory_id=?1",
+            params![identifier],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_images (inventory_id, filename, content, dispatcher) VALUES (?1, ?2, ?3, ?4)",
+            params![identifier, filename, content, user.id],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::ImageSet as u32, user.id, identifier],
+        )?;
+        tx.commit()?;
+        return Ok("Image was successfully set".into());
+    }
+
+    fn image_get(&self, identifier: &str) -> Result<(String, Vec<u8>)> {
+        let image = self
+            .db
+            .query_row(
+                "SELECT filename, content FROM invman_images WHERE inventory_id=?1",
+                params![identifier],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        return match image {
+            Some(image) => Ok(image),
+            None => bail!("Entity {} has no image", identifier),
+        };
+    }
+
+    fn image_thumbnail(&self, identifier: &str, size: &str) -> Result<Option<Vec<u8>>> {
+        let thumbnail = self
+            .db
+            .query_row(
+                "SELECT content FROM invman_image_thumbnails WHERE inventory_id=?1 AND size=?2",
+                params![identifier, size],
+                |row| row.get(0),
+            )
+            .optional()?;
+        return Ok(thumbnail);
+    }
+
+    fn image_thumbnail_store(
+        &mut self,
+        identifier: &str,
+        size: &str,
+        content: &[u8],
+    ) -> Result<()> {
+        self.db.execute(
+            "INSERT OR REPLACE INTO invman_image_thumbnails (inventory_id, size, content) VALUES (?1, ?2, ?3)",
+            params![identifier, size, content],
+        )?;
+        return Ok(());
+    }
+
+    fn image_remove(&mut self, identifier: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let deleted = tx.execute(
+            "DELETE FROM invman_images WHERE inventory_id=?1",
+            params![identifier],
+        )?;
+        if deleted == 0 {
+            bail!("Entity {} has no image", identifier);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::ImageRemove as u32, user.id, identifier],
+        )?;
+        tx.commit()?;
+        return Ok("Image was successfully removed".into());
+    }
+
+    fn read_only_set(
+        &mut self,
+        config: &mut AppConfig,
+        read_only: bool,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='read_only'",
+            params![read_only.to_string()],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::ReadOnlySet as u32,
+                user.id,
+                read_only.to_string()
+            ],
+        )?;
+        tx.commit()?;
+        config.read_only = read_only;
+        return Ok(match read_only {
+            true => "Database is now read-only".into(),
+            false => "Database is now writable".into(),
+        });
+    }
+
+    fn timezone_set(
+        &mut self,
+        config: &mut AppConfig,
+        timezone: &str,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='timezone'",
+            params![timezone],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::TimezoneSet as u32, user.id, timezone],
+        )?;
+        tx.commit()?;
+        config.timezone = timezone.into();
+        return Ok(format!("Timestamps are now displayed in {}", timezone));
+    }
+
+    fn display_format_set(
+        &mut self,
+        config: &mut AppConfig,
+        format: DisplayFormat,
+        user: &DBUser,
+    ) -> Result<String> {
+        let value = serde_json::to_string(&format)?;
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='display_format'",
+            params![value],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::DisplayFormatSet as u32, user.id, value],
+        )?;
+        tx.commit()?;
+        config.display_format = format;
+        return Ok("Display format was set".into());
+    }
+
+    fn label_printer_set(
+        &mut self,
+        config: &mut AppConfig,
+        printer: Option<String>,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='label_printer'",
+            params![serde_json::to_string(&printer)?],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::LabelPrinterSet as u32, user.id, printer],
+        )?;
+        tx.commit()?;
+        let message = match &printer {
+            Some(printer) => format!("Labels are now sent to the printer at {}", printer),
+            None => "Label printer was removed".into(),
+        };
+        config.label_printer = printer;
+        return Ok(message);
+    }
+
+    fn exchange_rates_set(
+        &mut self,
+        config: &mut AppConfig,
+        rates: ExchangeRates,
+        user: &DBUser,
+    ) -> Result<String> {
+        let value = serde_json::to_string(&rates)?;
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='exchange_rates'",
+            params![value],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::ExchangeRatesSet as u32, user.id, value],
+        )?;
+        tx.commit()?;
+        config.exchange_rates = rates;
+        return Ok("Exchange rates were set".into());
+    }
+
+    fn reason_codes_set(
+        &mut self,
+        config: &mut AppConfig,
+        codes: Vec<String>,
+        user: &DBUser,
+    ) -> Result<String> {
+        let value = serde_json::to_string(&codes)?;
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='reason_codes'",
+            params![value],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::ReasonCodesSet as u32, user.id, value],
+        )?;
+        tx.commit()?;
+        config.reason_codes = codes;
+        return Ok("Reason codes were set".into());
+    }
+
+    fn ldap_set(
+        &mut self,
+        config: &mut AppConfig,
+        settings: Option<LdapSettings>,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='ldap'",
+            params![serde_json::to_string(&settings)?],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::LdapSet as u32,
+                user.id,
+                settings.as_ref().map(|e| e.url.clone())
+            ],
+        )?;
+        tx.commit()?;
+        config.ldap = settings;
+        return Ok("LDAP settings were successfully set".into());
+    }
+
+    fn password_policy_set(
+        &mut self,
+        config: &mut AppConfig,
+        policy: PasswordPolicy,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='password_policy'",
+            params![serde_json::to_string(&policy)?],
+        )?;
+        // The banned passwords are left out of the event log
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher) VALUES (?1, ?2)",
+            params![EventActionNo::PasswordPolicySet as u32, user.id],
+        )?;
+        tx.commit()?;
+        config.password_policy = policy;
+        return Ok("Password policy was successfully set".into());
+    }
+
+    fn registration_set(
+        &mut self,
+        config: &mut AppConfig,
+        allow: bool,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='allow_registration'",
+            params![allow.to_string()],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::RegistrationSet as u32,
+                user.id,
+                allow.to_string()
+            ],
+        )?;
+        tx.commit()?;
+        config.allow_registration = allow;
+        return Ok(match allow {
+            true => "Registration was enabled".into(),
+            false => "Registration was disabled".into(),
+        });
+    }
+
+    fn retention_set(
+        &mut self,
+        config: &mut AppConfig,
+        rules: RetentionRules,
+        user: &DBUser,
+    ) -> Result<String> {
+        let old_rules = serde_json::to_string(&config.retention_rules)?;
+        let new_rules = serde_json::to_string(&rules)?;
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='retention_rules'",
+            params![new_rules],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::RetentionSet as u32,
+                user.id,
+                format!("{} -> {}", old_rules, new_rules)
+            ],
+        )?;
+        tx.commit()?;
+        config.retention_rules = rules;
+        return Ok("Retention rules were successfully set".into());
+    }
+
+    fn retention_apply(
+        &mut self,
+        rules: &RetentionRules,
+        dry_run: bool,
+        user: &DBUser,
+    ) -> Result<DBRetentionReport> {
+        let mut report = DBRetentionReport {
+            dry_run,
+            purged_entities: vec![],
+            purged_entity_history: 0,
+            skipped_referenced_entities: vec![],
+            pruned_events: 0,
+        };
+        let tx = self.db.savepoint()?;
+        if let Some(days) = rules.purge_deleted_after_days {
+            let expired = format!(
+                "deleted_at IS NOT NULL AND deleted_at < STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW', '-{} days')",
+                days
+            );
+            let unreferenced = unreferenced_entity_condition(&tx)?;
+            let select_ids = |condition: &str| -> Result<Vec<u32>> {
+                let mut stmt = tx.prepare(
+                    format!(
+                        "SELECT id FROM invman_inventory WHERE {} AND {} ORDER BY id",
+                        condition,
+                        org_scope("id")
+                    )
+                    .as_str(),
+                )?;
+                let ids = stmt
+                    .query_map((), |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<u32>>>()?;
+                return Ok(ids);
+            };
+            report.purged_entities =
+                select_ids(format!("{} AND {}", expired, unreferenced).as_str())?;
+            report.skipped_referenced_entities =
+                select_ids(format!("{} AND NOT ({})", expired, unreferenced).as_str())?;
+            let ids_json = serde_json::to_string(&report.purged_entities)?;
+            report.purged_entity_history = tx.execute(
+                "DELETE FROM invman_inventory_tx WHERE inventory_id IN (SELECT value FROM json_each(?1))",
+                params![ids_json],
+            )?;
+            // Neither table references the inventory, so they are not cleaned up by a cascade
+            for table in ["invman_inventory_owners", "invman_org_items"] {
+                tx.execute(
+                    format!(
+                        "DELETE FROM {} WHERE inventory_id IN (SELECT value FROM json_each(?1))",
+                        table
+                    )
+                    .as_str(),
+                    params![ids_json],
+                )?;
+            }
+            tx.execute(
+                "DELETE FROM invman_inventory WHERE id IN (SELECT value FROM json_each(?1))",
+                params![ids_json],
+            )?;
+        }
+        if let Some(days) = rules.prune_events_after_days {
+            report.pruned_events = tx.execute(
+                "DELETE FROM invman_event_tx WHERE created_at < STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW', '-' || ?1 || ' days')",
+                params![days],
+            )?;
+        }
+        if dry_run {
+            // Dropping the transaction rolls back what would have been removed
+            return Ok(report);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::RetentionApply as u32,
+                user.id,
+                serde_json::to_string(&report)?
+            ],
+        )?;
+        tx.commit()?;
+        return Ok(report);
+    }
+
+    fn dump(&self) -> Result<DBDump> {
+        let mut dump = DBDump {
+            format: DUMP_FORMAT.into(),
+            version: DUMP_VERSION,
+            schema_version: self
+                .db
+                .query_row("PRAGMA user_version", (), |row| row.get(0))?,
+            created_at: self.db.query_row(
+                "SELECT STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')",
+                (),
+                |row| row.get(0),
+            )?,
+            tables: Default::default(),
+        };
+        let tables = table_names(&self.db)?;
+        let mut progress =
+            Progress::start("backup", "Backing up tables", Some(tables.len() as u64));
+        for table in tables {
+            let mut stmt = self
+                .db
+                .prepare(format!("SELECT * FROM {} ORDER BY rowid", table).as_str())?;
+            let columns = stmt
+                .column_names()
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>();
+            let rows = stmt
+                .query_map((), |row| {
+                    let mut map = serde_json::Map::new();
+                    for (i, column) in columns.iter().enumerate() {
+                        map.insert(column.clone(), value_ref_to_json(row.get_ref(i)?));
+                    }
+                    Ok(map)
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            dump.tables.insert(table, rows);
+            progress.advance(1);
+        }
+        return Ok(dump);
+    }
+
+    fn load(&mut self, dump: &DBDump) -> Result<String> {
+        let version: usize = self
+            .db
+            .query_row("PRAGMA user_version", (), |row| row.get(0))?;
+        if dump.schema_version != version {
+            bail!(
+                "Dump was created at database version {}, but this database is at version {}",
+                dump.schema_version,
+                version
+            );
+        }
+        let is_empty: bool = self.db.query_row(
+            "SELECT NOT EXISTS(SELECT 1 FROM invman_users) AND NOT EXISTS(SELECT 1 FROM invman_inventory)",
+            (),
+            |row| row.get(0),
+        )?;
+        if !is_empty {
+            bail!("Dumps can only be loaded into an empty database");
+        }
+        self.check_outside_batch("Loading a dump")?;
+        let declaration = dump
+            .tables
+            .get("invman_config")
+            .and_then(|rows| {
+                rows.iter().find(|e| {
+                    e.get("name").and_then(|e| e.as_str()) == Some("inventory_schema_declaration")
+                })
+            })
+            .and_then(|e| e.get("value"))
+            .and_then(|e| e.as_str());
+        let declaration = match declaration {
+            Some(declaration) => SchemaCollection::new(serde_json::from_str(declaration)?),
+            None => bail!("Dump does not contain an inventory schema declaration"),
+        };
+        let create_inventory_table = self.make_temp_inventory_table(&declaration);
+
+        // The tables reference each other, so the checks are deferred until all rows are loaded
+        let owner = self.acquire_lock("inventory_table")?;
+        let result = self.without_foreign_keys(|db| db.load_tables(dump, &create_inventory_table));
+        self.release_lock("inventory_table", &owner)?;
+        result?;
+        return Ok(format!(
+            "Loaded {} rows",
+            dump.tables.values().map(|e| e.len()).sum::<usize>()
+        ));
+    }
+
+    fn fixture_users(&self) -> Result<Vec<DBFixtureUser>> {
+        let mut stmt = self.db.prepare(
+            "SELECT u.username, r.name, u.password FROM invman_users AS u JOIN invman_roles AS r ON r.id = u.role_id WHERE u.deleted_at IS NULL ORDER BY u.id",
+        )?;
+        let users = stmt
+            .query_map((), |row| {
+                let password: String = row.get(2)?;
+                Ok(DBFixtureUser {
+                    username: row.get(0)?,
+                    role: row.get(1)?,
+                    password: None,
+                    // Users of the directory have no password of their own
+                    password_hash: (!password.is_empty()).then_some(password),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        return Ok(users);
+    }
+
+    fn fixture_load(
+        &mut self,
+        schema: &[SchemaDeclaration],
+        users: &[DBFixtureUser],
+        entities: &[KeyValueCollection],
+        config: &mut AppConfig,
+    ) -> Result<String> {
+        let is_empty: bool = self.db.query_row(
+            "SELECT NOT EXISTS(SELECT 1 FROM invman_users) AND NOT EXISTS(SELECT 1 FROM invman_inventory)",
+            (),
+            |row| row.get(0),
+        )?;
+        if !is_empty {
+            bail!("Fixtures can only be loaded into an empty database");
+        }
+        self.check_outside_batch("Loading a fixture")?;
+        if users.is_empty() {
+            bail!("A fixture needs at least one user, who dispatches its changes");
+        }
+        let roles = self.role_list()?;
+        let mut accounts = vec![];
+        for (i, user) in users.iter().enumerate() {
+            if users[..i].iter().any(|e| e.username == user.username) {
+                bail!("User {} is given twice", user.username);
+            }
+            let role_id = match roles.iter().find(|e| e.name == user.role) {
+                Some(role) => role.id,
+                None => bail!(
+                    "Role {} of user {} does not exist",
+                    user.role,
+                    user.username
+                ),
+            };
+            let password_hash = match (&user.password, &user.password_hash) {
+                (Some(password), None) => {
+                    let salt = SaltString::generate(&mut OsRng);
+                    Argon2::default()
+                        .hash_password(password.as_bytes(), &salt)?
+                        .to_string()
+                }
+                (None, Some(hash)) if PasswordHash::new(hash).is_ok() => hash.clone(),
+                (None, Some(_)) => bail!("Password hash of user {} is invalid", user.username),
+                (Some(_), Some(_)) => bail!(
+                    "User {} has both a password and a password hash",
+                    user.username
+                ),
+                (None, None) => String::new(),
+            };
+            accounts.push((user.username.as_str(), role_id, password_hash));
+        }
+        let old_schema = config.inventory_schema_declaration.clone();
+        let mut new_schema = old_schema.clone();
+        for decl in schema {
+            decl.validate()?;
+            if new_schema.contains(decl).is_some() {
+                bail!("Schema column {} exists already", decl.name);
+            }
+            new_schema.collection.push(decl.clone());
+        }
+
+        // The inventory table is rebuilt like by schema alter, but together with the users and
+        // entities. The methods adding them cannot be called while a savepoint borrows the
+        // connection, so it is opened by SQL.
+        let owner = self.acquire_lock("inventory_table")?;
+        let result = self.without_foreign_keys(|db| {
+            db.db.execute_batch("SAVEPOINT fixture")?;
+            let result = db.load_fixture(&accounts, &old_schema, &new_schema, entities, config);
+            match result {
+                Ok(()) => db.db.execute_batch("RELEASE fixture")?,
+                Err(_) => {
+                    db.db
+                        .execute_batch("ROLLBACK TO fixture; RELEASE fixture")?;
+                    config.inventory_schema_declaration = old_schema;
+                }
+            }
+            return result;
+        });
+        self.release_lock("inventory_table", &owner)?;
+        result?;
+        return Ok(format!(
+            "Loaded {} users, {} schema columns and {} entities",
+            users.len(),
+            schema.len(),
+            entities.len()
+        ));
+    }
+
+    fn merge(
+        &mut self,
+        path: &str,
+        strategy: MergeStrategy,
+        config: &mut AppConfig,
+        user: &DBUser,
+    ) -> Result<DBMergeReport> {
+        let other = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let other_schema: String = other
+            .query_row(
+                "SELECT value FROM invman_config WHERE name='inventory_schema_declaration'",
+                (),
+                |row| row.get(0),
+            )
+            .with_context(|| format!("'{}' is not an invman database", path))?;
+        let other_schema = SchemaCollection::new(serde_json::from_str(&other_schema)?);
+        let local_schema = config.inventory_schema_declaration.clone();
+        let mut added_columns = vec![];
+        for decl in &other_schema.collection {
+            match local_schema.collection.iter().find(|e| e.name == decl.name) {
+                Some(local) if local.column_type != decl.column_type => bail!(
+                    "Column {} is of type {} here, but of type {} in '{}'",
+                    decl.name,
+                    local.column_type,
+                    decl.column_type,
+                    path
+                ),
+                Some(_) => (),
+                None => added_columns.push(decl.clone()),
+            }
+        }
+
+        let names = other_schema
+            .collection
+            .iter()
+            .map(|e| e.name.clone())
+            .collect::<Vec<String>>();
+        let entities = {
+            let mut stmt = other.prepare(
+                format!(
+                    "SELECT {} FROM invman_inventory WHERE deleted_at IS NULL ORDER BY id",
+                    ["id".to_string()]
+                        .iter()
+                        .chain(names.iter())
+                        .cloned()
+                        .collect::<Vec<String>>()
+                        .join(",")
+                )
+                .as_str(),
+            )?;
+            let entities = stmt
+                .query_map((), |row| {
+                    let values = (1..=names.len())
+                        .map(|i| row.get::<_, Value>(i))
+                        .collect::<rusqlite::Result<Vec<Value>>>()?;
+                    Ok((row.get::<_, u32>(0)?, values))
+                })?
+                .collect::<rusqlite::Result<Vec<(u32, Vec<Value>)>>>()?;
+            entities
+        };
+
+        // Entities are the same, if they share the value of a unique column
+        let unique_columns = local_schema
+            .collection
+            .iter()
+            .filter(|e| e.unique)
+            .filter_map(|e| names.iter().position(|name| *name == e.name))
+            .collect::<Vec<usize>>();
+        let mut matches = vec![];
+        for (_, values) in &entities {
+            let mut local_id = None;
+            for i in &unique_columns {
+                if values[*i] == Value::Null {
+                    continue;
+                }
+                local_id = self
+                    .db
+                    .query_row(
+                        format!("SELECT id FROM invman_inventory WHERE {}=?1", names[*i]).as_str(),
+                        [&values[*i]],
+                        |row| row.get::<_, u32>(0),
+                    )
+                    .optional()?;
+                if local_id.is_some() {
+                    break;
+                }
+            }
+            matches.push(local_id);
+        }
+        if let MergeStrategy::Fail = strategy {
+            if let Some(((other_id, _), local_id)) = entities
+                .iter()
+                .zip(matches.iter())
+                .find(|(_, local_id)| local_id.is_some())
+            {
+                bail!(
+                    "Entity {} of '{}' conflicts with entity {}",
+                    other_id,
+                    path,
+                    local_id.unwrap()
+                );
+            }
+        }
+
+        let mut report = DBMergeReport {
+            added_columns: added_columns.iter().map(|e| e.name.clone()).collect(),
+            added: 0,
+            overwritten: 0,
+            skipped: 0,
+            id_map: Default::default(),
+        };
+        if !added_columns.is_empty() {
+            let mut new_schema = local_schema.clone();
+            new_schema.collection.append(&mut added_columns);
+            self.alter_inventory_table(&new_schema, &local_schema, &SchemaActionNo::Alter, user)?;
+            config.inventory_schema_declaration = new_schema;
+        }
+
+        let select_item_sql = format!(
+            "SELECT {} FROM invman_inventory WHERE id=?1",
+            config.inventory_schema_declaration.sql_names(),
+        );
+        let tx = self.db.savepoint()?;
+        let select_item = |id: u32| -> Result<String> {
+            return Ok(tx
+                .query_row(&select_item_sql, params![id], |row| {
+                    Ok(row
+                        .to_typed_key_value(&config.inventory_schema_declaration)
+                        .with_context(|| {
+                            "Failed to convert row into typed key value representation"
+                        }))
+                })??
+                .to_json());
+        };
+        let latest_schema: u32 = tx.query_row(
+            "SELECT MAX(id) FROM invman_inventory_schema_tx",
+            (),
+            |row| row.get(0),
+        )?;
+        for ((other_id, values), local_id) in entities.iter().zip(matches) {
+            let (local_id, action_no, event_no, from_val) = match (local_id, strategy) {
+                (Some(local_id), MergeStrategy::Skip) | (Some(local_id), MergeStrategy::Fail) => {
+                    report.skipped += 1;
+                    report.id_map.insert(*other_id, local_id);
+                    continue;
+                }
+                (Some(local_id), MergeStrategy::Overwrite) => {
+                    let from_val = select_item(local_id)?;
+                    if !names.is_empty() {
+                        tx.execute(
+                            format!(
+                                "UPDATE invman_inventory SET {} WHERE id=?",
+                                names
+                                    .iter()
+                                    .map(|e| format!("{}=?", e))
+                                    .collect::<Vec<String>>()
+                                    .join(",")
+                            )
+                            .as_str(),
+                            params_from_iter(values.iter().chain([&Value::from(local_id)])),
+                        )?;
+                    }
+                    report.overwritten += 1;
+                    (
+                        local_id,
+                        DBOpNo::Edit,
+                        EventActionNo::InventoryEdit,
+                        Some(from_val),
+                    )
+                }
+                (None, _) => {
+                    tx.execute(
+                        format!(
+                            "INSERT INTO invman_inventory ({}) VALUES ({})",
+                            names.join(","),
+                            vec!["?"; names.len()].join(",")
+                        )
+                        .as_str(),
+                        params_from_iter(values.iter()),
+                    )?;
+                    report.added += 1;
+                    (
+                        tx.last_insert_rowid() as u32,
+                        DBOpNo::Add,
+                        EventActionNo::InventoryAdd,
+                        None,
+                    )
+                }
+            };
+            report.id_map.insert(*other_id, local_id);
+            tx.execute(
+                "INSERT INTO invman_inventory_tx (dispatcher, schema_id, inventory_id, action_no, from_val, to_val) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![user.id, latest_schema, local_id, action_no as u32, from_val, select_item(local_id)?],
+            )?;
+            tx.execute(
+                "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+                params![event_no as u32, user.id],
+            )?;
+        }
+        tx.commit()?;
+        return Ok(report);
+    }
+
+    fn batch_begin(&mut self) -> Result<()> {
+        // Outside of a transaction the savepoint starts one, within a batch it nests, e.g. for
+        // an import that commits every few rows. The commands use savepoints as well.
+        self.db.execute_batch("SAVEPOINT invman_batch")?;
+        return Ok(());
+    }
+
+    fn batch_commit(&mut self) -> Result<()> {
+        self.db.execute_batch("RELEASE invman_batch")?;
+        return Ok(());
+    }
+
+    fn batch_rollback(&mut self) -> Result<()> {
+        self.db
+            .execute_batch("ROLLBACK TO invman_batch; RELEASE invman_batch")?;
+        return Ok(());
+    }
+
+    fn outbox_record(&mut self, command: &[String]) -> Result<()> {
+        self.db.execute(
+            "INSERT INTO invman_outbox (command) VALUES (?1)",
+            params![serde_json::to_string(command)?],
+        )?;
+        return Ok(());
+    }
+
+    fn outbox_list(&self) -> Result<Vec<DBOutboxEntry>> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT id, command, created_at FROM invman_outbox ORDER BY id")?;
+        let entries = stmt
+            .query_map((), |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .map(|row| {
+                let (id, command, created_at) = row?;
+                return Ok(DBOutboxEntry {
+                    id,
+                    command: serde_json::from_str(&command)?,
+                    created_at,
+                });
+            })
+            .collect::<Result<Vec<DBOutboxEntry>>>()?;
+        return Ok(entries);
+    }
+
+    fn outbox_remove(&mut self, id: u32) -> Result<()> {
+        self.db
+            .execute("DELETE FROM invman_outbox WHERE id=?1", params![id])?;
+        return Ok(());
+    }
+
+    fn smtp_set(
+        &mut self,
+        config: &mut AppConfig,
+        settings: Option<SmtpSettings>,
+        user: &DBUser,
+    ) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='smtp'",
+            params![serde_json::to_string(&settings)?],
+        )?;
+        // The password is left out of the event log
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::NotifySmtpSet as u32,
+                user.id,
+                settings.as_ref().map(|e| e.host.clone())
+            ],
+        )?;
+        tx.commit()?;
+        config.smtp = settings;
+        return Ok("SMTP settings were successfully set".into());
+    }
+
+    fn scheduler_set(
+        &mut self,
+        config: &mut AppConfig,
+        jobs: Vec<ScheduledJob>,
+        user: &DBUser,
+    ) -> Result<String> {
+        let old_jobs = serde_json::to_string(&config.scheduler_jobs)?;
+        let new_jobs = serde_json::to_string(&jobs)?;
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='scheduler_jobs'",
+            params![new_jobs],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::SchedulerSet as u32,
+                user.id,
+                format!("{} -> {}", old_jobs, new_jobs)
+            ],
+        )?;
+        tx.commit()?;
+        config.scheduler_jobs = jobs;
+        return Ok("Scheduled jobs were successfully set".into());
+    }
+
+    fn hooks_set(
+        &mut self,
+        config: &mut AppConfig,
+        hooks: Vec<Hook>,
+        user: &DBUser,
+    ) -> Result<String> {
+        let old_hooks = serde_json::to_string(&config.hooks)?;
+        let new_hooks = serde_json::to_string(&hooks)?;
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='hooks'",
+            params![new_hooks],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::HooksSet as u32,
+                user.id,
+                format!("{} -> {}", old_hooks, new_hooks)
+            ],
+        )?;
+        tx.commit()?;
+        config.hooks = hooks;
+        return Ok("Hooks were successfully set".into());
+    }
+
+    fn notify_rule_add(&mut self, rule: &NotifyRuleProps, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let added = tx.execute(
+            "INSERT OR IGNORE INTO invman_notify_rules (action_no, channel, recipient, condition) VALUES (?1, ?2, ?3, ?4)",
+            params![rule.action_no, rule.channel.to_string(), rule.recipient, rule.condition],
+        )?;
+        if added == 0 {
+            bail!("Rule already exists");
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+            params![EventActionNo::NotifyRuleAdd as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok("Rule was successfully added".into());
+    }
+
+    fn notify_rule_list(&self) -> Result<Vec<DBNotifyRule>> {
+        let mut stmt = self.db.prepare(
+            "SELECT id, action_no, channel, recipient, condition, created_at FROM invman_notify_rules ORDER BY id",
+        )?;
+        let rules = stmt
+            .query_map((), |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, u32>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })?
+            .map(|row| {
+                let (id, action_no, channel, recipient, condition, created_at) = row?;
+                return Ok(DBNotifyRule {
+                    id,
+                    event: event_name(action_no).unwrap_or("unknown").into(),
+                    channel: channel.parse()?,
+                    recipient,
+                    condition,
+                    created_at,
+                });
+            })
+            .collect::<Result<Vec<DBNotifyRule>>>()?;
+        return Ok(rules);
+    }
+
+    fn notify_rule_remove(&mut self, id: u32, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        if tx.execute("DELETE FROM invman_notify_rules WHERE id=?1", params![id])? == 0 {
+            bail!("Rule {} does not exist", id);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::NotifyRuleRemove as u32, user.id, id],
+        )?;
+        tx.commit()?;
+        return Ok("Rule was successfully removed".into());
+    }
+
+    fn notify_pending(&self) -> Result<DBPendingNotifications> {
+        let cursor: u32 = self.db.query_row(
+            "SELECT CAST(value AS INTEGER) FROM invman_config WHERE name='notify_cursor'",
+            (),
+            |row| row.get(0),
+        )?;
+        let latest_event = self.latest_event()?;
+        // Events of add, edit and remove target the history entry holding the changed entity
+        let mut stmt = self.db.prepare(
+            "SELECT r.channel, r.recipient, r.condition, e.id, e.action_no, u.username, e.target, e.reason, e.created_at, h.to_val, h.from_val FROM invman_event_tx AS e JOIN invman_notify_rules AS r ON r.action_no=e.action_no LEFT JOIN invman_users AS u ON u.id=e.dispatcher LEFT JOIN invman_inventory_tx AS h ON h.id=e.target AND e.action_no IN (?3, ?4, ?5) WHERE e.id > ?1 AND e.id <= ?2 ORDER BY e.id, r.id",
+        )?;
+        let notifications = stmt
+            .query_map(
+                params![
+                    cursor,
+                    latest_event,
+                    EventActionNo::InventoryAdd as u32,
+                    EventActionNo::InventoryEdit as u32,
+                    EventActionNo::InventoryRemove as u32
+                ],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        DBEvent {
+                            id: row.get(3)?,
+                            event: event_name(row.get(4)?).unwrap_or("unknown").into(),
+                            dispatcher: row.get(5)?,
+                            target: row.get(6)?,
+                            reason: row.get(7)?,
+                            created_at: row.get(8)?,
+                            entity: None,
+                        },
+                        row.get::<_, Option<String>>(9)?,
+                        row.get::<_, Option<String>>(10)?,
+                    ))
+                },
+            )?
+            .map(|row| {
+                let (channel, recipient, condition, mut event, entity, previous_entity) = row?;
+                event.entity = entity.map(|e| serde_json::from_str(&e)).transpose()?;
+                return Ok(DBNotification {
+                    channel: channel.parse()?,
+                    recipient,
+                    condition,
+                    event,
+                    previous_entity: previous_entity
+                        .map(|e| serde_json::from_str(&e))
+                        .transpose()?,
+                });
+            })
+            .collect::<Result<Vec<DBNotification>>>()?;
+        return Ok(DBPendingNotifications {
+            latest_event,
+            notifications,
+        });
+    }
+
+    fn rule_add(&mut self, rule: &RuleProps, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let added = tx.execute(
+            "INSERT OR IGNORE INTO invman_rules (name, action_no, condition, set_values, notify_channel, notify_recipient) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                rule.name,
+                rule.action_no,
+                rule.condition,
+                serde_json::to_string(&rule.set)?,
+                rule.notify_channel.map(|e| e.to_string()),
+                rule.notify_recipient
+            ],
+        )?;
+        if added == 0 {
+            bail!("Rule '{}' already exists", rule.name);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+            params![EventActionNo::RuleAdd as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok("Rule was successfully added".into());
+    }
+
+    fn rule_list(&self) -> Result<Vec<DBRule>> {
+        let mut stmt = self.db.prepare(
+            "SELECT id, name, action_no, condition, set_values, notify_channel, notify_recipient, created_at FROM invman_rules ORDER BY id",
+        )?;
+        let rules = stmt
+            .query_map((), |row| row_to_rule(row, 0))?
+            .map(|row| row?)
+            .collect::<Result<Vec<DBRule>>>()?;
+        return Ok(rules);
+    }
+
+    fn rule_remove(&mut self, name: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let id: Option<u32> = tx
+            .query_row(
+                "SELECT id FROM invman_rules WHERE name=?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let id = match id {
+            Some(id) => id,
+            None => bail!("Rule '{}' does not exist", name),
+        };
+        tx.execute("DELETE FROM invman_rules WHERE id=?1", params![id])?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::RuleRemove as u32, user.id, id],
+        )?;
+        tx.commit()?;
+        return Ok("Rule was successfully removed".into());
+    }
+
+    fn rule_matches(&self, after_event: u32) -> Result<Vec<DBRuleMatch>> {
+        // Events of add, edit and remove target the history entry holding the changed entity
+        let mut stmt = self.db.prepare(
+            "SELECT r.id, r.name, r.action_no, r.condition, r.set_values, r.notify_channel, r.notify_recipient, r.created_at, e.id, u.username, e.target, e.reason, e.created_at, h.to_val, h.from_val FROM invman_event_tx AS e JOIN invman_rules AS r ON r.action_no=e.action_no LEFT JOIN invman_users AS u ON u.id=e.dispatcher LEFT JOIN invman_inventory_tx AS h ON h.id=e.target AND e.action_no IN (?2, ?3, ?4) WHERE e.id > ?1 ORDER BY e.id, r.id",
+        )?;
+        let matches = stmt
+            .query_map(
+                params![
+                    after_event,
+                    EventActionNo::InventoryAdd as u32,
+                    EventActionNo::InventoryEdit as u32,
+                    EventActionNo::InventoryRemove as u32
+                ],
+                |row| {
+                    Ok((
+                        row_to_rule(row, 0)?,
+                        row.get::<_, u32>(8)?,
+                        row.get::<_, Option<String>>(9)?,
+                        row.get::<_, Option<i64>>(10)?,
+                        row.get::<_, Option<String>>(11)?,
+                        row.get::<_, String>(12)?,
+                        row.get::<_, Option<String>>(13)?,
+                        row.get::<_, Option<String>>(14)?,
+                    ))
+                },
+            )?
+            .map(|row| {
+                let (rule, id, dispatcher, target, reason, created_at, entity, previous_entity) =
+                    row?;
+                let rule = rule?;
+                return Ok(DBRuleMatch {
+                    event: DBEvent {
+                        id,
+                        event: rule.event.clone(),
+                        dispatcher,
+                        target,
+                        reason,
+                        created_at,
+                        entity: entity.map(|e| serde_json::from_str(&e)).transpose()?,
+                    },
+                    rule,
+                    previous_entity: previous_entity
+                        .map(|e| serde_json::from_str(&e))
+                        .transpose()?,
+                });
+            })
+            .collect::<Result<Vec<DBRuleMatch>>>()?;
+        return Ok(matches);
+    }
+
+    fn event_list(&self, after_event: u32) -> Result<Vec<DBEvent>> {
+        // Events of add, edit and remove target the history entry holding the changed entity
+        let mut stmt = self.db.prepare(
+            "SELECT e.id, e.action_no, u.username, e.target, e.reason, e.created_at, h.to_val FROM invman_event_tx AS e LEFT JOIN invman_users AS u ON u.id=e.dispatcher LEFT JOIN invman_inventory_tx AS h ON h.id=e.target AND e.action_no IN (?2, ?3, ?4) WHERE e.id > ?1 ORDER BY e.id",
+        )?;
+        let events = stmt
+            .query_map(
+                params![
+                    after_event,
+                    EventActionNo::InventoryAdd as u32,
+                    EventActionNo::InventoryEdit as u32,
+                    EventActionNo::InventoryRemove as u32
+                ],
+                |row| {
+                    Ok((
+                        DBEvent {
+                            id: row.get(0)?,
+                            event: event_name(row.get(1)?).unwrap_or("unknown").into(),
+                            dispatcher: row.get(2)?,
+                            target: row.get(3)?,
+                            reason: row.get(4)?,
+                            created_at: row.get(5)?,
+                            entity: None,
+                        },
+                        row.get::<_, Option<String>>(6)?,
+                    ))
+                },
+            )?
+            .map(|row| {
+                let (mut event, entity) = row?;
+                event.entity = entity.map(|e| serde_json::from_str(&e)).transpose()?;
+                return Ok(event);
+            })
+            .collect::<Result<Vec<DBEvent>>>()?;
+        return Ok(events);
+    }
+
+    fn latest_event(&self) -> Result<u32> {
+        return Ok(self.db.query_row(
+            "SELECT COALESCE(MAX(id), 0) FROM invman_event_tx",
+            (),
+            |row| row.get(0),
+        )?);
+    }
+
+    fn notify_advance(&mut self, event_id: u32) -> Result<()> {
+        self.db.execute(
+            "UPDATE invman_config SET value=?1 WHERE name='notify_cursor'",
+            params![event_id.to_string()],
+        )?;
+        return Ok(());
+    }
+
+    fn doctor(&mut self, config: &AppConfig, fix: bool) -> Result<DBDoctorReport> {
+        let strings = |db: &Connection, sql: &str| -> Result<Vec<String>> {
+            let mut stmt = db.prepare(sql)?;
+            let rows = stmt
+                .query_map((), |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            return Ok(rows);
+        };
+        let mut report = DBDoctorReport {
+            integrity: strings(&self.db, "PRAGMA integrity_check")?
+                .into_iter()
+                .filter(|e| e != "ok")
+                .collect(),
+            foreign_key_violations: strings(
+                &self.db,
+                "SELECT \"table\" || ' row ' || rowid || ' references a missing ' || parent FROM pragma_foreign_key_check",
+            )?,
+            missing_columns: vec![],
+            undeclared_columns: vec![],
+            orphaned_history: vec![],
+            repairs: vec![],
+            fixed: fix,
+        };
+        let columns = strings(
+            &self.db,
+            "SELECT name FROM pragma_table_info('invman_inventory') WHERE name NOT IN ('id', 'created_at', 'updated_at', 'deleted_at')",
+        )?;
+        let declarations = &config.inventory_schema_declaration.collection;
+        report.missing_columns = declarations
+            .iter()
+            .filter(|e| !columns.contains(&e.name))
+            .map(|e| e.name.clone())
+            .collect();
+        report.undeclared_columns = columns
+            .into_iter()
+            .filter(|e| !declarations.iter().any(|d| &d.name == e))
+            .collect();
+        {
+            let mut stmt = self.db.prepare(
+                "SELECT id FROM invman_inventory_tx WHERE inventory_id NOT IN (SELECT id FROM invman_inventory) ORDER BY id",
+            )?;
+            report.orphaned_history = stmt
+                .query_map((), |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<u32>>>()?;
+        }
+
+        if !report.integrity.is_empty() {
+            report
+                .repairs
+                .push("The database file is corrupted, restore it from a backup".into());
+        }
+        if !report.foreign_key_violations.is_empty() {
+            report
+                .repairs
+                .push("Restore or remove the rows with missing references".into());
+        }
+        for column in &report.undeclared_columns {
+            report.repairs.push(format!(
+                "Declare column {} in the schema or remove it from the inventory table",
+                column
+            ));
+        }
+        let add_columns = declarations
+            .iter()
+            .filter(|e| report.missing_columns.contains(&e.name))
+            .map(|e| {
+                (
+                    e,
+                    format!(
+                        "ALTER TABLE invman_inventory ADD COLUMN {}",
+                        self.make_row_statement(e)
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        let tx = self.db.savepoint()?;
+        for (decl, sql) in add_columns {
+            match fix {
+                true => match tx.execute(sql.as_str(), ()) {
+                    Ok(_) => report.repairs.push(format!("Added column {}", decl.name)),
+                    Err(e) => report.repairs.push(format!(
+                        "Column {} could not be added ({}), alter the schema to recreate it",
+                        decl.name, e
+                    )),
+                },
+                false => report
+                    .repairs
+                    .push(format!("Add column {} (fixable)", decl.name)),
+            }
+        }
+        if !report.orphaned_history.is_empty() {
+            match fix {
+                true => {
+                    let count = tx.execute(
+                        "DELETE FROM invman_inventory_tx WHERE inventory_id NOT IN (SELECT id FROM invman_inventory)",
+                        (),
+                    )?;
+                    report
+                        .repairs
+                        .push(format!("Removed {} orphaned history rows", count));
+                }
+                false => report.repairs.push(format!(
+                    "Remove {} orphaned history rows (fixable)",
+                    report.orphaned_history.len()
+                )),
+            }
+        }
+        tx.commit()?;
+        return Ok(report);
+    }
+
+    fn optimize(&mut self, user: &DBUser) -> Result<DBOptimizeReport> {
+        let size = |db: &Connection| -> Result<u64> {
+            return Ok(db.query_row(
+                "SELECT page_count * page_size FROM pragma_page_count, pragma_page_size",
+                (),
+                |row| row.get(0),
+            )?);
+        };
+        self.check_outside_batch("Optimizing the database")?;
+        let size_before = size(&self.db)?;
+        // VACUUM cannot run within a transaction, hence each statement runs on its own
+        self.db.execute_batch("VACUUM; ANALYZE; PRAGMA optimize;")?;
+        let size_after = size(&self.db)?;
+        let report = DBOptimizeReport {
+            size_before,
+            size_after,
+            reclaimed: size_before.saturating_sub(size_after),
+        };
+        self.db.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::Optimize as u32,
+                user.id,
+                serde_json::to_string(&report)?
+            ],
+        )?;
+        return Ok(report);
+    }
+
+    fn health(&self) -> DBHealthReport {
+        let query = || -> Result<Option<String>> {
+            self.db
+                .query_row("SELECT COUNT(*) FROM invman_config", (), |_| Ok(()))?;
+            return Ok(None);
+        };
+        let version = || -> Result<Option<String>> {
+            let version: usize = self
+                .db
+                .query_row("PRAGMA user_version", (), |row| row.get(0))?;
+            if version != MIGRATIONS.len() + 1 {
+                bail!(
+                    "Database is at version {}, expected {}",
+                    version,
+                    MIGRATIONS.len() + 1
+                );
+            }
+            return Ok(Some(format!("Version {}", version)));
+        };
+        let write = || -> Result<Option<String>> {
+            self.db.execute_batch(
+                "CREATE TEMP TABLE IF NOT EXISTS invman_health (value TEXT);
+                 INSERT INTO invman_health (value) VALUES ('ok');",
+            )?;
+            let value: String =
+                self.db
+                    .query_row("SELECT value FROM invman_health", (), |row| row.get(0))?;
+            self.db.execute("DROP TABLE temp.invman_health", ())?;
+            if value != "ok" {
+                bail!("Read back '{}' instead of the written value", value);
+            }
+            return Ok(None);
+        };
+        let checks = [
+            ("query", &query as &dyn Fn() -> Result<Option<String>>),
+            ("schema_version", &version),
+            ("write", &write),
+        ]
+        .into_iter()
+        .map(|(name, check)| match check() {
+            Ok(detail) => DBHealthCheck {
+                name: name.into(),
+                ok: true,
+                detail,
+            },
+            Err(e) => DBHealthCheck {
+                name: name.into(),
+                ok: false,
+                detail: Some(e.to_string()),
+            },
+        })
+        .collect::<Vec<DBHealthCheck>>();
+        return DBHealthReport {
+            healthy: checks.iter().all(|e| e.ok),
+            checks,
+        };
+    }
+
+    fn metrics(&self) -> Result<DBMetrics> {
+        let count = |sql: &str| -> Result<u64> {
+            return Ok(self.db.query_row(sql, (), |row| row.get(0))?);
+        };
+        let events = {
+            let mut stmt = self
+                .db
+                .prepare("SELECT action_no, COUNT(*) FROM invman_event_tx GROUP BY action_no")?;
+            let events = stmt
+                .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<BTreeMap<u32, u64>>>()?;
+            events
+        };
+        return Ok(DBMetrics {
+            database_size: count(
+                "SELECT page_count * page_size FROM pragma_page_count, pragma_page_size",
+            )?,
+            entities: count("SELECT COUNT(*) FROM invman_inventory WHERE deleted_at IS NULL")?,
+            deleted_entities: count(
+                "SELECT COUNT(*) FROM invman_inventory WHERE deleted_at IS NOT NULL",
+            )?,
+            users: count("SELECT COUNT(*) FROM invman_users WHERE deleted_at IS NULL")?,
+            events,
+        });
+    }
+
+    fn history_compact(
+        &mut self,
+        keep_last: u32,
+        older_than_days: u32,
+        user: &DBUser,
+    ) -> Result<DBCompactionReport> {
+        let tx = self.db.savepoint()?;
+        let revisions = {
+            let mut stmt = tx.prepare(
+                "WITH ranked AS (SELECT id, inventory_id, created_at, ROW_NUMBER() OVER (PARTITION BY inventory_id ORDER BY id) AS pos, COUNT(*) OVER (PARTITION BY inventory_id) AS total FROM invman_inventory_tx) SELECT inventory_id, id FROM ranked WHERE pos > 1 AND pos <= total - MAX(?1, 1) AND created_at < STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW', '-' || ?2 || ' days') ORDER BY inventory_id, id",
+            )?;
+            let revisions = stmt
+                .query_map(params![keep_last, older_than_days], |row| {
+                    Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<(u32, u32)>>>()?;
+            revisions
+        };
+        let mut report = DBCompactionReport {
+            compacted_entities: 0,
+            removed_revisions: 0,
+        };
+        let mut progress = Progress::start(
+            "compact",
+            "Compacting history",
+            Some(revisions.len() as u64),
+        );
+        for run in revisions.chunk_by(|a, b| a.0 == b.0) {
+            progress.advance(run.len() as u64);
+            if run.len() < 2 {
+                continue;
+            }
+            // The last revision of the run becomes the snapshot, so that the order is kept
+            let first = run[0].1;
+            let snapshot = run[run.len() - 1].1;
+            let removed = serde_json::to_string(
+                &run[..run.len() - 1]
+                    .iter()
+                    .map(|(_, id)| *id)
+                    .collect::<Vec<u32>>(),
+            )?;
+            tx.execute(
+                "UPDATE invman_inventory_tx SET action_no=?1, from_val=(SELECT from_val FROM invman_inventory_tx WHERE id=?2) WHERE id=?3",
+                params![DBOpNo::Snapshot as u32, first, snapshot],
+            )?;
+            tx.execute(
+                "UPDATE invman_event_tx SET target=?1 WHERE action_no IN (?2, ?3, ?4) AND target IN (SELECT value FROM json_each(?5))",
+                params![
+                    snapshot,
+                    EventActionNo::InventoryAdd as u32,
+                    EventActionNo::InventoryEdit as u32,
+                    EventActionNo::InventoryRemove as u32,
+                    removed
+                ],
+            )?;
+            report.removed_revisions += tx.execute(
+                "DELETE FROM invman_inventory_tx WHERE id IN (SELECT value FROM json_each(?1))",
+                params![removed],
+            )?;
+            report.compacted_entities += 1;
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, reason) VALUES (?1, ?2, ?3)",
+            params![
+                EventActionNo::HistoryCompact as u32,
+                user.id,
+                serde_json::to_string(&report)?
+            ],
+        )?;
+        tx.commit()?;
+        return Ok(report);
+    }
+
+    fn supplier_add(&mut self, props: &SupplierProps, user: &DBUser) -> Result<String> {
+        let name = match props.name {
+            Some(name) => name,
+            None => bail!("A supplier requires a name"),
+        };
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "INSERT INTO invman_suppliers (name, contact, lead_time_days) VALUES (?1, ?2, ?3)",
+            params![name, props.contact, props.lead_time_days],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, (LAST_INSERT_ROWID()))",
+            params![EventActionNo::SupplierAdd as u32, user.id],
+        )?;
+        tx.commit()?;
+        return Ok("Supplier was successfully added".into());
+    }
+
+    fn supplier_list(&self) -> Result<Vec<DBSupplier>> {
+        let mut stmt = self.db.prepare(
+            "SELECT id, name, contact, lead_time_days, created_at, updated_at FROM invman_suppliers WHERE deleted_at IS NULL ORDER BY name",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(DBSupplier {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                contact: row.get(2)?,
+                lead_time_days: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBSupplier>>>()?);
+    }
+
+    fn search(&self, text: &str, props: &SearchProps) -> Result<DBSearchResults> {
+        let pattern = format!(
+            "%{}%",
+            text.replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let mut results = DBSearchResults::default();
+        if !props.columns.is_empty() {
+            let sql = props
+                .columns
+                .iter()
+                .map(|column| {
+                    format!(
+                        "SELECT id, '{col}', CAST({col} AS TEXT) FROM invman_inventory WHERE deleted_at IS NULL AND {col} LIKE ?1 ESCAPE '\\' AND {}",
+                        org_scope("id"),
+                        col = column
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(" UNION ALL ");
+            let mut stmt = self.db.prepare(&format!("{} ORDER BY 1 LIMIT ?2", sql))?;
+            let rows = stmt.query_map(params![pattern, props.limit], |row| {
+                Ok(DBSearchMatch {
+                    inventory_id: row.get(0)?,
+                    column: row.get(1)?,
+                    value: row.get(2)?,
+                })
+            })?;
+            results.inventory = rows.collect::<rusqlite::Result<Vec<DBSearchMatch>>>()?;
+        }
+        if props.comments {
+            let mut stmt = self.db.prepare(&format!(
+                "SELECT c.id, c.inventory_id, u.username, c.body, c.created_at FROM invman_comments AS c JOIN invman_users AS u ON u.id = c.author WHERE c.body LIKE ?1 ESCAPE '\\' AND {} ORDER BY c.id LIMIT ?2",
+                org_scope("c.inventory_id")
+            ))?;
+            let rows = stmt.query_map(params![pattern, props.limit], |row| {
+                Ok(DBComment {
+                    id: row.get(0)?,
+                    inventory_id: row.get(1)?,
+                    author: row.get(2)?,
+                    body: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })?;
+            results.comments = rows.collect::<rusqlite::Result<Vec<DBComment>>>()?;
+        }
+        if props.attachments {
+            let mut stmt = self.db.prepare(&format!(
+                "SELECT a.id, a.inventory_id, a.filename, a.size, u.username, a.created_at FROM invman_attachments AS a JOIN invman_users AS u ON u.id = a.dispatcher WHERE a.filename LIKE ?1 ESCAPE '\\' AND {} ORDER BY a.id LIMIT ?2",
+                org_scope("a.inventory_id")
+            ))?;
+            let rows = stmt.query_map(params![pattern, props.limit], |row| {
+                Ok(DBAttachment {
+                    id: row.get(0)?,
+                    inventory_id: row.get(1)?,
+                    filename: row.get(2)?,
+                    size: row.get(3)?,
+                    dispatcher: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            })?;
+            results.attachments = rows.collect::<rusqlite::Result<Vec<DBAttachment>>>()?;
+        }
+        if props.suppliers {
+            let mut stmt = self.db.prepare(
+                "SELECT id, name, contact, lead_time_days, created_at, updated_at FROM invman_suppliers WHERE deleted_at IS NULL AND (name LIKE ?1 ESCAPE '\\' OR contact LIKE ?1 ESCAPE '\\') ORDER BY name LIMIT ?2",
+            )?;
+            let rows = stmt.query_map(params![pattern, props.limit], |row| {
+                Ok(DBSupplier {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    contact: row.get(2)?,
+                    lead_time_days: row.get(3)?,
+                    created_at: row.get(4)?,
+                    updated_at: row.get(5)?,
+                })
+            })?;
+            results.suppliers = rows.collect::<rusqlite::Result<Vec<DBSupplier>>>()?;
+        }
+        if props.meta {
+            let mut stmt = self.db.prepare(&format!(
+                "SELECT m.inventory_id, m.key, m.value FROM invman_inventory_meta AS m JOIN invman_inventory AS i ON i.id = m.inventory_id WHERE i.deleted_at IS NULL AND (m.key LIKE ?1 ESCAPE '\\' OR m.value LIKE ?1 ESCAPE '\\') AND {} ORDER BY m.inventory_id, m.key LIMIT ?2",
+                org_scope("m.inventory_id")
+            ))?;
+            let rows = stmt.query_map(params![pattern, props.limit], |row| {
+                Ok(DBSearchMatch {
+                    inventory_id: row.get(0)?,
+                    column: row.get(1)?,
+                    value: row.get(2)?,
+                })
+            })?;
+            results.meta = rows.collect::<rusqlite::Result<Vec<DBSearchMatch>>>()?;
+        }
+        return Ok(results);
+    }
+
+    fn supplier_edit(
+        &mut self,
+        identifier: &str,
+        props: &SupplierProps,
+        user: &DBUser,
+    ) -> Result<String> {
+        if !self.supplier_exists(identifier)? {
+            bail!("No supplier with identifier {} found", identifier);
+        }
+        let mut fields = vec![];
+        let mut values = vec![Some(identifier.to_string())];
+        let mut set = |name: &str, value: Option<String>| {
+            values.push(value);
+            fields.push(format!("{}=?{}", name, values.len()));
+        };
+        if let Some(name) = props.name {
+            set("name", Some(name.clone()));
+        }
+        if let Some(contact) = props.contact {
+            set("contact", Some(contact.clone()));
+        }
+        if let Some(lead_time_days) = props.lead_time_days {
+            set("lead_time_days", Some(lead_time_days.to_string()));
+        }
+        if fields.is_empty() {
+            bail!("Nothing to edit, provide at least one field to change");
+        }
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            format!(
+                "UPDATE invman_suppliers SET {} WHERE id=?1",
+                fields.join(",")
+            )
+            .as_str(),
+            params_from_iter(values),
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::SupplierEdit as u32, user.id, identifier],
+        )?;
+        tx.commit()?;
+        return Ok("Supplier was successfully edited".into());
+    }
+
+    fn supplier_remove(&mut self, identifier: &str, user: &DBUser) -> Result<String> {
+        if !self.supplier_exists(identifier)? {
+            bail!("No supplier with identifier {} found", identifier);
+        }
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "UPDATE invman_suppliers SET deleted_at=(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')) WHERE id=?1",
+            params![identifier],
+        )?;
+        tx.execute(
+            "DELETE FROM invman_inventory_suppliers WHERE supplier_id=?1",
+            params![identifier],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::SupplierRemove as u32, user.id, identifier],
+        )?;
+        tx.commit()?;
+        return Ok("Supplier was successfully removed".into());
+    }
+
+    fn supplier_link(&mut self, identifier: &str, entity: &str, user: &DBUser) -> Result<String> {
+        if !self.supplier_exists(identifier)? {
+            bail!("No supplier with identifier {} found", identifier);
+        }
+        if !self.inventory_exists(entity)? {
+            bail!("No entity with identifier {} found", entity);
+        }
+        let tx = self.db.savepoint()?;
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO invman_inventory_suppliers (inventory_id, supplier_id) VALUES (?1, ?2)",
+            params![entity, identifier],
+        )?;
+        if inserted == 0 {
+            bail!(
+                "Entity {} is already linked to supplier {}",
+                entity,
+                identifier
+            );
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::SupplierLink as u32, user.id, identifier],
+        )?;
+        tx.commit()?;
+        return Ok("Entity was successfully linked to supplier".into());
+    }
+
+    fn supplier_unlink(&mut self, identifier: &str, entity: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let deleted = tx.execute(
+            "DELETE FROM invman_inventory_suppliers WHERE inventory_id=?1 AND supplier_id=?2",
+            params![entity, identifier],
+        )?;
+        if deleted == 0 {
+            bail!("Entity {} is not linked to supplier {}", entity, identifier);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::SupplierUnlink as u32, user.id, identifier],
+        )?;
+        tx.commit()?;
+        return Ok("Entity was successfully unlinked from supplier".into());
+    }
+
+    fn category_create(&mut self, path: &str, user: &DBUser) -> Result<String> {
+        let names = Self::category_path_names(path)?;
+        let tx = self.db.savepoint()?;
+        let mut parent_id: Option<u32> = None;
+        let mut created = false;
+        for idx in 0..names.len() {
+            let current = names[..=idx].join("/");
+            let existing: Option<u32> = tx
+                .query_row(
+                    "SELECT id FROM invman_categories WHERE path=?1",
+                    params![current],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            parent_id = match existing {
+                Some(id) => Some(id),
+                None => {
+                    tx.execute(
+                        "INSERT INTO invman_categories (name, parent_id, path) VALUES (?1, ?2, ?3)",
+                        params![names[idx], parent_id, current],
+                    )?;
+                    let id = tx.last_insert_rowid() as u32;
+                    tx.execute(
+                        "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+                        params![EventActionNo::CategoryCreate as u32, user.id, id, current],
+                    )?;
+                    created = true;
+                    Some(id)
+                }
+            };
+        }
+        if !created {
+            bail!("Category {} already exists", names.join("/"));
+        }
+        tx.commit()?;
+        return Ok("Category was successfully created".into());
+    }
+
+    fn category_list(&self) -> Result<Vec<DBCategory>> {
+        let mut stmt = self.db.prepare(
+            "SELECT c.id, c.name, c.path, c.parent_id, (SELECT COUNT(*) FROM invman_inventory_categories a WHERE a.category_id=c.id), c.created_at FROM invman_categories c ORDER BY c.path",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(DBCategory {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                path: row.get(2)?,
+                parent_id: row.get(3)?,
+                entities: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })?;
+        return Ok(rows.collect::<rusqlite::Result<Vec<DBCategory>>>()?);
+    }
+
+    fn category_id(&self, path: &str) -> Result<u32> {
+        let path = Self::category_path_names(path)?.join("/");
+        let id = self
+            .db
+            .query_row(
+                "SELECT id FROM invman_categories WHERE path=?1",
+                params![path],
+                |row| row.get(0),
+            )
+            .optional()?;
+        return match id {
+            Some(id) => Ok(id),
+            None => bail!("No category {} found", path),
+        };
+    }
+
+    fn category_remove(&mut self, path: &str, user: &DBUser) -> Result<String> {
+        let path = Self::category_path_names(path)?.join("/");
+        let id = self.category_id(&path)?;
+        let children: u32 = self.db.query_row(
+            "SELECT COUNT(*) FROM invman_categories WHERE parent_id=?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        if children > 0 {
+            bail!(
+                "Category {} still has subcategories, remove them first",
+                path
+            );
+        }
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "DELETE FROM invman_inventory_categories WHERE category_id=?1",
+            params![id],
+        )?;
+        tx.execute("DELETE FROM invman_categories WHERE id=?1", params![id])?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![EventActionNo::CategoryRemove as u32, user.id, id, path],
+        )?;
+        tx.commit()?;
+        return Ok("Category was successfully removed".into());
+    }
+
+    fn category_assign(&mut self, identifier: &str, path: &str, user: &DBUser) -> Result<String> {
+        let path = Self::category_path_names(path)?.join("/");
+        let id = self.category_id(&path)?;
+        if !self.inventory_exists(identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        let tx = self.db.savepoint()?;
+        tx.execute(
+            "INSERT INTO invman_inventory_categories (inventory_id, category_id) VALUES (?1, ?2) ON CONFLICT(inventory_id) DO UPDATE SET category_id=excluded.category_id, created_at=excluded.created_at",
+            params![identifier, id],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![EventActionNo::CategoryAssign as u32, user.id, identifier, path],
+        )?;
+        tx.commit()?;
+        return Ok("Entity was successfully assigned to category".into());
+    }
+
+    fn category_unassign(&mut self, identifier: &str, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let deleted = tx.execute(
+            "DELETE FROM invman_inventory_categories WHERE inventory_id=?1",
+            params![identifier],
+        )?;
+        if deleted == 0 {
+            bail!("Entity {} is not assigned to a category", identifier);
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::CategoryUnassign as u32, user.id, identifier],
+        )?;
+        tx.commit()?;
+        return Ok("Entity was successfully unassigned from its category".into());
+    }
+
+    fn kit_define(&mut self, name: &str, items: &[KitItemProps], user: &DBUser) -> Result<String> {
+        if name.trim().is_empty() {
+            bail!("A kit requires a name");
+        }
+        if items.is_empty() {
+            bail!("A kit requires at least one item");
+        }
+        let tx = self.db.savepoint()?;
+        for (i, item) in items.iter().enumerate() {
+            let identifier = item.identifier.to_string();
+            if item.quantity <= 0 {
+                bail!("Quantity of item {} must be greater than 0", identifier);
+            }
+            if items[..i].iter().any(|e| e.identifier == item.identifier) {
+                bail!("Item {} is given more than once", identifier);
+            }
+            if !is_entity_available(&tx, &identifier)? {
+                bail!("No entity with identifier {} found", identifier);
+            }
+        }
+        tx.execute(
+            "INSERT INTO invman_kits (name) VALUES (?1) ON CONFLICT(name) DO UPDATE SET updated_at=(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))",
+            params![name],
+        )?;
+        let kit_id: u32 = tx.query_row(
+            "SELECT id FROM invman_kits WHERE name=?1",
+            params![name],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "DELETE FROM invman_kit_items WHERE kit_id=?1",
+            params![kit_id],
+        )?;
+        for item in items {
+            tx.execute(
+                "INSERT INTO invman_kit_items (kit_id, inventory_id, quantity) VALUES (?1, ?2, ?3)",
+                params![kit_id, item.identifier, item.quantity],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![EventActionNo::KitDefine as u32, user.id, kit_id, name],
+        )?;
+        tx.commit()?;
+        return Ok("Kit was successfully defined".into());
+    }
+
+    fn kit_list(&self) -> Result<Vec<DBKit>> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT id, name, created_at, updated_at FROM invman_kits ORDER BY name")?;
+        let kits = stmt
+            .query_map([], |row| {
+                Ok(DBKit {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    items: vec![],
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<DBKit>>>()?;
+        return kits
+            .into_iter()
+            .map(|mut kit| {
+                kit.items = self.kit_items(kit.id)?;
+                return Ok(kit);
+            })
+            .collect();
+    }
+
+    fn kit_get(&self, name: &str) -> Result<DBKit> {
+        let id = self.kit_id(name)?;
+        let mut kit = self.db.query_row(
+            "SELECT id, name, created_at, updated_at FROM invman_kits WHERE id=?1",
+            params![id],
+            |row| {
+                Ok(DBKit {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    items: vec![],
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            },
+        )?;
+        kit.items = self.kit_items(id)?;
+        return Ok(kit);
+    }
+
+    fn kit_remove(&mut self, name: &str, user: &DBUser) -> Result<String> {
+        let id = self.kit_id(name)?;
+        let tx = self.db.savepoint()?;
+        tx.execute("DELETE FROM invman_kit_items WHERE kit_id=?1", params![id])?;
+        tx.execute("DELETE FROM invman_kits WHERE id=?1", params![id])?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![EventActionNo::KitRemove as u32, user.id, id, name],
+        )?;
+        tx.commit()?;
+        return Ok("Kit was successfully removed".into());
+    }
+
+    fn kit_pick(
+        &mut self,
+        name: &str,
+        props: &PickProps,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        if props.quantity <= 0 {
+            bail!("Quantity of a pick must be greater than 0");
+        }
+        let kit_id = self.kit_id(name)?;
+        let items = self.kit_items(kit_id)?;
+        let tx = self.db.savepoint()?;
+        let mut shortages = vec![];
+        for item in &items {
+            let identifier = item.inventory_id.to_string();
+            if !is_entity_available(&tx, &identifier)? {
+                bail!("Item {} is no longer available", identifier);
+            }
+            if is_entity_serialized(&tx, &identifier, config)? {
+                bail!(
+                    "Item {} is serialized and must be booked by serial",
+                    identifier
+                );
+            }
+            let required = item.quantity * props.quantity;
+            let available = stock_quantity(&tx, &identifier, props.location)?;
+            if available < required {
+                shortages.push(format!(
+                    "  item {}: {} required, {} available, {} missing",
+                    identifier,
+                    required,
+                    available,
+                    required - available
+                ));
+            }
+        }
+        if !shortages.is_empty() {
+            bail!(
+                "Insufficient stock to pick {} of kit {} at location '{}'\n{}",
+                props.quantity,
+                name,
+                props.location,
+                shortages.join("\n")
+            );
+        }
+        for item in &items {
+            tx.execute(
+                "INSERT INTO invman_stock_movements (inventory_id, location, quantity, dispatcher) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    item.inventory_id,
+                    props.location,
+                    -(item.quantity * props.quantity),
+                    user.id
+                ],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                EventActionNo::KitPick as u32,
+                user.id,
+                kit_id,
+                props.quantity.to_string()
+            ],
+        )?;
+        tx.commit()?;
+        return Ok(format!(
+            "Successfully picked {} of kit {}",
+            props.quantity, name
+        ));
+    }
+
+    fn stocktake_start(&mut self, location: &str, user: &DBUser) -> Result<String> {
+        if location.trim().is_empty() {
+            bail!("A stocktake requires a location");
+        }
+        let tx = self.db.savepoint()?;
+        if let Ok((_, open)) = open_stocktake(&tx) {
+            bail!(
+                "Stocktake of location '{}' is still open, close or cancel it first",
+                open
+            );
+        }
+        tx.execute(
+            "INSERT INTO invman_stocktakes (location, started_by) VALUES (?1, ?2)",
+            params![location, user.id],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, (LAST_INSERT_ROWID()), ?3)",
+            params![EventActionNo::StocktakeStart as u32, user.id, location],
+        )?;
+        tx.commit()?;
+        return Ok(format!("Stocktake of location '{}' was started", location));
+    }
+
+    fn stocktake_record(
+        &mut self,
+        identifier: &str,
+        counted: i64,
+        config: &AppConfig,
+        user: &DBUser,
+    ) -> Result<String> {
+        if counted < 0 {
+            bail!("Counted quantity cannot be negative");
+        }
+        let tx = self.db.savepoint()?;
+        let (stocktake, location) = open_stocktake(&tx)?;
+        if !is_entity_available(&tx, identifier)? {
+            bail!("No entity with identifier {} found", identifier);
+        }
+        if is_entity_serialized(&tx, identifier, config)? {
+            bail!(
+                "Entity {} is serialized and must be booked by serial",
+                identifier
+            );
+        }
+        let expected = stock_quantity(&tx, identifier, &location)?;
+        tx.execute(
+            "INSERT INTO invman_stocktake_counts (stocktake_id, inventory_id, expected, counted, dispatcher) VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT(stocktake_id, inventory_id) DO UPDATE SET expected=excluded.expected, counted=excluded.counted, dispatcher=excluded.dispatcher, created_at=excluded.created_at",
+            params![stocktake, identifier, expected, counted, user.id],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                EventActionNo::StocktakeRecord as u32,
+                user.id,
+                stocktake,
+                identifier
+            ],
+        )?;
+        tx.commit()?;
+        return Ok(format!(
+            "Count of entity {} was recorded, {} expected",
+            identifier, expected
+        ));
+    }
+
+    fn stocktake_status(&self) -> Result<DBStocktakeReport> {
+        let (stocktake, _) = open_stocktake(&self.db)?;
+        return stocktake_report(&self.db, stocktake);
+    }
+
+    fn stocktake_close(&mut self, user: &DBUser) -> Result<DBStocktakeReport> {
+        let tx = self.db.savepoint()?;
+        let (stocktake, location) = open_stocktake(&tx)?;
+        let report = stocktake_report(&tx, stocktake)?;
+        for line in report.lines.iter().filter(|e| e.variance != 0) {
+            // Movements booked after the count are kept, only the variance is adjusted
+            let identifier = line.inventory_id.to_string();
+            if stock_quantity(&tx, &identifier, &location)? + line.variance < 0 {
+                bail!(
+                    "Stock of entity {} was taken out since it was counted, record its count again",
+                    identifier
+                );
+            }
+            tx.execute(
+                "INSERT INTO invman_stock_movements (inventory_id, location, quantity, dispatcher, reason) VALUES (?1, ?2, ?3, ?4, 'stocktake')",
+                params![line.inventory_id, location, line.variance, user.id],
+            )?;
+        }
+        tx.execute(
+            "UPDATE invman_stocktakes SET closed_by=?1, closed_at=(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')) WHERE id=?2",
+            params![user.id, stocktake],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target) VALUES (?1, ?2, ?3)",
+            params![EventActionNo::StocktakeClose as u32, user.id, stocktake],
+        )?;
+        let report = stocktake_report(&tx, stocktake)?;
+        tx.commit()?;
+        return Ok(report);
+    }
+
+    fn stocktake_cancel(&mut self, user: &DBUser) -> Result<String> {
+        let tx = self.db.savepoint()?;
+        let (stocktake, location) = open_stocktake(&tx)?;
+        tx.execute(
+            "DELETE FROM invman_stocktake_counts WHERE stocktake_id=?1",
+            params![stocktake],
+        )?;
+        tx.execute(
+            "DELETE FROM invman_stocktakes WHERE id=?1",
+            params![stocktake],
+        )?;
+        tx.execute(
+            "INSERT INTO invman_event_tx (action_no, dispatcher, target, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                EventActionNo::StocktakeCancel as u32,
+                user.id,
+                stocktake,
+                location
+            ],
+        )?;
+        tx.commit()?;
+        return Ok(format!(
+            "Stocktake of location '{}' was cancelled",
+            location
+        ));
+    }
 }
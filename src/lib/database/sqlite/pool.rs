@@ -0,0 +1,175 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::InvManSqlite;
+use crate::database::InvManDBPool;
+use anyhow::{anyhow, bail, Result};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/**
+ * How long a pooled connection waits for a lock held by another connection before failing
+ */
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/**
+ * Connections to a single SQLite database that can be shared across threads. Every operation
+ * takes a connection of its own, which is opened on demand until the maximum is reached, after
+ * which operations wait until one is handed back.
+ */
+pub struct InvManSqlitePool {
+    path: String,
+    max_size: usize,
+    state: Mutex<PoolState>,
+    released: Condvar,
+}
+
+struct PoolState {
+    idle: Vec<InvManSqlite>,
+    /**
+     * Connections opened, whether idle or in use
+     */
+    open: usize,
+}
+
+impl InvManSqlitePool {
+    /**
+     * Opens the first connection right away, which creates and migrates the database, so that
+     * the following connections only open it. An in-memory database belongs to a single
+     * connection, so its pool never holds more than one.
+     */
+    pub fn new(path: &str, max_size: usize) -> Result<InvManSqlitePool> {
+        if max_size == 0 {
+            bail!("Connection pool must hold at least one connection");
+        }
+        let conn = InvManSqlite::new_at(path)?;
+        conn.db.busy_timeout(BUSY_TIMEOUT)?;
+        return Ok(InvManSqlitePool {
+            path: path.into(),
+            max_size: if path == ":memory:" { 1 } else { max_size },
+            state: Mutex::new(PoolState {
+                idle: vec![conn],
+                open: 1,
+            }),
+            released: Condvar::new(),
+        });
+    }
+
+    /**
+     * Takes an idle connection, opens a new one or waits for one to be handed back. The
+     * connection is handed back to the pool once it is dropped.
+     */
+    pub fn get(&self) -> Result<PooledSqlite<'_>> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow!("Connection pool is poisoned"))?;
+        loop {
+            if let Some(conn) = state.idle.pop() {
+                return Ok(PooledSqlite {
+                    pool: self,
+                    conn: Some(conn),
+                });
+            }
+            if state.open < self.max_size {
+                state.open += 1;
+                break;
+            }
+            state = self
+                .released
+                .wait(state)
+                .map_err(|_| anyhow!("Connection pool is poisoned"))?;
+        }
+        drop(state);
+
+        // Opening is slow compared to an operation, so other threads are not blocked meanwhile
+        let conn = InvManSqlite::open(&self.path).and_then(|conn| {
+            conn.db.busy_timeout(BUSY_TIMEOUT)?;
+            return Ok(conn);
+        });
+        return match conn {
+            Ok(conn) => Ok(PooledSqlite {
+                pool: self,
+                conn: Some(conn),
+            }),
+            Err(e) => {
+                if let Ok(mut state) = self.state.lock() {
+                    state.open -= 1;
+                }
+                self.released.notify_one();
+                Err(e)
+            }
+        };
+    }
+
+    /**
+     * Runs the operation with a connection of the pool.
+     */
+    pub fn with<T>(&self, operation: impl FnOnce(&mut dyn InvManDBPool) -> Result<T>) -> Result<T> {
+        let mut conn = self.get()?;
+        return operation(&mut *conn);
+    }
+}
+
+/**
+ * Connection taken from an InvManSqlitePool, which is handed back when dropped.
+ */
+pub struct PooledSqlite<'a> {
+    pool: &'a InvManSqlitePool,
+    conn: Option<InvManSqlite>,
+}
+
+impl Deref for PooledSqlite<'_> {
+    type Target = InvManSqlite;
+
+    fn deref(&self) -> &InvManSqlite {
+        return self.conn.as_ref().unwrap();
+    }
+}
+
+impl DerefMut for PooledSqlite<'_> {
+    fn deref_mut(&mut self) -> &mut InvManSqlite {
+        return self.conn.as_mut().unwrap();
+    }
+}
+
+impl Drop for PooledSqlite<'_> {
+    fn drop(&mut self) {
+        let conn = match self.conn.take() {
+            Some(conn) => conn,
+            None => return,
+        };
+        // The next operation may be of a user of another organization
+        conn.scope_to_org(None);
+        match self.pool.state.lock() {
+            // An operation that panicked may have left a transaction open
+            Ok(mut state) if !conn.db.is_autocommit() => state.open -= 1,
+            Ok(mut state) => state.idle.push(conn),
+            Err(_) => return,
+        }
+        self.pool.released.notify_one();
+    }
+}
+
+// The pool is meant to be shared across threads, which must keep compiling
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<InvManSqlitePool>();
+};
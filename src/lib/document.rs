@@ -0,0 +1,591 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use std::fmt::Write as _;
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const TABLE_FONT_SIZE: f32 = 8.0;
+/**
+ * Width of every character of Courier relative to the font size
+ */
+const COURIER_WIDTH: f32 = 0.6;
+
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+const MONO: Name = Name(b"F3");
+const MONO_BOLD: Name = Name(b"F4");
+
+/**
+ * Content of a printable report, independent of the format it is rendered in
+ */
+pub struct ReportDocument {
+    pub title: String,
+    /**
+     * Filters the report was created with, as name and value
+     */
+    pub filters: Vec<(String, String)>,
+    pub generated_at: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /**
+     * Totals and other aggregates printed below the table, as name and value
+     */
+    pub summary: Vec<(String, String)>,
+}
+
+struct Text {
+    font: Name<'static>,
+    size: f32,
+    y: f32,
+    content: String,
+}
+
+/**
+ * Places lines of text from the top to the bottom of pages, starting a new page when one is full
+ */
+struct Layout {
+    pages: Vec<Vec<Text>>,
+    y: f32,
+    /**
+     * Header of the table, repeated at the top of every page the table continues on
+     */
+    repeated: Option<String>,
+}
+
+impl Layout {
+    fn line(&mut self, font: Name<'static>, size: f32, content: String) {
+        let height = size * 1.4;
+        if self.pages.is_empty() || self.y - height < MARGIN {
+            self.pages.push(vec![]);
+            self.y = PAGE_HEIGHT - MARGIN;
+            if let Some(header) = self.repeated.clone() {
+                self.line(MONO_BOLD, TABLE_FONT_SIZE, header);
+            }
+        }
+        self.y -= height;
+        if let Some(page) = self.pages.last_mut() {
+            page.push(Text {
+                font,
+                size,
+                y: self.y,
+                content,
+            });
+        }
+    }
+}
+
+impl ReportDocument {
+    /**
+     * Renders the report as PDF of A4 pages. The table is set in a monospaced font, so that its
+     * columns align, and its header is repeated on every page. Cells too long for the page are
+     * shortened.
+     */
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let widths = self.column_widths();
+        let format_row = |row: &Vec<String>| {
+            row.iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| {
+                    let cell = if cell.chars().count() > *width {
+                        let mut cell = cell.chars().take(width - 1).collect::<String>();
+                        cell.push('…');
+                        cell
+                    } else {
+                        cell.clone()
+                    };
+                    format!("{:width$}", cell, width = width)
+                })
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        let mut layout = Layout {
+            pages: vec![],
+            y: 0.0,
+            repeated: None,
+        };
+        layout.line(BOLD, 16.0, self.title.clone());
+        layout.line(REGULAR, 9.0, format!("Generated at {}", self.generated_at));
+        let filters = match self.filters.is_empty() {
+            true => "none".into(),
+            false => self
+                .filters
+                .iter()
+                .map(|(name, value)| format!("{} {}", name, value))
+                .collect::<Vec<String>>()
+                .join(", "),
+        };
+        layout.line(REGULAR, 9.0, format!("Filters: {}", filters));
+        layout.line(REGULAR, 9.0, "".into());
+        let header = format_row(&self.columns);
+        layout.line(MONO_BOLD, TABLE_FONT_SIZE, header.clone());
+        layout.repeated = Some(header);
+        if self.rows.is_empty() {
+            layout.line(MONO, TABLE_FONT_SIZE, "No entries".into());
+        }
+        for row in &self.rows {
+            layout.line(MONO, TABLE_FONT_SIZE, format_row(row));
+        }
+        layout.repeated = None;
+        if !self.summary.is_empty() {
+            layout.line(REGULAR, 9.0, "".into());
+        }
+        for (name, value) in &self.summary {
+            layout.line(BOLD, 9.0, format!("{}: {}", name, value));
+        }
+        return self.write_pdf(layout.pages);
+    }
+
+    /**
+     * Widths of the columns in characters, the widest ones being narrowed until the table fits
+     * onto the page.
+     */
+    fn column_widths(&self) -> Vec<usize> {
+        let available = ((PAGE_WIDTH - 2.0 * MARGIN) / (TABLE_FONT_SIZE * COURIER_WIDTH)) as usize;
+        let mut widths = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|e| e.chars().count())
+                    .fold(name.chars().count(), usize::max)
+            })
+            .collect::<Vec<usize>>();
+        let separators = 2 * widths.len().saturating_sub(1);
+        while widths.iter().sum::<usize>() + separators > available {
+            match widths.iter_mut().max() {
+                Some(widest) if *widest > 4 => *widest -= 1,
+                _ => break,
+            }
+        }
+        return widths;
+    }
+
+    fn write_pdf(&self, pages: Vec<Vec<Text>>) -> Vec<u8> {
+        let catalog_id = Ref::new(1);
+        let page_tree_id = Ref::new(2);
+        let info_id = Ref::new(3);
+        let fonts = [
+            (REGULAR, Ref::new(4), "Helvetica"),
+            (BOLD, Ref::new(5), "Helvetica-Bold"),
+            (MONO, Ref::new(6), "Courier"),
+            (MONO_BOLD, Ref::new(7), "Courier-Bold"),
+        ];
+        let page_ids = (0..pages.len())
+            .map(|i| (Ref::new(8 + 2 * i as i32), Ref::new(9 + 2 * i as i32)))
+            .collect::<Vec<(Ref, Ref)>>();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(page_tree_id);
+        pdf.pages(page_tree_id)
+            .kids(page_ids.iter().map(|(page_id, _)| *page_id))
+            .count(page_ids.len() as i32);
+        pdf.document_info(info_id)
+            .title(TextStr(&self.title))
+            .producer(TextStr("invman"));
+        for (_, id, base_font) in fonts {
+            pdf.type1_font(id)
+                .base_font(Name(base_font.as_bytes()))
+                .encoding_predefined(Name(b"WinAnsiEncoding"));
+        }
+
+        let count = pages.len();
+        for (i, (texts, (page_id, content_id))) in pages.iter().zip(page_ids.iter()).enumerate() {
+            let mut page = pdf.page(*page_id);
+            page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+                .parent(page_tree_id)
+                .contents(*content_id);
+            let mut resources = page.resources();
+            let mut resource_fonts = resources.fonts();
+            for (name, id, _) in fonts {
+                resource_fonts.pair(name, id);
+            }
+            resource_fonts.finish();
+            resources.finish();
+            page.finish();
+
+            let mut content = Content::new();
+            for text in texts {
+                write_text(
+                    &mut content,
+                    text.font,
+                    text.size,
+                    MARGIN,
+                    text.y,
+                    &text.content,
+                );
+            }
+            let footer = format!("{} - Page {} of {}", self.title, i + 1, count);
+            write_text(&mut content, REGULAR, 8.0, MARGIN, MARGIN / 2.0, &footer);
+            pdf.stream(*content_id, &content.finish());
+        }
+        return pdf.finish();
+    }
+}
+
+fn write_text(content: &mut Content, font: Name, size: f32, x: f32, y: f32, text: &str) {
+    content.begin_text();
+    content.set_font(font, size);
+    content.next_line(x, y);
+    content.show(Str(&win_ansi(text)));
+    content.end_text();
+}
+
+/**
+ * Encodes the text for the standard fonts, which only know the characters of Windows-1252.
+ * Characters beyond are replaced by a question mark.
+ */
+fn win_ansi(text: &str) -> Vec<u8> {
+    return text
+        .chars()
+        .map(|c| match c {
+            '\u{20}'..='\u{7e}' | '\u{a0}'..='\u{ff}' => c as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        })
+        .collect();
+}
+
+pub enum ChartKind {
+    Bar,
+    Line,
+}
+
+pub struct ChartPoint {
+    pub label: String,
+    pub value: f64,
+    /**
+     * The value as shown to the reader, e.g. formatted with the configured separators
+     */
+    pub display: String,
+}
+
+pub struct Chart {
+    pub title: String,
+    pub kind: ChartKind,
+    pub points: Vec<ChartPoint>,
+}
+
+/**
+ * Overview of the inventory rendered as a single static HTML page, made up of charts followed
+ * by tables
+ */
+pub struct Dashboard {
+    pub title: String,
+    pub generated_at: String,
+    pub charts: Vec<Chart>,
+    /**
+     * Tables of the page, whose filters and generation time are not shown
+     */
+    pub tables: Vec<ReportDocument>,
+    /**
+     * Separators of the displayed numbers, so that the tables sort them by their value
+     */
+    pub decimal_separator: String,
+    pub thousands_separator: String,
+}
+
+const CHART_WIDTH: f64 = 640.0;
+const CHART_HEIGHT: f64 = 260.0;
+const CHART_LEFT: f64 = 20.0;
+const CHART_TOP: f64 = 20.0;
+const CHART_BOTTOM: f64 = 70.0;
+
+const HTML_STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+h1{margin-bottom:0}.generated{color:#666;margin-top:.3em}\
+.charts{display:flex;flex-wrap:wrap;gap:2em}figure{margin:0}\
+figcaption{font-weight:bold;margin-bottom:.5em}svg text{font-size:11px;fill:#444}\
+table{border-collapse:collapse;margin-bottom:1em}\
+th,td{border:1px solid #ccc;padding:.3em .6em;text-align:left}\
+th{background:#eee;cursor:pointer;user-select:none}\
+th[data-order=asc]::after{content:\" \\25B2\"}th[data-order=desc]::after{content:\" \\25BC\"}\
+tr:nth-child(even) td{background:#fafafa}dt{font-weight:bold}";
+
+/**
+ * Sorts a table by the clicked column, numerically if both cells are numbers
+ */
+const HTML_SCRIPT: &str = "document.querySelectorAll('table.sortable th').forEach(function(th){\
+th.addEventListener('click',function(){\
+var table=th.closest('table'),body=table.tBodies[0];\
+var index=Array.prototype.indexOf.call(th.parentNode.children,th);\
+var ascending=th.dataset.order!=='asc';\
+th.parentNode.querySelectorAll('th').forEach(function(e){delete e.dataset.order;});\
+th.dataset.order=ascending?'asc':'desc';\
+var value=function(row){var text=row.children[index].textContent;\
+var number=text.split(table.dataset.thousands||'\\u0000').join('').replace(table.dataset.decimal,'.');\
+return /^-?\\d+(\\.\\d+)?$/.test(number)?parseFloat(number):text;};\
+Array.from(body.rows).sort(function(a,b){var x=value(a),y=value(b);\
+var order=typeof x==='number'&&typeof y==='number'?x-y:String(x).localeCompare(String(y));\
+return ascending?order:-order;}).forEach(function(row){body.appendChild(row);});});});";
+
+impl Dashboard {
+    /**
+     * Renders the page as self-contained HTML, i.e. with its styles, scripts and charts (as SVG)
+     * inlined, so that it can be opened from any file share.
+     */
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let _ = writeln!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{title}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+             <p class=\"generated\">Generated at {}</p>",
+            HTML_STYLE,
+            escape_html(&self.generated_at),
+            title = escape_html(&self.title),
+        );
+        if !self.charts.is_empty() {
+            html.push_str("<div class=\"charts\">\n");
+            for chart in &self.charts {
+                let _ = writeln!(
+                    html,
+                    "<figure>\n<figcaption>{}</figcaption>\n{}</figure>",
+                    escape_html(&chart.title),
+                    chart.to_svg()
+                );
+            }
+            html.push_str("</div>\n");
+        }
+        for table in &self.tables {
+            let _ = writeln!(html, "<h2>{}</h2>", escape_html(&table.title));
+            if table.rows.is_empty() {
+                html.push_str("<p>No entries</p>\n");
+            } else {
+                let _ = write!(
+                    html,
+                    "<table class=\"sortable\" data-decimal=\"{}\" data-thousands=\"{}\">\n\
+                     <thead>\n<tr>",
+                    escape_html(&self.decimal_separator),
+                    escape_html(&self.thousands_separator)
+                );
+                for column in &table.columns {
+                    let _ = write!(html, "<th>{}</th>", escape_html(column));
+                }
+                html.push_str("</tr>\n</thead>\n<tbody>\n");
+                for row in &table.rows {
+                    html.push_str("<tr>");
+                    for cell in row {
+                        let _ = write!(html, "<td>{}</td>", escape_html(cell));
+                    }
+                    html.push_str("</tr>\n");
+                }
+                html.push_str("</tbody>\n</table>\n");
+            }
+            if !table.summary.is_empty() {
+                html.push_str("<dl>\n");
+                for (name, value) in &table.summary {
+                    let _ = writeln!(
+                        html,
+                        "<dt>{}</dt><dd>{}</dd>",
+                        escape_html(name),
+                        escape_html(value)
+                    );
+                }
+                html.push_str("</dl>\n");
+            }
+        }
+        let _ = writeln!(html, "<script>{}</script>\n</body>\n</html>", HTML_SCRIPT);
+        return html;
+    }
+}
+
+impl Chart {
+    fn to_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\">\n",
+            w = CHART_WIDTH,
+            h = CHART_HEIGHT
+        );
+        if self.points.is_empty() {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\">No data</text>\n</svg>",
+                CHART_LEFT,
+                CHART_TOP + 20.0
+            );
+            return svg;
+        }
+        let width = CHART_WIDTH - 2.0 * CHART_LEFT;
+        let height = CHART_HEIGHT - CHART_TOP - CHART_BOTTOM;
+        let baseline = CHART_TOP + height;
+        let max = self
+            .points
+            .iter()
+            .map(|e| e.value)
+            .fold(0.0, f64::max)
+            .max(f64::MIN_POSITIVE);
+        let y = |value: f64| baseline - height * value.max(0.0) / max;
+        let step = width / self.points.len() as f64;
+        // Labels of crowded charts are thinned out, so that they do not overlap
+        let every = self.points.len().div_ceil(16);
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{l}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#999\"/>",
+            l = CHART_LEFT,
+            r = CHART_LEFT + width,
+            b = baseline
+        );
+        let mut line = vec![];
+        for (i, point) in self.points.iter().enumerate() {
+            let center = CHART_LEFT + step * (i as f64 + 0.5);
+            let tooltip = format!(
+                "<title>{}: {}</title>",
+                escape_html(&point.label),
+                escape_html(&point.display)
+            );
+            match self.kind {
+                ChartKind::Bar => {
+                    let _ = writeln!(
+                        svg,
+                        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+                         fill=\"#4a7ebb\">{}</rect>",
+                        center - step * 0.35,
+                        y(point.value),
+                        step * 0.7,
+                        baseline - y(point.value),
+                        tooltip
+                    );
+                }
+                ChartKind::Line => {
+                    line.push(format!("{:.1},{:.1}", center, y(point.value)));
+                    let _ = writeln!(
+                        svg,
+                        "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"#4a7ebb\">{}</circle>",
+                        center,
+                        y(point.value),
+                        tooltip
+                    );
+                }
+            }
+            if i % every == 0 {
+                let label = match point.label.chars().count() > 14 {
+                    true => format!("{}…", point.label.chars().take(13).collect::<String>()),
+                    false => point.label.clone(),
+                };
+                let _ = writeln!(
+                    svg,
+                    "<text x=\"{x:.1}\" y=\"{y:.1}\" text-anchor=\"end\" \
+                     transform=\"rotate(-40 {x:.1} {y:.1})\">{}</text>",
+                    escape_html(&label),
+                    x = center,
+                    y = baseline + 14.0
+                );
+            }
+        }
+        if !line.is_empty() {
+            let _ = writeln!(
+                svg,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"#4a7ebb\" stroke-width=\"2\"/>",
+                line.join(" ")
+            );
+        }
+        if let Some(highest) = self
+            .points
+            .iter()
+            .max_by(|a, b| a.value.total_cmp(&b.value))
+        {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\">max {}</text>",
+                CHART_LEFT,
+                CHART_TOP - 6.0,
+                escape_html(&highest.display)
+            );
+        }
+        svg.push_str("</svg>\n");
+        return svg;
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    return text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;");
+}
+
+/**
+ * Label of an entity for label printers, made up of lines of text above a barcode
+ */
+pub struct Label {
+    /**
+     * Fields printed on the label, as name and value
+     */
+    pub fields: Vec<(String, String)>,
+    /**
+     * Value encoded by the barcode in Code 128
+     */
+    pub barcode: String,
+    pub copies: u32,
+}
+
+impl Label {
+    /**
+     * Renders the label in ZPL, the language of Zebra printers, which is also understood by many
+     * other label printers.
+     */
+    pub fn to_zpl(&self) -> String {
+        // ^CI28 switches to UTF-8 and ^FH allows escaping the control characters in field data
+        let mut zpl = String::from("^XA\n^CI28\n");
+        let mut y = 30;
+        for (name, value) in &self.fields {
+            let _ = writeln!(
+                zpl,
+                "^FO30,{}^A0N,28,28^FH^FD{}: {}^FS",
+                y,
+                escape_zpl(name),
+                escape_zpl(value)
+            );
+            y += 36;
+        }
+        let _ = writeln!(
+            zpl,
+            "^FO30,{}^BY2^BCN,80,Y,N,N^FH^FD{}^FS",
+            y + 10,
+            escape_zpl(&self.barcode)
+        );
+        let _ = write!(zpl, "^PQ{}\n^XZ", self.copies);
+        return zpl;
+    }
+}
+
+/**
+ * Escapes the characters that would be read as commands, as hexadecimal notation of ^FH
+ */
+fn escape_zpl(text: &str) -> String {
+    return text
+        .replace('_', "_5F")
+        .replace('^', "_5E")
+        .replace('~', "_7E")
+        .replace(['\r', '\n'], " ");
+}
@@ -0,0 +1,268 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::{
+    common::args::{CommandContext, InventoryAddArgs, InventoryListArgs, OutputType},
+    database::{AppConfig, DBUser, InvManConnection, InvManDBPool, InvManSqlite},
+};
+use anyhow::{anyhow, bail, Result};
+use std::{
+    ffi::{c_char, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+};
+
+/**
+ * Database opened by invman_open, together with the user all calls are made as. C callers only
+ * get a pointer to it.
+ */
+pub struct InvMan {
+    db: InvManSqlite,
+    config: AppConfig,
+    user: DBUser,
+}
+
+impl InvMan {
+    fn open(path: &str, auth: &str) -> Result<InvMan> {
+        let mut db = InvManConnection::sqlite_database(path)?;
+        let mut config = db.get_config()?;
+        let user = {
+            let mut ctx =
+                CommandContext::new(&mut db, &mut config, Some(auth.into()), OutputType::Json);
+            ctx.start_session()?;
+            ctx.session.take().unwrap()
+        };
+        return Ok(InvMan { db, config, user });
+    }
+
+    /**
+     * Runs the command as the user of the session, like a line of the shell.
+     */
+    fn run(&mut self, command: impl FnOnce(&mut CommandContext) -> Result<String>) -> String {
+        let mut ctx = CommandContext::new(&mut self.db, &mut self.config, None, OutputType::Json);
+        ctx.session = Some(self.user.clone());
+        // Unwinding into C is undefined behavior, so panics are reported like errors
+        let result = catch_unwind(AssertUnwindSafe(|| command(&mut ctx)))
+            .unwrap_or_else(|_| Err(anyhow!("invman panicked")));
+        let result = match result {
+            // Responses in JSON are embedded as they are, anything else as string
+            Ok(response) => serde_json::json!({
+                "ok": true,
+                "result": serde_json::from_str::<serde_json::Value>(&response)
+                    .unwrap_or(serde_json::Value::String(response)),
+            }),
+            Err(e) => serde_json::json!({
+                "ok": false,
+                "error": e.to_string(),
+            }),
+        };
+        return result.to_string();
+    }
+}
+
+/**
+ * Converts an entity given as JSON object into name=value notation.
+ */
+fn json_to_params(json: &str) -> Result<Vec<String>> {
+    let entity = match serde_json::from_str::<serde_json::Value>(json)? {
+        serde_json::Value::Object(entity) => entity,
+        _ => bail!("Entity must be a JSON object"),
+    };
+    return entity
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(name, value)| match value {
+            serde_json::Value::String(value) => Ok(format!("{}={}", name, value)),
+            serde_json::Value::Bool(_) | serde_json::Value::Number(_) => {
+                Ok(format!("{}={}", name, value))
+            }
+            _ => bail!("Value of {} must be a string, number or bool", name),
+        })
+        .collect();
+}
+
+unsafe fn to_str<'a>(value: *const c_char) -> Result<&'a str> {
+    if value.is_null() {
+        bail!("Argument must not be NULL");
+    }
+    return Ok(CStr::from_ptr(value).to_str()?);
+}
+
+fn to_c_string(value: String) -> *mut c_char {
+    // JSON escapes NUL characters, so the conversion never fails for the responses
+    return CString::new(value).map_or(ptr::null_mut(), CString::into_raw);
+}
+
+/**
+ * Opens the database at the path, which is created if it does not exist, and authenticates
+ * the user by username:password or token, as given to --auth.
+ *
+ * @returns The handle to pass to the other functions, NULL if opening or authenticating failed
+ *
+ * # Safety
+ * Both arguments must be NUL terminated strings.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn invman_open(path: *const c_char, auth: *const c_char) -> *mut InvMan {
+    let result = catch_unwind(|| InvMan::open(to_str(path)?, to_str(auth)?));
+    return match result {
+        Ok(Ok(invman)) => Box::into_raw(Box::new(invman)),
+        Ok(Err(e)) => {
+            tracing::warn!(error = %e, "Could not open invman");
+            ptr::null_mut()
+        }
+        Err(_) => ptr::null_mut(),
+    };
+}
+
+/**
+ * Closes the database of the handle, which must not be used afterwards.
+ *
+ * # Safety
+ * The handle must be returned by invman_open and not be closed already, or be NULL.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn invman_close(invman: *mut InvMan) {
+    if !invman.is_null() {
+        drop(Box::from_raw(invman));
+    }
+}
+
+/**
+ * Adds the entity given as JSON object, e.g. {"sku":"A1","weight":4}.
+ *
+ * @returns {"ok":true,"result":...} or {"ok":false,"error":"..."}, freed with invman_free
+ *
+ * # Safety
+ * The handle must be open and not be used by another thread at the same time, the entity must
+ * be a NUL terminated string.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn invman_add_json(
+    invman: *mut InvMan,
+    entity: *const c_char,
+) -> *mut c_char {
+    let invman = match invman.as_mut() {
+        Some(invman) => invman,
+        None => return ptr::null_mut(),
+    };
+    let entity = to_str(entity);
+    return to_c_string(invman.run(|ctx| {
+        return (InventoryAddArgs {
+            params: json_to_params(entity?)?,
+            positional: false,
+            allow_extra: false,
+            validate_only: false,
+        })
+        .add(ctx);
+    }));
+}
+
+/**
+ * Lists the entities with an identifier greater than after_id, at most limit of them. A limit
+ * of 0 or less lists all of them.
+ *
+ * @returns {"ok":true,"result":[...]} or {"ok":false,"error":"..."}, freed with invman_free
+ *
+ * # Safety
+ * The handle must be open and not be used by another thread at the same time.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn invman_list_json(
+    invman: *mut InvMan,
+    limit: i32,
+    after_id: i64,
+) -> *mut c_char {
+    let invman = match invman.as_mut() {
+        Some(invman) => invman,
+        None => return ptr::null_mut(),
+    };
+    return to_c_string(invman.run(|ctx| {
+        let list = InventoryListArgs {
+            limit: Some(limit).filter(|e| *e > 0),
+            after_id: Some(after_id).filter(|e| *e > 0),
+            fuzzy: None,
+            fuzzy_threshold: 0.6,
+            with_audit: false,
+            with_attachments: false,
+            include_archived: false,
+            supplier: None,
+            assigned_to: None,
+            category: None,
+            recursive: false,
+            sort: vec![],
+            raw: None,
+            params: vec![],
+            filter: None,
+            group_by: vec![],
+            aggregate: None,
+            expand: vec![],
+            condition: vec![],
+            explain: false,
+        }
+        .list(ctx)?;
+        // Paging wraps the entities into an envelope, which is unwrapped to keep one format
+        return match after_id > 0 {
+            true => Ok(serde_json::from_str::<serde_json::Value>(&list)?["data"].to_string()),
+            false => Ok(list),
+        };
+    }));
+}
+
+/**
+ * Frees a string returned by invman.
+ *
+ * # Safety
+ * The string must be returned by invman and not be freed already, or be NULL.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn invman_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::args::ReadOnlySetArgs;
+
+    #[test]
+    fn read_only_databases_are_not_written() {
+        let dir = std::env::temp_dir().join(format!("invman-ffi-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("storage").to_string_lossy().into_owned();
+        {
+            let mut db = InvManConnection::sqlite_database(&path).unwrap();
+            db.user_register("admin", "pw").unwrap();
+            let mut config = db.get_config().unwrap();
+            let mut ctx = CommandContext::new(
+                &mut db,
+                &mut config,
+                Some("admin:pw".into()),
+                OutputType::Json,
+            );
+            ReadOnlySetArgs { read_only: true }.set(&mut ctx).unwrap();
+        }
+        let mut invman = InvMan::open(&path, "admin:pw").unwrap();
+        let written = invman.run(|ctx| ctx.check_writable().map(|_| String::new()));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(written.contains("\"ok\":false"), "{}", written);
+    }
+}
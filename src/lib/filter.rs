@@ -0,0 +1,697 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::{common::args::ColumnType, database::AppConfig};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/**
+ * Columns every entity has, next to the ones of the schema declaration
+ */
+const MANAGED_COLUMNS: [&str; 4] = ["id", "created_at", "updated_at", "deleted_at"];
+
+/**
+ * Operators are matched longest first, so that >= is not read as > followed by =
+ */
+const OPERATORS: [(&str, CompareOp); 7] = [
+    ("!=", CompareOp::Ne),
+    (">=", CompareOp::Ge),
+    ("<=", CompareOp::Le),
+    ("=", CompareOp::Eq),
+    (">", CompareOp::Gt),
+    ("<", CompareOp::Lt),
+    ("~", CompareOp::Like),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    /**
+     * SQL LIKE, where % matches any run of characters and _ a single one
+     */
+    Like,
+}
+
+impl CompareOp {
+    fn sql(&self) -> &'static str {
+        return match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "!=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Like => "LIKE",
+        };
+    }
+}
+
+/**
+ * Parsed --where expression of inventory list, e.g.
+ * quantity >= 10 and (location ~ 'North%' or price is null)
+ *
+ * Values are kept as text and bound as parameters, so that the column affinity decides how
+ * they are compared.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WhereExpr {
+    And(Vec<WhereExpr>),
+    Or(Vec<WhereExpr>),
+    Compare {
+        column: String,
+        operator: CompareOp,
+        value: String,
+    },
+    In {
+        column: String,
+        values: Vec<String>,
+    },
+    Between {
+        column: String,
+        low: String,
+        high: String,
+    },
+    IsNull {
+        column: String,
+        negated: bool,
+    },
+}
+
+impl WhereExpr {
+    pub fn parse(expr: &str) -> Result<WhereExpr> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?,
+            pos: 0,
+        };
+        if parser.tokens.is_empty() {
+            bail!("The where expression is empty");
+        }
+        let parsed = parser.or()?;
+        if let Some(token) = parser.next() {
+            bail!("Unexpected {} in where expression", token.describe());
+        }
+        return Ok(parsed);
+    }
+
+    /**
+     * All the columns the expression compares, in order of appearance
+     */
+    pub fn columns(&self) -> Vec<&str> {
+        return match self {
+            WhereExpr::And(terms) | WhereExpr::Or(terms) => {
+                terms.iter().flat_map(|e| e.columns()).collect()
+            }
+            WhereExpr::Compare { column, .. }
+            | WhereExpr::In { column, .. }
+            | WhereExpr::Between { column, .. }
+            | WhereExpr::IsNull { column, .. } => vec![column.as_str()],
+        };
+    }
+
+    /**
+     * Ensures that only columns of the inventory are referenced, as their names end up in the
+     * SQL as they are
+     */
+    pub fn check_columns(&self, config: &AppConfig) -> Result<()> {
+        for column in self.columns() {
+            column_type(config, column)?;
+        }
+        return Ok(());
+    }
+
+    /**
+     * Compiles the expression into a condition, pushing the values onto the positional params
+     */
+    pub fn to_sql(&self, params: &mut Vec<String>) -> String {
+        return match self {
+            WhereExpr::And(terms) | WhereExpr::Or(terms) => {
+                let separator = match self {
+                    WhereExpr::And(_) => " AND ",
+                    _ => " OR ",
+                };
+                let terms = terms
+                    .iter()
+                    .map(|e| e.to_sql(params))
+                    .collect::<Vec<String>>();
+                format!("({})", terms.join(separator))
+            }
+            WhereExpr::Compare {
+                column,
+                operator,
+                value,
+            } => format!("{} {} {}", column, operator.sql(), bind(params, value)),
+            WhereExpr::In { column, values } => {
+                let values = values
+                    .iter()
+                    .map(|e| bind(params, e))
+                    .collect::<Vec<String>>();
+                format!("{} IN ({})", column, values.join(", "))
+            }
+            WhereExpr::Between { column, low, high } => {
+                format!(
+                    "{} BETWEEN {} AND {}",
+                    column,
+                    bind(params, low),
+                    bind(params, high)
+                )
+            }
+            WhereExpr::IsNull { column, negated } => match negated {
+                true => format!("{} IS NOT NULL", column),
+                false => format!("{} IS NULL", column),
+            },
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AggregateFn {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/**
+ * Aggregate function over a column, or over the rows for count(*)
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub function: AggregateFn,
+    pub column: Option<String>,
+}
+
+impl Aggregate {
+    /**
+     * Key of the aggregate in the grouped rows, e.g. sum_quantity, or count for count(*)
+     */
+    pub fn name(&self) -> String {
+        let function = format!("{:?}", self.function).to_lowercase();
+        return match &self.column {
+            Some(column) => format!("{}_{}", function, column),
+            None => function,
+        };
+    }
+
+    fn sql(&self) -> String {
+        let function = format!("{:?}", self.function).to_uppercase();
+        return format!(
+            "{}({}) AS {}",
+            function,
+            self.column.as_deref().unwrap_or("*"),
+            self.name()
+        );
+    }
+
+    fn column_type(&self, config: &AppConfig) -> Result<ColumnType> {
+        let column = match &self.column {
+            Some(column) => column,
+            None if self.function == AggregateFn::Count => return Ok(ColumnType::INT),
+            None => bail!("Only count can be used with *"),
+        };
+        let column_type = column_type(config, column)?;
+        return Ok(match self.function {
+            AggregateFn::Count => ColumnType::INT,
+            // Amounts are stored together with their currency, which would compare as text
+            AggregateFn::Min | AggregateFn::Max if column_type == ColumnType::MONEY => bail!(
+                "Cannot {} money column {}",
+                format!("{:?}", self.function).to_lowercase(),
+                column
+            ),
+            AggregateFn::Min | AggregateFn::Max => column_type,
+            AggregateFn::Sum | AggregateFn::Avg => {
+                if !matches!(column_type, ColumnType::INT | ColumnType::REAL) {
+                    bail!(
+                        "Cannot {} column {} as it is not an int or real column",
+                        format!("{:?}", self.function).to_lowercase(),
+                        column
+                    );
+                }
+                match self.function {
+                    AggregateFn::Sum => column_type,
+                    _ => ColumnType::REAL,
+                }
+            }
+        });
+    }
+}
+
+/**
+ * Groups the listed entities by the columns, returning one row per group with the values of
+ * the columns and the aggregates
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Grouping {
+    pub columns: Vec<String>,
+    pub aggregates: Vec<Aggregate>,
+}
+
+impl Grouping {
+    /**
+     * Parses aggregates like sum(quantity),avg(price), counting the rows if none are given
+     */
+    pub fn parse(columns: &[String], aggregates: Option<&str>) -> Result<Grouping> {
+        let mut parsed = vec![];
+        for aggregate in aggregates.unwrap_or("count(*)").split(',') {
+            let aggregate = aggregate.trim();
+            let (function, column) =
+                match aggregate.strip_suffix(')').and_then(|e| e.split_once('(')) {
+                    Some((function, column)) => (function.trim(), column.trim()),
+                    None => bail!(
+                        "Aggregate '{}' must be written as function(column), e.g. sum(quantity)",
+                        aggregate
+                    ),
+                };
+            let function = match function.to_lowercase().as_str() {
+                "count" => AggregateFn::Count,
+                "sum" => AggregateFn::Sum,
+                "avg" => AggregateFn::Avg,
+                "min" => AggregateFn::Min,
+                "max" => AggregateFn::Max,
+                _ => bail!(
+                    "Unknown aggregate function {}, use count, sum, avg, min or max",
+                    function
+                ),
+            };
+            parsed.push(Aggregate {
+                function,
+                column: match column {
+                    "*" => None,
+                    column => Some(column.to_string()),
+                },
+            });
+        }
+        return Ok(Grouping {
+            columns: columns.to_vec(),
+            aggregates: parsed,
+        });
+    }
+
+    /**
+     * All the columns the grouping reads, in order of appearance
+     */
+    pub fn columns(&self) -> Vec<&str> {
+        let aggregated = self.aggregates.iter().filter_map(|e| e.column.as_deref());
+        return self
+            .columns
+            .iter()
+            .map(|e| e.as_str())
+            .chain(aggregated)
+            .collect();
+    }
+
+    /**
+     * Names and types of the columns of the grouped rows, which fails on columns and functions
+     * that do not fit the schema, as their names end up in the SQL as they are
+     */
+    pub fn output_columns(&self, config: &AppConfig) -> Result<Vec<(String, ColumnType)>> {
+        let mut output = vec![];
+        for column in self.columns.iter() {
+            output.push((column.clone(), column_type(config, column)?));
+        }
+        for aggregate in self.aggregates.iter() {
+            let name = aggregate.name();
+            if output.iter().any(|(e, _)| *e == name) {
+                bail!("Aggregate {} is given more than once", name);
+            }
+            output.push((name, aggregate.column_type(config)?));
+        }
+        return Ok(output);
+    }
+
+    /**
+     * Wraps the query of the listed entities, limiting the number of groups instead
+     */
+    pub fn to_sql(&self, query: &str, limit: i32) -> String {
+        let mut selected = self.columns.clone();
+        selected.extend(self.aggregates.iter().map(|e| e.sql()));
+        let mut sql = format!("SELECT {} FROM ({})", selected.join(", "), query);
+        if !self.columns.is_empty() {
+            sql.push_str(&format!(
+                " GROUP BY {cols} ORDER BY {cols}",
+                cols = self.columns.join(", ")
+            ));
+        }
+        if limit > 0 {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        return sql;
+    }
+}
+
+/**
+ * Type of a column of the inventory, failing if there is no such column
+ */
+fn column_type(config: &AppConfig, column: &str) -> Result<ColumnType> {
+    if MANAGED_COLUMNS.contains(&column) {
+        return Ok(match column {
+            "id" => ColumnType::INT,
+            _ => ColumnType::TEXT,
+        });
+    }
+    return match config
+        .inventory_schema_declaration
+        .collection
+        .iter()
+        .find(|e| e.name == column)
+    {
+        Some(decl) => Ok(decl.column_type),
+        None => bail!("Column {} is not part of the schema", column),
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Comma,
+    Operator(CompareOp),
+    Word(String),
+    Quoted(String),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        return match self {
+            Token::Open => "'('".into(),
+            Token::Close => "')'".into(),
+            Token::Comma => "','".into(),
+            Token::Operator(op) => format!("operator '{}'", op.sql()),
+            Token::Word(word) => format!("'{}'", word),
+            Token::Quoted(value) => format!("string '{}'", value),
+        };
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>> {
+    let chars = expr.chars().collect::<Vec<char>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' | ')' | ',' => {
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Comma,
+                });
+                i += 1;
+            }
+            '\'' | '"' => {
+                // A doubled quote stands for the quote itself, like in SQL
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => bail!("Unterminated string in where expression"),
+                        Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                            value.push(c);
+                            i += 2;
+                        }
+                        Some(&q) if q == c => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&ch) => {
+                            value.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            _ => {
+                let rest = chars[i..].iter().collect::<String>();
+                if let Some((op, operator)) = OPERATORS.iter().find(|(op, _)| rest.starts_with(op))
+                {
+                    tokens.push(Token::Operator(*operator));
+                    i += op.len();
+                    continue;
+                }
+                let start = i;
+                while i < chars.len()
+                    && !chars[i].is_whitespace()
+                    && !"(),'\"=!<>~".contains(chars[i])
+                {
+                    i += 1;
+                }
+                if start == i {
+                    bail!("Unexpected character '{}' in where expression", c);
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect()));
+            }
+        }
+    }
+    return Ok(tokens);
+}
+
+/**
+ * Recursive descent over the tokens, where AND binds tighter than OR
+ */
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        return token;
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => return Ok(()),
+            Some(token) => bail!(
+                "Expected {} in where expression, found {}",
+                expected.describe(),
+                token.describe()
+            ),
+            None => bail!(
+                "Where expression ends where {} is expected",
+                expected.describe()
+            ),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if let Some(Token::Word(word)) = self.tokens.get(self.pos) {
+            if word.eq_ignore_ascii_case(keyword) {
+                self.pos += 1;
+                return true;
+            }
+        }
+        return false;
+    }
+
+    fn or(&mut self) -> Result<WhereExpr> {
+        let mut terms = vec![self.and()?];
+        while self.keyword("or") {
+            terms.push(self.and()?);
+        }
+        return Ok(match terms.len() {
+            1 => terms.remove(0),
+            _ => WhereExpr::Or(terms),
+        });
+    }
+
+    fn and(&mut self) -> Result<WhereExpr> {
+        let mut terms = vec![self.primary()?];
+        while self.keyword("and") {
+            terms.push(self.primary()?);
+        }
+        return Ok(match terms.len() {
+            1 => terms.remove(0),
+            _ => WhereExpr::And(terms),
+        });
+    }
+
+    fn primary(&mut self) -> Result<WhereExpr> {
+        let column = match self.next() {
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                self.expect(Token::Close)?;
+                return Ok(inner);
+            }
+            Some(Token::Word(word)) if is_identifier(&word) => word,
+            Some(token) => bail!(
+                "Expected a column name in where expression, found {}",
+                token.describe()
+            ),
+            None => bail!("Where expression ends where a column name is expected"),
+        };
+        if self.keyword("in") {
+            self.expect(Token::Open)?;
+            let mut values = vec![self.value()?];
+            while self.tokens.get(self.pos) == Some(&Token::Comma) {
+                self.pos += 1;
+                values.push(self.value()?);
+            }
+            self.expect(Token::Close)?;
+            return Ok(WhereExpr::In { column, values });
+        }
+        if self.keyword("between") {
+            let low = self.value()?;
+            if !self.keyword("and") {
+                bail!(
+                    "Expected 'and' after the lower bound of between on {}",
+                    column
+                );
+            }
+            let high = self.value()?;
+            return Ok(WhereExpr::Between { column, low, high });
+        }
+        if self.keyword("is") {
+            let negated = self.keyword("not");
+            if !self.keyword("null") {
+                bail!("Expected null after 'is' on {}", column);
+            }
+            return Ok(WhereExpr::IsNull { column, negated });
+        }
+        return match self.next() {
+            Some(Token::Operator(operator)) => Ok(WhereExpr::Compare {
+                column,
+                operator,
+                value: self.value()?,
+            }),
+            _ => bail!("Expected an operator after column {}", column),
+        };
+    }
+
+    fn value(&mut self) -> Result<String> {
+        return match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => Ok(value),
+            Some(token) => bail!(
+                "Expected a value in where expression, found {}",
+                token.describe()
+            ),
+            None => bail!("Where expression ends where a value is expected"),
+        };
+    }
+}
+
+fn bind(params: &mut Vec<String>, value: &str) -> String {
+    params.push(value.into());
+    return format!("?{}", params.len());
+}
+
+fn is_identifier(word: &str) -> bool {
+    return word
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sql(expr: &str) -> (String, Vec<String>) {
+        let mut params = vec![];
+        let sql = WhereExpr::parse(expr).unwrap().to_sql(&mut params);
+        return (sql, params);
+    }
+
+    #[test]
+    fn comparisons_bind_their_values() {
+        for (expr, expected) in [
+            ("quantity = 5", "quantity = ?1"),
+            ("quantity!=5", "quantity != ?1"),
+            ("quantity >= 5", "quantity >= ?1"),
+            ("quantity<=5", "quantity <= ?1"),
+            ("quantity > 5", "quantity > ?1"),
+            ("quantity < 5", "quantity < ?1"),
+            ("name ~ 'Dr%'", "name LIKE ?1"),
+        ] {
+            assert_eq!(sql(expr).0, expected, "{}", expr);
+        }
+        assert_eq!(sql("name ~ 'Dr%'").1, vec!["Dr%"]);
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            sql("a = 1 or b = 2 AND c = 3"),
+            (
+                "(a = ?1 OR (b = ?2 AND c = ?3))".into(),
+                vec!["1".into(), "2".into(), "3".into()]
+            )
+        );
+        assert_eq!(
+            sql("(a = 1 or b = 2) and c = 3").0,
+            "((a = ?1 OR b = ?2) AND c = ?3)"
+        );
+    }
+
+    #[test]
+    fn in_between_and_null_operators() {
+        assert_eq!(
+            sql("location in (North, 'South 2', East)"),
+            (
+                "location IN (?1, ?2, ?3)".into(),
+                vec!["North".into(), "South 2".into(), "East".into()]
+            )
+        );
+        assert_eq!(sql("price between 1 and 10").0, "price BETWEEN ?1 AND ?2");
+        assert_eq!(sql("price is null").0, "price IS NULL");
+        assert_eq!(sql("price IS NOT NULL").0, "price IS NOT NULL");
+    }
+
+    #[test]
+    fn doubled_quotes_stand_for_the_quote() {
+        assert_eq!(sql("note = 'it''s fine'").1, vec!["it's fine"]);
+        assert_eq!(sql(r#"note = "say ""hi""""#).1, vec![r#"say "hi""#]);
+    }
+
+    #[test]
+    fn columns_are_listed_in_order() {
+        let expr = WhereExpr::parse("a = 1 and (b in (1) or c is null)").unwrap();
+        assert_eq!(expr.columns(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        for expr in [
+            "",
+            "quantity",
+            "quantity =",
+            "quantity = 1 and",
+            "(quantity = 1",
+            "quantity = 1)",
+            "quantity in (1, 2",
+            "price between 1 10",
+            "price is 5",
+            "'name' = 1",
+            "name; = 1",
+            "name = 'open",
+            "a = 1 b = 2",
+        ] {
+            assert!(WhereExpr::parse(expr).is_err(), "{}", expr);
+        }
+    }
+}
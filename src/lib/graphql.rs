@@ -0,0 +1,1622 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::database::SchemaCollection;
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Map, Number, Value};
+use std::{collections::BTreeMap, fmt::Display};
+
+/**
+ * Punctuators are matched longest first, so that ... is not read as three dots
+ */
+const PUNCTUATORS: [&str; 14] = [
+    "...", "!", "$", "&", "(", ")", ":", "=", "@", "[", "]", "{", "|", "}",
+];
+
+const SCALARS: [&str; 5] = ["ID", "Int", "Float", "String", "Boolean"];
+
+/**
+ * Operation as POSTed by GraphQL clients
+ */
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GraphqlRequest {
+    pub query: String,
+    pub operation_name: Option<String>,
+    pub variables: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TypeRef {
+    Named(String),
+    List(Box<TypeRef>),
+    NonNull(Box<TypeRef>),
+}
+
+impl TypeRef {
+    fn named(name: &str) -> TypeRef {
+        return TypeRef::Named(name.into());
+    }
+
+    fn required(name: &str) -> TypeRef {
+        return TypeRef::NonNull(Box::new(TypeRef::named(name)));
+    }
+
+    /**
+     * The type without the lists and non-null markers around it
+     */
+    fn name(&self) -> &str {
+        return match self {
+            TypeRef::Named(name) => name,
+            TypeRef::List(inner) | TypeRef::NonNull(inner) => inner.name(),
+        };
+    }
+}
+
+impl Display for TypeRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            TypeRef::Named(name) => write!(f, "{}", name),
+            TypeRef::List(inner) => write!(f, "[{}]", inner),
+            TypeRef::NonNull(inner) => write!(f, "{}!", inner),
+        };
+    }
+}
+
+struct FieldDef {
+    description: String,
+    name: String,
+    arguments: Vec<(String, TypeRef)>,
+    field_type: TypeRef,
+}
+
+impl FieldDef {
+    fn new(name: &str, field_type: TypeRef) -> FieldDef {
+        return FieldDef {
+            description: String::new(),
+            name: name.into(),
+            arguments: vec![],
+            field_type,
+        };
+    }
+
+    fn with_arguments(mut self, arguments: &[(&str, TypeRef)]) -> FieldDef {
+        self.arguments = arguments
+            .iter()
+            .map(|(name, arg_type)| (name.to_string(), arg_type.clone()))
+            .collect();
+        return self;
+    }
+}
+
+struct TypeDef {
+    name: String,
+    /**
+     * Input types are only given as arguments, object types only returned
+     */
+    input: bool,
+    fields: Vec<FieldDef>,
+}
+
+/**
+ * Object and input types, together with the root types of the operations
+ */
+pub(crate) struct Schema {
+    types: Vec<TypeDef>,
+}
+
+impl Schema {
+    /**
+     * Describes the entities of the inventory together with the queries, mutations and
+     * subscriptions on them. As GraphQL names cannot contain dashes, those of the columns are
+     * replaced by underscores.
+     */
+    pub(crate) fn inventory(declarations: &SchemaCollection) -> Schema {
+        let mut entity = vec![
+            FieldDef::new("id", TypeRef::required("ID")),
+            FieldDef::new("created_at", TypeRef::required("String")),
+            FieldDef::new("updated_at", TypeRef::required("String")),
+        ];
+        let mut input = vec![];
+        for column in declarations.collection.iter() {
+            let (name, column_type) = column.to_graphql();
+            let field_type = match column.nullable {
+                true => TypeRef::named(column_type),
+                false => TypeRef::required(column_type),
+            };
+            entity.push(FieldDef {
+                description: column.hint.clone(),
+                name: name.clone(),
+                arguments: vec![],
+                field_type,
+            });
+            input.push(FieldDef {
+                description: column.hint.clone(),
+                name,
+                arguments: vec![],
+                field_type: TypeRef::named(column_type),
+            });
+        }
+        let entities = TypeRef::NonNull(Box::new(TypeRef::List(Box::new(TypeRef::required(
+            "Entity",
+        )))));
+        let object = |name: &str, fields: Vec<FieldDef>| TypeDef {
+            name: name.into(),
+            input: false,
+            fields,
+        };
+        return Schema {
+            types: vec![
+                object("Entity", entity),
+                TypeDef {
+                    name: "EntityInput".into(),
+                    input: true,
+                    fields: input,
+                },
+                object(
+                    "Event",
+                    vec![
+                        FieldDef::new("id", TypeRef::required("Int")),
+                        FieldDef::new("event", TypeRef::required("String")),
+                        FieldDef::new("dispatcher", TypeRef::named("String")),
+                        FieldDef::new("target", TypeRef::named("Int")),
+                        FieldDef::new("reason", TypeRef::named("String")),
+                        FieldDef::new("created_at", TypeRef::required("String")),
+                        FieldDef::new("entity", TypeRef::named("Entity")),
+                    ],
+                ),
+                object(
+                    "Query",
+                    vec![
+                        FieldDef::new("entity", TypeRef::named("Entity"))
+                            .with_arguments(&[("id", TypeRef::required("ID"))]),
+                        FieldDef::new("entities", entities).with_arguments(&[
+                            ("filter", TypeRef::named("EntityInput")),
+                            ("limit", TypeRef::named("Int")),
+                            ("after_id", TypeRef::named("Int")),
+                        ]),
+                    ],
+                ),
+                object(
+                    "Mutation",
+                    vec![
+                        FieldDef::new("add", TypeRef::required("Entity"))
+                            .with_arguments(&[("values", TypeRef::required("EntityInput"))]),
+                        FieldDef::new("edit", TypeRef::required("Entity")).with_arguments(&[
+                            ("id", TypeRef::required("ID")),
+                            ("values", TypeRef::required("EntityInput")),
+                        ]),
+                        FieldDef::new("remove", TypeRef::required("Boolean")).with_arguments(&[
+                            ("id", TypeRef::required("ID")),
+                            ("reason", TypeRef::named("String")),
+                        ]),
+                    ],
+                ),
+                object(
+                    "Subscription",
+                    vec![FieldDef::new("events", TypeRef::required("Event"))
+                        .with_arguments(&[("event", TypeRef::named("String"))])],
+                ),
+            ],
+        };
+    }
+
+    /**
+     * The schema in the schema definition language
+     */
+    pub(crate) fn sdl(&self) -> String {
+        return self
+            .types
+            .iter()
+            .map(|type_def| {
+                let fields = type_def
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let description = match field.description.is_empty() {
+                            true => String::new(),
+                            false => format!("  \"{}\"\n", field.description.replace('"', "\\\"")),
+                        };
+                        let arguments = match field.arguments.is_empty() {
+                            true => String::new(),
+                            false => format!(
+                                "({})",
+                                field
+                                    .arguments
+                                    .iter()
+                                    .map(|(name, arg_type)| format!("{}: {}", name, arg_type))
+                                    .collect::<Vec<String>>()
+                                    .join(", ")
+                            ),
+                        };
+                        format!(
+                            "{}  {}{}: {}",
+                            description, field.name, arguments, field.field_type
+                        )
+                    })
+                    .collect::<Vec<String>>();
+                let keyword = match type_def.input {
+                    true => "input",
+                    false => "type",
+                };
+                format!(
+                    "{} {} {{\n{}\n}}",
+                    keyword,
+                    type_def.name,
+                    fields.join("\n")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n");
+    }
+
+    fn get(&self, name: &str) -> Option<&TypeDef> {
+        return self.types.iter().find(|e| e.name == name);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punctuator(&'static str),
+    Name(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        return match self {
+            Token::Punctuator(punctuator) => format!("'{}'", punctuator),
+            Token::Name(name) => format!("'{}'", name),
+            Token::Int(value) => format!("number {}", value),
+            Token::Float(value) => format!("number {}", value),
+            Token::Str(value) => format!("string \"{}\"", value),
+        };
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() || c == ',' || c == '\u{feff}' {
+            i += 1;
+            continue;
+        }
+        if c == '#' {
+            while i < chars.len() && chars[i] != '\n' && chars[i] != '\r' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '"' {
+            let (value, end) = match chars[i..].starts_with(&['"', '"', '"']) {
+                true => block_string(&chars, i + 3)?,
+                false => string(&chars, i + 1)?,
+            };
+            tokens.push(Token::Str(value));
+            i = end;
+            continue;
+        }
+        if c == '-' || c.is_ascii_digit() {
+            let start = i;
+            let mut float = false;
+            i += 1;
+            while i < chars.len() {
+                match chars[i] {
+                    '0'..='9' => {}
+                    '.' | 'e' | 'E' => float = true,
+                    '+' | '-' if matches!(chars[i - 1], 'e' | 'E') => {}
+                    _ => break,
+                }
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let digits = text.trim_start_matches('-');
+            let leading_zero = digits.len() > 1
+                && digits.starts_with('0')
+                && digits.chars().nth(1).is_some_and(|e| e.is_ascii_digit());
+            let followed_by_name = chars
+                .get(i)
+                .is_some_and(|e| *e == '_' || e.is_ascii_alphabetic());
+            let token = match float {
+                true => text.parse::<f64>().ok().map(Token::Float),
+                false => text.parse::<i64>().ok().map(Token::Int),
+            };
+            match token {
+                Some(token) if !leading_zero && !followed_by_name => tokens.push(token),
+                _ => bail!("Invalid number {} in GraphQL document", text),
+            }
+            continue;
+        }
+        if c == '_' || c.is_ascii_alphabetic() {
+            let start = i;
+            while i < chars.len() && (chars[i] == '_' || chars[i].is_ascii_alphanumeric()) {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+            continue;
+        }
+        let rest = chars[i..].iter().take(3).collect::<String>();
+        match PUNCTUATORS.iter().find(|e| rest.starts_with(*e)) {
+            Some(punctuator) => {
+                tokens.push(Token::Punctuator(punctuator));
+                i += punctuator.len();
+            }
+            None => bail!("Unexpected character '{}' in GraphQL document", c),
+        }
+    }
+    return Ok(tokens);
+}
+
+/**
+ * @returns The value of the string starting at the index and the index after it
+ */
+fn string(chars: &[char], mut i: usize) -> Result<(String, usize)> {
+    let mut value = String::new();
+    loop {
+        match chars.get(i) {
+            None | Some('\n') | Some('\r') => bail!("Unterminated string in GraphQL document"),
+            Some('"') => return Ok((value, i + 1)),
+            Some('\\') => {
+                let escaped = match chars.get(i + 1) {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('/') => '/',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('u') => {
+                        let hex: String = chars.iter().skip(i + 2).take(4).collect();
+                        let escaped = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == 4)
+                            .and_then(char::from_u32);
+                        match escaped {
+                            Some(escaped) => {
+                                value.push(escaped);
+                                i += 6;
+                                continue;
+                            }
+                            None => bail!("Invalid unicode escape \\u{} in GraphQL document", hex),
+                        }
+                    }
+                    _ => bail!("Invalid escape sequence in GraphQL document"),
+                };
+                value.push(escaped);
+                i += 2;
+            }
+            Some(c) => {
+                value.push(*c);
+                i += 1;
+            }
+        }
+    }
+}
+
+/**
+ * Reads a """block string""", whose lines are stripped of their common indentation and of
+ * the blank lines around them
+ */
+fn block_string(chars: &[char], mut i: usize) -> Result<(String, usize)> {
+    let mut raw = String::new();
+    loop {
+        if i >= chars.len() {
+            bail!("Unterminated block string in GraphQL document");
+        }
+        if chars[i..].starts_with(&['"', '"', '"']) {
+            break;
+        }
+        if chars[i..].starts_with(&['\\', '"', '"', '"']) {
+            raw.push_str("\"\"\"");
+            i += 4;
+            continue;
+        }
+        raw.push(chars[i]);
+        i += 1;
+    }
+    let lines: Vec<&str> = raw
+        .split("\r\n")
+        .flat_map(|e| e.split(['\n', '\r']))
+        .collect();
+    let indent = lines
+        .iter()
+        .skip(1)
+        .filter(|e| !e.trim().is_empty())
+        .map(|e| e.len() - e.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    let mut lines: Vec<&str> = lines
+        .iter()
+        .enumerate()
+        .map(|(n, e)| match n {
+            0 => *e,
+            _ => e.get(indent..).unwrap_or(""),
+        })
+        .collect();
+    while lines.first().is_some_and(|e| e.trim().is_empty()) {
+        lines.remove(0);
+    }
+    while lines.last().is_some_and(|e| e.trim().is_empty()) {
+        lines.pop();
+    }
+    return Ok((lines.join("\n"), i + 3));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OperationKind {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+impl OperationKind {
+    fn root_type(&self) -> &'static str {
+        return match self {
+            OperationKind::Query => "Query",
+            OperationKind::Mutation => "Mutation",
+            OperationKind::Subscription => "Subscription",
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Literal {
+    Variable(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Boolean(bool),
+    Null,
+    Enum(String),
+    List(Vec<Literal>),
+    Object(Vec<(String, Literal)>),
+}
+
+#[derive(Debug)]
+struct Directive {
+    name: String,
+    arguments: Vec<(String, Literal)>,
+}
+
+#[derive(Debug)]
+enum Selection {
+    Field {
+        alias: Option<String>,
+        name: String,
+        arguments: Vec<(String, Literal)>,
+        directives: Vec<Directive>,
+        selection: Vec<Selection>,
+    },
+    Spread {
+        name: String,
+        directives: Vec<Directive>,
+    },
+    Inline {
+        type_condition: Option<String>,
+        directives: Vec<Directive>,
+        selection: Vec<Selection>,
+    },
+}
+
+#[derive(Debug)]
+struct VariableDef {
+    name: String,
+    var_type: TypeRef,
+    default: Option<Literal>,
+}
+
+#[derive(Debug)]
+struct Operation {
+    kind: OperationKind,
+    name: Option<String>,
+    variables: Vec<VariableDef>,
+    selection: Vec<Selection>,
+}
+
+#[derive(Debug)]
+struct Fragment {
+    type_condition: String,
+    selection: Vec<Selection>,
+}
+
+#[derive(Debug)]
+struct Document {
+    operations: Vec<Operation>,
+    fragments: BTreeMap<String, Fragment>,
+}
+
+/**
+ * Recursive descent over the tokens of an executable document
+ */
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        return self.tokens.get(self.pos);
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        return token.ok_or_else(|| anyhow!("GraphQL document ends unexpectedly"));
+    }
+
+    fn punctuator(&mut self, punctuator: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punctuator(e)) if *e == punctuator) {
+            self.pos += 1;
+            return true;
+        }
+        return false;
+    }
+
+    fn expect(&mut self, punctuator: &str) -> Result<()> {
+        match self.next()? {
+            Token::Punctuator(e) if e == punctuator => return Ok(()),
+            token => bail!(
+                "Expected '{}' in GraphQL document, found {}",
+                punctuator,
+                token.describe()
+            ),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Name(e)) if e == keyword) {
+            self.pos += 1;
+            return true;
+        }
+        return false;
+    }
+
+    fn name(&mut self) -> Result<String> {
+        return match self.next()? {
+            Token::Name(name) => Ok(name),
+            token => bail!(
+                "Expected a name in GraphQL document, found {}",
+                token.describe()
+            ),
+        };
+    }
+
+    fn document(&mut self) -> Result<Document> {
+        let mut document = Document {
+            operations: vec![],
+            fragments: BTreeMap::new(),
+        };
+        while self.peek().is_some() {
+            if self.keyword("fragment") {
+                let name = self.name()?;
+                if name == "on" {
+                    bail!("Fragments cannot be named on");
+                }
+                if !self.keyword("on") {
+                    bail!("Fragment {} needs a type condition", name);
+                }
+                let type_condition = self.name()?;
+                self.no_directives("fragment definitions")?;
+                let selection = self.selection_set()?;
+                let fragment = Fragment {
+                    type_condition,
+                    selection,
+                };
+                if document.fragments.insert(name.clone(), fragment).is_some() {
+                    bail!("Fragment {} is defined more than once", name);
+                }
+                continue;
+            }
+            let operation = self.operation()?;
+            if operation.name.is_some()
+                && document.operations.iter().any(|e| e.name == operation.name)
+            {
+                bail!(
+                    "Operation {} is defined more than once",
+                    operation.name.unwrap_or_default()
+                );
+            }
+            document.operations.push(operation);
+        }
+        if document.operations.is_empty() {
+            bail!("GraphQL document holds no operation");
+        }
+        return Ok(document);
+    }
+
+    fn operation(&mut self) -> Result<Operation> {
+        // The shorthand of a query is its selection set alone
+        if self.peek() == Some(&Token::Punctuator("{")) {
+            return Ok(Operation {
+                kind: OperationKind::Query,
+                name: None,
+                variables: vec![],
+                selection: self.selection_set()?,
+            });
+        }
+        let kind = match self.next()? {
+            Token::Name(e) if e == "query" => OperationKind::Query,
+            Token::Name(e) if e == "mutation" => OperationKind::Mutation,
+            Token::Name(e) if e == "subscription" => OperationKind::Subscription,
+            token => bail!(
+                "Expected an operation or fragment in GraphQL document, found {}",
+                token.describe()
+            ),
+        };
+        let name = match self.peek() {
+            Some(Token::Name(_)) => Some(self.name()?),
+            _ => None,
+        };
+        let mut variables = vec![];
+        if self.punctuator("(") {
+            while !self.punctuator(")") {
+                self.expect("$")?;
+                let name = self.name()?;
+                self.expect(":")?;
+                let var_type = self.type_ref()?;
+                let default = match self.punctuator("=") {
+                    true => Some(self.value(true)?),
+                    false => None,
+                };
+                self.no_directives("variables")?;
+                variables.push(VariableDef {
+                    name,
+                    var_type,
+                    default,
+                });
+            }
+        }
+        self.no_directives("operations")?;
+        return Ok(Operation {
+            kind,
+            name,
+            variables,
+            selection: self.selection_set()?,
+        });
+    }
+
+    fn type_ref(&mut self) -> Result<TypeRef> {
+        let inner = match self.punctuator("[") {
+            true => {
+                let item = self.type_ref()?;
+                self.expect("]")?;
+                TypeRef::List(Box::new(item))
+            }
+            false => TypeRef::Named(self.name()?),
+        };
+        return Ok(match self.punctuator("!") {
+            true => TypeRef::NonNull(Box::new(inner)),
+            false => inner,
+        });
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Selection>> {
+        self.expect("{")?;
+        let mut selection = vec![];
+        while !self.punctuator("}") {
+            selection.push(self.selection()?);
+        }
+        if selection.is_empty() {
+            bail!("Selection sets cannot be empty");
+        }
+        return Ok(selection);
+    }
+
+    fn selection(&mut self) -> Result<Selection> {
+        if self.punctuator("...") {
+            if self.keyword("on") {
+                let type_condition = Some(self.name()?);
+                return Ok(Selection::Inline {
+                    type_condition,
+                    directives: self.directives()?,
+                    selection: self.selection_set()?,
+                });
+            }
+            if let Some(Token::Name(_)) = self.peek() {
+                return Ok(Selection::Spread {
+                    name: self.name()?,
+                    directives: self.directives()?,
+                });
+            }
+            return Ok(Selection::Inline {
+                type_condition: None,
+                directives: self.directives()?,
+                selection: self.selection_set()?,
+            });
+        }
+        let mut name = self.name()?;
+        let mut alias = None;
+        if self.punctuator(":") {
+            alias = Some(name);
+            name = self.name()?;
+        }
+        let arguments = self.arguments(false)?;
+        let directives = self.directives()?;
+        let selection = match self.peek() == Some(&Token::Punctuator("{")) {
+            true => self.selection_set()?,
+            false => vec![],
+        };
+        return Ok(Selection::Field {
+            alias,
+            name,
+            arguments,
+            directives,
+            selection,
+        });
+    }
+
+    fn arguments(&mut self, constant: bool) -> Result<Vec<(String, Literal)>> {
+        let mut arguments = vec![];
+        if self.punctuator("(") {
+            while !self.punctuator(")") {
+                let name = self.name()?;
+                self.expect(":")?;
+                if arguments.iter().any(|(e, _)| *e == name) {
+                    bail!("Argument {} is given more than once", name);
+                }
+                arguments.push((name, self.value(constant)?));
+            }
+        }
+        return Ok(arguments);
+    }
+
+    fn directives(&mut self) -> Result<Vec<Directive>> {
+        let mut directives = vec![];
+        while self.punctuator("@") {
+            directives.push(Directive {
+                name: self.name()?,
+                arguments: self.arguments(false)?,
+            });
+        }
+        return Ok(directives);
+    }
+
+    fn no_directives(&mut self, location: &str) -> Result<()> {
+        if let Some(directive) = self.directives()?.first() {
+            bail!(
+                "Directive @{} cannot be used on {}",
+                directive.name,
+                location
+            );
+        }
+        return Ok(());
+    }
+
+    /**
+     * Parses a value, which cannot reference variables where it has to be constant, like the
+     * defaults of variables
+     */
+    fn value(&mut self, constant: bool) -> Result<Literal> {
+        return Ok(match self.next()? {
+            Token::Punctuator("$") if !constant => Literal::Variable(self.name()?),
+            Token::Int(value) => Literal::Int(value),
+            Token::Float(value) => Literal::Float(value),
+            Token::Str(value) => Literal::Str(value),
+            Token::Name(name) => match name.as_str() {
+                "true" => Literal::Boolean(true),
+                "false" => Literal::Boolean(false),
+                "null" => Literal::Null,
+                _ => Literal::Enum(name),
+            },
+            Token::Punctuator("[") => {
+                let mut items = vec![];
+                while !self.punctuator("]") {
+                    items.push(self.value(constant)?);
+                }
+                Literal::List(items)
+            }
+            Token::Punctuator("{") => {
+                let mut fields = vec![];
+                while !self.punctuator("}") {
+                    let name = self.name()?;
+                    self.expect(":")?;
+                    fields.push((name, self.value(constant)?));
+                }
+                Literal::Object(fields)
+            }
+            token => bail!(
+                "Expected a value in GraphQL document, found {}",
+                token.describe()
+            ),
+        });
+    }
+}
+
+/**
+ * Field of the operation with its arguments coerced, with fragments resolved into the selection
+ * and without the fields skipped by directives
+ */
+#[derive(Debug, Clone)]
+pub(crate) struct Field {
+    /**
+     * Key of the field in the response, its alias or its name
+     */
+    pub key: String,
+    pub name: String,
+    pub arguments: Map<String, Value>,
+    field_type: TypeRef,
+    /**
+     * Type the field is selected on, which __typename answers with
+     */
+    parent: String,
+    selection: Vec<Field>,
+}
+
+/**
+ * Operation of a request that was checked against the schema and can be executed
+ */
+#[derive(Debug)]
+pub(crate) struct Prepared {
+    pub kind: OperationKind,
+    pub fields: Vec<Field>,
+}
+
+/**
+ * Parses the operation of the request and checks its fields, arguments and variables against
+ * the schema, so that operations fail before any of their fields was resolved.
+ */
+pub(crate) fn prepare(schema: &Schema, request: &GraphqlRequest) -> Result<Prepared> {
+    let mut parser = Parser {
+        tokens: tokenize(&request.query)?,
+        pos: 0,
+    };
+    let document = parser.document()?;
+    let operation = match &request.operation_name {
+        Some(name) => document
+            .operations
+            .iter()
+            .find(|e| e.name.as_ref() == Some(name))
+            .ok_or_else(|| anyhow!("Operation {} is not in the GraphQL document", name))?,
+        None if document.operations.len() == 1 => &document.operations[0],
+        None => bail!("operationName has to name one of the operations of the document"),
+    };
+    let empty = Map::new();
+    let provided = request.variables.as_ref().unwrap_or(&empty);
+    let mut variables = Map::new();
+    for def in operation.variables.iter() {
+        if !is_input_type(schema, def.var_type.name()) {
+            bail!(
+                "Variable ${} cannot be of type {}, which is no input type",
+                def.name,
+                def.var_type
+            );
+        }
+        let value = match (provided.get(&def.name), &def.default) {
+            (Some(value), _) => Some(value.clone()),
+            (None, Some(default)) => literal_value(default, &Map::new(), &[])?,
+            (None, None) => None,
+        };
+        let path = format!("Variable ${}", def.name);
+        match value {
+            Some(value) => {
+                variables.insert(
+                    def.name.clone(),
+                    coerce_input(schema, value, &def.var_type, &path)?,
+                );
+            }
+            None if matches!(def.var_type, TypeRef::NonNull(_)) => {
+                bail!("{} of type {} is required", path, def.var_type)
+            }
+            None => {}
+        }
+    }
+    let root = schema.get(operation.kind.root_type()).ok_or_else(|| {
+        anyhow!(
+            "The schema has no {} operations",
+            operation.kind.root_type()
+        )
+    })?;
+    let collector = Collector {
+        schema,
+        fragments: &document.fragments,
+        variables: &variables,
+        defined: operation.variables.iter().map(|e| e.name.clone()).collect(),
+    };
+    let fields = collector.collect(root, &operation.selection, &mut vec![])?;
+    if operation.kind == OperationKind::Subscription && fields.len() != 1 {
+        bail!("Subscriptions have to select exactly one field");
+    }
+    return Ok(Prepared {
+        kind: operation.kind,
+        fields,
+    });
+}
+
+fn is_input_type(schema: &Schema, name: &str) -> bool {
+    return SCALARS.contains(&name) || schema.get(name).is_some_and(|e| e.input);
+}
+
+/**
+ * @returns The value of the literal, or None for variables that were not given
+ */
+fn literal_value(
+    literal: &Literal,
+    variables: &Map<String, Value>,
+    defined: &[String],
+) -> Result<Option<Value>> {
+    return Ok(Some(match literal {
+        Literal::Variable(name) => {
+            if !defined.contains(name) {
+                bail!("Variable ${} is not defined by the operation", name);
+            }
+            return Ok(variables.get(name).cloned());
+        }
+        Literal::Int(value) => Value::from(*value),
+        Literal::Float(value) => match Number::from_f64(*value) {
+            Some(value) => Value::Number(value),
+            None => bail!("Number {} cannot be represented", value),
+        },
+        Literal::Str(value) => Value::String(value.clone()),
+        Literal::Boolean(value) => Value::Bool(*value),
+        Literal::Null => Value::Null,
+        Literal::Enum(name) => bail!("Enum value {} is not accepted, as there are no enums", name),
+        Literal::List(items) => Value::Array(
+            items
+                .iter()
+                .map(|e| Ok(literal_value(e, variables, defined)?.unwrap_or(Value::Null)))
+                .collect::<Result<Vec<Value>>>()?,
+        ),
+        Literal::Object(fields) => {
+            let mut object = Map::new();
+            for (name, literal) in fields.iter() {
+                if let Some(value) = literal_value(literal, variables, defined)? {
+                    object.insert(name.clone(), value);
+                }
+            }
+            Value::Object(object)
+        }
+    }));
+}
+
+/**
+ * Checks a value given as argument or variable against its type, keeping the fields of input
+ * objects that were not given absent rather than null
+ */
+fn coerce_input(schema: &Schema, value: Value, input_type: &TypeRef, path: &str) -> Result<Value> {
+    let name = match input_type {
+        TypeRef::NonNull(inner) => {
+            if value.is_null() {
+                bail!("{} cannot be null", path);
+            }
+            return coerce_input(schema, value, inner, path);
+        }
+        _ if value.is_null() => return Ok(Value::Null),
+        TypeRef::List(inner) => {
+            return Ok(Value::Array(match value {
+                Value::Array(items) => items
+                    .into_iter()
+                    .map(|e| coerce_input(schema, e, inner, path))
+                    .collect::<Result<Vec<Value>>>()?,
+                value => vec![coerce_input(schema, value, inner, path)?],
+            }));
+        }
+        TypeRef::Named(name) => name,
+    };
+    if let Some(type_def) = schema.get(name).filter(|e| e.input) {
+        let fields = match value {
+            Value::Object(fields) => fields,
+            value => bail!("{} expects an object of type {}, got {}", path, name, value),
+        };
+        let mut object = Map::new();
+        for (key, value) in fields {
+            let def = match type_def.fields.iter().find(|e| e.name == key) {
+                Some(def) => def,
+                None => bail!("{} has no field {} in {}", path, key, name),
+            };
+            let path = format!("{}.{}", path, key);
+            object.insert(key, coerce_input(schema, value, &def.field_type, &path)?);
+        }
+        if let Some(def) = type_def
+            .fields
+            .iter()
+            .find(|e| matches!(e.field_type, TypeRef::NonNull(_)) && !object.contains_key(&e.name))
+        {
+            bail!("{} needs field {} of {}", path, def.name, name);
+        }
+        return Ok(Value::Object(object));
+    }
+    let is_int = |e: &Number| e.as_i64().is_some_and(|e| i32::try_from(e).is_ok());
+    return match (name.as_str(), value) {
+        ("Int", Value::Number(e)) if is_int(&e) => Ok(Value::Number(e)),
+        ("Float", Value::Number(e)) => Ok(Value::Number(e)),
+        ("String", Value::String(e)) => Ok(Value::String(e)),
+        ("Boolean", Value::Bool(e)) => Ok(Value::Bool(e)),
+        ("ID", Value::String(e)) => Ok(Value::String(e)),
+        ("ID", Value::Number(e)) if e.is_i64() => Ok(Value::String(e.to_string())),
+        (_, value) => bail!("{} expects a value of type {}, got {}", path, name, value),
+    };
+}
+
+/**
+ * Walks the selection of an operation on the schema
+ */
+struct Collector<'a> {
+    schema: &'a Schema,
+    fragments: &'a BTreeMap<String, Fragment>,
+    variables: &'a Map<String, Value>,
+    /**
+     * Names of the variables the operation defines, which are the only ones to be referenced
+     */
+    defined: Vec<String>,
+}
+
+impl Collector<'_> {
+    /**
+     * @param spreads Fragments that are spread into the selection, to detect cycles
+     */
+    fn collect(
+        &self,
+        type_def: &TypeDef,
+        selection: &[Selection],
+        spreads: &mut Vec<String>,
+    ) -> Result<Vec<Field>> {
+        let mut fields = vec![];
+        for item in selection {
+            match item {
+                Selection::Field {
+                    alias,
+                    name,
+                    arguments,
+                    directives,
+                    selection,
+                } => {
+                    if !self.included(directives)? {
+                        continue;
+                    }
+                    let key = alias.clone().unwrap_or_else(|| name.clone());
+                    let field = self.field(type_def, key, name, arguments, selection, spreads)?;
+                    merge(&mut fields, field)?;
+                }
+                Selection::Spread { name, directives } => {
+                    if !self.included(directives)? {
+                        continue;
+                    }
+                    let fragment = match self.fragments.get(name) {
+                        Some(fragment) => fragment,
+                        None => bail!("Fragment {} is not defined", name),
+                    };
+                    if spreads.contains(name) {
+                        bail!("Fragment {} spreads itself", name);
+                    }
+                    if !self.applies(type_def, &fragment.type_condition)? {
+                        continue;
+                    }
+                    spreads.push(name.clone());
+                    for field in self.collect(type_def, &fragment.selection, spreads)? {
+                        merge(&mut fields, field)?;
+                    }
+                    spreads.pop();
+                }
+                Selection::Inline {
+                    type_condition,
+                    directives,
+                    selection,
+                } => {
+                    if !self.included(directives)? {
+                        continue;
+                    }
+                    if let Some(type_condition) = type_condition {
+                        if !self.applies(type_def, type_condition)? {
+                            continue;
+                        }
+                    }
+                    for field in self.collect(type_def, selection, spreads)? {
+                        merge(&mut fields, field)?;
+                    }
+                }
+            }
+        }
+        return Ok(fields);
+    }
+
+    fn field(
+        &self,
+        type_def: &TypeDef,
+        key: String,
+        name: &str,
+        arguments: &[(String, Literal)],
+        selection: &[Selection],
+        spreads: &mut Vec<String>,
+    ) -> Result<Field> {
+        if name == "__typename" {
+            if !arguments.is_empty() || !selection.is_empty() {
+                bail!("Field __typename has neither arguments nor subfields");
+            }
+            return Ok(Field {
+                key,
+                name: name.into(),
+                arguments: Map::new(),
+                field_type: TypeRef::required("String"),
+                parent: type_def.name.clone(),
+                selection: vec![],
+            });
+        }
+        let def = match type_def.fields.iter().find(|e| e.name == name) {
+            Some(def) => def,
+            None => bail!("Cannot query field {} on type {}", name, type_def.name),
+        };
+        if let Some((argument, _)) = arguments
+            .iter()
+            .find(|(e, _)| !def.arguments.iter().any(|(name, _)| name == e))
+        {
+            bail!("Field {} has no argument {}", name, argument);
+        }
+        let mut coerced = Map::new();
+        for (argument, arg_type) in def.arguments.iter() {
+            let value = match arguments.iter().find(|(e, _)| e == argument) {
+                Some((_, literal)) => literal_value(literal, self.variables, &self.defined)?,
+                None => None,
+            };
+            let path = format!("Argument {} of field {}", argument, name);
+            match value {
+                Some(value) => {
+                    coerced.insert(
+                        argument.clone(),
+                        coerce_input(self.schema, value, arg_type, &path)?,
+                    );
+                }
+                None if matches!(arg_type, TypeRef::NonNull(_)) => bail!("{} is required", path),
+                None => {}
+            }
+        }
+        let selection = match self.schema.get(def.field_type.name()) {
+            Some(object) => {
+                if selection.is_empty() {
+                    bail!(
+                        "Field {} of type {} needs a selection of subfields",
+                        name,
+                        def.field_type
+                    );
+                }
+                self.collect(object, selection, spreads)?
+            }
+            None => {
+                if !selection.is_empty() {
+                    bail!("Field {} of type {} has no subfields", name, def.field_type);
+                }
+                vec![]
+            }
+        };
+        return Ok(Field {
+            key,
+            name: name.into(),
+            arguments: coerced,
+            field_type: def.field_type.clone(),
+            parent: type_def.name.clone(),
+            selection,
+        });
+    }
+
+    /**
+     * Whether the fragment with the type condition is selected on the type, which only holds
+     * for the type itself as there are neither interfaces nor unions
+     */
+    fn applies(&self, type_def: &TypeDef, type_condition: &str) -> Result<bool> {
+        match self.schema.get(type_condition) {
+            Some(condition) if !condition.input => return Ok(condition.name == type_def.name),
+            _ => bail!("Fragments cannot be on type {}", type_condition),
+        }
+    }
+
+    /**
+     * Applies the @skip and @include directives
+     */
+    fn included(&self, directives: &[Directive]) -> Result<bool> {
+        for directive in directives {
+            let condition = match directive.arguments.as_slice() {
+                [(name, literal)] if name == "if" => {
+                    literal_value(literal, self.variables, &self.defined)?
+                }
+                _ => None,
+            };
+            let condition = match (directive.name.as_str(), condition) {
+                ("skip" | "include", Some(Value::Bool(condition))) => condition,
+                ("skip" | "include", _) => {
+                    bail!(
+                        "Directive @{} needs the Boolean! argument if",
+                        directive.name
+                    )
+                }
+                (name, _) => bail!("Directive @{} is unknown", name),
+            };
+            if condition == (directive.name == "skip") {
+                return Ok(false);
+            }
+        }
+        return Ok(true);
+    }
+}
+
+/**
+ * Adds the field to the fields, merging the subfields of fields answered by the same key
+ */
+fn merge(fields: &mut Vec<Field>, field: Field) -> Result<()> {
+    let existing = match fields.iter_mut().find(|e| e.key == field.key) {
+        Some(existing) => existing,
+        None => {
+            fields.push(field);
+            return Ok(());
+        }
+    };
+    if existing.name != field.name || existing.arguments != field.arguments {
+        bail!(
+            "Fields {} and {} conflict, as both are answered as {}",
+            existing.name,
+            field.name,
+            field.key
+        );
+    }
+    for subfield in field.selection {
+        merge(&mut existing.selection, subfield)?;
+    }
+    return Ok(());
+}
+
+/**
+ * Value of a field, whose objects keep their fields in the order they were selected in
+ */
+#[derive(Debug, PartialEq)]
+pub(crate) enum Output {
+    Value(Value),
+    List(Vec<Output>),
+    Object(Vec<(String, Output)>),
+}
+
+impl Serialize for Output {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        return match self {
+            Output::Value(value) => value.serialize(serializer),
+            Output::List(items) => serializer.collect_seq(items),
+            Output::Object(fields) => serializer.collect_map(fields.iter().map(|(k, v)| (k, v))),
+        };
+    }
+}
+
+impl Field {
+    /**
+     * Completes the value a resolver returned for the field by the type and selection of the
+     * field. Objects are given with the fields of their type as keys.
+     */
+    pub(crate) fn complete(&self, value: &Value) -> Result<Output> {
+        return self.complete_type(&self.field_type, value);
+    }
+
+    fn complete_type(&self, field_type: &TypeRef, value: &Value) -> Result<Output> {
+        let name = match field_type {
+            TypeRef::NonNull(inner) => {
+                let output = self.complete_type(inner, value)?;
+                if output == Output::Value(Value::Null) {
+                    bail!(
+                        "Field {}.{} of type {} cannot be null",
+                        self.parent,
+                        self.name,
+                        self.field_type
+                    );
+                }
+                return Ok(output);
+            }
+            _ if value.is_null() => return Ok(Output::Value(Value::Null)),
+            TypeRef::List(inner) => match value {
+                Value::Array(items) => {
+                    return Ok(Output::List(
+                        items
+                            .iter()
+                            .map(|e| self.complete_type(inner, e))
+                            .collect::<Result<Vec<Output>>>()?,
+                    ))
+                }
+                _ => bail!("Field {}.{} has to be a list", self.parent, self.name),
+            },
+            TypeRef::Named(name) => name,
+        };
+        if SCALARS.contains(&name.as_str()) {
+            return match serialize_scalar(name, value) {
+                Some(value) => Ok(Output::Value(value)),
+                None => bail!(
+                    "Value {} of field {}.{} cannot be represented as {}",
+                    value,
+                    self.parent,
+                    self.name,
+                    name
+                ),
+            };
+        }
+        if !value.is_object() {
+            bail!("Field {}.{} has to be an object", self.parent, self.name);
+        }
+        let mut fields = vec![];
+        for field in self.selection.iter() {
+            let value = match field.name == "__typename" {
+                true => Value::String(field.parent.clone()),
+                false => value.get(&field.name).cloned().unwrap_or(Value::Null),
+            };
+            fields.push((field.key.clone(), field.complete(&value)?));
+        }
+        return Ok(Output::Object(fields));
+    }
+}
+
+/**
+ * Values are also accepted in their text notation, as columns of the inventory are handled as
+ * text
+ */
+fn serialize_scalar(name: &str, value: &Value) -> Option<Value> {
+    return match (name, value) {
+        ("ID" | "String", Value::String(_)) => Some(value.clone()),
+        ("ID", Value::Number(e)) => Some(Value::String(e.to_string())),
+        ("String", Value::Number(_) | Value::Bool(_)) => Some(Value::String(value.to_string())),
+        ("Int", Value::Number(e)) => e.as_i64().map(Value::from),
+        ("Int", Value::String(e)) => e.parse::<i64>().ok().map(Value::from),
+        ("Float", Value::Number(e)) => e.as_f64().and_then(Number::from_f64).map(Value::Number),
+        ("Float", Value::String(e)) => e
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        ("Boolean", Value::Bool(_)) => Some(value.clone()),
+        ("Boolean", Value::String(e)) => match e.as_str() {
+            "true" | "1" => Some(Value::Bool(true)),
+            "false" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        ("Boolean", Value::Number(e)) => match e.as_i64() {
+            Some(1) => Some(Value::Bool(true)),
+            Some(0) => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    };
+}
+
+#[derive(Debug, Serialize)]
+struct ResponseError {
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    path: Vec<String>,
+}
+
+/**
+ * Response to a GraphQL request, which holds the errors of the fields next to the data of the
+ * others
+ */
+#[derive(Debug, Serialize)]
+pub(crate) struct Response {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<ResponseError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Output>,
+}
+
+impl Response {
+    /**
+     * Response to a request that could not be executed at all
+     */
+    pub(crate) fn error(e: &anyhow::Error) -> Response {
+        return Response {
+            errors: vec![ResponseError {
+                message: e.to_string(),
+                path: vec![],
+            }],
+            data: None,
+        };
+    }
+
+    /**
+     * Response with the completed value of a single field, e.g. of a subscription
+     */
+    pub(crate) fn field(field: &Field, value: Result<Value>) -> Response {
+        return Response::fields(std::iter::once((field, value)));
+    }
+
+    /**
+     * Response with the completed values of the fields. A field failing to be resolved is
+     * null, which makes the data null if the field cannot be.
+     */
+    fn fields<'a>(fields: impl Iterator<Item = (&'a Field, Result<Value>)>) -> Response {
+        let mut data = vec![];
+        let mut errors = vec![];
+        let mut null = false;
+        for (field, value) in fields {
+            match value.and_then(|e| field.complete(&e)) {
+                Ok(output) => data.push((field.key.clone(), output)),
+                Err(e) => {
+                    null |= matches!(field.field_type, TypeRef::NonNull(_));
+                    errors.push(ResponseError {
+                        message: e.to_string(),
+                        path: vec![field.key.clone()],
+                    });
+                    data.push((field.key.clone(), Output::Value(Value::Null)));
+                }
+            }
+        }
+        return Response {
+            errors,
+            data: Some(match null {
+                true => Output::Value(Value::Null),
+                false => Output::Object(data),
+            }),
+        };
+    }
+}
+
+/**
+ * Resolves the fields of a query or mutation one after another, so that the fields of mutations
+ * see the changes of those before them.
+ */
+pub(crate) fn execute(
+    prepared: &Prepared,
+    resolve: &mut dyn FnMut(&Field) -> Result<Value>,
+) -> Response {
+    return Response::fields(prepared.fields.iter().map(|field| {
+        let value = match field.name == "__typename" {
+            true => Ok(Value::String(field.parent.clone())),
+            false => resolve(field),
+        };
+        (field, value)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::args::{ColumnType, SchemaDeclaration};
+    use serde_json::json;
+
+    fn schema() -> Schema {
+        let column = |name: &str, column_type: ColumnType, nullable: bool| SchemaDeclaration {
+            name: name.into(),
+            column_type,
+            nullable,
+            ..Default::default()
+        };
+        return Schema::inventory(&SchemaCollection {
+            collection: vec![
+                column("sku", ColumnType::VARCHAR, false),
+                column("unit-price", ColumnType::REAL, true),
+                column("quantity", ColumnType::INT, true),
+            ],
+        });
+    }
+
+    fn request(query: &str, variables: Value) -> GraphqlRequest {
+        return GraphqlRequest {
+            query: query.into(),
+            operation_name: None,
+            variables: variables.as_object().cloned(),
+        };
+    }
+
+    fn run(query: &str, variables: Value, resolve: &dyn Fn(&Field) -> Value) -> String {
+        let prepared = prepare(&schema(), &request(query, variables)).unwrap();
+        let response = execute(&prepared, &mut |field| Ok(resolve(field)));
+        return serde_json::to_string(&response).unwrap();
+    }
+
+    #[test]
+    fn fields_are_answered_in_the_order_of_the_selection() {
+        let response = run(
+            "{ entity(id: 7) { sku, id __typename ...price } }
+            fragment price on Entity { unit_price quantity }",
+            json!({}),
+            &|field| {
+                assert_eq!(field.arguments, *json!({"id": "7"}).as_object().unwrap());
+                json!({"id": 7, "sku": "A1", "unit_price": "2.5", "quantity": "3"})
+            },
+        );
+        assert_eq!(
+            response,
+            r#"{"data":{"entity":{"sku":"A1","id":"7","__typename":"Entity","unit_price":2.5,"quantity":3}}}"#
+        );
+    }
+
+    #[test]
+    fn variables_and_directives_are_applied() {
+        let query = "query Find($filter: EntityInput, $full: Boolean = false) {
+            items: entities(filter: $filter, limit: 2) { id sku @include(if: $full) }
+        }";
+        let response = run(
+            query,
+            json!({"filter": {"sku": "A1", "quantity": null}}),
+            &|field| {
+                assert_eq!(
+                    field.arguments["filter"],
+                    json!({"sku": "A1", "quantity": null})
+                );
+                assert_eq!(field.arguments["limit"], json!(2));
+                json!([{"id": 1, "sku": "A1"}])
+            },
+        );
+        assert_eq!(response, r#"{"data":{"items":[{"id":"1"}]}}"#);
+    }
+
+    #[test]
+    fn invalid_operations_are_rejected_before_they_are_resolved() {
+        for (query, error) in [
+            (
+                "{ entity(id: 1) { price } }",
+                "Cannot query field price on type Entity",
+            ),
+            (
+                "{ entity { id } }",
+                "Argument id of field entity is required",
+            ),
+            ("{ entity(id: 1) }", "needs a selection of subfields"),
+            (
+                "{ entities(limit: \"2\") { id } }",
+                "expects a value of type Int",
+            ),
+            (
+                "mutation { add(values: {price: 1}) { id } }",
+                "has no field price",
+            ),
+            ("{ entity(id: $id) { id } }", "Variable $id is not defined"),
+            (
+                "{ ...a } fragment a on Query { ...a }",
+                "Fragment a spreads itself",
+            ),
+            (
+                "subscription { a: events { id } b: events { id } }",
+                "exactly one field",
+            ),
+            ("{ entity(id: 1) { id", "ends unexpectedly"),
+        ] {
+            let e = prepare(&schema(), &request(query, json!({}))).unwrap_err();
+            assert!(e.to_string().contains(error), "{}: {}", query, e);
+        }
+    }
+
+    #[test]
+    fn failing_fields_are_null_with_an_error() {
+        let prepared = prepare(
+            &schema(),
+            &request(
+                "{ a: entity(id: 1) { id } b: entity(id: 2) { id } }",
+                json!({}),
+            ),
+        )
+        .unwrap();
+        let response = execute(&prepared, &mut |field| match field.arguments["id"] == "1" {
+            true => bail!("Cannot read inventory"),
+            false => Ok(json!({"id": 2})),
+        });
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"errors":[{"message":"Cannot read inventory","path":["a"]}],"data":{"a":null,"b":{"id":"2"}}}"#
+        );
+    }
+
+    #[test]
+    fn block_strings_are_dedented() {
+        let tokens = tokenize("\"\"\"\n    first\n      second\n\"\"\" \"a\\u00e4\\n\"").unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Str("first\n  second".into()),
+                Token::Str("a\u{e4}\n".into())
+            ]
+        );
+    }
+}
@@ -0,0 +1,650 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::{
+    common::args::{
+        check_event_names, CommandContext, InvManNotationHelper, InventoryListProps, ServeTls,
+        EVENTS_POLL_MILLISECONDS,
+    },
+    database::{DBEvent, DBUser, KeyValueCollection},
+    filter::{CompareOp, WhereExpr},
+    plugins,
+    server::{self, ServerState, OTP_HEADER},
+};
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    marker::PhantomData,
+    net::ToSocketAddrs,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tonic::{
+    body::BoxBody,
+    codec::ProstCodec,
+    codegen::{http, tokio_stream::wrappers::ReceiverStream, BoxFuture, Service},
+    metadata::MetadataMap,
+    server::{Grpc, NamedService},
+    transport::{Certificate, Identity, Server, ServerTlsConfig},
+    Code, Status,
+};
+
+/**
+ * Entities streamed by List are read in pages of this size, so that large inventories are not
+ * held in memory at once
+ */
+const LIST_PAGE_SIZE: i32 = 256;
+
+/**
+ * Messages a stream is ahead of its client at most
+ */
+const STREAM_BUFFER: usize = 32;
+
+/**
+ * Messages of proto/invman/v1/inventory.proto, which are declared by hand, so that building
+ * invman does not need protoc
+ */
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct Entity {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(string, tag = "2")]
+    pub created_at: String,
+    #[prost(string, tag = "3")]
+    pub updated_at: String,
+    #[prost(map = "string, string", tag = "4")]
+    pub values: HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct AddRequest {
+    #[prost(map = "string, string", tag = "1")]
+    pub values: HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct GetRequest {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ListRequest {
+    #[prost(map = "string, string", tag = "1")]
+    pub filter: HashMap<String, String>,
+    #[prost(int32, tag = "2")]
+    pub limit: i32,
+    #[prost(int64, optional, tag = "3")]
+    pub after_id: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct EditRequest {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(map = "string, string", tag = "2")]
+    pub values: HashMap<String, String>,
+    #[prost(string, optional, tag = "3")]
+    pub expect_version: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct RemoveRequest {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(string, optional, tag = "2")]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct RemoveResponse {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct WatchEventsRequest {
+    #[prost(uint32, tag = "1")]
+    pub after_event: u32,
+    #[prost(string, repeated, tag = "2")]
+    pub events: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct Event {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, tag = "2")]
+    pub event: String,
+    #[prost(string, optional, tag = "3")]
+    pub dispatcher: Option<String>,
+    #[prost(int64, optional, tag = "4")]
+    pub target: Option<i64>,
+    #[prost(string, optional, tag = "5")]
+    pub reason: Option<String>,
+    #[prost(string, tag = "6")]
+    pub created_at: String,
+    #[prost(message, optional, tag = "7")]
+    pub entity: Option<Entity>,
+}
+
+/**
+ * Serves the InventoryService until the process is stopped, with up to workers threads
+ * handling the calls. The database is worked on by the blocking threads of the runtime, as the
+ * connections of the pool are synchronous.
+ */
+pub(crate) fn serve(
+    state: ServerState,
+    listen: &str,
+    workers: usize,
+    tls: Option<&ServeTls>,
+) -> Result<()> {
+    let address = match listen.to_socket_addrs().ok().and_then(|mut e| e.next()) {
+        Some(address) => address,
+        None => bail!("Could not listen on {} (Not a valid address)", listen),
+    };
+    let mut server = Server::builder();
+    if let Some(tls) = tls {
+        server = server.tls_config(server_tls_config(tls)?)?;
+    }
+    let service = InventoryService {
+        state: Arc::new(state),
+    };
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(workers)
+        .enable_all()
+        .build()?;
+    tracing::info!(
+        listen,
+        workers,
+        tls = tls.is_some(),
+        "Serving the database over gRPC"
+    );
+    return runtime.block_on(async {
+        return match server.add_service(service).serve(address).await {
+            Ok(_) => Ok(()),
+            Err(e) => bail!("Could not listen on {} ({:#})", listen, anyhow!(e)),
+        };
+    });
+}
+
+fn server_tls_config(tls: &ServeTls) -> Result<ServerTlsConfig> {
+    let read = |path: &str| {
+        return match std::fs::read(path) {
+            Ok(pem) => Ok(pem),
+            Err(e) => bail!("Could not read {} ({})", path, e),
+        };
+    };
+    let mut config = ServerTlsConfig::new()
+        .identity(Identity::from_pem(read(&tls.certificate)?, read(&tls.key)?));
+    if let Some(client_ca) = &tls.client_ca {
+        config = config.client_ca_root(Certificate::from_pem(read(client_ca)?));
+    }
+    return Ok(config);
+}
+
+/**
+ * invman.v1.InventoryService, whose methods are answered by the operations of /rpc, so that
+ * the same users are permitted to them
+ */
+#[derive(Clone)]
+struct InventoryService {
+    state: Arc<ServerState>,
+}
+
+impl NamedService for InventoryService {
+    const NAME: &'static str = "invman.v1.InventoryService";
+}
+
+impl Service<http::Request<BoxBody>> for InventoryService {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        return Poll::Ready(Ok(()));
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        let state = self.state.clone();
+        let method = request
+            .uri()
+            .path()
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        return Box::pin(async move {
+            let response = match method.as_str() {
+                "Add" => {
+                    let mut grpc = Grpc::new(ProstCodec::default());
+                    grpc.unary(Unary(state, add), request).await
+                }
+                "Get" => {
+                    let mut grpc = Grpc::new(ProstCodec::default());
+                    grpc.unary(Unary(state, get), request).await
+                }
+                "List" => {
+                    let mut grpc = Grpc::new(ProstCodec::default());
+                    grpc.server_streaming(Streaming(state, list, PhantomData), request)
+                        .await
+                }
+                "Edit" => {
+                    let mut grpc = Grpc::new(ProstCodec::default());
+                    grpc.unary(Unary(state, edit), request).await
+                }
+                "Remove" => {
+                    let mut grpc = Grpc::new(ProstCodec::default());
+                    grpc.unary(Unary(state, remove), request).await
+                }
+                "WatchEvents" => {
+                    let mut grpc = Grpc::new(ProstCodec::default());
+                    grpc.server_streaming(Streaming(state, watch_events, PhantomData), request)
+                        .await
+                }
+                _ => Status::unimplemented(format!("Unknown method {}", method)).into_http(),
+            };
+            return Ok(response);
+        });
+    }
+}
+
+/**
+ * Credentials of a call, given like for /rpc by the authorization and x-invman-otp metadata
+ */
+struct Credentials {
+    auth: Option<String>,
+    otp: Option<String>,
+}
+
+impl Credentials {
+    fn of(metadata: &MetadataMap) -> Credentials {
+        let value = |key: &str| metadata.get(key).and_then(|e| e.to_str().ok());
+        return Credentials {
+            auth: value("authorization").and_then(server::credentials_of),
+            otp: value(OTP_HEADER).map(str::to_string),
+        };
+    }
+
+    /**
+     * Runs the function for the authenticated user like ServerState::with_user
+     */
+    fn with_user<T>(
+        &self,
+        state: &ServerState,
+        f: impl FnOnce(&mut CommandContext, &DBUser) -> Result<T>,
+    ) -> Result<T, Status> {
+        return state
+            .with_user(self.auth.clone(), self.otp.clone(), f)
+            .map_err(status);
+    }
+}
+
+/**
+ * Unary call answered by the function on a blocking thread
+ */
+struct Unary<F>(Arc<ServerState>, F);
+
+impl<Req, Res, F> Service<tonic::Request<Req>> for Unary<F>
+where
+    Req: Send + 'static,
+    Res: Send + 'static,
+    F: Fn(&ServerState, Credentials, Req) -> Result<Res, Status> + Copy + Send + 'static,
+{
+    type Response = tonic::Response<Res>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        return Poll::Ready(Ok(()));
+    }
+
+    fn call(&mut self, request: tonic::Request<Req>) -> Self::Future {
+        let (state, answer) = (self.0.clone(), self.1);
+        let credentials = Credentials::of(request.metadata());
+        let message = request.into_inner();
+        return Box::pin(async move {
+            let answered =
+                tokio::task::spawn_blocking(move || answer(&state, credentials, message));
+            return match answered.await {
+                Ok(result) => result.map(tonic::Response::new),
+                Err(e) => Err(Status::internal(e.to_string())),
+            };
+        });
+    }
+}
+
+/**
+ * Streaming call whose messages are sent by the function on a blocking thread. The function
+ * stops once sending fails, as the client is gone then.
+ */
+struct Streaming<F, Res>(Arc<ServerState>, F, PhantomData<fn() -> Res>);
+
+type Sender<T> = mpsc::Sender<Result<T, Status>>;
+
+impl<Req, Res, F> Service<tonic::Request<Req>> for Streaming<F, Res>
+where
+    Req: Send + 'static,
+    Res: Send + 'static,
+    F: Fn(&ServerState, Credentials, Req, &Sender<Res>) -> Result<(), Status>
+        + Copy
+        + Send
+        + 'static,
+{
+    type Response = tonic::Response<ReceiverStream<Result<Res, Status>>>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        return Poll::Ready(Ok(()));
+    }
+
+    fn call(&mut self, request: tonic::Request<Req>) -> Self::Future {
+        let (state, answer) = (self.0.clone(), self.1);
+        let credentials = Credentials::of(request.metadata());
+        let message = request.into_inner();
+        return Box::pin(async move {
+            let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+            tokio::task::spawn_blocking(move || {
+                if let Err(status) = answer(&state, credentials, message, &sender) {
+                    // Fails only if the client is gone already
+                    let _ = sender.blocking_send(Err(status));
+                }
+            });
+            return Ok(tonic::Response::new(ReceiverStream::new(receiver)));
+        });
+    }
+}
+
+/**
+ * Status of the failure, by the HTTP status /rpc answers it with
+ */
+fn status((status, e): (u16, anyhow::Error)) -> Status {
+    let code = match status {
+        401 => Code::Unauthenticated,
+        403 => Code::PermissionDenied,
+        404 => Code::Unimplemented,
+        503 => Code::Unavailable,
+        _ => Code::InvalidArgument,
+    };
+    return Status::new(code, e.to_string());
+}
+
+fn not_found(id: i64) -> Status {
+    return Status::not_found(format!("Entity {} does not exist", id));
+}
+
+/**
+ * The values in name=value notation, in the order of their names
+ */
+fn params(values: &HashMap<String, String>) -> Vec<String> {
+    let mut params: Vec<String> = values
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    params.sort();
+    return params;
+}
+
+fn add(
+    state: &ServerState,
+    credentials: Credentials,
+    request: AddRequest,
+) -> Result<Entity, Status> {
+    return credentials.with_user(state, |ctx, user| {
+        let id = server::add_entity(ctx, user, &params(&request.values))?;
+        return fetch(ctx, user, &id)?.ok_or_else(|| anyhow!("Added entity could not be found"));
+    });
+}
+
+fn get(
+    state: &ServerState,
+    credentials: Credentials,
+    request: GetRequest,
+) -> Result<Entity, Status> {
+    return credentials
+        .with_user(state, |ctx, user| fetch(ctx, user, &request.id.to_string()))?
+        .ok_or_else(|| not_found(request.id));
+}
+
+/**
+ * Streams the entities page by page, each of which is read like by inventory list
+ */
+fn list(
+    state: &ServerState,
+    credentials: Credentials,
+    request: ListRequest,
+    sender: &Sender<Entity>,
+) -> Result<(), Status> {
+    let mut remaining = match request.limit {
+        0 => None,
+        limit if limit > 0 => Some(limit),
+        _ => return Err(Status::invalid_argument("limit must not be negative")),
+    };
+    let terms: Vec<WhereExpr> = request
+        .filter
+        .into_iter()
+        .map(|(column, value)| WhereExpr::Compare {
+            column,
+            operator: CompareOp::Eq,
+            value,
+        })
+        .collect();
+    let filter = match terms.is_empty() {
+        true => None,
+        false => Some(WhereExpr::And(terms)),
+    };
+    // Paging by the id keeps the entities in the order of their ids
+    let mut after_id = request.after_id.unwrap_or(0);
+    loop {
+        let limit = remaining.map_or(LIST_PAGE_SIZE, |e| e.min(LIST_PAGE_SIZE));
+        let props = InventoryListProps {
+            limit,
+            after_id: Some(after_id),
+            fuzzy: None,
+            fuzzy_threshold: 0.0,
+            with_audit: false,
+            with_attachments: false,
+            include_archived: false,
+            supplier: None,
+            assigned_to: None,
+            category: None,
+            recursive: false,
+            raw: &None,
+            params: &vec![],
+            changed_since: None,
+            filter: filter.clone(),
+            group: None,
+            expand: vec![],
+        };
+        let page = credentials.with_user(state, |ctx, user| {
+            let mut result = server::call("inventory_list", json!([props]), ctx, user.clone())?;
+            return server::decode::<Vec<KeyValueCollection>>(result["result"].take());
+        })?;
+        for collection in &page {
+            let entity = entity_of_collection(collection);
+            after_id = entity.id;
+            if sender.blocking_send(Ok(entity)).is_err() {
+                return Ok(());
+            }
+        }
+        remaining = remaining.map(|e| e - page.len() as i32);
+        if page.len() < limit as usize || remaining == Some(0) {
+            return Ok(());
+        }
+    }
+}
+
+fn edit(
+    state: &ServerState,
+    credentials: Credentials,
+    request: EditRequest,
+) -> Result<Entity, Status> {
+    let id = request.id.to_string();
+    let params = params(&request.values);
+    return credentials
+        .with_user(state, |ctx, user| {
+            plugins::validate_entity(&params)?;
+            let declarations = &ctx.config.inventory_schema_declaration;
+            let entries: KeyValueCollection = params
+                .iter()
+                .map(|e| e.to_typed_key_value_entry(declarations))
+                .collect::<Result<Vec<_>>>()?
+                .into();
+            server::call(
+                "inventory_edit",
+                json!([id, entries, request.expect_version]),
+                ctx,
+                user.clone(),
+            )?;
+            return fetch(ctx, user, &id);
+        })?
+        .ok_or_else(|| not_found(request.id));
+}
+
+fn remove(
+    state: &ServerState,
+    credentials: Credentials,
+    request: RemoveRequest,
+) -> Result<RemoveResponse, Status> {
+    return credentials.with_user(state, |ctx, user| {
+        server::call(
+            "inventory_remove",
+            json!([request.id.to_string(), request.reason]),
+            ctx,
+            user.clone(),
+        )?;
+        return Ok(RemoveResponse {});
+    });
+}
+
+/**
+ * Streams the events like GET /events, to users that may read the database. The credentials
+ * are verified again whenever the cached ones expire, which ends the stream once they are no
+ * longer valid.
+ */
+fn watch_events(
+    state: &ServerState,
+    credentials: Credentials,
+    request: WatchEventsRequest,
+    sender: &Sender<Event>,
+) -> Result<(), Status> {
+    if let Err(e) = check_event_names(&request.events) {
+        return Err(Status::invalid_argument(e.to_string()));
+    }
+    let authorize = || {
+        let (auth, otp) = (credentials.auth.clone(), credentials.otp.clone());
+        return server::authorize(state, auth, otp, Some("db")).map_err(status);
+    };
+    authorize()?;
+    if let Err(e) = state.open_event_stream() {
+        return Err(Status::unavailable(e.to_string()));
+    }
+    let mut after_event = request.after_event;
+    let streamed = loop {
+        if let Err(e) = authorize() {
+            break Err(e);
+        }
+        let events = match credentials.with_user(state, |ctx, _| ctx.db.event_list(after_event)) {
+            Ok(events) => events,
+            Err(e) => break Err(e),
+        };
+        for event in events {
+            after_event = event.id;
+            if !request.events.is_empty() && !request.events.contains(&event.event) {
+                continue;
+            }
+            if sender.blocking_send(Ok(event_of(event))).is_err() {
+                break;
+            }
+        }
+        if sender.is_closed() {
+            break Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(EVENTS_POLL_MILLISECONDS));
+    };
+    state.close_event_stream();
+    return streamed;
+}
+
+/**
+ * The entity as read by inventory_get, unless it does not exist
+ */
+fn fetch(ctx: &mut CommandContext, user: &DBUser, id: &str) -> Result<Option<Entity>> {
+    let mut result = server::call("inventory_get", json!([id]), ctx, user.clone())?;
+    let collection: Option<KeyValueCollection> = server::decode(result["result"].take())?;
+    return Ok(collection.as_ref().map(entity_of_collection));
+}
+
+fn entity_of_collection(collection: &KeyValueCollection) -> Entity {
+    return entity_of(
+        collection
+            .collection
+            .iter()
+            .map(|e| (e.key.as_str(), e.to_plain_value())),
+    );
+}
+
+fn event_of(event: DBEvent) -> Event {
+    let entity = match &event.entity {
+        Some(Value::Object(values)) => Some(entity_of(values.iter().map(|(key, value)| {
+            let value = match value {
+                Value::Null => None,
+                Value::String(value) => Some(value.clone()),
+                value => Some(value.to_string()),
+            };
+            return (key.as_str(), value);
+        }))),
+        _ => None,
+    };
+    return Event {
+        id: event.id,
+        event: event.event,
+        dispatcher: event.dispatcher,
+        target: event.target,
+        reason: event.reason,
+        created_at: event.created_at,
+        entity,
+    };
+}
+
+/**
+ * The entity of the values by their columns, leaving out missing ones and those that are not
+ * columns of the schema
+ */
+fn entity_of<'a>(values: impl Iterator<Item = (&'a str, Option<String>)>) -> Entity {
+    let mut entity = Entity::default();
+    for (key, value) in values {
+        let value = match value {
+            Some(value) => value,
+            None => continue,
+        };
+        match key {
+            "id" => entity.id = value.parse().unwrap_or_default(),
+            "created_at" => entity.created_at = value,
+            "updated_at" => entity.updated_at = value,
+            "deleted_at" => {}
+            _ => {
+                entity.values.insert(key.to_string(), value);
+            }
+        }
+    }
+    return entity;
+}
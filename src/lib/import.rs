@@ -0,0 +1,179 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::utils::read_decompressed;
+use anyhow::{bail, Result};
+use calamine::{open_workbook, Data, Reader, Xlsx};
+use serde_json::Value;
+use std::path::Path;
+
+/**
+ * Format of a file to import entities from
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Csv,
+    Xlsx,
+    /**
+     * Array of objects, or one object per line, whose keys are the headers
+     */
+    Json,
+}
+
+impl ImportFormat {
+    /**
+     * Guesses the format from the extension of the file, CSV if it has none known.
+     */
+    pub fn of_file(file: &str) -> ImportFormat {
+        return match Path::new(file).extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("xlsx") => ImportFormat::Xlsx,
+            Some(e) if e.eq_ignore_ascii_case("json") || e.eq_ignore_ascii_case("ndjson") => {
+                ImportFormat::Json
+            }
+            _ => ImportFormat::Csv,
+        };
+    }
+}
+
+/**
+ * Reads all rows of the file as text, the first row being the header. Of a workbook, the given
+ * sheet is read, or the first one if None. CSV and JSON files may be gzip compressed.
+ */
+pub fn read_rows(
+    file: &str,
+    format: ImportFormat,
+    sheet: Option<&str>,
+) -> Result<Vec<Vec<String>>> {
+    return match format {
+        ImportFormat::Csv => read_csv(file),
+        ImportFormat::Xlsx => read_xlsx(file, sheet),
+        ImportFormat::Json => read_json(file),
+    };
+}
+
+/**
+ * Turns the objects into rows, the header holding every key in the order they first appear.
+ * Missing keys and nulls become empty cells.
+ */
+fn read_json(file: &str) -> Result<Vec<Vec<String>>> {
+    let content = read_decompressed(file)?;
+    let content = String::from_utf8_lossy(&content);
+    let objects = match content.trim_start().starts_with('[') {
+        true => serde_json::from_str::<Vec<Value>>(&content)?,
+        false => content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| match serde_json::from_str::<Value>(line) {
+                Ok(value) => Ok(value),
+                Err(e) => bail!("Line {} of '{}' is not valid JSON ({})", i + 1, file, e),
+            })
+            .collect::<Result<Vec<Value>>>()?,
+    };
+    let mut header: Vec<String> = vec![];
+    for object in objects.iter() {
+        let object = match object.as_object() {
+            Some(object) => object,
+            None => bail!("File '{}' must only contain objects", file),
+        };
+        for key in object.keys() {
+            if !header.contains(key) {
+                header.push(key.clone());
+            }
+        }
+    }
+    let mut rows = vec![header.clone()];
+    for (i, object) in objects.iter().enumerate() {
+        let mut values = vec![];
+        for key in header.iter() {
+            values.push(match &object[key] {
+                Value::Null => "".into(),
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                Value::Array(_) | Value::Object(_) => {
+                    bail!("Object {} has a nested value for '{}'", i + 1, key)
+                }
+            });
+        }
+        rows.push(values);
+    }
+    return Ok(rows);
+}
+
+fn read_csv(file: &str) -> Result<Vec<Vec<String>>> {
+    let content = read_decompressed(file)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_slice());
+    let mut rows = vec![];
+    for record in reader.records() {
+        rows.push(record?.iter().map(String::from).collect());
+    }
+    return Ok(rows);
+}
+
+fn read_xlsx(file: &str, sheet: Option<&str>) -> Result<Vec<Vec<String>>> {
+    let mut workbook: Xlsx<_> = match open_workbook(file) {
+        Ok(workbook) => workbook,
+        Err(e) => bail!("Could not read file '{}' ({})", file, e),
+    };
+    let sheet = match sheet {
+        Some(sheet) => sheet.to_string(),
+        None => match workbook.sheet_names().first() {
+            Some(sheet) => sheet.clone(),
+            None => bail!("Workbook '{}' has no sheets", file),
+        },
+    };
+    let range = match workbook.worksheet_range(&sheet) {
+        Ok(range) => range,
+        Err(e) => bail!("Could not read sheet '{}' ({})", sheet, e),
+    };
+    let mut rows = vec![];
+    for (i, row) in range.rows().enumerate() {
+        let mut values = vec![];
+        for cell in row {
+            values.push(match cell {
+                Data::Empty => "".into(),
+                Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+                Data::Int(i) => i.to_string(),
+                // Excel stores every number as float, whole ones are written without fraction
+                Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => (*f as i64).to_string(),
+                Data::Float(f) => f.to_string(),
+                Data::Bool(b) => b.to_string(),
+                Data::DateTime(d) => match d.as_datetime() {
+                    Some(d) if d.time() == chrono::NaiveTime::MIN => {
+                        d.format("%Y-%m-%d").to_string()
+                    }
+                    Some(d) => d.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    None => d.as_f64().to_string(),
+                },
+                Data::Error(e) => bail!(
+                    "Row {} of sheet '{}' contains an error ({})",
+                    i + 1,
+                    sheet,
+                    e
+                ),
+            });
+        }
+        rows.push(values);
+    }
+    return Ok(rows);
+}
@@ -16,6 +16,23 @@
  * You should have received a copy of the GNU General Public License
  * along with invman. If not, see <https://www.gnu.org/licenses/>.
  */
+mod access;
+mod auth;
 pub mod common;
 pub mod database;
+mod document;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod filter;
+mod graphql;
+#[cfg(feature = "grpc")]
+// tonic::Status is what every method of a service fails with
+#[allow(clippy::result_large_err)]
+mod grpc;
+mod import;
+mod notify;
+mod plugins;
+mod progress;
+mod server;
 mod utils;
+mod yaml;
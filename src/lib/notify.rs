@@ -0,0 +1,226 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::{
+    common::args::{NotifyChannel, SmtpSecurity, SmtpSettings},
+    database::{AppConfig, DBEvent},
+};
+use anyhow::{bail, Result};
+use lettre::{
+    message::header::ContentType, transport::smtp::authentication::Credentials, Message,
+    SmtpTransport, Transport,
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/**
+ * Requests to webhooks that take longer are considered failed.
+ */
+const WEBHOOK_TIMEOUT_SECONDS: u64 = 10;
+
+/**
+ * A way to notify a recipient of an event, e.g. by email.
+ */
+pub trait Channel {
+    /**
+     * Notifies the recipient, whose format depends on the channel, e.g. an email address or a
+     * URL, of the event.
+     */
+    fn send(&self, recipient: &str, event: &DBEvent) -> Result<()>;
+}
+
+/**
+ * Creates the channel that notifications are sent through.
+ */
+pub fn notify_channel(channel: NotifyChannel, config: &AppConfig) -> Result<Box<dyn Channel + '_>> {
+    return match channel {
+        NotifyChannel::Email => match &config.smtp {
+            Some(settings) => Ok(Box::new(EmailChannel { settings })),
+            None => bail!("SMTP is not configured, see notify smtp set"),
+        },
+        NotifyChannel::Slack => Ok(Box::new(SlackChannel)),
+        NotifyChannel::Webhook => Ok(Box::new(WebhookChannel)),
+    };
+}
+
+fn summary(event: &DBEvent) -> String {
+    return format!(
+        "Event {} was recorded at {} by {}.",
+        event.event,
+        event.created_at,
+        event.dispatcher.as_deref().unwrap_or("unknown"),
+    );
+}
+
+fn post_json(url: &str, payload: Value) -> Result<()> {
+    ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECONDS))
+        .build()
+        .post(url)
+        .send_json(payload)?;
+    return Ok(());
+}
+
+struct EmailChannel<'a> {
+    settings: &'a SmtpSettings,
+}
+
+impl Channel for EmailChannel<'_> {
+    fn send(&self, recipient: &str, event: &DBEvent) -> Result<()> {
+        return send_email(
+            self.settings,
+            recipient,
+            &format!("[invman] {} #{}", event.event, event.id),
+            format!(
+                "{}\n\n{}\n",
+                summary(event),
+                serde_json::to_string_pretty(event)?
+            ),
+        );
+    }
+}
+
+/**
+ * Sends a plain text email through the configured SMTP server.
+ */
+pub fn send_email(settings: &SmtpSettings, to: &str, subject: &str, body: String) -> Result<()> {
+    let builder = match settings.security {
+        SmtpSecurity::Tls => SmtpTransport::relay(&settings.host)?,
+        SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&settings.host)?,
+        SmtpSecurity::None => SmtpTransport::builder_dangerous(&settings.host),
+    };
+    let builder = match settings.port {
+        Some(port) => builder.port(port),
+        None => builder,
+    };
+    let builder = match (&settings.username, &settings.password) {
+        (Some(username), Some(password)) => {
+            builder.credentials(Credentials::new(username.clone(), password.clone()))
+        }
+        _ => builder,
+    };
+    let message = Message::builder()
+        .from(settings.from.parse()?)
+        .to(to.parse()?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)?;
+    builder.build().send(&message)?;
+    return Ok(());
+}
+
+struct SlackChannel;
+
+impl Channel for SlackChannel {
+    fn send(&self, recipient: &str, event: &DBEvent) -> Result<()> {
+        return post_json(
+            recipient,
+            json!({
+                "text": format!(
+                    "{}\n```{}```",
+                    summary(event),
+                    serde_json::to_string_pretty(event)?
+                ),
+            }),
+        );
+    }
+}
+
+struct WebhookChannel;
+
+impl Channel for WebhookChannel {
+    fn send(&self, recipient: &str, event: &DBEvent) -> Result<()> {
+        return post_json(
+            recipient,
+            json!({
+                "text": summary(event),
+                "event": event,
+            }),
+        );
+    }
+}
+
+/**
+ * Compares a column of an entity against a value, e.g. quantity<5. Values are compared as
+ * numbers if both are numeric, otherwise as text.
+ */
+pub struct NotifyCondition {
+    pub column: String,
+    operator: &'static str,
+    value: String,
+}
+
+impl NotifyCondition {
+    pub fn parse(condition: &str) -> Result<NotifyCondition> {
+        // Operators of two characters go first, so that <= is not taken for <
+        for operator in ["<=", ">=", "!=", "<", ">", "="] {
+            if let Some((column, value)) = condition.split_once(operator) {
+                let column = column.trim();
+                if column.is_empty() {
+                    bail!("Condition '{}' is missing the column", condition);
+                }
+                return Ok(NotifyCondition {
+                    column: column.into(),
+                    operator,
+                    value: value.trim().into(),
+                });
+            }
+        }
+        bail!(
+            "Condition '{}' must compare a column with one of <, <=, >, >=, = or !=",
+            condition
+        );
+    }
+
+    fn matches(&self, entity: Option<&Value>) -> bool {
+        let value = match entity.and_then(|e| e.get(&self.column)) {
+            Some(Value::Null) | None => return false,
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+        };
+        let ordering = match (value.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            _ => Some(value.as_str().cmp(self.value.as_str())),
+        };
+        let ordering = match ordering {
+            Some(ordering) => ordering,
+            None => return false,
+        };
+        return match self.operator {
+            "<" => ordering.is_lt(),
+            "<=" => ordering.is_le(),
+            ">" => ordering.is_gt(),
+            ">=" => ordering.is_ge(),
+            "=" => ordering.is_eq(),
+            _ => ordering.is_ne(),
+        };
+    }
+
+    /**
+     * Whether the changed entity matches, but did not before the change, e.g. when its
+     * quantity drops below a threshold. This way a rule fires once instead of on every change.
+     */
+    pub fn started_matching(
+        &self,
+        entity: Option<&Value>,
+        previous_entity: Option<&Value>,
+    ) -> bool {
+        return self.matches(entity) && !self.matches(previous_entity);
+    }
+}
@@ -0,0 +1,279 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/**
+ * Directory the plugins are loaded from, next to the database
+ */
+const PLUGINS_DIR: &str = "./plugins";
+
+/**
+ * Instructions a plugin may execute per call, so that a faulty plugin cannot hang invman
+ */
+#[cfg(feature = "plugins")]
+const PLUGIN_FUEL: u64 = 100_000_000;
+
+#[derive(Debug, Serialize)]
+pub struct PluginInfo {
+    pub name: String,
+    /**
+     * Hooks the plugin implements, i.e. validate_entity, transform_on_add and report
+     */
+    pub hooks: Vec<String>,
+}
+
+/**
+ * Finds the WASM modules within the plugins directory, ordered by their file name.
+ */
+fn discover() -> Result<Vec<PathBuf>> {
+    let dir = Path::new(PLUGINS_DIR);
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut paths = fs::read_dir(dir)?
+        .map(|e| Ok(e?.path()))
+        .collect::<Result<Vec<PathBuf>>>()?
+        .into_iter()
+        .filter(|e| e.extension().is_some_and(|e| e == "wasm"))
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+    return Ok(paths);
+}
+
+fn plugin_name(path: &Path) -> String {
+    return path
+        .file_stem()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_default();
+}
+
+#[cfg(feature = "plugins")]
+mod host {
+    use super::{plugin_name, PLUGIN_FUEL};
+    use anyhow::{anyhow, bail, Result};
+    use std::{fs, path::Path};
+    use wasmi::{Config, Engine, Instance, Linker, Memory, Module, Store};
+
+    pub struct Plugin {
+        pub name: String,
+        module: Module,
+        store: Store<()>,
+        instance: Instance,
+        memory: Memory,
+    }
+
+    impl Plugin {
+        pub fn load(path: &Path) -> Result<Plugin> {
+            let name = plugin_name(path);
+            let mut config = Config::default();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config);
+            let module = Module::new(&engine, &fs::read(path)?[..])
+                .map_err(|e| anyhow!("Plugin '{}' is not a valid WASM module ({})", name, e))?;
+            let mut store = Store::new(&engine, ());
+            let instance = Linker::<()>::new(&engine)
+                .instantiate(&mut store, &module)
+                .and_then(|e| e.start(&mut store))
+                .map_err(|e| anyhow!("Plugin '{}' could not be instantiated ({})", name, e))?;
+            let memory = match instance.get_memory(&store, "memory") {
+                Some(memory) => memory,
+                None => bail!("Plugin '{}' does not export its memory", name),
+            };
+            return Ok(Plugin {
+                name,
+                module,
+                store,
+                instance,
+                memory,
+            });
+        }
+
+        pub fn hooks(&self) -> Vec<String> {
+            return self
+                .module
+                .exports()
+                .filter(|e| e.ty().func().is_some())
+                .map(|e| e.name().to_string())
+                .filter(|e| {
+                    matches!(
+                        e.as_str(),
+                        "validate_entity" | "transform_on_add" | "report"
+                    )
+                })
+                .collect();
+        }
+
+        /**
+         * Calls a hook with the input written into the plugin's memory. The hook returns where
+         * its output is found as (pointer << 32) | length.
+         *
+         * @returns The output of the hook, None if the plugin does not implement it
+         */
+        pub fn call(&mut self, hook: &str, input: &str) -> Result<Option<String>> {
+            let func = match self
+                .instance
+                .get_typed_func::<(i32, i32), i64>(&self.store, hook)
+            {
+                Ok(func) => func,
+                Err(_) => return Ok(None),
+            };
+            let alloc = self
+                .instance
+                .get_typed_func::<i32, i32>(&self.store, "alloc")
+                .map_err(|_| anyhow!("Plugin '{}' does not export alloc", self.name))?;
+            let failed = |e: &dyn std::fmt::Display| {
+                anyhow!("Plugin '{}' failed in {} ({})", self.name, hook, e)
+            };
+            self.store.set_fuel(PLUGIN_FUEL).map_err(|e| failed(&e))?;
+            let len = i32::try_from(input.len())?;
+            let ptr = alloc.call(&mut self.store, len).map_err(|e| failed(&e))?;
+            self.memory
+                .write(&mut self.store, usize::try_from(ptr)?, input.as_bytes())
+                .map_err(|e| failed(&e))?;
+            let packed = func
+                .call(&mut self.store, (ptr, len))
+                .map_err(|e| failed(&e))?;
+            let mut output = vec![0; usize::try_from(packed & 0xffff_ffff)?];
+            self.memory
+                .read(&self.store, usize::try_from(packed >> 32)?, &mut output)
+                .map_err(|e| failed(&e))?;
+            return Ok(Some(String::from_utf8(output).map_err(|e| failed(&e))?));
+        }
+    }
+}
+
+#[cfg(feature = "plugins")]
+fn load_all() -> Result<Vec<host::Plugin>> {
+    return discover()?.iter().map(|e| host::Plugin::load(e)).collect();
+}
+
+/**
+ * Without plugin support no plugin is loaded, which must not go unnoticed when there are any.
+ */
+#[cfg(not(feature = "plugins"))]
+fn load_all() -> Result<Vec<NoPlugin>> {
+    if let Some(path) = discover()?.first() {
+        bail!(
+            "Found plugin '{}', but invman was built without the plugins feature",
+            plugin_name(path)
+        );
+    }
+    return Ok(vec![]);
+}
+
+#[cfg(not(feature = "plugins"))]
+struct NoPlugin {
+    name: String,
+}
+
+#[cfg(not(feature = "plugins"))]
+impl NoPlugin {
+    fn hooks(&self) -> Vec<String> {
+        return vec![];
+    }
+
+    fn call(&mut self, _hook: &str, _input: &str) -> Result<Option<String>> {
+        return Ok(None);
+    }
+}
+
+pub fn list() -> Result<Vec<PluginInfo>> {
+    return Ok(load_all()?
+        .iter()
+        .map(|e| PluginInfo {
+            name: e.name.clone(),
+            hooks: e.hooks(),
+        })
+        .collect());
+}
+
+/**
+ * Lets each plugin check the values of an entity, given as name=value, before they are written.
+ * A plugin rejects them by returning an error message.
+ */
+pub fn validate_entity(params: &[String]) -> Result<()> {
+    let input = to_json(params)?;
+    for mut plugin in load_all()? {
+        if let Some(error) = plugin.call("validate_entity", &input)? {
+            if !error.is_empty() {
+                bail!("Plugin '{}' rejected the entity: {}", plugin.name, error);
+            }
+        }
+    }
+    return Ok(());
+}
+
+/**
+ * Lets each plugin change the values of an entity to add, given as name=value, one after another.
+ */
+pub fn transform_on_add(params: &[String]) -> Result<Vec<String>> {
+    let mut params = params.to_vec();
+    for mut plugin in load_all()? {
+        if let Some(output) = plugin.call("transform_on_add", &to_json(&params)?)? {
+            let values: serde_json::Map<String, serde_json::Value> =
+                match serde_json::from_str(&output) {
+                    Ok(values) => values,
+                    Err(e) => bail!(
+                        "Plugin '{}' returned an invalid entity ({})",
+                        plugin.name,
+                        e
+                    ),
+                };
+            params = values
+                .into_iter()
+                .map(|(name, value)| match value {
+                    serde_json::Value::String(value) => format!("{}={}", name, value),
+                    value => format!("{}={}", name, value),
+                })
+                .collect();
+        }
+    }
+    return Ok(params);
+}
+
+/**
+ * Creates the custom report of the plugin from the entities given as JSON array.
+ */
+pub fn report(name: &str, entities: &str) -> Result<String> {
+    let mut plugin = match load_all()?.into_iter().find(|e| e.name == name) {
+        Some(plugin) => plugin,
+        None => bail!("Plugin '{}' does not exist", name),
+    };
+    return match plugin.call("report", entities)? {
+        Some(report) => Ok(report),
+        None => bail!("Plugin '{}' does not implement report", name),
+    };
+}
+
+fn to_json(params: &[String]) -> Result<String> {
+    let values = params
+        .iter()
+        .map(|e| match e.split_once('=') {
+            Some((name, value)) => Ok((name.to_string(), serde_json::Value::from(value))),
+            None => bail!("Given string {} is not in valid schema notation", e),
+        })
+        .collect::<Result<serde_json::Map<String, serde_json::Value>>>()?;
+    return Ok(serde_json::to_string(&values)?);
+}
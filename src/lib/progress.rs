@@ -0,0 +1,233 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde_json::{json, Value};
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+static OUTPUT: OnceLock<ProgressOutput> = OnceLock::new();
+
+/**
+ * Time between two events without a known total
+ */
+const EVENT_INTERVAL: Duration = Duration::from_millis(100);
+
+const REDRAWS_PER_SECOND: u8 = 10;
+
+/**
+ * How long running operations report their progress on stderr
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressOutput {
+    Bar,
+    /**
+     * One JSON object per line, e.g. {"task":"import","done":1000,"total":5000}
+     */
+    Json,
+    None,
+}
+
+/**
+ * Configures the progress output for the rest of the run, a bar if stderr is a terminal and
+ * nothing otherwise if None.
+ */
+pub fn init_progress(output: Option<ProgressOutput>) {
+    let _ = OUTPUT.set(output.unwrap_or_else(default_output));
+}
+
+fn default_output() -> ProgressOutput {
+    return match io::stderr().is_terminal() {
+        true => ProgressOutput::Bar,
+        false => ProgressOutput::None,
+    };
+}
+
+/**
+ * Progress of a long running operation, whose total is known or not. Finished once dropped,
+ * a bar then stays on its line, so that following messages start on a line of their own.
+ */
+pub struct Progress {
+    task: &'static str,
+    total: Option<u64>,
+    done: u64,
+    output: ProgressOutput,
+    bar: Option<ProgressBar>,
+    reported: Option<(Instant, u64)>,
+}
+
+impl Progress {
+    /**
+     * @param task Name of the operation in JSON events, e.g. import
+     * @param label Description of the operation next to the bar, e.g. Importing rows
+     */
+    pub fn start(task: &'static str, label: &'static str, total: Option<u64>) -> Progress {
+        return Progress::with_output(task, label, total, *OUTPUT.get_or_init(default_output));
+    }
+
+    fn with_output(
+        task: &'static str,
+        label: &'static str,
+        total: Option<u64>,
+        output: ProgressOutput,
+    ) -> Progress {
+        let bar = match output {
+            ProgressOutput::Bar => Some(bar(label, total)),
+            _ => None,
+        };
+        let mut progress = Progress {
+            task,
+            total,
+            done: 0,
+            output,
+            bar,
+            reported: None,
+        };
+        progress.report(false);
+        return progress;
+    }
+
+    pub fn set(&mut self, done: u64) {
+        self.done = done;
+        self.report(false);
+    }
+
+    pub fn advance(&mut self, by: u64) {
+        self.set(self.done + by);
+    }
+
+    fn percent(&self) -> Option<u64> {
+        return self
+            .total
+            .map(|total| (self.done * 100).checked_div(total).unwrap_or(100).min(100));
+    }
+
+    fn report(&mut self, finished: bool) {
+        if let Some(bar) = &self.bar {
+            // The bar limits its redraws itself
+            bar.set_position(self.done);
+            if finished {
+                bar.finish();
+            }
+            return;
+        }
+        if self.output != ProgressOutput::Json || !self.is_event_due(finished) {
+            return;
+        }
+        self.reported = Some((Instant::now(), self.percent().unwrap_or_default()));
+        let _ = writeln!(io::stderr().lock(), "{}", self.event(finished));
+    }
+
+    /**
+     * Events are sent for every percent, or in intervals without a known total
+     */
+    fn is_event_due(&self, finished: bool) -> bool {
+        return match self.reported {
+            _ if finished => true,
+            None => true,
+            Some((_, percent)) if self.total.is_some() => self.percent() != Some(percent),
+            Some((at, _)) => at.elapsed() >= EVENT_INTERVAL,
+        };
+    }
+
+    fn event(&self, finished: bool) -> Value {
+        return json!({
+            "task": self.task,
+            "done": self.done,
+            "total": self.total,
+            "finished": finished,
+        });
+    }
+}
+
+fn bar(label: &'static str, total: Option<u64>) -> ProgressBar {
+    let (bar, template) = match total {
+        Some(total) => (
+            ProgressBar::new(total),
+            "{msg} [{bar:30}] {percent:>3}% {pos}/{len} {elapsed}",
+        ),
+        None => (ProgressBar::new_spinner(), "{msg} {pos} {elapsed}"),
+    };
+    bar.set_draw_target(ProgressDrawTarget::stderr_with_hz(REDRAWS_PER_SECOND));
+    bar.set_style(
+        ProgressStyle::with_template(template)
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("#-"),
+    );
+    bar.set_message(label);
+    return bar;
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.report(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_progress(total: Option<u64>) -> Progress {
+        return Progress::with_output("import", "Importing rows", total, ProgressOutput::Json);
+    }
+
+    #[test]
+    fn percent_is_bounded() {
+        let mut progress = json_progress(Some(200));
+        progress.set(50);
+        assert_eq!(progress.percent(), Some(25));
+        progress.set(300);
+        assert_eq!(progress.percent(), Some(100));
+        progress.total = Some(0);
+        assert_eq!(progress.percent(), Some(100));
+        progress.total = None;
+        assert_eq!(progress.percent(), None);
+    }
+
+    #[test]
+    fn events_are_sent_for_every_percent() {
+        let mut progress = json_progress(Some(1000));
+        assert!(!progress.is_event_due(false));
+        progress.done = 9;
+        assert!(!progress.is_event_due(false));
+        progress.done = 10;
+        assert!(progress.is_event_due(false));
+        assert!(progress.is_event_due(true));
+        assert_eq!(
+            progress.event(true),
+            json!({"task":"import","done":10,"total":1000,"finished":true})
+        );
+    }
+
+    #[test]
+    fn bars_track_the_progress() {
+        let mut progress =
+            Progress::with_output("export", "Exporting", Some(10), ProgressOutput::Bar);
+        progress.advance(4);
+        progress.advance(3);
+        assert_eq!(progress.bar.as_ref().map(|e| e.position()), Some(7));
+        drop(progress);
+        let progress = Progress::with_output("export", "Exporting", None, ProgressOutput::None);
+        assert!(progress.bar.is_none() && progress.reported.is_none());
+    }
+}
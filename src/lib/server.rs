@@ -0,0 +1,2546 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::{
+    access::{
+        operation_access, require_column, require_read, require_read_collection,
+        require_read_filter, require_write, require_write_collection,
+    },
+    common::args::{
+        check_event_names, check_expansion, check_meta_key, prometheus_lines,
+        with_context_defaults, AdjustProps, BomComponentProps, BuildProps, ChangeMarker,
+        ColumnType, CommandContext, DisplayFormat, ExchangeRates, Expansion, Grouping, Hook,
+        InvManNotationHelper, InventoryListProps, KitItemProps, LdapSettings, LoanProps,
+        NotifyRuleProps, OutputType, OwnershipPolicy, PasswordPolicy, PickProps, PurchaseProps,
+        RetentionRules, RuleProps, ScheduledJob, SchemaDeclaration, SearchProps, SerialStatus,
+        ServeTls, SmtpSettings, SupplierProps, TransferProps, UserTokenIssueArgs,
+        UserTokenRefreshArgs, WhereExpr, EVENTS_POLL_MILLISECONDS,
+    },
+    database::{
+        AppConfig, DBDump, DBEvent, DBFixtureUser, DBUser, InvManDBPool, InvManSqlitePool,
+        KeyValueCollection, KeyValueTypeEntry, PermissionMode, SchemaCollection, PASSWORD_EXPIRED,
+    },
+    filter::CompareOp,
+    graphql::{self, Field, GraphqlRequest, OperationKind, Response as GraphqlResponse, Schema},
+    plugins,
+    utils::prepare_raw_sql,
+};
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::rand::{SecureRandom, SystemRandom};
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig, ServerConnection,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    io::{Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tiny_http::{Header, Request, Response, Server};
+
+/**
+ * Limit of a request's body, which holds e.g. the content of attachments and images
+ */
+const MAX_REQUEST_BYTES: u64 = 64 * 1024 * 1024;
+
+/**
+ * How long verified credentials are reused, so that a command's requests do not hash the
+ * password each. Changes to the user, e.g. of the password, take effect after this at the latest.
+ */
+const CREDENTIALS_TTL: Duration = Duration::from_secs(30);
+
+/**
+ * How long a client may take for the TLS handshake before it is disconnected
+ */
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/**
+ * Upper bounds of the buckets of the request latency histogram, in seconds
+ */
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/**
+ * Limit of streams of /events open at the same time
+ */
+const MAX_EVENT_STREAMS: usize = 64;
+
+/**
+ * Header carrying the one-time password of users with two-factor authentication
+ */
+pub(crate) const OTP_HEADER: &str = "X-Invman-Otp";
+
+/**
+ * Validity of tokens issued without a ttl, like by user token issue
+ */
+const DEFAULT_TOKEN_TTL: &str = "30d";
+
+/**
+ * Operations that do not require authentication, as their commands do not either
+ */
+const PUBLIC_METHODS: [&str; 6] = [
+    "get_config",
+    "user_register",
+    "health",
+    "latest_event",
+    "load",
+    "fixture_load",
+];
+
+/**
+ * Serves the database to remote clients, i.e. InvManHttpClient, until the process is stopped.
+ * Each operation is a POST to /rpc/<operation> with its parameters as JSON array, answered by
+ * {"result": ...} or {"error": "..."}. GET /metrics renders the metrics of the database and of
+ * the requests handled so far in the Prometheus text format, GET /health the report of the
+ * health command and GET /events streams the events as they are recorded. The server authenticates the user of every request and
+ * checks the same permissions as the commands do, so that clients cannot be trusted to.
+ * With tls, connections are encrypted and, if it names client CAs, clients have to present a
+ * certificate issued by one of them. With grpc, the InventoryService of
+ * proto/invman/v1/inventory.proto is served instead, whose methods are checked the same way.
+ */
+pub fn serve(
+    path: &str,
+    listen: &str,
+    workers: usize,
+    read_only: bool,
+    tls: Option<&ServeTls>,
+    grpc: bool,
+) -> Result<()> {
+    #[cfg(not(feature = "grpc"))]
+    if grpc {
+        bail!("invman was built without the grpc feature");
+    }
+    let state = ServerState {
+        pool: InvManSqlitePool::new(path, workers)?,
+        credentials: CredentialCache::default(),
+        metrics: ServerMetrics::default(),
+        read_only,
+        event_streams: AtomicUsize::new(0),
+    };
+    #[cfg(feature = "grpc")]
+    if grpc {
+        return crate::grpc::serve(state, listen, workers, tls);
+    }
+    let (server, tls) = match tls {
+        None => match Server::http(listen) {
+            Ok(server) => (server, None),
+            Err(e) => bail!("Could not listen on {} ({})", listen, e),
+        },
+        Some(tls) => {
+            let config = server_tls_config(tls)?;
+            let listener = match TcpListener::bind(listen) {
+                Ok(listener) => listener,
+                Err(e) => bail!("Could not listen on {} ({})", listen, e),
+            };
+            let socket = PlainSocket::new()?;
+            (plain_server(&socket)?, Some((listener, config, socket)))
+        }
+    };
+    tracing::info!(listen, workers, tls = tls.is_some(), "Serving the database");
+    std::thread::scope(|scope| {
+        if let Some((listener, config, socket)) = &tls {
+            scope.spawn(|| accept_tls(listener, config, socket.path()));
+        }
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                match server.recv() {
+                    // A panicking request is answered by tiny_http when dropped, the worker goes on
+                    Ok(request) => {
+                        let handled = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                            handle(request, scope, &state)
+                        }));
+                        if handled.is_err() {
+                            tracing::error!("Request handler panicked");
+                        }
+                    }
+                    Err(e) => tracing::error!(error = %e, "Could not receive request"),
+                }
+            });
+        }
+    });
+    return Ok(());
+}
+
+/**
+ * Reads the PEM encoded certificates of the file, e.g. a certificate chain or the CAs to trust.
+ */
+pub(crate) fn read_certificates(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let pem = match std::fs::read(path) {
+        Ok(pem) => pem,
+        Err(e) => bail!("Could not read {} ({})", path, e),
+    };
+    let certificates = CertificateDer::pem_slice_iter(&pem).collect::<Result<Vec<_>, _>>()?;
+    if certificates.is_empty() {
+        bail!("No PEM encoded certificate found in {}", path);
+    }
+    return Ok(certificates);
+}
+
+pub(crate) fn read_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let pem = match std::fs::read(path) {
+        Ok(pem) => pem,
+        Err(e) => bail!("Could not read {} ({})", path, e),
+    };
+    return match PrivateKeyDer::from_pem_slice(&pem) {
+        Ok(key) => Ok(key),
+        Err(e) => bail!("No PEM encoded private key found in {} ({})", path, e),
+    };
+}
+
+fn server_tls_config(tls: &ServeTls) -> Result<Arc<ServerConfig>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = match &tls.client_ca {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for certificate in read_certificates(path)? {
+                roots.add(certificate)?;
+            }
+            let verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build()?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let certificates = read_certificates(&tls.certificate)?;
+    return match builder.with_single_cert(certificates, read_private_key(&tls.key)?) {
+        Ok(config) => Ok(Arc::new(config)),
+        Err(e) => bail!("Could not use the certificate {} ({})", tls.certificate, e),
+    };
+}
+
+/**
+ * Unix socket the decrypted connections are forwarded to. It lies in a directory only the user
+ * of the server can access, so that other users of the machine cannot get around TLS through it.
+ */
+struct PlainSocket {
+    dir: PathBuf,
+}
+
+impl PlainSocket {
+    fn new() -> Result<PlainSocket> {
+        let mut suffix = [0u8; 8];
+        if SystemRandom::new().fill(&mut suffix).is_err() {
+            bail!("Could not generate the name of the socket");
+        }
+        let suffix: String = suffix.iter().map(|e| format!("{:02x}", e)).collect();
+        let dir = std::env::temp_dir().join(format!("invman-serve-{}", suffix));
+        if let Err(e) = create_private_dir(&dir) {
+            bail!("Could not create {} ({})", dir.display(), e);
+        }
+        return Ok(PlainSocket { dir });
+    }
+
+    fn path(&self) -> PathBuf {
+        return self.dir.join("plain.sock");
+    }
+}
+
+impl Drop for PlainSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    return std::fs::DirBuilder::new().mode(0o700).create(dir);
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    return std::fs::create_dir(dir);
+}
+
+#[cfg(unix)]
+fn plain_server(socket: &PlainSocket) -> Result<Server> {
+    return match Server::http_unix(&socket.path()) {
+        Ok(server) => Ok(server),
+        Err(e) => bail!("Could not listen on {} ({})", socket.path().display(), e),
+    };
+}
+
+#[cfg(not(unix))]
+fn plain_server(_: &PlainSocket) -> Result<Server> {
+    bail!("TLS is only supported on Unix, put the server behind a reverse proxy with TLS instead");
+}
+
+/**
+ * Decrypts the connections of clients and forwards them to the server listening on socket, each
+ * on threads of its own.
+ */
+#[cfg(unix)]
+fn accept_tls(listener: &TcpListener, config: &Arc<ServerConfig>, socket: PathBuf) {
+    for client in listener.incoming() {
+        let client = match client {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!(error = %e, "Could not accept connection");
+                continue;
+            }
+        };
+        let (config, socket) = (config.clone(), socket.clone());
+        std::thread::spawn(move || {
+            if let Err(e) = forward_tls(client, config, &socket) {
+                tracing::debug!(error = %e, "TLS connection closed");
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn accept_tls(_: &TcpListener, _: &Arc<ServerConfig>, _: PathBuf) {
+    // Not reached, as plain_server fails before
+}
+
+#[cfg(unix)]
+fn forward_tls(mut client: TcpStream, config: Arc<ServerConfig>, socket: &Path) -> Result<()> {
+    let mut tls = ServerConnection::new(config)?;
+    client.set_read_timeout(Some(TLS_HANDSHAKE_TIMEOUT))?;
+    while tls.is_handshaking() {
+        if let Err(e) = tls.complete_io(&mut client) {
+            let peer = client
+                .peer_addr()
+                .map(|e| e.to_string())
+                .unwrap_or_default();
+            tracing::warn!(peer, error = %e, "TLS handshake failed");
+            return Ok(());
+        }
+    }
+    client.set_read_timeout(None)?;
+    let plain = std::os::unix::net::UnixStream::connect(socket)?;
+    let tls = Mutex::new(tls);
+    return std::thread::scope(|scope| {
+        let responses = scope.spawn(|| {
+            let forwarded = forward_responses(&tls, &client, &plain);
+            // The server answers no further requests, so they are not waited for
+            let _ = client.shutdown(Shutdown::Both);
+            return forwarded;
+        });
+        let requests = forward_requests(&tls, &client, &plain);
+        // Lets the server answer the requests it already received before it closes
+        let _ = plain.shutdown(Shutdown::Write);
+        let responses = match responses.join() {
+            Ok(forwarded) => forwarded,
+            Err(_) => Err(anyhow!("Forwarding the responses panicked")),
+        };
+        return requests.and(responses);
+    });
+}
+
+/**
+ * Passes the decrypted requests of the client on to the server, until the client closes the
+ * connection.
+ */
+#[cfg(unix)]
+fn forward_requests(
+    tls: &Mutex<ServerConnection>,
+    mut client: &TcpStream,
+    mut plain: &std::os::unix::net::UnixStream,
+) -> Result<()> {
+    let mut buffer = [0u8; 16 * 1024];
+    loop {
+        // Requests may have been received together with the end of the handshake
+        let mut decrypted = vec![];
+        let closed = match tls.lock().unwrap().reader().read_to_end(&mut decrypted) {
+            Ok(_) => true,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => false,
+            Err(e) => return Err(e.into()),
+        };
+        plain.write_all(&decrypted)?;
+        if closed {
+            return Ok(());
+        }
+        let read = client.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+        let mut received = &buffer[..read];
+        let mut tls = tls.lock().unwrap();
+        while !received.is_empty() {
+            tls.read_tls(&mut received)?;
+            let processed = tls.process_new_packets();
+            // Alerts are sent before giving up on the connection
+            while tls.wants_write() {
+                tls.write_tls(&mut client)?;
+            }
+            processed?;
+        }
+    }
+}
+
+/**
+ * Passes the responses of the server on to the client encrypted, until the server closes the
+ * connection.
+ */
+#[cfg(unix)]
+fn forward_responses(
+    tls: &Mutex<ServerConnection>,
+    mut client: &TcpStream,
+    mut plain: &std::os::unix::net::UnixStream,
+) -> Result<()> {
+    let mut buffer = [0u8; 16 * 1024];
+    loop {
+        let read = plain.read(&mut buffer)?;
+        let mut tls = tls.lock().unwrap();
+        match read {
+            0 => tls.send_close_notify(),
+            _ => tls.writer().write_all(&buffer[..read])?,
+        }
+        while tls.wants_write() {
+            tls.write_tls(&mut client)?;
+        }
+        if read == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/**
+ * Users by the hash of the credentials they were authenticated with
+ */
+#[derive(Default)]
+struct CredentialCache {
+    users: Mutex<HashMap<Vec<u8>, (DBUser, Instant)>>,
+}
+
+impl CredentialCache {
+    fn get(&self, auth: &str) -> Option<DBUser> {
+        let users = self.users.lock().unwrap();
+        return match users.get(Sha256::digest(auth).as_slice()) {
+            Some((user, verified_at)) if verified_at.elapsed() < CREDENTIALS_TTL => {
+                Some(user.clone())
+            }
+            _ => None,
+        };
+    }
+
+    fn insert(&self, auth: &str, user: &DBUser) {
+        let mut users = self.users.lock().unwrap();
+        users.retain(|_, (_, verified_at)| verified_at.elapsed() < CREDENTIALS_TTL);
+        users.insert(
+            Sha256::digest(auth).to_vec(),
+            (user.clone(), Instant::now()),
+        );
+    }
+}
+
+/**
+ * What the workers of a server share
+ */
+pub(crate) struct ServerState {
+    pool: InvManSqlitePool,
+    credentials: CredentialCache,
+    metrics: ServerMetrics,
+    read_only: bool,
+    /**
+     * Open streams of /events, each of which has a thread of its own
+     */
+    event_streams: AtomicUsize,
+}
+
+impl ServerState {
+    /**
+     * Authenticates the user like for /rpc and runs the function in the context of the request
+     *
+     * @returns The result of the function, otherwise the status to answer with and the error
+     */
+    pub(crate) fn with_user<T>(
+        &self,
+        auth: Option<String>,
+        otp: Option<String>,
+        f: impl FnOnce(&mut CommandContext, &DBUser) -> Result<T>,
+    ) -> std::result::Result<T, (u16, anyhow::Error)> {
+        let result = self.pool.with(|db| {
+            let mut config = db.get_config()?;
+            let mut ctx = request_context(db, &mut config, auth, otp, self.read_only);
+            let user = match authenticate(&ctx, &self.credentials) {
+                Ok(user) => user,
+                Err(e) => return Ok(Err((401, e))),
+            };
+            if user.password_expired {
+                let e = anyhow!("User authentication failure ({})", PASSWORD_EXPIRED);
+                return Ok(Err((401, e)));
+            }
+            return Ok(
+                f(&mut ctx, &user).map_err(|e| match e.is::<UnknownOperation>() {
+                    true => (404, e),
+                    false => (400, e),
+                }),
+            );
+        });
+        return result.unwrap_or_else(|e| Err((503, e)));
+    }
+
+    /**
+     * Counts a stream of events as open, unless too many streams are open already
+     */
+    pub(crate) fn open_event_stream(&self) -> Result<()> {
+        if self.event_streams.fetch_add(1, Ordering::Relaxed) >= MAX_EVENT_STREAMS {
+            self.event_streams.fetch_sub(1, Ordering::Relaxed);
+            bail!(
+                "No more than {} event streams can be open",
+                MAX_EVENT_STREAMS
+            );
+        }
+        return Ok(());
+    }
+
+    pub(crate) fn close_event_stream(&self) {
+        self.event_streams.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/**
+ * Authenticates the user of a request to an endpoint outside of /rpc, who has to be allowed to
+ * read the table.
+ *
+ * @returns The user, otherwise the status to answer with and the error
+ */
+fn authorize_request(
+    state: &ServerState,
+    request: &Request,
+    table: &str,
+) -> std::result::Result<DBUser, (u16, anyhow::Error)> {
+    let (auth, otp) = (authorization(request), one_time_password(request));
+    return authorize(state, auth, otp, Some(table));
+}
+
+/**
+ * Authenticates the user, who has to be allowed to read the table if one is given.
+ */
+pub(crate) fn authorize(
+    state: &ServerState,
+    auth: Option<String>,
+    otp: Option<String>,
+    table: Option<&str>,
+) -> std::result::Result<DBUser, (u16, anyhow::Error)> {
+    let user = state.pool.with(|db| {
+        let mut config = db.get_config()?;
+        let ctx = request_context(db, &mut config, auth, otp, state.read_only);
+        return Ok(authenticate(&ctx, &state.credentials));
+    });
+    return match user {
+        Ok(Ok(user)) => match table.map_or(Ok(()), |table| require_read(&user, table)) {
+            Ok(_) => Ok(user),
+            Err(e) => Err((403, e)),
+        },
+        Ok(Err(e)) => Err((401, e)),
+        Err(e) => Err((503, e)),
+    };
+}
+
+/**
+ * Amount and duration of the handled requests by operation
+ */
+#[derive(Default)]
+struct ServerMetrics {
+    operations: Mutex<BTreeMap<String, OperationMetrics>>,
+    auth_failures: AtomicU64,
+}
+
+#[derive(Default)]
+struct OperationMetrics {
+    requests: BTreeMap<u16, u64>,
+    /**
+     * Requests that took at most the duration of the bucket with the same index
+     */
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    seconds: f64,
+}
+
+impl ServerMetrics {
+    /**
+     * Operations are only recorded by their name once they were answered, e.g. not just
+     * rejected for the credentials, so that clients cannot add metrics at will. Requests before
+     * are recorded as unknown operation.
+     */
+    fn record(&self, operation: &str, answered: bool, status: u16, elapsed: Duration) {
+        if status == 401 {
+            self.auth_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut operations = self.operations.lock().unwrap();
+        let operation = match answered || operations.contains_key(operation) {
+            true => operation,
+            false => "unknown",
+        };
+        let metrics = operations.entry(operation.into()).or_default();
+        *metrics.requests.entry(status).or_default() += 1;
+        let seconds = elapsed.as_secs_f64();
+        for (bucket, le) in LATENCY_BUCKETS.iter().enumerate() {
+            if seconds <= *le {
+                metrics.buckets[bucket] += 1;
+            }
+        }
+        metrics.seconds += seconds;
+    }
+
+    fn prometheus_lines(&self) -> Vec<String> {
+        let operations = self.operations.lock().unwrap();
+        let mut lines = vec![
+            "# HELP invman_requests_total Requests handled by the server".to_string(),
+            "# TYPE invman_requests_total counter".into(),
+        ];
+        for (operation, metrics) in operations.iter() {
+            for (status, count) in &metrics.requests {
+                lines.push(format!(
+                    "invman_requests_total{{operation=\"{}\",status=\"{}\"}} {}",
+                    operation, status, count
+                ));
+            }
+        }
+        lines.extend([
+            "# HELP invman_auth_failures_total Requests whose credentials were rejected"
+                .to_string(),
+            "# TYPE invman_auth_failures_total counter".into(),
+            format!(
+                "invman_auth_failures_total {}",
+                self.auth_failures.load(Ordering::Relaxed)
+            ),
+            "# HELP invman_request_duration_seconds Time taken to handle requests".into(),
+            "# TYPE invman_request_duration_seconds histogram".into(),
+        ]);
+        for (operation, metrics) in operations.iter() {
+            for (bucket, le) in LATENCY_BUCKETS.iter().enumerate() {
+                lines.push(format!(
+                    "invman_request_duration_seconds_bucket{{operation=\"{}\",le=\"{}\"}} {}",
+                    operation, le, metrics.buckets[bucket]
+                ));
+            }
+            let count: u64 = metrics.requests.values().sum();
+            lines.extend([
+                format!(
+                    "invman_request_duration_seconds_bucket{{operation=\"{}\",le=\"+Inf\"}} {}",
+                    operation, count
+                ),
+                format!(
+                    "invman_request_duration_seconds_sum{{operation=\"{}\"}} {}",
+                    operation, metrics.seconds
+                ),
+                format!(
+                    "invman_request_duration_seconds_count{{operation=\"{}\"}} {}",
+                    operation, count
+                ),
+            ]);
+        }
+        return lines;
+    }
+}
+
+/**
+ * Error of operations the server does not offer, which are answered with 404
+ */
+#[derive(Debug)]
+struct UnknownOperation(String);
+
+impl Display for UnknownOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "Operation {} is not available remotely", self.0);
+    }
+}
+
+impl std::error::Error for UnknownOperation {}
+
+fn handle<'scope>(
+    request: Request,
+    scope: &'scope std::thread::Scope<'scope, '_>,
+    state: &'scope ServerState,
+) {
+    let started = Instant::now();
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let routes: Vec<&Route> = ROUTES.iter().filter(|e| e.path == path).collect();
+    let (operation, (status, answered)) = match routes.first() {
+        Some(first) => {
+            let route = routes.iter().find(|e| e.method == *request.method());
+            let status = match route {
+                Some(route) => handle_route(request, route, scope, state),
+                None => {
+                    reply(request, 405, &json!({ "error": "Method not allowed" }));
+                    405
+                }
+            };
+            (first.operation.to_string(), (status, true))
+        }
+        None => match path.strip_prefix("/rpc/") {
+            Some(method) => (method.to_string(), handle_rpc(request, method, state)),
+            None => {
+                reply(request, 404, &json!({ "error": "Not found" }));
+                ("unknown".to_string(), (404, false))
+            }
+        },
+    };
+    let elapsed = started.elapsed();
+    state.metrics.record(&operation, answered, status, elapsed);
+    let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+    tracing::info!(operation, status, elapsed_ms, "Request handled");
+}
+
+/**
+ * Route outside of /rpc, whose users are authorized before it is handled
+ */
+struct Route {
+    method: tiny_http::Method,
+    path: &'static str,
+    /**
+     * Name the requests of the route are recorded by in the metrics
+     */
+    operation: &'static str,
+    access: Access,
+}
+
+/**
+ * Users that may request a route. The operations of /rpc check the same permissions as the
+ * commands instead.
+ */
+enum Access {
+    Anyone,
+    /**
+     * Authenticated users, whose permissions the route checks itself
+     */
+    User,
+    /**
+     * Users allowed to read the table
+     */
+    Read(&'static str),
+}
+
+const ROUTES: [Route; 7] = [
+    Route {
+        method: tiny_http::Method::Get,
+        path: "/health",
+        operation: "health",
+        access: Access::Anyone,
+    },
+    Route {
+        method: tiny_http::Method::Get,
+        path: "/metrics",
+        operation: "metrics",
+        access: Access::Read("db"),
+    },
+    Route {
+        method: tiny_http::Method::Get,
+        path: "/events",
+        operation: "events",
+        access: Access::Read("db"),
+    },
+    Route {
+        method: tiny_http::Method::Get,
+        path: "/graphql",
+        operation: "graphql",
+        access: Access::Read("config"),
+    },
+    // Authenticates the user itself, so that failures are answered as GraphQL responses
+    Route {
+        method: tiny_http::Method::Post,
+        path: "/graphql",
+        operation: "graphql",
+        access: Access::Anyone,
+    },
+    Route {
+        method: tiny_http::Method::Post,
+        path: "/token",
+        operation: "token_issue",
+        access: Access::User,
+    },
+    Route {
+        method: tiny_http::Method::Post,
+        path: "/token/refresh",
+        operation: "token_refresh",
+        access: Access::User,
+    },
+];
+
+/**
+ * Authorizes the user of the request to the route before it is handled.
+ *
+ * @returns The status of the response
+ */
+fn handle_route<'scope>(
+    request: Request,
+    route: &Route,
+    scope: &'scope std::thread::Scope<'scope, '_>,
+    state: &'scope ServerState,
+) -> u16 {
+    let authorized = match route.access {
+        Access::Anyone => Ok(()),
+        Access::User => authorize(
+            state,
+            authorization(&request),
+            one_time_password(&request),
+            None,
+        )
+        .map(|_| ()),
+        Access::Read(table) => authorize_request(state, &request, table).map(|_| ()),
+    };
+    if let Err((status, e)) = authorized {
+        reply(request, status, &error_body(&e));
+        return status;
+    }
+    return match route.operation {
+        "health" => handle_health(request, state),
+        "metrics" => handle_metrics(request, state),
+        "events" => handle_events(request, scope, state),
+        "graphql" => handle_graphql(request, scope, state),
+        operation => handle_token(request, operation, state),
+    };
+}
+
+/**
+ * Context of a request, with the read-only option of the config applied like for commands
+ */
+fn request_context<'a>(
+    db: &'a mut dyn InvManDBPool,
+    config: &'a mut AppConfig,
+    auth: Option<String>,
+    otp: Option<String>,
+    read_only: bool,
+) -> CommandContext<'a> {
+    let mut ctx = CommandContext::new(db, config, auth, OutputType::Json);
+    ctx.otp = otp;
+    ctx.read_only |= read_only;
+    return ctx;
+}
+
+/**
+ * Renders the metrics of the database, for users that may read it like with db metrics, and
+ * those of the server.
+ */
+fn handle_metrics(request: Request, state: &ServerState) -> u16 {
+    let (status, body) = match state.pool.with(|db| db.metrics()) {
+        Ok(metrics) => {
+            let mut lines = prometheus_lines(&metrics);
+            lines.extend(state.metrics.prometheus_lines());
+            (200, lines.join("\n") + "\n")
+        }
+        Err(e) => (503, e.to_string()),
+    };
+    reply_text(request, status, "text/plain; version=0.0.4", body);
+    return status;
+}
+
+/**
+ * Streams the events recorded from now on as server-sent events, to users that may read the
+ * database like with events watch. Events are filtered by their names with the event
+ * parameters, e.g. /events?event=inventory-add&event=inventory-edit. A stream continues after
+ * the event given by the Last-Event-ID header, which EventSource sends when it reconnects, or
+ * by the after parameter. Each stream is written by a thread of its own, so that it does not
+ * occupy a worker.
+ */
+fn handle_events<'scope>(
+    request: Request,
+    scope: &'scope std::thread::Scope<'scope, '_>,
+    state: &'scope ServerState,
+) -> u16 {
+    return match EventStream::new(state, &request) {
+        Ok(stream) => start_event_stream(request, stream, scope, state),
+        Err((status, e)) => {
+            reply(request, status, &error_body(&e));
+            status
+        }
+    };
+}
+
+/**
+ * Writes the stream of events as answer to the request by a thread of its own, unless too
+ * many streams are open already.
+ */
+fn start_event_stream<'scope>(
+    request: Request,
+    stream: EventStream,
+    scope: &'scope std::thread::Scope<'scope, '_>,
+    state: &'scope ServerState,
+) -> u16 {
+    if let Err(e) = state.open_event_stream() {
+        reply(request, 503, &error_body(&e));
+        return 503;
+    }
+    let mut out = request.into_writer();
+    scope.spawn(move || {
+        if let Err(e) = stream.write(state, out.as_mut()) {
+            tracing::debug!(error = %e, "Event stream closed");
+        }
+        state.close_event_stream();
+    });
+    return 200;
+}
+
+struct EventStream {
+    auth: Option<String>,
+    otp: Option<String>,
+    /**
+     * Names of the events to write, all events if empty
+     */
+    events: Vec<String>,
+    after_event: u32,
+    /**
+     * Field of a GraphQL subscription, whose responses are written as next events instead
+     */
+    subscription: Option<Field>,
+}
+
+impl EventStream {
+    fn new(
+        state: &ServerState,
+        request: &Request,
+    ) -> std::result::Result<EventStream, (u16, anyhow::Error)> {
+        let mut events = vec![];
+        let mut after = None;
+        for (key, value) in query_params(request.url()) {
+            match key {
+                "event" => events.push(value.to_string()),
+                "after" => match value.parse::<u32>() {
+                    Ok(id) => after = Some(id),
+                    Err(_) => return Err((400, anyhow!("after must be the id of an event"))),
+                },
+                _ => {}
+            }
+        }
+        if let Err(e) = check_event_names(&events) {
+            return Err((400, e));
+        }
+        return Ok(EventStream {
+            auth: authorization(request),
+            otp: one_time_password(request),
+            events,
+            after_event: EventStream::resume_after(state, request, after)?,
+            subscription: None,
+        });
+    }
+
+    /**
+     * The event given by the Last-Event-ID header or the parameter, otherwise the latest one
+     */
+    fn resume_after(
+        state: &ServerState,
+        request: &Request,
+        after: Option<u32>,
+    ) -> std::result::Result<u32, (u16, anyhow::Error)> {
+        let last_event_id = request
+            .headers()
+            .iter()
+            .find(|e| e.field.equiv("Last-Event-ID"))
+            .and_then(|e| e.value.as_str().parse::<u32>().ok());
+        return match last_event_id.or(after) {
+            Some(id) => Ok(id),
+            None => state
+                .pool
+                .with(|db| db.latest_event())
+                .map_err(|e| (503, e)),
+        };
+    }
+
+    /**
+     * Writes the events until the client disconnects or its credentials are no longer valid,
+     * which is checked as often as verified credentials are reused.
+     */
+    fn write(mut self, state: &ServerState, out: &mut dyn Write) -> Result<()> {
+        out.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+            Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        )?;
+        out.flush()?;
+        let mut verified_at = Instant::now();
+        loop {
+            for event in state.pool.with(|db| db.event_list(self.after_event))? {
+                self.after_event = event.id;
+                if !self.events.is_empty() && !self.events.contains(&event.event) {
+                    continue;
+                }
+                match &self.subscription {
+                    Some(field) => {
+                        let response = GraphqlResponse::field(field, graphql_event(&event));
+                        let data = serde_json::to_string(&response)?;
+                        write!(out, "id: {}\nevent: next\ndata: {}\n\n", event.id, data)?;
+                    }
+                    None => {
+                        let data = serde_json::to_string(&event)?;
+                        write!(out, "id: {}\ndata: {}\n\n", event.id, data)?;
+                    }
+                }
+            }
+            if verified_at.elapsed() >= CREDENTIALS_TTL {
+                // Also finds out whether the client is still connected
+                out.write_all(b": keep-alive\n\n")?;
+                let (auth, otp) = (self.auth.clone(), self.otp.clone());
+                if let Err((_, e)) = authorize(state, auth, otp, Some("db")) {
+                    return Err(e);
+                }
+                verified_at = Instant::now();
+            }
+            out.flush()?;
+            std::thread::sleep(Duration::from_millis(EVENTS_POLL_MILLISECONDS));
+        }
+    }
+}
+
+/**
+ * Parameters of the query of the URL, which are not decoded, as names and numbers do not need
+ * to be
+ */
+fn query_params(url: &str) -> Vec<(&str, &str)> {
+    return match url.split_once('?') {
+        Some((_, query)) => query.split('&').filter_map(|e| e.split_once('=')).collect(),
+        None => vec![],
+    };
+}
+
+/**
+ * Answers GraphQL operations on the entities of the inventory, whose schema is given on GET
+ * like by inventory schema graphql. Fields are resolved by the operations of /rpc, so that the
+ * same users are permitted to them. Subscriptions are streamed like /events, with a next event
+ * holding the response for each event.
+ */
+fn handle_graphql<'scope>(
+    mut request: Request,
+    scope: &'scope std::thread::Scope<'scope, '_>,
+    state: &'scope ServerState,
+) -> u16 {
+    if *request.method() == tiny_http::Method::Get {
+        let schema = state.pool.with(|db| {
+            return Ok(Schema::inventory(
+                &db.get_config()?.inventory_schema_declaration,
+            ));
+        });
+        let (status, body) = match schema {
+            Ok(schema) => (200, schema.sdl()),
+            Err(e) => (503, e.to_string()),
+        };
+        reply_text(request, status, "text/plain", body);
+        return status;
+    }
+    let graphql_request = read_body(&mut request)
+        .and_then(|body| Ok(serde_json::from_slice::<GraphqlRequest>(&body)?));
+    let answer = match graphql_request {
+        Ok(graphql_request) => {
+            let auth = authorization(&request);
+            let otp = one_time_password(&request);
+            let after = EventStream::resume_after(state, &request, None);
+            answer_graphql(&graphql_request, auth, otp, after, state)
+        }
+        Err(e) => GraphqlAnswer::Response(400, GraphqlResponse::error(&e)),
+    };
+    return match answer {
+        GraphqlAnswer::Response(status, response) => {
+            let body = serde_json::to_string(&response).unwrap_or_default();
+            reply_text(request, status, "application/json", body);
+            status
+        }
+        GraphqlAnswer::Subscription(stream) => start_event_stream(request, stream, scope, state),
+    };
+}
+
+enum GraphqlAnswer {
+    Response(u16, GraphqlResponse),
+    Subscription(EventStream),
+}
+
+/**
+ * Authenticates the user like for /rpc before the operation is looked at, so that only users
+ * learn about the schema.
+ *
+ * @param after Event a subscription continues after
+ */
+fn answer_graphql(
+    graphql_request: &GraphqlRequest,
+    auth: Option<String>,
+    otp: Option<String>,
+    after: std::result::Result<u32, (u16, anyhow::Error)>,
+    state: &ServerState,
+) -> GraphqlAnswer {
+    let failure = |status: u16, e: anyhow::Error| {
+        return GraphqlAnswer::Response(status, GraphqlResponse::error(&e));
+    };
+    let answer = state.with_user(auth.clone(), otp.clone(), |ctx, user| {
+        let schema = Schema::inventory(&ctx.config.inventory_schema_declaration);
+        let mut prepared = graphql::prepare(&schema, graphql_request)?;
+        if prepared.kind != OperationKind::Subscription {
+            let response = graphql::execute(&prepared, &mut |field| {
+                return resolve_graphql(field, ctx, user);
+            });
+            return Ok(GraphqlAnswer::Response(200, response));
+        }
+        // Like /events, which subscriptions are streamed as
+        if let Err(e) = require_read(user, "db") {
+            return Ok(failure(403, e));
+        }
+        let field = prepared.fields.remove(0);
+        let events: Vec<String> = field
+            .arguments
+            .get("event")
+            .and_then(Value::as_str)
+            .map(|e| vec![e.to_string()])
+            .unwrap_or_default();
+        check_event_names(&events)?;
+        return Ok(match after {
+            Ok(after_event) => GraphqlAnswer::Subscription(EventStream {
+                auth,
+                otp,
+                events,
+                after_event,
+                subscription: Some(field),
+            }),
+            Err((status, e)) => failure(status, e),
+        });
+    });
+    return answer.unwrap_or_else(|(status, e)| failure(status, e));
+}
+
+/**
+ * Resolves a field of a query or mutation by the operations of /rpc. Entities are given with
+ * the GraphQL names of their columns.
+ */
+fn resolve_graphql(field: &Field, ctx: &mut CommandContext, user: &DBUser) -> Result<Value> {
+    let declarations = ctx.config.inventory_schema_declaration.clone();
+    let id = field
+        .arguments
+        .get("id")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let values = match field.arguments.get("values") {
+        Some(Value::Object(values)) => values.clone(),
+        _ => Map::new(),
+    };
+    return match field.name.as_str() {
+        "entity" => graphql_entity(ctx, user, &id),
+        "entities" => {
+            let filter = match field.arguments.get("filter") {
+                Some(Value::Object(values)) => graphql_filter(&declarations, values),
+                _ => None,
+            };
+            let props = InventoryListProps {
+                limit: field
+                    .arguments
+                    .get("limit")
+                    .and_then(Value::as_i64)
+                    .map_or(-1, |e| e as i32),
+                after_id: field.arguments.get("after_id").and_then(Value::as_i64),
+                fuzzy: None,
+                fuzzy_threshold: 0.0,
+                with_audit: false,
+                with_attachments: false,
+                include_archived: false,
+                supplier: None,
+                assigned_to: None,
+                category: None,
+                recursive: false,
+                raw: &None,
+                params: &vec![],
+                changed_since: None,
+                filter,
+                group: None,
+                expand: vec![],
+            };
+            let mut result = call("inventory_list", json!([props]), ctx, user.clone())?;
+            let entities: Vec<KeyValueCollection> = decode(result["result"].take())?;
+            Ok(Value::Array(entities.iter().map(graphql_values).collect()))
+        }
+        "add" => {
+            let params = graphql_params(&declarations, &values)
+                .into_iter()
+                .filter_map(|(name, value)| Some(format!("{}={}", name, value?)))
+                .collect::<Vec<String>>();
+            let id = add_entity(ctx, user, &params)?;
+            graphql_entity(ctx, user, &id)
+        }
+        "edit" => {
+            let params = graphql_params(&declarations, &values);
+            plugins::validate_entity(
+                &params
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value.as_deref().unwrap_or("")))
+                    .collect::<Vec<String>>(),
+            )?;
+            let entries: KeyValueCollection = params
+                .into_iter()
+                .map(|(name, value)| match value {
+                    Some(value) => {
+                        format!("{}={}", name, value).to_typed_key_value_entry(&declarations)
+                    }
+                    None => {
+                        let column_type = declarations
+                            .collection
+                            .iter()
+                            .find(|e| e.name == name)
+                            .map_or(ColumnType::TEXT, |e| e.column_type);
+                        Ok(KeyValueTypeEntry::new(name, None, column_type))
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?
+                .into();
+            call(
+                "inventory_edit",
+                json!([id, entries, Value::Null]),
+                ctx,
+                user.clone(),
+            )?;
+            graphql_entity(ctx, user, &id)
+        }
+        "remove" => {
+            let reason = field.arguments.get("reason").cloned();
+            call("inventory_remove", json!([id, reason]), ctx, user.clone())?;
+            Ok(Value::Bool(true))
+        }
+        _ => Err(UnknownOperation(field.name.clone()).into()),
+    };
+}
+
+/**
+ * Adds the entity of the values in name=value notation like inventory add, which is what the
+ * plugins and the defaults of the context are applied by on the side of the client.
+ *
+ * @returns The id of the added entity
+ */
+pub(crate) fn add_entity(
+    ctx: &mut CommandContext,
+    user: &DBUser,
+    params: &[String],
+) -> Result<String> {
+    let declarations = ctx.config.inventory_schema_declaration.clone();
+    let params = plugins::transform_on_add(params)?;
+    plugins::validate_entity(&params)?;
+    let entries: KeyValueCollection = params
+        .iter()
+        .map(|e| e.to_typed_key_value_entry(&declarations))
+        .collect::<Result<Vec<_>>>()?
+        .into();
+    let entries = with_context_defaults(entries, &declarations, user)?;
+    // Nobody else can add entities before the transaction ends, so that the latest event is the
+    // one of the addition
+    ctx.db.batch_begin()?;
+    let added = call("inventory_add", json!([entries]), ctx, user.clone())
+        .and_then(|_| ctx.db.event_list(ctx.db.latest_event()?.saturating_sub(1)))
+        .and_then(|events| {
+            return events
+                .iter()
+                .rev()
+                .find(|e| e.event == "inventory-add")
+                .and_then(|e| e.entity.as_ref()?.get("id").map(|e| e.to_string()))
+                .ok_or_else(|| anyhow!("Added entity could not be found"));
+        });
+    return match added {
+        Ok(id) => {
+            ctx.db.batch_commit()?;
+            Ok(id)
+        }
+        Err(e) => {
+            ctx.db.batch_rollback()?;
+            Err(e)
+        }
+    };
+}
+
+fn graphql_entity(ctx: &mut CommandContext, user: &DBUser, id: &str) -> Result<Value> {
+    let mut result = call("inventory_get", json!([id]), ctx, user.clone())?;
+    let entity: Option<KeyValueCollection> = decode(result["result"].take())?;
+    return Ok(entity.as_ref().map_or(Value::Null, graphql_values));
+}
+
+/**
+ * The values of the entity by the GraphQL names of the columns, as text that the fields are
+ * completed from by their types
+ */
+fn graphql_values(entity: &KeyValueCollection) -> Value {
+    return Value::Object(
+        entity
+            .collection
+            .iter()
+            .map(|e| {
+                let value = e.to_plain_value().map_or(Value::Null, Value::String);
+                (e.key.replace('-', "_"), value)
+            })
+            .collect(),
+    );
+}
+
+/**
+ * The values of an EntityInput by the names of their columns, as text or None for null
+ */
+fn graphql_params(
+    declarations: &SchemaCollection,
+    values: &Map<String, Value>,
+) -> Vec<(String, Option<String>)> {
+    return declarations
+        .collection
+        .iter()
+        .filter_map(|decl| {
+            let value = match values.get(&decl.to_graphql().0)? {
+                Value::Null => None,
+                Value::String(value) => Some(value.clone()),
+                value => Some(value.to_string()),
+            };
+            Some((decl.name.clone(), value))
+        })
+        .collect();
+}
+
+/**
+ * Entities having the values of an EntityInput, all of them if it is empty
+ */
+fn graphql_filter(
+    declarations: &SchemaCollection,
+    values: &Map<String, Value>,
+) -> Option<WhereExpr> {
+    let terms: Vec<WhereExpr> = graphql_params(declarations, values)
+        .into_iter()
+        .map(|(column, value)| match value {
+            Some(value) => WhereExpr::Compare {
+                column,
+                operator: CompareOp::Eq,
+                value,
+            },
+            None => WhereExpr::IsNull {
+                column,
+                negated: false,
+            },
+        })
+        .collect();
+    return match terms.is_empty() {
+        true => None,
+        false => Some(WhereExpr::And(terms)),
+    };
+}
+
+/**
+ * The event with the entity by the GraphQL names of its columns
+ */
+fn graphql_event(event: &DBEvent) -> Result<Value> {
+    let mut value = serde_json::to_value(event)?;
+    if let Some(Value::Object(entity)) = value.get_mut("entity") {
+        *entity = std::mem::take(entity)
+            .into_iter()
+            .map(|(key, value)| (key.replace('-', "_"), value))
+            .collect();
+    }
+    return Ok(value);
+}
+
+/**
+ * Issues a token (JWT) to the user logging in with username and password, valid for the ttl
+ * of the parameters (Default: 30d), or replaces the token given as bearer by a new one, like
+ * user token issue and refresh do. Both answer with {"token": "..."}.
+ */
+fn handle_token(mut request: Request, operation: &str, state: &ServerState) -> u16 {
+    let params = read_body(&mut request).and_then(|body| {
+        return match body.is_empty() {
+            true => Ok(TokenParams::default()),
+            false => Ok(serde_json::from_slice::<TokenParams>(&body)?),
+        };
+    });
+    let (auth, otp) = (authorization(&request), one_time_password(&request));
+    let token = match params {
+        Ok(params) => state.with_user(auth, otp, |ctx, user| {
+            return match operation {
+                "token_refresh" => UserTokenRefreshArgs.refresh_for(ctx, user),
+                _ => UserTokenIssueArgs {
+                    ttl: params.ttl.unwrap_or_else(|| DEFAULT_TOKEN_TTL.to_string()),
+                    scopes: vec![],
+                }
+                .issue_for(ctx, user),
+            };
+        }),
+        Err(e) => Err((400, e)),
+    };
+    let (status, body) = match token {
+        Ok(token) => (200, json!({ "token": token })),
+        Err((status, e)) => (status, error_body(&e)),
+    };
+    reply(request, status, &body);
+    return status;
+}
+
+#[derive(Default, Deserialize)]
+struct TokenParams {
+    /**
+     * How long the token is valid, e.g. 2w
+     */
+    ttl: Option<String>,
+}
+
+/**
+ * Answers with the report of the health command, with 503 if a check failed, so that probes
+ * need no authentication and only look at the status.
+ */
+fn handle_health(request: Request, state: &ServerState) -> u16 {
+    let (status, body) = match state.pool.with(|db| Ok(db.health())) {
+        Ok(report) => match serde_json::to_value(&report) {
+            Ok(body) if report.healthy => (200, body),
+            Ok(body) => (503, body),
+            Err(e) => (500, error_body(&e.into())),
+        },
+        Err(e) => (503, error_body(&e)),
+    };
+    reply(request, status, &body);
+    return status;
+}
+
+/**
+ * @returns The status of the response and whether the operation answered it. Operations
+ * failing with 400 did, as only they check their parameters.
+ */
+fn handle_rpc(mut request: Request, method: &str, state: &ServerState) -> (u16, bool) {
+    let params = read_params(&mut request);
+    let parsed = params.is_ok();
+    let (status, body) = match params {
+        Ok(args) => {
+            let auth = authorization(&request);
+            let otp = one_time_password(&request);
+            match state.pool.with(|db| {
+                Ok(respond_to(
+                    db,
+                    method,
+                    args,
+                    auth,
+                    otp,
+                    &state.credentials,
+                    state.read_only,
+                ))
+            }) {
+                Ok(response) => response,
+                Err(e) => (503, error_body(&e)),
+            }
+        }
+        Err(e) => (400, error_body(&e)),
+    };
+    reply(request, status, &body);
+    return (status, parsed && (status == 200 || status == 400));
+}
+
+fn read_params(request: &mut Request) -> Result<Value> {
+    return match serde_json::from_slice(&read_body(request)?)? {
+        Value::Array(args) => Ok(Value::Array(args)),
+        _ => bail!("Parameters must be given as JSON array"),
+    };
+}
+
+fn read_body(request: &mut Request) -> Result<Vec<u8>> {
+    if *request.method() != tiny_http::Method::Post {
+        bail!("Operations must be requested by POST");
+    }
+    let mut body = vec![];
+    request
+        .as_reader()
+        .take(MAX_REQUEST_BYTES + 1)
+        .read_to_end(&mut body)?;
+    if body.len() as u64 > MAX_REQUEST_BYTES {
+        bail!("Request is larger than {} bytes", MAX_REQUEST_BYTES);
+    }
+    return Ok(body);
+}
+
+/**
+ * Credentials of the request in the notation of --auth, taken from basic or bearer
+ * authentication.
+ */
+fn authorization(request: &Request) -> Option<String> {
+    let value = request
+        .headers()
+        .iter()
+        .find(|e| e.field.equiv("Authorization"))?
+        .value
+        .as_str();
+    return credentials_of(value);
+}
+
+/**
+ * Credentials of the value of an Authorization header in the notation of --auth
+ */
+pub(crate) fn credentials_of(value: &str) -> Option<String> {
+    return match value.split_once(' ') {
+        Some(("Basic", encoded)) => STANDARD
+            .decode(encoded)
+            .ok()
+            .and_then(|e| String::from_utf8(e).ok()),
+        Some(("Bearer", token)) => Some(token.to_string()),
+        _ => None,
+    };
+}
+
+fn one_time_password(request: &Request) -> Option<String> {
+    return request
+        .headers()
+        .iter()
+        .find(|e| e.field.equiv(OTP_HEADER))
+        .map(|e| e.value.to_string());
+}
+
+/**
+ * Credentials are cached together with the one-time password, so that knowing the password
+ * alone never suffices for users with two-factor authentication.
+ */
+fn credentials_key(auth: &str, otp: Option<&str>) -> String {
+    return format!("{}\n{}", auth, otp.unwrap_or(""));
+}
+
+fn reply(request: Request, status: u16, body: &Value) {
+    reply_text(request, status, "application/json", body.to_string());
+}
+
+fn reply_text(request: Request, status: u16, content_type: &str, body: String) {
+    let mut response = Response::from_string(body).with_status_code(status);
+    if let Ok(header) = Header::from_bytes("Content-Type", content_type) {
+        response.add_header(header);
+    }
+    if let Err(e) = request.respond(response) {
+        tracing::warn!(error = %e, "Could not send response");
+    }
+}
+
+fn error_body(e: &anyhow::Error) -> Value {
+    return json!({ "error": e.to_string() });
+}
+
+fn respond_to(
+    db: &mut dyn InvManDBPool,
+    method: &str,
+    args: Value,
+    auth: Option<String>,
+    otp: Option<String>,
+    credentials: &CredentialCache,
+    read_only: bool,
+) -> (u16, Value) {
+    let mut config = match db.get_config() {
+        Ok(config) => config,
+        Err(e) => return (500, error_body(&e)),
+    };
+    // As for the commands, only the one lifting it ignores the read-only option of the config
+    let read_only = read_only || (config.read_only && method != "read_only_set");
+    let mut ctx = CommandContext {
+        db,
+        config: &mut config,
+        auth,
+        otp,
+        output: OutputType::Json,
+        read_only,
+        session: None,
+    };
+    if PUBLIC_METHODS.contains(&method) {
+        return match call_public(method, args, &mut ctx, credentials) {
+            Ok(body) => (200, body),
+            Err(e) => (400, error_body(&e)),
+        };
+    }
+    // Verifies the credentials of a client that authenticates, which wraps the failure itself
+    if method == "user_auth" {
+        let (username, password, otp) = match decode::<(String, String, Option<String>)>(args) {
+            Ok(credentials) => credentials,
+            Err(e) => return (400, error_body(&e)),
+        };
+        let key = credentials_key(&format!("{}:{}", username, password), otp.as_deref());
+        if let Some(mut user) = credentials.get(&key) {
+            user.read_only = ctx.read_only;
+            return (200, json!({ "result": user }));
+        }
+        let mut user = DBUser::default();
+        return match ctx
+            .db
+            .user_auth(&username, &password, otp.as_deref(), &mut user)
+        {
+            Ok(_) => {
+                user.read_only = ctx.read_only;
+                credentials.insert(&key, &user);
+                (200, json!({ "result": user }))
+            }
+            Err(e) => (401, error_body(&e)),
+        };
+    }
+    let user = match authenticate(&ctx, credentials) {
+        Ok(user) => user,
+        Err(e) => return (401, error_body(&e)),
+    };
+    // Changing the password is what a user whose password expired has to do
+    if user.password_expired && method != "user_edit" {
+        let e = anyhow!("User authentication failure ({})", PASSWORD_EXPIRED);
+        return (401, error_body(&e));
+    }
+    return match call(method, args, &mut ctx, user) {
+        Ok(body) => (200, body),
+        Err(e) if e.is::<UnknownOperation>() => (404, error_body(&e)),
+        Err(e) => (400, error_body(&e)),
+    };
+}
+
+/**
+ * Authenticates the user of the request like a command does, unless the credentials were
+ * verified shortly before.
+ */
+fn authenticate(ctx: &CommandContext, credentials: &CredentialCache) -> Result<DBUser> {
+    // Tokens are cheap to verify, so they are verified on every request, which makes revoking
+    // one take effect immediately
+    let key = match &ctx.auth {
+        Some(auth) if auth.contains(':') => credentials_key(auth, ctx.otp.as_deref()),
+        _ => return check_scopes(ctx.authenticate_expired()?),
+    };
+    let user = match credentials.get(&key) {
+        Some(mut user) => {
+            user.read_only = ctx.read_only;
+            ctx.db.scope_to_org(user.org_id);
+            user
+        }
+        None => {
+            let user = ctx.authenticate_expired()?;
+            credentials.insert(&key, &user);
+            user
+        }
+    };
+    return check_scopes(user);
+}
+
+fn check_scopes(user: DBUser) -> Result<DBUser> {
+    // Scopes restrict commands, but the server only sees the operations they are made of
+    if !user.scopes.is_empty() {
+        bail!("Tokens with scopes can only be used by the command line, not by a server");
+    }
+    return Ok(user);
+}
+
+fn call_public(
+    method: &str,
+    args: Value,
+    ctx: &mut CommandContext,
+    credentials: &CredentialCache,
+) -> Result<Value> {
+    return match method {
+        "get_config" => {
+            // Clients that could not authenticate yet get the config without secrets
+            let user = match ctx.auth {
+                Some(_) => authenticate(ctx, credentials).ok(),
+                None => None,
+            };
+            Ok(json!({ "result": visible_config(ctx.config, user.as_ref()) }))
+        }
+        "user_register" => {
+            let (username, password): (String, String) = decode(args)?;
+            ctx.check_writable()?;
+            // Admins still add users while others cannot register themselves
+            let is_admin = || {
+                ctx.auth.is_some()
+                    && authenticate(ctx, credentials).is_ok_and(|e| e.can_write_table("users"))
+            };
+            if !ctx.config.allow_registration && !is_admin() {
+                bail!("User registration failed (Registration is disabled by inventory administrator)");
+            }
+            if let Err(e) = ctx.config.password_policy.check(&password) {
+                bail!("User registration failed ({})", e);
+            }
+            respond(ctx.db.user_register(&username, &password)?)
+        }
+        "health" => respond(ctx.db.health()),
+        "latest_event" => respond(ctx.db.latest_event()?),
+        // Loading requires an empty database, which has no users to authenticate yet
+        "load" => {
+            let (dump,): (DBDump,) = decode(args)?;
+            ctx.check_writable()?;
+            respond(ctx.db.load(&dump)?)
+        }
+        // Like dumps, fixtures are only loaded into an empty database
+        "fixture_load" => {
+            let (schema, users, entities): (
+                Vec<SchemaDeclaration>,
+                Vec<DBFixtureUser>,
+                Vec<KeyValueCollection>,
+            ) = decode(args)?;
+            ctx.check_writable()?;
+            let result = ctx
+                .db
+                .fixture_load(&schema, &users, &entities, ctx.config)?;
+            Ok(json!({ "result": result, "config": visible_config(ctx.config, None) }))
+        }
+        _ => bail!("Unknown operation {}", method),
+    };
+}
+
+pub(crate) fn call(
+    method: &str,
+    args: Value,
+    ctx: &mut CommandContext,
+    user: DBUser,
+) -> Result<Value> {
+    match operation_access(method) {
+        Some(access) => access.check(ctx, &user)?,
+        None => return Err(UnknownOperation(method.into()).into()),
+    }
+    return match method {
+        "token_add" => {
+            let (expires_at, scopes): (i64, Vec<String>) = decode(args)?;
+            respond(ctx.db.token_add(expires_at, &scopes, &user)?)
+        }
+        "token_list" => respond(ctx.db.token_list(&user)?),
+        // The user of the token the request was authenticated by
+        "token_auth" => respond(user),
+        "token_revoke" => {
+            let (token_id,): (String,) = decode(args)?;
+            respond(ctx.db.token_revoke(&token_id, &user)?)
+        }
+        "user_id" => {
+            let (username,): (String,) = decode(args)?;
+            respond(ctx.db.user_id(&username)?)
+        }
+        "user_totp_enable" => {
+            let (secret, recovery_codes): (String, Vec<String>) = decode(args)?;
+            respond(ctx.db.user_totp_enable(&secret, &recovery_codes, &user)?)
+        }
+        "user_totp_disable" => {
+            let (username,): (Option<String>,) = decode(args)?;
+            if username.is_some() {
+                require_write(&user, "users")?;
+            }
+            respond(ctx.db.user_totp_disable(username.as_deref(), &user)?)
+        }
+        "user_edit" => {
+            let (username, password, must_change_password): (Option<String>, Option<String>, bool) =
+                decode(args)?;
+            if user.password_expired && (password.is_none() || username.is_some()) {
+                bail!(PASSWORD_EXPIRED);
+            }
+            if username.is_some() || must_change_password {
+                require_write(&user, "users")?;
+            }
+            respond(ctx.db.user_edit(
+                username.as_deref(),
+                password.as_deref(),
+                must_change_password,
+                ctx.config,
+                &user,
+            )?)
+        }
+        "user_purge" => {
+            let (username,): (String,) = decode(args)?;
+            respond(ctx.db.user_purge(&username, ctx.config, &user)?)
+        }
+        "org_create" => {
+            let (name,): (String,) = decode(args)?;
+            respond(ctx.db.org_create(&name, &user)?)
+        }
+        "org_invite" => {
+            let (org, username): (String, String) = decode(args)?;
+            respond(ctx.db.org_invite(&org, &username, &user)?)
+        }
+        "org_list" => respond(ctx.db.org_list()?),
+        "role_list" => respond(ctx.db.role_list()?),
+        "role_set_ownership" => {
+            let (role, ownership): (String, OwnershipPolicy) = decode(args)?;
+            respond(ctx.db.role_set_ownership(&role, ownership, &user)?)
+        }
+        "role_deny_column" => {
+            let (role, column, mode, deny): (String, String, PermissionMode, bool) = decode(args)?;
+            respond(ctx.db.role_deny_column(&role, &column, mode, deny, &user)?)
+        }
+        "inventory_owner" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.inventory_owner(&identifier)?)
+        }
+        "schema_alter" => {
+            let (decl,): (SchemaDeclaration,) = decode(args)?;
+            require_declaration(&decl)?;
+            let result = ctx.db.schema_alter(ctx.config, decl, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "schema_remove" => {
+            let (name,): (String,) = decode(args)?;
+            let result = ctx.db.schema_remove(ctx.config, &name, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "schema_extend" => {
+            let (decls,): (Vec<SchemaDeclaration>,) = decode(args)?;
+            for decl in decls.iter() {
+                require_declaration(decl)?;
+            }
+            let result = ctx.db.schema_extend(ctx.config, decls, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "inventory_add" => {
+            let (params,): (KeyValueCollection,) = decode(args)?;
+            require_write_collection(&user, ctx.config, &params)?;
+            respond(ctx.db.inventory_add(&params, ctx.config, &user)?)
+        }
+        "inventory_add_with_meta" => {
+            let (params, meta): (KeyValueCollection, BTreeMap<String, String>) = decode(args)?;
+            require_write_collection(&user, ctx.config, &params)?;
+            for key in meta.keys() {
+                check_meta_key(key)?;
+            }
+            respond(
+                ctx.db
+                    .inventory_add_with_meta(&params, &meta, ctx.config, &user)?,
+            )
+        }
+        "inventory_list" => {
+            let (props,): (ListPropsWire,) = decode(args)?;
+            check_list(&user, ctx.config, &props)?;
+            let mut data = ctx.db.inventory_list(&props.props(), ctx.config)?;
+            for entry in data.iter_mut() {
+                user.mask_collection("inventory", entry);
+            }
+            respond(data)
+        }
+        "inventory_list_explain" => {
+            let (props,): (ListPropsWire,) = decode(args)?;
+            check_list(&user, ctx.config, &props)?;
+            if user.org_id.is_some() {
+                bail!("Cannot use --explain as member of an organization");
+            }
+            respond(ctx.db.inventory_list_explain(&props.props(), ctx.config)?)
+        }
+        "inventory_archive" => {
+            let (filter,): (WhereExpr,) = decode(args)?;
+            require_read_filter(&user, ctx.config, &filter)?;
+            respond(ctx.db.inventory_archive(&filter, ctx.config, &user)?)
+        }
+        "inventory_count" => {
+            let (conditions,): (KeyValueCollection,) = decode(args)?;
+            require_read_collection(&user, ctx.config, &conditions)?;
+            respond(ctx.db.inventory_count(&conditions)?)
+        }
+        "inventory_exists" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.inventory_exists(&identifier)?)
+        }
+        "inventory_get" => {
+            let (identifier,): (String,) = decode(args)?;
+            let mut entity = ctx.db.inventory_get(&identifier, ctx.config)?;
+            if let Some(entity) = entity.as_mut() {
+                user.mask_collection("inventory", entity);
+            }
+            respond(entity)
+        }
+        "inventory_distinct" => {
+            let (column, conditions): (String, KeyValueCollection) = decode(args)?;
+            require_read_collection(&user, ctx.config, &conditions)?;
+            require_column(ctx.config, &column)?;
+            if user.is_read_denied("inventory", &column) {
+                bail!("Cannot read column {} of inventory", column);
+            }
+            let mut data = ctx
+                .db
+                .inventory_distinct(&column, &conditions, ctx.config)?;
+            for entry in data.iter_mut() {
+                user.mask_collection("inventory", entry);
+            }
+            respond(data)
+        }
+        "inventory_edit" => {
+            let (identifier, params, expect_version): (String, KeyValueCollection, Option<String>) =
+                decode(args)?;
+            ctx.check_owner(&user, &identifier)?;
+            require_write_collection(&user, ctx.config, &params)?;
+            respond(ctx.db.inventory_edit(
+                &identifier,
+                &params,
+                expect_version.as_deref(),
+                ctx.config,
+                &user,
+            )?)
+        }
+        "inventory_remove" => {
+            let (identifier, reason): (String, Option<String>) = decode(args)?;
+            ctx.check_owner(&user, &identifier)?;
+            respond(
+                ctx.db
+                    .inventory_remove(&identifier, reason.as_deref(), ctx.config, &user)?,
+            )
+        }
+        "inventory_merge" => {
+            let (keep, duplicate): (String, String) = decode(args)?;
+            ctx.check_owner(&user, &keep)?;
+            ctx.check_owner(&user, &duplicate)?;
+            respond(
+                ctx.db
+                    .inventory_merge(&keep, &duplicate, ctx.config, &user)?,
+            )
+        }
+        "inventory_checkout" => {
+            let (identifier, props): (String, LoanPropsWire) = decode(args)?;
+            respond(
+                ctx.db
+                    .inventory_checkout(&identifier, &props.props(), &user)?,
+            )
+        }
+        "inventory_checkin" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.inventory_checkin(&identifier, &user)?)
+        }
+        "loan_list" => {
+            let (overdue_only,): (bool,) = decode(args)?;
+            respond(ctx.db.loan_list(overdue_only)?)
+        }
+        "inventory_assign" => {
+            let (identifier, assignee): (String, u32) = decode(args)?;
+            respond(ctx.db.inventory_assign(&identifier, assignee, &user)?)
+        }
+        "inventory_unassign" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.inventory_unassign(&identifier, &user)?)
+        }
+        "purchase_set" => {
+            let (identifier, props): (String, PurchaseProps) = decode(args)?;
+            ctx.check_owner(&user, &identifier)?;
+            respond(ctx.db.purchase_set(&identifier, &props, &user)?)
+        }
+        "purchase_list" => respond(ctx.db.purchase_list()?),
+        "stock_adjust" => {
+            let (identifier, props): (String, AdjustPropsWire) = decode(args)?;
+            respond(
+                ctx.db
+                    .stock_adjust(&identifier, &props.props(), ctx.config, &user)?,
+            )
+        }
+        "stock_transfer" => {
+            let (identifier, props): (String, TransferPropsWire) = decode(args)?;
+            respond(
+                ctx.db
+                    .stock_transfer(&identifier, &props.props(), ctx.config, &user)?,
+            )
+        }
+        "stock_list" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.stock_list(&identifier)?)
+        }
+        "stock_movements" => {
+            let (identifier, location): (String, Option<String>) = decode(args)?;
+            respond(ctx.db.stock_movements(&identifier, location.as_deref())?)
+        }
+        "stock_consumption" => {
+            let (since,): (String,) = decode(args)?;
+            respond(ctx.db.stock_consumption(&since)?)
+        }
+        "reason_summary" => {
+            let (since,): (Option<String>,) = decode(args)?;
+            respond(ctx.db.reason_summary(since.as_deref())?)
+        }
+        "stock_ledger" => respond(ctx.db.stock_ledger()?),
+        "serial_history" => {
+            let (serial,): (String,) = decode(args)?;
+            respond(ctx.db.serial_history(&serial)?)
+        }
+        "bom_define" => {
+            let (assembly, components): (String, Vec<BomComponentProps>) = decode(args)?;
+            respond(ctx.db.bom_define(&assembly, &components, &user)?)
+        }
+        "bom_list" => {
+            let (assembly,): (String,) = decode(args)?;
+            respond(ctx.db.bom_list(&assembly)?)
+        }
+        "bom_build" => {
+            let (assembly, props): (String, BuildPropsWire) = decode(args)?;
+            respond(
+                ctx.db
+                    .bom_build(&assembly, &props.props(), ctx.config, &user)?,
+            )
+        }
+        "maintenance_schedule" => {
+            let (identifier, task, interval_days): (String, String, u32) = decode(args)?;
+            respond(
+                ctx.db
+                    .maintenance_schedule(&identifier, &task, interval_days, &user)?,
+            )
+        }
+        "maintenance_done" => {
+            let (identifier, task): (String, String) = decode(args)?;
+            respond(ctx.db.maintenance_done(&identifier, &task, &user)?)
+        }
+        "maintenance_due" => {
+            let (within_days,): (u32,) = decode(args)?;
+            respond(ctx.db.maintenance_due(within_days)?)
+        }
+        "attachment_add" => {
+            let (identifier, filename, content): (String, String, Vec<u8>) = decode(args)?;
+            ctx.check_owner(&user, &identifier)?;
+            respond(
+                ctx.db
+                    .attachment_add(&identifier, &filename, &content, &user)?,
+            )
+        }
+        "attachment_list" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.attachment_list(&identifier)?)
+        }
+        "attachment_content" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.attachment_content(&identifier)?)
+        }
+        "attachment_remove" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.attachment_remove(&identifier, &user)?)
+        }
+        "comment_add" => {
+            let (identifier, body): (String, String) = decode(args)?;
+            respond(ctx.db.comment_add(&identifier, &body, &user)?)
+        }
+        "meta_set" => {
+            let (identifier, key, value): (String, String, String) = decode(args)?;
+            check_meta_key(&key)?;
+            ctx.check_owner(&user, &identifier)?;
+            respond(ctx.db.meta_set(&identifier, &key, &value, &user)?)
+        }
+        "meta_remove" => {
+            let (identifier, key): (String, String) = decode(args)?;
+            ctx.check_owner(&user, &identifier)?;
+            respond(ctx.db.meta_remove(&identifier, &key, &user)?)
+        }
+        "meta_get" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.meta_get(&identifier)?)
+        }
+        "comment_list" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.comment_list(&identifier)?)
+        }
+        "image_set" => {
+            let (identifier, filename, content): (String, String, Vec<u8>) = decode(args)?;
+            ctx.check_owner(&user, &identifier)?;
+            respond(ctx.db.image_set(&identifier, &filename, &content, &user)?)
+        }
+        "image_get" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.image_get(&identifier)?)
+        }
+        "image_thumbnail" => {
+            let (identifier, size): (String, String) = decode(args)?;
+            respond(ctx.db.image_thumbnail(&identifier, &size)?)
+        }
+        "image_thumbnail_store" => {
+            let (identifier, size, content): (String, String, Vec<u8>) = decode(args)?;
+            respond(ctx.db.image_thumbnail_store(&identifier, &size, &content)?)
+        }
+        "image_remove" => {
+            let (identifier,): (String,) = decode(args)?;
+            ctx.check_owner(&user, &identifier)?;
+            respond(ctx.db.image_remove(&identifier, &user)?)
+        }
+        "read_only_set" => {
+            let (read_only,): (bool,) = decode(args)?;
+            let result = ctx.db.read_only_set(ctx.config, read_only, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "timezone_set" => {
+            let (timezone,): (String,) = decode(args)?;
+            let result = ctx.db.timezone_set(ctx.config, &timezone, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "display_format_set" => {
+            let (format,): (DisplayFormat,) = decode(args)?;
+            let result = ctx.db.display_format_set(ctx.config, format, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "label_printer_set" => {
+            let (printer,): (Option<String>,) = decode(args)?;
+            let result = ctx.db.label_printer_set(ctx.config, printer, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "exchange_rates_set" => {
+            let (rates,): (ExchangeRates,) = decode(args)?;
+            let result = ctx.db.exchange_rates_set(ctx.config, rates, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "reason_codes_set" => {
+            let (codes,): (Vec<String>,) = decode(args)?;
+            let result = ctx.db.reason_codes_set(ctx.config, codes, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "registration_set" => {
+            let (allow,): (bool,) = decode(args)?;
+            let result = ctx.db.registration_set(ctx.config, allow, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "password_policy_set" => {
+            let (policy,): (PasswordPolicy,) = decode(args)?;
+            let result = ctx.db.password_policy_set(ctx.config, policy, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "ldap_set" => {
+            let (settings,): (Option<LdapSettings>,) = decode(args)?;
+            let result = ctx.db.ldap_set(ctx.config, settings, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "retention_set" => {
+            let (rules,): (RetentionRules,) = decode(args)?;
+            let result = ctx.db.retention_set(ctx.config, rules, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "retention_apply" => {
+            let (rules, dry_run): (RetentionRules, bool) = decode(args)?;
+            if !dry_run {
+                ctx.check_writable()?;
+            }
+            respond(ctx.db.retention_apply(&rules, dry_run, &user)?)
+        }
+        "dump" => respond(ctx.db.dump()?),
+        "fixture_users" => respond(ctx.db.fixture_users()?),
+        "smtp_set" => {
+            let (settings,): (Option<SmtpSettings>,) = decode(args)?;
+            let result = ctx.db.smtp_set(ctx.config, settings, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "scheduler_set" => {
+            let (jobs,): (Vec<ScheduledJob>,) = decode(args)?;
+            let result = ctx.db.scheduler_set(ctx.config, jobs, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "hooks_set" => {
+            let (hooks,): (Vec<Hook>,) = decode(args)?;
+            let result = ctx.db.hooks_set(ctx.config, hooks, &user)?;
+            respond_config(result, ctx.config, &user)
+        }
+        "rule_add" => {
+            let (rule,): (RuleProps,) = decode(args)?;
+            respond(ctx.db.rule_add(&rule, &user)?)
+        }
+        "rule_list" => respond(ctx.db.rule_list()?),
+        "rule_remove" => {
+            let (name,): (String,) = decode(args)?;
+            respond(ctx.db.rule_remove(&name, &user)?)
+        }
+        "rule_matches" => {
+            let (after_event,): (u32,) = decode(args)?;
+            respond(ctx.db.rule_matches(after_event)?)
+        }
+        "event_list" => {
+            let (after_event,): (u32,) = decode(args)?;
+            respond(ctx.db.event_list(after_event)?)
+        }
+        "notify_rule_add" => {
+            let (rule,): (NotifyRuleProps,) = decode(args)?;
+            respond(ctx.db.notify_rule_add(&rule, &user)?)
+        }
+        "notify_rule_list" => respond(ctx.db.notify_rule_list()?),
+        "notify_rule_remove" => {
+            let (id,): (u32,) = decode(args)?;
+            respond(ctx.db.notify_rule_remove(id, &user)?)
+        }
+        "notify_pending" => respond(ctx.db.notify_pending()?),
+        "notify_advance" => {
+            let (event_id,): (u32,) = decode(args)?;
+            respond(ctx.db.notify_advance(event_id)?)
+        }
+        "doctor" => {
+            let (fix,): (bool,) = decode(args)?;
+            if fix {
+                ctx.check_writable()?;
+                require_write(&user, "db")?;
+            }
+            respond(ctx.db.doctor(ctx.config, fix)?)
+        }
+        "optimize" => respond(ctx.db.optimize(&user)?),
+        "metrics" => respond(ctx.db.metrics()?),
+        "history_compact" => {
+            let (keep_last, older_than_days): (u32, u32) = decode(args)?;
+            respond(ctx.db.history_compact(keep_last, older_than_days, &user)?)
+        }
+        "supplier_add" => {
+            let (props,): (SupplierPropsWire,) = decode(args)?;
+            respond(ctx.db.supplier_add(&props.props(), &user)?)
+        }
+        "supplier_list" => respond(ctx.db.supplier_list()?),
+        "search" => {
+            let (text, props): (String, SearchProps) = decode(args)?;
+            let props = readable_search(&user, ctx.config, props);
+            respond(ctx.db.search(&text, &props)?)
+        }
+        "supplier_edit" => {
+            let (identifier, props): (String, SupplierPropsWire) = decode(args)?;
+            respond(ctx.db.supplier_edit(&identifier, &props.props(), &user)?)
+        }
+        "supplier_remove" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.supplier_remove(&identifier, &user)?)
+        }
+        "supplier_link" => {
+            let (identifier, entity): (String, String) = decode(args)?;
+            respond(ctx.db.supplier_link(&identifier, &entity, &user)?)
+        }
+        "supplier_unlink" => {
+            let (identifier, entity): (String, String) = decode(args)?;
+            respond(ctx.db.supplier_unlink(&identifier, &entity, &user)?)
+        }
+        "category_create" => {
+            let (path,): (String,) = decode(args)?;
+            respond(ctx.db.category_create(&path, &user)?)
+        }
+        "category_list" => respond(ctx.db.category_list()?),
+        "category_id" => {
+            let (path,): (String,) = decode(args)?;
+            respond(ctx.db.category_id(&path)?)
+        }
+        "category_remove" => {
+            let (path,): (String,) = decode(args)?;
+            respond(ctx.db.category_remove(&path, &user)?)
+        }
+        "category_assign" => {
+            let (identifier, path): (String, String) = decode(args)?;
+            respond(ctx.db.category_assign(&identifier, &path, &user)?)
+        }
+        "category_unassign" => {
+            let (identifier,): (String,) = decode(args)?;
+            respond(ctx.db.category_unassign(&identifier, &user)?)
+        }
+        "kit_define" => {
+            let (name, items): (String, Vec<KitItemProps>) = decode(args)?;
+            respond(ctx.db.kit_define(&name, &items, &user)?)
+        }
+        "kit_list" => respond(ctx.db.kit_list()?),
+        "kit_get" => {
+            let (name,): (String,) = decode(args)?;
+            respond(ctx.db.kit_get(&name)?)
+        }
+        "kit_remove" => {
+            let (name,): (String,) = decode(args)?;
+            respond(ctx.db.kit_remove(&name, &user)?)
+        }
+        "kit_pick" => {
+            let (name, props): (String, PickPropsWire) = decode(args)?;
+            respond(ctx.db.kit_pick(&name, &props.props(), ctx.config, &user)?)
+        }
+        "stocktake_start" => {
+            let (location,): (String,) = decode(args)?;
+            respond(ctx.db.stocktake_start(&location, &user)?)
+        }
+        "stocktake_record" => {
+            let (identifier, counted): (String, i64) = decode(args)?;
+            respond(
+                ctx.db
+                    .stocktake_record(&identifier, counted, ctx.config, &user)?,
+            )
+        }
+        "stocktake_status" => respond(ctx.db.stocktake_status()?),
+        "stocktake_close" => respond(ctx.db.stocktake_close(&user)?),
+        "stocktake_cancel" => respond(ctx.db.stocktake_cancel(&user)?),
+        _ => Err(UnknownOperation(method.into()).into()),
+    };
+}
+
+pub(crate) fn decode<T: DeserializeOwned>(args: Value) -> Result<T> {
+    return match serde_json::from_value(args) {
+        Ok(params) => Ok(params),
+        Err(e) => bail!("Parameters do not fit the operation ({})", e),
+    };
+}
+
+fn respond<T: Serialize>(result: T) -> Result<Value> {
+    return Ok(json!({ "result": result }));
+}
+
+/**
+ * Responds together with the changed config, which the client continues with.
+ */
+fn respond_config<T: Serialize>(result: T, config: &AppConfig, user: &DBUser) -> Result<Value> {
+    return Ok(json!({ "result": result, "config": visible_config(config, Some(user)) }));
+}
+
+/**
+ * The SMTP password is only given to users that may send notifications.
+ */
+fn visible_config(config: &AppConfig, user: Option<&DBUser>) -> AppConfig {
+    let mut config = config.clone();
+    if !user.is_some_and(|e| e.can_write_table("notify")) {
+        if let Some(smtp) = config.smtp.as_mut() {
+            smtp.password = None;
+        }
+    }
+    return config;
+}
+
+/**
+ * Names and defaults of columns are put into the SQL that changes the table, so they are
+ * restricted to what is safe to put there.
+ */
+fn require_declaration(decl: &SchemaDeclaration) -> Result<()> {
+    decl.validate()?;
+    if decl.name.is_empty()
+        || !decl
+            .name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        bail!("Column names of a remote database may only contain a-z, 0-9 and _");
+    }
+    if decl.default.contains('\'') {
+        bail!("Defaults of a remote database cannot contain quotes");
+    }
+    if decl.default != "NULL"
+        && decl.default != "CURRENT_TIMESTAMP"
+        && decl.context_default().is_none()
+        && matches!(
+            decl.column_type,
+            ColumnType::INT | ColumnType::REAL | ColumnType::BOOL
+        )
+        && decl.default.parse::<f64>().is_err()
+        && !matches!(decl.default.as_str(), "true" | "false")
+    {
+        bail!(
+            "Default {} is not of type {}",
+            decl.default,
+            decl.column_type
+        );
+    }
+    return Ok(());
+}
+
+/**
+ * Same checks as inventory list, as raw conditions and past values could reveal the columns a
+ * role is denied to read.
+ */
+fn check_list(user: &DBUser, config: &AppConfig, props: &ListPropsWire) -> Result<()> {
+    if props.include_archived {
+        require_read(user, "archive")?;
+    }
+    if props.with_attachments {
+        require_read(user, "attachments")?;
+    }
+    if props.assigned_to.is_some_and(|e| e != user.id) {
+        require_read(user, "assignments")?;
+    }
+    if props.category.is_some() {
+        require_read(user, "categories")?;
+    }
+    let denies_reading = config
+        .inventory_schema_declaration
+        .collection
+        .iter()
+        .any(|e| user.is_read_denied("inventory", &e.name));
+    if denies_reading && (props.with_audit || props.raw.is_some()) {
+        bail!("Cannot use --with-audit or --raw while denied to read columns of inventory");
+    }
+    // The organization only scopes what the query selects from, not what raw SQL selects
+    if user.org_id.is_some() && props.raw.is_some() {
+        bail!("Cannot use --raw as member of an organization");
+    }
+    if let Some(fuzzy) = &props.fuzzy {
+        require_column(config, &fuzzy.key)?;
+        if user.is_read_denied("inventory", &fuzzy.key) {
+            bail!("Cannot read column {} of inventory", fuzzy.key);
+        }
+    }
+    if let Some(filter) = &props.filter {
+        require_read_filter(user, config, filter)?;
+    }
+    if let Some(group) = &props.group {
+        group.output_columns(config)?;
+        for column in group.columns() {
+            if user.is_read_denied("inventory", column) {
+                bail!("Cannot read column {} of inventory", column);
+            }
+        }
+    }
+    for expansion in props.expand.iter() {
+        require_read(user, expansion.table())?;
+        check_expansion(config, expansion)?;
+    }
+    if let Some(raw) = &props.raw {
+        prepare_raw_sql(raw, &props.params)?;
+    }
+    return Ok(());
+}
+
+/**
+ * Leaves out what the user may not read, and the columns that are not text columns of the
+ * schema.
+ */
+fn readable_search(user: &DBUser, config: &AppConfig, mut props: SearchProps) -> SearchProps {
+    props.columns.retain(|column| {
+        config
+            .inventory_schema_declaration
+            .collection
+            .iter()
+            .any(|e| {
+                e.name == *column && matches!(e.column_type, ColumnType::TEXT | ColumnType::VARCHAR)
+            })
+            && !user.is_read_denied("inventory", column)
+    });
+    props.comments &= user.can_read_table("comments");
+    props.attachments &= user.can_read_table("attachments");
+    props.suppliers &= user.can_read_table("suppliers");
+    props.meta &= user.can_read_table("meta");
+    return props;
+}
+
+/**
+ * Owned counterparts of the props that borrow their values
+ */
+#[derive(Deserialize)]
+struct ListPropsWire {
+    limit: i32,
+    after_id: Option<i64>,
+    fuzzy: Option<KeyValueTypeEntry>,
+    fuzzy_threshold: f64,
+    with_audit: bool,
+    with_attachments: bool,
+    include_archived: bool,
+    supplier: Option<u32>,
+    assigned_to: Option<u32>,
+    category: Option<u32>,
+    recursive: bool,
+    raw: Option<String>,
+    params: Vec<String>,
+    changed_since: Option<ChangeMarker>,
+    filter: Option<WhereExpr>,
+    group: Option<Grouping>,
+    expand: Vec<Expansion>,
+}
+
+impl ListPropsWire {
+    fn props(&self) -> InventoryListProps<'_> {
+        return InventoryListProps {
+            limit: self.limit,
+            after_id: self.after_id,
+            fuzzy: self.fuzzy.clone(),
+            fuzzy_threshold: self.fuzzy_threshold,
+            with_audit: self.with_audit,
+            with_attachments: self.with_attachments,
+            include_archived: self.include_archived,
+            supplier: self.supplier,
+            assigned_to: self.assigned_to,
+            category: self.category,
+            recursive: self.recursive,
+            raw: &self.raw,
+            params: &self.params,
+            changed_since: self.changed_since.clone(),
+            filter: self.filter.clone(),
+            group: self.group.clone(),
+            expand: self.expand.clone(),
+        };
+    }
+}
+
+#[derive(Deserialize)]
+struct LoanPropsWire {
+    borrower: String,
+    due_at: Option<String>,
+}
+
+impl LoanPropsWire {
+    fn props(&self) -> LoanProps<'_> {
+        return LoanProps {
+            borrower: &self.borrower,
+            due_at: self.due_at.clone(),
+        };
+    }
+}
+
+#[derive(Deserialize)]
+struct AdjustPropsWire {
+    quantity: i64,
+    location: String,
+    serials: Vec<String>,
+    status: SerialStatus,
+    unit_cost: Option<f64>,
+    currency: Option<String>,
+    reason: Option<String>,
+}
+
+impl AdjustPropsWire {
+    fn props(&self) -> AdjustProps<'_> {
+        return AdjustProps {
+            quantity: self.quantity,
+            location: &self.location,
+            serials: &self.serials,
+            status: self.status,
+            unit_cost: self.unit_cost,
+            currency: self.currency.clone(),
+            reason: self.reason.clone(),
+        };
+    }
+}
+
+#[derive(Deserialize)]
+struct TransferPropsWire {
+    quantity: i64,
+    from: String,
+    to: String,
+    serials: Vec<String>,
+}
+
+impl TransferPropsWire {
+    fn props(&self) -> TransferProps<'_> {
+        return TransferProps {
+            quantity: self.quantity,
+            from: &self.from,
+            to: &self.to,
+            serials: &self.serials,
+        };
+    }
+}
+
+#[derive(Deserialize)]
+struct BuildPropsWire {
+    quantity: i64,
+    location: String,
+}
+
+impl BuildPropsWire {
+    fn props(&self) -> BuildProps<'_> {
+        return BuildProps {
+            quantity: self.quantity,
+            location: &self.location,
+        };
+    }
+}
+
+#[derive(Deserialize)]
+struct PickPropsWire {
+    quantity: i64,
+    location: String,
+}
+
+impl PickPropsWire {
+    fn props(&self) -> PickProps<'_> {
+        return PickProps {
+            quantity: self.quantity,
+            location: &self.location,
+        };
+    }
+}
+
+#[derive(Deserialize)]
+struct SupplierPropsWire {
+    name: Option<String>,
+    contact: Option<String>,
+    lead_time_days: Option<u32>,
+}
+
+impl SupplierPropsWire {
+    fn props(&self) -> SupplierProps<'_> {
+        return SupplierProps {
+            name: &self.name,
+            contact: &self.contact,
+            lead_time_days: self.lead_time_days,
+        };
+    }
+}
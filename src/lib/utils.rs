@@ -17,122 +17,616 @@
  * You should have received a copy of the GNU General Public License
  * along with invman. If not, see <https://www.gnu.org/licenses/>.
  */
-use crate::common::args::{ColumnType, SchemaDeclaration};
+use crate::common::args::SchemaDeclaration;
 use anyhow::{anyhow, bail, Result};
+use flate2::read::GzDecoder;
+use ring::hmac;
+use sha2::{Digest, Sha256};
+use std::{fs, io::Read, path::Path};
 
-pub trait SchemaDeclarationVerify {
-    /**
-     * Check if a given String is in schema notation and is found within the vector
-     * of declarations. Then the string's value is checked against that schema.
-     *
-     * @returns A tuple in (name, value) syntax
-     */
-    fn check_against_declaration(
-        &self,
-        declarations: &Vec<SchemaDeclaration>,
-    ) -> Result<(String, String)>;
-}
-
-impl SchemaDeclarationVerify for String {
-    fn check_against_declaration(
-        &self,
-        declarations: &Vec<SchemaDeclaration>,
-    ) -> Result<(String, String)> {
-        let schema_not = self.split_once("=");
-        if schema_not.is_none() {
-            bail!("Given string {} is not in valid schema notation", self);
+pub trait InvManSerialization {
+    fn to_json(&self) -> String;
+}
+
+impl InvManSerialization for Vec<SchemaDeclaration> {
+    fn to_json(&self) -> String {
+        let mut jsons = self
+            .iter()
+            .map(|e| e.to_json())
+            .collect::<Vec<String>>()
+            .join(",");
+        jsons.insert(0, '[');
+        jsons.push(']');
+        return jsons;
+    }
+}
+
+/**
+ * Scales the image down to fit into a square of the given dimension, keeping its aspect ratio.
+ * Images that already fit are not scaled up.
+ *
+ * @returns The resized image encoded as PNG
+ */
+pub fn resize_image(content: &[u8], dimension: u32) -> Result<Vec<u8>> {
+    let image = image::load_from_memory(content)?;
+    let image = if image.width() > dimension || image.height() > dimension {
+        image.thumbnail(dimension, dimension)
+    } else {
+        image
+    };
+    let mut png = std::io::Cursor::new(vec![]);
+    image.write_to(&mut png, image::ImageFormat::Png)?;
+    return Ok(png.into_inner());
+}
+
+/**
+ * Parses a duration given in days (e.g. 90d), weeks (e.g. 2w) or years of 365 days (e.g. 1y)
+ * into the number of days.
+ *
+ * @returns The amount of days, which is greater than 0
+ */
+pub fn parse_days(duration: &str) -> Result<u32> {
+    let (amount, factor) = match duration.char_indices().last() {
+        Some((i, 'd')) => (&duration[..i], 1),
+        Some((i, 'w')) => (&duration[..i], 7),
+        Some((i, 'y')) => (&duration[..i], 365),
+        _ => bail!(
+            "Duration '{}' must end with d (days), w (weeks) or y (years)",
+            duration
+        ),
+    };
+    return match amount.parse::<u32>() {
+        Ok(amount) if amount > 0 => amount
+            .checked_mul(factor)
+            .ok_or_else(|| anyhow!("Duration '{}' is too long", duration)),
+        _ => bail!(
+            "Duration '{}' must start with a number greater than 0",
+            duration
+        ),
+    };
+}
+
+/**
+ * Splits a command line into its arguments like a shell does. Arguments are separated by
+ * whitespace, unless it is quoted with ' or " or escaped with a backslash.
+ *
+ * @returns The arguments, empty for a blank line
+ */
+pub fn split_command_line(line: &str) -> Result<Vec<String>> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => match chars.next() {
+                Some(c) => arg.get_or_insert_with(String::new).push(c),
+                None => bail!("Line ends with an unfinished escape"),
+            },
+            (Some(_), c) => arg.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(arg.take()),
+            (None, c) => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        bail!("Line ends within a quote");
+    }
+    args.extend(arg);
+    return Ok(args);
+}
+
+/**
+ * Calculates how similar two strings are, ranging from 0.0 (nothing in common) to 1.0 (equal).
+ * The score is based on the optimal string alignment distance (Levenshtein with transpositions)
+ * and is case insensitive. Besides the whole haystack, the needle is also compared against each
+ * of the haystack's words, so that a single word of a longer value can be found.
+ *
+ * @returns The best similarity score found
+ */
+pub fn fuzzy_similarity(haystack: &str, needle: &str) -> f64 {
+    let haystack = haystack.to_lowercase();
+    return std::iter::once(haystack.as_str())
+        .chain(haystack.split_whitespace())
+        .map(|candidate| string_similarity(candidate, needle))
+        .fold(0.0, f64::max);
+}
+
+/**
+ * Calculates how similar two whole strings are, ranging from 0.0 (nothing in common) to 1.0
+ * (equal), based on the same case insensitive distance as fuzzy_similarity.
+ */
+pub fn string_similarity(a: &str, b: &str) -> f64 {
+    let a = a.to_lowercase().chars().collect::<Vec<char>>();
+    let b = b.to_lowercase().chars().collect::<Vec<char>>();
+    let len = a.len().max(b.len());
+    if len == 0 {
+        return 1.0;
+    }
+    return 1.0 - (string_distance(&a, &b) as f64 / len as f64);
+}
+
+fn string_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
         }
-        let (name, value) = schema_not.unwrap();
-        let name = String::from(name);
-        let value = String::from(value);
-        let schema = declarations.iter().find(|e| e.name == name);
-        if schema.is_none() {
-            bail!("Field {} could not be found in schema declaration", name);
+    }
+    return rows[a.len()][b.len()];
+}
+
+/**
+ * Alphabet of base32 (RFC 4648), which authenticator apps expect secrets to be encoded in
+ */
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/**
+ * Encodes the bytes in base32 without padding.
+ */
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    for chunk in bytes.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = buffer
+            .iter()
+            .fold(0u64, |bits, e| bits << 8 | u64::from(*e));
+        for i in 0..(chunk.len() * 8).div_ceil(5) {
+            encoded.push(BASE32_ALPHABET[(bits >> (35 - i * 5) & 31) as usize] as char);
         }
+    }
+    return encoded;
+}
 
-        let schema = schema.unwrap();
-        return match schema.column_type {
-            ColumnType::BOOL => {
-                if value.to_ascii_lowercase() == "true" {
-                    Ok((name, String::from("true")))
-                } else if value.to_ascii_lowercase() == "false" {
-                    Ok((name, String::from("false")))
-                } else {
-                    Err(anyhow!("Value not of boolean type"))
+/**
+ * Decodes base32, regardless of case, padding and whitespace.
+ */
+pub fn base32_decode(encoded: &str) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in encoded.chars().filter(|e| !e.is_whitespace() && *e != '=') {
+        let value = match BASE32_ALPHABET
+            .iter()
+            .position(|e| *e as char == c.to_ascii_uppercase())
+        {
+            Some(value) => value as u32,
+            None => bail!("'{}' is not a valid base32 character", c),
+        };
+        bits = bits << 5 | value;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    return Ok(bytes);
+}
+
+/**
+ * Calculates the time-based one-time password (RFC 6238) of the secret for the given step of
+ * 30 seconds since the unix epoch. Like the default of authenticator apps, it consists of 6
+ * digits and is based on HMAC-SHA1.
+ */
+pub fn totp(secret: &[u8], step: u64) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let digest = hmac::sign(&key, &step.to_be_bytes());
+    let digest = digest.as_ref();
+    // Dynamic truncation picks 4 bytes at the offset given by the last nibble
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes(digest[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    return format!("{:06}", code % 1_000_000);
+}
+
+/**
+ * Writes the SHA-256 checksum of the file into a sidecar file, named like the file with
+ * `.sha256` appended, in the notation of sha256sum.
+ *
+ * @returns The name of the sidecar file
+ */
+pub fn write_checksum(file: &str) -> Result<String> {
+    let sidecar = format!("{}.sha256", file);
+    let name = Path::new(file)
+        .file_name()
+        .and_then(|e| e.to_str())
+        .unwrap_or(file);
+    if let Err(e) = fs::write(&sidecar, format!("{}  {}\n", file_checksum(file)?, name)) {
+        bail!("Could not write checksum file '{}' ({})", sidecar, e);
+    }
+    return Ok(sidecar);
+}
+
+/**
+ * Verifies the file against the checksum of its sidecar file written by write_checksum.
+ *
+ * @returns False if the file has no sidecar file
+ */
+pub fn verify_checksum(file: &str) -> Result<bool> {
+    let sidecar = format!("{}.sha256", file);
+    let expected = match fs::read_to_string(&sidecar) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => bail!("Could not read checksum file '{}' ({})", sidecar, e),
+    };
+    let expected = expected.split_whitespace().next().unwrap_or("");
+    if !expected.eq_ignore_ascii_case(&file_checksum(file)?) {
+        bail!(
+            "File '{}' does not match the checksum of '{}', it may be corrupted",
+            file,
+            sidecar
+        );
+    }
+    return Ok(true);
+}
+
+fn file_checksum(file: &str) -> Result<String> {
+    let mut content = match fs::File::open(file) {
+        Ok(content) => content,
+        Err(e) => bail!("Could not read file '{}' ({})", file, e),
+    };
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut content, &mut hasher)?;
+    return Ok(format!("{:x}", hasher.finalize()));
+}
+
+/**
+ * Reads the whole file, decompressing it if it is gzip compressed.
+ */
+pub fn read_decompressed(file: &str) -> Result<Vec<u8>> {
+    let content = match fs::read(file) {
+        Ok(content) => content,
+        Err(e) => bail!("Could not read file '{}' ({})", file, e),
+    };
+    // Every gzip stream starts with these magic bytes
+    if !content.starts_with(&[0x1f, 0x8b]) {
+        return Ok(content);
+    }
+    let mut decompressed = vec![];
+    if let Err(e) = GzDecoder::new(content.as_slice()).read_to_end(&mut decompressed) {
+        bail!("Could not decompress file '{}' ({})", file, e);
+    }
+    return Ok(decompressed);
+}
+
+/**
+ * Keywords that start statements changing the database or the connection. They cannot appear
+ * in a clause appended to a SELECT that only reads.
+ */
+const WRITE_KEYWORDS: [&str; 16] = [
+    "INSERT",
+    "UPDATE",
+    "DELETE",
+    "DROP",
+    "ALTER",
+    "CREATE",
+    "ATTACH",
+    "DETACH",
+    "PRAGMA",
+    "VACUUM",
+    "REINDEX",
+    "ANALYZE",
+    "BEGIN",
+    "COMMIT",
+    "ROLLBACK",
+    "SAVEPOINT",
+];
+
+/**
+ * Keywords combining SELECTs, which would let raw SQL append rows of another query to those of
+ * the inventory.
+ */
+const COMPOUND_KEYWORDS: [&str; 3] = ["UNION", "INTERSECT", "EXCEPT"];
+
+/**
+ * Verifies that raw SQL appended to the list of the inventory is a WHERE, ORDER BY or LIMIT
+ * clause, instead of e.g. a join of another table.
+ */
+pub fn check_raw_clause(raw: &str) -> Result<()> {
+    let keyword = raw
+        .trim_start()
+        .split(|c: char| !c.is_alphabetic())
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+    if !matches!(keyword.as_str(), "WHERE" | "ORDER" | "LIMIT") {
+        bail!("Raw SQL must be a WHERE, ORDER BY or LIMIT clause");
+    }
+    return Ok(());
+}
+
+/**
+ * Verifies that the raw SQL appended to a SELECT is a single clause which only reads and closes
+ * the parentheses it opens, so that it cannot escape the condition it is put into, and maps
+ * its named placeholders (e.g. `:sku`) to numbered ones. Named placeholders take their values
+ * from params in name=value notation, otherwise the params are passed on as they are. Literals,
+ * quoted identifiers and comments are skipped, so that e.g. `name = 'drop; me'` is accepted.
+ *
+ * @returns The SQL with numbered placeholders and the values in their order
+ */
+pub fn prepare_raw_sql(raw: &str, params: &[String]) -> Result<(String, Vec<String>)> {
+    let mut sql = String::with_capacity(raw.len());
+    let mut names: Vec<String> = vec![];
+    let mut positional = false;
+    let mut depth = 0;
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' | '[' => {
+                let end = if c == '[' { ']' } else { c };
+                sql.push(c);
+                loop {
+                    match chars.next() {
+                        Some(e) => {
+                            sql.push(e);
+                            if e == end {
+                                break;
+                            }
+                        }
+                        None => bail!("Raw SQL ends within a quote"),
+                    }
                 }
             }
-            ColumnType::VARCHAR | ColumnType::TEXT => {
-                let value = String::from(value);
-                let value_len = u32::try_from(value.len()).unwrap();
-                if value_len < schema.min_length {
-                    Err(anyhow!(
-                        "Field's {} length is less than schema's min length",
-                        name
-                    ))
-                } else if value_len > schema.max_length {
-                    Err(anyhow!(
-                        "Field's {} length is more than schema's max length",
-                        name
-                    ))
-                } else {
-                    let value = format!("\"{}\"", value);
-                    Ok((name, value))
+            '-' if chars.peek() == Some(&'-') => {
+                sql.push(c);
+                for e in chars.by_ref() {
+                    sql.push(e);
+                    if e == '\n' {
+                        break;
+                    }
                 }
             }
-            ColumnType::INT => match value.parse::<i64>() {
-                Ok(s) => {
-                    if schema.min > 0 && s < schema.min.into() {
-                        bail!("Field {} is smaller than schema's min", name);
-                    } else if schema.max > 0 && s > schema.max.into() {
-                        bail!("Field {} is larger than schema's max", name);
-                    } else {
-                        Ok((name, value))
+            '/' if chars.peek() == Some(&'*') => {
+                sql.push(c);
+                sql.push(chars.next().unwrap());
+                let mut previous = ' ';
+                loop {
+                    match chars.next() {
+                        Some(e) => {
+                            sql.push(e);
+                            if previous == '*' && e == '/' {
+                                break;
+                            }
+                            previous = e;
+                        }
+                        None => bail!("Raw SQL ends within a comment"),
                     }
                 }
-                Err(_) => Err(anyhow!("Field {} is not a valid integer type", name)),
-            },
-            ColumnType::REAL => match value.parse::<f64>() {
-                Ok(s) => {
-                    if s < schema.min.into() {
-                        bail!("Field {} is smaller than schema's min", name);
-                    } else if s > schema.max.into() {
-                        bail!("Field {} is larger than schema's max", name);
-                    } else {
-                        Ok((name, value))
+            }
+            ';' => bail!("Raw SQL must not contain more than one statement"),
+            '(' => {
+                depth += 1;
+                sql.push(c);
+            }
+            ')' => {
+                if depth == 0 {
+                    bail!("Raw SQL closes a parenthesis it did not open");
+                }
+                depth -= 1;
+                sql.push(c);
+            }
+            '?' => {
+                positional = true;
+                sql.push(c);
+            }
+            ':' if chars.peek().is_some_and(|e| e.is_alphabetic() || *e == '_') => {
+                let mut name = String::new();
+                while let Some(e) = chars.next_if(|e| e.is_alphanumeric() || *e == '_') {
+                    name.push(e);
+                }
+                let index = match names.iter().position(|e| *e == name) {
+                    Some(index) => index,
+                    None => {
+                        names.push(name);
+                        names.len() - 1
                     }
+                };
+                sql.push_str(&format!("?{}", index + 1));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = String::from(c);
+                while let Some(e) = chars.next_if(|e| e.is_alphanumeric() || *e == '_') {
+                    word.push(e);
                 }
-                Err(_) => Err(anyhow!("Field {} is not a valid real type", name)),
-            },
+                if WRITE_KEYWORDS.contains(&word.to_uppercase().as_str()) {
+                    bail!("Raw SQL must only read, but contains {}", word);
+                }
+                if COMPOUND_KEYWORDS.contains(&word.to_uppercase().as_str()) {
+                    bail!("Raw SQL must not combine queries with {}", word);
+                }
+                sql.push_str(&word);
+            }
+            c => sql.push(c),
+        }
+    }
+    if depth > 0 {
+        bail!("Raw SQL does not close all of its parentheses");
+    }
+    if names.is_empty() {
+        return Ok((sql, params.to_vec()));
+    }
+    if positional {
+        bail!("Raw SQL cannot mix named and positional placeholders");
+    }
+
+    let mut values: Vec<Option<String>> = vec![None; names.len()];
+    for param in params {
+        let (name, value) = match param.split_once('=') {
+            Some(e) => e,
+            None => bail!("Parameter '{}' is not in name=value notation", param),
         };
+        let index = match names.iter().position(|e| e == name) {
+            Some(index) => index,
+            None => bail!("Parameter {} is not used by the raw SQL", name),
+        };
+        if values[index].is_some() {
+            bail!("Parameter {} is given more than once", name);
+        }
+        values[index] = Some(value.to_string());
     }
+    let values = names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| value.ok_or_else(|| anyhow!("Parameter {} is missing", name)))
+        .collect::<Result<Vec<String>>>()?;
+    return Ok((sql, values));
 }
 
-pub trait InvManSerialization {
-    fn to_json(&self) -> String;
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub trait InvManDbHelper {
-    fn to_sql_names(&self) -> String;
-}
+    #[test]
+    fn command_lines_are_split_like_a_shell_does() {
+        assert_eq!(
+            split_command_line("inventory  add -s name='Cordless drill'\t-s \"note=a \\\"b\\\"\"")
+                .unwrap(),
+            vec![
+                "inventory",
+                "add",
+                "-s",
+                "name=Cordless drill",
+                "-s",
+                "note=a \"b\""
+            ]
+        );
+        assert_eq!(
+            split_command_line("a\\ b '' c").unwrap(),
+            vec!["a b", "", "c"]
+        );
+        assert_eq!(split_command_line("'a\\b'").unwrap(), vec!["a\\b"]);
+        assert!(split_command_line("   ").unwrap().is_empty());
+        assert!(split_command_line("name='open").is_err());
+        assert!(split_command_line("trailing\\").is_err());
+    }
 
-impl InvManSerialization for Vec<SchemaDeclaration> {
-    fn to_json(&self) -> String {
-        let mut jsons = self
-            .iter()
-            .map(|e| e.to_json())
-            .collect::<Vec<String>>()
-            .join(",");
-        jsons.insert(0, '[');
-        jsons.push(']');
-        return jsons;
+    #[test]
+    fn similarity_tolerates_typos() {
+        assert_eq!(string_similarity("", ""), 1.0);
+        assert_eq!(string_similarity("ThinkPad", "thinkpad"), 1.0);
+        assert_eq!(string_similarity("abc", "xyz"), 0.0);
+        // A transposition counts as a single edit
+        assert_eq!(string_similarity("thinkpad", "thinkpda"), 1.0 - 1.0 / 8.0);
+        assert_eq!(string_similarity("kitten", "sitting"), 1.0 - 3.0 / 7.0);
+        // Single words of longer values are found as well
+        assert_eq!(fuzzy_similarity("Lenovo ThinkPad X1", "thinkpda"), 0.875);
+        assert!(fuzzy_similarity("Cordless drill", "thinkpad") < 0.6);
     }
-}
 
-impl InvManDbHelper for Vec<SchemaDeclaration> {
-    fn to_sql_names(&self) -> String {
-        self.iter()
-            .map(|e| e.name.to_owned())
-            .collect::<Vec<String>>()
-            .join(",")
+    #[test]
+    fn base32_matches_rfc_4648() {
+        for (decoded, encoded) in [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ] {
+            assert_eq!(base32_encode(decoded.as_bytes()), encoded);
+            assert_eq!(base32_decode(encoded).unwrap(), decoded.as_bytes());
+        }
+        assert_eq!(base32_decode("mzxw 6yq=").unwrap(), b"foob");
+        assert!(base32_decode("MZXW1").is_err());
+    }
+
+    #[test]
+    fn totp_matches_rfc_6238() {
+        // The SHA-1 vectors of the RFC, of which the 6 digits authenticator apps show are the last
+        let secret = b"12345678901234567890";
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ] {
+            assert_eq!(totp(secret, time / 30), code, "{}", time);
+        }
+    }
+
+    #[test]
+    fn raw_clause_must_filter_or_order() {
+        assert!(check_raw_clause("WHERE sku = 'A1'").is_ok());
+        assert!(check_raw_clause("  order by id").is_ok());
+        assert!(check_raw_clause("LIMIT 5").is_ok());
+        assert!(check_raw_clause("JOIN invman_users").is_err());
+        assert!(check_raw_clause("WHEREVER").is_err());
+        assert!(check_raw_clause("").is_err());
+    }
+
+    #[test]
+    fn raw_sql_maps_named_placeholders() {
+        let (sql, values) = prepare_raw_sql(
+            "WHERE sku = :sku OR name = :name OR note = :sku",
+            &["name=Widget".into(), "sku=A1".into()],
+        )
+        .unwrap();
+        assert_eq!(sql, "WHERE sku = ?1 OR name = ?2 OR note = ?1");
+        assert_eq!(values, vec!["A1", "Widget"]);
+    }
+
+    #[test]
+    fn raw_sql_passes_positional_parameters_on() {
+        let (sql, values) = prepare_raw_sql("WHERE sku = ?", &["A1".into()]).unwrap();
+        assert_eq!(sql, "WHERE sku = ?");
+        assert_eq!(values, vec!["A1"]);
+    }
+
+    #[test]
+    fn raw_sql_rejects_writes_and_compounds() {
+        for raw in [
+            "WHERE 1; DROP TABLE invman_users",
+            "WHERE id IN (DELETE FROM invman_inventory)",
+            "WHERE 0 UNION ALL SELECT password FROM invman_users",
+            "WHERE 0 union select 1",
+            "WHERE id IN (SELECT 1 INTERSECT SELECT 2)",
+            "WHERE (sku = 'A1'",
+            "WHERE sku = 'A1')",
+            "WHERE sku = 'A1",
+            "WHERE 1 /* open",
+        ] {
+            assert!(prepare_raw_sql(raw, &[]).is_err(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn raw_sql_skips_literals_and_comments() {
+        for raw in [
+            "WHERE name = 'drop; me'",
+            "WHERE name = 'union'",
+            "WHERE \"delete\" = 1",
+            "WHERE 1 -- ; drop\n",
+            "WHERE 1 /* ; ) */",
+        ] {
+            assert!(prepare_raw_sql(raw, &[]).is_ok(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn raw_sql_checks_named_parameters() {
+        assert!(prepare_raw_sql("WHERE sku = :sku", &[]).is_err());
+        assert!(prepare_raw_sql("WHERE sku = :sku", &["sku".into()]).is_err());
+        assert!(prepare_raw_sql("WHERE sku = :sku", &["name=A".into()]).is_err());
+        assert!(prepare_raw_sql("WHERE sku = :sku", &["sku=A".into(), "sku=B".into()]).is_err());
+        assert!(prepare_raw_sql("WHERE sku = :sku AND id = ?", &["sku=A".into()]).is_err());
     }
 }
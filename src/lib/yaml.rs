@@ -0,0 +1,500 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use anyhow::{bail, Result};
+use serde_json::{Map, Number, Value};
+
+/**
+ * Writes the value as block-style YAML. Strings are double quoted where they would otherwise be
+ * read back as another type or break the structure.
+ */
+pub fn to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => write_map(&mut out, map, 0),
+        Value::Array(items) if !items.is_empty() => write_seq(&mut out, items, 0),
+        _ => {
+            out.push_str(&scalar(value));
+            out.push('\n');
+        }
+    }
+    return out;
+}
+
+fn write_map(out: &mut String, map: &Map<String, Value>, indent: usize) {
+    for (key, value) in map {
+        out.push_str(&" ".repeat(indent));
+        out.push_str(&string_scalar(key));
+        out.push(':');
+        write_child(out, value, indent);
+    }
+}
+
+fn write_seq(out: &mut String, items: &[Value], indent: usize) {
+    for item in items {
+        out.push_str(&" ".repeat(indent));
+        out.push('-');
+        match item {
+            Value::Object(map) if !map.is_empty() => {
+                // The first key shares the line of the dash, the others are aligned with it
+                let mut nested = String::new();
+                write_map(&mut nested, map, indent + 2);
+                out.push(' ');
+                out.push_str(&nested[indent + 2..]);
+            }
+            _ => write_child(out, item, indent),
+        }
+    }
+}
+
+fn write_child(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push('\n');
+            write_map(out, map, indent + 2);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push('\n');
+            write_seq(out, items, indent + 2);
+        }
+        _ => {
+            out.push(' ');
+            out.push_str(&scalar(value));
+            out.push('\n');
+        }
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    return match value {
+        Value::Null => "null".into(),
+        Value::Bool(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        Value::String(value) => string_scalar(value),
+        Value::Array(_) => "[]".into(),
+        Value::Object(_) => "{}".into(),
+    };
+}
+
+/**
+ * The string as plain scalar if it reads back as the same string, else double quoted, whose
+ * escapes are those of JSON.
+ */
+fn string_scalar(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value.trim() == value
+        && !value.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`',
+        ])
+        && !value.contains(": ")
+        && !value.contains(" #")
+        && !value.ends_with(':')
+        && !value.chars().any(|c| c.is_control())
+        && matches!(plain_scalar(value), Value::String(_));
+    return match is_plain {
+        true => value.into(),
+        false => Value::String(value.into()).to_string(),
+    };
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    /**
+     * Content of the line without indentation and comment
+     */
+    text: &'a str,
+}
+
+/**
+ * Reads the block-style subset of YAML that to_yaml writes, as people write it by hand:
+ * mappings, sequences, plain and quoted scalars, flow collections of scalars and comments.
+ * Block scalars, anchors, aliases and tags are not supported.
+ */
+pub fn from_yaml(text: &str) -> Result<Value> {
+    let mut lines = vec![];
+    for (i, raw) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        let content = strip_comment(raw).trim_end();
+        let text = content.trim_start_matches(' ');
+        if text.is_empty() {
+            continue;
+        }
+        if text.starts_with('\t') {
+            bail!("Line {}: Tabs cannot be used for indentation", i + 1);
+        }
+        if content == "---" || content == "..." {
+            continue;
+        }
+        lines.push(Line {
+            number: i + 1,
+            indent: content.len() - text.len(),
+            text,
+        });
+    }
+    if lines.is_empty() {
+        return Ok(Value::Null);
+    }
+    let mut pos = 0;
+    let value = parse_node(&mut lines, &mut pos)?;
+    if pos < lines.len() {
+        bail!("Line {}: Unexpected content", lines[pos].number);
+    }
+    return Ok(value);
+}
+
+/**
+ * Cuts off the comment of the line, i.e. from a # at its start or after whitespace that is not
+ * within a quoted scalar.
+ */
+fn strip_comment(raw: &str) -> &str {
+    let chars = raw.char_indices().collect::<Vec<_>>();
+    let mut quote = None;
+    let mut previous = None;
+    let mut after_space = true;
+    let mut i = 0;
+    while i < chars.len() {
+        let (at, c) = chars[i];
+        match quote {
+            Some('"') if c == '\\' => i += 1,
+            Some('\'') if c == '\'' && chars.get(i + 1).is_some_and(|e| e.1 == '\'') => i += 1,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '#' && after_space => return &raw[..at],
+            None if c == '"' || c == '\'' => {
+                // Quotes only start a scalar, e.g. not the apostrophe in: name: it's
+                let opens = match previous {
+                    None | Some('[' | '{' | ',') => true,
+                    Some(':' | '-') => after_space,
+                    Some(_) => false,
+                };
+                if opens {
+                    quote = Some(c);
+                }
+            }
+            None => (),
+        }
+        after_space = c.is_whitespace();
+        if !after_space {
+            previous = Some(c);
+        }
+        i += 1;
+    }
+    return raw;
+}
+
+fn is_seq_item(text: &str) -> bool {
+    return text == "-" || text.starts_with("- ");
+}
+
+fn parse_node(lines: &mut [Line], pos: &mut usize) -> Result<Value> {
+    let line = lines[*pos];
+    if is_seq_item(line.text) {
+        return parse_seq(lines, pos, line.indent);
+    }
+    if find_separator(line.text).is_some() {
+        return parse_map(lines, pos, line.indent);
+    }
+    *pos += 1;
+    return parse_scalar(line.text, line.number);
+}
+
+/**
+ * The node below the line before pos, null if the next line is not indented further
+ */
+fn parse_child(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Value> {
+    if *pos < lines.len() && lines[*pos].indent > indent {
+        return parse_node(lines, pos);
+    }
+    return Ok(Value::Null);
+}
+
+fn parse_seq(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Value> {
+    let mut items = vec![];
+    while *pos < lines.len() && lines[*pos].indent == indent && is_seq_item(lines[*pos].text) {
+        let line = lines[*pos];
+        let rest = line.text[1..].trim_start();
+        if rest.is_empty() {
+            *pos += 1;
+            items.push(parse_child(lines, pos, indent)?);
+        } else {
+            // The item is parsed as if it started on a line of its own at the column it starts
+            lines[*pos] = Line {
+                indent: indent + line.text.len() - rest.len(),
+                text: rest,
+                ..line
+            };
+            items.push(parse_node(lines, pos)?);
+        }
+    }
+    if *pos < lines.len() && lines[*pos].indent > indent {
+        bail!("Line {}: Unexpected indentation", lines[*pos].number);
+    }
+    return Ok(Value::Array(items));
+}
+
+fn parse_map(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Value> {
+    let mut map = Map::new();
+    while *pos < lines.len() && lines[*pos].indent == indent && !is_seq_item(lines[*pos].text) {
+        let line = lines[*pos];
+        let separator = match find_separator(line.text) {
+            Some(separator) => separator,
+            None => bail!("Line {}: Expected a key: value pair", line.number),
+        };
+        let key = line.text[..separator].trim();
+        let key = match key.starts_with(['"', '\'']) {
+            true => match parse_scalar(key, line.number)? {
+                Value::String(key) => key,
+                _ => unreachable!(),
+            },
+            false => key.to_string(),
+        };
+        let value = line.text[separator + 1..].trim();
+        *pos += 1;
+        let value = match value.is_empty() {
+            // Sequences may be indented as far as their key
+            true if *pos < lines.len()
+                && lines[*pos].indent == indent
+                && is_seq_item(lines[*pos].text) =>
+            {
+                parse_seq(lines, pos, indent)?
+            }
+            true => parse_child(lines, pos, indent)?,
+            false => parse_scalar(value, line.number)?,
+        };
+        if map.insert(key.clone(), value).is_some() {
+            bail!("Line {}: Key '{}' is given twice", line.number, key);
+        }
+    }
+    if *pos < lines.len() && lines[*pos].indent > indent {
+        bail!("Line {}: Unexpected indentation", lines[*pos].number);
+    }
+    return Ok(Value::Object(map));
+}
+
+/**
+ * Position of the colon separating key and value, i.e. the first one followed by a space or the
+ * end of the line, after a quoted key. None if the text is no key: value pair.
+ */
+fn find_separator(text: &str) -> Option<usize> {
+    let start = match text.chars().next() {
+        Some(q @ ('"' | '\'')) => closing_quote(text, q)? + 1,
+        // Flow collections are values, their colons separate the entries within
+        Some('[' | '{') => return None,
+        _ => 0,
+    };
+    let bytes = text.as_bytes();
+    return (start..bytes.len())
+        .find(|&i| bytes[i] == b':' && (i + 1 == bytes.len() || bytes[i + 1] == b' '));
+}
+
+/**
+ * Position of the quote closing the scalar the text starts with
+ */
+fn closing_quote(text: &str, quote: char) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if quote == '"' => {
+                chars.next();
+            }
+            '\'' if quote == '\'' && chars.peek().is_some_and(|e| e.1 == '\'') => {
+                chars.next();
+            }
+            c if c == quote => return Some(i),
+            _ => (),
+        }
+    }
+    return None;
+}
+
+fn parse_scalar(text: &str, number: usize) -> Result<Value> {
+    return match text.chars().next() {
+        Some(q @ ('"' | '\'')) => {
+            if closing_quote(text, q) != Some(text.len() - 1) {
+                bail!("Line {}: Quoted scalar is not closed", number);
+            }
+            match q {
+                '"' => match serde_json::from_str::<String>(text) {
+                    Ok(value) => Ok(Value::String(value)),
+                    Err(e) => bail!("Line {}: Invalid double quoted scalar ({})", number, e),
+                },
+                _ => Ok(Value::String(text[1..text.len() - 1].replace("''", "'"))),
+            }
+        }
+        Some('[') if text.ends_with(']') => Ok(Value::Array(
+            split_flow(&text[1..text.len() - 1], number)?
+                .into_iter()
+                .map(|e| parse_scalar(e, number))
+                .collect::<Result<_>>()?,
+        )),
+        Some('{') if text.ends_with('}') => {
+            let mut map = Map::new();
+            for entry in split_flow(&text[1..text.len() - 1], number)? {
+                let (key, value) = match find_separator(entry) {
+                    Some(separator) => (&entry[..separator], &entry[separator + 1..]),
+                    None => bail!("Line {}: Expected a key: value pair", number),
+                };
+                let key = match parse_scalar(key.trim(), number)? {
+                    Value::String(key) => key,
+                    _ => key.trim().to_string(),
+                };
+                map.insert(key, parse_scalar(value.trim(), number)?);
+            }
+            Ok(Value::Object(map))
+        }
+        Some('[' | '{') => bail!("Line {}: Flow collection is not closed", number),
+        Some('|' | '>') => bail!(
+            "Line {}: Block scalars are not supported, use a double quoted scalar with \\n",
+            number
+        ),
+        Some('&' | '*' | '!') => bail!(
+            "Line {}: Anchors, aliases and tags are not supported",
+            number
+        ),
+        _ => Ok(plain_scalar(text)),
+    };
+}
+
+/**
+ * Splits the content of a flow collection at its commas, which may only hold scalars
+ */
+fn split_flow(content: &str, number: usize) -> Result<Vec<&str>> {
+    let mut items = vec![];
+    let mut rest = content.trim();
+    while !rest.is_empty() {
+        let end = match rest.chars().next() {
+            Some('[' | '{') => bail!("Line {}: Nested flow collections are not supported", number),
+            Some(q @ ('"' | '\'')) => match closing_quote(rest, q) {
+                Some(i) => rest[i..].find(',').map(|e| e + i),
+                None => bail!("Line {}: Quoted scalar is not closed", number),
+            },
+            _ => rest.find(','),
+        };
+        match end {
+            Some(end) => {
+                items.push(rest[..end].trim());
+                rest = rest[end + 1..].trim_start();
+            }
+            None => {
+                items.push(rest.trim());
+                rest = "";
+            }
+        }
+    }
+    return Ok(items);
+}
+
+/**
+ * Null, booleans and numbers in the notation of the YAML core schema, strings otherwise
+ */
+fn plain_scalar(text: &str) -> Value {
+    match text {
+        "null" | "Null" | "NULL" | "~" => return Value::Null,
+        "true" | "True" | "TRUE" => return Value::Bool(true),
+        "false" | "False" | "FALSE" => return Value::Bool(false),
+        _ => (),
+    }
+    if let Ok(value) = text.parse::<i64>() {
+        return Value::Number(value.into());
+    }
+    let is_numeric = text.chars().any(|c| c.is_ascii_digit())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+    if is_numeric {
+        if let Some(value) = text.parse::<f64>().ok().and_then(Number::from_f64) {
+            return Value::Number(value);
+        }
+    }
+    return Value::String(text.into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_what_it_writes() {
+        let value = json!({
+            "schema": [
+                {"name": "sku", "type": "varchar", "max_length": 32},
+                {"name": "price", "nullable": true, "default": null},
+            ],
+            "entities": [
+                {"sku": "A-1", "name": "Cordless drill", "quantity": 5, "price": 9.5},
+                {"sku": "007", "name": "- dash: colon # hash", "note": "", "tags": []},
+                {"sku": "true", "name": "it's \"quoted\"\nand multiline", "meta": {}},
+            ],
+            "empty": {},
+        });
+        let yaml = to_yaml(&value);
+        assert_eq!(from_yaml(&yaml).unwrap(), value, "{}", yaml);
+    }
+
+    #[test]
+    fn reads_yaml_written_by_hand() {
+        let yaml = "\
+# Fixture of the tests
+---
+users:
+- name: admin   # first user
+  roles: [admin, 'stock keeper', \"a, b\"]
+- name: bob
+settings: {currency: EUR, 'precision': 2, rounding: ~}
+note: it's fine
+flags:
+  - True
+  - no
+";
+        assert_eq!(
+            from_yaml(yaml).unwrap(),
+            json!({
+                "users": [
+                    {"name": "admin", "roles": ["admin", "stock keeper", "a, b"]},
+                    {"name": "bob"},
+                ],
+                "settings": {"currency": "EUR", "precision": 2, "rounding": null},
+                "note": "it's fine",
+                "flags": [true, "no"],
+            })
+        );
+        assert_eq!(from_yaml("# only a comment\n").unwrap(), Value::Null);
+        assert_eq!(from_yaml("'it''s'").unwrap(), json!("it's"));
+    }
+
+    #[test]
+    fn rejects_unsupported_or_invalid_yaml() {
+        for yaml in [
+            "a: 1\na: 2",
+            "a: 1\n  b: 2",
+            "a:\n\t- 1",
+            "a: \"open",
+            "a: [1, 2",
+            "a: [[1]]",
+            "a: |\n  text",
+            "a: &anchor 1",
+            "- 1\nb: 2",
+        ] {
+            assert!(from_yaml(yaml).is_err(), "{}", yaml);
+        }
+    }
+}
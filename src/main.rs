@@ -17,15 +17,61 @@
  * You should have received a copy of the GNU General Public License
  * along with invman. If not, see <https://www.gnu.org/licenses/>.
  */
-use clap::{Args, Parser, Subcommand, ValueEnum};
+mod report;
+mod shell;
+
+use anyhow::{bail, Result};
+use clap::{error::ErrorKind, ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use invman::{
     common::args::{
-        ColumnType, CommandContext, InventoryAddArgs, InventoryEditArgs, InventoryListArgs,
-        InventoryRemoveArgs, InventorySchemaAlterArgs, InventorySchemaListArgs,
-        InventorySchemaRemoveArgs, OutputType, UserArgs, UserEditArgs,
+        init_progress, parse_timezone, AttachmentGetArgs, AttachmentListArgs, AttachmentRemoveArgs,
+        BaseCurrencySetArgs, BatchArgs, BenchArgs, BomBuildArgs, BomDefineArgs, BomShowArgs,
+        CategoryAssignArgs, CategoryCreateArgs, CategoryListArgs, CategoryRemoveArgs,
+        CategoryUnassignArgs, ColumnType, CommandContext, CommentListArgs, DemoSeedArgs,
+        DisplayFormat, DisplayFormatSetArgs, DoctorArgs, DumpArgs, EventsWatchArgs,
+        ExchangeRateListArgs, ExchangeRateRemoveArgs, ExchangeRateSetArgs, Expansion, ExportFormat,
+        FixtureLoadArgs, FixtureSaveArgs, HealthArgs, HistoryCompactArgs, HookFailurePolicy,
+        HookListArgs, HookRemoveArgs, HookSetArgs, HtmlReportArgs, ImageSize, ImportFormat,
+        InitArgs, InventoryAddArgs, InventoryAdjustArgs, InventoryArchiveArgs, InventoryAssignArgs,
+        InventoryAttachArgs, InventoryCheckinArgs, InventoryCheckoutArgs, InventoryCommentArgs,
+        InventoryCountArgs, InventoryDedupeArgs, InventoryDistinctArgs, InventoryEditArgs,
+        InventoryExistsArgs, InventoryExportArgs, InventoryImageGetArgs, InventoryImageRemoveArgs,
+        InventoryImageSetArgs, InventoryImportArgs, InventoryLabelArgs, InventoryListArgs,
+        InventoryMergeArgs, InventoryMetaGetArgs, InventoryMetaRemoveArgs, InventoryMetaSetArgs,
+        InventoryPurchaseArgs, InventoryRemoveArgs, InventorySchemaAlterArgs,
+        InventorySchemaExampleArgs, InventorySchemaGraphqlArgs, InventorySchemaInferArgs,
+        InventorySchemaJsonSchemaArgs, InventorySchemaListArgs, InventorySchemaRemoveArgs,
+        InventoryStockArgs, InventoryTransferArgs, InventoryUnassignArgs, KitDefineArgs,
+        KitListArgs, KitPickArgs, KitRemoveArgs, KitShowArgs, LabelPrinterSetArgs, LdapClearArgs,
+        LdapSetArgs, LdapShowArgs, LoadArgs, LoanListArgs, MaintenanceDoneArgs, MaintenanceDueArgs,
+        MaintenanceScheduleArgs, MergeArgs, MergeStrategy, MetricsArgs, NotifyChannel,
+        NotifyRuleAddArgs, NotifyRuleListArgs, NotifyRuleRemoveArgs, NotifyRunArgs, OptimizeArgs,
+        OrgCreateArgs, OrgInviteArgs, OrgListArgs, OutboxListArgs, OutboxRecordArgs, OutputType,
+        OwnershipPolicy, PasswordPolicySetArgs, PasswordPolicyShowArgs, PluginListArgs,
+        PluginReportArgs, PostHookArgs, PreHookArgs, ProgressOutput, ReadOnlySetArgs,
+        ReasonCodeAddArgs, ReasonCodeListArgs, ReasonCodeRemoveArgs, ReasonReportArgs,
+        RegistrationSetArgs, ReorderSuggestArgs, RetentionApplyArgs, RetentionSetArgs,
+        RetentionShowArgs, RoleDenyArgs, RoleListArgs, RoleOwnershipArgs, RuleAddArgs,
+        RuleListArgs, RuleRemoveArgs, RulesApplyArgs, SchedulerAddArgs, SchedulerListArgs,
+        SchedulerRemoveArgs, SchedulerRunArgs, ScopeLevel, SearchArgs, SerialHistoryArgs,
+        SerialStatus, ServeArgs, ServeTls, SmtpSecurity, SmtpSetArgs, SmtpSettings, SmtpShowArgs,
+        StdinArgs, StockTrendArgs, StocktakeCancelArgs, StocktakeCloseArgs, StocktakeRecordArgs,
+        StocktakeStartArgs, StocktakeStatusArgs, SupplierAddArgs, SupplierEditArgs,
+        SupplierLinkArgs, SupplierListArgs, SupplierRemoveArgs, SyncPushArgs, TimezoneSetArgs,
+        TrendInterval, UserArgs, UserEditArgs, UserPurgeArgs, UserTokenIssueArgs,
+        UserTokenListArgs, UserTokenRefreshArgs, UserTokenRevokeArgs, UserTotpDisableArgs,
+        UserTotpEnableArgs, ValuationMethod, ValuationReportArgs, WarrantyReportArgs,
     },
-    database::{InvManConnection, InvManDBPool},
+    database::{InvManConnection, InvManDBPool, PermissionMode, OTP_REQUIRED},
+};
+use std::{
+    fs::OpenOptions,
+    io::{IsTerminal, Write},
+    sync::Mutex,
+    time::Instant,
 };
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 #[derive(Parser)]
 #[command(name = "invman")]
@@ -34,14 +80,78 @@ use invman::{
 struct InventoryManagerCli {
     #[command(subcommand)]
     /// Manage user account's in your system
-    command: InventoryManagerCliSub,
+    command: Option<InventoryManagerCliSub>,
 
     /// Username:Password syntax used for secured access
     #[arg(short, long)]
     auth: Option<String>,
 
+    #[arg(long)]
+    /// One-time password or recovery code of users with two-factor authentication, asked for if missing
+    otp: Option<String>,
+
     #[arg(short, long, value_enum)]
     output: Option<OutputTypeCli>,
+
+    #[arg(long)]
+    /// Queues changing commands in the outbox, so that they can be pushed with sync push later
+    offline: bool,
+
+    #[arg(long)]
+    /// Opens the database read-only and rejects every command that would change it
+    read_only: bool,
+
+    #[arg(long)]
+    /// Reads one command per line from stdin and writes one NDJSON result per line
+    stdin: bool,
+
+    #[arg(short, long, action = ArgAction::Count)]
+    /// Logs to stderr, -v for commands and authentication, -vv additionally for SQL statements
+    verbose: u8,
+
+    #[arg(long)]
+    /// Appends the log as JSON lines to the given file
+    log_file: Option<String>,
+
+    #[arg(short, long)]
+    /// Prints only data, leaving out status messages and warnings
+    quiet: bool,
+
+    #[arg(long)]
+    /// Prints without colors, as does setting the NO_COLOR environment variable
+    no_color: bool,
+
+    #[arg(long)]
+    /// Timezone the plain output displays timestamps in, e.g. Europe/Berlin, instead of the configured one
+    timezone: Option<String>,
+
+    #[arg(long, default_value = "./storage")]
+    /// Database file to use, or :memory: for a temporary database that is gone after the command
+    database: String,
+
+    #[arg(long, conflicts_with_all = ["database", "offline"])]
+    /// Uses the database of a server started with serve, e.g. https://inventory.internal
+    remote: Option<String>,
+
+    #[arg(long, requires = "remote")]
+    /// PEM file with CA certificates to trust for --remote besides the public ones
+    remote_ca: Option<String>,
+
+    #[arg(long, requires_all = ["remote", "remote_key"])]
+    /// PEM file with the client certificate presented to --remote
+    remote_cert: Option<String>,
+
+    #[arg(long, requires = "remote_cert")]
+    /// PEM file with the private key of --remote-cert
+    remote_key: Option<String>,
+
+    #[arg(long)]
+    /// Writes the output into the file instead of stdout, which is only replaced once the command succeeded
+    out: Option<String>,
+
+    #[arg(long, value_enum)]
+    /// Reports the progress of imports, exports, backups, history compactions and schema rebuilds on stderr, as a bar if it is a terminal by default
+    progress: Option<ProgressOutputCli>,
 }
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, ValueEnum, Ord)]
@@ -52,6 +162,7 @@ pub enum ColumnTypeCli {
     INT,
     REAL,
     BOOL,
+    MONEY,
 }
 
 impl ColumnTypeCli {
@@ -62,6 +173,7 @@ impl ColumnTypeCli {
             ColumnTypeCli::REAL => ColumnType::REAL,
             ColumnTypeCli::TEXT => ColumnType::TEXT,
             ColumnTypeCli::VARCHAR => ColumnType::VARCHAR,
+            ColumnTypeCli::MONEY => ColumnType::MONEY,
         };
     }
 }
@@ -70,271 +182,3576 @@ impl ColumnTypeCli {
 enum OutputTypeCli {
     Plain,
     Json,
+    Ndjson,
+    Pdf,
 }
 
-impl OutputTypeCli {
-    fn to_lib(&self) -> OutputType {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ProgressOutputCli {
+    /// Bar redrawn in place
+    Bar,
+    /// One JSON event per line, e.g. {"task":"import","done":1000,"total":5000,"finished":false}
+    Json,
+    /// No progress at all
+    None,
+}
+
+impl ProgressOutputCli {
+    fn to_lib(self) -> ProgressOutput {
         return match self {
-            OutputTypeCli::Json => OutputType::Json,
-            OutputTypeCli::Plain => OutputType::Plain,
+            ProgressOutputCli::Bar => ProgressOutput::Bar,
+            ProgressOutputCli::Json => ProgressOutput::Json,
+            ProgressOutputCli::None => ProgressOutput::None,
         };
     }
 }
 
-#[derive(Args, Debug)]
-pub struct InventoryRemoveCliArgs {
-    #[arg(short, long)]
-    /// The identifier used to target a specific entity
-    identifier: String,
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum SerialStatusCli {
+    InStock,
+    Sold,
+    Rma,
 }
 
-impl InventoryRemoveCliArgs {
-    fn to_lib(&self) -> InventoryRemoveArgs {
-        return InventoryRemoveArgs {
-            identifier: self.identifier.clone(),
+impl SerialStatusCli {
+    fn to_lib(self) -> SerialStatus {
+        return match self {
+            SerialStatusCli::InStock => SerialStatus::InStock,
+            SerialStatusCli::Sold => SerialStatus::Sold,
+            SerialStatusCli::Rma => SerialStatus::Rma,
         };
     }
 }
 
-#[derive(Args, Debug)]
-pub struct InventoryEditCliArgs {
-    #[arg(short, long)]
-    /// The identifier used to target a specific entity
-    identifier: String,
-
-    #[arg(short, long)]
-    /// Enter your parameters according to your specified schema in a name=value way
-    set: Vec<String>,
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ExpansionCli {
+    #[value(alias = "supplier")]
+    Suppliers,
+    #[value(alias = "location")]
+    Locations,
+    Category,
+    Assignment,
 }
 
-impl InventoryEditCliArgs {
-    fn to_lib(&self) -> InventoryEditArgs {
-        return InventoryEditArgs {
-            identifier: self.identifier.clone(),
-            set: self.set.clone(),
+impl ExpansionCli {
+    fn to_lib(self) -> Expansion {
+        return match self {
+            ExpansionCli::Suppliers => Expansion::Suppliers,
+            ExpansionCli::Locations => Expansion::Locations,
+            ExpansionCli::Category => Expansion::Category,
+            ExpansionCli::Assignment => Expansion::Assignment,
         };
     }
 }
 
-#[derive(Args, Debug)]
-pub struct InventoryAddCliArgs {
-    /// Enter your parameters according to your specified schema in a name=value way
-    params: Vec<String>,
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ImageSizeCli {
+    Thumb,
+    Medium,
+    Original,
 }
 
-impl InventoryAddCliArgs {
-    fn to_lib(&self) -> InventoryAddArgs {
-        return InventoryAddArgs {
-            params: self.params.clone(),
+impl ImageSizeCli {
+    fn to_lib(self) -> ImageSize {
+        return match self {
+            ImageSizeCli::Thumb => ImageSize::Thumb,
+            ImageSizeCli::Medium => ImageSize::Medium,
+            ImageSizeCli::Original => ImageSize::Original,
         };
     }
 }
 
-#[derive(Args, Debug)]
-pub struct InventorySchemaRemoveCliArgs {
-    /// Name of the schema column
-    name: String,
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum MergeStrategyCli {
+    Skip,
+    Overwrite,
+    Fail,
 }
 
-impl InventorySchemaRemoveCliArgs {
-    fn to_lib(&self) -> InventorySchemaRemoveArgs {
-        return InventorySchemaRemoveArgs {
-            name: self.name.clone(),
+impl MergeStrategyCli {
+    fn to_lib(self) -> MergeStrategy {
+        return match self {
+            MergeStrategyCli::Skip => MergeStrategy::Skip,
+            MergeStrategyCli::Overwrite => MergeStrategy::Overwrite,
+            MergeStrategyCli::Fail => MergeStrategy::Fail,
         };
     }
 }
 
-#[derive(Args, Debug)]
-pub struct UserRegisterCliArgs {
-    /// Name of the user
-    name: String,
-    /// Password of the user
-    password: String,
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum SmtpSecurityCli {
+    Tls,
+    Starttls,
+    None,
 }
 
-impl UserRegisterCliArgs {
-    fn to_lib(&self) -> UserArgs {
-        return UserArgs {
-            name: self.name.clone(),
-            password: self.password.clone(),
+impl SmtpSecurityCli {
+    fn to_lib(self) -> SmtpSecurity {
+        return match self {
+            SmtpSecurityCli::Tls => SmtpSecurity::Tls,
+            SmtpSecurityCli::Starttls => SmtpSecurity::Starttls,
+            SmtpSecurityCli::None => SmtpSecurity::None,
         };
     }
 }
 
-#[derive(Args, Debug)]
-pub struct UserEditCliArgs {
-    /// Options to change into in option1=value1 option2=value2 syntax
-    options: Vec<String>,
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum NotifyChannelCli {
+    Email,
+    Slack,
+    Webhook,
 }
 
-impl UserEditCliArgs {
-    fn to_lib(&self) -> UserEditArgs {
-        return UserEditArgs {
-            options: self.options.clone(),
+impl NotifyChannelCli {
+    fn to_lib(self) -> NotifyChannel {
+        return match self {
+            NotifyChannelCli::Email => NotifyChannel::Email,
+            NotifyChannelCli::Slack => NotifyChannel::Slack,
+            NotifyChannelCli::Webhook => NotifyChannel::Webhook,
+        };
+    }
+}
+
+impl OutputTypeCli {
+    fn to_lib(self) -> OutputType {
+        return match self {
+            OutputTypeCli::Json => OutputType::Json,
+            OutputTypeCli::Plain => OutputType::Plain,
+            OutputTypeCli::Ndjson => OutputType::Ndjson,
+            OutputTypeCli::Pdf => OutputType::Pdf,
         };
     }
 }
 
 #[derive(Args, Debug)]
-pub struct InventorySchemaAlterCliArgs {
+pub struct InventoryRemoveCliArgs {
     #[arg(short, long)]
-    /// Specifies the name as tag for your column. Only following values are allowed [a-z\-\_] (Letters from a-z (lowercase), - (dash) and _ (underscore))
-    name: String,
+    /// The identifier used to target a specific entity
+    identifier: String,
 
     #[arg(long)]
-    /// Explicitly defines its display name for printing (Default: Parsed name value)
-    display_name: Option<String>,
+    /// Why the entity is removed, one of the reason codes of the config
+    reason: Option<String>,
+}
 
-    #[arg(short, long)]
-    /// If set to true then one and only one kind of its value can be found in the system (Default: false)
-    unique: bool,
+impl InventoryRemoveCliArgs {
+    fn to_lib(&self) -> InventoryRemoveArgs {
+        return InventoryRemoveArgs {
+            identifier: self.identifier.clone(),
+            reason: self.reason.clone(),
+        };
+    }
+}
 
+#[derive(Args, Debug)]
+pub struct InventoryMergeCliArgs {
     #[arg(short, long)]
-    /// Specifies the maximum length of this parameter (only applies to strings) (Default: 0)
-    max_length: Option<u32>,
-
-    #[arg(long)]
-    /// Specifies the minimum length of this parameter (only applies to strings) (Default: 0)
-    min_length: Option<u32>,
-
-    #[arg(long)]
-    /// Specifies the maximum value of this parameter (only applies to INT and REAL) (Default: 0)
-    max: Option<u32>,
-
-    #[arg(long)]
-    /// Specifies the minimum value of this parameter (only applies to INT and REAL) (Default: 0)
-    min: Option<u32>,
+    /// Identifier of the entity that is kept
+    keep: String,
 
-    #[arg(long)]
-    /// Allows for value NULL to be inserted if no value is provided and no default is specified (Default: false)
-    nullable: Option<bool>,
+    #[arg(short, long)]
+    /// Identifier of the duplicate that is merged into the kept entity and removed
+    merge: String,
+}
 
-    #[arg(short, long, value_enum)]
-    /// Define the type of data stored in the column, choose between
-    ///     - TEXT for texts of arbritrary length that cannot be searched
-    ///     - VARCHAR that must have max_length specified for texts that can be searched
-    ///     - INT for whole numbers
-    ///     - REAL for real numbers
-    ///     - BOOL for boolean value, i.e. only values of true and false
-    column_type: ColumnTypeCli,
+impl InventoryMergeCliArgs {
+    fn to_lib(&self) -> InventoryMergeArgs {
+        return InventoryMergeArgs {
+            keep: self.keep.clone(),
+            duplicate: self.merge.clone(),
+        };
+    }
+}
 
+#[derive(Args, Debug)]
+pub struct InventoryDedupeCliArgs {
     #[arg(short, long)]
-    /// The default value that will be used if no value is provided (Default: NULL)
-    ///     TIPS:
-    ///     - Use CURRENT_TIMESTAMP to automatically use the current Datetime as value
-    default: Option<String>,
+    /// Name of the schema column whose values are compared
+    by: String,
 
-    #[arg(long)]
-    /// Hint for external applications of how to display this column (Default: Empty String)
-    hint: Option<String>,
+    #[arg(short, long)]
+    /// Also find values that are similar, e.g. differing by a typo
+    fuzzy: bool,
 
-    #[arg(long)]
-    /// For external applications as additional layout information (Default: Empty String)
-    layout: Option<String>,
+    #[arg(short, long, default_value_t = 0.8, requires = "fuzzy")]
+    /// Minimum similarity between 0 and 1 of values found by --fuzzy
+    threshold: f64,
 }
 
-impl InventorySchemaAlterCliArgs {
-    fn to_lib(&self) -> InventorySchemaAlterArgs {
-        return InventorySchemaAlterArgs {
-            name: self.name.clone(),
-            display_name: self.display_name.clone(),
-            unique: self.unique,
-            max_length: self.max_length,
-            min_length: self.min_length,
-            max: self.max,
-            min: self.min,
-            nullable: self.nullable,
-            column_type: self.column_type.to_lib(),
-            default: self.default.clone(),
-            hint: self.hint.clone(),
-            layout: self.layout.clone(),
+impl InventoryDedupeCliArgs {
+    fn to_lib(&self) -> InventoryDedupeArgs {
+        return InventoryDedupeArgs {
+            by: self.by.clone(),
+            fuzzy: self.fuzzy,
+            threshold: self.threshold,
         };
     }
 }
 
-#[derive(Subcommand, Debug)]
-pub enum InventoryCommands {
-    /// Add an entity to your inventory
-    Add(InventoryAddCliArgs),
+#[derive(Args, Debug)]
+pub struct InventoryCountCliArgs {
+    #[arg(short = 'w', long = "where")]
+    /// Only count entities matching all of the given name=value conditions
+    conditions: Vec<String>,
+}
 
-    /// List all entities stored in your inventory
-    List(InventoryListCliArgs),
+impl InventoryCountCliArgs {
+    fn to_lib(&self) -> InventoryCountArgs {
+        return InventoryCountArgs {
+            conditions: self.conditions.clone(),
+        };
+    }
+}
 
-    #[command(subcommand)]
-    /// Change the schema in which your entities are stored
-    Schema(InventorySchemaCommands),
+#[derive(Args, Debug)]
+pub struct InventoryDistinctCliArgs {
+    /// Name of the schema column
+    column: String,
 
-    /// Edit an existing entity in your inventory
-    Edit(InventoryEditCliArgs),
+    #[arg(short = 'w', long = "where")]
+    /// Only consider entities matching all of the given name=value conditions
+    conditions: Vec<String>,
 
-    /// Remove an entity from your inventory
-    Remove(InventoryRemoveCliArgs),
+    #[arg(short, long)]
+    /// Additionally output how many entities share each value
+    count: bool,
 }
 
-#[derive(Subcommand, Debug)]
-pub enum ConfigCommands {}
-
-#[derive(Subcommand, Debug)]
-pub enum UserCommands {
-    /// Register a new user
-    Register(UserRegisterCliArgs),
-    Edit(UserEditCliArgs),
+impl InventoryDistinctCliArgs {
+    fn to_lib(&self) -> InventoryDistinctArgs {
+        return InventoryDistinctArgs {
+            column: self.column.clone(),
+            conditions: self.conditions.clone(),
+            count: self.count,
+        };
+    }
 }
 
 #[derive(Args, Debug)]
-pub struct InventoryListCliArgs {
+pub struct InventoryLabelCliArgs {
     #[arg(short, long)]
-    /// Limit the amount of entities to be returned
-    limit: Option<i32>,
+    /// The identifier used to target a specific entity
+    identifier: String,
 
-    #[arg(short, long)]
-    /// How the returned rows should be sorted
-    sort: Vec<String>,
+    #[arg(short, long = "field")]
+    /// Column printed on the label, may be given multiple times (Default: all columns)
+    fields: Vec<String>,
 
     #[arg(short, long)]
-    /// Executes the query directly onto the database. BEWARE that parameters must be passed seperatly with --params flags, otherwise your system will be vulnerable to SQL injection attacks
-    raw: Option<String>,
+    /// Column encoded by the barcode (Default: id)
+    barcode: Option<String>,
 
-    #[arg(short, long)]
-    /// Parameters that are passed with the raw SQL string
-    params: Vec<String>,
+    #[arg(short, long, default_value_t = 1)]
+    /// Number of labels to print
+    copies: u32,
 
     #[arg(short, long)]
-    /// How the returned rows should be sorted
-    condition: Vec<String>,
+    /// Send the label to the configured label printer instead of printing it
+    send: bool,
 }
 
-impl InventoryListCliArgs {
-    fn to_lib(&self) -> InventoryListArgs {
-        return InventoryListArgs {
-            limit: self.limit,
-            sort: self.sort.clone(),
-            raw: self.raw.clone(),
-            params: self.params.clone(),
-            condition: self.condition.clone(),
+impl InventoryLabelCliArgs {
+    fn to_lib(&self) -> InventoryLabelArgs {
+        return InventoryLabelArgs {
+            identifier: self.identifier.clone(),
+            fields: self.fields.clone(),
+            barcode: self.barcode.clone(),
+            copies: self.copies,
+            send: self.send,
         };
     }
 }
 
 #[derive(Args, Debug)]
-struct InventorySchemaListCliArgs;
+pub struct InventoryExistsCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+}
 
-impl InventorySchemaListCliArgs {
-    fn to_lib(&self) -> InventorySchemaListArgs {
-        return InventorySchemaListArgs;
+impl InventoryExistsCliArgs {
+    fn to_lib(&self) -> InventoryExistsArgs {
+        return InventoryExistsArgs {
+            identifier: self.identifier.clone(),
+        };
     }
 }
 
-#[derive(Subcommand, Debug)]
-pub enum InventorySchemaCommands {
-    /// Add or edit a schema column
-    Alter(InventorySchemaAlterCliArgs),
+#[derive(Args, Debug)]
+pub struct InventoryArchiveCliArgs {
+    #[arg(short, long = "where")]
+    /// Condition selecting the entities to archive, e.g. "deleted_at < 2024-01-01"
+    condition: String,
+}
 
-    /// Remove a schema column
+impl InventoryArchiveCliArgs {
+    fn to_lib(&self) -> InventoryArchiveArgs {
+        return InventoryArchiveArgs {
+            condition: self.condition.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryCheckoutCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(short, long)]
+    /// Name of the person borrowing the entity
+    to: String,
+
+    #[arg(short, long)]
+    /// Date in YYYY-MM-DD format at which the entity is expected to be returned
+    due: Option<String>,
+}
+
+impl InventoryCheckoutCliArgs {
+    fn to_lib(&self) -> InventoryCheckoutArgs {
+        return InventoryCheckoutArgs {
+            identifier: self.identifier.clone(),
+            to: self.to.clone(),
+            due: self.due.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryCheckinCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+}
+
+impl InventoryCheckinCliArgs {
+    fn to_lib(&self) -> InventoryCheckinArgs {
+        return InventoryCheckinArgs {
+            identifier: self.identifier.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryAssignCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(short, long)]
+    /// Username of the user the entity is handed over to
+    to: String,
+}
+
+impl InventoryAssignCliArgs {
+    fn to_lib(&self) -> InventoryAssignArgs {
+        return InventoryAssignArgs {
+            identifier: self.identifier.clone(),
+            to: self.to.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryAttachCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    /// Path of the file to attach
+    file: String,
+}
+
+impl InventoryAttachCliArgs {
+    fn to_lib(&self) -> InventoryAttachArgs {
+        return InventoryAttachArgs {
+            identifier: self.identifier.clone(),
+            file: self.file.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryCommentCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    /// Text of the comment
+    body: String,
+}
+
+impl InventoryCommentCliArgs {
+    fn to_lib(&self) -> InventoryCommentArgs {
+        return InventoryCommentArgs {
+            identifier: self.identifier.clone(),
+            body: self.body.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CommentListCliArgs {
+    #[arg(short, long)]
+    /// The identifier of the entity whose comments are listed
+    identifier: String,
+}
+
+impl CommentListCliArgs {
+    fn to_lib(&self) -> CommentListArgs {
+        return CommentListArgs {
+            identifier: self.identifier.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CommentCommands {
+    /// List the comments on an entity
+    List(CommentListCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryImageSetCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    /// Path of the PNG or JPEG image
+    file: String,
+}
+
+impl InventoryImageSetCliArgs {
+    fn to_lib(&self) -> InventoryImageSetArgs {
+        return InventoryImageSetArgs {
+            identifier: self.identifier.clone(),
+            file: self.file.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryImageGetCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(short, long, value_enum, default_value = "original")]
+    /// Size of the image, thumb (128px) and medium (512px) are resized PNGs
+    size: ImageSizeCli,
+
+    #[arg(short, long)]
+    /// Path the image is written to (Default: Derived from its original filename)
+    out: Option<String>,
+}
+
+impl InventoryImageGetCliArgs {
+    fn to_lib(&self) -> InventoryImageGetArgs {
+        return InventoryImageGetArgs {
+            identifier: self.identifier.clone(),
+            size: self.size.to_lib(),
+            out: self.out.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryImageRemoveCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+}
+
+impl InventoryImageRemoveCliArgs {
+    fn to_lib(&self) -> InventoryImageRemoveArgs {
+        return InventoryImageRemoveArgs {
+            identifier: self.identifier.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum InventoryImageCommands {
+    /// Set the image of an entity
+    Set(InventoryImageSetCliArgs),
+
+    /// Write the image of an entity to disk, optionally resized
+    Get(InventoryImageGetCliArgs),
+
+    /// Remove the image of an entity
+    Remove(InventoryImageRemoveCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryMetaSetCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    /// Key of the metadata, e.g. calibration-lab
+    key: String,
+
+    /// Value to set the key to, replacing its previous value
+    value: String,
+}
+
+impl InventoryMetaSetCliArgs {
+    fn to_lib(&self) -> InventoryMetaSetArgs {
+        return InventoryMetaSetArgs {
+            identifier: self.identifier.clone(),
+            key: self.key.clone(),
+            value: self.value.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryMetaGetCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    /// Key whose value is printed (Default: All metadata as JSON object)
+    key: Option<String>,
+}
+
+impl InventoryMetaGetCliArgs {
+    fn to_lib(&self) -> InventoryMetaGetArgs {
+        return InventoryMetaGetArgs {
+            identifier: self.identifier.clone(),
+            key: self.key.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryMetaRemoveCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    /// Key of the metadata
+    key: String,
+}
+
+impl InventoryMetaRemoveCliArgs {
+    fn to_lib(&self) -> InventoryMetaRemoveArgs {
+        return InventoryMetaRemoveArgs {
+            identifier: self.identifier.clone(),
+            key: self.key.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum InventoryMetaCommands {
+    /// Set a metadata key of an entity
+    Set(InventoryMetaSetCliArgs),
+
+    /// Print the metadata of an entity
+    Get(InventoryMetaGetCliArgs),
+
+    /// Remove a metadata key of an entity
+    Remove(InventoryMetaRemoveCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct AttachmentListCliArgs {
+    #[arg(short, long)]
+    /// The identifier of the entity whose attachments are listed
+    identifier: String,
+}
+
+impl AttachmentListCliArgs {
+    fn to_lib(&self) -> AttachmentListArgs {
+        return AttachmentListArgs {
+            identifier: self.identifier.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct AttachmentGetCliArgs {
+    #[arg(long)]
+    /// The identifier of the attachment
+    id: String,
+
+    #[arg(short, long)]
+    /// Path the file is written to (Default: Its original filename)
+    out: Option<String>,
+}
+
+impl AttachmentGetCliArgs {
+    fn to_lib(&self) -> AttachmentGetArgs {
+        return AttachmentGetArgs {
+            identifier: self.id.clone(),
+            out: self.out.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct AttachmentRemoveCliArgs {
+    #[arg(long)]
+    /// The identifier of the attachment
+    id: String,
+}
+
+impl AttachmentRemoveCliArgs {
+    fn to_lib(&self) -> AttachmentRemoveArgs {
+        return AttachmentRemoveArgs {
+            identifier: self.id.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AttachmentCommands {
+    /// List the files attached to an entity
+    List(AttachmentListCliArgs),
+
+    /// Write an attached file to disk
+    Get(AttachmentGetCliArgs),
+
+    /// Remove an attached file
+    Remove(AttachmentRemoveCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryPurchaseCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(short, long)]
+    /// Date in YYYY-MM-DD format at which the entity was purchased
+    date: String,
+
+    #[arg(short, long)]
+    /// Price the entity was purchased for
+    price: f64,
+
+    #[arg(short, long)]
+    /// Months the entity is covered by warranty after its purchase
+    warranty_months: Option<u32>,
+
+    #[arg(short, long)]
+    /// Months over which the purchase price is depreciated (Default: No depreciation)
+    useful_life_months: Option<u32>,
+
+    #[arg(long)]
+    /// Currency of the price, e.g. USD (Default: base currency)
+    currency: Option<String>,
+}
+
+impl InventoryPurchaseCliArgs {
+    fn to_lib(&self) -> InventoryPurchaseArgs {
+        return InventoryPurchaseArgs {
+            identifier: self.identifier.clone(),
+            date: self.date.clone(),
+            price: self.price,
+            warranty_months: self.warranty_months,
+            useful_life_months: self.useful_life_months,
+            currency: self.currency.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct WarrantyReportCliArgs {
+    #[arg(short, long, default_value = "30d")]
+    /// List warranties expiring within the given days (e.g. 60d) or weeks (e.g. 8w)
+    within: String,
+
+    #[arg(long)]
+    /// File the report is written to with the PDF output (-o pdf)
+    out: Option<String>,
+
+    #[arg(long, conflicts_with = "out")]
+    /// Print the queries of the report and their plans instead of the report
+    explain: bool,
+}
+
+impl WarrantyReportCliArgs {
+    fn to_lib(&self) -> WarrantyReportArgs {
+        return WarrantyReportArgs {
+            within: self.within.clone(),
+            out: self.out.clone(),
+        };
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ValuationMethodCli {
+    /// Purchase price less the straight-line depreciation
+    Depreciation,
+    /// Stock valued at the unit costs of its latest receipts
+    Fifo,
+    /// Stock valued at the weighted average unit cost of its receipts
+    Avg,
+}
+
+impl ValuationMethodCli {
+    fn to_lib(self) -> ValuationMethod {
+        return match self {
+            ValuationMethodCli::Depreciation => ValuationMethod::Depreciation,
+            ValuationMethodCli::Fifo => ValuationMethod::Fifo,
+            ValuationMethodCli::Avg => ValuationMethod::Average,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ValuationReportCliArgs {
+    #[arg(short, long, value_enum, default_value_t = ValuationMethodCli::Depreciation)]
+    /// How the entities are valued
+    method: ValuationMethodCli,
+
+    #[arg(long)]
+    /// File the report is written to with the PDF output (-o pdf)
+    out: Option<String>,
+
+    #[arg(long)]
+    /// Currency the amounts are converted into (Default: base currency)
+    currency: Option<String>,
+
+    #[arg(long, conflicts_with = "out")]
+    /// Print the queries of the report and their plans instead of the report
+    explain: bool,
+}
+
+impl ValuationReportCliArgs {
+    fn to_lib(&self) -> ValuationReportArgs {
+        return ValuationReportArgs {
+            method: self.method.to_lib(),
+            out: self.out.clone(),
+            currency: self.currency.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct HtmlReportCliArgs {
+    #[arg(long)]
+    /// File the page is written to (e.g. index.html)
+    out: String,
+
+    #[arg(short, long)]
+    /// Column to chart the number of entities by (e.g. category)
+    category: Option<String>,
+
+    #[arg(short, long, default_value_t = 12)]
+    /// Number of past months the value trend covers
+    months: u32,
+}
+
+impl HtmlReportCliArgs {
+    fn to_lib(&self) -> HtmlReportArgs {
+        return HtmlReportArgs {
+            out: self.out.clone(),
+            category: self.category.clone(),
+            months: self.months,
+        };
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TrendIntervalCli {
+    Day,
+    /// Weeks starting on Monday
+    Week,
+    Month,
+}
+
+impl TrendIntervalCli {
+    fn to_lib(self) -> TrendInterval {
+        return match self {
+            TrendIntervalCli::Day => TrendInterval::Day,
+            TrendIntervalCli::Week => TrendInterval::Week,
+            TrendIntervalCli::Month => TrendInterval::Month,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct StockTrendCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(long, value_enum, default_value_t = TrendIntervalCli::Week)]
+    /// Length of the periods the quantity is reported for
+    interval: TrendIntervalCli,
+
+    #[arg(short, long)]
+    /// Only follow the stock at this location (Default: total stock)
+    location: Option<String>,
+
+    #[arg(short, long, value_enum, default_value_t = ExportFormatCli::Json)]
+    /// Format the time series is written in
+    format: ExportFormatCli,
+
+    #[arg(long)]
+    /// Print the queries of the report and their plans instead of the report
+    explain: bool,
+}
+
+impl StockTrendCliArgs {
+    fn to_lib(&self) -> StockTrendArgs {
+        return StockTrendArgs {
+            identifier: self.identifier.clone(),
+            interval: self.interval.to_lib(),
+            location: self.location.clone(),
+            format: self.format.to_lib(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ReorderSuggestCliArgs {
+    #[arg(short, long, default_value = "90d")]
+    /// Days (e.g. 90d), weeks (e.g. 12w) or years (e.g. 1y) the average consumption is calculated over
+    window: String,
+
+    #[arg(long, default_value_t = 14)]
+    /// Lead time in days of entities without a linked supplier that has one
+    lead_time: u32,
+
+    #[arg(long, default_value_t = 7)]
+    /// Days of consumption kept in stock as buffer
+    safety_days: u32,
+
+    #[arg(long, default_value = "30d")]
+    /// Days (e.g. 30d) or weeks (e.g. 4w) of consumption a reorder should cover
+    cover: String,
+
+    #[arg(short, long, value_enum, default_value_t = ExportFormatCli::Json)]
+    /// Format the suggestions are written in
+    format: ExportFormatCli,
+
+    #[arg(long)]
+    /// Print the queries of the report and their plans instead of the report
+    explain: bool,
+}
+
+impl ReorderSuggestCliArgs {
+    fn to_lib(&self) -> ReorderSuggestArgs {
+        return ReorderSuggestArgs {
+            window: self.window.clone(),
+            lead_time_days: self.lead_time,
+            safety_days: self.safety_days,
+            cover: self.cover.clone(),
+            format: self.format.to_lib(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ReasonReportCliArgs {
+    #[arg(short, long)]
+    /// Days (e.g. 90d), weeks (e.g. 12w) or years (e.g. 1y) to sum up (Default: all time)
+    window: Option<String>,
+
+    #[arg(short, long, value_enum, default_value_t = ExportFormatCli::Json)]
+    /// Format the summary is written in
+    format: ExportFormatCli,
+
+    #[arg(long)]
+    /// Print the queries of the report and their plans instead of the report
+    explain: bool,
+}
+
+impl ReasonReportCliArgs {
+    fn to_lib(&self) -> ReasonReportArgs {
+        return ReasonReportArgs {
+            window: self.window.clone(),
+            format: self.format.to_lib(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum InventoryReportCommands {
+    /// List entities whose warranty expires soon
+    WarrantyExpiring(WarrantyReportCliArgs),
+
+    /// Show the depreciated book value of your entities or the value of their stock
+    Valuation(ValuationReportCliArgs),
+
+    /// Write a self-contained HTML page with charts and sortable tables of your inventory
+    Html(HtmlReportCliArgs),
+
+    /// Show the stock of an entity over time, per day, week or month
+    Trend(StockTrendCliArgs),
+
+    /// Suggest reorder points and quantities from the average consumption of your entities
+    ReorderSuggest(ReorderSuggestCliArgs),
+
+    /// Sum up the stock adjusted and the entities removed per reason, e.g. to analyze losses
+    Reasons(ReasonReportCliArgs),
+}
+
+impl InventoryReportCommands {
+    fn explain(&self) -> bool {
+        return match self {
+            InventoryReportCommands::WarrantyExpiring(args) => args.explain,
+            InventoryReportCommands::Valuation(args) => args.explain,
+            InventoryReportCommands::Html(_) => false,
+            InventoryReportCommands::Trend(args) => args.explain,
+            InventoryReportCommands::ReorderSuggest(args) => args.explain,
+            InventoryReportCommands::Reasons(args) => args.explain,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryUnassignCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+}
+
+impl InventoryUnassignCliArgs {
+    fn to_lib(&self) -> InventoryUnassignArgs {
+        return InventoryUnassignArgs {
+            identifier: self.identifier.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryAdjustCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(short, long, allow_negative_numbers = true)]
+    /// Quantity to add to the stock, use a negative number to take from it
+    qty: i64,
+
+    #[arg(short, long)]
+    /// Location at which the stock is kept
+    location: String,
+
+    #[arg(long = "serial")]
+    /// Serial of a unit that is booked, required once per unit for serialized entities
+    serials: Vec<String>,
+
+    #[arg(long, value_enum)]
+    /// Status of the serials taken from the stock (Default: sold, in-stock when adding)
+    status: Option<SerialStatusCli>,
+
+    #[arg(short, long)]
+    /// Cost of a single unit added to the stock, used by the FIFO and average cost valuation
+    unit_cost: Option<f64>,
+
+    #[arg(long, requires = "unit_cost")]
+    /// Currency of the unit cost, e.g. USD (Default: base currency)
+    currency: Option<String>,
+
+    #[arg(long)]
+    /// Why the stock is adjusted, one of the reason codes of the config
+    reason: Option<String>,
+}
+
+impl InventoryAdjustCliArgs {
+    fn to_lib(&self) -> InventoryAdjustArgs {
+        let status = match self.status {
+            Some(status) => status,
+            None if self.qty > 0 => SerialStatusCli::InStock,
+            None => SerialStatusCli::Sold,
+        };
+        return InventoryAdjustArgs {
+            identifier: self.identifier.clone(),
+            quantity: self.qty,
+            location: self.location.clone(),
+            serials: self.serials.clone(),
+            status: status.to_lib(),
+            unit_cost: self.unit_cost,
+            currency: self.currency.clone(),
+            reason: self.reason.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryTransferCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(short, long)]
+    /// Quantity to move between the locations
+    qty: i64,
+
+    #[arg(short, long)]
+    /// Location the stock is taken from
+    from: String,
+
+    #[arg(short, long)]
+    /// Location the stock is moved to
+    to: String,
+
+    #[arg(long = "serial")]
+    /// Serial of a unit that is moved, required once per unit for serialized entities
+    serials: Vec<String>,
+}
+
+impl InventoryTransferCliArgs {
+    fn to_lib(&self) -> InventoryTransferArgs {
+        return InventoryTransferArgs {
+            identifier: self.identifier.clone(),
+            quantity: self.qty,
+            from: self.from.clone(),
+            to: self.to.clone(),
+            serials: self.serials.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryStockCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+}
+
+impl InventoryStockCliArgs {
+    fn to_lib(&self) -> InventoryStockArgs {
+        return InventoryStockArgs {
+            identifier: self.identifier.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryEditCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(short, long)]
+    /// Enter your parameters according to your specified schema in a name=value way
+    set: Vec<String>,
+
+    #[arg(long)]
+    /// Fails, if the entity is no longer at the given updated_at or revision (see --with-audit)
+    expect_version: Option<String>,
+}
+
+impl InventoryEditCliArgs {
+    fn to_lib(&self) -> InventoryEditArgs {
+        return InventoryEditArgs {
+            identifier: self.identifier.clone(),
+            set: self.set.clone(),
+            expect_version: self.expect_version.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryAddCliArgs {
+    /// Enter your parameters according to your specified schema in a name=value way
+    params: Vec<String>,
+
+    #[arg(short, long)]
+    /// Takes leading values without a name in the order of the schema columns, e.g. Widget 5 9.99
+    positional: bool,
+
+    #[arg(long)]
+    /// Keeps name=value pairs of columns unknown to the schema as metadata of the entity
+    allow_extra: bool,
+
+    #[arg(long)]
+    /// Only checks the entity like an addition does, without adding it
+    validate_only: bool,
+}
+
+impl InventoryAddCliArgs {
+    fn to_lib(&self) -> InventoryAddArgs {
+        return InventoryAddArgs {
+            params: self.params.clone(),
+            positional: self.positional,
+            allow_extra: self.allow_extra,
+            validate_only: self.validate_only,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventorySchemaRemoveCliArgs {
+    /// Name of the schema column
+    name: String,
+}
+
+impl InventorySchemaRemoveCliArgs {
+    fn to_lib(&self) -> InventorySchemaRemoveArgs {
+        return InventorySchemaRemoveArgs {
+            name: self.name.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct UserRegisterCliArgs {
+    /// Name of the user
+    name: String,
+    /// Password of the user
+    password: String,
+}
+
+impl UserRegisterCliArgs {
+    fn to_lib(&self) -> UserArgs {
+        return UserArgs {
+            name: self.name.clone(),
+            password: self.password.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct UserEditCliArgs {
+    /// Options to change into in option1=value1 option2=value2 syntax, e.g. password=<NEW>
+    options: Vec<String>,
+
+    #[arg(short, long)]
+    /// User to edit, requires writing the users table (Default: yourself)
+    username: Option<String>,
+
+    #[arg(long)]
+    /// Force the user to change the password when logging in next
+    must_change_password: bool,
+}
+
+impl UserEditCliArgs {
+    fn to_lib(&self) -> UserEditArgs {
+        return UserEditArgs {
+            options: self.options.clone(),
+            username: self.username.clone(),
+            must_change_password: self.must_change_password,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct UserPurgeCliArgs {
+    /// Name of the user to purge
+    username: String,
+}
+
+impl UserPurgeCliArgs {
+    fn to_lib(&self) -> UserPurgeArgs {
+        return UserPurgeArgs {
+            username: self.username.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct UserTokenIssueCliArgs {
+    #[arg(long, default_value = "30d")]
+    /// How long the token is valid in days (e.g. 30d), weeks (e.g. 2w) or years (e.g. 1y)
+    ttl: String,
+
+    #[arg(long = "scope")]
+    /// Scope in area:level notation limiting the commands of the token (e.g. inventory:write)
+    scopes: Vec<String>,
+}
+
+impl UserTokenIssueCliArgs {
+    fn to_lib(&self) -> UserTokenIssueArgs {
+        return UserTokenIssueArgs {
+            ttl: self.ttl.clone(),
+            scopes: self.scopes.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct UserTokenRevokeCliArgs {
+    /// Id of the token as listed by user token list
+    id: String,
+}
+
+impl UserTokenRevokeCliArgs {
+    fn to_lib(&self) -> UserTokenRevokeArgs {
+        return UserTokenRevokeArgs {
+            id: self.id.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct UserTotpDisableCliArgs {
+    #[arg(short, long)]
+    /// User whose two-factor authentication is disabled, e.g. after losing the authenticator app (Default: yourself)
+    username: Option<String>,
+}
+
+impl UserTotpDisableCliArgs {
+    fn to_lib(&self) -> UserTotpDisableArgs {
+        return UserTotpDisableArgs {
+            username: self.username.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserTotpCommands {
+    /// Generate a secret for an authenticator app and recovery codes, both shown only once
+    Enable,
+
+    /// Disable two-factor authentication
+    Disable(UserTotpDisableCliArgs),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserTokenCommands {
+    /// Issue a token, which is passed to --auth instead of username:password
+    Issue(UserTokenIssueCliArgs),
+
+    /// Replace the token logged in with by a new one
+    Refresh,
+
+    /// List your tokens
+    List,
+
+    /// Revoke a token, so that it can no longer be used
+    Revoke(UserTokenRevokeCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct InventorySchemaAlterCliArgs {
+    #[arg(short, long)]
+    /// Specifies the name as tag for your column. Only following values are allowed [a-z\-\_] (Letters from a-z (lowercase), - (dash) and _ (underscore))
+    name: String,
+
+    #[arg(long)]
+    /// Explicitly defines its display name for printing (Default: Parsed name value)
+    display_name: Option<String>,
+
+    #[arg(short, long)]
+    /// If set to true then one and only one kind of its value can be found in the system (Default: false)
+    unique: bool,
+
+    #[arg(short, long)]
+    /// Specifies the maximum length of this parameter (only applies to strings) (Default: 0)
+    max_length: Option<u32>,
+
+    #[arg(long)]
+    /// Specifies the minimum length of this parameter (only applies to strings) (Default: 0)
+    min_length: Option<u32>,
+
+    #[arg(long)]
+    /// Specifies the maximum value of this parameter (only applies to INT and REAL) (Default: 0)
+    max: Option<u32>,
+
+    #[arg(long)]
+    /// Specifies the minimum value of this parameter (only applies to INT and REAL) (Default: 0)
+    min: Option<u32>,
+
+    #[arg(long)]
+    /// Allows for value NULL to be inserted if no value is provided and no default is specified (Default: false)
+    nullable: Option<bool>,
+
+    #[arg(short, long, value_enum)]
+    /// Define the type of data stored in the column, choose between
+    ///     - TEXT for texts of arbritrary length that cannot be searched
+    ///     - VARCHAR that must have max_length specified for texts that can be searched
+    ///     - INT for whole numbers
+    ///     - REAL for real numbers
+    ///     - BOOL for boolean value, i.e. only values of true and false
+    column_type: ColumnTypeCli,
+
+    #[arg(short, long)]
+    /// The default value that will be used if no value is provided (Default: NULL)
+    ///     TIPS:
+    ///     - Use CURRENT_TIMESTAMP to automatically use the current Datetime as value
+    ///     - Use CURRENT_USER for the name of the user adding the entity
+    ///     - Use ENV(NAME) for the environment variable NAME of the invman adding the entity
+    default: Option<String>,
+
+    #[arg(long)]
+    /// Hint for external applications of how to display this column (Default: Empty String)
+    hint: Option<String>,
+
+    #[arg(long)]
+    /// For external applications as additional layout information (Default: Empty String)
+    layout: Option<String>,
+
+    #[arg(long)]
+    /// Flags a BOOL column that marks entities whose units are tracked by serial numbers (Default: false)
+    serialized: bool,
+}
+
+impl InventorySchemaAlterCliArgs {
+    fn to_lib(&self) -> InventorySchemaAlterArgs {
+        return InventorySchemaAlterArgs {
+            name: self.name.clone(),
+            display_name: self.display_name.clone(),
+            unique: self.unique,
+            max_length: self.max_length,
+            min_length: self.min_length,
+            max: self.max,
+            min: self.min,
+            nullable: self.nullable,
+            column_type: self.column_type.to_lib(),
+            default: self.default.clone(),
+            hint: self.hint.clone(),
+            layout: self.layout.clone(),
+            serialized: self.serialized,
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum InventoryCommands {
+    /// Add an entity to your inventory
+    Add(InventoryAddCliArgs),
+
+    /// List all entities stored in your inventory
+    List(InventoryListCliArgs),
+
+    /// Write all entities to stdout, e.g. to share them or load them into other tools
+    Export(InventoryExportCliArgs),
+
+    /// Add the entities of a CSV or Excel file, either all or none of them
+    Import(InventoryImportCliArgs),
+
+    /// Check a file like an import does and list the errors of all rows, without importing it
+    Validate(InventoryValidateCliArgs),
+
+    /// Count the entities stored in your inventory
+    Count(InventoryCountCliArgs),
+
+    /// Check whether an entity with the given identifier exists
+    Exists(InventoryExistsCliArgs),
+
+    /// List the unique values of a schema column
+    Distinct(InventoryDistinctCliArgs),
+
+    /// Render the label of an entity with a barcode in ZPL, or send it to the label printer
+    Label(InventoryLabelCliArgs),
+
+    #[command(subcommand)]
+    /// Change the schema in which your entities are stored
+    Schema(InventorySchemaCommands),
+
+    /// Edit an existing entity in your inventory
+    Edit(InventoryEditCliArgs),
+
+    /// Remove an entity from your inventory
+    Remove(InventoryRemoveCliArgs),
+
+    /// Find entities that are likely duplicates by the values of a column
+    Dedupe(InventoryDedupeCliArgs),
+
+    /// Merge a duplicate with its history, stock and attachments into another entity
+    Merge(InventoryMergeCliArgs),
+
+    /// Move old entities and their history into a separate archive file
+    Archive(InventoryArchiveCliArgs),
+
+    /// Lend an entity to someone
+    Checkout(InventoryCheckoutCliArgs),
+
+    /// Return a lent entity
+    Checkin(InventoryCheckinCliArgs),
+
+    /// Assign an entity to a user holding it
+    Assign(InventoryAssignCliArgs),
+
+    /// Take an entity back from the user holding it
+    Unassign(InventoryUnassignCliArgs),
+
+    /// Set the purchase date, price, warranty and useful life of an entity
+    Purchase(InventoryPurchaseCliArgs),
+
+    /// Attach a file, e.g. an invoice, to an entity
+    Attach(InventoryAttachCliArgs),
+
+    /// Leave a comment on an entity
+    Comment(InventoryCommentCliArgs),
+
+    #[command(subcommand)]
+    /// Manage the photos of your entities
+    Image(InventoryImageCommands),
+
+    #[command(subcommand)]
+    /// Manage one-off attributes of your entities that are not worth a schema column
+    Meta(InventoryMetaCommands),
+
+    #[command(subcommand)]
+    /// Create reports on purchases, e.g. for finance exports
+    Report(InventoryReportCommands),
+
+    /// Add stock of an entity to or take it from a location
+    Adjust(InventoryAdjustCliArgs),
+
+    /// Move stock of an entity from one location to another
+    Transfer(InventoryTransferCliArgs),
+
+    /// List the stock of an entity per location
+    Stock(InventoryStockCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TimezoneSetCliArgs {
+    /// IANA name of the timezone, e.g. Europe/Berlin or UTC
+    timezone: String,
+}
+
+impl TimezoneSetCliArgs {
+    fn to_lib(&self) -> TimezoneSetArgs {
+        return TimezoneSetArgs {
+            timezone: self.timezone.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct DisplayFormatSetCliArgs {
+    #[arg(long, default_value = ".")]
+    /// Separates the integer part of numbers from their fraction, e.g. ","
+    decimal_separator: String,
+
+    #[arg(long, default_value = "")]
+    /// Separates groups of three digits of numbers, e.g. ".", no grouping if not given
+    thousands_separator: String,
+
+    #[arg(long, default_value = "%Y-%m-%d %H:%M:%S %Z")]
+    /// Format of timestamps in strftime notation, e.g. "%d.%m.%Y %H:%M"
+    date_format: String,
+}
+
+impl DisplayFormatSetCliArgs {
+    fn to_lib(&self) -> DisplayFormatSetArgs {
+        return DisplayFormatSetArgs {
+            format: DisplayFormat {
+                decimal_separator: self.decimal_separator.clone(),
+                thousands_separator: self.thousands_separator.clone(),
+                date_format: self.date_format.clone(),
+            },
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct LabelPrinterSetCliArgs {
+    #[arg(required_unless_present = "remove")]
+    /// Address of the printer in host or host:port notation (Default port: 9100)
+    printer: Option<String>,
+
+    #[arg(short, long, conflicts_with = "printer")]
+    /// Remove the label printer
+    remove: bool,
+}
+
+impl LabelPrinterSetCliArgs {
+    fn to_lib(&self) -> LabelPrinterSetArgs {
+        return LabelPrinterSetArgs {
+            printer: self.printer.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// Set the timezone the plain output displays timestamps in
+    Timezone(TimezoneSetCliArgs),
+
+    /// Set how the plain output formats numbers and timestamps
+    Format(DisplayFormatSetCliArgs),
+
+    /// Set the network printer labels are sent to
+    LabelPrinter(LabelPrinterSetCliArgs),
+
+    #[command(subcommand)]
+    /// Manage the exchange rates amounts in other currencies are converted with
+    Rates(RatesCommands),
+
+    #[command(subcommand)]
+    /// Manage the reason codes stock adjustments and removals are given
+    Reasons(ReasonCommands),
+
+    #[command(subcommand)]
+    /// Configure the directory users without a password of their own log in with
+    Ldap(LdapCommands),
+
+    #[command(subcommand)]
+    /// Configure the rules passwords have to follow
+    PasswordPolicy(PasswordPolicyCommands),
+
+    /// Allow or stop users registering themselves
+    Registration(RegistrationSetCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ExchangeRateSetCliArgs {
+    /// Currencies in FROM:TO notation, e.g. EUR:USD
+    pair: String,
+
+    /// What one of the first currency is worth in the second, e.g. 1.09
+    rate: f64,
+}
+
+impl ExchangeRateSetCliArgs {
+    fn to_lib(&self) -> ExchangeRateSetArgs {
+        return ExchangeRateSetArgs {
+            pair: self.pair.clone(),
+            rate: self.rate,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ExchangeRateRemoveCliArgs {
+    /// Currencies in FROM:TO notation, e.g. EUR:USD
+    pair: String,
+}
+
+impl ExchangeRateRemoveCliArgs {
+    fn to_lib(&self) -> ExchangeRateRemoveArgs {
+        return ExchangeRateRemoveArgs {
+            pair: self.pair.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ExchangeRateListCliArgs;
+
+impl ExchangeRateListCliArgs {
+    fn to_lib(&self) -> ExchangeRateListArgs {
+        return ExchangeRateListArgs;
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BaseCurrencySetCliArgs {
+    /// Code of the currency, e.g. EUR
+    currency: String,
+}
+
+impl BaseCurrencySetCliArgs {
+    fn to_lib(&self) -> BaseCurrencySetArgs {
+        return BaseCurrencySetArgs {
+            currency: self.currency.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ReasonCodeAddCliArgs {
+    /// The reason code, a single word, e.g. damage
+    code: String,
+}
+
+impl ReasonCodeAddCliArgs {
+    fn to_lib(&self) -> ReasonCodeAddArgs {
+        return ReasonCodeAddArgs {
+            code: self.code.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ReasonCodeRemoveCliArgs {
+    /// The reason code
+    code: String,
+}
+
+impl ReasonCodeRemoveCliArgs {
+    fn to_lib(&self) -> ReasonCodeRemoveArgs {
+        return ReasonCodeRemoveArgs {
+            code: self.code.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct PasswordPolicySetCliArgs {
+    #[arg(long, default_value_t = 0)]
+    /// Minimum number of characters
+    min_length: u32,
+
+    #[arg(long, default_value_t = 0)]
+    /// How many of lowercase letters, uppercase letters, digits and other characters are required
+    min_classes: u32,
+
+    #[arg(long = "ban")]
+    /// Password to refuse regardless of case (Repeatable)
+    banned: Vec<String>,
+
+    #[arg(long)]
+    /// File of passwords to refuse, one per line
+    ban_file: Option<String>,
+
+    #[arg(long)]
+    /// Duration after which passwords have to be changed, e.g. 90d (Default: never)
+    max_age: Option<String>,
+}
+
+impl PasswordPolicySetCliArgs {
+    fn to_lib(&self) -> PasswordPolicySetArgs {
+        return PasswordPolicySetArgs {
+            min_length: self.min_length,
+            min_classes: self.min_classes,
+            banned: self.banned.clone(),
+            banned_file: self.ban_file.clone(),
+            max_age: self.max_age.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PasswordPolicyCommands {
+    /// Replace the password policy, rules that are not given are turned off
+    Set(PasswordPolicySetCliArgs),
+
+    /// Show the password policy
+    Show,
+}
+
+#[derive(Args, Debug)]
+pub struct LdapSetCliArgs {
+    #[arg(long)]
+    /// URL of the directory, ldaps://host[:port] or ldap://host[:port] upgraded with StartTLS
+    url: String,
+
+    #[arg(long)]
+    /// DN users bind as, {username} is replaced, e.g. {username}@corp.example.com
+    bind_dn: String,
+
+    #[arg(long)]
+    /// Where users are searched for to read their groups, e.g. dc=corp,dc=example,dc=com
+    base_dn: String,
+
+    #[arg(long, default_value = "sAMAccountName")]
+    /// Attribute holding the username, e.g. uid for OpenLDAP
+    user_attribute: String,
+
+    #[arg(long = "group")]
+    /// Group mapped to a role in GROUP=ROLE notation, the first matching one wins (Repeatable)
+    groups: Vec<String>,
+
+    #[arg(long)]
+    /// Role of users that are in none of the groups, without it they cannot log in
+    default_role: Option<String>,
+
+    #[arg(long)]
+    /// PEM file with certificates to trust in addition to the public ones
+    ca_file: Option<String>,
+}
+
+impl LdapSetCliArgs {
+    fn to_lib(&self) -> LdapSetArgs {
+        return LdapSetArgs {
+            url: self.url.clone(),
+            bind_dn: self.bind_dn.clone(),
+            base_dn: self.base_dn.clone(),
+            user_attribute: self.user_attribute.clone(),
+            groups: self.groups.clone(),
+            default_role: self.default_role.clone(),
+            ca_file: self.ca_file.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LdapCommands {
+    /// Set the directory and how its groups map to roles
+    Set(LdapSetCliArgs),
+
+    /// Show the LDAP settings
+    Show,
+
+    /// Remove the LDAP settings, users without a password of their own can no longer log in
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReasonCommands {
+    /// Add a reason code
+    Add(ReasonCodeAddCliArgs),
+
+    /// Remove a reason code, once none is left a reason is no longer required
+    Remove(ReasonCodeRemoveCliArgs),
+
+    /// List the reason codes
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RatesCommands {
+    /// Set the exchange rate of a pair of currencies
+    Set(ExchangeRateSetCliArgs),
+
+    /// Remove the exchange rate of a pair of currencies
+    Remove(ExchangeRateRemoveCliArgs),
+
+    /// List the exchange rates and the base currency
+    List(ExchangeRateListCliArgs),
+
+    /// Set the currency of amounts given without one, which reports are converted into
+    Base(BaseCurrencySetCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct SupplierAddCliArgs {
+    #[arg(short, long)]
+    /// Name of the supplier, must be unique
+    name: String,
+
+    #[arg(short, long)]
+    /// How to get in touch with the supplier, e.g. an email address or phone number
+    contact: Option<String>,
+
+    #[arg(short, long)]
+    /// Days it usually takes from ordering until the delivery arrives
+    lead_time: Option<u32>,
+}
+
+impl SupplierAddCliArgs {
+    fn to_lib(&self) -> SupplierAddArgs {
+        return SupplierAddArgs {
+            name: self.name.clone(),
+            contact: self.contact.clone(),
+            lead_time_days: self.lead_time,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SupplierListCliArgs;
+
+impl SupplierListCliArgs {
+    fn to_lib(&self) -> SupplierListArgs {
+        return SupplierListArgs;
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SupplierEditCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific supplier
+    identifier: String,
+
+    #[arg(short, long)]
+    /// New name of the supplier
+    name: Option<String>,
+
+    #[arg(short, long)]
+    /// New contact of the supplier
+    contact: Option<String>,
+
+    #[arg(short, long)]
+    /// New lead time of the supplier in days
+    lead_time: Option<u32>,
+}
+
+impl SupplierEditCliArgs {
+    fn to_lib(&self) -> SupplierEditArgs {
+        return SupplierEditArgs {
+            identifier: self.identifier.clone(),
+            name: self.name.clone(),
+            contact: self.contact.clone(),
+            lead_time_days: self.lead_time,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SupplierRemoveCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific supplier
+    identifier: String,
+}
+
+impl SupplierRemoveCliArgs {
+    fn to_lib(&self) -> SupplierRemoveArgs {
+        return SupplierRemoveArgs {
+            identifier: self.identifier.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SupplierLinkCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific supplier
+    identifier: String,
+
+    #[arg(short, long)]
+    /// The identifier of the inventory entity
+    entity: String,
+}
+
+impl SupplierLinkCliArgs {
+    fn to_lib(&self) -> SupplierLinkArgs {
+        return SupplierLinkArgs {
+            identifier: self.identifier.clone(),
+            entity: self.entity.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CategoryCreateCliArgs {
+    /// Path of the category, its ancestors' names separated by slashes, e.g. electronics/laptops
+    path: String,
+}
+
+impl CategoryCreateCliArgs {
+    fn to_lib(&self) -> CategoryCreateArgs {
+        return CategoryCreateArgs {
+            path: self.path.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CategoryListCliArgs;
+
+impl CategoryListCliArgs {
+    fn to_lib(&self) -> CategoryListArgs {
+        return CategoryListArgs;
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CategoryRemoveCliArgs {
+    /// Path of the category
+    path: String,
+}
+
+impl CategoryRemoveCliArgs {
+    fn to_lib(&self) -> CategoryRemoveArgs {
+        return CategoryRemoveArgs {
+            path: self.path.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CategoryAssignCliArgs {
+    #[arg(short, long)]
+    /// The identifier of the inventory entity
+    identifier: String,
+
+    /// Path of the category
+    path: String,
+}
+
+impl CategoryAssignCliArgs {
+    fn to_lib(&self) -> CategoryAssignArgs {
+        return CategoryAssignArgs {
+            identifier: self.identifier.clone(),
+            path: self.path.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CategoryUnassignCliArgs {
+    #[arg(short, long)]
+    /// The identifier of the inventory entity
+    identifier: String,
+}
+
+impl CategoryUnassignCliArgs {
+    fn to_lib(&self) -> CategoryUnassignArgs {
+        return CategoryUnassignArgs {
+            identifier: self.identifier.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LoanCommands {
+    /// List all entities that are currently checked out
+    List,
+
+    /// List all checked out entities that are past their due date
+    Overdue,
+}
+
+#[derive(Args, Debug)]
+pub struct BomDefineCliArgs {
+    /// The identifier of the assembly entity
+    assembly: String,
+
+    #[arg(short, long = "component", num_args = 2, value_names = ["id=ID", "qty=QUANTITY"], required = true)]
+    /// Component the assembly is built from, may be given multiple times
+    components: Vec<String>,
+}
+
+impl BomDefineCliArgs {
+    fn to_lib(&self) -> BomDefineArgs {
+        return BomDefineArgs {
+            assembly: self.assembly.clone(),
+            components: self.components.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BomShowCliArgs {
+    /// The identifier of the assembly entity
+    assembly: String,
+}
+
+impl BomShowCliArgs {
+    fn to_lib(&self) -> BomShowArgs {
+        return BomShowArgs {
+            assembly: self.assembly.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BomBuildCliArgs {
+    /// The identifier of the assembly entity
+    assembly: String,
+
+    #[arg(short, long)]
+    /// Quantity of the assembly to build
+    qty: i64,
+
+    #[arg(short, long)]
+    /// Location whose stock is used for the build
+    location: String,
+}
+
+impl BomBuildCliArgs {
+    fn to_lib(&self) -> BomBuildArgs {
+        return BomBuildArgs {
+            assembly: self.assembly.clone(),
+            quantity: self.qty,
+            location: self.location.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct KitDefineCliArgs {
+    /// Name of the kit
+    name: String,
+
+    #[arg(short, long = "item", num_args = 2, value_names = ["ID", "xQUANTITY"], required = true)]
+    /// Item the kit is composed of together with its quantity, e.g. 12 x2, may be given multiple times
+    items: Vec<String>,
+}
+
+impl KitDefineCliArgs {
+    fn to_lib(&self) -> KitDefineArgs {
+        return KitDefineArgs {
+            name: self.name.clone(),
+            items: self.items.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct KitListCliArgs;
+
+impl KitListCliArgs {
+    fn to_lib(&self) -> KitListArgs {
+        return KitListArgs;
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct KitShowCliArgs {
+    /// Name of the kit
+    name: String,
+}
+
+impl KitShowCliArgs {
+    fn to_lib(&self) -> KitShowArgs {
+        return KitShowArgs {
+            name: self.name.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct KitRemoveCliArgs {
+    /// Name of the kit
+    name: String,
+}
+
+impl KitRemoveCliArgs {
+    fn to_lib(&self) -> KitRemoveArgs {
+        return KitRemoveArgs {
+            name: self.name.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct KitPickCliArgs {
+    /// Name of the kit
+    name: String,
+
+    #[arg(short, long)]
+    /// Quantity of kits to pick
+    qty: i64,
+
+    #[arg(short, long)]
+    /// Location whose stock the items are taken from
+    location: String,
+}
+
+impl KitPickCliArgs {
+    fn to_lib(&self) -> KitPickArgs {
+        return KitPickArgs {
+            name: self.name.clone(),
+            quantity: self.qty,
+            location: self.location.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KitCommands {
+    /// Define the items a kit is composed of
+    Define(KitDefineCliArgs),
+
+    /// List all kits with their items
+    List(KitListCliArgs),
+
+    /// Show the items of a kit
+    Show(KitShowCliArgs),
+
+    /// Remove a kit
+    Remove(KitRemoveCliArgs),
+
+    /// Take the items of kits out of the stock at a location
+    Pick(KitPickCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct StocktakeStartCliArgs {
+    #[arg(short, long)]
+    /// Location whose stock is counted
+    location: String,
+}
+
+impl StocktakeStartCliArgs {
+    fn to_lib(&self) -> StocktakeStartArgs {
+        return StocktakeStartArgs {
+            location: self.location.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct StocktakeRecordCliArgs {
+    #[arg(short, long)]
+    /// The identifier of the counted inventory entity
+    identifier: String,
+
+    #[arg(short, long)]
+    /// Quantity of the entity found at the location
+    counted: i64,
+}
+
+impl StocktakeRecordCliArgs {
+    fn to_lib(&self) -> StocktakeRecordArgs {
+        return StocktakeRecordArgs {
+            identifier: self.identifier.clone(),
+            counted: self.counted,
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StocktakeCommands {
+    /// Start counting the stock at a location
+    Start(StocktakeStartCliArgs),
+
+    /// Record the counted quantity of an entity
+    Record(StocktakeRecordCliArgs),
+
+    /// Show the variances of the counts recorded so far
+    Status,
+
+    /// Adjust the stock by the variances of the counts and report them
+    Close,
+
+    /// Discard the open stocktake without adjusting the stock
+    Cancel,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BomCommands {
+    /// Define the components an assembly is built from
+    Define(BomDefineCliArgs),
+
+    /// Show the components of an assembly
+    Show(BomShowCliArgs),
+
+    /// Build an assembly from the stock of its components
+    Build(BomBuildCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct MaintenanceScheduleCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(short, long)]
+    /// Interval in days (e.g. 90d) or weeks (e.g. 2w) at which the task recurs
+    every: String,
+
+    #[arg(short, long)]
+    /// Name of the maintenance task
+    task: String,
+}
+
+impl MaintenanceScheduleCliArgs {
+    fn to_lib(&self) -> MaintenanceScheduleArgs {
+        return MaintenanceScheduleArgs {
+            identifier: self.identifier.clone(),
+            every: self.every.clone(),
+            task: self.task.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct MaintenanceDoneCliArgs {
+    #[arg(short, long)]
+    /// The identifier used to target a specific entity
+    identifier: String,
+
+    #[arg(short, long)]
+    /// Name of the completed maintenance task
+    task: String,
+}
+
+impl MaintenanceDoneCliArgs {
+    fn to_lib(&self) -> MaintenanceDoneArgs {
+        return MaintenanceDoneArgs {
+            identifier: self.identifier.clone(),
+            task: self.task.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct MaintenanceDueCliArgs {
+    #[arg(short, long, default_value = "30d")]
+    /// Also list tasks that become due within the given days (e.g. 30d) or weeks (e.g. 4w)
+    within: String,
+}
+
+impl MaintenanceDueCliArgs {
+    fn to_lib(&self) -> MaintenanceDueArgs {
+        return MaintenanceDueArgs {
+            within: self.within.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MaintenanceCommands {
+    /// Schedule a recurring maintenance task for an entity
+    Schedule(MaintenanceScheduleCliArgs),
+
+    /// List overdue and upcoming maintenance tasks
+    Due(MaintenanceDueCliArgs),
+
+    /// Record the completion of a maintenance task
+    Done(MaintenanceDoneCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RetentionSetCliArgs {
+    #[arg(long)]
+    /// Delete soft-deleted entities for good after the given duration, e.g. 180d (Default: Never)
+    purge_deleted_after: Option<String>,
+
+    #[arg(long)]
+    /// Delete events from the event log after the given duration, e.g. 1y (Default: Never)
+    prune_events_after: Option<String>,
+}
+
+impl RetentionSetCliArgs {
+    fn to_lib(&self) -> RetentionSetArgs {
+        return RetentionSetArgs {
+            purge_deleted_after: self.purge_deleted_after.clone(),
+            prune_events_after: self.prune_events_after.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RetentionCommands {
+    /// Replace the retention rules
+    Set(RetentionSetCliArgs),
+
+    /// Show the current retention rules
+    Show,
+}
+
+#[derive(Args, Debug)]
+pub struct RetentionApplyCliArgs {
+    #[arg(long)]
+    /// Only report what would be removed
+    dry_run: bool,
+}
+
+impl RetentionApplyCliArgs {
+    fn to_lib(&self) -> RetentionApplyArgs {
+        return RetentionApplyArgs {
+            dry_run: self.dry_run,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct HistoryCompactCliArgs {
+    #[arg(long, default_value_t = 50)]
+    /// Amount of the latest revisions of each entity that are kept as they are
+    keep_last: u32,
+
+    #[arg(long, default_value = "1y")]
+    /// Only revisions older than the given duration, e.g. 1y, are collapsed
+    older_than: String,
+}
+
+impl HistoryCompactCliArgs {
+    fn to_lib(&self) -> HistoryCompactArgs {
+        return HistoryCompactArgs {
+            keep_last: self.keep_last,
+            older_than: self.older_than.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct DoctorCliArgs {
+    #[arg(long)]
+    /// Apply the repairs that do not lose any data
+    fix: bool,
+}
+
+impl DoctorCliArgs {
+    fn to_lib(&self) -> DoctorArgs {
+        return DoctorArgs { fix: self.fix };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct DumpCliArgs {
+    /// File the dump is written to, which must not exist yet
+    file: String,
+
+    #[arg(long)]
+    /// Compress the dump with gzip
+    gzip: bool,
+
+    #[arg(long)]
+    /// Write the SHA-256 checksum of the dump into a sidecar file, verified by db load
+    sha256: bool,
+}
+
+impl DumpCliArgs {
+    fn to_lib(&self) -> DumpArgs {
+        return DumpArgs {
+            file: self.file.clone(),
+            gzip: self.gzip,
+            sha256: self.sha256,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct LoadCliArgs {
+    /// File of a dump created with db dump
+    file: String,
+}
+
+impl LoadCliArgs {
+    fn to_lib(&self) -> LoadArgs {
+        return LoadArgs {
+            file: self.file.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FixtureCommands {
+    /// Save the schema, the users and selected entities into a YAML file
+    Save(FixtureSaveCliArgs),
+
+    /// Recreate the content of a fixture, the database must be empty
+    Load(FixtureLoadCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct FixtureSaveCliArgs {
+    /// YAML file the fixture is written to
+    file: String,
+
+    #[arg(short, long)]
+    /// Condition selecting the saved entities, like the one of inventory list --raw (Default: All)
+    raw: Option<String>,
+
+    #[arg(short, long)]
+    /// Parameters that are passed with the raw SQL string, as name=value for named placeholders like :name
+    params: Vec<String>,
+
+    #[arg(short, long)]
+    /// Maximum number of entities saved
+    limit: Option<i32>,
+}
+
+impl FixtureSaveCliArgs {
+    fn to_lib(&self) -> FixtureSaveArgs {
+        return FixtureSaveArgs {
+            file: self.file.clone(),
+            raw: self.raw.clone(),
+            params: self.params.clone(),
+            limit: self.limit,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct FixtureLoadCliArgs {
+    /// YAML file of a fixture, saved with fixture save or written by hand
+    file: String,
+}
+
+impl FixtureLoadCliArgs {
+    fn to_lib(&self) -> FixtureLoadArgs {
+        return FixtureLoadArgs {
+            file: self.file.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct MergeCliArgs {
+    /// Storage file of the other invman database
+    file: String,
+
+    #[arg(long, value_enum, default_value = "fail")]
+    /// What happens to entities that share a unique value with an existing entity
+    strategy: MergeStrategyCli,
+}
+
+impl MergeCliArgs {
+    fn to_lib(&self) -> MergeArgs {
+        return MergeArgs {
+            file: self.file.clone(),
+            strategy: self.strategy.to_lib(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ServeCliArgs {
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    /// Address to listen on, in host:port notation
+    listen: String,
+
+    #[arg(short, long, default_value_t = 4)]
+    /// Number of requests handled at the same time
+    workers: usize,
+
+    #[arg(long, requires = "tls_key")]
+    /// PEM file with the certificate (chain) to serve HTTPS with
+    tls_cert: Option<String>,
+
+    #[arg(long, requires = "tls_cert")]
+    /// PEM file with the private key of --tls-cert
+    tls_key: Option<String>,
+
+    #[arg(long, requires = "tls_cert")]
+    /// PEM file with the CA certificates, one of which must have issued the certificate of each client
+    tls_client_ca: Option<String>,
+
+    #[arg(long)]
+    /// Serve the InventoryService of proto/invman/v1/inventory.proto over gRPC instead
+    grpc: bool,
+}
+
+impl ServeCliArgs {
+    fn to_lib(&self, database: &str, read_only: bool, auth: Option<&str>) -> ServeArgs {
+        return ServeArgs {
+            database: database.into(),
+            listen: self.listen.clone(),
+            workers: self.workers,
+            read_only,
+            tls: match (&self.tls_cert, &self.tls_key) {
+                (Some(certificate), Some(key)) => Some(ServeTls {
+                    certificate: certificate.clone(),
+                    key: key.clone(),
+                    client_ca: self.tls_client_ca.clone(),
+                }),
+                _ => None,
+            },
+            grpc: self.grpc,
+            auth: auth.map(String::from),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InitCliArgs {
+    #[arg(long)]
+    /// Username of the admin to create
+    admin_user: String,
+
+    #[arg(long)]
+    /// Password of the admin (Default: Read from stdin)
+    admin_password: Option<String>,
+
+    #[arg(long)]
+    /// Stop others from registering, so that only admins add users
+    disable_registration: bool,
+}
+
+impl InitCliArgs {
+    fn to_lib(&self) -> InitArgs {
+        return InitArgs {
+            admin_user: self.admin_user.clone(),
+            admin_password: self.admin_password.clone(),
+            disable_registration: self.disable_registration,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RegistrationSetCliArgs {
+    #[arg(action = clap::ArgAction::Set)]
+    /// Whether anyone may register, admins can always add users
+    allow: bool,
+}
+
+impl RegistrationSetCliArgs {
+    fn to_lib(&self) -> RegistrationSetArgs {
+        return RegistrationSetArgs { allow: self.allow };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DemoCommands {
+    /// Install a sample schema and fill it with fake entities to explore invman with
+    Seed(DemoSeedCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct DemoSeedCliArgs {
+    #[arg(short, long, default_value_t = 100)]
+    /// Number of entities that are generated
+    entities: u32,
+
+    #[arg(long)]
+    /// Seed of the generated data, the same seed generating the same entities (Default: Random)
+    seed: Option<u64>,
+}
+
+impl DemoSeedCliArgs {
+    fn to_lib(&self) -> DemoSeedArgs {
+        return DemoSeedArgs {
+            entities: self.entities,
+            seed: self.seed,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BenchCliArgs {
+    #[arg(short, long, default_value_t = 10000)]
+    /// Number of entities that are added, listed and edited
+    entities: u32,
+
+    #[arg(short, long, default_value_t = 10)]
+    /// Number of columns of the inventory schema
+    schema_cols: u32,
+}
+
+impl BenchCliArgs {
+    fn to_lib(&self) -> BenchArgs {
+        return BenchArgs {
+            entities: self.entities,
+            schema_cols: self.schema_cols,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SearchCliArgs {
+    /// Text to search for, case insensitive
+    text: String,
+
+    #[arg(short, long, default_value_t = 50)]
+    /// Maximum number of matches per group
+    limit: u32,
+}
+
+impl SearchCliArgs {
+    fn to_lib(&self) -> SearchArgs {
+        return SearchArgs {
+            text: self.text.clone(),
+            limit: self.limit,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BatchCliArgs {
+    /// Script with one command per line (Default: Read from stdin)
+    file: Option<String>,
+}
+
+impl BatchCliArgs {
+    fn to_lib(&self) -> BatchArgs {
+        return BatchArgs {
+            file: self.file.clone().filter(|e| e != "-"),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SyncPushCliArgs {
+    /// Storage file of the central database
+    central: String,
+}
+
+impl SyncPushCliArgs {
+    fn to_lib(&self) -> SyncPushArgs {
+        return SyncPushArgs {
+            central: self.central.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SmtpSetCliArgs {
+    #[arg(long)]
+    /// Host name of the SMTP server
+    host: String,
+
+    #[arg(long)]
+    /// Port of the SMTP server (Default: Depends on the security)
+    port: Option<u16>,
+
+    #[arg(long, value_enum, default_value = "starttls")]
+    /// How the connection to the server is encrypted
+    security: SmtpSecurityCli,
+
+    #[arg(long)]
+    /// Username to log in to the server
+    username: Option<String>,
+
+    #[arg(long)]
+    /// Password to log in to the server, stored as is in the database
+    password: Option<String>,
+
+    #[arg(long)]
+    /// Sender address of the emails
+    from: String,
+}
+
+impl SmtpSetCliArgs {
+    fn to_lib(&self) -> SmtpSetArgs {
+        return SmtpSetArgs {
+            settings: Some(SmtpSettings {
+                host: self.host.clone(),
+                port: self.port,
+                security: self.security.to_lib(),
+                username: self.username.clone(),
+                password: self.password.clone(),
+                from: self.from.clone(),
+            }),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SmtpCommands {
+    /// Set the SMTP server used to send emails
+    Set(SmtpSetCliArgs),
+
+    /// Show the SMTP settings without the password
+    Show,
+
+    /// Remove the SMTP settings, which stops sending emails
+    Clear,
+}
+
+#[derive(Args, Debug)]
+pub struct NotifyRuleAddCliArgs {
+    #[arg(long)]
+    /// Event to be notified about, e.g. inventory-remove
+    event: String,
+
+    #[arg(long, value_enum, default_value = "email")]
+    /// How the recipient is notified
+    channel: NotifyChannelCli,
+
+    #[arg(long)]
+    /// Email address of the recipient, or URL of the webhook
+    to: String,
+
+    #[arg(long)]
+    /// Only notify once the changed entity starts to match, e.g. quantity<5
+    condition: Option<String>,
+}
+
+impl NotifyRuleAddCliArgs {
+    fn to_lib(&self) -> NotifyRuleAddArgs {
+        return NotifyRuleAddArgs {
+            event: self.event.clone(),
+            channel: self.channel.to_lib(),
+            to: self.to.clone(),
+            condition: self.condition.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct NotifyRuleRemoveCliArgs {
+    /// Identifier of the rule
+    id: u32,
+}
+
+impl NotifyRuleRemoveCliArgs {
+    fn to_lib(&self) -> NotifyRuleRemoveArgs {
+        return NotifyRuleRemoveArgs { id: self.id };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NotifyRuleCommands {
+    /// Notify a recipient whenever the event is recorded
+    Add(NotifyRuleAddCliArgs),
+
+    /// List all rules
+    List,
+
+    /// Remove a rule
+    Remove(NotifyRuleRemoveCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct NotifyRunCliArgs {
+    #[arg(long)]
+    /// Only report who would be notified of which event
+    dry_run: bool,
+}
+
+impl NotifyRunCliArgs {
+    fn to_lib(&self) -> NotifyRunArgs {
+        return NotifyRunArgs {
+            dry_run: self.dry_run,
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NotifyCommands {
+    #[command(subcommand)]
+    /// Configure the server that emails are sent through
+    Smtp(SmtpCommands),
+
+    #[command(subcommand)]
+    /// Configure who is notified of which events
+    Rule(NotifyRuleCommands),
+
+    /// Send the notifications of the events recorded since the last run
+    Run(NotifyRunCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct SchedulerAddCliArgs {
+    #[arg(long)]
+    /// Unique name of the job
+    name: String,
+
+    #[arg(long)]
+    /// When the job runs as cron expression, e.g. "0 2 * * *" for every night at 2 o'clock
+    schedule: String,
+
+    #[arg(long)]
+    /// Command to run without invman and its options, {date} is replaced by the current date
+    command: String,
+
+    #[arg(long)]
+    /// Email the output of each run to the given address
+    mail_to: Option<String>,
+}
+
+impl SchedulerAddCliArgs {
+    fn to_lib(&self) -> SchedulerAddArgs {
+        return SchedulerAddArgs {
+            name: self.name.clone(),
+            schedule: self.schedule.clone(),
+            command: self.command.clone(),
+            mail_to: self.mail_to.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SchedulerRemoveCliArgs {
+    /// Name of the job
+    name: String,
+}
+
+impl SchedulerRemoveCliArgs {
+    fn to_lib(&self) -> SchedulerRemoveArgs {
+        return SchedulerRemoveArgs {
+            name: self.name.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RuleAddCliArgs {
+    #[arg(long)]
+    /// Unique name of the rule
+    name: String,
+
+    #[arg(long)]
+    /// Event the rule applies to: inventory-add, inventory-edit or inventory-remove
+    event: String,
+
+    #[arg(long)]
+    /// Only apply once the entity matches, e.g. "quantity<=0"
+    condition: Option<String>,
+
+    #[arg(long, num_args = 1..)]
+    /// Values to set on the entity in name=value syntax
+    set: Vec<String>,
+
+    #[arg(long, value_enum, default_value_t = NotifyChannelCli::Email)]
+    /// Channel used to notify
+    notify_channel: NotifyChannelCli,
+
+    #[arg(long)]
+    /// Email address or webhook URL to notify
+    notify: Option<String>,
+}
+
+impl RuleAddCliArgs {
+    fn to_lib(&self) -> RuleAddArgs {
+        return RuleAddArgs {
+            name: self.name.clone(),
+            event: self.event.clone(),
+            condition: self.condition.clone(),
+            set: self.set.clone(),
+            notify_channel: self.notify_channel.to_lib(),
+            notify: self.notify.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RuleRemoveCliArgs {
+    /// Name of the rule
+    name: String,
+}
+
+impl RuleRemoveCliArgs {
+    fn to_lib(&self) -> RuleRemoveArgs {
+        return RuleRemoveArgs {
+            name: self.name.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct OrgCreateCliArgs {
+    /// Name of the organization
+    name: String,
+}
+
+impl OrgCreateCliArgs {
+    fn to_lib(&self) -> OrgCreateArgs {
+        return OrgCreateArgs {
+            name: self.name.clone(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct OrgInviteCliArgs {
+    /// Name of the organization
+    org: String,
+
+    /// Name of the user joining the organization
+    username: String,
+}
+
+impl OrgInviteCliArgs {
+    fn to_lib(&self) -> OrgInviteArgs {
+        return OrgInviteArgs {
+            org: self.org.clone(),
+            username: self.username.clone(),
+        };
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum OwnershipPolicyCli {
+    /// Any entity, regardless of who added it
+    All,
+    /// Only the entities added by the user
+    Own,
+}
+
+impl OwnershipPolicyCli {
+    fn to_lib(self) -> OwnershipPolicy {
+        return match self {
+            OwnershipPolicyCli::All => OwnershipPolicy::All,
+            OwnershipPolicyCli::Own => OwnershipPolicy::Own,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RoleOwnershipCliArgs {
+    /// Name of the role, e.g. guest
+    role: String,
+
+    #[arg(value_enum)]
+    /// Which entities the users of the role may edit and remove
+    ownership: OwnershipPolicyCli,
+}
+
+impl RoleOwnershipCliArgs {
+    fn to_lib(&self) -> RoleOwnershipArgs {
+        return RoleOwnershipArgs {
+            role: self.role.clone(),
+            ownership: self.ownership.to_lib(),
+        };
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PermissionModeCli {
+    /// Listing the column, which leaves it out of the output
+    Read,
+    /// Setting the column when adding or editing entities
+    Write,
+}
+
+impl PermissionModeCli {
+    fn to_lib(self) -> PermissionMode {
+        return match self {
+            PermissionModeCli::Read => PermissionMode::Read,
+            PermissionModeCli::Write => PermissionMode::Write,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RoleColumnCliArgs {
+    /// Name of the role, e.g. guest
+    role: String,
+
+    /// Column of the inventory schema
+    column: String,
+
+    #[arg(value_enum)]
+    /// Whether reading or writing the column is concerned
+    mode: PermissionModeCli,
+}
+
+impl RoleColumnCliArgs {
+    fn to_lib(&self, deny: bool) -> RoleDenyArgs {
+        return RoleDenyArgs {
+            role: self.role.clone(),
+            column: self.column.clone(),
+            mode: self.mode.to_lib(),
+            deny,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct EventsWatchCliArgs {
+    #[arg(long)]
+    /// Only write events of the given names, e.g. inventory-add (Default: all events)
+    event: Vec<String>,
+
+    #[arg(long)]
+    /// Start after the event of the given id instead of with new events
+    after: Option<u32>,
+}
+
+impl EventsWatchCliArgs {
+    fn to_lib(&self) -> EventsWatchArgs {
+        return EventsWatchArgs {
+            events: self.event.clone(),
+            after_event: self.after,
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EventCommands {
+    /// Write events as lines of JSON as soon as they are recorded, until stopped
+    Watch(EventsWatchCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct PluginReportCliArgs {
+    /// Name of the plugin, i.e. its file name without .wasm
+    name: String,
+}
+
+impl PluginReportCliArgs {
+    fn to_lib(&self) -> PluginReportArgs {
+        return PluginReportArgs {
+            name: self.name.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PluginCommands {
+    /// List the plugins and the hooks they implement
+    List,
+
+    /// Create the custom report of a plugin from all entities
+    Report(PluginReportCliArgs),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum HookFailurePolicyCli {
+    Fail,
+    Warn,
+    Ignore,
+}
+
+impl HookFailurePolicyCli {
+    fn to_lib(self) -> HookFailurePolicy {
+        return match self {
+            HookFailurePolicyCli::Fail => HookFailurePolicy::Fail,
+            HookFailurePolicyCli::Warn => HookFailurePolicy::Warn,
+            HookFailurePolicyCli::Ignore => HookFailurePolicy::Ignore,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct HookSetCliArgs {
+    /// When the hook runs: pre-<command> (e.g. pre-inventory-add) or post-<event> (e.g. post-inventory-add)
+    name: String,
+
+    /// Command line run by the shell, receiving the command or event as JSON on stdin
+    command: String,
+
+    #[arg(long, value_enum, default_value_t = HookFailurePolicyCli::Fail)]
+    /// What happens when the hook exits with an error
+    on_failure: HookFailurePolicyCli,
+}
+
+impl HookSetCliArgs {
+    fn to_lib(&self) -> HookSetArgs {
+        return HookSetArgs {
+            name: self.name.clone(),
+            command: self.command.clone(),
+            on_failure: self.on_failure.to_lib(),
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct HookRemoveCliArgs {
+    /// Name of the hook
+    name: String,
+}
+
+impl HookRemoveCliArgs {
+    fn to_lib(&self) -> HookRemoveArgs {
+        return HookRemoveArgs {
+            name: self.name.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HookCommands {
+    /// Set the command run by a hook, replacing the previous one
+    Set(HookSetCliArgs),
+
+    /// List all hooks
+    List,
+
+    /// Remove a hook
+    Remove(HookRemoveCliArgs),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RuleCommands {
+    /// Add a rule applied after each change of the inventory
+    Add(RuleAddCliArgs),
+
+    /// List all rules
+    List,
+
+    /// Remove a rule
+    Remove(RuleRemoveCliArgs),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum OrgCommands {
+    /// Create an organization
+    Create(OrgCreateCliArgs),
+
+    /// Make a user member of an organization
+    Invite(OrgInviteCliArgs),
+
+    /// List the organizations
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RoleCommands {
+    /// List the roles and their policies
+    List,
+
+    /// Set which entities the users of a role may change
+    Ownership(RoleOwnershipCliArgs),
+
+    /// Deny the users of a role to read or write a column, even with permissions granting it
+    Deny(RoleColumnCliArgs),
+
+    /// Lift the denial of a column
+    Allow(RoleColumnCliArgs),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SchedulerCommands {
+    /// Add a job that runs a command on a schedule
+    Add(SchedulerAddCliArgs),
+
+    /// List all jobs
+    List,
+
+    /// Remove a job
+    Remove(SchedulerRemoveCliArgs),
+
+    /// Run the jobs when they are due, until stopped
+    Run,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SyncCommands {
+    /// Replay the queued commands against the central database
+    Push(SyncPushCliArgs),
+
+    /// List the commands queued while working offline
+    Outbox,
+}
+
+#[derive(Args, Debug)]
+pub struct ReadOnlyCliArgs {
+    #[arg(action = clap::ArgAction::Set)]
+    /// Whether every invocation opens the database read-only
+    read_only: bool,
+}
+
+impl ReadOnlyCliArgs {
+    fn to_lib(&self) -> ReadOnlySetArgs {
+        return ReadOnlySetArgs {
+            read_only: self.read_only,
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DbCommands {
+    #[command(subcommand)]
+    /// Configure how long deleted entities and events are kept
+    Retention(RetentionCommands),
+
+    /// Remove what the retention rules no longer keep and report it
+    ApplyRetention(RetentionApplyCliArgs),
+
+    /// Collapse old revisions of the entities into snapshots
+    CompactHistory(HistoryCompactCliArgs),
+
+    /// Check the database for corruption and inconsistencies
+    Doctor(DoctorCliArgs),
+
+    /// Shrink the database file and refresh the query statistics
+    Optimize,
+
+    /// Print metrics of the database in the Prometheus text format
+    Metrics,
+
+    /// Export the whole database into a portable file
+    Dump(DumpCliArgs),
+
+    /// Recreate the database from a dump, the database must be empty
+    Load(LoadCliArgs),
+
+    /// Import the entities of another invman database
+    Merge(MergeCliArgs),
+
+    /// Make the database read-only for every invocation or writable again
+    ReadOnly(ReadOnlyCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct SerialHistoryCliArgs {
+    /// The serial number of the unit
+    serial: String,
+}
+
+impl SerialHistoryCliArgs {
+    fn to_lib(&self) -> SerialHistoryArgs {
+        return SerialHistoryArgs {
+            serial: self.serial.clone(),
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SerialCommands {
+    /// Show every status and location change of a unit
+    History(SerialHistoryCliArgs),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SupplierCommands {
+    /// Add a new supplier
+    Add(SupplierAddCliArgs),
+
+    /// List all suppliers
+    List(SupplierListCliArgs),
+
+    /// Edit an existing supplier
+    Edit(SupplierEditCliArgs),
+
+    /// Remove a supplier
+    Remove(SupplierRemoveCliArgs),
+
+    /// Link an inventory entity to a supplier
+    Link(SupplierLinkCliArgs),
+
+    /// Remove the link between an inventory entity and a supplier
+    Unlink(SupplierLinkCliArgs),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CategoryCommands {
+    /// Create a category together with its missing parents
+    Create(CategoryCreateCliArgs),
+
+    /// List all categories
+    List(CategoryListCliArgs),
+
+    /// Remove a category without subcategories
+    Remove(CategoryRemoveCliArgs),
+
+    /// Assign an inventory entity to a category, replacing its previous category
+    Assign(CategoryAssignCliArgs),
+
+    /// Remove an inventory entity from its category
+    Unassign(CategoryUnassignCliArgs),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserCommands {
+    /// Register a new user
+    Register(UserRegisterCliArgs),
+
+    /// Change the password of a user
+    Edit(UserEditCliArgs),
+
+    /// Delete a user for good, anonymizing the records the user left behind
+    Purge(UserPurgeCliArgs),
+
+    #[command(subcommand)]
+    /// Manage tokens to log in with instead of the password
+    Token(UserTokenCommands),
+
+    #[command(subcommand, name = "2fa")]
+    /// Manage two-factor authentication by one-time passwords
+    TwoFactor(UserTotpCommands),
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExportFormatCli {
+    /// One JSON array of all entities
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma separated values with a header line
+    Csv,
+}
+
+impl ExportFormatCli {
+    fn to_lib(self) -> ExportFormat {
+        return match self {
+            ExportFormatCli::Json => ExportFormat::Json,
+            ExportFormatCli::Ndjson => ExportFormat::Ndjson,
+            ExportFormatCli::Csv => ExportFormat::Csv,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryExportCliArgs {
+    #[arg(short, long, value_enum, default_value_t = ExportFormatCli::Json)]
+    /// Format the entities are written in
+    format: ExportFormatCli,
+
+    #[arg(long, value_delimiter = ',')]
+    /// Sensitive columns left out of the export, e.g. --redact supplier,purchase_price
+    redact: Vec<String>,
+
+    #[arg(long, requires = "redact")]
+    /// Replace the redacted columns by their SHA-256 hash instead of leaving them out
+    hash: bool,
+
+    #[arg(long, conflicts_with = "since")]
+    /// Only export the entities created, changed or removed after the transaction of this id
+    since_tx: Option<u32>,
+
+    #[arg(long)]
+    /// Only export the entities created, changed or removed since this UTC date, e.g. 2024-05-01
+    since: Option<String>,
+
+    #[arg(short = 'o', long = "to")]
+    /// File the export is written to instead of stdout, overwritten if it exists
+    file: Option<String>,
+
+    #[arg(long)]
+    /// Compress the export with gzip
+    gzip: bool,
+
+    #[arg(long, requires = "file")]
+    /// Write the SHA-256 checksum of the export into a sidecar file, verified by inventory import
+    sha256: bool,
+}
+
+impl InventoryExportCliArgs {
+    fn to_lib(&self) -> InventoryExportArgs {
+        return InventoryExportArgs {
+            format: self.format.to_lib(),
+            redact: self.redact.clone(),
+            hash: self.hash,
+            since_tx: self.since_tx,
+            since: self.since.clone(),
+            file: self.file.clone(),
+            gzip: self.gzip,
+            sha256: self.sha256,
+        };
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ImportFormatCli {
+    /// Comma separated values with a header line
+    Csv,
+    /// Excel workbook, whose sheet has a header row
+    Xlsx,
+    /// Array of objects, or one object per line, whose keys name the columns
+    Json,
+}
+
+impl ImportFormatCli {
+    fn to_lib(self) -> ImportFormat {
+        return match self {
+            ImportFormatCli::Csv => ImportFormat::Csv,
+            ImportFormatCli::Xlsx => ImportFormat::Xlsx,
+            ImportFormatCli::Json => ImportFormat::Json,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryImportCliArgs {
+    /// File to import the entities from
+    file: String,
+
+    #[arg(short, long, value_enum)]
+    /// Format of the file, guessed from its extension if not given
+    format: Option<ImportFormatCli>,
+
+    #[arg(long)]
+    /// Sheet of an Excel workbook to import, the first one if not given
+    sheet: Option<String>,
+
+    #[arg(short, long)]
+    /// Header of the file named differently than its schema column, e.g. --map "Part No=sku"
+    map: Vec<String>,
+
+    #[arg(long)]
+    /// Threads parsing and validating the rows (Default: Number of CPUs)
+    workers: Option<usize>,
+
+    #[arg(long, default_value_t = 0)]
+    /// Commit every given number of rows instead of importing all rows in a single transaction
+    batch_size: usize,
+
+    #[arg(long)]
+    /// Keeps the columns of the file unknown to the schema as metadata of the entities
+    allow_extra: bool,
+
+    #[arg(long)]
+    /// Only checks the file and lists the errors of all rows, without importing any of them
+    validate_only: bool,
+}
+
+impl InventoryImportCliArgs {
+    fn to_lib(&self) -> InventoryImportArgs {
+        return InventoryImportArgs {
+            file: self.file.clone(),
+            format: self.format.map(|e| e.to_lib()),
+            sheet: self.sheet.clone(),
+            map: self.map.clone(),
+            workers: self
+                .workers
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |e| e.get())),
+            batch_size: self.batch_size,
+            allow_extra: self.allow_extra,
+            validate_only: self.validate_only,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryValidateCliArgs {
+    /// File to check the entities of
+    file: String,
+
+    #[arg(short, long, value_enum)]
+    /// Format of the file, guessed from its extension if not given
+    format: Option<ImportFormatCli>,
+
+    #[arg(long)]
+    /// Sheet of an Excel workbook to check, the first one if not given
+    sheet: Option<String>,
+
+    #[arg(short, long)]
+    /// Header of the file named differently than its schema column, e.g. --map "Part No=sku"
+    map: Vec<String>,
+
+    #[arg(long)]
+    /// Threads parsing and validating the rows (Default: Number of CPUs)
+    workers: Option<usize>,
+
+    #[arg(long)]
+    /// Accepts the columns of the file unknown to the schema as metadata of the entities
+    allow_extra: bool,
+}
+
+impl InventoryValidateCliArgs {
+    fn to_lib(&self) -> InventoryImportArgs {
+        return InventoryImportArgs {
+            file: self.file.clone(),
+            format: self.format.map(|e| e.to_lib()),
+            sheet: self.sheet.clone(),
+            map: self.map.clone(),
+            workers: self
+                .workers
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |e| e.get())),
+            batch_size: 0,
+            allow_extra: self.allow_extra,
+            validate_only: true,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventoryListCliArgs {
+    #[arg(short, long)]
+    /// Limit the amount of entities to be returned
+    limit: Option<i32>,
+
+    #[arg(long)]
+    /// Only return entities with an identifier greater than the given cursor, ordered by identifier
+    after_id: Option<i64>,
+
+    #[arg(short, long)]
+    /// Typo-tolerant lookup in name=value syntax, returns entities whose column is similar to value ordered by similarity
+    fuzzy: Option<String>,
+
+    #[arg(long, default_value_t = 0.6)]
+    /// Minimum similarity between 0 and 1 an entity needs to be matched by --fuzzy
+    fuzzy_threshold: f64,
+
+    #[arg(long)]
+    /// Include the username and time of the latest change of each entity
+    with_audit: bool,
+
+    #[arg(long)]
+    /// Include the identifier, filename and size of each entity's attachments
+    with_attachments: bool,
+
+    #[arg(long)]
+    /// Also return entities that have been moved into the archive
+    include_archived: bool,
+
+    #[arg(long)]
+    /// Only return entities linked to the supplier with the given identifier
+    supplier: Option<u32>,
+
+    #[arg(long)]
+    /// Only return entities currently assigned to the given username, use "me" for yourself
+    assigned_to: Option<String>,
+
+    #[arg(long)]
+    /// Only return entities assigned to the category with the given path
+    category: Option<String>,
+
+    #[arg(long, requires = "category")]
+    /// Also return entities assigned to any descendant of --category
+    recursive: bool,
+
+    #[arg(short, long)]
+    /// How the returned rows should be sorted
+    sort: Vec<String>,
+
+    #[arg(short, long)]
+    /// Appends the clause directly to the query, it may only read. BEWARE that parameters must be passed seperatly with --params flags, otherwise your system will be vulnerable to SQL injection attacks
+    raw: Option<String>,
+
+    #[arg(short, long)]
+    /// Parameters that are passed with the raw SQL string, as name=value for named placeholders like :name
+    params: Vec<String>,
+
+    #[arg(short = 'w', long = "where", conflicts_with = "raw")]
+    /// Only return entities matching the expression, e.g. "quantity >= 10 and (location ~ 'North%' or price is null)", see the README for all operators
+    filter: Option<String>,
+
+    #[arg(long, value_delimiter = ',')]
+    /// Return one row per group of entities sharing the values of these columns, comma separated
+    group_by: Vec<String>,
+
+    #[arg(long)]
+    /// Aggregates returned for every group, e.g. "sum(quantity),avg(price)" (Default: count(*))
+    aggregate: Option<String>,
+
+    #[arg(long, value_delimiter = ',')]
+    /// Embed the related records into every entity, comma separated
+    expand: Vec<ExpansionCli>,
+
+    #[arg(short, long)]
+    /// How the returned rows should be sorted
+    condition: Vec<String>,
+
+    #[arg(long)]
+    /// Print the query and its plan instead of executing it
+    explain: bool,
+}
+
+impl InventoryListCliArgs {
+    fn to_lib(&self) -> InventoryListArgs {
+        return InventoryListArgs {
+            limit: self.limit,
+            after_id: self.after_id,
+            fuzzy: self.fuzzy.clone(),
+            fuzzy_threshold: self.fuzzy_threshold,
+            with_audit: self.with_audit,
+            with_attachments: self.with_attachments,
+            include_archived: self.include_archived,
+            supplier: self.supplier,
+            assigned_to: self.assigned_to.clone(),
+            category: self.category.clone(),
+            recursive: self.recursive,
+            sort: self.sort.clone(),
+            raw: self.raw.clone(),
+            params: self.params.clone(),
+            filter: self.filter.clone(),
+            group_by: self.group_by.clone(),
+            aggregate: self.aggregate.clone(),
+            expand: self.expand.iter().map(|e| e.to_lib()).collect(),
+            condition: self.condition.clone(),
+            explain: self.explain,
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventorySchemaListCliArgs;
+
+impl InventorySchemaListCliArgs {
+    fn to_lib(&self) -> InventorySchemaListArgs {
+        return InventorySchemaListArgs;
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InventorySchemaInferCliArgs {
+    /// File to infer the schema columns from, its first row being the header
+    file: String,
+
+    #[arg(short, long, value_enum)]
+    /// Format of the file, guessed from its extension if not given
+    format: Option<ImportFormatCli>,
+
+    #[arg(long)]
+    /// Sheet of an Excel workbook to read, the first one if not given
+    sheet: Option<String>,
+
+    #[arg(long)]
+    /// Add the proposed columns to the schema instead of only printing them
+    apply: bool,
+}
+
+impl InventorySchemaInferCliArgs {
+    fn to_lib(&self) -> InventorySchemaInferArgs {
+        return InventorySchemaInferArgs {
+            file: self.file.clone(),
+            format: self.format.map(|e| e.to_lib()),
+            sheet: self.sheet.clone(),
+            apply: self.apply,
+        };
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum InventorySchemaCommands {
+    /// Add or edit a schema column
+    Alter(InventorySchemaAlterCliArgs),
+
+    /// Remove a schema column
     Remove(InventorySchemaRemoveCliArgs),
 
     /// List your schema columns
     List(InventorySchemaListCliArgs),
+
+    /// Propose schema columns for the headers and values of a CSV or Excel file
+    Infer(InventorySchemaInferCliArgs),
+
+    /// Print the schema as JSON Schema for external forms and validators
+    JsonSchema,
+
+    /// Print the schema as GraphQL types, e.g. for generating clients
+    Graphql,
+
+    /// Print a template with one example entity in the shape import and the API expect
+    Example(InventorySchemaExampleCliArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct InventorySchemaExampleCliArgs {
+    #[arg(short, long, value_enum, default_value_t = ExportFormatCli::Json)]
+    /// Format the example entity is written in
+    format: ExportFormatCli,
+}
+
+impl InventorySchemaExampleCliArgs {
+    fn to_lib(&self) -> InventorySchemaExampleArgs {
+        return InventorySchemaExampleArgs {
+            format: self.format.to_lib(),
+        };
+    }
 }
 
 #[derive(Subcommand)]
@@ -350,45 +3767,974 @@ enum InventoryManagerCliSub {
     #[command(subcommand)]
     /// Manage your articles
     Inventory(InventoryCommands),
+
+    #[command(subcommand)]
+    /// Manage the suppliers of your articles
+    Supplier(SupplierCommands),
+
+    #[command(subcommand)]
+    /// Organize your articles in a tree of categories
+    Category(CategoryCommands),
+
+    #[command(subcommand)]
+    /// Keep track of lent articles
+    Loans(LoanCommands),
+
+    #[command(subcommand)]
+    /// Track individual units of serialized articles
+    Serial(SerialCommands),
+
+    #[command(subcommand)]
+    /// Manage the bills of materials of assemblies
+    Bom(BomCommands),
+
+    #[command(subcommand)]
+    /// Manage kits bundling several articles that are picked together
+    Kit(KitCommands),
+
+    #[command(subcommand)]
+    /// Count the stock at a location and correct it by the variances
+    Stocktake(StocktakeCommands),
+
+    #[command(subcommand)]
+    /// Keep track of recurring maintenance of your articles
+    Maintenance(MaintenanceCommands),
+
+    #[command(subcommand)]
+    /// Manage the files attached to your articles
+    Attachments(AttachmentCommands),
+
+    #[command(subcommand)]
+    /// Read the comments on your articles
+    Comments(CommentCommands),
+
+    #[command(subcommand)]
+    /// Maintain the database itself
+    Db(DbCommands),
+
+    #[command(subcommand)]
+    /// Push the changes made offline to a central database
+    Sync(SyncCommands),
+
+    #[command(subcommand)]
+    /// Notify by email or webhooks when events are recorded
+    Notify(NotifyCommands),
+
+    #[command(subcommand)]
+    /// Run commands on a schedule, e.g. nightly backups
+    Scheduler(SchedulerCommands),
+
+    #[command(subcommand)]
+    /// Change entities or notify automatically when entities change
+    Rule(RuleCommands),
+
+    #[command(subcommand)]
+    /// Run external programs before commands or after events
+    Hook(HookCommands),
+
+    #[command(subcommand)]
+    /// Use the WASM plugins found in ./plugins
+    Plugin(PluginCommands),
+
+    #[command(subcommand)]
+    /// Follow the events recorded in the event log
+    Events(EventCommands),
+
+    #[command(subcommand)]
+    /// Separate the inventory of organizations, each seeing only its own entities
+    Org(OrgCommands),
+
+    #[command(subcommand)]
+    /// Configure what the users of a role may do
+    Role(RoleCommands),
+
+    /// Run a script of commands atomically, i.e. either all or none of them take effect
+    Batch(BatchCliArgs),
+
+    /// Run commands interactively with a single connection and authentication
+    Shell,
+
+    /// Create the database together with its admin, instead of the first user becoming it
+    Init(InitCliArgs),
+
+    /// Check that the database can be queried and written, fails if it cannot
+    Health,
+
+    /// Search the inventory, comments, attachments and suppliers for a text
+    Search(SearchCliArgs),
+
+    #[command(subcommand)]
+    /// Generate demo data to try invman before designing your own schema
+    Demo(DemoCommands),
+
+    #[command(subcommand)]
+    /// Save and load declarative fixtures for reproducible test environments
+    Fixture(FixtureCommands),
+
+    /// Measure the throughput of invman on synthetic data in a temporary database
+    Bench(BenchCliArgs),
+
+    /// Serve the database over HTTP to invman instances started with --remote
+    Serve(ServeCliArgs),
 }
 
-fn main() {
-    use InventoryManagerCliSub::{Config, Inventory, User};
+impl InventoryManagerCliSub {
+    /**
+     * Whether the command changes the inventory and is thus queued when working offline.
+     * Maintenance of the database itself only concerns the local replica.
+     */
+    fn is_queued_offline(&self) -> bool {
+        use InventoryManagerCliSub::*;
+        return match self {
+            // Tokens are signed by the database they are issued by, so they are not synced
+            User(UserCommands::Token(_)) => false,
+            // The secret is generated anew when replayed, so it would not match the app's
+            User(UserCommands::TwoFactor(_)) => false,
+            User(_) | Bom(BomCommands::Define(_)) | Bom(BomCommands::Build(_)) => true,
+            Inventory(args) => !matches!(
+                args,
+                InventoryCommands::List(_)
+                    | InventoryCommands::Export(_)
+                    | InventoryCommands::Count(_)
+                    | InventoryCommands::Exists(_)
+                    | InventoryCommands::Distinct(_)
+                    | InventoryCommands::Label(_)
+                    | InventoryCommands::Dedupe(_)
+                    | InventoryCommands::Stock(_)
+                    | InventoryCommands::Report(_)
+                    | InventoryCommands::Image(InventoryImageCommands::Get(_))
+                    | InventoryCommands::Meta(InventoryMetaCommands::Get(_))
+                    | InventoryCommands::Validate(_)
+                    | InventoryCommands::Add(InventoryAddCliArgs {
+                        validate_only: true,
+                        ..
+                    })
+                    | InventoryCommands::Import(InventoryImportCliArgs {
+                        validate_only: true,
+                        ..
+                    })
+                    | InventoryCommands::Schema(InventorySchemaCommands::List(_))
+                    | InventoryCommands::Schema(InventorySchemaCommands::JsonSchema)
+                    | InventoryCommands::Schema(InventorySchemaCommands::Graphql)
+                    | InventoryCommands::Schema(InventorySchemaCommands::Example(_))
+                    | InventoryCommands::Schema(InventorySchemaCommands::Infer(
+                        InventorySchemaInferCliArgs { apply: false, .. }
+                    ))
+            ),
+            Attachments(args) => matches!(args, AttachmentCommands::Remove(_)),
+            Maintenance(args) => !matches!(args, MaintenanceCommands::Due(_)),
+            Supplier(args) => !matches!(args, SupplierCommands::List(_)),
+            Category(args) => !matches!(args, CategoryCommands::List(_)),
+            Kit(args) => !matches!(args, KitCommands::List(_) | KitCommands::Show(_)),
+            Stocktake(args) => !matches!(args, StocktakeCommands::Status),
+            Org(args) => !matches!(args, OrgCommands::List),
+            Role(args) => !matches!(args, RoleCommands::List),
+            Config(_) | Loans(_) | Bom(_) | Comments(_) | Serial(_) | Db(_) | Sync(_)
+            | Batch(_) | Shell | Init(_) | Health | Notify(_) | Scheduler(_) | Rule(_)
+            | Hook(_) | Plugin(_) | Events(_) | Search(_) | Bench(_) | Serve(_) => false,
+            // Fake data is for exploring a database, not for the central one
+            Demo(_) => false,
+            Fixture(_) => false,
+        };
+    }
+
+    /**
+     * Whether the command can run within the transaction of a batch. Rebuilding tables,
+     * attaching the archive and vacuuming need a connection without a transaction, while the
+     * commands running other commands or until they are stopped are no part of a script.
+     */
+    fn is_nestable(&self) -> bool {
+        use InventoryManagerCliSub::*;
+        return !matches!(
+            self,
+            Inventory(
+                InventoryCommands::Schema(
+                    InventorySchemaCommands::Alter(_)
+                        | InventorySchemaCommands::Remove(_)
+                        | InventorySchemaCommands::Infer(InventorySchemaInferCliArgs {
+                            apply: true,
+                            ..
+                        })
+                ) | InventoryCommands::Archive(_)
+            ) | User(UserCommands::Purge(_))
+                | Db(DbCommands::Load(_) | DbCommands::Optimize)
+                | Demo(_)
+                | Fixture(FixtureCommands::Load(_))
+                | Scheduler(SchedulerCommands::Run)
+                | Events(_)
+                | Batch(_)
+                | Shell
+                | Init(_)
+                | Serve(_)
+        );
+    }
+
+    /**
+     * The area and level a token's scopes must allow to run the command. Commands running
+     * other commands check each of them, managing tokens is left to the tokens themselves.
+     */
+    fn scope(&self) -> Option<(&'static str, ScopeLevel)> {
+        use InventoryManagerCliSub::*;
+        use ScopeLevel::{Admin, Read, Write};
+        let level = |read: bool| if read { Read } else { Write };
+        return match self {
+            User(UserCommands::Token(_) | UserCommands::TwoFactor(_)) => None,
+            User(_) => Some(("user", Admin)),
+            Config(
+                ConfigCommands::Ldap(LdapCommands::Show)
+                | ConfigCommands::PasswordPolicy(PasswordPolicyCommands::Show),
+            ) => Some(("config", Read)),
+            Config(
+                ConfigCommands::Ldap(_)
+                | ConfigCommands::PasswordPolicy(_)
+                | ConfigCommands::Registration(_),
+            ) => Some(("config", Admin)),
+            Config(args) => Some((
+                "config",
+                level(matches!(
+                    args,
+                    ConfigCommands::Rates(RatesCommands::List(_))
+                        | ConfigCommands::Reasons(ReasonCommands::List)
+                )),
+            )),
+            Inventory(InventoryCommands::Schema(args)) => match args {
+                InventorySchemaCommands::List(_)
+                | InventorySchemaCommands::JsonSchema
+                | InventorySchemaCommands::Graphql
+                | InventorySchemaCommands::Example(_)
+                | InventorySchemaCommands::Infer(InventorySchemaInferCliArgs {
+                    apply: false,
+                    ..
+                }) => Some(("schema", Read)),
+                _ => Some(("schema", Admin)),
+            },
+            Inventory(args) => Some((
+                "inventory",
+                level(matches!(
+                    args,
+                    InventoryCommands::List(_)
+                        | InventoryCommands::Export(_)
+                        | InventoryCommands::Count(_)
+                        | InventoryCommands::Exists(_)
+                        | InventoryCommands::Distinct(_)
+                        | InventoryCommands::Label(_)
+                        | InventoryCommands::Dedupe(_)
+                        | InventoryCommands::Stock(_)
+                        | InventoryCommands::Report(_)
+                        | InventoryCommands::Image(InventoryImageCommands::Get(_))
+                        | InventoryCommands::Meta(InventoryMetaCommands::Get(_))
+                        | InventoryCommands::Validate(_)
+                        | InventoryCommands::Add(InventoryAddCliArgs {
+                            validate_only: true,
+                            ..
+                        })
+                        | InventoryCommands::Import(InventoryImportCliArgs {
+                            validate_only: true,
+                            ..
+                        })
+                )),
+            )),
+            Search(_) => Some(("inventory", Read)),
+            Supplier(args) => Some(("supplier", level(matches!(args, SupplierCommands::List(_))))),
+            Category(args) => Some(("category", level(matches!(args, CategoryCommands::List(_))))),
+            Loans(_) => Some(("loans", Read)),
+            Serial(_) => Some(("serial", Read)),
+            Bom(args) => Some(("bom", level(matches!(args, BomCommands::Show(_))))),
+            Kit(args) => Some((
+                "kit",
+                level(matches!(args, KitCommands::List(_) | KitCommands::Show(_))),
+            )),
+            Stocktake(args) => Some((
+                "stocktake",
+                level(matches!(args, StocktakeCommands::Status)),
+            )),
+            Maintenance(args) => Some((
+                "maintenance",
+                level(matches!(args, MaintenanceCommands::Due(_))),
+            )),
+            Attachments(args) => Some((
+                "attachments",
+                level(!matches!(args, AttachmentCommands::Remove(_))),
+            )),
+            Comments(_) => Some(("comments", Read)),
+            Db(DbCommands::Doctor(_) | DbCommands::Metrics) => Some(("db", Read)),
+            Db(_) => Some(("db", Admin)),
+            Sync(args) => Some(("sync", level(matches!(args, SyncCommands::Outbox)))),
+            Notify(_) => Some(("notify", Write)),
+            Scheduler(args) => Some(("scheduler", level(matches!(args, SchedulerCommands::List)))),
+            Rule(args) => Some(("rule", level(matches!(args, RuleCommands::List)))),
+            Hook(args) => Some(("hook", level(matches!(args, HookCommands::List)))),
+            Plugin(_) => Some(("plugin", Read)),
+            Events(_) => Some(("events", Read)),
+            Org(OrgCommands::List) => Some(("org", Read)),
+            Org(_) => Some(("org", Admin)),
+            Role(RoleCommands::List) => Some(("role", Read)),
+            Role(_) => Some(("role", Admin)),
+            // Installs schema columns before adding the entities
+            Demo(_) => Some(("schema", Admin)),
+            Fixture(FixtureCommands::Save(_)) => Some(("db", Read)),
+            // Loading requires an empty database, which has no tokens yet
+            Fixture(FixtureCommands::Load(_)) => None,
+            Batch(_) | Shell | Init(_) | Health | Bench(_) | Serve(_) => None,
+        };
+    }
+}
+
+/**
+ * The arguments of the invoked command without the global options, in particular without the
+ * credentials, as these must not be stored. The global options are taken from the definition of
+ * the command line, so that new ones are left out as well.
+ */
+fn command_args() -> Vec<String> {
+    return strip_global_options(std::env::args().skip(1));
+}
+
+fn strip_global_options(args: impl Iterator<Item = String>) -> Vec<String> {
+    let cli = InventoryManagerCli::command();
+    let mut args = args.peekable();
+    while let Some(arg) = args.next_if(|e| e.starts_with('-') && e.len() > 1) {
+        let (option, inline) = match arg.strip_prefix("--") {
+            Some(long) => {
+                let name = long.split('=').next().unwrap_or_default();
+                let option = cli.get_arguments().find(|e| e.get_long() == Some(name));
+                (option, long.contains('='))
+            }
+            None => {
+                let short = arg.chars().nth(1);
+                let option = cli.get_arguments().find(|e| e.get_short() == short);
+                (option, arg.len() > 2)
+            }
+        };
+        match option {
+            Some(option) if option.get_action().takes_values() && !inline => {
+                args.next();
+            }
+            Some(_) => {}
+            // Unknown options are rejected by clap before, so this is not reached
+            None => return std::iter::once(arg).chain(args).collect(),
+        }
+    }
+    return args.collect();
+}
 
+fn main() {
     let cli = InventoryManagerCli::parse();
-    let mut conn = InvManConnection::sqlite().unwrap();
-    let pool: &mut dyn InvManDBPool = &mut conn;
-    let mut config = pool.get_config();
+    match (&cli.command, cli.stdin) {
+        (None, false) => InventoryManagerCli::command()
+            .error(
+                ErrorKind::MissingSubcommand,
+                "A command is required, unless --stdin is given",
+            )
+            .exit(),
+        (Some(_), true) => InventoryManagerCli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "A command cannot be given together with --stdin",
+            )
+            .exit(),
+        _ => {}
+    }
+    report::init(cli.quiet, cli.no_color);
+    if let Err(e) = init_logging(cli.verbose, cli.log_file.as_deref()) {
+        report::error(&e.to_string());
+        std::process::exit(1);
+    }
+    init_progress(match cli.command {
+        // Operations of clients are not reported on the terminal of the server
+        Some(InventoryManagerCliSub::Serve(_)) => Some(ProgressOutput::None),
+        _ => cli.progress.map(|e| e.to_lib()),
+    });
+    if let Some(InventoryManagerCliSub::Serve(args)) = &cli.command {
+        if cli.remote.is_some() {
+            report::error("A remote database cannot be served");
+            std::process::exit(1);
+        }
+        match args
+            .to_lib(&cli.database, cli.read_only, cli.auth.as_deref())
+            .serve()
+        {
+            Ok(s) => report::response(&s),
+            Err(e) => {
+                report::error(&e.to_string());
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    // The read-only option of the config can only be lifted by the command that changes it
+    let lifts_read_only = matches!(
+        cli.command,
+        Some(InventoryManagerCliSub::Db(DbCommands::ReadOnly(_)))
+    );
+    let mut conn: Box<dyn InvManDBPool> = match &cli.remote {
+        // The server rejects changes itself if its database is read-only
+        Some(url) => {
+            let client = InvManConnection::http(url, cli.auth.as_deref());
+            let identity = cli.remote_cert.as_deref().zip(cli.remote_key.as_deref());
+            match cli.remote_ca.is_none() && identity.is_none() {
+                true => Box::new(client),
+                false => match client.with_tls(cli.remote_ca.as_deref(), identity) {
+                    Ok(client) => Box::new(client),
+                    Err(e) => {
+                        report::error(&e.to_string());
+                        std::process::exit(1);
+                    }
+                },
+            }
+        }
+        None => {
+            let read_only = cli.read_only
+                || (!lifts_read_only
+                    && match InvManConnection::sqlite_database(&cli.database)
+                        .and_then(|db| db.get_config())
+                    {
+                        Ok(config) => config.read_only,
+                        Err(e) => {
+                            report::error(&e.to_string());
+                            std::process::exit(1);
+                        }
+                    });
+            match read_only {
+                true => match InvManConnection::sqlite_read_only_at(&cli.database) {
+                    Ok(conn) => Box::new(conn),
+                    Err(e) => {
+                        report::error(&e.to_string());
+                        std::process::exit(1);
+                    }
+                },
+                false => match InvManConnection::sqlite_database(&cli.database) {
+                    Ok(conn) => Box::new(conn),
+                    Err(e) => {
+                        report::error(&e.to_string());
+                        std::process::exit(1);
+                    }
+                },
+            }
+        }
+    };
+    let pool: &mut dyn InvManDBPool = conn.as_mut();
+    let mut config = match pool.get_config() {
+        Ok(config) => config,
+        Err(e) => {
+            report::error(&e.to_string());
+            std::process::exit(1);
+        }
+    };
+    let read_only = cli.read_only || (!lifts_read_only && config.read_only);
+    if let Some(timezone) = &cli.timezone {
+        match parse_timezone(timezone) {
+            Ok(timezone) => config.timezone = timezone.name().into(),
+            Err(e) => {
+                report::error(&e.to_string());
+                std::process::exit(1);
+            }
+        }
+    }
     let mut ctx = CommandContext {
         db: pool,
         auth: cli.auth,
+        otp: cli.otp,
         config: &mut config,
         output: cli.output.unwrap_or(OutputTypeCli::Json).to_lib(),
+        read_only,
+        session: None,
+    };
+    if let Err(e) = authenticate_otp(&mut ctx) {
+        report::error(&e.to_string());
+        std::process::exit(1);
+    }
+    let mut out_file = match cli.out.as_deref().map(report::OutFile::create) {
+        Some(Ok(file)) => Some(file),
+        Some(Err(e)) => {
+            report::error(&e.to_string());
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    let mut stdout = std::io::stdout();
+    let out: &mut dyn Write = match out_file.as_mut() {
+        Some(file) => file,
+        None => &mut stdout,
     };
 
     let response = match &cli.command {
+        Some(command) => {
+            let mut response = run(&command_args(), command, &mut ctx, out);
+            if cli.offline && response.is_ok() && command.is_queued_offline() {
+                if let Err(e) = (OutboxRecordArgs {
+                    command: command_args(),
+                })
+                .record(&mut ctx)
+                {
+                    response = Err(e);
+                }
+            }
+            response
+        }
+        None => StdinArgs.run(&mut ctx, &replay, out),
+    };
+    // The output file is only put in place once everything was written into it
+    let response = match out_file {
+        Some(file) => response.and_then(|s| file.commit(&s)),
+        None => response,
+    };
+
+    match response {
+        Ok(s) => report::response(&s),
+        Err(e) => {
+            report::error(&e.to_string());
+            std::process::exit(1);
+        }
+    }
+}
+
+/**
+ * Replays a command of a batch within its transaction, unless the command cannot be part of
+ * one.
+ */
+fn replay_in_batch(args: &[String], ctx: &mut CommandContext) -> Result<String> {
+    let cli = InventoryManagerCli::try_parse_from(
+        std::iter::once("invman").chain(args.iter().map(|e| e.as_str())),
+    )?;
+    return match &cli.command {
+        Some(command) if !command.is_nestable() => bail!(
+            "Commands that rebuild tables, attach the archive, optimize the database or run other commands cannot be part of a batch"
+        ),
+        Some(command) => run(args, command, ctx, &mut std::io::stdout()),
+        None => bail!("No command was given"),
+    };
+}
+
+/**
+ * Replays a command given by its arguments, e.g. one that was queued offline.
+ */
+fn replay(args: &[String], ctx: &mut CommandContext) -> Result<String> {
+    let cli = InventoryManagerCli::try_parse_from(
+        std::iter::once("invman").chain(args.iter().map(|e| e.as_str())),
+    )?;
+    return match &cli.command {
+        Some(command) => run(args, command, ctx, &mut std::io::stdout()),
+        None => bail!("No command was given"),
+    };
+}
+
+/**
+ * Sets up logging to stderr, depending on the verbosity, and as JSON into the log file.
+ */
+fn init_logging(verbose: u8, log_file: Option<&str>) -> Result<()> {
+    let level = match verbose {
+        0 => LevelFilter::OFF,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let file_layer = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_writer(Mutex::new(file))
+                    .with_filter(level.max(LevelFilter::INFO)),
+            )
+        }
+        None => None,
+    };
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_ansi(std::io::stderr().is_terminal())
+                .with_filter(level),
+        )
+        .with(file_layer)
+        .try_init()?;
+    return Ok(());
+}
+
+/**
+ * Authenticates users with two-factor authentication once for all commands of the invocation,
+ * as a recovery code can only be used once. The one-time password is asked for on the terminal
+ * if it is required but was not given.
+ */
+fn authenticate_otp(ctx: &mut CommandContext) -> Result<()> {
+    if ctx.otp.is_some() {
+        return ctx.start_session();
+    }
+    if !std::io::stdin().is_terminal() || ctx.auth.as_deref().is_none_or(|e| !e.contains(':')) {
+        return Ok(());
+    }
+    match ctx.start_session() {
+        Err(e) if e.to_string().contains(OTP_REQUIRED) => {}
+        // Other failures are left to the command, which may not even need the user
+        _ => return Ok(()),
+    }
+    eprint!("One-time password: ");
+    let mut otp = String::new();
+    std::io::stdin().read_line(&mut otp)?;
+    ctx.otp = Some(otp.trim().to_string());
+    return ctx.start_session();
+}
+
+/**
+ * Names a command by its subcommands, without any of its values, to be safely logged.
+ */
+fn command_name(args: &[String]) -> String {
+    return args
+        .iter()
+        .take_while(|e| !e.starts_with('-'))
+        .take(2)
+        .cloned()
+        .collect::<Vec<String>>()
+        .join(" ");
+}
+
+/**
+ * Runs a command and logs its outcome together with the time it took.
+ */
+fn run(
+    args: &[String],
+    command: &InventoryManagerCliSub,
+    ctx: &mut CommandContext,
+    out: &mut dyn Write,
+) -> Result<String> {
+    let name = command_name(args);
+    let started = Instant::now();
+    let latest_event = match command.is_queued_offline() && !ctx.read_only {
+        true => Some(ctx.db.latest_event()?),
+        false => None,
+    };
+    let mut response = match command.scope() {
+        Some((area, level)) => ctx.check_scope(area, level),
+        None => Ok(()),
+    }
+    .and_then(|_| {
+        (PreHookArgs {
+            command: name.replace(' ', "-"),
+            args: args.to_vec(),
+        })
+        .run(ctx)
+    })
+    .and_then(|_| dispatch(command, ctx, out));
+    if let (Ok(_), Some(after_event)) = (&response, latest_event) {
+        if let Err(e) = (RulesApplyArgs { after_event })
+            .apply(ctx)
+            .and_then(|_| (PostHookArgs { after_event }).run(ctx))
+        {
+            response = Err(e);
+        }
+    }
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+    match &response {
+        Ok(_) => tracing::info!(command = name, elapsed_ms, "Command succeeded"),
+        Err(e) => tracing::error!(command = name, elapsed_ms, error = %e, "Command failed"),
+    }
+    return response;
+}
+
+/**
+ * Runs the command, where commands that stream their data write it into out.
+ */
+fn dispatch(
+    command: &InventoryManagerCliSub,
+    ctx: &mut CommandContext,
+    out: &mut dyn Write,
+) -> Result<String> {
+    use InventoryManagerCliSub::{
+        Attachments, Batch, Bench, Bom, Category, Comments, Config, Db, Demo, Events, Fixture,
+        Health, Hook, Init, Inventory, Kit, Loans, Maintenance, Notify, Org, Plugin, Role, Rule,
+        Scheduler, Search, Serial, Serve, Shell, Stocktake, Supplier, Sync, User,
+    };
+
+    return match command {
         User(args) => match args {
-            UserCommands::Register(args) => args.to_lib().register(&mut ctx),
-            UserCommands::Edit(args) => args.to_lib().edit(&ctx),
+            UserCommands::Register(args) => args.to_lib().register(ctx),
+            UserCommands::Edit(args) => args.to_lib().edit(ctx),
+            UserCommands::Purge(args) => args.to_lib().purge(ctx),
+            UserCommands::Token(args) => match args {
+                UserTokenCommands::Issue(args) => args.to_lib().issue(ctx),
+                UserTokenCommands::Refresh => UserTokenRefreshArgs.refresh(ctx),
+                UserTokenCommands::List => UserTokenListArgs.list(ctx),
+                UserTokenCommands::Revoke(args) => args.to_lib().revoke(ctx),
+            },
+            UserCommands::TwoFactor(args) => match args {
+                UserTotpCommands::Enable => UserTotpEnableArgs.enable(ctx),
+                UserTotpCommands::Disable(args) => args.to_lib().disable(ctx),
+            },
         },
         Config(args) => match args {
-            _ => Ok("not a command".into()),
+            ConfigCommands::Timezone(args) => args.to_lib().set(ctx),
+            ConfigCommands::Format(args) => args.to_lib().set(ctx),
+            ConfigCommands::LabelPrinter(args) => args.to_lib().set(ctx),
+            ConfigCommands::Rates(command) => match command {
+                RatesCommands::Set(args) => args.to_lib().set(ctx),
+                RatesCommands::Remove(args) => args.to_lib().remove(ctx),
+                RatesCommands::List(args) => args.to_lib().list(ctx),
+                RatesCommands::Base(args) => args.to_lib().set(ctx),
+            },
+            ConfigCommands::Reasons(command) => match command {
+                ReasonCommands::Add(args) => args.to_lib().add(ctx),
+                ReasonCommands::Remove(args) => args.to_lib().remove(ctx),
+                ReasonCommands::List => ReasonCodeListArgs.list(ctx),
+            },
+            ConfigCommands::Registration(args) => args.to_lib().set(ctx),
+            ConfigCommands::PasswordPolicy(command) => match command {
+                PasswordPolicyCommands::Set(args) => args.to_lib().set(ctx),
+                PasswordPolicyCommands::Show => PasswordPolicyShowArgs.show(ctx),
+            },
+            ConfigCommands::Ldap(command) => match command {
+                LdapCommands::Set(args) => args.to_lib().set(ctx),
+                LdapCommands::Show => LdapShowArgs.show(ctx),
+                LdapCommands::Clear => LdapClearArgs.clear(ctx),
+            },
         },
         Inventory(args) => match args {
-            InventoryCommands::Add(args) => args.to_lib().add(&mut ctx),
-            InventoryCommands::List(args) => args.to_lib().list(&ctx),
-            InventoryCommands::Edit(args) => args.to_lib().edit(&mut ctx),
-            InventoryCommands::Remove(args) => args.to_lib().remove(&mut ctx),
+            InventoryCommands::Add(args) => args.to_lib().add(ctx),
+            InventoryCommands::List(args) => args.to_lib().list_into(ctx, out),
+            InventoryCommands::Export(args) => args.to_lib().export(ctx, out),
+            InventoryCommands::Import(args) => args.to_lib().import(ctx),
+            InventoryCommands::Validate(args) => args.to_lib().import(ctx),
+            InventoryCommands::Count(args) => args.to_lib().count(ctx),
+            InventoryCommands::Exists(args) => args.to_lib().exists(ctx),
+            InventoryCommands::Distinct(args) => args.to_lib().distinct(ctx),
+            InventoryCommands::Label(args) => args.to_lib().label(ctx),
+            InventoryCommands::Edit(args) => args.to_lib().edit(ctx),
+            InventoryCommands::Remove(args) => args.to_lib().remove(ctx),
+            InventoryCommands::Dedupe(args) => args.to_lib().dedupe(ctx),
+            InventoryCommands::Merge(args) => args.to_lib().merge(ctx),
+            InventoryCommands::Archive(args) => args.to_lib().archive(ctx),
+            InventoryCommands::Checkout(args) => args.to_lib().checkout(ctx),
+            InventoryCommands::Checkin(args) => args.to_lib().checkin(ctx),
+            InventoryCommands::Assign(args) => args.to_lib().assign(ctx),
+            InventoryCommands::Unassign(args) => args.to_lib().unassign(ctx),
+            InventoryCommands::Purchase(args) => args.to_lib().purchase(ctx),
+            InventoryCommands::Attach(args) => args.to_lib().attach(ctx),
+            InventoryCommands::Comment(args) => args.to_lib().comment(ctx),
+            InventoryCommands::Image(args) => match args {
+                InventoryImageCommands::Set(args) => args.to_lib().set(ctx),
+                InventoryImageCommands::Get(args) => args.to_lib().get(ctx),
+                InventoryImageCommands::Remove(args) => args.to_lib().remove(ctx),
+            },
+            InventoryCommands::Meta(args) => match args {
+                InventoryMetaCommands::Set(args) => args.to_lib().set(ctx),
+                InventoryMetaCommands::Get(args) => args.to_lib().get(ctx),
+                InventoryMetaCommands::Remove(args) => args.to_lib().remove(ctx),
+            },
+            InventoryCommands::Report(args) => {
+                let report = |ctx: &CommandContext| match args {
+                    InventoryReportCommands::WarrantyExpiring(args) => args.to_lib().report(ctx),
+                    InventoryReportCommands::Valuation(args) => args.to_lib().report(ctx),
+                    InventoryReportCommands::Html(args) => args.to_lib().report(ctx),
+                    InventoryReportCommands::Trend(args) => args.to_lib().report(ctx),
+                    InventoryReportCommands::ReorderSuggest(args) => args.to_lib().report(ctx),
+                    InventoryReportCommands::Reasons(args) => args.to_lib().report(ctx),
+                };
+                match args.explain() {
+                    true => ctx.explain(report),
+                    false => report(ctx),
+                }
+            }
+            InventoryCommands::Adjust(args) => args.to_lib().adjust(ctx),
+            InventoryCommands::Transfer(args) => args.to_lib().transfer(ctx),
+            InventoryCommands::Stock(args) => args.to_lib().stock(ctx),
             InventoryCommands::Schema(args) => match args {
-                InventorySchemaCommands::Alter(args) => args.to_lib().alter(&mut ctx),
-                InventorySchemaCommands::List(args) => args.to_lib().schema_list(&mut ctx),
-                InventorySchemaCommands::Remove(args) => args.to_lib().remove(&mut ctx),
+                InventorySchemaCommands::Alter(args) => args.to_lib().alter(ctx),
+                InventorySchemaCommands::List(args) => args.to_lib().schema_list(ctx),
+                InventorySchemaCommands::JsonSchema => {
+                    InventorySchemaJsonSchemaArgs.json_schema(ctx)
+                }
+                InventorySchemaCommands::Graphql => InventorySchemaGraphqlArgs.graphql(ctx),
+                InventorySchemaCommands::Example(args) => args.to_lib().example(ctx),
+                InventorySchemaCommands::Remove(args) => args.to_lib().remove(ctx),
+                InventorySchemaCommands::Infer(args) => args.to_lib().infer(ctx),
+            },
+        },
+        Loans(args) => match args {
+            LoanCommands::List => LoanListArgs {
+                overdue_only: false,
+            }
+            .list(ctx),
+            LoanCommands::Overdue => LoanListArgs { overdue_only: true }.list(ctx),
+        },
+        Bom(args) => match args {
+            BomCommands::Define(args) => args.to_lib().define(ctx),
+            BomCommands::Show(args) => args.to_lib().show(ctx),
+            BomCommands::Build(args) => args.to_lib().build(ctx),
+        },
+        Kit(args) => match args {
+            KitCommands::Define(args) => args.to_lib().define(ctx),
+            KitCommands::List(args) => args.to_lib().list(ctx),
+            KitCommands::Show(args) => args.to_lib().show(ctx),
+            KitCommands::Remove(args) => args.to_lib().remove(ctx),
+            KitCommands::Pick(args) => args.to_lib().pick(ctx),
+        },
+        Stocktake(args) => match args {
+            StocktakeCommands::Start(args) => args.to_lib().start(ctx),
+            StocktakeCommands::Record(args) => args.to_lib().record(ctx),
+            StocktakeCommands::Status => StocktakeStatusArgs.status(ctx),
+            StocktakeCommands::Close => StocktakeCloseArgs.close(ctx),
+            StocktakeCommands::Cancel => StocktakeCancelArgs.cancel(ctx),
+        },
+        Attachments(args) => match args {
+            AttachmentCommands::List(args) => args.to_lib().list(ctx),
+            AttachmentCommands::Get(args) => args.to_lib().get(ctx),
+            AttachmentCommands::Remove(args) => args.to_lib().remove(ctx),
+        },
+        Db(args) => match args {
+            DbCommands::Retention(args) => match args {
+                RetentionCommands::Set(args) => args.to_lib().set(ctx),
+                RetentionCommands::Show => RetentionShowArgs.show(ctx),
             },
+            DbCommands::ApplyRetention(args) => args.to_lib().apply(ctx),
+            DbCommands::CompactHistory(args) => args.to_lib().compact(ctx),
+            DbCommands::Doctor(args) => args.to_lib().diagnose(ctx),
+            DbCommands::Optimize => OptimizeArgs.optimize(ctx),
+            DbCommands::Metrics => MetricsArgs.metrics(ctx),
+            DbCommands::Dump(args) => args.to_lib().dump(ctx),
+            DbCommands::Load(args) => args.to_lib().load(ctx),
+            DbCommands::Merge(args) => args.to_lib().merge(ctx),
+            DbCommands::ReadOnly(args) => args.to_lib().set(ctx),
+        },
+        Batch(args) => args.to_lib().run(ctx, &replay_in_batch),
+        Shell => shell::run_shell(ctx),
+        Init(args) => args.to_lib().init(ctx),
+        Health => HealthArgs.check(ctx),
+        Search(args) => args.to_lib().search(ctx),
+        Demo(DemoCommands::Seed(args)) => args.to_lib().seed(ctx),
+        Fixture(args) => match args {
+            FixtureCommands::Save(args) => args.to_lib().save(ctx),
+            FixtureCommands::Load(args) => args.to_lib().load(ctx),
+        },
+        Bench(args) => args.to_lib().bench(),
+        // Started by main before the database is opened, as it opens connections of its own
+        Serve(_) => bail!("The server cannot be started from the shell or a script"),
+        Notify(args) => match args {
+            NotifyCommands::Smtp(args) => match args {
+                SmtpCommands::Set(args) => args.to_lib().set(ctx),
+                SmtpCommands::Show => SmtpShowArgs.show(ctx),
+                SmtpCommands::Clear => SmtpSetArgs { settings: None }.set(ctx),
+            },
+            NotifyCommands::Rule(args) => match args {
+                NotifyRuleCommands::Add(args) => args.to_lib().add(ctx),
+                NotifyRuleCommands::List => NotifyRuleListArgs.list(ctx),
+                NotifyRuleCommands::Remove(args) => args.to_lib().remove(ctx),
+            },
+            NotifyCommands::Run(args) => args.to_lib().run(ctx),
+        },
+        Events(args) => match args {
+            EventCommands::Watch(args) => args.to_lib().watch(ctx, &mut std::io::stdout()),
+        },
+        Role(args) => match args {
+            RoleCommands::List => RoleListArgs.list(ctx),
+            RoleCommands::Ownership(args) => args.to_lib().set(ctx),
+            RoleCommands::Deny(args) => args.to_lib(true).deny(ctx),
+            RoleCommands::Allow(args) => args.to_lib(false).deny(ctx),
+        },
+        Org(args) => match args {
+            OrgCommands::Create(args) => args.to_lib().create(ctx),
+            OrgCommands::Invite(args) => args.to_lib().invite(ctx),
+            OrgCommands::List => OrgListArgs.list(ctx),
+        },
+        Plugin(args) => match args {
+            PluginCommands::List => PluginListArgs.list(ctx),
+            PluginCommands::Report(args) => args.to_lib().report(ctx),
+        },
+        Hook(args) => match args {
+            HookCommands::Set(args) => args.to_lib().set(ctx),
+            HookCommands::List => HookListArgs.list(ctx),
+            HookCommands::Remove(args) => args.to_lib().remove(ctx),
+        },
+        Rule(args) => match args {
+            RuleCommands::Add(args) => args.to_lib().add(ctx),
+            RuleCommands::List => RuleListArgs.list(ctx),
+            RuleCommands::Remove(args) => args.to_lib().remove(ctx),
+        },
+        Scheduler(args) => match args {
+            SchedulerCommands::Add(args) => args.to_lib().add(ctx),
+            SchedulerCommands::List => SchedulerListArgs.list(ctx),
+            SchedulerCommands::Remove(args) => args.to_lib().remove(ctx),
+            SchedulerCommands::Run => SchedulerRunArgs.run(ctx, &replay, &mut std::io::stdout()),
+        },
+        Sync(args) => match args {
+            SyncCommands::Push(args) => args.to_lib().push(ctx, &replay),
+            SyncCommands::Outbox => OutboxListArgs.list(ctx),
+        },
+        Comments(args) => match args {
+            CommentCommands::List(args) => args.to_lib().list(ctx),
+        },
+        Maintenance(args) => match args {
+            MaintenanceCommands::Schedule(args) => args.to_lib().schedule(ctx),
+            MaintenanceCommands::Due(args) => args.to_lib().due(ctx),
+            MaintenanceCommands::Done(args) => args.to_lib().done(ctx),
+        },
+        Serial(args) => match args {
+            SerialCommands::History(args) => args.to_lib().history(ctx),
+        },
+        Supplier(args) => match args {
+            SupplierCommands::Add(args) => args.to_lib().add(ctx),
+            SupplierCommands::List(args) => args.to_lib().list(ctx),
+            SupplierCommands::Edit(args) => args.to_lib().edit(ctx),
+            SupplierCommands::Remove(args) => args.to_lib().remove(ctx),
+            SupplierCommands::Link(args) => args.to_lib().link(ctx),
+            SupplierCommands::Unlink(args) => args.to_lib().unlink(ctx),
+        },
+        Category(args) => match args {
+            CategoryCommands::Create(args) => args.to_lib().create(ctx),
+            CategoryCommands::List(args) => args.to_lib().list(ctx),
+            CategoryCommands::Remove(args) => args.to_lib().remove(ctx),
+            CategoryCommands::Assign(args) => args.to_lib().assign(ctx),
+            CategoryCommands::Unassign(args) => args.to_lib().unassign(ctx),
         },
     };
+}
 
-    match response {
-        Ok(s) => println!("{}", s),
-        Err(e) => eprintln!("{}", e.to_string()),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(args: &[&str]) -> Vec<String> {
+        return strip_global_options(args.iter().map(|e| e.to_string()));
+    }
+
+    #[test]
+    fn global_options_are_left_out_of_the_command() {
+        let command = ["inventory", "add", "sku=A1"];
+        for global in [
+            vec!["-a", "admin:pw"],
+            vec!["--auth", "admin:pw"],
+            vec!["--auth=admin:pw"],
+            vec!["-aadmin:pw"],
+            vec![
+                "--database",
+                "./storage",
+                "--offline",
+                "--read-only",
+                "--stdin",
+            ],
+            vec!["--otp", "123456", "-o", "json", "--progress", "none"],
+            vec![
+                "-vv",
+                "-q",
+                "--log-file=log",
+                "--timezone",
+                "UTC",
+                "--no-color",
+            ],
+            vec!["--out", "file", "--database=./storage"],
+        ] {
+            let args = global
+                .iter()
+                .chain(command.iter())
+                .copied()
+                .collect::<Vec<_>>();
+            assert_eq!(strip(&args), command, "{:?}", global);
+        }
+    }
+
+    #[test]
+    fn options_of_the_command_are_kept() {
+        let command = ["inventory", "list", "-a", "x", "--database", "y"];
+        assert_eq!(strip(&command), command);
+        assert!(strip(&["-a", "admin:pw"]).is_empty());
     }
 }
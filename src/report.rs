@@ -0,0 +1,230 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use anyhow::{anyhow, Result};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Write},
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+static REPORTER: OnceLock<Reporter> = OnceLock::new();
+
+/**
+ * Writes the results and messages of the commands, colored by their level where the terminal
+ * supports it.
+ */
+struct Reporter {
+    /**
+     * Only data is printed, status messages and warnings are left out
+     */
+    quiet: bool,
+    color: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Level {
+    Info,
+    /**
+     * Like info, but kept apart from the data on stdout
+     */
+    Summary,
+    Warn,
+    Error,
+}
+
+/**
+ * Configures the reporting for the rest of the run, colors are turned off by --no-color or the
+ * NO_COLOR environment variable (https://no-color.org).
+ */
+pub fn init(quiet: bool, no_color: bool) {
+    let no_color = no_color || std::env::var_os("NO_COLOR").is_some_and(|e| !e.is_empty());
+    let _ = REPORTER.set(Reporter {
+        quiet,
+        color: !no_color,
+    });
+}
+
+fn reporter() -> &'static Reporter {
+    return REPORTER.get_or_init(|| Reporter {
+        quiet: false,
+        color: true,
+    });
+}
+
+/**
+ * Prints the response of a command. Data, e.g. JSON or tables, is printed as is, while short
+ * status messages like "Entity was successfully added to inventory" are reported as info.
+ */
+pub fn response(response: &str) {
+    if response.is_empty() {
+        return;
+    }
+    if is_status_message(response) {
+        info(response);
+    } else {
+        println!("{}", response);
+    }
+}
+
+/**
+ * Status messages are single sentences, while data is either JSON, spans several lines, like
+ * tables, or is a single word, like a token.
+ */
+fn is_status_message(response: &str) -> bool {
+    return !response.contains('\n')
+        && response.contains(' ')
+        && serde_json::from_str::<serde_json::Value>(response).is_err();
+}
+
+pub fn info(message: &str) {
+    write(Level::Info, message);
+}
+
+pub fn warn(message: &str) {
+    write(Level::Warn, message);
+}
+
+pub fn error(message: &str) {
+    write(Level::Error, message);
+}
+
+fn write(level: Level, message: &str) {
+    let reporter = reporter();
+    let (prefix, color) = match level {
+        Level::Info if reporter.quiet => return,
+        Level::Info => ("", "32"),
+        Level::Summary if reporter.quiet => return,
+        Level::Summary => ("", "32"),
+        Level::Warn if reporter.quiet => return,
+        Level::Warn => ("warning: ", "33"),
+        Level::Error => ("error: ", "31"),
+    };
+    // Info belongs to the output of a command, warnings and errors do not
+    let terminal = match level {
+        Level::Info => io::stdout().is_terminal(),
+        Level::Summary | Level::Warn | Level::Error => io::stderr().is_terminal(),
+    };
+    let message = if reporter.color && terminal {
+        format!("\x1b[{}m{}{}\x1b[0m", color, prefix, message)
+    } else {
+        format!("{}{}", prefix, message)
+    };
+    match level {
+        Level::Info => println!("{}", message),
+        Level::Summary | Level::Warn | Level::Error => eprintln!("{}", message),
+    }
+}
+
+/**
+ * File given by --out, which the output is written into under a temporary name next to it.
+ * It only replaces the file once the command succeeded, so that an interrupted or failed
+ * command never leaves a partial file behind.
+ */
+pub struct OutFile {
+    path: PathBuf,
+    temp: PathBuf,
+    file: Option<BufWriter<File>>,
+    bytes: u64,
+    lines: u64,
+}
+
+impl OutFile {
+    pub fn create(path: &str) -> Result<OutFile> {
+        let path = PathBuf::from(path);
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(anyhow!("'{}' is not a file path", path.display())),
+        };
+        // Renaming is only atomic within the same file system, hence the same directory
+        let temp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+        let file = File::create(&temp)
+            .map_err(|e| anyhow!("Could not create file '{}' ({})", path.display(), e))?;
+        return Ok(OutFile {
+            path,
+            temp,
+            file: Some(BufWriter::new(file)),
+            bytes: 0,
+            lines: 0,
+        });
+    }
+
+    /**
+     * Writes the data of the response and moves the file into place, reporting what was
+     * written to stderr. Status messages are not written but returned for printing.
+     */
+    pub fn commit(mut self, response: &str) -> Result<String> {
+        let status = is_status_message(response);
+        if !response.is_empty() && !status {
+            writeln!(self, "{}", response)?;
+        }
+        let file = self
+            .file
+            .take()
+            .expect("Output file is only committed once");
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&self.temp, &self.path)
+            .map_err(|e| anyhow!("Could not write file '{}' ({})", self.path.display(), e))?;
+        write(
+            Level::Summary,
+            &format!(
+                "Wrote {} bytes in {} lines to '{}'",
+                self.bytes,
+                self.lines,
+                self.path.display()
+            ),
+        );
+        return Ok(match status {
+            true => response.into(),
+            false => "".into(),
+        });
+    }
+}
+
+impl Write for OutFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let file = self
+            .file
+            .as_mut()
+            .expect("Output file was already committed");
+        let written = file.write(buf)?;
+        self.bytes += written as u64;
+        self.lines += buf[..written].iter().filter(|e| **e == b'\n').count() as u64;
+        return Ok(written);
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        };
+    }
+}
+
+impl Drop for OutFile {
+    /**
+     * Removes the temporary file of a command that failed, leaving the previous file as it was
+     */
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
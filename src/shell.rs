@@ -0,0 +1,148 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::{replay, report, InventoryManagerCli};
+use anyhow::Result;
+use clap::CommandFactory;
+use invman::common::args::{split_command_line, CommandContext};
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+
+/**
+ * History of the entered commands, kept next to the storage file
+ */
+const HISTORY_FILE: &str = "./.invman_history";
+
+struct ShellHelper {
+    columns: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    /**
+     * Completes subcommands and options of the command entered so far as well as the schema
+     * column names in name= notation.
+     */
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let mut command = InventoryManagerCli::command();
+        for arg in line[..start].split_whitespace() {
+            if let Some(subcommand) = command.find_subcommand(arg) {
+                command = subcommand.clone();
+            }
+        }
+        let candidates = command
+            .get_subcommands()
+            .map(|e| e.get_name().to_string())
+            .chain(
+                command
+                    .get_arguments()
+                    .filter_map(|e| e.get_long())
+                    .map(|e| format!("--{}", e)),
+            )
+            .chain(self.columns.iter().map(|e| format!("{}=", e)))
+            .filter(|e| e.starts_with(word))
+            .map(|e| Pair {
+                display: e.clone(),
+                replacement: e,
+            })
+            .collect();
+        return Ok((start, candidates));
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+fn column_names(ctx: &CommandContext) -> Vec<String> {
+    return ctx
+        .config
+        .inventory_schema_declaration
+        .collection
+        .iter()
+        .map(|e| e.name.clone())
+        .collect();
+}
+
+/**
+ * Reads commands until exit, quit or the end of input, all of them share the connection and
+ * are run as the user authenticated once at the start.
+ */
+pub fn run_shell(ctx: &mut CommandContext) -> Result<String> {
+    ctx.start_session()?;
+    let mut editor = Editor::<ShellHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ShellHelper {
+        columns: column_names(ctx),
+    }));
+    // The history does not exist on the first start
+    let _ = editor.load_history(HISTORY_FILE);
+    loop {
+        let line = match editor.readline("invman> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let args = match split_command_line(&line) {
+            Ok(args) if args.is_empty() => continue,
+            Ok(args) => args,
+            Err(e) => {
+                report::error(&e.to_string());
+                continue;
+            }
+        };
+        editor.add_history_entry(line.as_str())?;
+        match args[0].as_str() {
+            "exit" | "quit" => break,
+            "shell" => report::warn("The shell is already running"),
+            _ => match replay(&args, ctx) {
+                Ok(response) => report::response(&response),
+                Err(e) => report::error(&e.to_string()),
+            },
+        }
+        // The schema may have been altered by the command
+        if let Some(helper) = editor.helper_mut() {
+            helper.columns = column_names(ctx);
+        }
+    }
+    editor.save_history(HISTORY_FILE)?;
+    return Ok(String::new());
+}
@@ -0,0 +1,64 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, TestServer, ADMIN, BOB};
+
+#[test]
+fn commands_and_requests_are_checked_alike() {
+    let db = TestDb::new();
+    let error = db.err(Some(BOB), &["inventory", "schema", "remove", "name"]);
+    assert!(
+        error.contains("Cannot write to the config table"),
+        "{}",
+        error
+    );
+    let server = TestServer::start(&db, &[]);
+    let url = format!("http://{}", server.listen);
+    let output = server.run(&[
+        "--remote",
+        &url,
+        "-a",
+        BOB,
+        "inventory",
+        "schema",
+        "remove",
+        "name",
+    ]);
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains("Cannot write to the config table"),
+        "{}",
+        error
+    );
+    drop(server);
+    db.ok(Some(ADMIN), &["inventory", "schema", "remove", "name"]);
+}
+
+#[test]
+fn read_only_databases_reject_changes_before_running_them() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["db", "read-only", "true"]);
+    let error = db.err(Some(ADMIN), &["supplier", "add", "-n", "ACME"]);
+    assert!(error.contains("opened in read-only mode"), "{}", error);
+    db.ok(Some(ADMIN), &["supplier", "list"]);
+}
@@ -0,0 +1,47 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+use std::process::Command;
+
+#[test]
+fn archives_belong_to_their_database() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A2"]);
+    let archived = db.ok(
+        Some(ADMIN),
+        &["inventory", "archive", "--where", "sku = A1"],
+    );
+    assert!(archived.contains("Archived 1 entities"), "{}", archived);
+    assert!(db.dir.join("storage.archive").exists());
+    // The archive is found from any directory the database is used from
+    let output = Command::new(env!("CARGO_BIN_EXE_invman_bin"))
+        .current_dir(std::env::temp_dir())
+        .args(["--database", &db.path(), "-a", ADMIN])
+        .args(["inventory", "list", "--include-archived"])
+        .output()
+        .unwrap();
+    let listed = String::from_utf8_lossy(&output.stdout);
+    assert!(listed.contains("A1") && listed.contains("A2"), "{}", listed);
+}
@@ -0,0 +1,82 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+
+/**
+ * Writes the lines into the file of the given name in the directory of the database.
+ */
+fn write(db: &TestDb, name: &str, lines: &[&str]) {
+    std::fs::write(db.dir.join(name), lines.join("\n")).unwrap();
+}
+
+fn skus(db: &TestDb) -> Vec<String> {
+    let listed: serde_json::Value =
+        serde_json::from_str(&db.ok(Some(ADMIN), &["inventory", "list"])).unwrap();
+    return listed
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["sku"].as_str().unwrap().to_string())
+        .collect();
+}
+
+#[test]
+fn imports_within_a_batch_take_effect_with_it() {
+    let db = TestDb::new();
+    write(
+        &db,
+        "parts.csv",
+        &["sku,name", "A-1,Bolt", "A-2,Nut", "A-3,Washer"],
+    );
+    let import = "inventory import parts.csv --batch-size 2";
+    write(
+        &db,
+        "failing.invman",
+        &[import, "inventory edit -i 99 -s name=Screw"],
+    );
+    let error = db.err(Some(ADMIN), &["batch", "failing.invman"]);
+    assert!(error.contains("Line 2"), "{}", error);
+    // The rows the import committed are undone together with the batch
+    assert!(skus(&db).is_empty());
+    write(&db, "import.invman", &[import, "inventory add sku=A-4"]);
+    db.ok(Some(ADMIN), &["batch", "import.invman"]);
+    assert_eq!(skus(&db), ["A-1", "A-2", "A-3", "A-4"]);
+}
+
+#[test]
+fn commands_needing_a_connection_of_their_own_are_rejected() {
+    let db = TestDb::new();
+    let alter = "inventory schema alter -n weight -c real --nullable true";
+    write(&db, "alter.invman", &["inventory add sku=A-1", alter]);
+    let error = db.err(Some(ADMIN), &["batch", "alter.invman"]);
+    assert!(
+        error.contains("Line 2: Commands that rebuild tables"),
+        "{}",
+        error
+    );
+    assert!(skus(&db).is_empty());
+    write(&db, "nested.invman", &["batch alter.invman"]);
+    let error = db.err(Some(ADMIN), &["batch", "nested.invman"]);
+    assert!(error.contains("cannot be part of a batch"), "{}", error);
+}
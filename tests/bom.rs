@@ -0,0 +1,80 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+
+#[test]
+fn builds_short_of_a_component_book_nothing() {
+    let db = TestDb::new();
+    for sku in ["C1", "C2", "ASM"] {
+        db.ok(Some(ADMIN), &["inventory", "add", &format!("sku={}", sku)]);
+    }
+    db.adjust("1", 10, "A");
+    db.adjust("2", 1, "A");
+    db.ok(
+        Some(ADMIN),
+        &[
+            "bom",
+            "define",
+            "3",
+            "--component",
+            "id=1",
+            "qty=2",
+            "--component",
+            "id=2",
+            "qty=1",
+        ],
+    );
+    let error = db.err(
+        Some(ADMIN),
+        &["bom", "build", "3", "--qty", "2", "--location", "A"],
+    );
+    assert!(
+        error.contains("component 2: 2 required, 1 available, 1 missing"),
+        "{}",
+        error
+    );
+    assert_eq!(db.stock("1", "A"), 10);
+    assert_eq!(db.stock("2", "A"), 1);
+    assert_eq!(db.stock("3", "A"), 0);
+    let builds = || -> i64 {
+        let conn = rusqlite::Connection::open(db.path()).unwrap();
+        return conn
+            .query_row(
+                "SELECT COUNT(*) FROM invman_event_tx WHERE action_no=401",
+                (),
+                |row| row.get(0),
+            )
+            .unwrap();
+    };
+    assert_eq!(builds(), 0);
+    db.adjust("2", 1, "A");
+    db.ok(
+        Some(ADMIN),
+        &["bom", "build", "3", "--qty", "2", "--location", "A"],
+    );
+    assert_eq!(db.stock("1", "A"), 6);
+    assert_eq!(db.stock("2", "A"), 0);
+    assert_eq!(db.stock("3", "A"), 2);
+    assert_eq!(builds(), 1);
+}
@@ -0,0 +1,242 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::{
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    process::{Child, Command, Output, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+pub const ADMIN: &str = "admin:Admin-pw-123";
+pub const BOB: &str = "bob:Bob-pw-1234";
+
+static DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+/**
+ * Database file of a test in a directory of its own, which is removed with it. Commands are run
+ * by the invman binary, like users would run them.
+ */
+pub struct TestDb {
+    pub dir: PathBuf,
+}
+
+impl TestDb {
+    /**
+     * Creates the database with the admin, the user bob without permissions and a schema of
+     * sku and name.
+     */
+    pub fn new() -> TestDb {
+        let dir = std::env::temp_dir().join(format!(
+            "invman-test-{}-{}",
+            std::process::id(),
+            DATABASES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = TestDb { dir };
+        db.ok(
+            None,
+            &[
+                "init",
+                "--admin-user",
+                "admin",
+                "--admin-password",
+                "Admin-pw-123",
+            ],
+        );
+        db.ok(Some(ADMIN), &["user", "register", "bob", "Bob-pw-1234"]);
+        for column in ["sku", "name"] {
+            db.ok(
+                Some(ADMIN),
+                &[
+                    "inventory",
+                    "schema",
+                    "alter",
+                    "-n",
+                    column,
+                    "-c",
+                    "varchar",
+                    "-m",
+                    "32",
+                    "--nullable",
+                    "true",
+                ],
+            );
+        }
+        return db;
+    }
+
+    pub fn path(&self) -> String {
+        return self.dir.join("storage").to_string_lossy().into_owned();
+    }
+
+    /**
+     * Runs invman on the database as the user given in the notation of --auth.
+     */
+    pub fn run(&self, auth: Option<&str>, args: &[&str]) -> Output {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_invman_bin"));
+        command
+            .current_dir(&self.dir)
+            .args(["--database", &self.path()]);
+        if let Some(auth) = auth {
+            command.args(["-a", auth]);
+        }
+        return command.args(args).output().unwrap();
+    }
+
+    /**
+     * @returns The output of the command, which has to succeed
+     */
+    pub fn ok(&self, auth: Option<&str>, args: &[&str]) -> String {
+        let output = self.run(auth, args);
+        assert!(
+            output.status.success(),
+            "{:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return String::from_utf8_lossy(&output.stdout).into_owned();
+    }
+
+    /**
+     * @returns The error of the command, which has to fail
+     */
+    pub fn err(&self, auth: Option<&str>, args: &[&str]) -> String {
+        let output = self.run(auth, args);
+        assert!(
+            !output.status.success(),
+            "{:?} succeeded: {}",
+            args,
+            String::from_utf8_lossy(&output.stdout)
+        );
+        return String::from_utf8_lossy(&output.stderr).into_owned();
+    }
+
+    /**
+     * Books the quantity of the entity into the stock at the location as a correction.
+     */
+    pub fn adjust(&self, identifier: &str, qty: i64, location: &str) {
+        self.ok(
+            Some(ADMIN),
+            &[
+                "inventory",
+                "adjust",
+                "--identifier",
+                identifier,
+                "--qty",
+                &qty.to_string(),
+                "--location",
+                location,
+                "--reason",
+                "correction",
+            ],
+        );
+    }
+
+    /**
+     * @returns The quantity of the entity in stock at the location, as listed by inventory stock
+     */
+    pub fn stock(&self, identifier: &str, location: &str) -> i64 {
+        let listed: serde_json::Value = serde_json::from_str(&self.ok(
+            Some(ADMIN),
+            &["inventory", "stock", "--identifier", identifier],
+        ))
+        .unwrap();
+        return listed
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|stock| stock["location"] == location)
+            .map_or(0, |stock| stock["quantity"].as_i64().unwrap());
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/**
+ * invman serving a TestDb on a free port of the local machine, which is stopped with it
+ */
+pub struct TestServer {
+    child: Child,
+    /**
+     * Address the server listens on, in host:port notation
+     */
+    pub listen: String,
+}
+
+impl TestServer {
+    /**
+     * Starts serve with the additional args and waits until it accepts connections.
+     */
+    pub fn start(db: &TestDb, args: &[&str]) -> TestServer {
+        return TestServer::start_as(db, None, args);
+    }
+
+    /**
+     * Starts serve like start, with the credentials of --auth.
+     */
+    pub fn start_as(db: &TestDb, auth: Option<&str>, args: &[&str]) -> TestServer {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let listen = format!("127.0.0.1:{}", port);
+        let child = Command::new(env!("CARGO_BIN_EXE_invman_bin"))
+            .current_dir(&db.dir)
+            .args(["--database", &db.path()])
+            .args(auth.iter().flat_map(|auth| ["-a", auth]))
+            .args(["serve", "--listen", &listen])
+            .args(args)
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let server = TestServer { child, listen };
+        for _ in 0..100 {
+            if TcpStream::connect(&server.listen).is_ok() {
+                return server;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        panic!("Server on {} did not start", server.listen);
+    }
+
+    /**
+     * Runs invman with the args, which have to give --remote.
+     */
+    pub fn run(&self, args: &[&str]) -> Output {
+        return Command::new(env!("CARGO_BIN_EXE_invman_bin"))
+            .args(args)
+            .output()
+            .unwrap();
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,326 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, TestServer, ADMIN, BOB};
+use std::collections::HashMap;
+use tonic::{
+    client::Grpc,
+    codec::ProstCodec,
+    codegen::http::uri::PathAndQuery,
+    transport::{Certificate, Channel, ClientTlsConfig, Identity},
+    Code, Request, Status,
+};
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Entity {
+    #[prost(int64, tag = "1")]
+    id: i64,
+    #[prost(map = "string, string", tag = "4")]
+    values: HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct AddRequest {
+    #[prost(map = "string, string", tag = "1")]
+    values: HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct GetRequest {
+    #[prost(int64, tag = "1")]
+    id: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ListRequest {
+    #[prost(map = "string, string", tag = "1")]
+    filter: HashMap<String, String>,
+    #[prost(int32, tag = "2")]
+    limit: i32,
+    #[prost(int64, optional, tag = "3")]
+    after_id: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct RemoveRequest {
+    #[prost(int64, tag = "1")]
+    id: i64,
+    #[prost(string, optional, tag = "2")]
+    reason: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct RemoveResponse {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct WatchEventsRequest {
+    #[prost(uint32, tag = "1")]
+    after_event: u32,
+    #[prost(string, repeated, tag = "2")]
+    events: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Event {
+    #[prost(string, tag = "2")]
+    event: String,
+    #[prost(message, optional, tag = "7")]
+    entity: Option<Entity>,
+}
+
+/**
+ * Calls the methods of the InventoryService by their paths, like a generated client does
+ */
+struct Client {
+    runtime: tokio::runtime::Runtime,
+    grpc: Grpc<Channel>,
+}
+
+// tonic::Status is what every call fails with
+#[allow(clippy::result_large_err)]
+impl Client {
+    fn connect(server: &TestServer) -> Client {
+        return Client::try_connect(server, None).unwrap();
+    }
+
+    fn try_connect(server: &TestServer, tls: Option<ClientTlsConfig>) -> Result<Client, String> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let scheme = match tls {
+            Some(_) => "https",
+            None => "http",
+        };
+        let mut endpoint = Channel::from_shared(format!("{}://{}", scheme, server.listen)).unwrap();
+        if let Some(tls) = tls {
+            endpoint = endpoint.tls_config(tls).unwrap();
+        }
+        let channel = runtime
+            .block_on(endpoint.connect())
+            .map_err(|e| format!("{:?}", e))?;
+        return Ok(Client {
+            runtime,
+            grpc: Grpc::new(channel),
+        });
+    }
+
+    fn unary<T, U>(&mut self, method: &str, auth: &str, message: T) -> Result<U, Status>
+    where
+        T: prost::Message + 'static,
+        U: prost::Message + Default + 'static,
+    {
+        let request = authorized(message, auth);
+        return self.runtime.block_on(async {
+            self.grpc.ready().await.unwrap();
+            let response = self
+                .grpc
+                .unary(request, path(method), ProstCodec::default())
+                .await?;
+            return Ok(response.into_inner());
+        });
+    }
+
+    /**
+     * @returns The messages of the stream, up to the given number of them
+     */
+    fn stream<T, U>(
+        &mut self,
+        method: &str,
+        auth: &str,
+        message: T,
+        max: usize,
+    ) -> Result<Vec<U>, Status>
+    where
+        T: prost::Message + 'static,
+        U: prost::Message + Default + 'static,
+    {
+        let request = authorized(message, auth);
+        return self.runtime.block_on(async {
+            self.grpc.ready().await.unwrap();
+            let mut stream = self
+                .grpc
+                .server_streaming(request, path(method), ProstCodec::default())
+                .await?
+                .into_inner();
+            let mut messages = vec![];
+            while messages.len() < max {
+                match stream.message().await? {
+                    Some(message) => messages.push(message),
+                    None => break,
+                }
+            }
+            return Ok(messages);
+        });
+    }
+}
+
+fn path(method: &str) -> PathAndQuery {
+    let path = format!("/invman.v1.InventoryService/{}", method);
+    return PathAndQuery::try_from(path).unwrap();
+}
+
+fn authorized<T>(message: T, auth: &str) -> Request<T> {
+    let credentials = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, auth);
+    let mut request = Request::new(message);
+    request.metadata_mut().insert(
+        "authorization",
+        format!("Basic {}", credentials).parse().unwrap(),
+    );
+    return request;
+}
+
+fn values(values: &[(&str, &str)]) -> HashMap<String, String> {
+    return values
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+}
+
+#[test]
+fn grpc_answers_the_inventory_service_with_the_permissions_of_rpc() {
+    let db = TestDb::new();
+    let server = TestServer::start(&db, &["--grpc"]);
+    let mut client = Client::connect(&server);
+    let mut ids = vec![];
+    for sku in ["A-1", "A-2", "A-3"] {
+        let added: Entity = client
+            .unary(
+                "Add",
+                ADMIN,
+                AddRequest {
+                    values: values(&[("sku", sku), ("name", "Bolt")]),
+                },
+            )
+            .unwrap();
+        assert_eq!(added.values["sku"], sku);
+        ids.push(added.id);
+    }
+    // Reading is permitted to everyone
+    let entity: Entity = client.unary("Get", BOB, GetRequest { id: ids[1] }).unwrap();
+    assert_eq!(entity.values, values(&[("sku", "A-2"), ("name", "Bolt")]));
+    let missing = client
+        .unary::<_, Entity>("Get", BOB, GetRequest { id: 99 })
+        .unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+    let listed = |client: &mut Client, request: ListRequest| {
+        return client
+            .stream::<_, Entity>("List", BOB, request, usize::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect::<Vec<i64>>();
+    };
+    assert_eq!(listed(&mut client, ListRequest::default()), ids);
+    let request = ListRequest {
+        limit: 1,
+        after_id: Some(ids[0]),
+        ..Default::default()
+    };
+    assert_eq!(listed(&mut client, request), [ids[1]]);
+    let request = ListRequest {
+        filter: values(&[("sku", "A-3")]),
+        ..Default::default()
+    };
+    assert_eq!(listed(&mut client, request), [ids[2]]);
+    // Bob may not change the inventory and wrong credentials are rejected
+    let denied = client
+        .unary::<_, Entity>(
+            "Add",
+            BOB,
+            AddRequest {
+                values: values(&[("sku", "B")]),
+            },
+        )
+        .unwrap_err();
+    assert_eq!(denied.code(), Code::InvalidArgument);
+    let rejected = client
+        .stream::<_, Entity>("List", "bob:wrong", ListRequest::default(), 1)
+        .unwrap_err();
+    assert_eq!(rejected.code(), Code::Unauthenticated);
+    let removal = RemoveRequest {
+        id: ids[0],
+        reason: Some("damage".into()),
+    };
+    let _: RemoveResponse = client.unary("Remove", ADMIN, removal).unwrap();
+    let removed = client
+        .unary::<_, Entity>("Get", BOB, GetRequest { id: ids[0] })
+        .unwrap_err();
+    assert_eq!(removed.code(), Code::NotFound);
+    // Events are only streamed to users that may read the database
+    let watch = WatchEventsRequest {
+        after_event: 0,
+        events: vec!["inventory-remove".into()],
+    };
+    let denied = client
+        .stream::<_, Event>("WatchEvents", BOB, watch.clone(), 1)
+        .unwrap_err();
+    assert_eq!(denied.code(), Code::PermissionDenied);
+    let events: Vec<Event> = client.stream("WatchEvents", ADMIN, watch, 1).unwrap();
+    assert_eq!(events[0].event, "inventory-remove");
+    assert_eq!(events[0].entity.as_ref().unwrap().id, ids[0]);
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name);
+    return std::fs::read(path).unwrap();
+}
+
+#[test]
+fn grpc_clients_need_a_certificate_issued_by_the_client_ca() {
+    let db = TestDb::new();
+    let dir = format!("{}/tests/fixtures/tls", env!("CARGO_MANIFEST_DIR"));
+    let (cert, key, ca) = (
+        format!("{}/server.pem", dir),
+        format!("{}/server.key", dir),
+        format!("{}/ca.pem", dir),
+    );
+    let server = TestServer::start(
+        &db,
+        &[
+            "--grpc",
+            "--tls-cert",
+            &cert,
+            "--tls-key",
+            &key,
+            "--tls-client-ca",
+            &ca,
+        ],
+    );
+    let tls = ClientTlsConfig::new()
+        .ca_certificate(Certificate::from_pem(fixture("ca.pem")))
+        .domain_name("localhost");
+    let identity = Identity::from_pem(fixture("client.pem"), fixture("client.key"));
+    let mut client = Client::try_connect(&server, Some(tls.clone().identity(identity))).unwrap();
+    let entities: Vec<Entity> = client
+        .stream("List", ADMIN, ListRequest::default(), usize::MAX)
+        .unwrap();
+    assert!(entities.is_empty());
+    // The handshake fails without a client certificate, which surfaces with the first call
+    let anonymous = Client::try_connect(&server, Some(tls)).and_then(|mut client| {
+        return client
+            .stream::<_, Entity>("List", ADMIN, ListRequest::default(), 1)
+            .map_err(|e| e.to_string());
+    });
+    assert!(anonymous.is_err());
+}
@@ -0,0 +1,125 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use anyhow::Result;
+use common::{ADMIN, BOB};
+use invman::{
+    common::args::{
+        CommandContext, InventoryListArgs, OutputType, ReasonCodeListArgs, UserTotpEnableArgs,
+    },
+    database::{InvManConnection, InvManDBPool},
+};
+use serde_json::Value;
+
+/**
+ * In-memory database with the admin and the user bob without permissions, like
+ * --database :memory: sets it up, run through the library like its consumers do.
+ */
+fn setup() -> impl InvManDBPool {
+    let mut db = InvManConnection::sqlite_in_memory().unwrap();
+    for user in [ADMIN, BOB] {
+        let (username, password) = user.split_once(':').unwrap();
+        db.user_register(username, password).unwrap();
+    }
+    return db;
+}
+
+fn run(
+    db: &mut dyn InvManDBPool,
+    auth: &str,
+    otp: Option<&str>,
+    command: impl FnOnce(&mut CommandContext) -> Result<String>,
+) -> Result<String> {
+    let mut config = db.get_config()?;
+    let mut ctx = CommandContext::new(db, &mut config, Some(auth.into()), OutputType::Json);
+    ctx.otp = otp.map(String::from);
+    return command(&mut ctx);
+}
+
+fn raw_list(raw: &str, params: &[&str]) -> InventoryListArgs {
+    return InventoryListArgs {
+        limit: None,
+        after_id: None,
+        fuzzy: None,
+        fuzzy_threshold: 0.6,
+        with_audit: false,
+        with_attachments: false,
+        include_archived: false,
+        supplier: None,
+        assigned_to: None,
+        category: None,
+        recursive: false,
+        sort: vec![],
+        raw: Some(raw.into()),
+        params: params.iter().map(|e| e.to_string()).collect(),
+        filter: None,
+        group_by: vec![],
+        aggregate: None,
+        expand: vec![],
+        condition: vec![],
+        explain: false,
+    };
+}
+
+#[test]
+fn raw_sql_only_reads_the_inventory() {
+    let mut db = setup();
+    for raw in [
+        "WHERE 0 UNION ALL SELECT id,created_at,updated_at,deleted_at FROM invman_users",
+        "WHERE id IN (SELECT id FROM invman_users WHERE password LIKE '$argon2%')",
+        "WHERE 1; DELETE FROM invman_users",
+        "JOIN invman_users u ON u.id = invman_inventory.id",
+    ] {
+        let listed = run(&mut db, BOB, None, |ctx| raw_list(raw, &[]).list(ctx));
+        assert!(listed.is_err(), "{}", raw);
+    }
+    let listed = run(&mut db, BOB, None, |ctx| {
+        raw_list("WHERE id > :id ORDER BY id", &["id=0"]).list(ctx)
+    });
+    assert_eq!(listed.unwrap(), "[]");
+}
+
+#[test]
+fn recovery_codes_are_only_accepted_once() {
+    let mut db = setup();
+    let enrollment: Value = serde_json::from_str(
+        &run(&mut db, ADMIN, None, |ctx| UserTotpEnableArgs.enable(ctx)).unwrap(),
+    )
+    .unwrap();
+    let code = enrollment["recovery_codes"][0].as_str().unwrap();
+    let error = run(&mut db, ADMIN, None, |ctx| ReasonCodeListArgs.list(ctx)).unwrap_err();
+    assert!(error.to_string().contains("--otp"), "{}", error);
+    run(&mut db, ADMIN, Some(code), |ctx| {
+        ReasonCodeListArgs.list(ctx)
+    })
+    .unwrap();
+    let error = run(&mut db, ADMIN, Some(code), |ctx| {
+        ReasonCodeListArgs.list(ctx)
+    })
+    .unwrap_err();
+    assert!(
+        error.to_string().contains("One-time password is incorrect"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,58 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+
+#[test]
+fn picks_short_of_an_item_book_nothing() {
+    let db = TestDb::new();
+    for sku in ["I1", "I2"] {
+        db.ok(Some(ADMIN), &["inventory", "add", &format!("sku={}", sku)]);
+    }
+    db.adjust("1", 10, "A");
+    db.adjust("2", 3, "A");
+    db.ok(
+        Some(ADMIN),
+        &[
+            "kit", "define", "starter", "--item", "1", "x2", "--item", "2", "x1",
+        ],
+    );
+    let error = db.err(
+        Some(ADMIN),
+        &["kit", "pick", "starter", "--qty", "5", "--location", "A"],
+    );
+    assert!(
+        error.contains("item 2: 5 required, 3 available, 2 missing"),
+        "{}",
+        error
+    );
+    // The first item would be taken out before the second one is found short
+    assert_eq!(db.stock("1", "A"), 10);
+    assert_eq!(db.stock("2", "A"), 3);
+    db.ok(
+        Some(ADMIN),
+        &["kit", "pick", "starter", "--qty", "3", "--location", "A"],
+    );
+    assert_eq!(db.stock("1", "A"), 4);
+    assert_eq!(db.stock("2", "A"), 0);
+}
@@ -0,0 +1,140 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+
+fn rows_of(db: &TestDb, table: &str, identifier: i64) -> i64 {
+    let conn = rusqlite::Connection::open(db.path()).unwrap();
+    return conn
+        .query_row(
+            format!("SELECT COUNT(*) FROM {} WHERE inventory_id=?1", table).as_str(),
+            [identifier],
+            |row| row.get(0),
+        )
+        .unwrap();
+}
+
+fn set_org(db: &TestDb, org: Option<&str>) {
+    let conn = rusqlite::Connection::open(db.path()).unwrap();
+    conn.execute(
+        "UPDATE invman_users SET org_id=(SELECT id FROM invman_orgs WHERE name=?1) WHERE username='admin'",
+        [org],
+    )
+    .unwrap();
+}
+
+#[test]
+fn merges_move_the_records_of_the_duplicate() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["org", "create", "team"]);
+    db.ok(Some(ADMIN), &["org", "invite", "team", "admin"]);
+    for sku in ["A1", "A1-dup"] {
+        db.ok(Some(ADMIN), &["inventory", "add", &format!("sku={}", sku)]);
+    }
+    db.adjust("1", 3, "A");
+    db.adjust("2", 4, "A");
+    db.ok(
+        Some(ADMIN),
+        &[
+            "inventory",
+            "checkout",
+            "--identifier",
+            "2",
+            "--to",
+            "carol",
+        ],
+    );
+    std::fs::write(db.dir.join("invoice.txt"), "invoice").unwrap();
+    db.ok(
+        Some(ADMIN),
+        &["inventory", "attach", "--identifier", "2", "invoice.txt"],
+    );
+    db.ok(
+        Some(ADMIN),
+        &["inventory", "comment", "--identifier", "2", "swapped PSU"],
+    );
+    db.ok(
+        Some(ADMIN),
+        &[
+            "inventory",
+            "meta",
+            "set",
+            "--identifier",
+            "2",
+            "lab",
+            "ACME",
+        ],
+    );
+    db.ok(
+        Some(ADMIN),
+        &["inventory", "merge", "--keep", "1", "--merge", "2"],
+    );
+    assert_eq!(db.stock("1", "A"), 7);
+    assert_eq!(db.stock("2", "A"), 0);
+    assert!(db.ok(Some(ADMIN), &["loans", "list"]).contains("carol"));
+    for table in [
+        "invman_loans",
+        "invman_attachments",
+        "invman_comments",
+        "invman_inventory_meta",
+    ] {
+        assert_eq!(rows_of(&db, table, 1), 1, "{}", table);
+        assert_eq!(rows_of(&db, table, 2), 0, "{}", table);
+    }
+    assert!(db
+        .ok(Some(ADMIN), &["comments", "list", "--identifier", "1"])
+        .contains("swapped PSU"));
+    assert!(db
+        .ok(
+            Some(ADMIN),
+            &["inventory", "meta", "get", "--identifier", "1"]
+        )
+        .contains("ACME"));
+    for table in ["invman_inventory_owners", "invman_org_items"] {
+        assert_eq!(rows_of(&db, table, 1), 1, "{}", table);
+        assert_eq!(rows_of(&db, table, 2), 0, "{}", table);
+    }
+}
+
+#[test]
+fn entities_of_different_organizations_are_not_merged() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["org", "create", "team"]);
+    db.ok(Some(ADMIN), &["org", "create", "other"]);
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A0"]);
+    set_org(&db, Some("team"));
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    set_org(&db, Some("other"));
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A2"]);
+    // Users of no organization see the entities of all of them
+    set_org(&db, None);
+    for (keep, duplicate) in [("2", "3"), ("1", "2")] {
+        let error = db.err(
+            Some(ADMIN),
+            &["inventory", "merge", "--keep", keep, "--merge", duplicate],
+        );
+        assert!(error.contains("different organizations"), "{}", error);
+    }
+    assert_eq!(rows_of(&db, "invman_org_items", 2), 1);
+    assert_eq!(rows_of(&db, "invman_org_items", 3), 1);
+}
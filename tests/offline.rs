@@ -0,0 +1,73 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+
+#[test]
+fn credentials_never_reach_the_outbox() {
+    let db = TestDb::new();
+    let path = db.path();
+    for global in [
+        vec!["-a", ADMIN],
+        vec!["--auth", ADMIN, "--read-only"],
+        vec!["--auth=admin:Admin-pw-123", "--otp", "000000"],
+    ] {
+        // --read-only and a wrong one-time password make the command fail, which is fine here
+        let mut args = vec!["--offline"];
+        args.extend(global);
+        args.extend(["inventory", "add", "sku=offline"]);
+        let _ = db.run(None, &args);
+    }
+    let outbox = db.ok(Some(ADMIN), &["sync", "outbox"]);
+    assert!(outbox.contains("sku=offline"), "{}", outbox);
+    for secret in [
+        "Admin-pw-123",
+        "--auth",
+        "\"-a\"",
+        "--database",
+        "--offline",
+        "000000",
+    ] {
+        assert!(!outbox.contains(secret), "{} in {}", secret, outbox);
+    }
+    let stored = std::fs::read(&path).unwrap();
+    assert!(!String::from_utf8_lossy(&stored).contains("Admin-pw-123"));
+}
+
+#[test]
+fn push_honours_a_read_only_central_database() {
+    let local = TestDb::new();
+    let central = TestDb::new();
+    central.ok(Some(ADMIN), &["db", "read-only", "true"]);
+    local.ok(
+        Some(ADMIN),
+        &["--offline", "inventory", "add", "sku=offline"],
+    );
+    let pushed = local.ok(Some(ADMIN), &["sync", "push", &central.path()]);
+    assert!(pushed.contains("\"applied\":false"), "{}", pushed);
+    assert!(local
+        .ok(Some(ADMIN), &["sync", "outbox"])
+        .contains("sku=offline"));
+    let listed = central.ok(Some(ADMIN), &["inventory", "list"]);
+    assert!(!listed.contains("offline"), "{}", listed);
+}
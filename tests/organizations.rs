@@ -0,0 +1,62 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN, BOB};
+
+#[test]
+fn organization_members_cannot_escape_their_scope() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    db.ok(Some(ADMIN), &["org", "create", "team"]);
+    db.ok(Some(ADMIN), &["org", "invite", "team", "bob"]);
+    assert_eq!(db.ok(Some(BOB), &["inventory", "list"]).trim(), "[]");
+    for raw in [
+        "WHERE 0 UNION ALL SELECT * FROM main.invman_inventory",
+        "WHERE 1",
+        "ORDER BY id",
+    ] {
+        let error = db.err(Some(BOB), &["inventory", "list", "-r", raw]);
+        assert!(error.contains("organization"), "{}", error);
+    }
+    db.err(Some(BOB), &["inventory", "list", "--explain"]);
+    assert!(db
+        .ok(Some(ADMIN), &["inventory", "list", "-r", "WHERE 1"])
+        .contains("A1"));
+}
+
+#[test]
+fn organization_members_only_archive_their_entities() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    db.ok(Some(ADMIN), &["org", "create", "team"]);
+    db.ok(Some(ADMIN), &["org", "invite", "team", "admin"]);
+    for condition in ["sku = A1", "1) OR (1", "id > 0 or 1 = 1"] {
+        db.err(Some(ADMIN), &["inventory", "archive", "--where", condition]);
+    }
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=B1"]);
+    let archived = db.ok(
+        Some(ADMIN),
+        &["inventory", "archive", "--where", "sku ~ '%1'"],
+    );
+    assert!(archived.contains("Archived 1 entities"), "{}", archived);
+}
@@ -0,0 +1,60 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+use serde_json::Value;
+
+fn page(db: &TestDb, args: &[&str]) -> Value {
+    let mut list = vec!["inventory", "list"];
+    list.extend_from_slice(args);
+    return serde_json::from_str(&db.ok(Some(ADMIN), &list)).unwrap();
+}
+
+#[test]
+fn every_page_has_a_cursor() {
+    let db = TestDb::new();
+    for sku in ["A1", "A2", "A3"] {
+        db.ok(Some(ADMIN), &["inventory", "add", &format!("sku={}", sku)]);
+    }
+    let first = page(&db, &["--limit", "2", "--after-id", "0"]);
+    assert_eq!(first["data"].as_array().unwrap().len(), 2);
+    let cursor = first["next_cursor"]
+        .as_i64()
+        .expect("first page has a cursor");
+    let second = page(&db, &["--limit", "2", "--after-id", &cursor.to_string()]);
+    assert_eq!(second["data"][0]["sku"], "A3");
+    assert!(second["next_cursor"].is_null());
+    // Listings without a cursor keep returning the bare entities
+    assert_eq!(page(&db, &[]).as_array().unwrap().len(), 3);
+    assert_eq!(page(&db, &["--limit", "2"]).as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn cursors_cannot_be_combined_with_raw_queries() {
+    let db = TestDb::new();
+    let error = db.err(
+        Some(ADMIN),
+        &["inventory", "list", "--after-id", "1", "-r", "WHERE 1"],
+    );
+    assert!(error.contains("Cannot combine --after-id"), "{}", error);
+}
@@ -0,0 +1,79 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN, BOB};
+
+#[test]
+fn raw_sql_cannot_read_password_hashes() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    let union = "WHERE 0 UNION ALL SELECT id,created_at,updated_at,deleted_at,password,password FROM invman_users";
+    for raw in [
+        union,
+        "WHERE sku IN (SELECT password FROM invman_users)",
+        "WHERE (SELECT COUNT(*) FROM invman_users WHERE password LIKE '$argon2%') > 0",
+        "JOIN invman_users ON 1",
+    ] {
+        let error = db.err(Some(BOB), &["inventory", "list", "-r", raw]);
+        assert!(error.contains("Raw SQL"), "{}", error);
+        db.err(Some(BOB), &["inventory", "list", "--explain", "-r", raw]);
+    }
+}
+
+#[test]
+fn raw_sql_cannot_read_other_tables_with_audit() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    // The audit columns read invman_users, but only the names of the users
+    let raw = "WHERE sku IN (SELECT password FROM invman_users)";
+    db.err(Some(BOB), &["inventory", "list", "--with-audit", "-r", raw]);
+}
+
+#[test]
+fn raw_sql_filters_the_inventory() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=B1"]);
+    let listed = db.ok(
+        Some(BOB),
+        &[
+            "inventory",
+            "list",
+            "-r",
+            "WHERE sku = :sku",
+            "-p",
+            "sku=B1",
+        ],
+    );
+    assert!(listed.contains("B1") && !listed.contains("A1"));
+    let listed = db.ok(
+        Some(BOB),
+        &[
+            "inventory",
+            "list",
+            "-r",
+            "WHERE id IN (SELECT id FROM invman_inventory WHERE sku LIKE 'A%') ORDER BY id",
+        ],
+    );
+    assert!(listed.contains("A1") && !listed.contains("B1"));
+}
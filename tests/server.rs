@@ -0,0 +1,354 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, TestServer, ADMIN, BOB};
+
+/**
+ * @returns The status and body of the response to a GET of the path
+ */
+fn get(server: &TestServer, path: &str, auth: Option<&str>) -> (u16, String) {
+    return send(server, "GET", path, auth);
+}
+
+/**
+ * Sends the request with the credentials in the notation of --auth, username and password by
+ * basic authentication and tokens as bearer.
+ *
+ * @returns The status and body of the response
+ */
+fn send(server: &TestServer, method: &str, path: &str, auth: Option<&str>) -> (u16, String) {
+    let mut request = ureq::request(method, &format!("http://{}{}", server.listen, path));
+    match auth {
+        Some(auth) if auth.contains(':') => {
+            let credentials =
+                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, auth);
+            request = request.set("Authorization", &format!("Basic {}", credentials));
+        }
+        Some(token) => request = request.set("Authorization", &format!("Bearer {}", token)),
+        None => {}
+    }
+    return match request.call() {
+        Ok(response) => (response.status(), response.into_string().unwrap()),
+        Err(ureq::Error::Status(status, response)) => (status, response.into_string().unwrap()),
+        Err(e) => panic!("{}", e),
+    };
+}
+
+#[test]
+fn metrics_count_requests_and_authentication_failures() {
+    let db = TestDb::new();
+    let server = TestServer::start(&db, &[]);
+    let url = format!("http://{}", server.listen);
+    let list = ["--remote", &url, "inventory", "list"];
+    assert!(server
+        .run(&[&["-a", ADMIN][..], &list].concat())
+        .status
+        .success());
+    assert!(!server
+        .run(&[&["-a", "admin:wrong"][..], &list].concat())
+        .status
+        .success());
+    assert_eq!(get(&server, "/metrics", None).0, 401);
+    assert_eq!(get(&server, "/metrics", Some(BOB)).0, 403);
+    let (status, metrics) = get(&server, "/metrics", Some(ADMIN));
+    assert_eq!(status, 200);
+    for line in [
+        "invman_users 2",
+        "invman_requests_total{operation=\"inventory_list\",status=\"200\"} 1",
+        "invman_request_duration_seconds_bucket{operation=\"inventory_list\",le=\"+Inf\"} 1",
+        "invman_request_duration_seconds_count{operation=\"inventory_list\"} 1",
+    ] {
+        assert!(metrics.lines().any(|e| e == line), "{} missing", line);
+    }
+    // The failed login and the anonymous request of the metrics, bob was authenticated
+    assert!(metrics.lines().any(|e| e == "invman_auth_failures_total 2"));
+}
+
+#[test]
+fn unknown_operations_are_not_recorded_by_their_name() {
+    let db = TestDb::new();
+    let server = TestServer::start(&db, &[]);
+    for operation in ["made_up", "another_one"] {
+        let response =
+            ureq::post(&format!("http://{}/rpc/{}", server.listen, operation)).send_string("[]");
+        assert!(matches!(response, Err(ureq::Error::Status(401, _))));
+    }
+    let (_, metrics) = get(&server, "/metrics", Some(ADMIN));
+    assert!(!metrics.contains("made_up") && !metrics.contains("another_one"));
+    assert!(metrics
+        .lines()
+        .any(|e| e == "invman_requests_total{operation=\"unknown\",status=\"401\"} 2"));
+}
+
+#[test]
+fn health_needs_no_authentication() {
+    let db = TestDb::new();
+    let server = TestServer::start(&db, &[]);
+    let (status, body) = get(&server, "/health", None);
+    assert_eq!(status, 200);
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["healthy"], true);
+    let checks: Vec<&str> = report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(checks, ["query", "schema_version", "write"]);
+}
+
+#[test]
+fn events_are_streamed_as_they_are_recorded() {
+    let db = TestDb::new();
+    let server = TestServer::start(&db, &[]);
+    let url = format!("http://{}", server.listen);
+    assert_eq!(get(&server, "/events", None).0, 401);
+    assert_eq!(get(&server, "/events?event=made-up", Some(ADMIN)).0, 400);
+    let credentials = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, ADMIN);
+    let response = ureq::AgentBuilder::new()
+        .timeout_read(std::time::Duration::from_secs(10))
+        .build()
+        .get(&format!("{}/events?event=inventory-add", url))
+        .set("Authorization", &format!("Basic {}", credentials))
+        .call()
+        .unwrap();
+    assert_eq!(response.content_type(), "text/event-stream");
+    let add = |sku: &str| {
+        let sku = format!("sku={}", sku);
+        let args = ["--remote", &url, "-a", ADMIN, "inventory", "add", &sku];
+        assert!(server.run(&args).status.success());
+    };
+    add("A-1");
+    let edit = [
+        "--remote",
+        &url,
+        "-a",
+        ADMIN,
+        "inventory",
+        "edit",
+        "-i",
+        "1",
+    ];
+    assert!(server
+        .run(&[&edit[..], &["-s", "sku=A-2"]].concat())
+        .status
+        .success());
+    add("A-3");
+    let mut lines = std::io::BufRead::lines(std::io::BufReader::new(response.into_reader()));
+    let mut data = vec![];
+    while data.len() < 2 {
+        let line = lines.next().unwrap().unwrap();
+        if let Some(event) = line.strip_prefix("data: ") {
+            let event: serde_json::Value = serde_json::from_str(event).unwrap();
+            data.push(event["entity"]["sku"].as_str().unwrap().to_string());
+        }
+    }
+    // The edit is left out by the filter
+    assert_eq!(data, ["A-1", "A-3"]);
+}
+
+/**
+ * @returns The status and the body of the response to the GraphQL query
+ */
+fn graphql(server: &TestServer, auth: &str, query: &str) -> (u16, serde_json::Value) {
+    let credentials = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, auth);
+    let response = ureq::post(&format!("http://{}/graphql", server.listen))
+        .set("Authorization", &format!("Basic {}", credentials))
+        .send_json(serde_json::json!({ "query": query }));
+    let (status, response) = match response {
+        Ok(response) => (response.status(), response),
+        Err(ureq::Error::Status(status, response)) => (status, response),
+        Err(e) => panic!("{}", e),
+    };
+    return (
+        status,
+        serde_json::from_reader(response.into_reader()).unwrap(),
+    );
+}
+
+#[test]
+fn graphql_resolves_entities_with_the_permissions_of_rpc() {
+    let db = TestDb::new();
+    let server = TestServer::start(&db, &[]);
+    let (status, added) = graphql(
+        &server,
+        ADMIN,
+        "mutation { add(values: {sku: \"A-1\", name: \"Bolt\"}) { id sku } }",
+    );
+    assert_eq!(status, 200);
+    assert_eq!(
+        added["data"]["add"],
+        serde_json::json!({"id": "1", "sku": "A-1"})
+    );
+    let (_, edited) = graphql(
+        &server,
+        ADMIN,
+        "mutation { edit(id: 1, values: {name: null}) { name } }",
+    );
+    assert_eq!(edited["data"]["edit"]["name"], serde_json::Value::Null);
+    let (_, listed) = graphql(
+        &server,
+        BOB,
+        "{ entities(filter: {sku: \"A-1\"}) { id __typename } }",
+    );
+    assert_eq!(
+        listed["data"]["entities"],
+        serde_json::json!([{"id": "1", "__typename": "Entity"}])
+    );
+    // Bob may read, but not add, which fails the field instead of the request
+    let (status, denied) = graphql(
+        &server,
+        BOB,
+        "mutation { add(values: {sku: \"B\"}) { id } }",
+    );
+    assert_eq!(status, 200);
+    assert_eq!(denied["data"], serde_json::Value::Null);
+    assert_eq!(denied["errors"][0]["path"], serde_json::json!(["add"]));
+    assert_eq!(graphql(&server, "bob:wrong", "{ entities { id } }").0, 401);
+    let (status, invalid) = graphql(&server, ADMIN, "{ entities { price } }");
+    assert_eq!(status, 400);
+    assert_eq!(
+        invalid["errors"][0]["message"],
+        "Cannot query field price on type Entity"
+    );
+    let (status, sdl) = get(&server, "/graphql", Some(ADMIN));
+    assert_eq!(status, 200);
+    assert!(sdl.contains("type Mutation {"));
+}
+
+#[test]
+fn tokens_are_issued_on_login_and_rejected_once_revoked() {
+    let db = TestDb::new();
+    let server = TestServer::start(&db, &[]);
+    let token = |response: (u16, String)| {
+        assert_eq!(response.0, 200, "{}", response.1);
+        let body: serde_json::Value = serde_json::from_str(&response.1).unwrap();
+        return body["token"].as_str().unwrap().to_string();
+    };
+    let issued = token(send(&server, "POST", "/token", Some(ADMIN)));
+    assert_eq!(get(&server, "/metrics", Some(&issued)).0, 200);
+    let url = format!("http://{}", server.listen);
+    let output = server.run(&["--remote", &url, "-a", &issued, "inventory", "list"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // Tokens are issued on login with the password only, and routes are limited to the roles
+    // allowed to them
+    assert_eq!(send(&server, "POST", "/token", Some(&issued)).0, 400);
+    assert_eq!(send(&server, "POST", "/token", Some("admin:wrong")).0, 401);
+    assert_eq!(send(&server, "POST", "/metrics", Some(&issued)).0, 405);
+    let bobs = token(send(&server, "POST", "/token", Some(BOB)));
+    assert_eq!(get(&server, "/metrics", Some(&bobs)).0, 403);
+    // Refreshing revokes the old token, which is rejected right away
+    let refreshed = token(send(&server, "POST", "/token/refresh", Some(&issued)));
+    assert_eq!(get(&server, "/metrics", Some(&issued)).0, 401);
+    assert_eq!(get(&server, "/metrics", Some(&refreshed)).0, 200);
+    let tokens: serde_json::Value =
+        serde_json::from_str(&db.ok(Some(ADMIN), &["user", "token", "list"])).unwrap();
+    for token in tokens.as_array().unwrap() {
+        if token["revoked_at"].is_null() {
+            let id = token["id"].as_str().unwrap();
+            db.ok(Some(ADMIN), &["user", "token", "revoke", id]);
+        }
+    }
+    assert_eq!(get(&server, "/metrics", Some(&refreshed)).0, 401);
+}
+
+#[test]
+fn retention_rules_are_applied_with_the_credentials_of_the_server() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["org", "create", "team"]);
+    db.ok(Some(ADMIN), &["org", "invite", "team", "admin"]);
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    db.ok(
+        Some(ADMIN),
+        &[
+            "inventory",
+            "remove",
+            "--identifier",
+            "1",
+            "--reason",
+            "damage",
+        ],
+    );
+    let sqlite = rusqlite::Connection::open(db.path()).unwrap();
+    sqlite
+        .execute(
+            "UPDATE invman_inventory SET deleted_at='2000-01-01 00:00:00.000'",
+            (),
+        )
+        .unwrap();
+    db.ok(
+        Some(ADMIN),
+        &[
+            "db",
+            "retention",
+            "set",
+            "--purge-deleted-after",
+            "1d",
+            "--prune-events-after",
+            "1y",
+        ],
+    );
+    let error = db.err(Some("admin:wrong"), &["serve", "--listen", "127.0.0.1:0"]);
+    assert!(
+        error.contains("Could not apply the retention rules"),
+        "{}",
+        error
+    );
+    let server = TestServer::start_as(&db, Some(ADMIN), &[]);
+    let credentials = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, ADMIN);
+    let response = ureq::AgentBuilder::new()
+        .timeout_read(std::time::Duration::from_secs(10))
+        .build()
+        .get(&format!(
+            "http://{}/events?event=retention-apply&after=0",
+            server.listen
+        ))
+        .set("Authorization", &format!("Basic {}", credentials))
+        .call()
+        .unwrap();
+    let mut lines = std::io::BufRead::lines(std::io::BufReader::new(response.into_reader()));
+    let event = loop {
+        let line = lines.next().unwrap().unwrap();
+        if let Some(event) = line.strip_prefix("data: ") {
+            break serde_json::from_str::<serde_json::Value>(event).unwrap();
+        }
+    };
+    assert_eq!(event["event"], "retention-apply", "{}", event);
+    // The owner and organization of the purged entity are removed with it
+    for table in [
+        "invman_inventory",
+        "invman_inventory_owners",
+        "invman_org_items",
+    ] {
+        let count: u32 = sqlite
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), (), |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, 0, "{}", table);
+    }
+}
@@ -0,0 +1,66 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+
+fn transfer(db: &TestDb, qty: &str) -> std::process::Output {
+    return db.run(
+        Some(ADMIN),
+        &[
+            "inventory",
+            "transfer",
+            "--identifier",
+            "1",
+            "--qty",
+            qty,
+            "--from",
+            "A",
+            "--to",
+            "B",
+        ],
+    );
+}
+
+#[test]
+fn transfers_beyond_the_stock_change_nothing() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    db.adjust("1", 5, "A");
+    let output = transfer(&db, "7");
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains("5 available, 7 requested"), "{}", error);
+    assert_eq!(db.stock("1", "A"), 5);
+    assert_eq!(db.stock("1", "B"), 0);
+}
+
+#[test]
+fn transfers_keep_the_total() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    db.adjust("1", 5, "A");
+    assert!(transfer(&db, "3").status.success());
+    assert_eq!(db.stock("1", "A"), 2);
+    assert_eq!(db.stock("1", "B"), 3);
+    assert_eq!(db.stock("1", "A") + db.stock("1", "B"), 5);
+}
@@ -0,0 +1,83 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+use serde_json::Value;
+
+fn record(db: &TestDb, identifier: &str, counted: &str) -> std::process::Output {
+    return db.run(
+        Some(ADMIN),
+        &[
+            "stocktake",
+            "record",
+            "--identifier",
+            identifier,
+            "--counted",
+            counted,
+        ],
+    );
+}
+
+#[test]
+fn closing_books_the_variance_once() {
+    let db = TestDb::new();
+    for sku in ["A1", "A2"] {
+        db.ok(Some(ADMIN), &["inventory", "add", &format!("sku={}", sku)]);
+    }
+    db.adjust("1", 10, "A");
+    db.adjust("2", 4, "A");
+    db.ok(Some(ADMIN), &["stocktake", "start", "--location", "A"]);
+    assert!(record(&db, "1", "7").status.success());
+    assert!(record(&db, "2", "4").status.success());
+    let report: Value = serde_json::from_str(&db.ok(Some(ADMIN), &["stocktake", "close"])).unwrap();
+    assert_eq!(report["lines"][0]["variance"], -3);
+    assert_eq!(report["lines"][1]["variance"], 0);
+    assert_eq!(db.stock("1", "A"), 7);
+    assert_eq!(db.stock("2", "A"), 4);
+    let conn = rusqlite::Connection::open(db.path()).unwrap();
+    let adjustments: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM invman_stock_movements WHERE reason='stocktake'",
+            (),
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(adjustments, 1);
+}
+
+#[test]
+fn closed_stocktakes_take_no_counts() {
+    let db = TestDb::new();
+    db.ok(Some(ADMIN), &["inventory", "add", "sku=A1"]);
+    db.adjust("1", 10, "A");
+    db.ok(Some(ADMIN), &["stocktake", "start", "--location", "A"]);
+    assert!(record(&db, "1", "7").status.success());
+    db.ok(Some(ADMIN), &["stocktake", "close"]);
+    let output = record(&db, "1", "2");
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains("No stocktake is open"), "{}", error);
+    let error = db.err(Some(ADMIN), &["stocktake", "close"]);
+    assert!(error.contains("No stocktake is open"), "{}", error);
+    assert_eq!(db.stock("1", "A"), 7);
+}
@@ -0,0 +1,107 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, TestServer, ADMIN};
+
+fn fixture(name: &str) -> String {
+    return format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name);
+}
+
+#[test]
+fn clients_need_a_certificate_issued_by_the_client_ca() {
+    let db = TestDb::new();
+    let (cert, key, ca) = (
+        fixture("server.pem"),
+        fixture("server.key"),
+        fixture("ca.pem"),
+    );
+    let server = TestServer::start(
+        &db,
+        &[
+            "--tls-cert",
+            &cert,
+            "--tls-key",
+            &key,
+            "--tls-client-ca",
+            &ca,
+        ],
+    );
+    let url = format!("https://{}", server.listen);
+    let (client_cert, client_key) = (fixture("client.pem"), fixture("client.key"));
+    let output = server.run(&[
+        "--remote",
+        &url,
+        "--remote-ca",
+        &ca,
+        "--remote-cert",
+        &client_cert,
+        "--remote-key",
+        &client_key,
+        "-a",
+        ADMIN,
+        "inventory",
+        "list",
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = server.run(&[
+        "--remote",
+        &url,
+        "--remote-ca",
+        &ca,
+        "-a",
+        ADMIN,
+        "inventory",
+        "list",
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn servers_are_verified_by_their_certificate() {
+    let db = TestDb::new();
+    let (cert, key) = (fixture("server.pem"), fixture("server.key"));
+    let server = TestServer::start(&db, &["--tls-cert", &cert, "--tls-key", &key]);
+    let url = format!("https://{}", server.listen);
+    let output = server.run(&["--remote", &url, "-a", ADMIN, "inventory", "list"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("UnknownIssuer"));
+    let output = server.run(&[
+        "--remote",
+        &url,
+        "--remote-ca",
+        &fixture("ca.pem"),
+        "-a",
+        ADMIN,
+        "inventory",
+        "list",
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
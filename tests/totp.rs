@@ -0,0 +1,98 @@
+/**
+ * This file is part of invman.
+ *
+ * invman - Manage your inventory easily, declaratively, without the headache.
+ * Copyright (C) 2023  Maik Steiger <m.steiger@csurielektronics.com>
+ *
+ * invman is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * invman is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with invman. If not, see <https://www.gnu.org/licenses/>.
+ */
+// Each test crate only uses some of the helpers
+#[allow(dead_code)]
+mod common;
+
+use common::{TestDb, ADMIN};
+use ring::hmac;
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/**
+ * Enables two-factor authentication of the admin.
+ *
+ * @returns The secret and the recovery codes
+ */
+fn enable(db: &TestDb) -> (Vec<u8>, Vec<String>) {
+    let enrollment: Value =
+        serde_json::from_str(&db.ok(Some(ADMIN), &["user", "2fa", "enable"])).unwrap();
+    let mut secret = vec![];
+    let mut bits = 0u64;
+    let mut count = 0;
+    for c in enrollment["secret"].as_str().unwrap().chars() {
+        let value = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567".find(c).unwrap() as u64;
+        bits = bits << 5 | value;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            secret.push((bits >> count) as u8);
+        }
+    }
+    let codes = enrollment["recovery_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e.as_str().unwrap().to_string())
+        .collect();
+    return (secret, codes);
+}
+
+fn current_otp(secret: &[u8]) -> String {
+    let step = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        / 30;
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let digest = hmac::sign(&key, &step.to_be_bytes());
+    let digest = digest.as_ref();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes(digest[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    return format!("{:06}", code % 1_000_000);
+}
+
+#[test]
+fn one_time_passwords_are_only_accepted_once() {
+    let db = TestDb::new();
+    let (secret, _) = enable(&db);
+    let otp = current_otp(&secret);
+    db.ok(Some(ADMIN), &["--otp", &otp, "inventory", "list"]);
+    let error = db.err(Some(ADMIN), &["--otp", &otp, "inventory", "list"]);
+    assert!(
+        error.contains("One-time password is incorrect"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn incorrect_one_time_passwords_lock_the_user_out() {
+    let db = TestDb::new();
+    let (_, recovery_codes) = enable(&db);
+    for _ in 0..5 {
+        db.err(Some(ADMIN), &["--otp", "wrong-code", "inventory", "list"]);
+    }
+    let error = db.err(
+        Some(ADMIN),
+        &["--otp", &recovery_codes[0], "inventory", "list"],
+    );
+    assert!(error.contains("Too many incorrect"), "{}", error);
+}